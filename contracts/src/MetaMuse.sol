@@ -61,6 +61,16 @@ contract MetaMuse is ERC721, Ownable {
         uint256 verificationTime
     );
     
+    event InteractionPermissionGranted(
+        uint256 indexed tokenId,
+        address indexed user
+    );
+    
+    event InteractionPermissionRevoked(
+        uint256 indexed tokenId,
+        address indexed user
+    );
+    
     uint256 private _nextTokenId = 1;
     
     constructor(address _verifier) ERC721("MetaMuse", "MUSE") Ownable(msg.sender) {
@@ -181,6 +191,7 @@ contract MetaMuse is ERC721, Ownable {
     function grantInteractionPermission(uint256 _tokenId, address _user) external {
         require(ownerOf(_tokenId) == msg.sender, "Not owner");
         interactionPermissions[_tokenId][_user] = true;
+        emit InteractionPermissionGranted(_tokenId, _user);
     }
     
     /**
@@ -189,6 +200,7 @@ contract MetaMuse is ERC721, Ownable {
     function revokeInteractionPermission(uint256 _tokenId, address _user) external {
         require(ownerOf(_tokenId) == msg.sender, "Not owner");
         interactionPermissions[_tokenId][_user] = false;
+        emit InteractionPermissionRevoked(_tokenId, _user);
     }
     
     /**
@@ -0,0 +1,19 @@
+-- Public keys registered through wallet-signed key derivation and muse data keys
+-- wrapped for each holder. Neither can decrypt content without a wallet signature.
+
+CREATE TABLE IF NOT EXISTS user_public_keys (
+    id TEXT PRIMARY KEY,
+    owner TEXT NOT NULL,
+    data TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS wrapped_muse_keys (
+    id TEXT PRIMARY KEY,
+    owner TEXT NOT NULL,
+    data TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_wrapped_muse_keys_owner ON wrapped_muse_keys (owner);
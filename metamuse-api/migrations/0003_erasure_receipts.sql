@@ -0,0 +1,10 @@
+-- Signed receipts for completed data erasure requests, owned by the erased address
+
+CREATE TABLE IF NOT EXISTS erasure_receipts (
+    id TEXT PRIMARY KEY,
+    owner TEXT NOT NULL,
+    data TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_erasure_receipts_owner ON erasure_receipts (owner);
@@ -0,0 +1,20 @@
+-- Ratings users submitted and commitments issued for their chat turns, owned by the
+-- user's address so they can be exported per user
+
+CREATE TABLE IF NOT EXISTS interaction_ratings (
+    id TEXT PRIMARY KEY,
+    owner TEXT NOT NULL,
+    data TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_interaction_ratings_owner ON interaction_ratings (owner);
+
+CREATE TABLE IF NOT EXISTS interaction_commitments (
+    id TEXT PRIMARY KEY,
+    owner TEXT NOT NULL,
+    data TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_interaction_commitments_owner ON interaction_commitments (owner);
@@ -1,22 +1,698 @@
 // Agent workflow management for MetaMuse
-// This module will handle enhanced workflow orchestration in future versions
+// Runs autonomous conversations between muses on a seed topic
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::RwLock;
 
+use crate::config::Config;
+use crate::context_builder::{ContextBuilder, ContextRequest};
+use crate::group_chat::{next_in_rotation, GroupChat, GroupChatCoordinator, TurnPolicy};
+use crate::ipfs_chat_history::{IPFSChatHistoryManager, IPFSChatSession};
+use crate::muse_orchestrator::{MuseOrchestrator, MuseTraits};
+use crate::persist_memory::{InteractionData, MemorySystem};
+use crate::verification::{CommitmentRecord, VerificationSystem};
+
+/// Earlier turns a reply is compared with when looking for repetition
+const REPETITION_WINDOW: usize = 4;
+/// Finished runs kept per user; older transcripts stay in their group chat sessions
+const FINISHED_RUN_HISTORY: usize = 20;
+
+/// What a conversation between muses is about and when it ends
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationSpec {
+    /// Muse token ids in speaking order for round-robin
+    pub participants: Vec<String>,
+    pub seed_topic: String,
+    /// Capped by `AGENT_WORKFLOW_MAX_TURNS`
+    pub max_turns: usize,
+    pub turn_policy: TurnPolicy,
+    #[serde(default)]
+    pub stop: StopConditions,
+}
+
+/// Ends a conversation before its last turn
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StopConditions {
+    /// Case-insensitive phrases that end the conversation once a muse says one
+    #[serde(default)]
+    pub stop_phrases: Vec<String>,
+    /// Total tokens of all replies
+    #[serde(default)]
+    pub max_total_tokens: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowStatus {
+    Running,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum StopReason {
+    MaxTurns,
+    StopPhrase { muse_id: String, phrase: String },
+    Repetition { muse_id: String },
+    EmptyReply { muse_id: String },
+    TokenBudget,
+    Cancelled,
+    Failed { error: String },
+}
+
+/// One muse's turn, with the commitment that lets it be rated and minted like a chat reply
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptTurn {
+    pub turn: u32,
+    pub muse_id: String,
+    pub message_id: String,
+    pub interaction_id: String,
+    /// The message this turn answers: the seed topic or the previous turn
+    pub prompt: String,
+    pub content: String,
+    pub timestamp: u64,
+    pub response_tokens: Option<u32>,
+    pub commitment_hash: String,
+    pub signature: String,
+}
+
+/// A conversation between muses, stored as a group chat session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowRun {
+    pub run_id: String,
+    pub user_address: String,
+    pub session_id: String,
+    pub spec: ConversationSpec,
+    pub status: WorkflowStatus,
+    pub stop_reason: Option<StopReason>,
+    pub turns: Vec<TranscriptTurn>,
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+}
+
+/// A transcript turn as a prompt/response pair for training data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrainingExample {
+    pub muse_id: String,
+    pub prompt: String,
+    pub response: String,
+    pub commitment_hash: String,
+}
+
+impl WorkflowRun {
+    pub fn training_examples(&self) -> Vec<TrainingExample> {
+        self.turns
+            .iter()
+            .map(|turn| TrainingExample {
+                muse_id: turn.muse_id.clone(),
+                prompt: turn.prompt.clone(),
+                response: turn.content.clone(),
+                commitment_hash: turn.commitment_hash.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Traits and DNA hash of every muse taking part, keyed by token id
+pub type MuseProfiles = HashMap<String, (MuseTraits, [u8; 32])>;
+
+/// Runs autonomous conversations between muses. Each turn is generated with the speaker's
+/// own traits and memories, stored in a group chat session and signed like a chat reply.
 pub struct AgentWorkflow {
-    // Future: Enhanced workflow orchestration
-    // Cross-agent communication protocols
-    // Dynamic agent selection based on context
-    // Performance optimization for agent switching
+    orchestrator: Arc<MuseOrchestrator>,
+    context_builder: Arc<ContextBuilder>,
+    group_chat: Arc<GroupChatCoordinator>,
+    ipfs_chat_history: Arc<IPFSChatHistoryManager>,
+    memory_system: Arc<MemorySystem>,
+    verification_system: Arc<VerificationSystem>,
+    max_turns: usize,
+    repetition_threshold: f32,
+    memory_limit: usize,
+    max_running_per_user: usize,
+    runs: RwLock<HashMap<String, WorkflowRun>>,
+    // Runs starting or running per lowercase user address
+    running: RwLock<HashMap<String, usize>>,
+    cancelled: RwLock<HashSet<String>>,
 }
 
 impl AgentWorkflow {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(
+        orchestrator: Arc<MuseOrchestrator>,
+        context_builder: Arc<ContextBuilder>,
+        group_chat: Arc<GroupChatCoordinator>,
+        ipfs_chat_history: Arc<IPFSChatHistoryManager>,
+        memory_system: Arc<MemorySystem>,
+        verification_system: Arc<VerificationSystem>,
+        config: &Config,
+    ) -> Self {
+        Self {
+            orchestrator,
+            context_builder,
+            group_chat,
+            ipfs_chat_history,
+            memory_system,
+            verification_system,
+            max_turns: config.agent_workflow_max_turns.max(1),
+            repetition_threshold: config.agent_workflow_repetition_threshold.clamp(0.0, 1.0),
+            memory_limit: config.context_memory_limit,
+            max_running_per_user: config.agent_workflow_max_running_per_user.max(1),
+            runs: RwLock::new(HashMap::new()),
+            running: RwLock::new(HashMap::new()),
+            cancelled: RwLock::new(HashSet::new()),
+        }
+    }
+
+    pub fn max_participants(&self) -> usize {
+        self.group_chat.max_participants()
+    }
+
+    /// Store the seed topic in a new group session and run the conversation in the background.
+    /// Returns `None` when the user already has `AGENT_WORKFLOW_MAX_RUNNING_PER_USER` running.
+    pub async fn start(self: &Arc<Self>, user_address: String, mut spec: ConversationSpec, profiles: MuseProfiles) -> Result<Option<WorkflowRun>> {
+        spec.bound(self.max_turns);
+
+        if !self.reserve_slot(&user_address).await {
+            return Ok(None);
+        }
+        let session = match self.create_session(&user_address, &spec).await {
+            Ok(session) => session,
+            Err(e) => {
+                self.release_slot(&user_address).await;
+                return Err(e);
+            }
+        };
+
+        let run = WorkflowRun {
+            run_id: format!("workflow_{}", uuid::Uuid::new_v4()),
+            user_address,
+            session_id: session.session_id.clone(),
+            spec,
+            status: WorkflowStatus::Running,
+            stop_reason: None,
+            turns: Vec::new(),
+            started_at: now_secs(),
+            finished_at: None,
+        };
+        self.runs.write().await.insert(run.run_id.clone(), run.clone());
+
+        println!("🤝 Starting muse conversation {} between {:?} on '{}'",
+                run.run_id, run.spec.participants, run.spec.seed_topic);
+
+        let workflow = self.clone();
+        let run_id = run.run_id.clone();
+        tokio::spawn(async move {
+            let stop_reason = workflow.converse(&run_id, &profiles).await;
+            workflow.finish(&run_id, stop_reason).await;
+        });
+
+        Ok(Some(run))
+    }
+
+    async fn create_session(&self, user_address: &str, spec: &ConversationSpec) -> Result<Arc<IPFSChatSession>> {
+        let group = GroupChat {
+            participants: spec.participants.clone(),
+            turn_policy: spec.turn_policy.clone(),
+        };
+        let session = self.ipfs_chat_history.create_group_session(user_address.to_string(), group).await?;
+        let seed_id = format!("seed_{}", uuid::Uuid::new_v4());
+        self.ipfs_chat_history
+            .add_message(&session.session_id, "user".to_string(), spec.seed_topic.clone(), seed_id)
+            .await?;
+        Ok(session)
+    }
+
+    /// Count a new run against the user's cap, unless it is already reached
+    async fn reserve_slot(&self, user_address: &str) -> bool {
+        let mut running = self.running.write().await;
+        let count = running.entry(user_address.to_lowercase()).or_insert(0);
+        if *count >= self.max_running_per_user {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+
+    async fn release_slot(&self, user_address: &str) {
+        let mut running = self.running.write().await;
+        let key = user_address.to_lowercase();
+        if let Some(count) = running.get_mut(&key) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                running.remove(&key);
+            }
+        }
+    }
+
+    pub async fn get_run(&self, run_id: &str) -> Option<WorkflowRun> {
+        self.runs.read().await.get(run_id).cloned()
+    }
+
+    /// Runs started by `user_address`, newest first
+    pub async fn runs_for_user(&self, user_address: &str) -> Vec<WorkflowRun> {
+        let mut runs: Vec<WorkflowRun> = self.runs.read().await
+            .values()
+            .filter(|run| run.user_address.eq_ignore_ascii_case(user_address))
+            .cloned()
+            .collect();
+        runs.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        runs
+    }
+
+    /// Stop a running conversation after the turn in progress
+    pub async fn cancel(&self, run_id: &str) -> bool {
+        match self.runs.read().await.get(run_id) {
+            Some(run) if run.status == WorkflowStatus::Running => {
+                self.cancelled.write().await.insert(run_id.to_string());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    async fn converse(&self, run_id: &str, profiles: &MuseProfiles) -> StopReason {
+        let Some(run) = self.get_run(run_id).await else {
+            return StopReason::Failed { error: "Run not found".to_string() };
+        };
+        let spec = &run.spec;
+        let group = GroupChat {
+            participants: spec.participants.clone(),
+            turn_policy: spec.turn_policy.clone(),
+        };
+        let participant_traits: Vec<(String, MuseTraits)> = spec.participants
+            .iter()
+            .filter_map(|id| profiles.get(id).map(|(traits, _)| (id.clone(), traits.clone())))
+            .collect();
+        let moderator_traits = match &spec.turn_policy {
+            TurnPolicy::Moderator { moderator_id } => profiles.get(moderator_id).map(|(traits, _)| traits),
+            _ => None,
+        };
+
+        let mut prompt = spec.seed_topic.clone();
+        let mut recent: Vec<String> = Vec::new();
+        let mut total_tokens = 0;
+
+        for turn in 1..=spec.max_turns as u32 {
+            if self.cancelled.read().await.contains(run_id) {
+                return StopReason::Cancelled;
+            }
+
+            let session = match self.ipfs_chat_history.get_session(&run.session_id).await {
+                Ok(session) => session,
+                Err(e) => return StopReason::Failed { error: e.to_string() },
+            };
+            let speaker = self.next_speaker(&group, &session, &prompt, &participant_traits, moderator_traits).await;
+            let Some((traits, dna_hash)) = profiles.get(&speaker) else {
+                return StopReason::Failed { error: format!("No traits for muse #{}", speaker) };
+            };
+
+            let entry = match self.take_turn(&run, &group, &session, &speaker, traits, *dna_hash, &prompt, turn).await {
+                Ok(entry) => entry,
+                Err(e) => {
+                    println!("❌ Muse #{} failed to take turn {} of {}: {}", speaker, turn, run_id, e);
+                    return StopReason::Failed { error: e.to_string() };
+                }
+            };
+            let content = entry.content.clone();
+            total_tokens += entry.response_tokens.map(|t| t as usize).unwrap_or_else(|| content.len().div_ceil(4));
+            if let Some(run) = self.runs.write().await.get_mut(run_id) {
+                run.turns.push(entry);
+            }
+
+            if let Some(reason) = stop_after_turn(&spec.stop, &speaker, &content, &recent, total_tokens, self.repetition_threshold) {
+                return reason;
+            }
+
+            recent.push(content.clone());
+            if recent.len() > REPETITION_WINDOW {
+                recent.remove(0);
+            }
+            prompt = content;
+        }
+
+        StopReason::MaxTurns
+    }
+
+    /// Speaker chosen by the turn policy, never the muse that just spoke
+    async fn next_speaker(
+        &self,
+        group: &GroupChat,
+        session: &IPFSChatSession,
+        last_message: &str,
+        traits: &[(String, MuseTraits)],
+        moderator_traits: Option<&MuseTraits>,
+    ) -> String {
+        let last_speaker = session.active_path().last().and_then(|m| m.speaker.clone());
+        self.group_chat
+            .select_speakers(group, session, last_message, traits, moderator_traits)
+            .await
+            .into_iter()
+            .find(|speaker| Some(speaker) != last_speaker.as_ref())
+            .or_else(|| next_in_rotation(group, session))
+            .unwrap_or_else(|| group.participants[0].clone())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn take_turn(
+        &self,
+        run: &WorkflowRun,
+        group: &GroupChat,
+        session: &IPFSChatSession,
+        speaker: &str,
+        traits: &MuseTraits,
+        dna_hash: [u8; 32],
+        prompt: &str,
+        turn: u32,
+    ) -> Result<TranscriptTurn> {
+        let start_time = std::time::Instant::now();
+        let token_id: u64 = speaker.parse()?;
+
+        let others = group.participants
+            .iter()
+            .filter(|id| id.as_str() != speaker)
+            .map(|id| format!("Muse #{}", id))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let system_prompt = format!(
+            "{}\n\nYou are Muse #{} in a conversation with {} about: {}\n\
+            Reply only as Muse #{}, in a few sentences, and move the conversation forward.",
+            self.orchestrator.build_personality_system_prompt(traits),
+            speaker, others, run.spec.seed_topic, speaker
+        );
+
+        let memories = self.memory_system
+            .get_user_contextual_memories(speaker, &run.user_address, &format!("{} {}", run.spec.seed_topic, prompt), self.memory_limit)
+            .await
+            .unwrap_or_default();
+
+        let context = self.context_builder.build(ContextRequest {
+            system_prompt,
+            template: None,
+            memories,
+            session: Some(session),
+            leaf_id: None,
+            user_message: "",
+            speaker_label: Some(format!("Muse #{}", speaker)),
+        }).await?;
+        let prompt_tokens = context.exact.then_some(context.prompt_tokens as u32);
+        let context_used = context.memories_used.clone();
+
+        // Each muse answers with the model of its own dominant trait
+        let (model_id, llama_engine) = self.orchestrator.select_engine(traits).await;
+        let reply = self.orchestrator
+            .generate_response_with_history(speaker, traits, prompt, context, llama_engine)
+            .await?;
+        let reply = reply.trim().to_string();
+        let response_time_ms = start_time.elapsed().as_millis() as u64;
+
+        let message_id = format!("muse_msg_{}_{}", speaker, uuid::Uuid::new_v4());
+        self.ipfs_chat_history
+            .add_reply(
+                &run.session_id,
+                session.active_leaf.clone(),
+                "assistant".to_string(),
+                reply.clone(),
+                message_id.clone(),
+                Some(speaker.to_string()),
+                Vec::new(),
+                None,
+            )
+            .await?;
+
+        let response_tokens = self.context_builder.token_counter()
+            .count_exact(&reply)
+            .await
+            .map(|tokens| tokens as u32);
+        let interaction = InteractionData {
+            user_prompt: prompt.to_string(),
+            ai_response: reply.clone(),
+            personality_traits: traits.clone(),
+            context_used,
+            session_id: Some(run.session_id.clone()),
+            conversation_turn: turn,
+            response_time_ms,
+            model_used: model_id,
+            prompt_tokens,
+            response_tokens,
+            user_satisfaction: None,
+            user_address: Some(run.user_address.clone()),
+            tool_calls: Vec::new(),
+        };
+        let verifiable_interaction = self.verification_system
+            .create_interaction_from_data(token_id, dna_hash, &interaction);
+        let commitment = self.verification_system
+            .create_commitment(&verifiable_interaction)
+            .await?;
+
+        let timestamp = now_secs();
+        let interaction_id = format!("interaction_{}_{}", speaker, message_id.trim_start_matches("muse_msg_"));
+        let commitment_hash = format!("0x{}", hex::encode(commitment.commitment_hash));
+        let signature = format!("0x{}", hex::encode(&commitment.signature));
+        self.verification_system.record_commitment(CommitmentRecord {
+            interaction_id: interaction_id.clone(),
+            muse_id: token_id,
+            user_address: run.user_address.clone(),
+            session_id: Some(run.session_id.clone()),
+            message_id: Some(message_id.clone()),
+            muse_dna_hash: format!("0x{}", hex::encode(dna_hash)),
+            commitment_hash: commitment_hash.clone(),
+            signature: signature.clone(),
+            recovery_id: commitment.recovery_id,
+            signer: self.verification_system.get_public_key_address(),
+            foreign: false,
+            created_at: timestamp,
+        }).await;
+
+        println!("🗣️ Muse #{} took turn {} of {} in {}ms", speaker, turn, run.run_id, response_time_ms);
+
+        Ok(TranscriptTurn {
+            turn,
+            muse_id: speaker.to_string(),
+            message_id,
+            interaction_id,
+            prompt: prompt.to_string(),
+            content: reply,
+            timestamp,
+            response_tokens,
+            commitment_hash,
+            signature,
+        })
+    }
+
+    async fn finish(&self, run_id: &str, stop_reason: StopReason) {
+        let mut runs = self.runs.write().await;
+        // Removed under the runs lock so a concurrent `cancel` can't leave a stale flag behind
+        self.cancelled.write().await.remove(run_id);
+        let Some(run) = runs.get_mut(run_id) else { return };
+
+        run.status = match &stop_reason {
+            StopReason::Cancelled => WorkflowStatus::Cancelled,
+            StopReason::Failed { .. } => WorkflowStatus::Failed,
+            _ => WorkflowStatus::Completed,
+        };
+        run.finished_at = Some(now_secs());
+        println!("🏁 Muse conversation {} finished after {} turns: {:?}", run_id, run.turns.len(), stop_reason);
+        run.stop_reason = Some(stop_reason);
+
+        let user_address = run.user_address.clone();
+        prune_finished_runs(&mut runs, &user_address);
+        drop(runs);
+        self.release_slot(&user_address).await;
+    }
+}
+
+impl ConversationSpec {
+    /// Clamp the turn count to `1..=max_turns` and drop blank stop phrases
+    fn bound(&mut self, max_turns: usize) {
+        self.max_turns = self.max_turns.clamp(1, max_turns);
+        self.stop.stop_phrases.retain(|phrase| !phrase.trim().is_empty());
+    }
+}
+
+/// Why the conversation ends after `speaker` said `content`, if it does. `recent` holds the
+/// replies before it and `total_tokens` includes this one.
+fn stop_after_turn(
+    stop: &StopConditions,
+    speaker: &str,
+    content: &str,
+    recent: &[String],
+    total_tokens: usize,
+    repetition_threshold: f32,
+) -> Option<StopReason> {
+    let muse_id = speaker.to_string();
+    if content.trim().is_empty() {
+        return Some(StopReason::EmptyReply { muse_id });
+    }
+    let lower = content.to_lowercase();
+    if let Some(phrase) = stop.stop_phrases.iter().find(|p| lower.contains(&p.to_lowercase())) {
+        return Some(StopReason::StopPhrase { muse_id, phrase: phrase.clone() });
+    }
+    if recent.iter().any(|earlier| word_overlap(earlier, content) >= repetition_threshold) {
+        return Some(StopReason::Repetition { muse_id });
+    }
+    if stop.max_total_tokens.is_some_and(|budget| total_tokens >= budget) {
+        return Some(StopReason::TokenBudget);
+    }
+    None
+}
+
+/// Drop the oldest finished runs of `user_address` beyond `FINISHED_RUN_HISTORY`
+fn prune_finished_runs(runs: &mut HashMap<String, WorkflowRun>, user_address: &str) {
+    let mut finished: Vec<(u64, String)> = runs.values()
+        .filter(|run| run.user_address.eq_ignore_ascii_case(user_address))
+        .filter_map(|run| run.finished_at.map(|finished_at| (finished_at, run.run_id.clone())))
+        .collect();
+    if finished.len() <= FINISHED_RUN_HISTORY {
+        return;
+    }
+    finished.sort();
+    for (_, run_id) in &finished[..finished.len() - FINISHED_RUN_HISTORY] {
+        runs.remove(run_id);
+    }
+}
+
+/// Share of distinct words the two texts have in common (Jaccard similarity)
+fn word_overlap(a: &str, b: &str) -> f32 {
+    let words = |text: &str| -> HashSet<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    };
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    a.intersection(&b).count() as f32 / a.union(&b).count() as f32
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "0x00000000000000000000000000000000000a11ce";
+    const BOB: &str = "0x0000000000000000000000000000000000000b0b";
+
+    fn spec(max_turns: usize, stop: StopConditions) -> ConversationSpec {
+        ConversationSpec {
+            participants: vec!["1".to_string(), "2".to_string()],
+            seed_topic: "Gardens".to_string(),
+            max_turns,
+            turn_policy: TurnPolicy::RoundRobin,
+            stop,
+        }
+    }
+
+    fn phrases(phrases: &[&str]) -> StopConditions {
+        StopConditions {
+            stop_phrases: phrases.iter().map(|p| p.to_string()).collect(),
+            max_total_tokens: None,
+        }
+    }
+
+    fn finished_run(run_id: &str, user_address: &str, finished_at: Option<u64>) -> WorkflowRun {
+        WorkflowRun {
+            run_id: run_id.to_string(),
+            user_address: user_address.to_string(),
+            session_id: format!("session_{}", run_id),
+            spec: spec(4, StopConditions::default()),
+            status: if finished_at.is_some() { WorkflowStatus::Completed } else { WorkflowStatus::Running },
+            stop_reason: None,
+            turns: Vec::new(),
+            started_at: 0,
+            finished_at,
+        }
+    }
+
+    #[test]
+    fn turn_limit_is_clamped_to_the_configured_maximum() {
+        let mut requested = spec(50, phrases(&["goodbye", "  ", ""]));
+        requested.bound(10);
+        assert_eq!(requested.max_turns, 10);
+        assert_eq!(requested.stop.stop_phrases, vec!["goodbye".to_string()]);
+
+        let mut none = spec(0, StopConditions::default());
+        none.bound(10);
+        assert_eq!(none.max_turns, 1);
+
+        let mut within = spec(3, StopConditions::default());
+        within.bound(10);
+        assert_eq!(within.max_turns, 3);
     }
-    
-    pub async fn enhanced_orchestration(&self) -> Result<()> {
-        // Future implementation
-        Ok(())
+
+    #[test]
+    fn ordinary_replies_keep_the_conversation_going() {
+        let recent = vec!["Roses need full sun and rich soil.".to_string()];
+        let stop = StopConditions { stop_phrases: vec!["goodbye".to_string()], max_total_tokens: Some(100) };
+        let reason = stop_after_turn(&stop, "2", "Ferns prefer shade near the pond.", &recent, 99, 0.8);
+        assert!(reason.is_none());
+    }
+
+    #[test]
+    fn stop_phrases_match_case_insensitively() {
+        let stop = phrases(&["That Settles It"]);
+        let reason = stop_after_turn(&stop, "1", "Well, that settles it then.", &[], 10, 0.8);
+        assert!(matches!(reason, Some(StopReason::StopPhrase { muse_id, phrase }) if muse_id == "1" && phrase == "That Settles It"));
+    }
+
+    #[test]
+    fn empty_replies_stop_before_other_checks() {
+        let stop = StopConditions { stop_phrases: vec!["goodbye".to_string()], max_total_tokens: Some(1) };
+        let reason = stop_after_turn(&stop, "2", "   ", &[], 10, 0.8);
+        assert!(matches!(reason, Some(StopReason::EmptyReply { muse_id }) if muse_id == "2"));
+    }
+
+    #[test]
+    fn repeating_a_recent_reply_stops_the_conversation() {
+        let recent = vec![
+            "Roses need full sun and rich soil.".to_string(),
+            "Ferns prefer shade near the pond.".to_string(),
+        ];
+        let stop = StopConditions::default();
+        let reason = stop_after_turn(&stop, "1", "roses need full sun and rich soil!", &recent, 10, 0.8);
+        assert!(matches!(reason, Some(StopReason::Repetition { muse_id }) if muse_id == "1"));
+
+        // Only the replies still in the window count
+        let reason = stop_after_turn(&stop, "1", "Roses need full sun and rich soil.", &recent[1..], 10, 0.8);
+        assert!(reason.is_none());
+    }
+
+    #[test]
+    fn token_budget_stops_once_reached() {
+        let stop = StopConditions { stop_phrases: Vec::new(), max_total_tokens: Some(100) };
+        assert!(stop_after_turn(&stop, "1", "Tulips bloom in spring.", &[], 99, 0.8).is_none());
+        assert!(matches!(stop_after_turn(&stop, "1", "Tulips bloom in spring.", &[], 100, 0.8), Some(StopReason::TokenBudget)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn finished_runs_are_pruned_per_user() {
+        let mut runs = HashMap::new();
+        for i in 0..FINISHED_RUN_HISTORY as u64 + 2 {
+            let run = finished_run(&format!("alice_{}", i), ALICE, Some(i));
+            runs.insert(run.run_id.clone(), run);
+        }
+        let running = finished_run("alice_running", ALICE, None);
+        runs.insert(running.run_id.clone(), running);
+        let bob = finished_run("bob_0", BOB, Some(0));
+        runs.insert(bob.run_id.clone(), bob);
+
+        prune_finished_runs(&mut runs, &ALICE.to_uppercase().replace("0X", "0x"));
+        assert!(!runs.contains_key("alice_0"));
+        assert!(!runs.contains_key("alice_1"));
+        assert!(runs.contains_key("alice_2"));
+        assert!(runs.contains_key("alice_running"));
+        assert!(runs.contains_key("bob_0"));
+        assert_eq!(runs.len(), FINISHED_RUN_HISTORY + 2);
+    }
+}
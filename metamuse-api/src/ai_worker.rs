@@ -1,8 +1,20 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::io::{self, BufRead, BufReader, Write};
-use alith::inference::LlamaEngine;
-use alith::core::chat::{Request, Completion, ResponseContent};
+use std::num::NonZeroU32;
+use llama_cpp_2::context::params::{LlamaContextParams, LlamaPoolingType};
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::params::LlamaModelParams;
+use llama_cpp_2::model::{AddBos, LlamaChatMessage, LlamaModel, Special};
+use llama_cpp_2::sampling::LlamaSampler;
+
+/// Context window for each inference request
+const CONTEXT_SIZE: u32 = 4096;
+/// Token budget per embedding batch; each text is also truncated to the model's trained context
+const EMBEDDING_CONTEXT_SIZE: u32 = 2048;
+/// Texts packed into one embedding decode; llama.cpp caps sequences per context at 64
+const EMBEDDING_MAX_SEQUENCES: usize = 64;
 
 /// Clean up repetitive text patterns that can occur in AI generation
 fn clean_repetitive_text(text: &str) -> String {
@@ -49,6 +61,42 @@ pub struct AIWorkerRequest {
     pub max_tokens: usize,
     pub model_path: String,
     pub request_id: String,
+    /// Health check ping - answered without running inference
+    #[serde(default)]
+    pub health_check: bool,
+    /// Emit an `AIWorkerStreamChunk` line per generated token before the final response
+    #[serde(default)]
+    pub stream: bool,
+    /// Texts to embed - only served by workers started with `--embedding-model`
+    #[serde(default)]
+    pub embed: Option<Vec<String>>,
+    /// Texts to count tokens for with the chat model's tokenizer. A non-empty `prompt`
+    /// is also counted the way inference would feed it, chat template included.
+    #[serde(default)]
+    pub tokenize: Option<Vec<String>>,
+    /// GBNF grammar the output must match, e.g. one built from a JSON schema
+    #[serde(default)]
+    pub grammar: Option<String>,
+}
+
+/// Token counts from the loaded model's tokenizer
+#[derive(Debug, Serialize)]
+pub struct TokenizeResult {
+    /// One count per text, without BOS or chat template
+    pub counts: Vec<usize>,
+    /// Tokens of the formatted `prompt`, when one was sent
+    pub prompt_tokens: Option<usize>,
+    /// Tokens the chat template and BOS add around any prompt
+    pub template_tokens: usize,
+    /// Context window shared by the prompt and the generated tokens
+    pub context_size: usize,
+}
+
+/// Incremental token chunk written to stdout for streaming requests
+#[derive(Debug, Serialize)]
+pub struct AIWorkerStreamChunk {
+    pub request_id: String,
+    pub token: String,
 }
 
 /// Response structure from AI inference worker process
@@ -59,45 +107,345 @@ pub struct AIWorkerResponse {
     pub error: Option<String>,
     pub request_id: String,
     pub inference_time_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embeddings: Option<Vec<Vec<f32>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokenized: Option<TokenizeResult>,
+}
+
+/// GGUF model driven directly through llama.cpp so tokens can be emitted as they are decoded
+/// The model stays loaded; every request gets a fresh context, so no KV cache state leaks between requests
+struct StreamingEngine {
+    backend: LlamaBackend,
+    model: LlamaModel,
+}
+
+impl StreamingEngine {
+    fn load(model_path: &str) -> Result<Self> {
+        let backend = LlamaBackend::init()
+            .map_err(|e| anyhow::anyhow!("Failed to initialize llama.cpp backend: {}", e))?;
+        let model = LlamaModel::load_from_file(&backend, model_path, &LlamaModelParams::default())
+            .map_err(|e| anyhow::anyhow!("Failed to load model {}: {}", model_path, e))?;
+        
+        Ok(Self { backend, model })
+    }
+    
+    /// Wrap the prompt in the model's own chat template when the GGUF ships one
+    fn format_prompt(&self, prompt: &str) -> String {
+        let Ok(template) = self.model.chat_template(None) else {
+            return prompt.to_string();
+        };
+        let Ok(message) = LlamaChatMessage::new("user".to_string(), prompt.to_string()) else {
+            return prompt.to_string();
+        };
+        
+        self.model
+            .apply_chat_template(&template, &[message], true)
+            .unwrap_or_else(|_| prompt.to_string())
+    }
+    
+    /// Count tokens exactly as `generate` would see them
+    fn tokenize(&self, prompt: &str, texts: &[String]) -> Result<TokenizeResult> {
+        let mut counts = Vec::with_capacity(texts.len());
+        for text in texts {
+            counts.push(self.model.str_to_token(text, AddBos::Never)?.len());
+        }
+        
+        let prompt_tokens = if prompt.is_empty() {
+            None
+        } else {
+            Some(self.model.str_to_token(&self.format_prompt(prompt), AddBos::Always)?.len())
+        };
+        let template_tokens = self.model.str_to_token(&self.format_prompt(""), AddBos::Always)?.len();
+        
+        Ok(TokenizeResult {
+            counts,
+            prompt_tokens,
+            template_tokens,
+            context_size: CONTEXT_SIZE as usize,
+        })
+    }
+    
+    /// Run one completion, calling `on_token` with each decoded piece of text. With a GBNF
+    /// `grammar`, tokens that would break it are never sampled.
+    fn generate<F>(&self, prompt: &str, temperature: f32, max_tokens: usize, grammar: Option<&str>, mut on_token: F) -> Result<String>
+    where
+        F: FnMut(&str) -> Result<()>,
+    {
+        // The prompt is decoded as one batch, so the batch must hold a full context's worth of tokens
+        let context_params = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(CONTEXT_SIZE))
+            .with_n_batch(CONTEXT_SIZE);
+        let mut context = self.model.new_context(&self.backend, context_params)
+            .map_err(|e| anyhow::anyhow!("Failed to create llama context: {}", e))?;
+        
+        let formatted_prompt = self.format_prompt(prompt);
+        let prompt_tokens = self.model.str_to_token(&formatted_prompt, AddBos::Always)?;
+        
+        let context_size = context.n_ctx() as usize;
+        if prompt_tokens.len() >= context_size {
+            return Err(anyhow::anyhow!(
+                "Prompt is {} tokens, exceeding the {} token context", prompt_tokens.len(), context_size
+            ));
+        }
+        let max_new_tokens = max_tokens.min(context_size - prompt_tokens.len());
+        
+        // Feed the prompt, only requesting logits for the last token
+        let mut batch = LlamaBatch::new(prompt_tokens.len().max(512), 1);
+        let last_index = prompt_tokens.len() as i32 - 1;
+        for (position, token) in (0_i32..).zip(prompt_tokens.iter().copied()) {
+            batch.add(token, position, &[0], position == last_index)?;
+        }
+        context.decode(&mut batch)?;
+        
+        let mut samplers = Vec::new();
+        if let Some(grammar) = grammar {
+            samplers.push(LlamaSampler::grammar(&self.model, grammar, "root")
+                .map_err(|e| anyhow::anyhow!("Invalid grammar: {:?}", e))?);
+        }
+        if temperature <= 0.0 {
+            samplers.push(LlamaSampler::greedy());
+        } else {
+            samplers.push(LlamaSampler::temp(temperature));
+            samplers.push(LlamaSampler::dist(std::process::id()));
+        }
+        let mut sampler = LlamaSampler::chain_simple(samplers);
+        
+        // Tokens can split multi-byte characters, so decode incrementally
+        let mut decoder = encoding_rs::UTF_8.new_decoder();
+        let mut output = String::new();
+        let mut position = batch.n_tokens();
+        
+        for _ in 0..max_new_tokens {
+            // `sample` already accepts the token into every sampler in the chain; accepting it
+            // again would advance the grammar twice and reject the next token
+            let token = sampler.sample(&context, batch.n_tokens() - 1);
+            
+            if self.model.is_eog_token(token) {
+                break;
+            }
+            
+            let bytes = self.model.token_to_bytes(token, Special::Tokenize)?;
+            let mut piece = String::with_capacity(32);
+            let _ = decoder.decode_to_string(&bytes, &mut piece, false);
+            
+            if !piece.is_empty() {
+                output.push_str(&piece);
+                on_token(&piece)?;
+            }
+            
+            batch.clear();
+            batch.add(token, position, &[0], true)?;
+            position += 1;
+            context.decode(&mut batch)?;
+        }
+        
+        Ok(output)
+    }
+}
+
+/// Sentence-embedding GGUF model run in llama.cpp embedding mode
+struct EmbeddingEngine {
+    backend: LlamaBackend,
+    model: LlamaModel,
+    // Cross-encoders need rank pooling, whatever their metadata says
+    rank: bool,
+}
+
+impl EmbeddingEngine {
+    fn load(model_path: &str, rank: bool) -> Result<Self> {
+        let backend = LlamaBackend::init()
+            .map_err(|e| anyhow::anyhow!("Failed to initialize llama.cpp backend: {}", e))?;
+        let model = LlamaModel::load_from_file(&backend, model_path, &LlamaModelParams::default())
+            .map_err(|e| anyhow::anyhow!("Failed to load embedding model {}: {}", model_path, e))?;
+        let engine = Self { backend, model, rank };
+        
+        // A model without a classification head yields a vector rather than one score
+        if rank {
+            let probe = engine.embed(&["query: ping\ndocument: pong".to_string()])?;
+            let outputs = probe.first().map_or(0, |scores| scores.len());
+            if outputs != 1 {
+                return Err(anyhow::anyhow!("{} is not a reranker: rank pooling produced {} outputs per pair", model_path, outputs));
+            }
+        }
+        
+        Ok(engine)
+    }
+    
+    /// Embed every text, packing as many sequences per decode as fit in the context.
+    /// Pooling follows the model's own metadata (mean/CLS), one vector per text, or
+    /// is rank pooling with one relevance score per text for rerankers.
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        // Each packed text is its own sequence, and llama.cpp only accepts seq ids below n_seq_max
+        let max_sequences = texts.len().clamp(1, EMBEDDING_MAX_SEQUENCES);
+        let mut context_params = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(EMBEDDING_CONTEXT_SIZE))
+            .with_n_batch(EMBEDDING_CONTEXT_SIZE)
+            .with_n_ubatch(EMBEDDING_CONTEXT_SIZE)
+            .with_n_seq_max(max_sequences as u32)
+            .with_embeddings(true);
+        if self.rank {
+            context_params = context_params.with_pooling_type(LlamaPoolingType::Rank);
+        }
+        let mut context = self.model.new_context(&self.backend, context_params)
+            .map_err(|e| anyhow::anyhow!("Failed to create embedding context: {}", e))?;
+        
+        let max_tokens = EMBEDDING_CONTEXT_SIZE as usize;
+        // Positions past the trained length are outside BERT-style position tables (512 for MiniLM)
+        let max_sequence_tokens = max_tokens.min(self.model.n_ctx_train() as usize);
+        // Every token belongs to exactly one sequence
+        let mut batch = LlamaBatch::new(max_tokens, 1);
+        let mut embeddings = Vec::with_capacity(texts.len());
+        let mut sequences_in_batch = 0;
+        
+        for text in texts {
+            let mut tokens = self.model.str_to_token(text, AddBos::Always)?;
+            tokens.truncate(max_sequence_tokens);
+            
+            let full = sequences_in_batch as usize >= max_sequences;
+            if full || batch.n_tokens() as usize + tokens.len() > max_tokens {
+                Self::decode_batch(&mut context, &mut batch, sequences_in_batch, &mut embeddings)?;
+                sequences_in_batch = 0;
+            }
+            
+            batch.add_sequence(&tokens, sequences_in_batch, false)?;
+            sequences_in_batch += 1;
+        }
+        if sequences_in_batch > 0 {
+            Self::decode_batch(&mut context, &mut batch, sequences_in_batch, &mut embeddings)?;
+        }
+        
+        Ok(embeddings)
+    }
+    
+    fn decode_batch(
+        context: &mut llama_cpp_2::context::LlamaContext,
+        batch: &mut LlamaBatch,
+        sequences: i32,
+        output: &mut Vec<Vec<f32>>,
+    ) -> Result<()> {
+        context.clear_kv_cache();
+        context.decode(batch)?;
+        
+        for sequence in 0..sequences {
+            let embedding = context.embeddings_seq_ith(sequence)
+                .map_err(|e| anyhow::anyhow!("Model did not produce a pooled embedding (is it an embedding model?): {}", e))?;
+            output.push(embedding.to_vec());
+        }
+        
+        batch.clear();
+        Ok(())
+    }
 }
 
-/// AI Worker Process - runs in isolation, handles one request, then exits
-/// This eliminates KV cache conflicts by ensuring fresh alith backend for each request
-pub struct AIWorker;
+/// AI Worker Process - long-lived member of the API's worker pool
+/// Keeps the GGUF model loaded and serves line-delimited JSON requests from stdin
+pub struct AIWorker {
+    engine: Option<StreamingEngine>,
+    model_path: Option<String>,
+    // Set instead of `engine` when the worker was started with `--embedding-model` or `--rerank-model`
+    embedding_engine: Option<EmbeddingEngine>,
+}
 
 impl AIWorker {
     /// Main entry point for AI worker process
-    /// Reads JSON request from stdin, processes AI inference, writes JSON response to stdout
-    pub async fn run() -> Result<()> {
+    /// Reads one JSON request per stdin line and writes one JSON response per stdout line until stdin closes
+    pub async fn run(preload_model_path: Option<String>, embedding_model_path: Option<String>, rerank_model_path: Option<String>) -> Result<()> {
         eprintln!("🤖 AI Worker Process started - PID: {}", std::process::id());
         
-        // Read request from stdin
+        let mut worker = AIWorker {
+            engine: None,
+            model_path: None,
+            embedding_engine: None,
+        };
+        
+        // Load the model before accepting requests so the first health check means "ready"
+        if let Some(model_path) = embedding_model_path {
+            eprintln!("🚀 Loading embedding model from {}...", model_path);
+            worker.embedding_engine = Some(EmbeddingEngine::load(&model_path, false)?);
+            eprintln!("✅ Embedding model loaded in worker process");
+        } else if let Some(model_path) = rerank_model_path {
+            eprintln!("🚀 Loading rerank model from {}...", model_path);
+            worker.embedding_engine = Some(EmbeddingEngine::load(&model_path, true)?);
+            eprintln!("✅ Rerank model loaded in worker process");
+        } else if let Some(model_path) = preload_model_path {
+            worker.ensure_engine(&model_path).await?;
+        }
+        
         let stdin = io::stdin();
-        let mut stdin_reader = BufReader::new(stdin.lock());
-        let mut input_line = String::new();
+        let stdin_reader = BufReader::new(stdin.lock());
+        
+        for input_line in stdin_reader.lines() {
+            let input_line = input_line?;
+            if input_line.trim().is_empty() {
+                continue;
+            }
+            
+            let request: AIWorkerRequest = match serde_json::from_str(input_line.trim()) {
+                Ok(request) => request,
+                Err(e) => {
+                    eprintln!("❌ AI Worker received malformed request: {}", e);
+                    Self::write_response(&AIWorkerResponse {
+                        success: false,
+                        response: None,
+                        error: Some(format!("Malformed request: {}", e)),
+                        request_id: "unknown".to_string(),
+                        inference_time_ms: 0,
+                        embeddings: None,
+                        tokenized: None,
+                    })?;
+                    continue;
+                }
+            };
+            
+            let worker_response = worker.handle_request(&request).await;
+            Self::write_response(&worker_response)?;
+        }
+        
+        eprintln!("🏁 AI Worker stdin closed, shutting down - PID: {}", std::process::id());
         
-        stdin_reader.read_line(&mut input_line)?;
-        let request: AIWorkerRequest = serde_json::from_str(&input_line.trim())?;
+        Ok(())
+    }
+    
+    /// Handle a single request (health check, inference, embedding or tokenize)
+    async fn handle_request(&mut self, request: &AIWorkerRequest) -> AIWorkerResponse {
+        if request.health_check {
+            let loaded = self.engine.is_some() || self.embedding_engine.is_some();
+            return AIWorkerResponse {
+                success: true,
+                response: Some(if loaded { "ready" } else { "idle" }.to_string()),
+                error: None,
+                request_id: request.request_id.clone(),
+                inference_time_ms: 0,
+                embeddings: None,
+                tokenized: None,
+            };
+        }
         
         eprintln!("🎯 AI Worker processing request: {}", request.request_id);
         
         let start_time = std::time::Instant::now();
         
-        // Process the AI inference request
-        let response = Self::process_inference_request(&request).await;
+        // Process the AI inference, embedding or tokenize request
+        let response = match (&request.embed, &request.tokenize) {
+            (Some(texts), _) => self.process_embedding_request(texts).map(|embeddings| (None, Some(embeddings), None)),
+            (None, Some(texts)) => self.process_tokenize_request(request, texts).await.map(|tokenized| (None, None, Some(tokenized))),
+            (None, None) => self.process_inference_request(request).await.map(|text| (Some(text), None, None)),
+        };
         
         let inference_time_ms = start_time.elapsed().as_millis() as u64;
         
-        // Create response
-        let worker_response = match response {
-            Ok(ai_response) => {
-                eprintln!("✅ AI Worker inference successful for request: {}", request.request_id);
+        match response {
+            Ok((ai_response, embeddings, tokenized)) => {
+                eprintln!("✅ AI Worker completed request: {} in {}ms", request.request_id, inference_time_ms);
                 AIWorkerResponse {
                     success: true,
-                    response: Some(ai_response),
+                    response: ai_response,
                     error: None,
                     request_id: request.request_id.clone(),
                     inference_time_ms,
+                    embeddings,
+                    tokenized,
                 }
             }
             Err(e) => {
@@ -108,54 +456,104 @@ impl AIWorker {
                     error: Some(e.to_string()),
                     request_id: request.request_id.clone(),
                     inference_time_ms,
+                    embeddings: None,
+                    tokenized: None,
                 }
             }
-        };
+        }
+    }
+    
+    /// Load the model once and keep it for the lifetime of the process
+    async fn ensure_engine(&mut self, model_path: &str) -> Result<()> {
+        if self.engine.is_some() {
+            if self.model_path.as_deref() != Some(model_path) {
+                eprintln!("⚠️ AI Worker already serving {:?}, ignoring request for {}", self.model_path, model_path);
+            }
+            return Ok(());
+        }
+        
+        eprintln!("🚀 Loading model from {}...", model_path);
+        
+        let engine = StreamingEngine::load(model_path)?;
         
-        // Write response to stdout
-        let response_json = serde_json::to_string(&worker_response)?;
-        println!("{}", response_json);
-        io::stdout().flush()?;
+        eprintln!("✅ Model loaded in worker process");
         
-        eprintln!("🏁 AI Worker process completed request: {} in {}ms", request.request_id, inference_time_ms);
+        self.engine = Some(engine);
+        self.model_path = Some(model_path.to_string());
         
         Ok(())
     }
     
-    /// Process AI inference request with fresh alith backend
-    async fn process_inference_request(request: &AIWorkerRequest) -> Result<String> {
-        eprintln!("🚀 Creating fresh LlamaEngine for isolated inference...");
+    /// Embed a batch of texts with the worker's embedding model
+    fn process_embedding_request(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let engine = self.embedding_engine.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Worker was not started with --embedding-model"))?;
         
-        // Create fresh LlamaEngine - this should work since it's a new process
-        let mut engine = LlamaEngine::new(&request.model_path).await
-            .map_err(|e| anyhow::anyhow!("Failed to create LlamaEngine in worker process: {}", e))?;
+        eprintln!("📐 Embedding {} text(s)...", texts.len());
+        engine.embed(texts)
+    }
+    
+    /// Count tokens with the chat model's tokenizer
+    async fn process_tokenize_request(&mut self, request: &AIWorkerRequest, texts: &[String]) -> Result<TokenizeResult> {
+        if self.embedding_engine.is_some() {
+            return Err(anyhow::anyhow!("Embedding workers do not serve tokenize requests"));
+        }
+        self.ensure_engine(&request.model_path).await?;
         
-        eprintln!("✅ Fresh LlamaEngine created successfully in worker process");
+        let engine = self.engine.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Model not loaded"))?;
+        engine.tokenize(&request.prompt, texts)
+    }
+    
+    /// Process AI inference request with the worker's loaded model
+    async fn process_inference_request(&mut self, request: &AIWorkerRequest) -> Result<String> {
+        // llama.cpp's backend can only be initialised once per process
+        if self.embedding_engine.is_some() {
+            return Err(anyhow::anyhow!("Embedding workers do not serve inference requests"));
+        }
+        self.ensure_engine(&request.model_path).await?;
         
-        // Create inference request
-        let mut inference_request = Request::new(request.prompt.clone(), String::new());
-        inference_request.temperature = Some(request.temperature);
-        inference_request.max_tokens = Some(request.max_tokens);
+        let engine = self.engine.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Model not loaded"))?;
         
-        eprintln!("📤 Sending inference request to fresh LlamaEngine...");
+        eprintln!("📤 Running inference (stream={}, grammar={})...", request.stream, request.grammar.is_some());
         
-        // Execute inference with fresh backend (no KV cache conflicts possible)
-        match engine.completion(inference_request).await {
-            Ok(response) => {
-                let generated_text = response.content();
-                eprintln!("🎉 AI inference successful! Generated {} characters", generated_text.len());
-                
-                // Clean repetitive patterns to prevent infinite loops
-                let cleaned_text = clean_repetitive_text(&generated_text);
-                eprintln!("🧹 Cleaned text from {} to {} characters", generated_text.len(), cleaned_text.len());
-                
-                Ok(cleaned_text)
-            }
-            Err(e) => {
-                eprintln!("❌ AI inference failed in worker process: {}", e);
-                Err(anyhow::anyhow!("AI inference failed: {}", e))
+        let grammar = request.grammar.as_deref();
+        let generated_text = engine.generate(&request.prompt, request.temperature, request.max_tokens, grammar, |token| {
+            if request.stream {
+                Self::write_line(&AIWorkerStreamChunk {
+                    request_id: request.request_id.clone(),
+                    token: token.to_string(),
+                })?;
             }
+            Ok(())
+        })?;
+        
+        eprintln!("🎉 AI inference successful! Generated {} characters", generated_text.len());
+        
+        // Constrained output is already well-formed; cleaning could break it
+        if grammar.is_some() {
+            return Ok(generated_text);
         }
+        
+        // Clean repetitive patterns to prevent infinite loops
+        let cleaned_text = clean_repetitive_text(&generated_text);
+        eprintln!("🧹 Cleaned text from {} to {} characters", generated_text.len(), cleaned_text.len());
+        
+        Ok(cleaned_text)
+    }
+    
+    /// Write a single JSON response line to stdout (stdout is reserved for the protocol)
+    fn write_response(response: &AIWorkerResponse) -> Result<()> {
+        Self::write_line(response)
+    }
+    
+    fn write_line<T: Serialize>(message: &T) -> Result<()> {
+        let message_json = serde_json::to_string(message)?;
+        let mut stdout = io::stdout().lock();
+        writeln!(stdout, "{}", message_json)?;
+        stdout.flush()?;
+        Ok(())
     }
 }
 
@@ -165,8 +563,22 @@ async fn main() -> Result<()> {
     // Set up basic logging to stderr (stdout is reserved for JSON communication)
     eprintln!("🤖 AI Worker binary starting...");
     
+    // Optional `--model <path>` preloads the model before the first request
+    let args: Vec<String> = std::env::args().collect();
+    let preload_model_path = args.iter()
+        .position(|arg| arg == "--model")
+        .and_then(|i| args.get(i + 1).cloned());
+    // `--embedding-model <path>` turns the worker into an embedding server instead
+    let embedding_model_path = args.iter()
+        .position(|arg| arg == "--embedding-model")
+        .and_then(|i| args.get(i + 1).cloned());
+    // `--rerank-model <path>` serves a cross-encoder with rank pooling, one score per pair
+    let rerank_model_path = args.iter()
+        .position(|arg| arg == "--rerank-model")
+        .and_then(|i| args.get(i + 1).cloned());
+    
     // Run the AI worker
-    if let Err(e) = AIWorker::run().await {
+    if let Err(e) = AIWorker::run(preload_model_path, embedding_model_path, rerank_model_path).await {
         eprintln!("❌ AI Worker failed: {}", e);
         
         // Send error response to stdout
@@ -176,6 +588,8 @@ async fn main() -> Result<()> {
             error: Some(e.to_string()),
             request_id: "unknown".to_string(),
             inference_time_ms: 0,
+            embeddings: None,
+            tokenized: None,
         };
         
         let error_json = serde_json::to_string(&error_response)?;
@@ -185,4 +599,34 @@ async fn main() -> Result<()> {
     }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Also the `ai-worker` crate root, so the grammar is spelled out rather than built by `json_grammar`
+    const ANALYSIS_GRAMMAR: &str = r#"root ::= "{" ws "\"analysis\":" ws string "," ws "\"confidence\":" ws number ws "}"
+string ::= "\"" [^"\\\x00-\x1f]* "\""
+number ::= "0" ("." [0-9]+)?
+ws ::= [ \t\n]*"#;
+
+    /// Needs a chat GGUF: `METAMUSE_TEST_MODEL=/path/to/model.gguf cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn grammar_constrained_generation_parses_as_json() {
+        let Ok(model_path) = std::env::var("METAMUSE_TEST_MODEL") else {
+            return;
+        };
+        let engine = StreamingEngine::load(&model_path).unwrap();
+
+        let output = engine
+            .generate("Analyse how a witty, empathetic muse should greet a new user.", 0.7, 256, Some(ANALYSIS_GRAMMAR), |_| Ok(()))
+            .unwrap();
+
+        let parsed: serde_json::Value = serde_json::from_str(&output)
+            .unwrap_or_else(|e| panic!("constrained output is not valid JSON ({}): {}", e, output));
+        assert!(parsed["analysis"].is_string());
+        assert!(parsed["confidence"].is_number());
+    }
+}
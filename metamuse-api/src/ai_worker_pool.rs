@@ -0,0 +1,494 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{mpsc, Mutex, OwnedMutexGuard};
+
+use crate::config::Config;
+use crate::llama_engine_wrapper::{AIWorkerMessage, AIWorkerRequest, AIWorkerResponse, TokenizeResult};
+
+/// Timeout for a single health check ping on an idle worker
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A worker did not answer a request in time. Returned inside `anyhow::Error`, so callers
+/// tell timeouts apart with `downcast_ref`.
+#[derive(Debug)]
+pub struct WorkerTimeout(pub Duration);
+
+impl std::fmt::Display for WorkerTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AI worker timed out after {}s", self.0.as_secs())
+    }
+}
+
+impl std::error::Error for WorkerTimeout {}
+
+/// A pre-spawned `ai-worker` process with the model already loaded
+struct WorkerProcess {
+    worker_id: usize,
+    child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    requests_served: usize,
+    // Tokens forwarded for the request currently in flight
+    tokens_forwarded: usize,
+}
+
+impl WorkerProcess {
+    /// Send one request line and wait for the response line with the matching request_id
+    /// Streamed token chunks are forwarded to `token_tx` as they arrive
+    async fn send(
+        &mut self,
+        request: &AIWorkerRequest,
+        timeout: Duration,
+        token_tx: Option<&mpsc::UnboundedSender<String>>,
+    ) -> Result<AIWorkerResponse> {
+        self.tokens_forwarded = 0;
+
+        let request_json = serde_json::to_string(request)?;
+        self.stdin.write_all(request_json.as_bytes()).await?;
+        self.stdin.write_all(b"\n").await?;
+        self.stdin.flush().await?;
+
+        tokio::time::timeout(timeout, self.read_response(&request.request_id, token_tx))
+            .await
+            .map_err(|_| WorkerTimeout(timeout))?
+    }
+
+    /// Read stdout lines until the response for `request_id` arrives
+    async fn read_response(
+        &mut self,
+        request_id: &str,
+        token_tx: Option<&mpsc::UnboundedSender<String>>,
+    ) -> Result<AIWorkerResponse> {
+        loop {
+            let line = self.stdout.next_line().await?
+                .ok_or_else(|| anyhow::anyhow!("AI worker closed stdout"))?;
+            let line = line.trim();
+
+            // Anything that is not a protocol line (e.g. native library output) is skipped
+            if !line.starts_with('{') {
+                continue;
+            }
+
+            let message: AIWorkerMessage = serde_json::from_str(line)
+                .map_err(|e| anyhow::anyhow!("Failed to parse AI worker response: {}", e))?;
+
+            match message {
+                AIWorkerMessage::Chunk(chunk) if chunk.request_id == request_id => {
+                    // A dropped receiver just means the client went away - keep draining to the final line
+                    if let Some(tx) = token_tx {
+                        let _ = tx.send(chunk.token);
+                    }
+                    self.tokens_forwarded += 1;
+                }
+                AIWorkerMessage::Response(response) if response.request_id == request_id => {
+                    return Ok(response);
+                }
+                AIWorkerMessage::Chunk(chunk) => {
+                    println!("⚠️ Worker #{} - Ignoring stale chunk for {}", self.worker_id, chunk.request_id);
+                }
+                AIWorkerMessage::Response(response) => {
+                    println!("⚠️ Worker #{} - Ignoring stale response for {}", self.worker_id, response.request_id);
+                }
+            }
+        }
+    }
+
+    /// Whether the process has exited
+    fn has_exited(&mut self) -> bool {
+        !matches!(self.child.try_wait(), Ok(None))
+    }
+
+    async fn kill(mut self) {
+        if let Err(e) = self.child.kill().await {
+            println!("⚠️ Worker #{} - Failed to kill process: {}", self.worker_id, e);
+        }
+    }
+}
+
+/// Pool of long-lived `ai-worker` processes speaking line-delimited JSON over stdin/stdout
+pub struct AIWorkerPool {
+    binary_path: PathBuf,
+    model_path: String,
+    // `--model` for inference workers, `--embedding-model` for embedding workers,
+    // `--rerank-model` for cross-encoder workers
+    model_flag: &'static str,
+    request_timeout: Duration,
+    startup_timeout: Duration,
+    max_requests_per_worker: usize,
+    slots: Vec<Arc<Mutex<Option<WorkerProcess>>>>,
+    next_slot: AtomicUsize,
+    request_counter: AtomicU64,
+    respawn_counter: AtomicU64,
+    // ✅ NEW: Set once the pool's model is unloaded; no worker is started again
+    shut_down: AtomicBool,
+}
+
+impl AIWorkerPool {
+    pub async fn new(config: &Config, model_path: &str) -> Result<Arc<Self>> {
+        Self::start(config, model_path, "--model", config.ai_worker_pool_size).await
+    }
+
+    /// Pool of workers serving a sentence-embedding model instead of chat inference
+    pub async fn new_embedding(config: &Config, model_path: &str) -> Result<Arc<Self>> {
+        Self::start(config, model_path, "--embedding-model", config.embedding_workers).await
+    }
+
+    /// Pool of workers scoring query/document pairs with a cross-encoder (rank pooling)
+    pub async fn new_rerank(config: &Config, model_path: &str) -> Result<Arc<Self>> {
+        Self::start(config, model_path, "--rerank-model", config.embedding_workers).await
+    }
+
+    async fn start(config: &Config, model_path: &str, model_flag: &'static str, pool_size: usize) -> Result<Arc<Self>> {
+        let binary_path = Self::resolve_binary_path(config)?;
+        let pool_size = pool_size.max(1);
+
+        println!("🏊 Starting AI worker pool: {} workers ({} {}) using {}", pool_size, model_flag, model_path, binary_path.display());
+
+        let pool = Arc::new(Self {
+            binary_path,
+            model_path: model_path.to_string(),
+            model_flag,
+            request_timeout: Duration::from_secs(config.ai_worker_request_timeout_secs),
+            startup_timeout: Duration::from_secs(config.ai_worker_startup_timeout_secs),
+            max_requests_per_worker: config.ai_worker_max_requests,
+            slots: (0..pool_size).map(|_| Arc::new(Mutex::new(None))).collect(),
+            next_slot: AtomicUsize::new(0),
+            request_counter: AtomicU64::new(0),
+            respawn_counter: AtomicU64::new(0),
+            shut_down: AtomicBool::new(false),
+        });
+
+        // Spawn all workers concurrently; each slot stays locked until its model is loaded
+        let mut startups = Vec::new();
+        for worker_id in 0..pool_size {
+            let guard = pool.slots[worker_id].clone().lock_owned().await;
+            startups.push(pool.clone().respawn_in_background(worker_id, guard));
+        }
+        for startup in startups {
+            let _ = startup.await;
+        }
+
+        let live = pool.live_workers().await;
+        if live == 0 {
+            return Err(anyhow::anyhow!("No AI workers could be started"));
+        }
+
+        println!("✅ AI worker pool ready: {}/{} workers warm", live, pool_size);
+        Ok(pool)
+    }
+
+    /// Resolve the built `ai-worker` binary - explicit config first, then next to the current executable
+    fn resolve_binary_path(config: &Config) -> Result<PathBuf> {
+        if let Some(path) = &config.ai_worker_binary_path {
+            let path = PathBuf::from(path);
+            if path.exists() {
+                return Ok(path);
+            }
+            return Err(anyhow::anyhow!("AI_WORKER_BINARY_PATH does not exist: {}", path.display()));
+        }
+
+        let current_exe = std::env::current_exe()?;
+        let path = current_exe.with_file_name(format!("ai-worker{}", std::env::consts::EXE_SUFFIX));
+        if path.exists() {
+            Ok(path)
+        } else {
+            Err(anyhow::anyhow!(
+                "ai-worker binary not found at {} (run `cargo build --bin ai-worker` or set AI_WORKER_BINARY_PATH)",
+                path.display()
+            ))
+        }
+    }
+
+    /// Spawn a worker process and wait until it has loaded the model
+    async fn spawn_worker(&self, worker_id: usize) -> Result<WorkerProcess> {
+        let mut child = Command::new(&self.binary_path)
+            .arg(self.model_flag)
+            .arg(&self.model_path)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Failed to spawn AI worker process: {}", e))?;
+
+        let stdin = child.stdin.take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get stdin handle for AI worker"))?;
+        let stdout = child.stdout.take()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get stdout handle for AI worker"))?;
+
+        let mut worker = WorkerProcess {
+            worker_id,
+            child,
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+            requests_served: 0,
+            tokens_forwarded: 0,
+        };
+
+        // The worker only reads stdin after the model is loaded, so the first ping doubles as readiness
+        let ping = AIWorkerRequest::health_check(format!("startup_{}_{}", worker_id, uuid::Uuid::new_v4()));
+        match worker.send(&ping, self.startup_timeout, None).await {
+            Ok(response) if response.success => {
+                println!("✅ Worker #{} ready (PID {:?})", worker_id, worker.child.id());
+                Ok(worker)
+            }
+            Ok(response) => {
+                worker.kill().await;
+                Err(anyhow::anyhow!("AI worker failed to start: {}", response.error.unwrap_or_default()))
+            }
+            Err(e) => {
+                worker.kill().await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Replace the worker in a locked slot without blocking the caller
+    /// The slot stays locked until the replacement is warm, so requests go to other workers meanwhile
+    fn respawn_in_background(self: Arc<Self>, worker_id: usize, mut guard: OwnedMutexGuard<Option<WorkerProcess>>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            if let Some(old_worker) = guard.take() {
+                old_worker.kill().await;
+                let respawns = self.respawn_counter.fetch_add(1, Ordering::SeqCst) + 1;
+                println!("🔄 Worker #{} - Respawning (total respawns: {})", worker_id, respawns);
+            }
+            if self.shut_down.load(Ordering::SeqCst) {
+                return;
+            }
+
+            match self.spawn_worker(worker_id).await {
+                Ok(worker) => *guard = Some(worker),
+                Err(e) => println!("❌ Worker #{} - Failed to start: {} (will retry on next health check)", worker_id, e),
+            }
+        })
+    }
+
+    /// Pick an idle worker, falling back to waiting on the next one in round-robin order
+    async fn acquire(&self) -> (usize, OwnedMutexGuard<Option<WorkerProcess>>) {
+        let start = self.next_slot.fetch_add(1, Ordering::SeqCst);
+        let pool_size = self.slots.len();
+
+        for offset in 0..pool_size {
+            let worker_id = (start + offset) % pool_size;
+            if let Ok(guard) = self.slots[worker_id].clone().try_lock_owned() {
+                if guard.is_some() {
+                    return (worker_id, guard);
+                }
+            }
+        }
+
+        let worker_id = start % pool_size;
+        (worker_id, self.slots[worker_id].clone().lock_owned().await)
+    }
+
+    /// Run one inference request on a pooled worker
+    pub async fn generate(self: &Arc<Self>, prompt: &str, temperature: f32, max_tokens: usize) -> Result<AIWorkerResponse> {
+        self.dispatch(prompt, temperature, max_tokens, None, None, None, None).await
+    }
+
+    /// ✅ NEW: Run one inference request whose output must match the GBNF `grammar`
+    pub async fn generate_with_grammar(self: &Arc<Self>, prompt: &str, temperature: f32, max_tokens: usize, grammar: &str) -> Result<AIWorkerResponse> {
+        self.dispatch(prompt, temperature, max_tokens, None, None, None, Some(grammar.to_string())).await
+    }
+
+    /// Embed a batch of texts on a pooled embedding worker
+    pub async fn embed(self: &Arc<Self>, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let expected = texts.len();
+        let response = self.dispatch("", 0.0, 0, None, Some(texts), None, None).await?;
+        if !response.success {
+            return Err(anyhow::anyhow!("Embedding failed: {}", response.error.unwrap_or_default()));
+        }
+
+        let embeddings = response.embeddings
+            .ok_or_else(|| anyhow::anyhow!("Embedding worker returned no embeddings"))?;
+        if embeddings.len() != expected {
+            return Err(anyhow::anyhow!("Embedding worker returned {} vectors for {} texts", embeddings.len(), expected));
+        }
+        Ok(embeddings)
+    }
+
+    /// Count tokens of `texts` and, when non-empty, of `prompt` as inference would format it
+    pub async fn tokenize(self: &Arc<Self>, prompt: &str, texts: Vec<String>) -> Result<TokenizeResult> {
+        let expected = texts.len();
+        let response = self.dispatch(prompt, 0.0, 0, None, None, Some(texts), None).await?;
+        if !response.success {
+            return Err(anyhow::anyhow!("Tokenize failed: {}", response.error.unwrap_or_default()));
+        }
+
+        let tokenized = response.tokenized
+            .ok_or_else(|| anyhow::anyhow!("AI worker returned no token counts"))?;
+        if tokenized.counts.len() != expected {
+            return Err(anyhow::anyhow!("AI worker returned {} token counts for {} texts", tokenized.counts.len(), expected));
+        }
+        Ok(tokenized)
+    }
+
+    /// Run one inference request, forwarding each generated token to `token_tx` as the worker decodes it
+    pub async fn generate_stream(
+        self: &Arc<Self>,
+        prompt: &str,
+        temperature: f32,
+        max_tokens: usize,
+        token_tx: mpsc::UnboundedSender<String>,
+    ) -> Result<AIWorkerResponse> {
+        self.dispatch(prompt, temperature, max_tokens, Some(token_tx), None, None, None).await
+    }
+
+    /// Crashed workers are respawned and the request is retried once on another worker
+    #[allow(clippy::too_many_arguments)]
+    async fn dispatch(
+        self: &Arc<Self>,
+        prompt: &str,
+        temperature: f32,
+        max_tokens: usize,
+        token_tx: Option<mpsc::UnboundedSender<String>>,
+        embed: Option<Vec<String>>,
+        tokenize: Option<Vec<String>>,
+        grammar: Option<String>,
+    ) -> Result<AIWorkerResponse> {
+        if self.shut_down.load(Ordering::SeqCst) {
+            return Err(anyhow::anyhow!("AI worker pool for {} has been shut down", self.model_path));
+        }
+        let request_num = self.request_counter.fetch_add(1, Ordering::SeqCst) + 1;
+        let request = AIWorkerRequest {
+            prompt: prompt.to_string(),
+            temperature,
+            max_tokens,
+            model_path: self.model_path.clone(),
+            request_id: format!("pool_req_{}", request_num),
+            health_check: false,
+            stream: token_tx.is_some(),
+            embed,
+            tokenize,
+            grammar,
+        };
+
+        let mut last_error = anyhow::anyhow!("No AI worker available");
+
+        for attempt in 1..=2 {
+            let (worker_id, mut guard) = self.acquire().await;
+
+            // Slot may be empty if the last respawn failed - try to bring it back inline
+            if guard.is_none() {
+                match self.spawn_worker(worker_id).await {
+                    Ok(worker) => *guard = Some(worker),
+                    Err(e) => {
+                        last_error = e;
+                        continue;
+                    }
+                }
+            }
+
+            let worker = guard.as_mut().expect("worker slot populated");
+            println!("📤 Request {} - Dispatching to worker #{} (attempt {})", request.request_id, worker_id, attempt);
+
+            match worker.send(&request, self.request_timeout, token_tx.as_ref()).await {
+                Ok(response) => {
+                    worker.requests_served += 1;
+
+                    let exhausted = self.max_requests_per_worker > 0
+                        && worker.requests_served >= self.max_requests_per_worker;
+                    // A failed response (prompt too long, bad grammar, ...) is the request's fault;
+                    // each request gets a fresh context, so the warm worker stays usable
+                    if exhausted {
+                        println!("♻️ Worker #{} - Recycling after {} request(s)", worker_id, worker.requests_served);
+                        self.clone().respawn_in_background(worker_id, guard);
+                    }
+
+                    println!("📋 Request {} - Worker #{} answered in {}ms (success={})",
+                             request.request_id, worker_id, response.inference_time_ms, response.success);
+                    return Ok(response);
+                }
+                Err(e) => {
+                    println!("❌ Request {} - Worker #{} failed: {}", request.request_id, worker_id, e);
+                    let timed_out = e.downcast_ref::<WorkerTimeout>().is_some();
+                    let partially_streamed = worker.tokens_forwarded > 0;
+                    self.clone().respawn_in_background(worker_id, guard);
+
+                    // A timeout already consumed the caller's budget, and a retry after partial
+                    // streaming would duplicate tokens the caller has already seen
+                    if timed_out || partially_streamed {
+                        return Err(e);
+                    }
+                    last_error = e;
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    /// Periodically ping idle workers and respawn any that crashed or stopped answering
+    pub fn start_health_monitor(self: &Arc<Self>, interval: Duration) {
+        let pool = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                if pool.shut_down.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                for worker_id in 0..pool.slots.len() {
+                    // Busy workers are healthy by definition - skip them
+                    let Ok(mut guard) = pool.slots[worker_id].clone().try_lock_owned() else {
+                        continue;
+                    };
+
+                    let healthy = match guard.as_mut() {
+                        None => false,
+                        Some(worker) => {
+                            if worker.has_exited() {
+                                println!("💀 Worker #{} - Process exited unexpectedly", worker_id);
+                                false
+                            } else {
+                                let ping = AIWorkerRequest::health_check(format!("health_{}_{}", worker_id, uuid::Uuid::new_v4()));
+                                match worker.send(&ping, HEALTH_CHECK_TIMEOUT, None).await {
+                                    Ok(response) => response.success,
+                                    Err(e) => {
+                                        println!("⚠️ Worker #{} - Health check failed: {}", worker_id, e);
+                                        false
+                                    }
+                                }
+                            }
+                        }
+                    };
+
+                    if !healthy {
+                        println!("🔄 Worker #{} - Respawning unhealthy worker", worker_id);
+                        pool.clone().respawn_in_background(worker_id, guard);
+                    }
+                }
+            }
+        });
+    }
+
+    /// ✅ NEW: Stop every worker, waiting for requests in flight to finish, and keep the pool down
+    pub async fn shutdown(&self) {
+        self.shut_down.store(true, Ordering::SeqCst);
+        for (worker_id, slot) in self.slots.iter().enumerate() {
+            if let Some(worker) = slot.lock().await.take() {
+                worker.kill().await;
+                println!("🛑 Worker #{} - Stopped ({})", worker_id, self.model_path);
+            }
+        }
+    }
+
+    /// Number of slots currently holding a worker process
+    async fn live_workers(&self) -> usize {
+        let mut live = 0;
+        for slot in &self.slots {
+            if slot.lock().await.is_some() {
+                live += 1;
+            }
+        }
+        live
+    }
+}
@@ -0,0 +1,545 @@
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{FromRequestParts, Path},
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::verification::{hex_string_to_bytes, recover_personal_sign_address};
+use crate::AppState;
+
+const SIWE_HEADER_SUFFIX: &str = " wants you to sign in with your Ethereum account:";
+
+/// Fields of an EIP-4361 message that the backend checks
+#[derive(Debug, Clone)]
+pub struct SiweMessage {
+    pub domain: String,
+    pub address: String,
+    pub version: String,
+    pub chain_id: u64,
+    pub nonce: String,
+    pub issued_at: DateTime<Utc>,
+    pub expiration_time: Option<DateTime<Utc>>,
+    pub not_before: Option<DateTime<Utc>>,
+}
+
+impl SiweMessage {
+    pub fn parse(message: &str) -> Result<Self> {
+        let mut lines = message.lines();
+
+        let header = lines.next().ok_or_else(|| anyhow!("Empty SIWE message"))?;
+        let domain = header.strip_suffix(SIWE_HEADER_SUFFIX)
+            .ok_or_else(|| anyhow!("Missing SIWE header line"))?;
+        // Newer wallets may prefix the domain with a scheme
+        let domain = domain.trim_start_matches("https://").trim_start_matches("http://");
+
+        let address = lines.next().map(str::trim).unwrap_or_default();
+        if !address.starts_with("0x") || address.len() != 42 || hex::decode(&address[2..]).is_err() {
+            return Err(anyhow!("Invalid address in SIWE message: {}", address));
+        }
+
+        // The optional statement sits between the address and the URI field, so only
+        // parse `Key: value` pairs from the URI onwards
+        let mut fields = HashMap::new();
+        for line in lines.skip_while(|line| !line.starts_with("URI: ")) {
+            if let Some((key, value)) = line.split_once(": ") {
+                fields.insert(key, value.trim());
+            }
+        }
+
+        let field = |key: &str| fields.get(key).copied().ok_or_else(|| anyhow!("Missing SIWE field: {}", key));
+        let timestamp = |value: &str| {
+            DateTime::parse_from_rfc3339(value)
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|e| anyhow!("Invalid SIWE timestamp {}: {}", value, e))
+        };
+
+        // Required by EIP-4361 even though the backend does not act on it
+        field("URI")?;
+
+        Ok(Self {
+            domain: domain.to_string(),
+            address: address.to_string(),
+            version: field("Version")?.to_string(),
+            chain_id: field("Chain ID")?.parse().map_err(|_| anyhow!("Invalid SIWE chain id"))?,
+            nonce: field("Nonce")?.to_string(),
+            issued_at: timestamp(field("Issued At")?)?,
+            expiration_time: fields.get("Expiration Time").copied().map(timestamp).transpose()?,
+            not_before: fields.get("Not Before").copied().map(timestamp).transpose()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthSession {
+    pub address: String,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Sign-In with Ethereum (EIP-4361) authentication
+///
+/// Flow:
+/// 1. `GET /api/v1/auth/nonce` issues a single-use nonce
+/// 2. The wallet signs a SIWE message containing that nonce (`personal_sign`)
+/// 3. `POST /api/v1/auth/verify` checks the message and signature and returns a session token
+/// 4. Clients send `Authorization: Bearer <token>`; handlers take `AuthenticatedUser`
+pub struct AuthService {
+    domain: String,
+    chain_id: u64,
+    nonce_ttl: Duration,
+    session_ttl: Duration,
+    max_nonces: usize,
+    // nonce -> expiry; a nonce is removed as soon as a sign-in attempt uses it
+    nonces: RwLock<HashMap<String, DateTime<Utc>>>,
+    // session token -> session
+    sessions: RwLock<HashMap<String, AuthSession>>,
+}
+
+impl AuthService {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            domain: config.siwe_domain.clone(),
+            chain_id: config.chain_id,
+            nonce_ttl: Duration::seconds(config.auth_nonce_ttl_secs as i64),
+            session_ttl: Duration::seconds(config.auth_session_ttl_secs as i64),
+            max_nonces: config.auth_max_outstanding_nonces.max(1),
+            nonces: RwLock::new(HashMap::new()),
+            sessions: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    /// Issue a single-use nonce for a SIWE message. Returns `None` while
+    /// `AUTH_MAX_OUTSTANDING_NONCES` unexpired nonces are waiting to be used.
+    pub async fn issue_nonce(&self) -> Option<(String, DateTime<Utc>)> {
+        let now = Utc::now();
+        let mut nonces = self.nonces.write().await;
+        nonces.retain(|_, expiry| *expiry > now);
+        if nonces.len() >= self.max_nonces {
+            return None;
+        }
+
+        let nonce = Uuid::new_v4().simple().to_string();
+        let expires_at = now + self.nonce_ttl;
+        nonces.insert(nonce.clone(), expires_at);
+
+        Some((nonce, expires_at))
+    }
+
+    /// Verify a signed SIWE message and open a session for the signer
+    pub async fn sign_in(&self, message: &str, signature: &str) -> Result<(String, AuthSession)> {
+        let siwe = SiweMessage::parse(message)?;
+        let now = Utc::now();
+
+        if siwe.domain != self.domain {
+            return Err(anyhow!("SIWE domain {} does not match {}", siwe.domain, self.domain));
+        }
+        if siwe.version != "1" {
+            return Err(anyhow!("Unsupported SIWE version: {}", siwe.version));
+        }
+        if siwe.chain_id != self.chain_id {
+            return Err(anyhow!("SIWE chain id {} does not match {}", siwe.chain_id, self.chain_id));
+        }
+        if siwe.issued_at > now + Duration::minutes(5) {
+            return Err(anyhow!("SIWE message is issued in the future"));
+        }
+        if siwe.expiration_time.is_some_and(|expiry| expiry <= now) {
+            return Err(anyhow!("SIWE message has expired"));
+        }
+        if siwe.not_before.is_some_and(|not_before| not_before > now) {
+            return Err(anyhow!("SIWE message is not valid yet"));
+        }
+
+        // Consume the nonce before checking the signature so it can never be replayed
+        match self.nonces.write().await.remove(&siwe.nonce) {
+            Some(expiry) if expiry > now => {}
+            Some(_) => return Err(anyhow!("SIWE nonce has expired")),
+            None => return Err(anyhow!("Unknown or already used SIWE nonce")),
+        }
+
+        let signature_bytes = hex_string_to_bytes(signature)?;
+        let signer = recover_personal_sign_address(message.as_bytes(), &signature_bytes)?;
+        if !signer.eq_ignore_ascii_case(&siwe.address) {
+            return Err(anyhow!("Signature was produced by {}, not {}", signer, siwe.address));
+        }
+
+        let expires_at = match siwe.expiration_time {
+            Some(expiry) => expiry.min(now + self.session_ttl),
+            None => now + self.session_ttl,
+        };
+        let session = AuthSession {
+            address: signer,
+            issued_at: now,
+            expires_at,
+        };
+        let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+
+        let mut sessions = self.sessions.write().await;
+        sessions.retain(|_, s| s.expires_at > now);
+        sessions.insert(token.clone(), session.clone());
+
+        println!("🔐 Wallet {} signed in (session expires {})", session.address, session.expires_at);
+        Ok((token, session))
+    }
+
+    /// Look up a live session by token
+    pub async fn authenticate(&self, token: &str) -> Option<AuthSession> {
+        let sessions = self.sessions.read().await;
+        sessions.get(token)
+            .filter(|session| session.expires_at > Utc::now())
+            .cloned()
+    }
+
+    pub async fn sign_out(&self, token: &str) -> bool {
+        self.sessions.write().await.remove(token).is_some()
+    }
+}
+
+/// Wallet address of the signed-in caller.
+///
+/// Read from `Authorization: Bearer <token>`, or from an `access_token` query
+/// parameter for WebSocket upgrades where browsers cannot set headers.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub address: String,
+    pub token: String,
+}
+
+impl AuthenticatedUser {
+    /// Whether an address supplied in a request body belongs to the caller
+    pub fn owns(&self, address: &str) -> bool {
+        self.address.eq_ignore_ascii_case(address.trim())
+    }
+}
+
+impl FromRequestParts<Arc<AppState>> for AuthenticatedUser {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(parts).ok_or(StatusCode::UNAUTHORIZED)?;
+        let session = state.auth_service.authenticate(&token).await
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        Ok(Self {
+            address: session.address,
+            token,
+        })
+    }
+}
+
+/// Signed-in caller whose address is listed in `ADMIN_ADDRESSES`
+#[derive(Debug, Clone)]
+pub struct AdminUser(pub AuthenticatedUser);
+
+impl FromRequestParts<Arc<AppState>> for AdminUser {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let user = AuthenticatedUser::from_request_parts(parts, state).await?;
+        if !state.config.admin_addresses.iter().any(|admin| user.owns(admin)) {
+            println!("🚫 {} is not an admin", user.address);
+            return Err(StatusCode::FORBIDDEN);
+        }
+        Ok(Self(user))
+    }
+}
+
+/// Signed-in caller who is allowed to interact with the muse named by the `{id}`
+/// (or `{muse_id}`) path segment: its creator, its on-chain owner or a granted address.
+#[derive(Debug, Clone)]
+pub struct MuseAccess {
+    pub token_id: u64,
+    pub user: AuthenticatedUser,
+}
+
+impl FromRequestParts<Arc<AppState>> for MuseAccess {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let user = AuthenticatedUser::from_request_parts(parts, state).await?;
+
+        let Path(params) = Path::<HashMap<String, String>>::from_request_parts(parts, state).await
+            .map_err(|_| StatusCode::BAD_REQUEST)?;
+        let token_id: u64 = params.get("id")
+            .or_else(|| params.get("muse_id"))
+            .and_then(|id| id.parse().ok())
+            .ok_or(StatusCode::BAD_REQUEST)?;
+
+        check_muse_access(state, token_id, &user.address).await?;
+        Ok(Self { token_id, user })
+    }
+}
+
+/// Whether `address` may interact with the muse: its creator, its on-chain owner or a granted address
+pub async fn check_muse_access(state: &AppState, token_id: u64, address: &str) -> Result<(), StatusCode> {
+    if !state.config.enforce_interaction_permissions || is_recorded_creator(state, token_id, address).await {
+        return Ok(());
+    }
+
+    match state.blockchain_client.can_interact(token_id, address).await {
+        Ok(true) => Ok(()),
+        Ok(false) => {
+            println!("🚫 {} has no permission to interact with muse #{}", address, token_id);
+            Err(StatusCode::FORBIDDEN)
+        }
+        Err(e) => {
+            println!("❌ Permission check failed for muse #{}: {}", token_id, e);
+            Err(StatusCode::SERVICE_UNAVAILABLE)
+        }
+    }
+}
+
+/// Whether `address` created the muse through this backend. Muses are minted by the
+/// backend wallet, so their creator is not the on-chain owner.
+pub async fn is_recorded_creator(state: &AppState, token_id: u64, address: &str) -> bool {
+    state.user_muses.read().await
+        .iter()
+        .any(|(user, token_ids)| user.eq_ignore_ascii_case(address) && token_ids.contains(&token_id))
+}
+
+/// Whether `address` may manage who can interact with the muse
+pub async fn is_muse_owner(state: &AppState, token_id: u64, address: &str) -> bool {
+    if is_recorded_creator(state, token_id, address).await {
+        return true;
+    }
+
+    match state.blockchain_client.get_muse_data(token_id).await {
+        Ok(muse) => muse.owner.eq_ignore_ascii_case(address),
+        Err(_) => false,
+    }
+}
+
+fn bearer_token(parts: &Parts) -> Option<String> {
+    let header_token = parts.headers.get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+
+    header_token.or_else(|| {
+        parts.uri.query()?
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("access_token="))
+            .map(str::to_string)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::SecondsFormat;
+    use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+    use sha3::{Digest, Keccak256};
+
+    const DOMAIN: &str = "metamuse.test";
+    const CHAIN_ID: u64 = 133;
+
+    fn service() -> AuthService {
+        AuthService {
+            domain: DOMAIN.to_string(),
+            chain_id: CHAIN_ID,
+            nonce_ttl: Duration::minutes(5),
+            session_ttl: Duration::hours(1),
+            max_nonces: 100,
+            nonces: RwLock::new(HashMap::new()),
+            sessions: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn wallet(seed: u8) -> (SecretKey, String) {
+        let secret = SecretKey::from_slice(&[seed; 32]).unwrap();
+        let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &secret);
+        (secret, crate::verification::public_key_to_address(&public_key))
+    }
+
+    /// `personal_sign` as a wallet does it, with v as 27/28
+    fn personal_sign(secret: &SecretKey, message: &str) -> String {
+        let prefixed = format!("\x19Ethereum Signed Message:\n{}{}", message.len(), message);
+        let digest = Message::from_digest_slice(&Keccak256::digest(prefixed.as_bytes())).unwrap();
+        let (recovery_id, signature) = Secp256k1::new().sign_ecdsa_recoverable(&digest, secret).serialize_compact();
+        let mut bytes = signature.to_vec();
+        bytes.push(27 + recovery_id.to_i32() as u8);
+        format!("0x{}", hex::encode(bytes))
+    }
+
+    fn timestamp(time: DateTime<Utc>) -> String {
+        time.to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+
+    fn message(domain: &str, address: &str, chain_id: u64, nonce: &str, extra: &str) -> String {
+        format!(
+            "{} wants you to sign in with your Ethereum account:\n{}\n\nNonce: forged\n\n\
+             URI: https://{}\nVersion: 1\nChain ID: {}\nNonce: {}\nIssued At: {}{}",
+            domain, address, domain, chain_id, nonce, timestamp(Utc::now()), extra
+        )
+    }
+
+    #[test]
+    fn known_key_maps_to_known_address() {
+        let secret = SecretKey::from_slice(&{
+            let mut key = [0u8; 32];
+            key[31] = 1;
+            key
+        }).unwrap();
+        let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &secret);
+        let address = crate::verification::public_key_to_address(&public_key);
+        assert!(address.eq_ignore_ascii_case("0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf"), "{}", address);
+    }
+
+    #[test]
+    fn parses_required_and_optional_fields() {
+        let (_, address) = wallet(1);
+        let expiry = Utc::now() + Duration::minutes(10);
+        let text = message(
+            &format!("https://{}", DOMAIN),
+            &address,
+            CHAIN_ID,
+            "abc123",
+            &format!("\nExpiration Time: {}\nNot Before: 2024-01-01T00:00:00Z", timestamp(expiry)),
+        );
+
+        let siwe = SiweMessage::parse(&text).unwrap();
+        assert_eq!(siwe.domain, DOMAIN);
+        assert_eq!(siwe.address, address);
+        assert_eq!(siwe.version, "1");
+        assert_eq!(siwe.chain_id, CHAIN_ID);
+        // A statement that looks like a field is not one
+        assert_eq!(siwe.nonce, "abc123");
+        assert_eq!(siwe.expiration_time.map(|t| t.timestamp()), Some(expiry.timestamp()));
+        assert_eq!(siwe.not_before.map(timestamp), Some("2024-01-01T00:00:00Z".to_string()));
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        let (_, address) = wallet(1);
+        let valid = message(DOMAIN, &address, CHAIN_ID, "abc123", "");
+        assert!(SiweMessage::parse(&valid).is_ok());
+
+        let cases = [
+            String::new(),
+            valid.replacen(" wants you to sign in", " would like you to sign in", 1),
+            valid.replacen(&address, "0x1234", 1),
+            valid.replacen(&address, &format!("0x{}", "zz".repeat(20)), 1),
+            valid.replacen("Chain ID: 133", "Chain ID: mainnet", 1),
+            valid.replacen("\nVersion: 1", "", 1),
+            valid.replacen(&format!("URI: https://{}\n", DOMAIN), "", 1),
+            format!("{}\nExpiration Time: tomorrow", valid),
+        ];
+        for text in cases {
+            assert!(SiweMessage::parse(&text).is_err(), "{:?} should be rejected", text);
+        }
+    }
+
+    #[tokio::test]
+    async fn signed_message_opens_a_session_once() {
+        let auth = service();
+        let (secret, address) = wallet(1);
+        let (nonce, _) = auth.issue_nonce().await.unwrap();
+        let text = message(DOMAIN, &address, CHAIN_ID, &nonce, "");
+        let signature = personal_sign(&secret, &text);
+
+        let (token, session) = auth.sign_in(&text, &signature).await.unwrap();
+        assert!(session.address.eq_ignore_ascii_case(&address));
+        assert_eq!(auth.authenticate(&token).await.map(|s| s.address), Some(session.address));
+
+        // The nonce is single use
+        let replay = auth.sign_in(&text, &signature).await.unwrap_err();
+        assert!(replay.to_string().contains("nonce"), "{}", replay);
+
+        assert!(auth.sign_out(&token).await);
+        assert!(auth.authenticate(&token).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn session_ends_with_the_message_expiry() {
+        let auth = service();
+        let (secret, address) = wallet(1);
+        let (nonce, _) = auth.issue_nonce().await.unwrap();
+        let expiry = Utc::now() + Duration::minutes(2);
+        let text = message(DOMAIN, &address, CHAIN_ID, &nonce, &format!("\nExpiration Time: {}", timestamp(expiry)));
+
+        let (_, session) = auth.sign_in(&text, &personal_sign(&secret, &text)).await.unwrap();
+        assert_eq!(session.expires_at.timestamp(), expiry.timestamp());
+    }
+
+    #[tokio::test]
+    async fn rejects_signatures_from_another_wallet() {
+        let auth = service();
+        let (_, address) = wallet(1);
+        let (other_secret, _) = wallet(2);
+        let (nonce, _) = auth.issue_nonce().await.unwrap();
+        let text = message(DOMAIN, &address, CHAIN_ID, &nonce, "");
+
+        let error = auth.sign_in(&text, &personal_sign(&other_secret, &text)).await.unwrap_err();
+        assert!(error.to_string().contains("Signature was produced by"), "{}", error);
+
+        // A signature over different text recovers a different signer too
+        let (secret, _) = wallet(1);
+        let (nonce, _) = auth.issue_nonce().await.unwrap();
+        let text = message(DOMAIN, &address, CHAIN_ID, &nonce, "");
+        let signature = personal_sign(&secret, &format!("{}\n", text));
+        assert!(auth.sign_in(&text, &signature).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn outstanding_nonces_are_capped() {
+        let mut auth = service();
+        auth.max_nonces = 2;
+        let (secret, address) = wallet(1);
+
+        let (nonce, _) = auth.issue_nonce().await.unwrap();
+        auth.issue_nonce().await.unwrap();
+        assert!(auth.issue_nonce().await.is_none());
+
+        // Using a nonce frees its slot, whether or not the sign-in succeeds
+        let text = message(DOMAIN, &address, CHAIN_ID, &nonce, "");
+        auth.sign_in(&text, &personal_sign(&secret, &text)).await.unwrap();
+        auth.issue_nonce().await.unwrap();
+        assert!(auth.issue_nonce().await.is_none());
+
+        // So does expiry
+        for expiry in auth.nonces.write().await.values_mut() {
+            *expiry = Utc::now() - Duration::seconds(1);
+        }
+        auth.issue_nonce().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_messages_for_another_domain_chain_or_time() {
+        let auth = service();
+        let (secret, address) = wallet(1);
+
+        let past = format!("\nExpiration Time: {}", timestamp(Utc::now() - Duration::minutes(1)));
+        let future = format!("\nNot Before: {}", timestamp(Utc::now() + Duration::hours(1)));
+        let cases = [
+            ("evil.test", CHAIN_ID, "", "domain"),
+            (DOMAIN, 1, "", "chain id"),
+            (DOMAIN, CHAIN_ID, past.as_str(), "expired"),
+            (DOMAIN, CHAIN_ID, future.as_str(), "not valid yet"),
+        ];
+        for (domain, chain_id, extra, reason) in cases {
+            let (nonce, _) = auth.issue_nonce().await.unwrap();
+            let text = message(domain, &address, chain_id, &nonce, extra);
+            let error = auth.sign_in(&text, &personal_sign(&secret, &text)).await.unwrap_err();
+            assert!(error.to_string().contains(reason), "expected {}: {}", reason, error);
+        }
+
+        // Nonces the service never issued are refused
+        let text = message(DOMAIN, &address, CHAIN_ID, "never-issued", "");
+        let error = auth.sign_in(&text, &personal_sign(&secret, &text)).await.unwrap_err();
+        assert!(error.to_string().contains("Unknown or already used"), "{}", error);
+    }
+}
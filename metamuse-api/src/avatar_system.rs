@@ -1,8 +1,11 @@
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use std::path::Path;
+use crate::storage_backend::StorageBackend;
 
 /// Avatar management system for customizable muse visual representations
 /// 
@@ -154,6 +157,17 @@ pub enum ProcessingStatus {
 }
 
 /// Avatar management service
+/// Durable storage for avatars and collections, rehydrated into `AvatarManager` at startup
+#[async_trait]
+pub trait AvatarRepository: Send + Sync {
+    async fn save_avatar(&self, avatar: &Avatar) -> anyhow::Result<()>;
+    async fn load_avatars(&self) -> anyhow::Result<Vec<Avatar>>;
+    async fn save_collection(&self, collection: &AvatarCollection) -> anyhow::Result<()>;
+    async fn load_collections(&self) -> anyhow::Result<Vec<AvatarCollection>>;
+    async fn delete_avatar(&self, avatar_id: &str) -> anyhow::Result<()>;
+    async fn delete_collection(&self, collection_id: &str) -> anyhow::Result<()>;
+}
+
 pub struct AvatarManager {
     avatars: HashMap<String, Avatar>,
     collections: HashMap<String, AvatarCollection>,
@@ -167,8 +181,10 @@ pub struct AvatarManager {
     
     // IPFS configuration
     ipfs_gateway: String,
-    pinata_api_key: Option<String>,
-    pinata_secret: Option<String>,
+    storage: Option<Arc<dyn StorageBackend>>,
+    
+    // Durable storage
+    repository: Option<Arc<dyn AvatarRepository>>,
 }
 
 impl AvatarManager {
@@ -188,8 +204,8 @@ impl AvatarManager {
                 ImageFormat::WEBP,
             ],
             ipfs_gateway: std::env::var("IPFS_GATEWAY_URL").unwrap_or_else(|_| "https://gateway.pinata.cloud/ipfs".to_string()),
-            pinata_api_key: std::env::var("IPFS_API_KEY").ok(),
-            pinata_secret: std::env::var("IPFS_API_SECRET").ok(),
+            storage: None,
+            repository: None,
         };
         
         // Initialize with curated avatars
@@ -197,6 +213,54 @@ impl AvatarManager {
         manager
     }
     
+    /// Store uploaded images in the shared content storage backend
+    pub fn with_storage(mut self, storage: Arc<dyn StorageBackend>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+    
+    fn cdn_url(&self, ipfs_hash: &str) -> String {
+        match &self.storage {
+            Some(storage) => storage.public_url(ipfs_hash),
+            None => format!("{}/{}", self.ipfs_gateway, ipfs_hash),
+        }
+    }
+    
+    /// Attach durable storage and rehydrate uploaded avatars and collections
+    pub async fn with_repository(mut self, repository: Arc<dyn AvatarRepository>) -> anyhow::Result<Self> {
+        let avatars = repository.load_avatars().await?;
+        let collections = repository.load_collections().await?;
+        let (avatar_count, collection_count) = (avatars.len(), collections.len());
+        
+        for avatar in avatars {
+            let user_avatars = self.user_avatars.entry(avatar.created_by.clone()).or_insert_with(Vec::new);
+            if !user_avatars.contains(&avatar.id) {
+                user_avatars.push(avatar.id.clone());
+            }
+            self.avatars.insert(avatar.id.clone(), avatar);
+        }
+        
+        for collection in collections {
+            let user_collections = self.user_collections.entry(collection.owner.clone()).or_insert_with(Vec::new);
+            if !user_collections.contains(&collection.id) {
+                user_collections.push(collection.id.clone());
+            }
+            self.collections.insert(collection.id.clone(), collection);
+        }
+        
+        println!("🖼️ Rehydrated {} avatars and {} collections from database", avatar_count, collection_count);
+        self.repository = Some(repository);
+        Ok(self)
+    }
+    
+    async fn persist_avatar(&self, avatar_id: &str) {
+        if let (Some(repository), Some(avatar)) = (&self.repository, self.avatars.get(avatar_id)) {
+            if let Err(e) = repository.save_avatar(avatar).await {
+                println!("⚠️  Failed to persist avatar {}: {}", avatar_id, e);
+            }
+        }
+    }
+    
     /// Upload and process a new avatar
     pub async fn upload_avatar(
         &mut self,
@@ -217,7 +281,7 @@ impl AvatarManager {
         let optimized_data = self.optimize_image(file_data, &format, &dimensions)?;
         
         // Upload to IPFS
-        let ipfs_hash = self.upload_to_ipfs(&optimized_data).await?;
+        let ipfs_hash = self.upload_to_ipfs(&optimized_data, &format).await?;
         
         // Generate metadata
         let (description, color_palette, style, mood) = self.generate_metadata(&optimized_data, &request)?;
@@ -227,7 +291,7 @@ impl AvatarManager {
             id: avatar_id.clone(),
             name: request.name,
             ipfs_hash: ipfs_hash.clone(),
-            cdn_url: Some(format!("{}/{}", self.ipfs_gateway, ipfs_hash)),
+            cdn_url: Some(self.cdn_url(&ipfs_hash)),
             local_path: None,
             format,
             dimensions,
@@ -258,14 +322,72 @@ impl AvatarManager {
             .or_insert_with(Vec::new)
             .push(avatar_id.clone());
         
+        self.persist_avatar(&avatar_id).await;
+        
         Ok(AvatarUploadResponse {
             avatar_id,
             ipfs_hash: ipfs_hash.clone(),
-            cdn_url: Some(format!("{}/{}", self.ipfs_gateway, ipfs_hash)),
+            cdn_url: Some(self.cdn_url(&ipfs_hash)),
             processing_status: ProcessingStatus::Complete,
         })
     }
     
+    /// Remove every avatar uploaded by `user_address` and every collection they own.
+    /// Other collections drop references to the removed avatars. Returns the removed
+    /// avatars (their content still needs unpinning) and the number of collections removed.
+    pub async fn erase_user_content(&mut self, user_address: &str) -> (Vec<Avatar>, usize) {
+        let avatar_ids: Vec<String> = self.avatars.values()
+            .filter(|avatar| avatar.created_by.eq_ignore_ascii_case(user_address))
+            .map(|avatar| avatar.id.clone())
+            .collect();
+        let collection_ids: Vec<String> = self.collections.values()
+            .filter(|collection| collection.owner.eq_ignore_ascii_case(user_address))
+            .map(|collection| collection.id.clone())
+            .collect();
+        
+        let mut erased = Vec::new();
+        for avatar_id in &avatar_ids {
+            if let Some(avatar) = self.avatars.remove(avatar_id) {
+                erased.push(avatar);
+            }
+            if let Some(repository) = &self.repository {
+                if let Err(e) = repository.delete_avatar(avatar_id).await {
+                    println!("⚠️  Failed to delete avatar {}: {}", avatar_id, e);
+                }
+            }
+        }
+        for collection_id in &collection_ids {
+            self.collections.remove(collection_id);
+            if let Some(repository) = &self.repository {
+                if let Err(e) = repository.delete_collection(collection_id).await {
+                    println!("⚠️  Failed to delete avatar collection {}: {}", collection_id, e);
+                }
+            }
+        }
+        self.user_avatars.retain(|address, _| !address.eq_ignore_ascii_case(user_address));
+        self.user_collections.retain(|address, _| !address.eq_ignore_ascii_case(user_address));
+        
+        let mut updated = Vec::new();
+        for collection in self.collections.values_mut() {
+            let before = collection.avatar_ids.len();
+            collection.avatar_ids.retain(|id| !avatar_ids.contains(id));
+            if collection.avatar_ids.len() != before {
+                collection.updated_at = Utc::now();
+                updated.push(collection.clone());
+            }
+        }
+        if let Some(repository) = &self.repository {
+            for collection in &updated {
+                if let Err(e) = repository.save_collection(collection).await {
+                    println!("⚠️  Failed to persist avatar collection {}: {}", collection.id, e);
+                }
+            }
+        }
+        
+        println!("🧽 Erased {} avatars and {} collections of {}", erased.len(), collection_ids.len(), user_address);
+        (erased, collection_ids.len())
+    }
+    
     /// Generate AI avatar based on personality traits
     pub async fn generate_avatar(
         &mut self,
@@ -344,7 +466,7 @@ impl AvatarManager {
     }
     
     /// Create avatar collection
-    pub fn create_collection(
+    pub async fn create_collection(
         &mut self,
         name: String,
         description: String,
@@ -378,6 +500,12 @@ impl AvatarManager {
             .or_insert_with(Vec::new)
             .push(collection_id.clone());
         
+        if let (Some(repository), Some(collection)) = (&self.repository, self.collections.get(&collection_id)) {
+            if let Err(e) = repository.save_collection(collection).await {
+                println!("⚠️  Failed to persist avatar collection {}: {}", collection_id, e);
+            }
+        }
+        
         Ok(collection_id)
     }
     
@@ -398,7 +526,7 @@ impl AvatarManager {
     }
     
     /// Rate an avatar
-    pub fn rate_avatar(&mut self, avatar_id: &str, rating: u8) -> Result<(), String> {
+    pub async fn rate_avatar(&mut self, avatar_id: &str, rating: u8) -> Result<(), String> {
         if rating < 1 || rating > 5 {
             return Err("Rating must be between 1 and 5".to_string());
         }
@@ -409,6 +537,7 @@ impl AvatarManager {
             avatar.rating_count += 1;
             avatar.rating = (total_rating + rating as f32) / avatar.rating_count as f32;
             avatar.updated_at = Utc::now();
+            self.persist_avatar(avatar_id).await;
             Ok(())
         } else {
             Err("Avatar not found".to_string())
@@ -416,10 +545,11 @@ impl AvatarManager {
     }
     
     /// Increment usage count
-    pub fn increment_usage(&mut self, avatar_id: &str) {
+    pub async fn increment_usage(&mut self, avatar_id: &str) {
         if let Some(avatar) = self.avatars.get_mut(avatar_id) {
             avatar.usage_count += 1;
             avatar.last_used = Utc::now();
+            self.persist_avatar(avatar_id).await;
         }
     }
     
@@ -558,23 +688,28 @@ impl AvatarManager {
         }
     }
     
-    async fn upload_to_ipfs(&self, data: &[u8]) -> Result<String, String> {
-        // ✅ IMPLEMENTED: Actual IPFS upload via Pinata API
+    async fn upload_to_ipfs(&self, data: &[u8], format: &ImageFormat) -> Result<String, String> {
+        let Some(storage) = &self.storage else {
+            // Generate mock hash for development when no storage backend is attached
+            eprintln!("⚠️ No content storage configured, generating mock hash for development");
+            return self.generate_mock_hash(data);
+        };
         
-        if let (Some(api_key), Some(api_secret)) = (&self.pinata_api_key, &self.pinata_secret) {
-            // Use actual Pinata upload
-            match self.upload_to_pinata(data, api_key, api_secret).await {
-                Ok(ipfs_hash) => Ok(ipfs_hash),
-                Err(e) => {
-                    eprintln!("❌ Pinata upload failed: {}", e);
-                    // Fallback to mock hash for development
-                    self.generate_mock_hash(data)
-                }
+        let (extension, content_type) = match format {
+            ImageFormat::PNG => ("png", "image/png"),
+            ImageFormat::JPG => ("jpg", "image/jpeg"),
+            ImageFormat::GIF => ("gif", "image/gif"),
+            ImageFormat::SVG => ("svg", "image/svg+xml"),
+            ImageFormat::WEBP => ("webp", "image/webp"),
+        };
+        
+        match storage.put(data.to_vec(), &format!("avatar.{}", extension), content_type).await {
+            Ok(ipfs_hash) => Ok(ipfs_hash),
+            Err(e) => {
+                eprintln!("❌ Avatar upload to {} storage failed: {}", storage.name(), e);
+                // Fallback to mock hash for development
+                self.generate_mock_hash(data)
             }
-        } else {
-            // Generate mock hash for development when no API keys
-            eprintln!("⚠️ No IPFS API keys found, generating mock hash for development");
-            self.generate_mock_hash(data)
         }
     }
     
@@ -589,41 +724,6 @@ impl AvatarManager {
         Ok(format!("Qm{:016x}{:016x}", hash, hash.wrapping_mul(31)))
     }
     
-    async fn upload_to_pinata(&self, data: &[u8], api_key: &str, api_secret: &str) -> Result<String, String> {
-        let client = reqwest::Client::new();
-        
-        // Create multipart form for Pinata upload
-        let form = reqwest::multipart::Form::new()
-            .part("file", reqwest::multipart::Part::bytes(data.to_vec())
-                .file_name("avatar.png")
-                .mime_str("image/png")
-                .map_err(|e| format!("Failed to create multipart: {}", e))?
-            );
-        
-        let response = client
-            .post("https://api.pinata.cloud/pinning/pinFileToIPFS")
-            .header("pinata_api_key", api_key)
-            .header("pinata_secret_api_key", api_secret)
-            .multipart(form)
-            .send()
-            .await
-            .map_err(|e| format!("Failed to upload to Pinata: {}", e))?;
-            
-        if response.status().is_success() {
-            let json: serde_json::Value = response.json().await
-                .map_err(|e| format!("Failed to parse Pinata response: {}", e))?;
-                
-            if let Some(ipfs_hash) = json.get("IpfsHash").and_then(|h| h.as_str()) {
-                Ok(ipfs_hash.to_string())
-            } else {
-                Err("Pinata response missing IpfsHash".to_string())
-            }
-        } else {
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            Err(format!("Pinata upload failed: {}", error_text))
-        }
-    }
-    
     fn generate_metadata(&self, data: &[u8], request: &AvatarUploadRequest) -> Result<(Option<String>, Vec<String>, AvatarStyle, Option<AvatarMood>), String> {
         // Mock metadata generation (in production, use AI services)
         let description = request.description.clone().or_else(|| {
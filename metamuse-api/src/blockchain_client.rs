@@ -1,14 +1,14 @@
 use anyhow::Result;
 use ethers::{
-    core::types::{Address, U256, Bytes, Filter, Log},
+    core::types::{Address, U256, H256, Bytes, Filter, Log},
     middleware::SignerMiddleware,
     providers::{Provider, Http, Middleware, StreamExt},
     signers::{LocalWallet, Signer},
     contract::abigen,
-    utils::{format_ether, parse_ether},
+    utils::{format_ether, keccak256, parse_ether},
 };
 use serde::{Deserialize, Serialize};
-use std::{sync::Arc, str::FromStr, collections::HashMap};
+use std::{sync::Arc, str::FromStr, collections::HashMap, time::{Duration, Instant}};
 use tokio::sync::RwLock;
 use crate::{config::Config, muse_orchestrator::MuseTraits};
 
@@ -94,6 +94,26 @@ abigen!(
             "stateMutability": "view",
             "type": "function"
         },
+        {
+            "inputs": [
+                {"internalType": "uint256", "name": "_tokenId", "type": "uint256"},
+                {"internalType": "address", "name": "_user", "type": "address"}
+            ],
+            "name": "grantInteractionPermission",
+            "outputs": [],
+            "stateMutability": "nonpayable",
+            "type": "function"
+        },
+        {
+            "inputs": [
+                {"internalType": "uint256", "name": "_tokenId", "type": "uint256"},
+                {"internalType": "address", "name": "_user", "type": "address"}
+            ],
+            "name": "revokeInteractionPermission",
+            "outputs": [],
+            "stateMutability": "nonpayable",
+            "type": "function"
+        },
         {
             "anonymous": false,
             "inputs": [
@@ -127,6 +147,24 @@ abigen!(
             ],
             "name": "InteractionVerified",
             "type": "event"
+        },
+        {
+            "anonymous": false,
+            "inputs": [
+                {"indexed": true, "internalType": "uint256", "name": "tokenId", "type": "uint256"},
+                {"indexed": true, "internalType": "address", "name": "user", "type": "address"}
+            ],
+            "name": "InteractionPermissionGranted",
+            "type": "event"
+        },
+        {
+            "anonymous": false,
+            "inputs": [
+                {"indexed": true, "internalType": "uint256", "name": "tokenId", "type": "uint256"},
+                {"indexed": true, "internalType": "address", "name": "user", "type": "address"}
+            ],
+            "name": "InteractionPermissionRevoked",
+            "type": "event"
         }
     ]"#
 );
@@ -260,6 +298,9 @@ pub struct BlockchainClient {
     rating_contract_address: Address,
     // Cache for frequently accessed muse data
     muse_cache: RwLock<HashMap<u64, MuseData>>,
+    // Short-lived canInteract results, cleared by permission and transfer events
+    permission_cache: RwLock<HashMap<(u64, Address), (bool, Instant)>>,
+    permission_cache_ttl: Duration,
     config: Config,
 }
 
@@ -295,6 +336,8 @@ impl BlockchainClient {
             contract_address,
             rating_contract_address,
             muse_cache: RwLock::new(HashMap::new()),
+            permission_cache: RwLock::new(HashMap::new()),
+            permission_cache_ttl: Duration::from_secs(config.permission_cache_ttl_secs),
             config: config.clone(),
         })
     }
@@ -369,17 +412,88 @@ impl BlockchainClient {
         Ok(data)
     }
     
-    /// Check if a user can interact with a specific Muse
+    /// Check if a user can interact with a specific Muse (owner or granted address)
     pub async fn can_interact(&self, token_id: u64, user_address: &str) -> Result<bool> {
         let user_addr = Address::from_str(user_address)?;
+        
+        // Check cache first
+        {
+            let cache = self.permission_cache.read().await;
+            if let Some((allowed, checked_at)) = cache.get(&(token_id, user_addr)) {
+                if checked_at.elapsed() < self.permission_cache_ttl {
+                    return Ok(*allowed);
+                }
+            }
+        }
+        
         let can_interact = self.contract
             .can_interact(U256::from(token_id), user_addr)
             .call()
             .await?;
         
+        self.permission_cache.write().await.insert((token_id, user_addr), (can_interact, Instant::now()));
+        
         Ok(can_interact)
     }
     
+    /// Allow another address to interact with a Muse (backend wallet must own it on chain)
+    pub async fn grant_interaction_permission(&self, token_id: u64, user_address: &str) -> Result<TransactionInfo> {
+        let user_addr = Address::from_str(user_address)?;
+        let call = self.contract.grant_interaction_permission(U256::from(token_id), user_addr);
+        
+        let gas_estimate = call.estimate_gas().await?;
+        let gas_limit = gas_estimate * 120 / 100;
+        
+        let call_with_gas = call.gas(gas_limit);
+        let pending_tx = call_with_gas.send().await?;
+        let tx_hash = pending_tx.tx_hash();
+        
+        let receipt = pending_tx.await?.ok_or_else(|| {
+            anyhow::anyhow!("Transaction failed")
+        })?;
+        
+        // Don't wait for the event listener to pick up the change
+        self.invalidate_permissions(token_id).await;
+        
+        Ok(TransactionInfo {
+            hash: format!("{:?}", tx_hash),
+            block_number: receipt.block_number.map(|n| n.as_u64()),
+            gas_used: receipt.gas_used.map(|g| g.as_u64()),
+            status: receipt.status == Some(1.into()),
+        })
+    }
+    
+    /// Revoke a previously granted interaction permission
+    pub async fn revoke_interaction_permission(&self, token_id: u64, user_address: &str) -> Result<TransactionInfo> {
+        let user_addr = Address::from_str(user_address)?;
+        let call = self.contract.revoke_interaction_permission(U256::from(token_id), user_addr);
+        
+        let gas_estimate = call.estimate_gas().await?;
+        let gas_limit = gas_estimate * 120 / 100;
+        
+        let call_with_gas = call.gas(gas_limit);
+        let pending_tx = call_with_gas.send().await?;
+        let tx_hash = pending_tx.tx_hash();
+        
+        let receipt = pending_tx.await?.ok_or_else(|| {
+            anyhow::anyhow!("Transaction failed")
+        })?;
+        
+        self.invalidate_permissions(token_id).await;
+        
+        Ok(TransactionInfo {
+            hash: format!("{:?}", tx_hash),
+            block_number: receipt.block_number.map(|n| n.as_u64()),
+            gas_used: receipt.gas_used.map(|g| g.as_u64()),
+            status: receipt.status == Some(1.into()),
+        })
+    }
+    
+    /// Drop cached permission checks for a Muse
+    pub async fn invalidate_permissions(&self, token_id: u64) {
+        self.permission_cache.write().await.retain(|(cached_token_id, _), _| *cached_token_id != token_id);
+    }
+    
     /// Commit an interaction to the blockchain
     pub async fn commit_interaction(
         &self,
@@ -467,7 +581,7 @@ impl BlockchainClient {
         
         // Listen for events in a loop
         while let Some(log) = stream.next().await {
-            if let Ok(event_data) = self.parse_log_to_event(&log).await {
+            if let Ok(event_data) = parse_log_to_event(&log) {
                 println!("📅 New event: {:?}", event_data);
                 
                 // Call the event handler
@@ -485,6 +599,20 @@ impl BlockchainClient {
         Ok(())
     }
     
+    /// Keep the contract event listener running so permission changes reach the cache promptly
+    pub fn start_event_listener(self: &Arc<Self>) {
+        let client = self.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = client.listen_for_events(|_| Ok(())).await {
+                    println!("⚠️  Blockchain event listener stopped: {}", e);
+                }
+                // Cached permissions still expire after PERMISSION_CACHE_TTL_SECS while we reconnect
+                tokio::time::sleep(Duration::from_secs(30)).await;
+            }
+        });
+    }
+    
     /// Get recent events for a specific Muse
     pub async fn get_muse_events(&self, token_id: u64, from_block: Option<u64>) -> Result<Vec<EventData>> {
         let from_block = from_block.unwrap_or(0);
@@ -498,7 +626,7 @@ impl BlockchainClient {
         
         let mut events = Vec::new();
         for log in logs {
-            if let Ok(event_data) = self.parse_log_to_event(&log).await {
+            if let Ok(event_data) = parse_log_to_event(&log) {
                 events.push(event_data);
             }
         }
@@ -539,7 +667,7 @@ impl BlockchainClient {
             
             // Look for MuseCreated event first (preferred)
             if log_sig == muse_created_sig && log.topics.len() >= 2 {
-                let token_id = U256::from(log.topics[1].0).as_u64();
+                let token_id = topic_token_id(&log.topics[1])?;
                 println!("✅ Found token ID from MuseCreated event: {}", token_id);
                 return Ok(token_id);
             }
@@ -547,7 +675,7 @@ impl BlockchainClient {
             // Fallback to Transfer event (ERC721 mint)
             if log_sig == transfer_sig && log.topics.len() >= 4 {
                 // For Transfer: topics[1]=from, topics[2]=to, topics[3]=tokenId
-                let token_id = U256::from(log.topics[3].0).as_u64();
+                let token_id = topic_token_id(&log.topics[3])?;
                 println!("✅ Found token ID from Transfer event: {}", token_id);
                 return Ok(token_id);
             }
@@ -565,6 +693,16 @@ impl BlockchainClient {
         
         let event_signature = log.topics[0];
         
+        // Permission-affecting events are matched on their exact topic hashes
+        if event_signature == event_topic("InteractionPermissionGranted(uint256,address)")
+            || event_signature == event_topic("InteractionPermissionRevoked(uint256,address)")
+        {
+            return self.handle_permission_changed_event(log).await;
+        }
+        if event_signature == event_topic("Transfer(address,address,uint256)") {
+            return self.handle_transfer_event(log).await;
+        }
+        
         // Event signatures (first 4 bytes of keccak256 hash of event signature)
         // These would match the actual event signatures from the contract
         match format!("{:?}", event_signature).as_str() {
@@ -587,7 +725,7 @@ impl BlockchainClient {
     
     async fn handle_muse_created_event(&self, log: &Log) -> Result<()> {
         if log.topics.len() >= 2 {
-            let token_id = U256::from(log.topics[1].0).as_u64();
+            let token_id = topic_token_id(&log.topics[1])?;
             let creator = if log.topics.len() > 2 {
                 format!("{:?}", log.topics[2])
             } else {
@@ -610,7 +748,7 @@ impl BlockchainClient {
     
     async fn handle_interaction_committed_event(&self, log: &Log) -> Result<()> {
         if log.topics.len() >= 3 {
-            let token_id = U256::from(log.topics[1].0).as_u64();
+            let token_id = topic_token_id(&log.topics[1])?;
             let commitment_hash = format!("{:?}", log.topics[2]);
             
             println!("💭 Interaction committed for Muse {}: {}", token_id, commitment_hash);
@@ -626,7 +764,7 @@ impl BlockchainClient {
     
     async fn handle_interaction_verified_event(&self, log: &Log) -> Result<()> {
         if log.topics.len() >= 3 {
-            let token_id = U256::from(log.topics[1].0).as_u64();
+            let token_id = topic_token_id(&log.topics[1])?;
             let commitment_hash = format!("{:?}", log.topics[2]);
             
             println!("✅ Interaction verified for Muse {}: {}", token_id, commitment_hash);
@@ -643,37 +781,29 @@ impl BlockchainClient {
         Ok(())
     }
     
-    async fn parse_log_to_event(&self, log: &Log) -> Result<EventData> {
-        // Parse log based on event signature
-        if log.topics.is_empty() {
-            return Err(anyhow::anyhow!("No topics in log"));
+    async fn handle_permission_changed_event(&self, log: &Log) -> Result<()> {
+        if log.topics.len() >= 3 {
+            let token_id = topic_token_id(&log.topics[1])?;
+            let user = Address::from(log.topics[2]);
+            
+            println!("🔑 Interaction permission changed for Muse {}: {:?}", token_id, user);
+            self.invalidate_permissions(token_id).await;
         }
         
-        let event_signature = log.topics[0];
-        
-        // Basic event parsing - in production would use proper ABI decoding
-        let event_data = EventData {
-            event_type: format!("{:?}", event_signature),
-            token_id: if log.topics.len() > 1 {
-                Some(U256::from(log.topics[1].0).as_u64())
-            } else {
-                None
-            },
-            commitment_hash: if log.topics.len() > 2 {
-                Some(format!("{:?}", log.topics[2]))
-            } else {
-                None
-            },
-            user_address: if log.topics.len() > 3 {
-                Some(format!("{:?}", log.topics[3]))
-            } else {
-                None
-            },
-            block_number: log.block_number.unwrap_or_default().as_u64(),
-            transaction_hash: format!("{:?}", log.transaction_hash.unwrap_or_default()),
-        };
+        Ok(())
+    }
+    
+    async fn handle_transfer_event(&self, log: &Log) -> Result<()> {
+        // For Transfer: topics[1]=from, topics[2]=to, topics[3]=tokenId
+        if log.topics.len() >= 4 {
+            let token_id = topic_token_id(&log.topics[3])?;
+            
+            println!("📦 Muse {} changed owner: {:?}", token_id, Address::from(log.topics[2]));
+            self.muse_cache.write().await.remove(&token_id);
+            self.invalidate_permissions(token_id).await;
+        }
         
-        Ok(event_data)
+        Ok(())
     }
     
     // ✅ NEW: AI Alignment Market blockchain integration - REAL TRANSACTIONS!
@@ -871,6 +1001,52 @@ impl BlockchainClient {
 }
 
 // Utility functions for blockchain integration
+fn event_topic(signature: &str) -> H256 {
+    H256::from(keccak256(signature.as_bytes()))
+}
+
+/// Decode an indexed uint256 topic as a token id, rejecting values wider than u64
+fn topic_token_id(topic: &H256) -> Result<u64> {
+    let value = U256::from(topic.0);
+    if value > U256::from(u64::MAX) {
+        return Err(anyhow::anyhow!("Token id topic {:?} does not fit in u64", topic));
+    }
+    Ok(value.low_u64())
+}
+
+fn parse_log_to_event(log: &Log) -> Result<EventData> {
+    if log.topics.is_empty() {
+        return Err(anyhow::anyhow!("No topics in log"));
+    }
+    
+    let event_signature = log.topics[0];
+    let topic = |i: usize| log.topics.get(i).map(|t| format!("{:?}", t));
+    
+    // Where the token id sits depends on the event: ERC-721 Transfer indexes
+    // (from, to, tokenId), the MetaMuse events index the token id first
+    let (token_id, commitment_hash, user_address) = if event_signature == event_topic("Transfer(address,address,uint256)") {
+        let token_id = log.topics.get(3).map(topic_token_id).transpose()?;
+        (token_id, None, topic(2))
+    } else if event_signature == event_topic("InteractionPermissionGranted(uint256,address)")
+        || event_signature == event_topic("InteractionPermissionRevoked(uint256,address)")
+    {
+        let token_id = log.topics.get(1).map(topic_token_id).transpose()?;
+        (token_id, None, topic(2))
+    } else {
+        let token_id = log.topics.get(1).map(topic_token_id).transpose()?;
+        (token_id, topic(2), topic(3))
+    };
+    
+    Ok(EventData {
+        event_type: format!("{:?}", event_signature),
+        token_id,
+        commitment_hash,
+        user_address,
+        block_number: log.block_number.unwrap_or_default().as_u64(),
+        transaction_hash: format!("{:?}", log.transaction_hash.unwrap_or_default()),
+    })
+}
+
 pub fn wei_to_ether(wei: U256) -> String {
     format_ether(wei)
 }
@@ -878,3 +1054,61 @@ pub fn wei_to_ether(wei: U256) -> String {
 pub fn ether_to_wei(ether: &str) -> Result<U256> {
     parse_ether(ether).map_err(|e| anyhow::anyhow!("Failed to parse ether: {}", e))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address_topic(address: Address) -> H256 {
+        H256::from(address)
+    }
+
+    fn token_topic(token_id: u64) -> H256 {
+        let mut bytes = [0u8; 32];
+        U256::from(token_id).to_big_endian(&mut bytes);
+        H256::from(bytes)
+    }
+
+    fn log_with_topics(topics: Vec<H256>) -> Log {
+        Log { topics, ..Default::default() }
+    }
+
+    #[test]
+    fn parses_token_id_of_a_non_mint_transfer() {
+        let from = Address::from_str("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").unwrap();
+        let to = Address::from_str("0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359").unwrap();
+        let log = log_with_topics(vec![
+            event_topic("Transfer(address,address,uint256)"),
+            address_topic(from),
+            address_topic(to),
+            token_topic(42),
+        ]);
+
+        let event = parse_log_to_event(&log).unwrap();
+        assert_eq!(event.token_id, Some(42));
+        assert_eq!(event.user_address, Some(format!("{:?}", address_topic(to))));
+        assert_eq!(event.commitment_hash, None);
+    }
+
+    #[test]
+    fn parses_token_id_first_for_metamuse_events() {
+        let user = Address::from_str("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").unwrap();
+        let log = log_with_topics(vec![
+            event_topic("InteractionPermissionGranted(uint256,address)"),
+            token_topic(7),
+            address_topic(user),
+        ]);
+        assert_eq!(parse_log_to_event(&log).unwrap().token_id, Some(7));
+    }
+
+    #[test]
+    fn rejects_token_ids_wider_than_u64() {
+        let user = Address::from_str("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").unwrap();
+        let log = log_with_topics(vec![
+            event_topic("InteractionCommitted(uint256,bytes32)"),
+            address_topic(user),
+        ]);
+        assert!(parse_log_to_event(&log).is_err());
+        assert!(parse_log_to_event(&log_with_topics(Vec::new())).is_err());
+    }
+}
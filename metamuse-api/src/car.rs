@@ -0,0 +1,330 @@
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+
+/// Multihash code for sha2-256, the only hash function accepted on read
+const SHA2_256_CODE: u64 = 0x12;
+/// CBOR tag for IPLD links
+const CID_TAG: u64 = 42;
+
+/// One IPFS block in a CAR (content-addressed archive) file
+#[derive(Debug, Clone)]
+pub struct CarBlock {
+    pub cid: Vec<u8>,
+    pub data: Vec<u8>,
+}
+
+/// Encode a CARv1 file: a varint-prefixed DAG-CBOR header `{roots: [CID], version: 1}`
+/// followed by one varint-prefixed section per block holding the binary CID and the data.
+/// See https://ipld.io/specs/transport/car/carv1/
+pub fn write_car(roots: &[Vec<u8>], blocks: &[CarBlock]) -> Vec<u8> {
+    // DAG-CBOR sorts map keys by length first, so "roots" precedes "version"
+    let mut header = Vec::new();
+    cbor_head(&mut header, 5, 2);
+    cbor_text(&mut header, "roots");
+    cbor_head(&mut header, 4, roots.len() as u64);
+    for root in roots {
+        cbor_head(&mut header, 6, CID_TAG);
+        // Links carry the identity multibase prefix (0x00) before the binary CID
+        cbor_head(&mut header, 2, root.len() as u64 + 1);
+        header.push(0x00);
+        header.extend_from_slice(root);
+    }
+    cbor_text(&mut header, "version");
+    cbor_head(&mut header, 0, 1);
+
+    let mut car = Vec::new();
+    write_varint(&mut car, header.len() as u64);
+    car.extend_from_slice(&header);
+    for block in blocks {
+        write_varint(&mut car, (block.cid.len() + block.data.len()) as u64);
+        car.extend_from_slice(&block.cid);
+        car.extend_from_slice(&block.data);
+    }
+    car
+}
+
+/// Parse a CARv1 file into its roots and blocks, checking every block against its CID
+pub fn read_car(bytes: &[u8]) -> Result<(Vec<Vec<u8>>, Vec<CarBlock>)> {
+    let mut pos = 0;
+    let header_len = read_varint(bytes, &mut pos)? as usize;
+    let header = take(bytes, &mut pos, header_len)?;
+    let roots = parse_header(header)?;
+
+    let mut blocks = Vec::new();
+    while pos < bytes.len() {
+        let section_len = read_varint(bytes, &mut pos)? as usize;
+        let section = take(bytes, &mut pos, section_len)?;
+        let (cid_len, digest) = parse_cid(section)?;
+        let data = &section[cid_len..];
+
+        if Sha256::digest(data).as_slice() != digest {
+            return Err(anyhow!("CAR block {} does not match its CID", blocks.len()));
+        }
+        blocks.push(CarBlock { cid: section[..cid_len].to_vec(), data: data.to_vec() });
+    }
+
+    Ok((roots, blocks))
+}
+
+fn parse_header(header: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut pos = 0;
+    let Cbor::Map(entries) = decode_cbor(header, &mut pos, 0)? else {
+        return Err(anyhow!("CAR header is not a map"));
+    };
+
+    let mut version = None;
+    let mut roots = Vec::new();
+    for (key, value) in entries {
+        match (key, value) {
+            (Cbor::Text(key), Cbor::Uint(v)) if key == "version" => version = Some(v),
+            (Cbor::Text(key), Cbor::Array(links)) if key == "roots" => {
+                for link in links {
+                    match link {
+                        Cbor::Tag(CID_TAG, inner) => match *inner {
+                            Cbor::Bytes(bytes) if bytes.first() == Some(&0x00) => roots.push(bytes[1..].to_vec()),
+                            _ => return Err(anyhow!("Malformed CAR root")),
+                        },
+                        _ => return Err(anyhow!("CAR root is not a CID link")),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if version != Some(1) {
+        return Err(anyhow!("Unsupported CAR version {:?}", version));
+    }
+    Ok(roots)
+}
+
+/// Length of the CID at the start of `section` and its sha2-256 digest
+fn parse_cid(section: &[u8]) -> Result<(usize, &[u8])> {
+    // CIDv0 is a bare sha2-256 multihash
+    if section.len() >= 34 && section[0] == 0x12 && section[1] == 0x20 {
+        return Ok((34, &section[2..34]));
+    }
+
+    let mut pos = 0;
+    let version = read_varint(section, &mut pos)?;
+    if version != 1 {
+        return Err(anyhow!("Unsupported CID version {}", version));
+    }
+    let _codec = read_varint(section, &mut pos)?;
+    let hash_code = read_varint(section, &mut pos)?;
+    let digest_len = read_varint(section, &mut pos)? as usize;
+    if hash_code != SHA2_256_CODE || digest_len != 32 {
+        return Err(anyhow!("Unsupported multihash 0x{:x}", hash_code));
+    }
+    let digest = take(section, &mut pos, digest_len)?;
+    Ok((pos, digest))
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*pos).ok_or_else(|| anyhow!("Truncated varint"))?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(anyhow!("Varint too long"))
+}
+
+fn take<'a>(bytes: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+    let end = pos.checked_add(len).filter(|end| *end <= bytes.len())
+        .ok_or_else(|| anyhow!("Truncated CAR data"))?;
+    let slice = &bytes[*pos..end];
+    *pos = end;
+    Ok(slice)
+}
+
+fn cbor_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    match value {
+        0..=23 => out.push(major | value as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, value as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(value as u16).to_be_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
+
+fn cbor_text(out: &mut Vec<u8>, text: &str) {
+    cbor_head(out, 3, text.len() as u64);
+    out.extend_from_slice(text.as_bytes());
+}
+
+/// The subset of CBOR that appears in CAR headers
+enum Cbor {
+    Uint(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Tag(u64, Box<Cbor>),
+    Other,
+}
+
+fn decode_cbor(bytes: &[u8], pos: &mut usize, depth: usize) -> Result<Cbor> {
+    if depth > 16 {
+        return Err(anyhow!("CAR header nested too deeply"));
+    }
+
+    let initial = *bytes.get(*pos).ok_or_else(|| anyhow!("Truncated CAR header"))?;
+    *pos += 1;
+    let (major, info) = (initial >> 5, initial & 0x1f);
+    let value = match info {
+        0..=23 => info as u64,
+        24 => take(bytes, pos, 1)?[0] as u64,
+        25 => u16::from_be_bytes(take(bytes, pos, 2)?.try_into()?) as u64,
+        26 => u32::from_be_bytes(take(bytes, pos, 4)?.try_into()?) as u64,
+        27 => u64::from_be_bytes(take(bytes, pos, 8)?.try_into()?),
+        _ => return Err(anyhow!("Indefinite-length CBOR is not allowed in CAR headers")),
+    };
+
+    Ok(match major {
+        0 => Cbor::Uint(value),
+        2 => Cbor::Bytes(take(bytes, pos, value as usize)?.to_vec()),
+        3 => Cbor::Text(String::from_utf8(take(bytes, pos, value as usize)?.to_vec())?),
+        4 => {
+            let mut items = Vec::new();
+            for _ in 0..value {
+                items.push(decode_cbor(bytes, pos, depth + 1)?);
+            }
+            Cbor::Array(items)
+        }
+        5 => {
+            let mut entries = Vec::new();
+            for _ in 0..value {
+                let key = decode_cbor(bytes, pos, depth + 1)?;
+                entries.push((key, decode_cbor(bytes, pos, depth + 1)?));
+            }
+            Cbor::Map(entries)
+        }
+        6 => Cbor::Tag(value, Box::new(decode_cbor(bytes, pos, depth + 1)?)),
+        _ => Cbor::Other,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// CIDv1, raw codec, sha2-256
+    fn raw_cid(data: &[u8]) -> Vec<u8> {
+        let mut cid = vec![0x01, 0x55, 0x12, 0x20];
+        cid.extend_from_slice(&Sha256::digest(data));
+        cid
+    }
+
+    fn block(data: &[u8]) -> CarBlock {
+        CarBlock { cid: raw_cid(data), data: data.to_vec() }
+    }
+
+    #[test]
+    fn round_trip_keeps_roots_and_blocks() {
+        let blocks = vec![block(b"{\"archive\":true}"), block(b"session"), block(&[0u8; 300])];
+        let car = write_car(&[blocks[0].cid.clone()], &blocks);
+
+        let (roots, read) = read_car(&car).unwrap();
+        assert_eq!(roots, vec![blocks[0].cid.clone()]);
+        assert_eq!(read.len(), 3);
+        for (original, read) in blocks.iter().zip(&read) {
+            assert_eq!(original.cid, read.cid);
+            assert_eq!(original.data, read.data);
+        }
+    }
+
+    #[test]
+    fn header_matches_the_carv1_layout() {
+        let root = raw_cid(b"root");
+        let car = write_car(std::slice::from_ref(&root), &[]);
+
+        // varint length, then {"roots": [tag 42 (0x00 ++ cid)], "version": 1}
+        let mut expected = vec![0xa2, 0x65];
+        expected.extend_from_slice(b"roots");
+        expected.extend_from_slice(&[0x81, 0xd8, 0x2a, 0x58, 37, 0x00]);
+        expected.extend_from_slice(&root);
+        expected.push(0x67);
+        expected.extend_from_slice(b"version");
+        expected.push(0x01);
+        assert_eq!(car[0] as usize, expected.len());
+        assert_eq!(&car[1..], expected.as_slice());
+    }
+
+    #[test]
+    fn accepts_cidv0_blocks() {
+        let data = b"legacy block";
+        let mut cid = vec![0x12, 0x20];
+        cid.extend_from_slice(&Sha256::digest(data));
+        let car = write_car(std::slice::from_ref(&cid), &[CarBlock { cid: cid.clone(), data: data.to_vec() }]);
+
+        let (_, blocks) = read_car(&car).unwrap();
+        assert_eq!(blocks[0].cid, cid);
+        assert_eq!(blocks[0].data, data);
+    }
+
+    #[test]
+    fn rejects_tampered_and_truncated_files() {
+        let blocks = vec![block(b"first"), block(b"second")];
+        let car = write_car(&[blocks[0].cid.clone()], &blocks);
+
+        let mut tampered = car.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(read_car(&tampered).is_err());
+
+        assert!(read_car(&car[..car.len() - 3]).is_err());
+        assert!(read_car(&[]).is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_versions_and_hashes() {
+        let mut header = Vec::new();
+        cbor_head(&mut header, 5, 2);
+        cbor_text(&mut header, "roots");
+        cbor_head(&mut header, 4, 0);
+        cbor_text(&mut header, "version");
+        cbor_head(&mut header, 0, 2);
+        let mut car = Vec::new();
+        write_varint(&mut car, header.len() as u64);
+        car.extend_from_slice(&header);
+        assert!(read_car(&car).is_err());
+
+        // sha3-256 (0x16) multihash
+        let mut cid = vec![0x01, 0x55, 0x16, 0x20];
+        cid.extend_from_slice(&[7u8; 32]);
+        let car = write_car(&[], &[CarBlock { cid, data: b"data".to_vec() }]);
+        assert!(read_car(&car).is_err());
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for value in [0, 1, 127, 128, 300, 16_384, u32::MAX as u64, u64::MAX] {
+            let mut out = Vec::new();
+            write_varint(&mut out, value);
+            let mut pos = 0;
+            assert_eq!(read_varint(&out, &mut pos).unwrap(), value);
+            assert_eq!(pos, out.len());
+        }
+    }
+}
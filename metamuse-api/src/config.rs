@@ -12,6 +12,91 @@ pub struct Config {
     pub humor_model_url: String,
     pub model_cache_dir: String,
     
+    // Model Registry Configuration
+    pub default_model_path: String,
+    pub model_memory_budget_mb: u64,
+    pub trait_model_routing: bool,
+    
+    // Model Download Configuration
+    pub model_manifest_path: String,
+    pub model_allow_unverified: bool,
+    pub model_download_min_free_mb: u64,
+    pub model_auto_download: bool,
+    
+    // AI Worker Pool Configuration
+    pub ai_worker_binary_path: Option<String>,
+    pub ai_worker_pool_size: usize,
+    pub ai_worker_request_timeout_secs: u64,
+    pub ai_worker_startup_timeout_secs: u64,
+    pub ai_worker_health_check_interval_secs: u64,
+    pub ai_worker_max_requests: usize,
+    
+    // Embedding Model Configuration
+    pub embedding_model_path: String,
+    pub embedding_dimension: usize,
+    pub embedding_batch_size: usize,
+    pub embedding_workers: usize,
+    pub embedding_allow_fallback: bool,
+    
+    // Reranker Configuration
+    pub rerank_model_path: String,
+    pub rerank_pair_template: String,
+    pub rerank_top_n: usize,
+    
+    // Memory Retention Configuration
+    pub memory_retention_interval_secs: u64,
+    pub memory_ttl_high_hours: u64,
+    pub memory_ttl_medium_hours: u64,
+    pub memory_ttl_low_hours: u64,
+    pub memory_ttl_temporary_hours: u64,
+    pub memory_importance_half_life_days: u64,
+    pub memory_access_reinforcement: f32,
+    pub memory_min_importance: f32,
+    
+    // Memory Consolidation Configuration
+    pub memory_consolidation_interval_secs: u64,
+    pub memory_consolidation_similarity: f32,
+    pub memory_consolidation_min_cluster_size: usize,
+    pub memory_consolidation_max_cluster_size: usize,
+    pub memory_consolidation_min_age_hours: u64,
+    pub memory_consolidation_demotion: f32,
+    
+    // Chat History Compression Configuration
+    pub chat_compression_ratio: f32,
+    pub chat_segment_token_budget: usize,
+    
+    // Context Window Configuration
+    pub context_window_tokens: usize,
+    pub context_response_reserve_tokens: usize,
+    pub context_min_recent_turns: usize,
+    pub context_memory_limit: usize,
+    
+    // Group Chat Configuration
+    pub group_chat_max_participants: usize,
+    pub group_chat_max_speakers_per_turn: usize,
+    
+    // Agent Workflow Configuration
+    pub agent_workflow_max_turns: usize,
+    pub agent_workflow_repetition_threshold: f32,
+    pub agent_workflow_max_running_per_user: usize,
+    
+    // Tool Calling Configuration
+    pub tools_enabled: bool,
+    pub tool_max_calls_per_turn: usize,
+    pub tool_result_max_chars: usize,
+    
+    // Personality Blending Configuration
+    pub blend_mode: String,
+    pub blend_candidates: usize,
+    
+    // Chain-of-Thought Configuration
+    pub cot_reasoning_enabled: bool,
+    pub cot_max_tokens: usize,
+    
+    // Vector Index Configuration
+    pub vector_index_dir: String,
+    pub vector_index_snapshot_interval_secs: u64,
+    
     // Blockchain Configuration
     pub signing_key: String,
     pub ethereum_rpc_url: String,
@@ -24,18 +109,43 @@ pub struct Config {
     pub interaction_dat_contract_address: String,
     pub training_data_dat_contract_address: String,
     pub block_explorer_url: String,
+    pub enforce_interaction_permissions: bool,
+    pub permission_cache_ttl_secs: u64,
+    
+    // Authentication Configuration (Sign-In with Ethereum)
+    pub siwe_domain: String,
+    pub auth_nonce_ttl_secs: u64,
+    pub auth_max_outstanding_nonces: usize,
+    pub auth_session_ttl_secs: u64,
+    pub admin_addresses: Vec<String>,
     
-    // Storage Configuration (Pinata IPFS)
+    // Storage Configuration (local directory, Kubo or Pinata IPFS)
+    pub storage_backend: Option<String>,
+    pub local_storage_dir: String,
+    pub kubo_api_url: String,
     pub ipfs_api_key: Option<String>,
     pub ipfs_api_secret: Option<String>,
     pub ipfs_jwt_token: Option<String>,
     pub ipfs_gateway_url: String,
+    pub require_storage_encryption: bool,
     pub database_url: Option<String>,
 }
 
 impl Config {
     pub fn from_env() -> Result<Self> {
-        Ok(Config {
+        let signing_key = env::var("SIGNING_KEY")
+            .map_err(|_| anyhow::anyhow!("SIGNING_KEY not set"))?;
+        Ok(Self::with_signing_key(signing_key))
+    }
+
+    /// Settings from the environment, or their defaults, for a throwaway signing key
+    #[cfg(test)]
+    pub fn for_tests() -> Self {
+        Self::with_signing_key("11".repeat(32))
+    }
+
+    fn with_signing_key(signing_key: String) -> Self {
+        Config {
             // GGUF Model Configuration
             base_model_url: env::var("BASE_MODEL_URL")
                 .unwrap_or_else(|_| "https://huggingface.co/Qwen/Qwen2.5-14B-Instruct-GGUF/resolve/main/qwen2.5-14b-instruct-q8_0.gguf".to_string()),
@@ -50,9 +160,235 @@ impl Config {
             model_cache_dir: env::var("MODEL_CACHE_DIR")
                 .unwrap_or_else(|_| "./models".to_string()),
                 
+            // Model Registry Configuration
+            default_model_path: env::var("DEFAULT_MODEL_PATH")
+                .unwrap_or_else(|_| "./models/qwen2.5-1.5b-instruct-q5_k_m.gguf".to_string()),
+            model_memory_budget_mb: env::var("MODEL_MEMORY_BUDGET_MB")
+                .unwrap_or_else(|_| "16384".to_string())
+                .parse()
+                .unwrap_or(16384),
+            trait_model_routing: env::var("TRAIT_MODEL_ROUTING")
+                .map(|v| v != "false" && v != "0")
+                .unwrap_or(true),
+                
+            // Model Download Configuration
+            model_manifest_path: env::var("MODEL_MANIFEST_PATH")
+                .unwrap_or_else(|_| "./models/manifest.json".to_string()),
+            model_allow_unverified: env::var("MODEL_ALLOW_UNVERIFIED")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            model_download_min_free_mb: env::var("MODEL_DOWNLOAD_MIN_FREE_MB")
+                .unwrap_or_else(|_| "1024".to_string())
+                .parse()
+                .unwrap_or(1024),
+            model_auto_download: env::var("MODEL_AUTO_DOWNLOAD")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+                
+            // AI Worker Pool Configuration
+            ai_worker_binary_path: env::var("AI_WORKER_BINARY_PATH").ok(),
+            ai_worker_pool_size: env::var("AI_WORKER_POOL_SIZE")
+                .unwrap_or_else(|_| "2".to_string())
+                .parse()
+                .unwrap_or(2),
+            ai_worker_request_timeout_secs: env::var("AI_WORKER_REQUEST_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "120".to_string())
+                .parse()
+                .unwrap_or(120),
+            ai_worker_startup_timeout_secs: env::var("AI_WORKER_STARTUP_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "180".to_string())
+                .parse()
+                .unwrap_or(180),
+            ai_worker_health_check_interval_secs: env::var("AI_WORKER_HEALTH_CHECK_INTERVAL_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            ai_worker_max_requests: env::var("AI_WORKER_MAX_REQUESTS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .unwrap_or(0),
+                
+            // Embedding Model Configuration
+            embedding_model_path: env::var("EMBEDDING_MODEL_PATH")
+                .unwrap_or_else(|_| "./models/all-MiniLM-L6-v2.Q8_0.gguf".to_string()),
+            embedding_dimension: env::var("EMBEDDING_DIMENSION")
+                .unwrap_or_else(|_| "384".to_string())
+                .parse()
+                .unwrap_or(384),
+            embedding_batch_size: env::var("EMBEDDING_BATCH_SIZE")
+                .unwrap_or_else(|_| "32".to_string())
+                .parse()
+                .unwrap_or(32),
+            embedding_workers: env::var("EMBEDDING_WORKERS")
+                .unwrap_or_else(|_| "1".to_string())
+                .parse()
+                .unwrap_or(1),
+            embedding_allow_fallback: env::var("EMBEDDING_ALLOW_FALLBACK")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+                
+            // Reranker Configuration
+            rerank_model_path: env::var("RERANK_MODEL_PATH")
+                .unwrap_or_else(|_| "./models/bge-reranker-v2-m3.Q8_0.gguf".to_string()),
+            rerank_pair_template: env::var("RERANK_PAIR_TEMPLATE")
+                .unwrap_or_else(|_| "{query}</s></s>{document}".to_string()),
+            rerank_top_n: env::var("RERANK_TOP_N")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse()
+                .unwrap_or(20),
+                
+            // Memory Retention Configuration
+            memory_retention_interval_secs: env::var("MEMORY_RETENTION_INTERVAL_SECS")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()
+                .unwrap_or(3600),
+            memory_ttl_high_hours: env::var("MEMORY_TTL_HIGH_HOURS")
+                .unwrap_or_else(|_| "4320".to_string())
+                .parse()
+                .unwrap_or(4320),
+            memory_ttl_medium_hours: env::var("MEMORY_TTL_MEDIUM_HOURS")
+                .unwrap_or_else(|_| "720".to_string())
+                .parse()
+                .unwrap_or(720),
+            memory_ttl_low_hours: env::var("MEMORY_TTL_LOW_HOURS")
+                .unwrap_or_else(|_| "168".to_string())
+                .parse()
+                .unwrap_or(168),
+            memory_ttl_temporary_hours: env::var("MEMORY_TTL_TEMPORARY_HOURS")
+                .unwrap_or_else(|_| "24".to_string())
+                .parse()
+                .unwrap_or(24),
+            memory_importance_half_life_days: env::var("MEMORY_IMPORTANCE_HALF_LIFE_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            memory_access_reinforcement: env::var("MEMORY_ACCESS_REINFORCEMENT")
+                .unwrap_or_else(|_| "0.1".to_string())
+                .parse()
+                .unwrap_or(0.1),
+            memory_min_importance: env::var("MEMORY_MIN_IMPORTANCE")
+                .unwrap_or_else(|_| "0.05".to_string())
+                .parse()
+                .unwrap_or(0.05),
+                
+            // Memory Consolidation Configuration
+            memory_consolidation_interval_secs: env::var("MEMORY_CONSOLIDATION_INTERVAL_SECS")
+                .unwrap_or_else(|_| "21600".to_string())
+                .parse()
+                .unwrap_or(21600),
+            memory_consolidation_similarity: env::var("MEMORY_CONSOLIDATION_SIMILARITY")
+                .unwrap_or_else(|_| "0.75".to_string())
+                .parse()
+                .unwrap_or(0.75),
+            memory_consolidation_min_cluster_size: env::var("MEMORY_CONSOLIDATION_MIN_CLUSTER_SIZE")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .unwrap_or(3),
+            memory_consolidation_max_cluster_size: env::var("MEMORY_CONSOLIDATION_MAX_CLUSTER_SIZE")
+                .unwrap_or_else(|_| "12".to_string())
+                .parse()
+                .unwrap_or(12),
+            memory_consolidation_min_age_hours: env::var("MEMORY_CONSOLIDATION_MIN_AGE_HOURS")
+                .unwrap_or_else(|_| "24".to_string())
+                .parse()
+                .unwrap_or(24),
+            memory_consolidation_demotion: env::var("MEMORY_CONSOLIDATION_DEMOTION")
+                .unwrap_or_else(|_| "0.5".to_string())
+                .parse()
+                .unwrap_or(0.5),
+                
+            // Chat History Compression Configuration
+            chat_compression_ratio: env::var("CHAT_COMPRESSION_RATIO")
+                .unwrap_or_else(|_| "0.2".to_string())
+                .parse()
+                .unwrap_or(0.2),
+            chat_segment_token_budget: env::var("CHAT_SEGMENT_TOKEN_BUDGET")
+                .unwrap_or_else(|_| "2000".to_string())
+                .parse()
+                .unwrap_or(2000),
+                
+            // Context Window Configuration
+            context_window_tokens: env::var("CONTEXT_WINDOW_TOKENS")
+                .unwrap_or_else(|_| "4096".to_string())
+                .parse()
+                .unwrap_or(4096),
+            context_response_reserve_tokens: env::var("CONTEXT_RESPONSE_RESERVE_TOKENS")
+                .unwrap_or_else(|_| "512".to_string())
+                .parse()
+                .unwrap_or(512),
+            context_min_recent_turns: env::var("CONTEXT_MIN_RECENT_TURNS")
+                .unwrap_or_else(|_| "4".to_string())
+                .parse()
+                .unwrap_or(4),
+            context_memory_limit: env::var("CONTEXT_MEMORY_LIMIT")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .unwrap_or(5),
+                
+            // Group Chat Configuration
+            group_chat_max_participants: env::var("GROUP_CHAT_MAX_PARTICIPANTS")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .unwrap_or(5),
+            group_chat_max_speakers_per_turn: env::var("GROUP_CHAT_MAX_SPEAKERS_PER_TURN")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .unwrap_or(3),
+                
+            // Agent Workflow Configuration
+            agent_workflow_max_turns: env::var("AGENT_WORKFLOW_MAX_TURNS")
+                .unwrap_or_else(|_| "20".to_string())
+                .parse()
+                .unwrap_or(20),
+            agent_workflow_repetition_threshold: env::var("AGENT_WORKFLOW_REPETITION_THRESHOLD")
+                .unwrap_or_else(|_| "0.85".to_string())
+                .parse()
+                .unwrap_or(0.85),
+            agent_workflow_max_running_per_user: env::var("AGENT_WORKFLOW_MAX_RUNNING_PER_USER")
+                .unwrap_or_else(|_| "2".to_string())
+                .parse()
+                .unwrap_or(2),
+                
+            // Tool Calling Configuration
+            tools_enabled: env::var("TOOLS_ENABLED")
+                .map(|v| v != "false" && v != "0")
+                .unwrap_or(true),
+            tool_max_calls_per_turn: env::var("TOOL_MAX_CALLS_PER_TURN")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .unwrap_or(3),
+            tool_result_max_chars: env::var("TOOL_RESULT_MAX_CHARS")
+                .unwrap_or_else(|_| "1500".to_string())
+                .parse()
+                .unwrap_or(1500),
+                
+            // Personality Blending Configuration
+            blend_mode: env::var("BLEND_MODE")
+                .unwrap_or_else(|_| "off".to_string()),
+            blend_candidates: env::var("BLEND_CANDIDATES")
+                .unwrap_or_else(|_| "3".to_string())
+                .parse()
+                .unwrap_or(3),
+                
+            // Chain-of-Thought Configuration
+            cot_reasoning_enabled: env::var("COT_REASONING_ENABLED")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
+            cot_max_tokens: env::var("COT_MAX_TOKENS")
+                .unwrap_or_else(|_| "512".to_string())
+                .parse()
+                .unwrap_or(512),
+                
+            // Vector Index Configuration
+            vector_index_dir: env::var("VECTOR_INDEX_DIR")
+                .unwrap_or_else(|_| "./data/index".to_string()),
+            vector_index_snapshot_interval_secs: env::var("VECTOR_INDEX_SNAPSHOT_INTERVAL_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap_or(60),
+                
             // Blockchain Configuration
-            signing_key: env::var("SIGNING_KEY")
-                .map_err(|_| anyhow::anyhow!("SIGNING_KEY not set"))?,
+            signing_key,
             ethereum_rpc_url: env::var("ETHEREUM_RPC_URL")
                 .unwrap_or_else(|_| "https://hyperion-testnet.metisdevops.link".to_string()),
             chain_id: env::var("CHAIN_ID")
@@ -75,14 +411,52 @@ impl Config {
                 .unwrap_or_else(|_| "0x0000000000000000000000000000000000000000".to_string()),
             block_explorer_url: env::var("BLOCK_EXPLORER_URL")
                 .unwrap_or_else(|_| "https://hyperion-testnet-explorer.metisdevops.link".to_string()),
+            enforce_interaction_permissions: env::var("ENFORCE_INTERACTION_PERMISSIONS")
+                .map(|v| v != "false" && v != "0")
+                .unwrap_or(true),
+            permission_cache_ttl_secs: env::var("PERMISSION_CACHE_TTL_SECS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap_or(60),
                 
-            // Storage Configuration (Pinata IPFS)
+            // Authentication Configuration (Sign-In with Ethereum)
+            siwe_domain: env::var("SIWE_DOMAIN")
+                .unwrap_or_else(|_| "localhost:3000".to_string()),
+            auth_nonce_ttl_secs: env::var("AUTH_NONCE_TTL_SECS")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse()
+                .unwrap_or(300),
+            auth_max_outstanding_nonces: env::var("AUTH_MAX_OUTSTANDING_NONCES")
+                .unwrap_or_else(|_| "10000".to_string())
+                .parse()
+                .unwrap_or(10000),
+            auth_session_ttl_secs: env::var("AUTH_SESSION_TTL_SECS")
+                .unwrap_or_else(|_| "86400".to_string())
+                .parse()
+                .unwrap_or(86400),
+            admin_addresses: env::var("ADMIN_ADDRESSES")
+                .unwrap_or_default()
+                .split(',')
+                .map(|address| address.trim().to_lowercase())
+                .filter(|address| !address.is_empty())
+                .collect(),
+                
+            // Storage Configuration (local directory, Kubo or Pinata IPFS)
+            storage_backend: env::var("STORAGE_BACKEND").ok(),
+            local_storage_dir: env::var("LOCAL_STORAGE_DIR")
+                .unwrap_or_else(|_| "./data/ipfs".to_string()),
+            kubo_api_url: env::var("KUBO_API_URL")
+                .unwrap_or_else(|_| "http://127.0.0.1:5001".to_string()),
             ipfs_api_key: env::var("IPFS_API_KEY").ok(),
             ipfs_api_secret: env::var("IPFS_API_SECRET").ok(),
             ipfs_jwt_token: env::var("IPFS_JWT_TOKEN").ok(),
             ipfs_gateway_url: env::var("IPFS_GATEWAY_URL")
                 .unwrap_or_else(|_| "https://gateway.pinata.cloud/ipfs".to_string()),
+            require_storage_encryption: env::var("REQUIRE_STORAGE_ENCRYPTION")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .unwrap_or(true),
             database_url: env::var("DATABASE_URL").ok(),
-        })
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,608 @@
+use anyhow::{anyhow, Result};
+use alith::core::chat::Message;
+use std::sync::Arc;
+
+use crate::ai_worker_pool::AIWorkerPool;
+use crate::config::Config;
+use crate::ipfs_chat_history::{IPFSChatMessage, IPFSChatSession};
+
+/// Characters per token assumed when no tokenizer is available
+const ESTIMATED_CHARS_PER_TOKEN: usize = 4;
+/// Chat template overhead assumed when no tokenizer is available
+const ESTIMATED_TEMPLATE_TOKENS: usize = 16;
+/// Recounts allowed when the whole prompt tokenizes longer than the sum of its parts
+const MAX_REPACKS: usize = 3;
+
+const MEMORY_HEADER: &str = "\n\nRelevant Memories:\n";
+const HISTORY_HEADER: &str = "\n\nConversation History:\n";
+
+/// Token counts for one batch of texts
+#[derive(Debug, Clone)]
+pub struct TokenCount {
+    pub texts: Vec<usize>,
+    /// The whole prompt as the model sees it, chat template included
+    pub prompt: Option<usize>,
+    pub template_tokens: usize,
+    pub context_size: usize,
+    /// False when the counts were estimated from characters
+    pub exact: bool,
+}
+
+/// Counts tokens with the loaded GGUF model's tokenizer through the ai-worker pool,
+/// estimating from characters when no worker is available
+pub struct TokenCounter {
+    worker_pool: Option<Arc<AIWorkerPool>>,
+    fallback_context_size: usize,
+}
+
+impl TokenCounter {
+    pub fn new(worker_pool: Option<Arc<AIWorkerPool>>, config: &Config) -> Self {
+        Self {
+            worker_pool,
+            fallback_context_size: config.context_window_tokens.max(1),
+        }
+    }
+
+    /// Count each text and, when given, the whole prompt
+    pub async fn count(&self, prompt: Option<&str>, texts: Vec<String>) -> TokenCount {
+        if let Some(worker_pool) = &self.worker_pool {
+            match worker_pool.tokenize(prompt.unwrap_or_default(), texts.clone()).await {
+                Ok(result) => {
+                    return TokenCount {
+                        texts: result.counts,
+                        prompt: result.prompt_tokens,
+                        template_tokens: result.template_tokens,
+                        context_size: result.context_size,
+                        exact: true,
+                    };
+                }
+                Err(e) => println!("⚠️  Tokenizer unavailable, estimating token counts: {}", e),
+            }
+        }
+
+        TokenCount {
+            texts: texts.iter().map(|text| estimate_tokens(text)).collect(),
+            prompt: prompt.map(|prompt| estimate_tokens(prompt) + ESTIMATED_TEMPLATE_TOKENS),
+            template_tokens: ESTIMATED_TEMPLATE_TOKENS,
+            context_size: self.fallback_context_size,
+            exact: false,
+        }
+    }
+
+    /// Tokens of the whole prompt as the model sees it, chat template included
+    pub async fn count_prompt(&self, prompt: &str) -> usize {
+        let count = self.count(Some(prompt), Vec::new()).await;
+        count.prompt.unwrap_or_else(|| estimate_tokens(prompt) + count.template_tokens)
+    }
+
+    /// Tokens of `text` from the model's tokenizer, `None` when only an estimate is available
+    pub async fn count_exact(&self, text: &str) -> Option<usize> {
+        let count = self.count(None, vec![text.to_string()]).await;
+        count.texts.first().copied().filter(|_| count.exact)
+    }
+}
+
+/// Everything that may go into one chat prompt
+pub struct ContextRequest<'a> {
+    pub system_prompt: String,
+    /// Applied prompt template layered on top of the personality
+    pub template: Option<String>,
+    /// Retrieved memories, most relevant first
+    pub memories: Vec<String>,
+    /// Session supplying compressed segments and recent turns
+    pub session: Option<&'a IPFSChatSession>,
+    /// Message the history ends at; the session's selected branch when None
+    pub leaf_id: Option<&'a str>,
+    /// Empty when muses converse among themselves; the speaker then just takes its turn
+    pub user_message: &'a str,
+    /// Name the reply is prompted under; "Muse" when None
+    pub speaker_label: Option<String>,
+}
+
+/// A prompt packed into the model's context window
+#[derive(Debug, Clone)]
+pub struct AssembledContext {
+    pub prompt: String,
+    /// Summaries and turns that made it into the prompt, oldest first
+    pub history: Vec<Message>,
+    pub memories_used: Vec<String>,
+    pub prompt_tokens: usize,
+    /// Whether the token counts came from the model's tokenizer
+    pub exact: bool,
+    pub context_size: usize,
+    /// Room left for the reply
+    pub max_response_tokens: usize,
+    /// Sections left out for lack of room
+    pub dropped: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SectionKind {
+    Template,
+    Memory,
+    Summary,
+    Turn,
+}
+
+struct Section {
+    kind: SectionKind,
+    /// Exactly what is rendered into the prompt
+    text: String,
+    /// Chronological position for summaries and turns
+    position: usize,
+    role: String,
+    content: String,
+    tokens: usize,
+}
+
+/// Packs system prompt, template, memories, conversation summaries and recent turns into
+/// the model's context window. System prompt and user message always go in; then the
+/// template, the most recent turns, memories, older turns and summaries, in that order.
+pub struct ContextBuilder {
+    token_counter: TokenCounter,
+    response_reserve: usize,
+    min_recent_turns: usize,
+}
+
+impl ContextBuilder {
+    pub fn new(token_counter: TokenCounter, config: &Config) -> Self {
+        Self {
+            token_counter,
+            response_reserve: config.context_response_reserve_tokens,
+            min_recent_turns: config.context_min_recent_turns,
+        }
+    }
+
+    pub fn token_counter(&self) -> &TokenCounter {
+        &self.token_counter
+    }
+
+    pub async fn build(&self, request: ContextRequest<'_>) -> Result<AssembledContext> {
+        let (mut sections, turn_replies) = collect_sections(&request);
+        let mut tail = "\n".to_string();
+        if !request.user_message.is_empty() {
+            tail.push_str(&format!("User: {}\n", request.user_message));
+        }
+        for reply in &turn_replies {
+            tail.push_str(&format!("\n{}\n", reply));
+        }
+        tail.push_str(&format!("\n{}:", request.speaker_label.as_deref().unwrap_or("Muse")));
+
+        let mut texts = vec![request.system_prompt.clone(), tail.clone(), MEMORY_HEADER.to_string(), HISTORY_HEADER.to_string()];
+        texts.extend(sections.iter().map(|section| section.text.clone()));
+        let count = self.token_counter.count(None, texts).await;
+        let [system_tokens, tail_tokens, memory_header_tokens, history_header_tokens] = [0, 1, 2, 3].map(|i| count.texts[i]);
+        for (section, tokens) in sections.iter_mut().zip(&count.texts[4..]) {
+            section.tokens = *tokens;
+        }
+
+        let limit = count.context_size.saturating_sub(self.response_reserve);
+        let budget = limit.saturating_sub(count.template_tokens);
+        let mut used = system_tokens + tail_tokens;
+        if used > budget {
+            return Err(anyhow!(
+                "System prompt and message need {} tokens, but only {} fit in the {}-token context",
+                used, budget, count.context_size
+            ));
+        }
+
+        // Indexes into `sections` in the order they were packed
+        let mut selected: Vec<usize> = Vec::new();
+        let mut try_add = |index: usize, selected: &mut Vec<usize>| {
+            let kind = sections[index].kind;
+            let first_of_group = match kind {
+                SectionKind::Memory => !selected.iter().any(|&i| sections[i].kind == SectionKind::Memory),
+                SectionKind::Summary | SectionKind::Turn => !selected.iter().any(|&i| matches!(sections[i].kind, SectionKind::Summary | SectionKind::Turn)),
+                SectionKind::Template => false,
+            };
+            let header = match (first_of_group, kind) {
+                (false, _) | (_, SectionKind::Template) => 0,
+                (true, SectionKind::Memory) => memory_header_tokens,
+                (true, _) => history_header_tokens,
+            };
+            let cost = sections[index].tokens + header;
+            if used + cost > budget {
+                return false;
+            }
+            used += cost;
+            selected.push(index);
+            true
+        };
+
+        let of_kind = |kind: SectionKind| -> Vec<usize> {
+            sections.iter().enumerate().filter(|(_, s)| s.kind == kind).map(|(i, _)| i).collect()
+        };
+        let turns_newest_first: Vec<usize> = of_kind(SectionKind::Turn).into_iter().rev().collect();
+
+        for index in of_kind(SectionKind::Template) {
+            try_add(index, &mut selected);
+        }
+        // History must stay contiguous back from the present: turns go in newest first up to the
+        // first that does not fit, and summaries, older than every turn, only once all turns are in
+        let mut next_turn = 0;
+        while next_turn < turns_newest_first.len().min(self.min_recent_turns) && try_add(turns_newest_first[next_turn], &mut selected) {
+            next_turn += 1;
+        }
+        let turns_blocked = next_turn < turns_newest_first.len().min(self.min_recent_turns);
+        for index in of_kind(SectionKind::Memory) {
+            try_add(index, &mut selected);
+        }
+        let mut history_complete = !turns_blocked;
+        if history_complete {
+            for &index in &turns_newest_first[next_turn..] {
+                if !try_add(index, &mut selected) {
+                    history_complete = false;
+                    break;
+                }
+            }
+        }
+        if history_complete {
+            for index in of_kind(SectionKind::Summary).into_iter().rev() {
+                if !try_add(index, &mut selected) {
+                    break;
+                }
+            }
+        }
+
+        // Token boundaries can shift where sections meet, so check the whole prompt and
+        // drop the last-packed sections if it came out longer than the sum of its parts
+        let mut prompt;
+        let mut prompt_tokens;
+        let mut attempt = 0;
+        loop {
+            prompt = render(&request.system_prompt, &sections, &selected, &tail);
+            let prompt_count = self.token_counter.count(Some(&prompt), Vec::new()).await;
+            prompt_tokens = prompt_count.prompt.unwrap_or_else(|| estimate_tokens(&prompt) + count.template_tokens);
+
+            if prompt_tokens <= limit || selected.is_empty() || attempt == MAX_REPACKS {
+                break;
+            }
+            attempt += 1;
+            let mut overshoot = prompt_tokens - limit;
+            while overshoot > 0 {
+                let Some(index) = selected.pop() else { break };
+                overshoot = overshoot.saturating_sub(sections[index].tokens);
+            }
+        }
+
+        let mut history_sections: Vec<&Section> = selected.iter()
+            .map(|&i| &sections[i])
+            .filter(|s| matches!(s.kind, SectionKind::Summary | SectionKind::Turn))
+            .collect();
+        history_sections.sort_by_key(|s| s.position);
+        let history = history_sections.iter()
+            .map(|s| Message { role: s.role.clone(), content: s.content.clone() })
+            .collect();
+        let memories_used = selected.iter()
+            .map(|&i| &sections[i])
+            .filter(|s| s.kind == SectionKind::Memory)
+            .map(|s| s.content.clone())
+            .collect();
+
+        let context = AssembledContext {
+            prompt,
+            history,
+            memories_used,
+            prompt_tokens,
+            exact: count.exact,
+            context_size: count.context_size,
+            max_response_tokens: count.context_size.saturating_sub(prompt_tokens).max(1),
+            dropped: sections.len() - selected.len(),
+        };
+
+        println!("🧮 Packed {} {} prompt tokens into a {}-token context ({} history entries, {} memories, {} sections dropped)",
+                context.prompt_tokens, if context.exact { "exact" } else { "estimated" }, context.context_size,
+                context.history.len(), context.memories_used.len(), context.dropped);
+
+        Ok(context)
+    }
+}
+
+/// Every optional section with its rendered text, in chronological order within each kind,
+/// plus the replies other muses in a group already gave to the current message
+fn collect_sections(request: &ContextRequest<'_>) -> (Vec<Section>, Vec<String>) {
+    let mut sections = Vec::new();
+    let mut turn_replies = Vec::new();
+
+    if let Some(template) = request.template.as_ref().filter(|t| !t.trim().is_empty()) {
+        sections.push(Section {
+            kind: SectionKind::Template,
+            text: format!("\n\nTemplate Instructions:\n{}", template),
+            position: 0,
+            role: "system".to_string(),
+            content: template.clone(),
+            tokens: 0,
+        });
+    }
+
+    for (position, memory) in request.memories.iter().enumerate() {
+        sections.push(Section {
+            kind: SectionKind::Memory,
+            text: format!("- {}\n", memory),
+            position,
+            role: "system".to_string(),
+            content: memory.clone(),
+            tokens: 0,
+        });
+    }
+
+    if let Some(session) = request.session {
+        for (position, segment) in session.compressed_segments.iter().enumerate() {
+            let content = segment.context_summary();
+            sections.push(Section {
+                kind: SectionKind::Summary,
+                text: format!("System: {}\n", content),
+                position,
+                role: "system".to_string(),
+                content,
+                tokens: 0,
+            });
+        }
+
+        let path = match request.leaf_id {
+            Some(leaf_id) => session.branch_path(leaf_id),
+            None => session.active_path(),
+        };
+
+        // The current message is usually stored before the prompt is built; it and any replies
+        // other muses in a group already gave to it go in the tail instead
+        let mut messages = path.as_slice();
+        if let Some(turn_start) = messages.iter().rposition(|m| m.role == "user") {
+            if !request.user_message.is_empty() && messages[turn_start].content == request.user_message {
+                turn_replies = messages[turn_start + 1..]
+                    .iter()
+                    .map(|m| format!("{}: {}", display_role(m), m.content))
+                    .collect();
+                messages = &messages[..turn_start];
+            }
+        }
+
+        let offset = session.compressed_segments.len();
+        for (position, message) in messages.iter().enumerate() {
+            sections.push(Section {
+                kind: SectionKind::Turn,
+                text: format!("{}: {}\n", display_role(message), message.content),
+                position: offset + position,
+                role: message.role.clone(),
+                content: message.content.clone(),
+                tokens: 0,
+            });
+        }
+    }
+
+    (sections, turn_replies)
+}
+
+/// How a message's author is named in the prompt
+fn display_role(message: &IPFSChatMessage) -> String {
+    match (message.role.as_str(), &message.speaker) {
+        ("assistant", Some(speaker)) => format!("Muse #{}", speaker),
+        ("assistant", None) => "Muse".to_string(),
+        ("user", _) => "User".to_string(),
+        ("system", _) => "System".to_string(),
+        (other, _) => other.to_string(),
+    }
+}
+
+fn render(system_prompt: &str, sections: &[Section], selected: &[usize], tail: &str) -> String {
+    let mut chosen: Vec<&Section> = selected.iter().map(|&i| &sections[i]).collect();
+    chosen.sort_by_key(|s| s.position);
+
+    let mut prompt = system_prompt.to_string();
+    for section in chosen.iter().filter(|s| s.kind == SectionKind::Template) {
+        prompt.push_str(&section.text);
+    }
+
+    let mut memories = chosen.iter().filter(|s| s.kind == SectionKind::Memory).peekable();
+    if memories.peek().is_some() {
+        prompt.push_str(MEMORY_HEADER);
+        memories.for_each(|s| prompt.push_str(&s.text));
+    }
+
+    let mut history = chosen.iter().filter(|s| matches!(s.kind, SectionKind::Summary | SectionKind::Turn)).peekable();
+    if history.peek().is_some() {
+        prompt.push_str(HISTORY_HEADER);
+        history.for_each(|s| prompt.push_str(&s.text));
+    }
+
+    prompt.push_str(tail);
+    prompt
+}
+
+fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(ESTIMATED_CHARS_PER_TOKEN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipfs_chat_history::CompressedSegment;
+
+    const SYSTEM_PROMPT: &str = "You are a muse.";
+
+    /// Packs with the character estimate: 4 characters per token, 16 template tokens
+    fn builder(context_size: usize, response_reserve: usize, min_recent_turns: usize) -> ContextBuilder {
+        ContextBuilder {
+            token_counter: TokenCounter { worker_pool: None, fallback_context_size: context_size },
+            response_reserve,
+            min_recent_turns,
+        }
+    }
+
+    /// Turn `i` renders as one 81-character line, 21 estimated tokens
+    fn turn_text(i: usize) -> String {
+        format!("{:<74}", format!("Turn {} of the conversation.", i))
+    }
+
+    fn message(i: usize, role: &str, content: String, speaker: Option<&str>) -> IPFSChatMessage {
+        IPFSChatMessage {
+            id: format!("m{}", i),
+            role: role.to_string(),
+            content,
+            timestamp: i as u64,
+            token_count: 0,
+            importance: 0.5,
+            compressed: false,
+            original_length: None,
+            parent_id: (i > 0).then(|| format!("m{}", i - 1)),
+            speaker: speaker.map(str::to_string),
+            tool_calls: Vec::new(),
+            reasoning: None,
+        }
+    }
+
+    /// Six alternating turns after one summary of earlier conversation
+    fn session() -> IPFSChatSession {
+        let messages: Vec<IPFSChatMessage> = (0..6)
+            .map(|i| message(i, if i % 2 == 0 { "user" } else { "assistant" }, turn_text(i), None))
+            .collect();
+        let segment = CompressedSegment {
+            segment_id: "segment_1".to_string(),
+            start_timestamp: 0,
+            end_timestamp: 60,
+            original_message_count: 12,
+            compressed_summary: "The user talked about a hiking trip and asked for advice on packing light.".to_string(),
+            key_topics: Vec::new(),
+            emotional_context: String::new(),
+            importance_score: 0.5,
+            level: 0,
+        };
+
+        IPFSChatSession {
+            session_id: "session_test".to_string(),
+            muse_id: "1".to_string(),
+            user_address: "0x00000000000000000000000000000000000a11ce".to_string(),
+            created_at: 0,
+            last_updated: 0,
+            message_count: messages.len(),
+            total_tokens_estimate: 0,
+            active_leaf: messages.last().map(|m| m.id.clone()),
+            messages,
+            group: None,
+            compressed_segments: vec![segment],
+            topics: Vec::new(),
+            emotional_tone: None,
+            importance_score: 0.5,
+            ipfs_hash: None,
+            version: 1,
+        }
+    }
+
+    fn request<'a>(session: &'a IPFSChatSession, user_message: &'a str) -> ContextRequest<'a> {
+        ContextRequest {
+            system_prompt: SYSTEM_PROMPT.to_string(),
+            template: Some("Be brief.".to_string()),
+            // 11 estimated tokens each
+            memories: vec![format!("{:<38}", "Likes mountains."), format!("{:<38}", "Lives in Oslo.")],
+            session: Some(session),
+            leaf_id: None,
+            user_message,
+            speaker_label: None,
+        }
+    }
+
+    fn history_turns(context: &AssembledContext) -> Vec<String> {
+        context.history.iter().filter(|m| m.role != "system").map(|m| m.content.clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn everything_that_fits_is_rendered_in_order() {
+        let session = session();
+        let context = builder(4096, 512, 2).build(request(&session, "hello")).await.unwrap();
+
+        let prompt = &context.prompt;
+        let order = [
+            SYSTEM_PROMPT,
+            "\n\nTemplate Instructions:\nBe brief.",
+            MEMORY_HEADER,
+            "- Likes mountains.",
+            HISTORY_HEADER,
+            "System: [Conversation Summary",
+            "User: Turn 0",
+            "Muse: Turn 5",
+            "\nUser: hello\n\nMuse:",
+        ];
+        let positions: Vec<usize> = order.iter().map(|part| prompt.find(part).unwrap()).collect();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]), "{}", prompt);
+        assert!(prompt.ends_with("\nMuse:"));
+
+        assert_eq!(context.history.len(), 7);
+        assert_eq!(context.history[0].role, "system");
+        assert_eq!(context.memories_used.len(), 2);
+        assert_eq!(context.dropped, 0);
+        assert!(!context.exact);
+        assert_eq!(context.max_response_tokens, 4096 - context.prompt_tokens);
+    }
+
+    #[tokio::test]
+    async fn summaries_only_go_in_once_every_turn_fits() {
+        let session = session();
+        // Budget 240 - 40 - 16 = 184: every part but the summary
+        let context = builder(240, 40, 2).build(request(&session, "hello")).await.unwrap();
+
+        assert_eq!(history_turns(&context), (0..6).map(turn_text).collect::<Vec<_>>());
+        assert!(!context.prompt.contains("Conversation Summary"));
+        assert_eq!(context.dropped, 1);
+    }
+
+    #[tokio::test]
+    async fn older_turns_are_dropped_oldest_first_with_the_summaries() {
+        let session = session();
+        // Budget 200 - 40 - 16 = 144: template, memories and the four newest turns
+        let context = builder(200, 40, 2).build(request(&session, "hello")).await.unwrap();
+
+        assert_eq!(history_turns(&context), (2..6).map(turn_text).collect::<Vec<_>>());
+        assert_eq!(context.history.len(), 4);
+        assert!(!context.prompt.contains("Conversation Summary"));
+        assert_eq!(context.memories_used.len(), 2);
+        assert!(context.prompt.contains("Template Instructions"));
+        assert_eq!(context.dropped, 3);
+        assert!(context.prompt_tokens <= 200 - 40);
+    }
+
+    #[tokio::test]
+    async fn recent_turns_outrank_memories() {
+        let session = session();
+        // Budget 120 - 30 - 16 = 74: template and the two most recent turns only
+        let context = builder(120, 30, 2).build(request(&session, "hello")).await.unwrap();
+
+        assert_eq!(history_turns(&context), (4..6).map(turn_text).collect::<Vec<_>>());
+        assert!(context.memories_used.is_empty());
+        assert!(!context.prompt.contains(MEMORY_HEADER));
+        assert!(context.prompt.contains("Template Instructions"));
+    }
+
+    #[tokio::test]
+    async fn stored_message_and_group_replies_go_in_the_tail() {
+        let mut session = session();
+        session.messages.push(message(6, "user", "hello".to_string(), None));
+        session.messages.push(message(7, "assistant", "Hi from seven.".to_string(), Some("7")));
+        session.active_leaf = Some("m7".to_string());
+
+        let mut request = request(&session, "hello");
+        request.speaker_label = Some("Muse #3".to_string());
+        let context = builder(4096, 512, 2).build(request).await.unwrap();
+
+        assert!(context.prompt.ends_with("\nUser: hello\n\nMuse #7: Hi from seven.\n\nMuse #3:"), "{}", context.prompt);
+        assert_eq!(context.prompt.matches("User: hello").count(), 1);
+        assert_eq!(history_turns(&context), (0..6).map(turn_text).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn system_prompt_over_budget_is_an_error() {
+        let request = |system_prompt: String| ContextRequest {
+            system_prompt,
+            template: None,
+            memories: Vec::new(),
+            session: None,
+            leaf_id: None,
+            user_message: "hello",
+            speaker_label: None,
+        };
+
+        // 175 tokens against a budget of 200 - 20 - 16 = 164
+        let error = builder(200, 20, 2).build(request("a".repeat(700))).await.unwrap_err();
+        assert!(error.to_string().contains("System prompt and message need"));
+        assert!(builder(200, 20, 2).build(request(SYSTEM_PROMPT.to_string())).await.is_ok());
+    }
+}
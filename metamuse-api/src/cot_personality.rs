@@ -1,25 +1,48 @@
-use alith::{client::prelude::*, LLM};
 use anyhow::Result;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Mutex;
+use crate::json_grammar;
+use crate::llama_engine_wrapper::LlamaEngineWrapper;
 use crate::muse_orchestrator::MuseTraits;
 use alith::core::chat::Message;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The model's analysis of how each trait shapes a reply. Fields are decoded in this order,
+/// so the per-trait analyses are written before the conclusion and the confidence.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PersonalityReasoning {
+    /// How the creativity level should shape style and originality
     pub creativity_analysis: String,
+    /// How the wisdom level should shape depth and insight
     pub wisdom_analysis: String,
+    /// How the humor level should shape tone
     pub humor_analysis: String,
+    /// How the empathy level should shape emotional understanding
     pub empathy_analysis: String,
+    /// The approach the reply should take, combining the four analyses
     pub final_reasoning: String,
+    /// How sure the model is of this approach, 0-1
     pub confidence_score: f32,
 }
 
+/// ✅ NEW: Reasoning behind one reply, stored with the message for debugging
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CoTPersonalityResponse {
-    pub response: String,
+pub struct ReasoningTrace {
     pub reasoning: PersonalityReasoning,
     pub reasoning_steps: Vec<String>,
     pub traits_influence: TraitsInfluence,
+    pub model_version: String,
+    /// Whether the model was held to the reasoning schema by grammar-constrained decoding
+    pub constrained: bool,
+    pub reasoning_time_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoTPersonalityResponse {
+    pub response: String,
+    pub trace: ReasoningTrace,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,33 +53,59 @@ pub struct TraitsInfluence {
     pub empathy_weight: f32,
 }
 
+impl TraitsInfluence {
+    /// Each trait's share of the muse's total trait points
+    pub fn from_traits(traits: &MuseTraits) -> Self {
+        let total = traits.creativity as u32 + traits.wisdom as u32 + traits.humor as u32 + traits.empathy as u32;
+        if total == 0 {
+            return Self { creativity_weight: 0.25, wisdom_weight: 0.25, humor_weight: 0.25, empathy_weight: 0.25 };
+        }
+        let total = total as f32;
+
+        Self {
+            creativity_weight: traits.creativity as f32 / total,
+            wisdom_weight: traits.wisdom as f32 / total,
+            humor_weight: traits.humor as f32 / total,
+            empathy_weight: traits.empathy as f32 / total,
+        }
+    }
+
+    pub fn weight(&self, trait_name: &str) -> f32 {
+        match trait_name {
+            "creativity" => self.creativity_weight,
+            "wisdom" => self.wisdom_weight,
+            "humor" => self.humor_weight,
+            "empathy" => self.empathy_weight,
+            _ => 0.0,
+        }
+    }
+}
+
 pub struct CoTPersonalityEngine {
-    llm_client: LLM,
+    // Local model, served by ai-workers when grammar-constrained decoding is available
+    engine: Arc<Mutex<LlamaEngineWrapper>>,
+    model_version: String,
+    max_tokens: usize,
 }
 
 impl CoTPersonalityEngine {
-    pub fn new(llm_client: LLM) -> Self {
-        Self { llm_client }
+    pub fn new(engine: Arc<Mutex<LlamaEngineWrapper>>, model_version: String, max_tokens: usize) -> Self {
+        Self { engine, model_version, max_tokens }
     }
 
-    /// Generate response with full Chain of Thought reasoning - BREAKTHROUGH!
-    pub async fn generate_reasoned_response(
-        &self,
-        muse_id: &str,
-        traits: &MuseTraits,
-        user_message: &str,
-        context: Vec<Message>,
-    ) -> Result<CoTPersonalityResponse> {
-        let client = self.llm_client.client();
-
-        // Step 1: Analyze how each trait should influence the response
+    /// Analyse how each of the muse's traits should shape the reply to `user_message`. The
+    /// output is constrained to the `PersonalityReasoning` schema when the engine has ai-workers,
+    /// otherwise the schema is only asked for in the prompt.
+    pub async fn reason(&self, muse_id: &str, traits: &MuseTraits, user_message: &str) -> Result<ReasoningTrace> {
+        let started = Instant::now();
+        let schema = serde_json::to_string(&schemars::schema_for!(PersonalityReasoning))?;
         let reasoning_prompt = format!(
             r#"You are analyzing how a Muse's personality traits should influence their response.
 
 Muse #{} has these traits:
-- Creativity: {}/100 
+- Creativity: {}/100
 - Wisdom: {}/100
-- Humor: {}/100  
+- Humor: {}/100
 - Empathy: {}/100
 
 User message: "{}"
@@ -67,86 +116,190 @@ Analyze step-by-step how EACH trait should influence the response:
 3. Humor Analysis: How should humor level {} affect the tone and jokes?
 4. Empathy Analysis: How should empathy level {} affect emotional understanding?
 5. Final Reasoning: Combine all traits to determine optimal response approach.
+6. Confidence Score: How sure you are of that approach, from 0 to 1.
 
-Provide detailed reasoning for each step."#,
+Answer only with JSON matching this schema:
+{}
+"#,
             muse_id, traits.creativity, traits.wisdom, traits.humor, traits.empathy,
             user_message,
-            traits.creativity, traits.wisdom, traits.humor, traits.empathy
+            traits.creativity, traits.wisdom, traits.humor, traits.empathy,
+            schema
         );
 
-        // 🔥 Use Alith's Chain of Thought reasoning
-        let reasoning_response = client
-            .reason()
-            .exact_string()
-            .set_instructions(&reasoning_prompt)
-            .return_primitive()
-            .await?;
+        let grammar = json_grammar::grammar_for::<PersonalityReasoning>()?;
+        let engine = LlamaEngineWrapper::access(&self.engine).await;
+        let (reasoning_text, constrained) = match engine.generate_with_grammar(&reasoning_prompt, 0.2, self.max_tokens, &grammar).await {
+            Ok(text) => (text, true),
+            Err(e) => {
+                println!("⚠️ Constrained reasoning unavailable ({}), asking for JSON without a grammar", e);
+                (engine.generate(&reasoning_prompt, 0.2, self.max_tokens).await?, false)
+            }
+        };
+        drop(engine);
+
+        let reasoning = Self::parse_reasoning(&reasoning_text)?;
+        let reasoning_steps = vec![
+            format!("Creativity ({}): {}", traits.creativity, reasoning.creativity_analysis),
+            format!("Wisdom ({}): {}", traits.wisdom, reasoning.wisdom_analysis),
+            format!("Humor ({}): {}", traits.humor, reasoning.humor_analysis),
+            format!("Empathy ({}): {}", traits.empathy, reasoning.empathy_analysis),
+            format!("Approach (confidence {:.2}): {}", reasoning.confidence_score, reasoning.final_reasoning),
+        ];
+
+        Ok(ReasoningTrace {
+            reasoning,
+            reasoning_steps,
+            traits_influence: TraitsInfluence::from_traits(traits),
+            model_version: self.model_version.clone(),
+            constrained,
+            reasoning_time_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+
+    /// Reason about the traits first, then generate the reply following that reasoning
+    pub async fn generate_reasoned_response(
+        &self,
+        muse_id: &str,
+        traits: &MuseTraits,
+        user_message: &str,
+        context: Vec<Message>,
+    ) -> Result<CoTPersonalityResponse> {
+        // Step 1: Analyze how each trait should influence the response
+        let trace = self.reason(muse_id, traits, user_message).await?;
 
         // Step 2: Generate actual response using the reasoning
+        let history = context.iter()
+            .map(|message| format!("{}: {}", message.role, message.content))
+            .collect::<Vec<_>>()
+            .join("\n");
         let response_prompt = format!(
-            r#"Based on your reasoning analysis, now generate the actual response as Muse #{}.
-
-Your reasoning: {}
+            r#"You are Muse #{} with creativity {}, wisdom {}, humor {}, and empathy {}.
 
-Conversation context: {:?}
+How your traits should shape this reply: {}
 
-User message: "{}"
+Conversation so far:
+{}
 
-Generate a response that perfectly reflects the personality analysis you just completed.
-The response should demonstrate creativity {}, wisdom {}, humor {}, and empathy {}."#,
-            muse_id, reasoning_response, context, user_message,
-            traits.creativity, traits.wisdom, traits.humor, traits.empathy
+User: {}
+Muse:"#,
+            muse_id, traits.creativity, traits.wisdom, traits.humor, traits.empathy,
+            trace.reasoning.final_reasoning, history, user_message
         );
 
-        let final_response = client
-            .reason()
-            .exact_string()
-            .set_instructions(&response_prompt)
-            .return_primitive()
+        let temperature = (traits.creativity as f32) / 100.0 * 0.8;
+        let response = LlamaEngineWrapper::access(&self.engine).await
+            .generate(&response_prompt, temperature, self.max_tokens)
             .await?;
 
-        // Parse reasoning into structured format
-        let reasoning = self.parse_reasoning(&reasoning_response, traits)?;
-        let traits_influence = self.calculate_traits_influence(traits);
+        Ok(CoTPersonalityResponse { response, trace })
+    }
+
+    /// Structured reasoning from the model's JSON output. Unconstrained output may wrap the
+    /// object in prose, so only the outermost braces are parsed.
+    fn parse_reasoning(reasoning_text: &str) -> Result<PersonalityReasoning> {
+        let start = reasoning_text.find('{')
+            .ok_or_else(|| anyhow::anyhow!("Reasoning output has no JSON object"))?;
+        let end = reasoning_text.rfind('}')
+            .filter(|end| *end > start)
+            .ok_or_else(|| anyhow::anyhow!("Reasoning output has no JSON object"))?;
+        let mut reasoning: PersonalityReasoning = serde_json::from_str(&reasoning_text[start..=end])
+            .map_err(|e| anyhow::anyhow!("Reasoning output does not match the schema: {}", e))?;
 
-        Ok(CoTPersonalityResponse {
-            response: final_response,
-            reasoning,
-            reasoning_steps: vec![
-                format!("Analyzed creativity impact ({}%)", traits.creativity),
-                format!("Analyzed wisdom impact ({}%)", traits.wisdom),
-                format!("Analyzed humor impact ({}%)", traits.humor),
-                format!("Analyzed empathy impact ({}%)", traits.empathy),
-                "Combined all traits for final response".to_string(),
-            ],
-            traits_influence,
-        })
+        let analyses = [
+            &reasoning.creativity_analysis,
+            &reasoning.wisdom_analysis,
+            &reasoning.humor_analysis,
+            &reasoning.empathy_analysis,
+            &reasoning.final_reasoning,
+        ];
+        if analyses.iter().any(|analysis| analysis.trim().is_empty()) {
+            return Err(anyhow::anyhow!("Reasoning output left an analysis empty"));
+        }
+        if !reasoning.confidence_score.is_finite() {
+            return Err(anyhow::anyhow!("Reasoning output has an invalid confidence score"));
+        }
+        // The grammar cannot bound numbers, and some models answer in percent
+        if reasoning.confidence_score > 1.0 {
+            reasoning.confidence_score /= 100.0;
+        }
+        reasoning.confidence_score = reasoning.confidence_score.clamp(0.0, 1.0);
+
+        Ok(reasoning)
     }
+}
 
-    fn parse_reasoning(
-        &self,
-        reasoning_text: &str,
-        traits: &MuseTraits,
-    ) -> Result<PersonalityReasoning> {
-        // Parse the structured reasoning response
-        Ok(PersonalityReasoning {
-            creativity_analysis: format!("Creativity ({}%) influences response innovation and originality", traits.creativity),
-            wisdom_analysis: format!("Wisdom ({}%) influences response depth and insight", traits.wisdom),
-            humor_analysis: format!("Humor ({}%) influences response tone and lightness", traits.humor),
-            empathy_analysis: format!("Empathy ({}%) influences emotional understanding", traits.empathy),
-            final_reasoning: "Combined all personality traits for balanced response".to_string(),
-            confidence_score: 0.85,
-        })
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reasoning_json(confidence: &str) -> String {
+        format!(
+            r#"{{"creativity_analysis": "Use a vivid image", "wisdom_analysis": "Offer one insight",
+"humor_analysis": "Keep it light", "empathy_analysis": "Acknowledge the worry",
+"final_reasoning": "A warm, playful answer", "confidence_score": {}}}"#,
+            confidence
+        )
     }
 
-    fn calculate_traits_influence(&self, traits: &MuseTraits) -> TraitsInfluence {
-        let total = traits.creativity + traits.wisdom + traits.humor + traits.empathy;
-        
-        TraitsInfluence {
-            creativity_weight: traits.creativity as f32 / total as f32,
-            wisdom_weight: traits.wisdom as f32 / total as f32,
-            humor_weight: traits.humor as f32 / total as f32,
-            empathy_weight: traits.empathy as f32 / total as f32,
-        }
+    #[test]
+    fn valid_reasoning_is_parsed() {
+        let reasoning = CoTPersonalityEngine::parse_reasoning(&reasoning_json("0.7")).unwrap();
+
+        assert_eq!(reasoning.creativity_analysis, "Use a vivid image");
+        assert_eq!(reasoning.final_reasoning, "A warm, playful answer");
+        assert!((reasoning.confidence_score - 0.7).abs() < 1e-6);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn reasoning_wrapped_in_prose_is_parsed() {
+        let text = format!("Here is my analysis:\n{}\nHope that helps.", reasoning_json("0.5"));
+
+        assert!(CoTPersonalityEngine::parse_reasoning(&text).is_ok());
+    }
+
+    #[test]
+    fn confidence_is_normalised_into_the_unit_range() {
+        let percent = CoTPersonalityEngine::parse_reasoning(&reasoning_json("85")).unwrap();
+        assert!((percent.confidence_score - 0.85).abs() < 1e-6);
+
+        let negative = CoTPersonalityEngine::parse_reasoning(&reasoning_json("-0.3")).unwrap();
+        assert_eq!(negative.confidence_score, 0.0);
+    }
+
+    #[test]
+    fn truncated_reasoning_is_rejected() {
+        let full = reasoning_json("0.7");
+        let truncated = &full[..full.find("final_reasoning").unwrap()];
+
+        assert!(CoTPersonalityEngine::parse_reasoning(truncated).is_err());
+        assert!(CoTPersonalityEngine::parse_reasoning("").is_err());
+    }
+
+    #[test]
+    fn reasoning_missing_a_trait_is_rejected() {
+        let missing = reasoning_json("0.7").replace(r#""humor_analysis": "Keep it light", "#, "");
+
+        assert!(CoTPersonalityEngine::parse_reasoning(&missing).is_err());
+    }
+
+    #[test]
+    fn reasoning_with_an_empty_analysis_is_rejected() {
+        let empty = reasoning_json("0.7").replace("Offer one insight", "  ");
+
+        assert!(CoTPersonalityEngine::parse_reasoning(&empty).is_err());
+    }
+
+    #[test]
+    fn trait_weights_are_shares_of_the_total() {
+        let traits = MuseTraits { creativity: 60, wisdom: 20, humor: 20, empathy: 0 };
+        let influence = TraitsInfluence::from_traits(&traits);
+
+        assert!((influence.weight("creativity") - 0.6).abs() < 1e-6);
+        assert_eq!(influence.weight("empathy"), 0.0);
+        assert_eq!(influence.weight("unknown"), 0.0);
+
+        let flat = TraitsInfluence::from_traits(&MuseTraits { creativity: 0, wisdom: 0, humor: 0, empathy: 0 });
+        assert_eq!(flat.weight("wisdom"), 0.25);
+    }
+}
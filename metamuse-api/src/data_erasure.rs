@@ -0,0 +1,454 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+
+use crate::avatar_system::AvatarManager;
+use crate::ipfs_chat_history::IPFSChatHistoryManager;
+use crate::memory_consolidation::MemoryConsolidator;
+use crate::persist_memory::MemorySystem;
+use crate::semantic_search::SemanticSearchService;
+use crate::storage_backend::StorageBackend;
+use crate::training_data_market::TrainingDataMarketplace;
+use crate::verification::{recover_personal_sign_address, VerificationSystem};
+
+/// What was removed from one subsystem
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ErasureCounts {
+    pub erased: usize,
+    pub unpinned: usize,
+    pub unpin_failures: usize,
+}
+
+/// Per-subsystem outcome of an erasure. Only fixed-order fields, so the signed
+/// digest can be recomputed from the JSON receipt.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ErasureSummary {
+    pub chat_sessions: ErasureCounts,
+    pub memories: ErasureCounts,
+    pub semantic_entries: ErasureCounts,
+    pub training_contributions: ErasureCounts,
+    pub avatars: ErasureCounts,
+    pub avatar_collections: usize,
+    /// Validated contributions kept because they were rewarded on-chain
+    pub retained_contributions: Vec<String>,
+    /// Data deliberately kept, with the reason
+    pub retained: Vec<String>,
+}
+
+/// Signed record of a completed erasure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErasureReceipt {
+    pub receipt_id: String,
+    pub user_address: String,
+    pub requested_at: u64,
+    pub completed_at: u64,
+    pub summary: ErasureSummary,
+    /// keccak256 of the JSON encoding of the fields above
+    pub digest: String,
+    /// Service address whose `personal_sign` signature over `digest` is in `signature`
+    pub signer: String,
+    pub signature: String,
+}
+
+#[derive(Serialize)]
+struct ReceiptBody<'a> {
+    receipt_id: &'a str,
+    user_address: &'a str,
+    requested_at: u64,
+    completed_at: u64,
+    summary: &'a ErasureSummary,
+}
+
+impl ErasureReceipt {
+    fn body_digest(&self) -> Result<[u8; 32]> {
+        let body = ReceiptBody {
+            receipt_id: &self.receipt_id,
+            user_address: &self.user_address,
+            requested_at: self.requested_at,
+            completed_at: self.completed_at,
+            summary: &self.summary,
+        };
+        Ok(Keccak256::digest(serde_json::to_vec(&body)?).into())
+    }
+
+    /// The digest matches the receipt contents and was signed by `signer`
+    pub fn verify(&self) -> Result<bool> {
+        let digest = self.body_digest()?;
+        if format!("0x{}", hex::encode(digest)) != self.digest {
+            return Ok(false);
+        }
+
+        let signature = hex::decode(self.signature.trim_start_matches("0x"))?;
+        let recovered = recover_personal_sign_address(&digest, &signature)?;
+        Ok(recovered.eq_ignore_ascii_case(&self.signer))
+    }
+}
+
+/// Durable storage for erasure receipts
+#[async_trait]
+pub trait ErasureRepository: Send + Sync {
+    async fn save_receipt(&self, receipt: &ErasureReceipt) -> Result<()>;
+    async fn load_receipts(&self) -> Result<Vec<ErasureReceipt>>;
+}
+
+/// Removes everything stored for a wallet address across subsystems and issues a
+/// signed receipt
+pub struct DataErasureService {
+    ipfs_chat_history: Arc<IPFSChatHistoryManager>,
+    memory_system: Arc<MemorySystem>,
+    // Re-summarises other users' memories whose shared summary was erased
+    memory_consolidator: Option<Arc<MemoryConsolidator>>,
+    semantic_search: Arc<SemanticSearchService>,
+    training_data_market: Arc<Mutex<TrainingDataMarketplace>>,
+    avatar_manager: Arc<Mutex<AvatarManager>>,
+    verification_system: Arc<VerificationSystem>,
+    storage: Arc<dyn StorageBackend>,
+    // user address (lowercase) -> receipts, oldest first
+    receipts: RwLock<HashMap<String, Vec<ErasureReceipt>>>,
+    repository: Option<Arc<dyn ErasureRepository>>,
+    // One erasure at a time so concurrent requests cannot interleave
+    running: Mutex<()>,
+}
+
+impl DataErasureService {
+    pub fn new(
+        ipfs_chat_history: Arc<IPFSChatHistoryManager>,
+        memory_system: Arc<MemorySystem>,
+        semantic_search: Arc<SemanticSearchService>,
+        training_data_market: Arc<Mutex<TrainingDataMarketplace>>,
+        avatar_manager: Arc<Mutex<AvatarManager>>,
+        verification_system: Arc<VerificationSystem>,
+        storage: Arc<dyn StorageBackend>,
+    ) -> Self {
+        Self {
+            ipfs_chat_history,
+            memory_system,
+            memory_consolidator: None,
+            semantic_search,
+            training_data_market,
+            avatar_manager,
+            verification_system,
+            storage,
+            receipts: RwLock::new(HashMap::new()),
+            repository: None,
+            running: Mutex::new(()),
+        }
+    }
+
+    /// Re-consolidate memories released when a summary shared with the erased user goes
+    pub fn with_consolidator(mut self, memory_consolidator: Arc<MemoryConsolidator>) -> Self {
+        self.memory_consolidator = Some(memory_consolidator);
+        self
+    }
+
+    /// Attach durable storage and rehydrate issued receipts
+    pub async fn with_repository(mut self, repository: Arc<dyn ErasureRepository>) -> Result<Self> {
+        let receipts = repository.load_receipts().await?;
+        let count = receipts.len();
+        {
+            let mut by_user = self.receipts.write().await;
+            for receipt in receipts {
+                by_user.entry(receipt.user_address.clone()).or_insert_with(Vec::new).push(receipt);
+            }
+        }
+
+        println!("🧾 Rehydrated {} erasure receipts", count);
+        self.repository = Some(repository);
+        Ok(self)
+    }
+
+    pub async fn receipts(&self, user_address: &str) -> Vec<ErasureReceipt> {
+        self.receipts.read().await
+            .get(&user_address.to_lowercase())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Erase all sessions, memories, embeddings, avatars and unvalidated training
+    /// contributions of `user_address`, unpinning their content where possible
+    pub async fn erase_user(&self, user_address: &str) -> Result<ErasureReceipt> {
+        let _guard = self.running.lock().await;
+        let user_address = user_address.to_lowercase();
+        let requested_at = now_secs();
+        let mut summary = ErasureSummary::default();
+
+        let sessions = self.ipfs_chat_history.erase_user_sessions(&user_address).await;
+        summary.chat_sessions = ErasureCounts {
+            erased: sessions.session_ids.len(),
+            unpinned: sessions.unpinned,
+            unpin_failures: sessions.unpin_failures,
+        };
+
+        let (memory_ids, removal) = self.memory_system
+            .erase_user_memories(&user_address, &sessions.session_ids)
+            .await?;
+        summary.memories = ErasureCounts {
+            erased: memory_ids.len() + removal.removed_summaries.len(),
+            unpinned: removal.unpinned,
+            unpin_failures: removal.unpin_failures,
+        };
+        if let Some(consolidator) = &self.memory_consolidator {
+            consolidator.reconsolidate_in_background(removal.released_sources);
+        }
+
+        let (contributions, retained_contributions) = self.training_data_market.lock().await
+            .erase_contributor(&user_address)
+            .await;
+        let contribution_ids: Vec<String> = contributions.iter().map(|c| c.contribution_id.clone()).collect();
+        summary.training_contributions = self.unpin_all(
+            contributions.iter().map(|c| c.ipfs_hash.as_str()),
+            contributions.len(),
+        ).await;
+        summary.retained_contributions = retained_contributions;
+
+        let (erased, unpinned, unpin_failures) = self.semantic_search
+            .erase_user_content(&user_address, &contribution_ids)
+            .await;
+        summary.semantic_entries = ErasureCounts { erased, unpinned, unpin_failures };
+
+        let (avatars, collections) = self.avatar_manager.lock().await
+            .erase_user_content(&user_address)
+            .await;
+        summary.avatars = self.unpin_all(avatars.iter().map(|a| a.ipfs_hash.as_str()), avatars.len()).await;
+        summary.avatar_collections = collections;
+
+        // Local storage only drops pins, so delete blocks nothing else references now
+        if let Err(e) = self.storage.collect_garbage().await {
+            println!("⚠️  Failed to collect unpinned content after erasure: {}", e);
+        }
+
+        summary.retained = vec![
+            "Muse ownership and interaction permissions mirror on-chain state".to_string(),
+            "DAT metadata stays pinned because minted tokens reference it".to_string(),
+            "On-chain transactions cannot be erased".to_string(),
+            "Submitted ratings and interaction commitments mirror on-chain submissions".to_string(),
+            "This receipt, which records the erased address".to_string(),
+        ];
+        if !summary.retained_contributions.is_empty() {
+            summary.retained.push("Validated training contributions were rewarded on-chain".to_string());
+        }
+
+        let receipt = self.sign_receipt(&user_address, requested_at, summary)?;
+        if let Some(repository) = &self.repository {
+            if let Err(e) = repository.save_receipt(&receipt).await {
+                println!("⚠️  Failed to persist erasure receipt {}: {}", receipt.receipt_id, e);
+            }
+        }
+        self.receipts.write().await
+            .entry(user_address.clone())
+            .or_insert_with(Vec::new)
+            .push(receipt.clone());
+
+        println!("🧾 Erasure {} completed for {}", receipt.receipt_id, user_address);
+        Ok(receipt)
+    }
+
+    async fn unpin_all<'a>(&self, cids: impl Iterator<Item = &'a str>, erased: usize) -> ErasureCounts {
+        let mut counts = ErasureCounts { erased, ..Default::default() };
+        for cid in cids.filter(|cid| !cid.is_empty()) {
+            match self.storage.unpin(cid).await {
+                Ok(()) => counts.unpinned += 1,
+                Err(e) => {
+                    counts.unpin_failures += 1;
+                    println!("⚠️  Failed to unpin {}: {}", cid, e);
+                }
+            }
+        }
+        counts
+    }
+
+    fn sign_receipt(&self, user_address: &str, requested_at: u64, summary: ErasureSummary) -> Result<ErasureReceipt> {
+        let mut receipt = ErasureReceipt {
+            receipt_id: format!("erasure_{}", uuid::Uuid::new_v4()),
+            user_address: user_address.to_string(),
+            requested_at,
+            completed_at: now_secs(),
+            summary,
+            digest: String::new(),
+            signer: self.verification_system.get_public_key_address(),
+            signature: String::new(),
+        };
+
+        let digest = receipt.body_digest()?;
+        receipt.digest = format!("0x{}", hex::encode(digest));
+        receipt.signature = format!("0x{}", hex::encode(self.verification_system.sign_personal_message(&digest)?));
+        Ok(receipt)
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain_client::BlockchainClient;
+    use crate::config::Config;
+    use crate::embedder::{Embedder, HashingEmbedder};
+    use crate::encryption::EncryptionService;
+    use crate::muse_orchestrator::MuseTraits;
+    use crate::persist_memory::{InteractionData, MemoryCategory, MuseMemory, RetentionPriority};
+    use crate::storage_backend::LocalStorageBackend;
+    use crate::vector_index::VectorIndex;
+
+    const ALICE: &str = "0x00000000000000000000000000000000000a11ce";
+    const BOB: &str = "0x0000000000000000000000000000000000000b0b";
+
+    struct Fixture {
+        service: DataErasureService,
+        chat_history: Arc<IPFSChatHistoryManager>,
+        memory_system: Arc<MemorySystem>,
+        semantic_search: Arc<SemanticSearchService>,
+        avatar_manager: Arc<Mutex<AvatarManager>>,
+        dir: std::path::PathBuf,
+    }
+
+    async fn fixture(name: &str) -> Fixture {
+        let dir = std::env::temp_dir().join(format!("data_erasure_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let dir_str = dir.to_str().unwrap();
+        let config = Config::for_tests();
+        let storage: Arc<dyn StorageBackend> = Arc::new(LocalStorageBackend::new(dir_str).unwrap());
+        let encryption = Arc::new(EncryptionService::unencrypted());
+        let embedder = Arc::new(HashingEmbedder::new(64));
+        let index = |name: &str| Arc::new(VectorIndex::open(dir_str, name, embedder.dimension(), &embedder.identity()));
+
+        let chat_history = Arc::new(IPFSChatHistoryManager::new(storage.clone(), encryption.clone()).await.unwrap());
+        let memory_system = Arc::new(MemorySystem::new(&config, storage.clone(), encryption, embedder.clone(), index("memories")).await.unwrap());
+        let semantic_search = Arc::new(SemanticSearchService::new(config.clone(), chat_history.clone(), storage.clone(), embedder.clone(), index("semantic")));
+        let blockchain_client = Arc::new(BlockchainClient::new(&config).await.unwrap());
+        let training_data_market = Arc::new(Mutex::new(TrainingDataMarketplace::new(config.clone(), blockchain_client, semantic_search.clone(), chat_history.clone())));
+        let avatar_manager = Arc::new(Mutex::new(AvatarManager::new()));
+
+        let service = DataErasureService::new(
+            chat_history.clone(),
+            memory_system.clone(),
+            semantic_search.clone(),
+            training_data_market,
+            avatar_manager.clone(),
+            Arc::new(VerificationSystem::new(&config).unwrap()),
+            storage,
+        );
+        Fixture { service, chat_history, memory_system, semantic_search, avatar_manager, dir }
+    }
+
+    fn memory(id: &str, user: &str, session_id: &str) -> MuseMemory {
+        MuseMemory {
+            memory_id: id.to_string(),
+            muse_id: "7".to_string(),
+            interaction_data: InteractionData {
+                user_prompt: format!("Something {} said", user),
+                ai_response: "Noted.".to_string(),
+                personality_traits: MuseTraits { creativity: 50, wisdom: 50, humor: 50, empathy: 50 },
+                context_used: Vec::new(),
+                session_id: Some(session_id.to_string()),
+                conversation_turn: 1,
+                response_time_ms: 0,
+                model_used: "mock".to_string(),
+                prompt_tokens: None,
+                response_tokens: None,
+                user_satisfaction: None,
+                user_address: Some(user.to_string()),
+                tool_calls: Vec::new(),
+            },
+            embedding: None,
+            importance: 0.5,
+            timestamp: 1,
+            ipfs_hash: None,
+            tags: Vec::new(),
+            category: MemoryCategory::Personal,
+            emotional_tone: None,
+            context_window: None,
+            version: 1,
+            access_count: 0,
+            last_accessed: 1,
+            retention_priority: RetentionPriority::Medium,
+            consolidated_into: None,
+            history: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn receipts_verify_against_their_digest_and_signer() {
+        let fixture = fixture("receipt").await;
+        let receipt = fixture.service.erase_user(ALICE).await.unwrap();
+        assert!(receipt.verify().unwrap());
+        assert_eq!(receipt.signer, fixture.service.verification_system.get_public_key_address());
+
+        let mut tampered = receipt.clone();
+        tampered.summary.memories.erased += 1;
+        assert!(!tampered.verify().unwrap());
+
+        // A digest recomputed over the tampered body still needs the service's signature
+        tampered.digest = format!("0x{}", hex::encode(tampered.body_digest().unwrap()));
+        assert!(!matches!(tampered.verify(), Ok(true)));
+
+        let mut other_signer = receipt.clone();
+        other_signer.signer = BOB.to_string();
+        assert!(!other_signer.verify().unwrap());
+
+        let _ = std::fs::remove_dir_all(&fixture.dir);
+    }
+
+    #[tokio::test]
+    async fn erasure_covers_every_subsystem() {
+        let fixture = fixture("subsystems").await;
+        let alice_session = fixture.chat_history
+            .initialize_session("session_alice".to_string(), "7".to_string(), ALICE.to_string())
+            .await
+            .unwrap();
+        fixture.chat_history
+            .add_message(&alice_session.session_id, "user".to_string(), "Hello".to_string(), "a1".to_string())
+            .await
+            .unwrap();
+        fixture.chat_history
+            .initialize_session("session_bob".to_string(), "7".to_string(), BOB.to_string())
+            .await
+            .unwrap();
+        fixture.memory_system.import_memory(memory("mem_alice", ALICE, "session_alice")).await.unwrap();
+        fixture.memory_system.import_memory(memory("mem_bob", BOB, "session_bob")).await.unwrap();
+        fixture.semantic_search
+            .auto_index_message("session_alice", ALICE, "7", "Hello from Alice", "user", "a1")
+            .await
+            .unwrap();
+        fixture.semantic_search
+            .auto_index_message("session_bob", BOB, "7", "Hello from Bob", "user", "b1")
+            .await
+            .unwrap();
+        fixture.avatar_manager.lock().await
+            .create_collection("Mine".to_string(), "Alice's avatars".to_string(), ALICE, Vec::new(), false)
+            .await
+            .unwrap();
+
+        // Addresses are matched case-insensitively
+        let receipt = fixture.service.erase_user(&ALICE.to_uppercase().replace("0X", "0x")).await.unwrap();
+        let summary = &receipt.summary;
+        assert_eq!(receipt.user_address, ALICE);
+        assert_eq!(summary.chat_sessions.erased, 1);
+        assert_eq!(summary.chat_sessions.unpin_failures, 0);
+        assert_eq!(summary.memories.erased, 1);
+        assert_eq!(summary.semantic_entries.erased, 1);
+        assert_eq!(summary.avatar_collections, 1);
+        assert_eq!(summary.training_contributions.erased, 0);
+        assert!(!summary.retained.is_empty());
+
+        assert!(fixture.chat_history.get_session("session_alice").await.is_err());
+        assert!(fixture.memory_system.get_memory("7", "mem_alice").await.is_none());
+        assert!(fixture.avatar_manager.lock().await.get_user_collections(ALICE).is_empty());
+        // Other users keep their data
+        assert!(fixture.chat_history.get_session("session_bob").await.is_ok());
+        assert!(fixture.memory_system.get_memory("7", "mem_bob").await.is_some());
+
+        assert_eq!(fixture.service.receipts(ALICE).await.len(), 1);
+        assert!(fixture.service.receipts(BOB).await.is_empty());
+        let _ = std::fs::remove_dir_all(&fixture.dir);
+    }
+}
@@ -0,0 +1,634 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use crate::car::{read_car, write_car, CarBlock};
+use crate::ipfs_chat_history::{IPFSChatHistoryManager, IPFSChatSession};
+use crate::persist_memory::{MemorySystem, MuseMemory};
+use crate::rating_system::{AIAlignmentMarket, RatingRecord};
+use crate::semantic_search::SemanticSearchService;
+use crate::storage_backend::{cid_to_string, cid_v1_bytes, StorageBackend};
+use crate::verification::{CommitmentRecord, VerificationSystem};
+
+/// Bumped when the archive layout changes incompatibly
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+/// Largest archive the import endpoints accept
+pub const MAX_ARCHIVE_BYTES: usize = 256 * 1024 * 1024;
+
+/// A stored object referenced by the archive. `car_cid` is the raw-block CIDv1 the
+/// content is filed under in the CAR file, which differs from `cid` when the source
+/// backend chunked or wrapped it (e.g. Kubo's UnixFS CIDv0).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveBlock {
+    pub cid: String,
+    pub car_cid: String,
+    pub size: usize,
+    /// "chat_session", "memory" or "dat_metadata"
+    pub kind: String,
+}
+
+/// Everything stored about one user's relationship with one muse. Chat sessions carry
+/// their compressed segments.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportArchive {
+    pub format_version: u32,
+    pub exported_at: u64,
+    /// Signer address of the exporting deployment
+    pub source: String,
+    pub user_address: String,
+    pub muse_id: String,
+    pub sessions: Vec<IPFSChatSession>,
+    pub memories: Vec<MuseMemory>,
+    pub ratings: Vec<RatingRecord>,
+    pub dats: Vec<serde_json::Value>,
+    pub commitments: Vec<CommitmentRecord>,
+    pub blocks: Vec<ArchiveBlock>,
+    /// Referenced CIDs that could not be read from storage at export time
+    pub missing_blocks: Vec<String>,
+}
+
+/// What an import added on this deployment
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportSummary {
+    pub sessions: usize,
+    pub memories: usize,
+    pub ratings: usize,
+    pub dats: usize,
+    pub commitments: usize,
+    /// Chat messages re-embedded into the semantic index
+    pub messages_indexed: usize,
+    pub blocks_restored: usize,
+    /// Blocks listed in the archive but not included in it
+    pub blocks_missing: usize,
+    /// Records already present here, belonging to another user or muse, or commitments
+    /// whose signature does not verify
+    pub skipped: usize,
+    /// Memories left out because only the muse owner may add memories
+    pub memories_not_permitted: usize,
+    /// Original CID -> CID the content was stored under here, when they differ
+    pub cid_remap: HashMap<String, String>,
+}
+
+/// Bundles a (user, muse) relationship into a portable archive and rehydrates one
+/// exported elsewhere
+pub struct DataExportService {
+    ipfs_chat_history: Arc<IPFSChatHistoryManager>,
+    memory_system: Arc<MemorySystem>,
+    semantic_search: Arc<SemanticSearchService>,
+    rating_market: Arc<AIAlignmentMarket>,
+    verification_system: Arc<VerificationSystem>,
+    storage: Arc<dyn StorageBackend>,
+}
+
+impl DataExportService {
+    pub fn new(
+        ipfs_chat_history: Arc<IPFSChatHistoryManager>,
+        memory_system: Arc<MemorySystem>,
+        semantic_search: Arc<SemanticSearchService>,
+        rating_market: Arc<AIAlignmentMarket>,
+        verification_system: Arc<VerificationSystem>,
+        storage: Arc<dyn StorageBackend>,
+    ) -> Self {
+        Self {
+            ipfs_chat_history,
+            memory_system,
+            semantic_search,
+            rating_market,
+            verification_system,
+            storage,
+        }
+    }
+
+    /// JSON archive of everything `user_address` has with `muse_id`
+    pub async fn export_json(&self, user_address: &str, muse_id: &str) -> Result<ExportArchive> {
+        let (archive, _) = self.collect(user_address, muse_id).await?;
+        Ok(archive)
+    }
+
+    /// CARv1 file whose root block is the JSON archive, followed by every stored
+    /// object it references
+    pub async fn export_car(&self, user_address: &str, muse_id: &str) -> Result<Vec<u8>> {
+        let (archive, blocks) = self.collect(user_address, muse_id).await?;
+
+        let manifest = serde_json::to_vec(&archive)?;
+        let root = cid_v1_bytes(&manifest);
+        let mut car_blocks = vec![CarBlock { cid: root.clone(), data: manifest }];
+        car_blocks.extend(blocks);
+
+        Ok(write_car(&[root], &car_blocks))
+    }
+
+    async fn collect(&self, user_address: &str, muse_id: &str) -> Result<(ExportArchive, Vec<CarBlock>)> {
+        let token_id: u64 = muse_id.parse().map_err(|_| anyhow!("Invalid muse id {}", muse_id))?;
+
+        let mut sessions = self.ipfs_chat_history.sessions_for_user_muse(user_address, muse_id).await;
+        sessions.sort_by(|a, b| b.last_updated.cmp(&a.last_updated));
+        let session_ids: Vec<String> = sessions.iter().map(|s| s.session_id.clone()).collect();
+
+        let memories = self.memory_system.user_memories(muse_id, user_address, &session_ids).await;
+        let ratings = self.rating_market.user_ratings(user_address, token_id).await;
+        let commitments = self.verification_system.user_commitments(user_address, token_id).await;
+
+        let mut dats = Vec::new();
+        for dat in self.semantic_search.get_user_dats(user_address).await? {
+            if self.dat_muse_id(&dat).await == Some(token_id) {
+                dats.push(dat);
+            }
+        }
+
+        // CID -> kind, ordered so repeated exports produce the same CAR
+        let mut referenced: BTreeMap<String, &str> = BTreeMap::new();
+        for session in &sessions {
+            for cid in self.ipfs_chat_history.session_cids(&session.session_id).await {
+                referenced.insert(cid, "chat_session");
+            }
+            if let Some(cid) = &session.ipfs_hash {
+                referenced.insert(cid.clone(), "chat_session");
+            }
+        }
+        for memory in &memories {
+            let cids = memory.ipfs_hash.iter().chain(memory.history.iter().filter_map(|r| r.ipfs_hash.as_ref()));
+            for cid in cids {
+                referenced.insert(cid.clone(), "memory");
+            }
+        }
+        for dat in &dats {
+            if let Some(cid) = dat.get("ipfs_hash").and_then(|v| v.as_str()) {
+                referenced.insert(cid.to_string(), "dat_metadata");
+            }
+        }
+
+        let mut blocks = Vec::new();
+        let mut car_blocks: Vec<CarBlock> = Vec::new();
+        let mut missing_blocks = Vec::new();
+        for (cid, kind) in referenced {
+            match self.storage.get(&cid).await {
+                Ok(data) => {
+                    let car_cid = cid_v1_bytes(&data);
+                    blocks.push(ArchiveBlock {
+                        cid,
+                        car_cid: cid_to_string(&car_cid),
+                        size: data.len(),
+                        kind: kind.to_string(),
+                    });
+                    if !car_blocks.iter().any(|block| block.cid == car_cid) {
+                        car_blocks.push(CarBlock { cid: car_cid, data });
+                    }
+                }
+                Err(e) => {
+                    println!("⚠️ Export of {} for {} is missing block {}: {}", muse_id, user_address, cid, e);
+                    missing_blocks.push(cid);
+                }
+            }
+        }
+
+        let archive = ExportArchive {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            exported_at: now_secs(),
+            source: self.verification_system.get_public_key_address(),
+            user_address: user_address.to_lowercase(),
+            muse_id: muse_id.to_string(),
+            sessions,
+            memories,
+            ratings,
+            dats,
+            commitments,
+            blocks,
+            missing_blocks,
+        };
+
+        println!("📦 Exported muse {} for {}: {} sessions, {} memories, {} ratings, {} DATs, {} commitments, {} blocks",
+                 muse_id, user_address, archive.sessions.len(), archive.memories.len(), archive.ratings.len(),
+                 archive.dats.len(), archive.commitments.len(), archive.blocks.len());
+        Ok((archive, car_blocks))
+    }
+
+    /// Muse a DAT was minted for. Entries indexed before the muse was recorded fall
+    /// back to the stored metadata.
+    async fn dat_muse_id(&self, dat: &serde_json::Value) -> Option<u64> {
+        let as_id = |value: &serde_json::Value| value.as_u64().or_else(|| value.as_str().and_then(|s| s.parse().ok()));
+        if let Some(muse_id) = dat.get("muse_id").and_then(as_id) {
+            return Some(muse_id);
+        }
+
+        let cid = dat.get("ipfs_hash")?.as_str()?;
+        let stored: serde_json::Value = serde_json::from_slice(&self.storage.get(cid).await.ok()?).ok()?;
+        // DAT metadata is stored as the "content" string of a semantic content document
+        let metadata: serde_json::Value = serde_json::from_str(stored.get("content")?.as_str()?).ok()?;
+        metadata.get("interaction_proof")?.get("muse_token_id").and_then(as_id)
+    }
+
+    /// Import a JSON archive. Stored objects are not part of it, so only records are restored.
+    pub async fn import_json(
+        &self,
+        user_address: &str,
+        muse_id: &str,
+        archive: ExportArchive,
+        may_add_memories: bool,
+    ) -> Result<ImportSummary> {
+        self.import(user_address, muse_id, archive, HashMap::new(), may_add_memories).await
+    }
+
+    /// Import a CAR file produced by `export_car`, restoring its blocks to this deployment's storage
+    pub async fn import_car(
+        &self,
+        user_address: &str,
+        muse_id: &str,
+        car: &[u8],
+        may_add_memories: bool,
+    ) -> Result<ImportSummary> {
+        let (roots, blocks) = read_car(car)?;
+        let root = roots.first().ok_or_else(|| anyhow!("CAR file has no root"))?;
+
+        let mut blocks: HashMap<String, Vec<u8>> = blocks.into_iter()
+            .map(|block| (cid_to_string(&block.cid), block.data))
+            .collect();
+        let manifest = blocks.remove(&cid_to_string(root))
+            .ok_or_else(|| anyhow!("CAR file does not contain its root block"))?;
+        let archive: ExportArchive = serde_json::from_slice(&manifest)
+            .map_err(|e| anyhow!("CAR root is not an export archive: {}", e))?;
+
+        self.import(user_address, muse_id, archive, blocks, may_add_memories).await
+    }
+
+    async fn import(
+        &self,
+        user_address: &str,
+        muse_id: &str,
+        archive: ExportArchive,
+        blocks: HashMap<String, Vec<u8>>,
+        may_add_memories: bool,
+    ) -> Result<ImportSummary> {
+        if archive.format_version > ARCHIVE_FORMAT_VERSION {
+            return Err(anyhow!("Unsupported archive format version {}", archive.format_version));
+        }
+        if !archive.user_address.eq_ignore_ascii_case(user_address) || archive.muse_id != muse_id {
+            return Err(anyhow!("Archive belongs to {} / muse {}", archive.user_address, archive.muse_id));
+        }
+        let token_id: u64 = muse_id.parse().map_err(|_| anyhow!("Invalid muse id {}", muse_id))?;
+        let mut summary = ImportSummary::default();
+
+        // Blocks first, so DAT metadata resolves by the time its entry is registered
+        for block in &archive.blocks {
+            let Some(data) = blocks.get(&block.car_cid) else {
+                summary.blocks_missing += 1;
+                continue;
+            };
+            if self.storage.exists(&block.cid).await.unwrap_or(false) {
+                continue;
+            }
+            let filename = format!("import_{}_{}", block.kind, block.cid);
+            match self.storage.put(data.clone(), &filename, "application/octet-stream").await {
+                Ok(cid) => {
+                    summary.blocks_restored += 1;
+                    if cid != block.cid {
+                        summary.cid_remap.insert(block.cid.clone(), cid);
+                    }
+                }
+                Err(e) => println!("⚠️ Failed to restore block {}: {}", block.cid, e),
+            }
+        }
+
+        for mut dat in archive.dats {
+            let owned = dat.get("user_address").and_then(|v| v.as_str())
+                .is_some_and(|address| address.eq_ignore_ascii_case(user_address));
+            if !owned {
+                summary.skipped += 1;
+                continue;
+            }
+            let remapped = dat.get("ipfs_hash").and_then(|v| v.as_str())
+                .and_then(|cid| summary.cid_remap.get(cid))
+                .cloned();
+            if let Some(cid) = remapped {
+                dat["ipfs_hash"] = serde_json::Value::String(cid);
+            }
+            match self.semantic_search.import_user_dat(&dat).await {
+                Ok(()) => summary.dats += 1,
+                Err(e) => {
+                    println!("⚠️ Skipping DAT entry: {}", e);
+                    summary.skipped += 1;
+                }
+            }
+        }
+
+        // Newest first, so it becomes the session a returning user continues
+        let mut sessions = archive.sessions;
+        sessions.sort_by(|a, b| b.last_updated.cmp(&a.last_updated));
+        for session in sessions {
+            if session.muse_id != muse_id || !session.user_address.eq_ignore_ascii_case(user_address) {
+                summary.skipped += 1;
+                continue;
+            }
+            if !self.ipfs_chat_history.import_session(session.clone()).await? {
+                summary.skipped += 1;
+                continue;
+            }
+            summary.sessions += 1;
+
+            for message in &session.messages {
+                let indexed = self.semantic_search
+                    .auto_index_message(&session.session_id, user_address, muse_id, &message.content, &message.role, &message.id)
+                    .await;
+                if indexed.is_ok() {
+                    summary.messages_indexed += 1;
+                }
+            }
+        }
+
+        let mut memories = archive.memories;
+        memories.sort_by_key(|memory| memory.timestamp);
+        for memory in memories {
+            if memory.muse_id != muse_id {
+                summary.skipped += 1;
+            } else if !may_add_memories {
+                summary.memories_not_permitted += 1;
+            } else if self.memory_system.import_memory(memory).await? {
+                summary.memories += 1;
+            } else {
+                summary.skipped += 1;
+            }
+        }
+
+        let (ratings, foreign_ratings): (Vec<RatingRecord>, Vec<RatingRecord>) = archive.ratings.into_iter()
+            .partition(|r| r.rating.muse_id == token_id && r.rating.user_address.eq_ignore_ascii_case(user_address));
+        let offered = ratings.len();
+        summary.ratings = self.rating_market.import_ratings(ratings).await;
+        summary.skipped += foreign_ratings.len() + offered - summary.ratings;
+
+        let (commitments, foreign_commitments): (Vec<CommitmentRecord>, Vec<CommitmentRecord>) = archive.commitments.into_iter()
+            .partition(|c| c.muse_id == token_id && c.user_address.eq_ignore_ascii_case(user_address));
+        let offered = commitments.len();
+        summary.commitments = self.verification_system.import_commitments(commitments).await;
+        summary.skipped += foreign_commitments.len() + offered - summary.commitments;
+
+        println!("📥 Imported muse {} for {}: {} sessions, {} memories, {} ratings, {} DATs, {} commitments, {} blocks",
+                 muse_id, user_address, summary.sessions, summary.memories, summary.ratings,
+                 summary.dats, summary.commitments, summary.blocks_restored);
+        Ok(summary)
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain_client::BlockchainClient;
+    use crate::config::Config;
+    use crate::embedder::{Embedder, HashingEmbedder};
+    use crate::encryption::EncryptionService;
+    use crate::muse_orchestrator::MuseTraits;
+    use crate::persist_memory::{InteractionData, MemoryCategory, RetentionPriority};
+    use crate::rating_system::InteractionRating;
+    use crate::storage_backend::LocalStorageBackend;
+    use crate::vector_index::VectorIndex;
+    use crate::verification::{bytes_to_hex_string, InferenceParams, VerifiableInteraction};
+
+    const ALICE: &str = "0x00000000000000000000000000000000000a11ce";
+    const BOB: &str = "0x0000000000000000000000000000000000000b0b";
+
+    struct Deployment {
+        service: DataExportService,
+        chat_history: Arc<IPFSChatHistoryManager>,
+        memory_system: Arc<MemorySystem>,
+        rating_market: Arc<AIAlignmentMarket>,
+        verification_system: Arc<VerificationSystem>,
+        dir: std::path::PathBuf,
+    }
+
+    async fn deployment(name: &str) -> Deployment {
+        let dir = std::env::temp_dir().join(format!("data_export_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let dir_str = dir.to_str().unwrap();
+        let config = Config::for_tests();
+        let storage: Arc<dyn StorageBackend> = Arc::new(LocalStorageBackend::new(dir_str).unwrap());
+        let encryption = Arc::new(EncryptionService::unencrypted());
+        let embedder = Arc::new(HashingEmbedder::new(64));
+        let index = |name: &str| Arc::new(VectorIndex::open(dir_str, name, embedder.dimension(), &embedder.identity()));
+
+        let chat_history = Arc::new(IPFSChatHistoryManager::new(storage.clone(), encryption.clone()).await.unwrap());
+        let memory_system = Arc::new(MemorySystem::new(&config, storage.clone(), encryption, embedder.clone(), index("memories")).await.unwrap());
+        let semantic_search = Arc::new(SemanticSearchService::new(config.clone(), chat_history.clone(), storage.clone(), embedder.clone(), index("semantic")));
+        let blockchain_client = Arc::new(BlockchainClient::new(&config).await.unwrap());
+        let rating_market = Arc::new(AIAlignmentMarket::new(blockchain_client, config.clone()));
+        let verification_system = Arc::new(VerificationSystem::new(&config).unwrap());
+
+        let service = DataExportService::new(
+            chat_history.clone(),
+            memory_system.clone(),
+            semantic_search,
+            rating_market.clone(),
+            verification_system.clone(),
+            storage,
+        );
+        Deployment { service, chat_history, memory_system, rating_market, verification_system, dir }
+    }
+
+    fn memory(id: &str, user: &str, session_id: &str) -> MuseMemory {
+        MuseMemory {
+            memory_id: id.to_string(),
+            muse_id: "7".to_string(),
+            interaction_data: InteractionData {
+                user_prompt: format!("Something {} said", user),
+                ai_response: "Noted.".to_string(),
+                personality_traits: MuseTraits { creativity: 50, wisdom: 50, humor: 50, empathy: 50 },
+                context_used: Vec::new(),
+                session_id: Some(session_id.to_string()),
+                conversation_turn: 1,
+                response_time_ms: 0,
+                model_used: "mock".to_string(),
+                prompt_tokens: None,
+                response_tokens: None,
+                user_satisfaction: None,
+                user_address: Some(user.to_string()),
+                tool_calls: Vec::new(),
+            },
+            embedding: None,
+            importance: 0.5,
+            timestamp: 1,
+            ipfs_hash: None,
+            tags: Vec::new(),
+            category: MemoryCategory::Personal,
+            emotional_tone: None,
+            context_window: None,
+            version: 1,
+            access_count: 0,
+            last_accessed: 1,
+            retention_priority: RetentionPriority::Medium,
+            consolidated_into: None,
+            history: Vec::new(),
+        }
+    }
+
+    /// A chat, a memory, a rating and a signed commitment between `user` and muse 7
+    async fn seed(deployment: &Deployment, user: &str, suffix: &str) {
+        let session_id = format!("session_{}", suffix);
+        deployment.chat_history
+            .initialize_session(session_id.clone(), "7".to_string(), user.to_string())
+            .await
+            .unwrap();
+        for (role, content, id) in [("user", "Hello", "m1"), ("assistant", "Hi there", "m2")] {
+            deployment.chat_history
+                .add_message(&session_id, role.to_string(), content.to_string(), format!("{}_{}", id, suffix))
+                .await
+                .unwrap();
+        }
+        deployment.memory_system.import_memory(memory(&format!("mem_{}", suffix), user, &session_id)).await.unwrap();
+        deployment.rating_market.import_ratings(vec![RatingRecord {
+            rating_id: format!("rating_{}", suffix),
+            rating: InteractionRating {
+                muse_id: 7,
+                interaction_hash: "0xabc".to_string(),
+                quality_score: 8,
+                personality_accuracy: 7,
+                helpfulness: 9,
+                feedback: "Good".to_string(),
+                user_address: user.to_string(),
+            },
+            transaction_hash: None,
+            reward_amount: 0,
+            submitted_at: 1,
+        }]).await;
+
+        let muse_dna_hash = [3u8; 32];
+        let interaction = VerifiableInteraction {
+            muse_id: 7,
+            muse_dna_hash,
+            user_prompt: "Hello".to_string(),
+            ai_response: "Hi there".to_string(),
+            personality_traits: MuseTraits { creativity: 50, wisdom: 50, humor: 50, empathy: 50 },
+            timestamp: 1_700_000_000,
+            inference_params: InferenceParams::default(),
+            tool_calls: Vec::new(),
+        };
+        let signer = &deployment.verification_system;
+        let commitment = signer.create_commitment(&interaction).await.unwrap();
+        signer.record_commitment(CommitmentRecord {
+            interaction_id: format!("interaction_{}", suffix),
+            muse_id: 7,
+            user_address: user.to_string(),
+            session_id: Some(session_id),
+            message_id: None,
+            muse_dna_hash: bytes_to_hex_string(&muse_dna_hash),
+            commitment_hash: bytes_to_hex_string(&commitment.commitment_hash),
+            signature: bytes_to_hex_string(&commitment.signature),
+            recovery_id: commitment.recovery_id,
+            signer: signer.get_public_key_address(),
+            foreign: false,
+            created_at: 1_700_000_000,
+        }).await;
+    }
+
+    #[tokio::test]
+    async fn exports_only_the_users_records_with_the_muse() {
+        let source = deployment("scope").await;
+        seed(&source, ALICE, "alice").await;
+        seed(&source, BOB, "bob").await;
+
+        let archive = source.service.export_json(ALICE, "7").await.unwrap();
+        assert_eq!(archive.format_version, ARCHIVE_FORMAT_VERSION);
+        assert_eq!(archive.user_address, ALICE);
+        assert_eq!(archive.source, source.verification_system.get_public_key_address());
+        assert_eq!(archive.sessions.len(), 1);
+        assert_eq!(archive.sessions[0].session_id, "session_alice");
+        assert_eq!(archive.memories.len(), 1);
+        assert_eq!(archive.memories[0].memory_id, "mem_alice");
+        assert_eq!(archive.ratings.len(), 1);
+        assert_eq!(archive.commitments.len(), 1);
+        assert!(archive.missing_blocks.is_empty());
+
+        assert!(source.service.export_json(ALICE, "8").await.unwrap().sessions.is_empty());
+        assert!(source.service.export_json(ALICE, "not-a-muse").await.is_err());
+        let _ = std::fs::remove_dir_all(&source.dir);
+    }
+
+    #[tokio::test]
+    async fn json_round_trip_restores_records_once() {
+        let source = deployment("json_source").await;
+        let target = deployment("json_target").await;
+        seed(&source, ALICE, "alice").await;
+        let archive = source.service.export_json(ALICE, "7").await.unwrap();
+
+        // Archives only import into the relationship they were exported from
+        assert!(target.service.import_json(BOB, "7", archive.clone(), true).await.is_err());
+        assert!(target.service.import_json(ALICE, "8", archive.clone(), true).await.is_err());
+
+        let summary = target.service.import_json(ALICE, "7", archive.clone(), true).await.unwrap();
+        assert_eq!(summary.sessions, 1);
+        assert_eq!(summary.messages_indexed, 2);
+        assert_eq!(summary.memories, 1);
+        assert_eq!(summary.ratings, 1);
+        assert_eq!(summary.commitments, 1);
+        assert_eq!(summary.skipped, 0);
+        // A JSON archive carries no stored objects
+        assert_eq!(summary.blocks_restored, 0);
+        assert_eq!(summary.blocks_missing, archive.blocks.len());
+
+        let session = target.chat_history.get_session("session_alice").await.unwrap();
+        assert_eq!(session.messages.len(), 2);
+        assert!(target.memory_system.get_memory("7", "mem_alice").await.is_some());
+        let commitments = target.verification_system.user_commitments(ALICE, 7).await;
+        assert_eq!(commitments.len(), 1);
+        // Signed with the same key, so the commitment is this deployment's own
+        assert!(!commitments[0].foreign);
+
+        // Importing again adds nothing
+        let again = target.service.import_json(ALICE, "7", archive, true).await.unwrap();
+        assert_eq!(again.sessions + again.memories + again.ratings + again.commitments, 0);
+        assert_eq!(again.skipped, 4);
+
+        let _ = std::fs::remove_dir_all(&source.dir);
+        let _ = std::fs::remove_dir_all(&target.dir);
+    }
+
+    #[tokio::test]
+    async fn memories_need_permission_and_tampered_commitments_are_skipped() {
+        let source = deployment("checks_source").await;
+        let target = deployment("checks_target").await;
+        seed(&source, ALICE, "alice").await;
+        let mut archive = source.service.export_json(ALICE, "7").await.unwrap();
+        archive.commitments[0].commitment_hash = bytes_to_hex_string(&[9u8; 32]);
+
+        let summary = target.service.import_json(ALICE, "7", archive, false).await.unwrap();
+        assert_eq!(summary.memories, 0);
+        assert_eq!(summary.memories_not_permitted, 1);
+        assert_eq!(summary.commitments, 0);
+        assert_eq!(summary.skipped, 1);
+        assert!(target.memory_system.get_memory("7", "mem_alice").await.is_none());
+        assert!(target.verification_system.user_commitments(ALICE, 7).await.is_empty());
+
+        let _ = std::fs::remove_dir_all(&source.dir);
+        let _ = std::fs::remove_dir_all(&target.dir);
+    }
+
+    #[tokio::test]
+    async fn car_round_trip_restores_stored_blocks() {
+        let source = deployment("car_source").await;
+        let target = deployment("car_target").await;
+        seed(&source, ALICE, "alice").await;
+        let archive = source.service.export_json(ALICE, "7").await.unwrap();
+        assert!(!archive.blocks.is_empty());
+
+        let car = source.service.export_car(ALICE, "7").await.unwrap();
+        let summary = target.service.import_car(ALICE, "7", &car, true).await.unwrap();
+        assert_eq!(summary.blocks_missing, 0);
+        assert_eq!(summary.blocks_restored, archive.blocks.len());
+        assert_eq!(summary.sessions, 1);
+        assert_eq!(summary.memories, 1);
+        assert_eq!(summary.commitments, 1);
+
+        let restored = target.service.export_json(ALICE, "7").await.unwrap();
+        assert_eq!(restored.sessions[0].messages.len(), archive.sessions[0].messages.len());
+        assert_eq!(restored.memories[0].memory_id, "mem_alice");
+
+        assert!(target.service.import_car(ALICE, "7", b"not a car file", true).await.is_err());
+        let _ = std::fs::remove_dir_all(&source.dir);
+        let _ = std::fs::remove_dir_all(&target.dir);
+    }
+}
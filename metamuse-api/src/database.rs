@@ -0,0 +1,700 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::any::{install_default_drivers, AnyPoolOptions};
+use sqlx::{AnyPool, Row};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::avatar_system::{Avatar, AvatarCollection, AvatarRepository};
+use crate::config::Config;
+use crate::data_erasure::{ErasureReceipt, ErasureRepository};
+use crate::encryption::{KeyRepository, UserPublicKey, WrappedMuseKey};
+use crate::persist_memory::{MemoryRepository, MuseMemory};
+use crate::plugin_system::{Plugin, PluginRepository};
+use crate::rating_system::{RatingRecord, RatingRepository};
+use crate::template_system::{PromptTemplate, TemplateRepository};
+use crate::training_data_market::{ContributorProfile, TrainingDataContribution, TrainingDataRepository};
+use crate::verification::{CommitmentRecord, CommitmentRepository};
+
+/// Used when `DATABASE_URL` is not set so a fresh checkout persists to a local file
+const DEFAULT_DATABASE_URL: &str = "sqlite://metamuse.db?mode=rwc";
+const MAX_CONNECTIONS: u32 = 5;
+
+/// Durable mapping of user addresses to the muses they created
+#[async_trait]
+pub trait UserMuseRepository: Send + Sync {
+    async fn save_user_muse(&self, user_address: &str, token_id: u64) -> Result<()>;
+    async fn load_user_muses(&self) -> Result<HashMap<String, Vec<u64>>>;
+}
+
+/// SQL storage layer (SQLite by default, Postgres when `DATABASE_URL` points at one).
+///
+/// Subsystem records are stored as JSON documents next to their id and owner, so the
+/// in-memory structs stay the source of truth for their shape.
+pub struct Database {
+    pool: AnyPool,
+}
+
+impl Database {
+    pub async fn connect(config: &Config) -> Result<Arc<Self>> {
+        let database_url = config.database_url.clone()
+            .unwrap_or_else(|| DEFAULT_DATABASE_URL.to_string());
+        Self::open(&database_url, MAX_CONNECTIONS).await
+    }
+
+    /// Connect to `database_url` and apply pending migrations. An in-memory SQLite database
+    /// lives per connection, so it needs `max_connections` of 1.
+    async fn open(database_url: &str, max_connections: u32) -> Result<Arc<Self>> {
+        install_default_drivers();
+
+        let backend = database_url.split(':').next().unwrap_or("unknown").to_string();
+
+        let pool = AnyPoolOptions::new()
+            .max_connections(max_connections)
+            .connect(database_url)
+            .await?;
+
+        sqlx::migrate!("./migrations").run(&pool).await?;
+
+        println!("🗄️  Database connected ({}) - migrations applied", backend);
+        Ok(Arc::new(Self { pool }))
+    }
+
+    async fn upsert_document<T: Serialize + Sync>(
+        &self,
+        table: &str,
+        id: &str,
+        owner: &str,
+        document: &T,
+    ) -> Result<()> {
+        let data = serde_json::to_string(document)?;
+        let now = now_secs();
+        let query = format!(
+            "INSERT INTO {} (id, owner, data, created_at, updated_at) VALUES ($1, $2, $3, $4, $5) \
+             ON CONFLICT (id) DO UPDATE SET owner = excluded.owner, data = excluded.data, updated_at = excluded.updated_at",
+            table
+        );
+
+        sqlx::query(&query)
+            .bind(id.to_string())
+            .bind(owner.to_string())
+            .bind(data)
+            .bind(now)
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_document(&self, table: &str, id: &str) -> Result<()> {
+        let query = format!("DELETE FROM {} WHERE id = $1", table);
+        sqlx::query(&query)
+            .bind(id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn load_documents<T: DeserializeOwned>(&self, table: &str) -> Result<Vec<T>> {
+        let query = format!("SELECT id, data FROM {} ORDER BY created_at, id", table);
+        let rows = sqlx::query(&query).fetch_all(&self.pool).await?;
+
+        let mut documents = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id: String = row.try_get("id")?;
+            let data: String = row.try_get("data")?;
+            match serde_json::from_str(&data) {
+                Ok(document) => documents.push(document),
+                Err(e) => println!("⚠️  Skipping unreadable row {} in {}: {}", id, table, e),
+            }
+        }
+
+        Ok(documents)
+    }
+}
+
+fn now_secs() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[async_trait]
+impl MemoryRepository for Database {
+    async fn save_memory(&self, memory: &MuseMemory) -> Result<()> {
+        self.upsert_document("muse_memories", &memory.memory_id, &memory.muse_id, memory).await
+    }
+
+    async fn load_memories(&self) -> Result<Vec<MuseMemory>> {
+        self.load_documents("muse_memories").await
+    }
+
+    async fn delete_memory(&self, memory_id: &str) -> Result<()> {
+        self.delete_document("muse_memories", memory_id).await
+    }
+}
+
+#[async_trait]
+impl TemplateRepository for Database {
+    async fn save_template(&self, template: &PromptTemplate) -> Result<()> {
+        self.upsert_document("prompt_templates", &template.id, &template.created_by, template).await
+    }
+
+    async fn load_templates(&self) -> Result<Vec<PromptTemplate>> {
+        self.load_documents("prompt_templates").await
+    }
+
+    async fn delete_template(&self, template_id: &str) -> Result<()> {
+        self.delete_document("prompt_templates", template_id).await
+    }
+}
+
+#[async_trait]
+impl AvatarRepository for Database {
+    async fn save_avatar(&self, avatar: &Avatar) -> Result<()> {
+        self.upsert_document("avatars", &avatar.id, &avatar.created_by, avatar).await
+    }
+
+    async fn load_avatars(&self) -> Result<Vec<Avatar>> {
+        self.load_documents("avatars").await
+    }
+
+    async fn save_collection(&self, collection: &AvatarCollection) -> Result<()> {
+        self.upsert_document("avatar_collections", &collection.id, &collection.owner, collection).await
+    }
+
+    async fn load_collections(&self) -> Result<Vec<AvatarCollection>> {
+        self.load_documents("avatar_collections").await
+    }
+
+    async fn delete_avatar(&self, avatar_id: &str) -> Result<()> {
+        self.delete_document("avatars", avatar_id).await
+    }
+
+    async fn delete_collection(&self, collection_id: &str) -> Result<()> {
+        self.delete_document("avatar_collections", collection_id).await
+    }
+}
+
+#[async_trait]
+impl TrainingDataRepository for Database {
+    async fn save_contribution(&self, contribution: &TrainingDataContribution) -> Result<()> {
+        self.upsert_document(
+            "training_contributions",
+            &contribution.contribution_id,
+            &contribution.contributor_address,
+            contribution,
+        ).await
+    }
+
+    async fn load_contributions(&self) -> Result<Vec<TrainingDataContribution>> {
+        self.load_documents("training_contributions").await
+    }
+
+    async fn save_contributor(&self, profile: &ContributorProfile) -> Result<()> {
+        self.upsert_document("contributor_profiles", &profile.address, &profile.address, profile).await
+    }
+
+    async fn load_contributors(&self) -> Result<Vec<ContributorProfile>> {
+        self.load_documents("contributor_profiles").await
+    }
+
+    async fn delete_contribution(&self, contribution_id: &str) -> Result<()> {
+        self.delete_document("training_contributions", contribution_id).await
+    }
+
+    async fn delete_contributor(&self, address: &str) -> Result<()> {
+        self.delete_document("contributor_profiles", address).await
+    }
+}
+
+#[async_trait]
+impl PluginRepository for Database {
+    async fn save_plugin(&self, plugin: &Plugin) -> Result<()> {
+        self.upsert_document("plugins", &plugin.id, &plugin.creator, plugin).await
+    }
+
+    async fn load_plugins(&self) -> Result<Vec<Plugin>> {
+        self.load_documents("plugins").await
+    }
+
+    async fn save_muse_plugin(&self, muse_id: &str, plugin_id: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO muse_plugins (muse_id, plugin_id, created_at) VALUES ($1, $2, $3) \
+             ON CONFLICT (muse_id, plugin_id) DO NOTHING",
+        )
+        .bind(muse_id.to_string())
+        .bind(plugin_id.to_string())
+        .bind(now_secs())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn load_muse_plugins(&self) -> Result<Vec<(String, String)>> {
+        let rows = sqlx::query("SELECT muse_id, plugin_id FROM muse_plugins ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter()
+            .map(|row| Ok((row.try_get("muse_id")?, row.try_get("plugin_id")?)))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl UserMuseRepository for Database {
+    async fn save_user_muse(&self, user_address: &str, token_id: u64) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_muses (user_address, token_id, created_at) VALUES ($1, $2, $3) \
+             ON CONFLICT (user_address, token_id) DO NOTHING",
+        )
+        .bind(user_address.to_string())
+        .bind(token_id as i64)
+        .bind(now_secs())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn load_user_muses(&self) -> Result<HashMap<String, Vec<u64>>> {
+        let rows = sqlx::query("SELECT user_address, token_id FROM user_muses ORDER BY created_at, token_id")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut user_muses: HashMap<String, Vec<u64>> = HashMap::new();
+        for row in rows {
+            let user_address: String = row.try_get("user_address")?;
+            let token_id: i64 = row.try_get("token_id")?;
+            user_muses.entry(user_address).or_default().push(token_id as u64);
+        }
+
+        Ok(user_muses)
+    }
+}
+
+#[async_trait]
+impl KeyRepository for Database {
+    async fn save_public_key(&self, key: &UserPublicKey) -> Result<()> {
+        self.upsert_document("user_public_keys", &key.address, &key.address, key).await
+    }
+
+    async fn load_public_keys(&self) -> Result<Vec<UserPublicKey>> {
+        self.load_documents("user_public_keys").await
+    }
+
+    async fn save_wrapped_key(&self, key: &WrappedMuseKey) -> Result<()> {
+        self.upsert_document("wrapped_muse_keys", &key.storage_id(), &key.muse_id, key).await
+    }
+
+    async fn delete_wrapped_key(&self, key: &WrappedMuseKey) -> Result<()> {
+        self.delete_document("wrapped_muse_keys", &key.storage_id()).await
+    }
+
+    async fn load_wrapped_keys(&self) -> Result<Vec<WrappedMuseKey>> {
+        self.load_documents("wrapped_muse_keys").await
+    }
+}
+
+#[async_trait]
+impl ErasureRepository for Database {
+    async fn save_receipt(&self, receipt: &ErasureReceipt) -> Result<()> {
+        self.upsert_document("erasure_receipts", &receipt.receipt_id, &receipt.user_address, receipt).await
+    }
+
+    async fn load_receipts(&self) -> Result<Vec<ErasureReceipt>> {
+        self.load_documents("erasure_receipts").await
+    }
+}
+
+#[async_trait]
+impl RatingRepository for Database {
+    async fn save_rating(&self, record: &RatingRecord) -> Result<()> {
+        self.upsert_document("interaction_ratings", &record.rating_id, &record.rating.user_address.to_lowercase(), record).await
+    }
+
+    async fn load_ratings(&self) -> Result<Vec<RatingRecord>> {
+        self.load_documents("interaction_ratings").await
+    }
+}
+
+#[async_trait]
+impl CommitmentRepository for Database {
+    async fn save_commitment(&self, record: &CommitmentRecord) -> Result<()> {
+        self.upsert_document("interaction_commitments", &record.interaction_id, &record.user_address.to_lowercase(), record).await
+    }
+
+    async fn load_commitments(&self) -> Result<Vec<CommitmentRecord>> {
+        self.load_documents("interaction_commitments").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::avatar_system::AvatarManager;
+    use crate::plugin_system::{PluginCategory, PluginSystem};
+    use crate::template_system::TemplateManager;
+    use serde_json::json;
+
+    const ALICE: &str = "0x00000000000000000000000000000000000a11ce";
+
+    async fn database() -> Arc<Database> {
+        Database::open("sqlite::memory:", 1).await.unwrap()
+    }
+
+    fn fixture<T: DeserializeOwned>(value: serde_json::Value) -> T {
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// Records have no `PartialEq`, so compare what would be stored
+    fn assert_same<T: Serialize>(loaded: &[T], expected: &[T]) {
+        assert_eq!(serde_json::to_value(loaded).unwrap(), serde_json::to_value(expected).unwrap());
+    }
+
+    fn memory(memory_id: &str) -> MuseMemory {
+        fixture(json!({
+            "memory_id": memory_id,
+            "muse_id": "7",
+            "interaction_data": {
+                "user_prompt": "What do you remember?",
+                "ai_response": "Everything you told me.",
+                "personality_traits": { "creativity": 70, "wisdom": 60, "humor": 50, "empathy": 80 },
+                "context_used": [],
+                "session_id": "session_1",
+                "conversation_turn": 1,
+                "response_time_ms": 120,
+                "model_used": "mock",
+                "prompt_tokens": 12,
+                "response_tokens": 5,
+                "user_satisfaction": null,
+                "user_address": ALICE,
+            },
+            "embedding": [0.25, 0.5],
+            "importance": 0.5,
+            "timestamp": 1_700_000_000u64,
+            "ipfs_hash": null,
+            "tags": ["recall"],
+            "category": "Conversation",
+            "emotional_tone": null,
+            "context_window": null,
+            "version": 1,
+            "access_count": 0,
+            "last_accessed": 1_700_000_000u64,
+            "retention_priority": "Medium",
+        }))
+    }
+
+    fn plugin(id: &str) -> Plugin {
+        Plugin {
+            id: id.to_string(),
+            name: "Rhymer".to_string(),
+            description: "Answers in rhyme".to_string(),
+            wasm_hash: "bafkreirhymer".to_string(),
+            category: PluginCategory::Creative,
+            creator: ALICE.to_string(),
+            version: "1.0.0".to_string(),
+            active: true,
+        }
+    }
+
+    fn collection(id: &str) -> AvatarCollection {
+        fixture(json!({
+            "id": id,
+            "name": "Favourites",
+            "description": "Avatars I like",
+            "owner": ALICE,
+            "avatar_ids": ["avatar_1"],
+            "is_public": false,
+            "created_at": "2024-01-01T00:00:00Z",
+            "updated_at": "2024-01-01T00:00:00Z",
+        }))
+    }
+
+    #[tokio::test]
+    async fn memories_round_trip() {
+        let db = database().await;
+        let (first, mut second) = (memory("memory_1"), memory("memory_2"));
+        db.save_memory(&first).await.unwrap();
+        db.save_memory(&second).await.unwrap();
+
+        // Saving again replaces the stored document
+        second.version = 2;
+        second.tags.push("edited".to_string());
+        db.save_memory(&second).await.unwrap();
+        assert_same(&db.load_memories().await.unwrap(), &[first.clone(), second]);
+
+        db.delete_memory("memory_2").await.unwrap();
+        assert_same(&db.load_memories().await.unwrap(), &[first]);
+    }
+
+    #[tokio::test]
+    async fn templates_round_trip() {
+        let db = database().await;
+        let mut template = TemplateManager::new().get_template("supportive_friend").cloned().unwrap();
+        template.id = "template_1".to_string();
+        template.is_custom = true;
+        template.created_by = ALICE.to_string();
+
+        db.save_template(&template).await.unwrap();
+        assert_same(&db.load_templates().await.unwrap(), &[template]);
+
+        db.delete_template("template_1").await.unwrap();
+        assert!(db.load_templates().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn avatars_and_collections_round_trip() {
+        let db = database().await;
+        let avatar: Avatar = fixture(json!({
+            "id": "avatar_1",
+            "name": "Sunny",
+            "ipfs_hash": "bafkreisunny",
+            "cdn_url": null,
+            "local_path": null,
+            "format": "PNG",
+            "dimensions": { "width": 512, "height": 512 },
+            "file_size": 2048,
+            "created_by": ALICE,
+            "is_public": true,
+            "tags": ["bright"],
+            "category": "UserUpload",
+            "usage_count": 0,
+            "last_used": "2024-01-01T00:00:00Z",
+            "generated_description": null,
+            "color_palette": ["#ffcc00"],
+            "style": "Cartoon",
+            "mood": "Happy",
+            "price_muse_tokens": 0,
+            "rating": 0.0,
+            "rating_count": 0,
+            "created_at": "2024-01-01T00:00:00Z",
+            "updated_at": "2024-01-01T00:00:00Z",
+            "version": "1.0.0",
+        }));
+        let collection = collection("collection_1");
+
+        db.save_avatar(&avatar).await.unwrap();
+        db.save_collection(&collection).await.unwrap();
+        assert_same(&db.load_avatars().await.unwrap(), &[avatar]);
+        assert_same(&db.load_collections().await.unwrap(), &[collection]);
+
+        db.delete_avatar("avatar_1").await.unwrap();
+        db.delete_collection("collection_1").await.unwrap();
+        assert!(db.load_avatars().await.unwrap().is_empty());
+        assert!(db.load_collections().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn training_data_round_trips() {
+        let db = database().await;
+        let contribution: TrainingDataContribution = fixture(json!({
+            "contribution_id": "contribution_1",
+            "contributor_address": ALICE,
+            "muse_token_id": 7,
+            "contribution_type": "ConversationCorrection",
+            "original_data": { "response": "Teh answer" },
+            "improved_data": { "response": "The answer" },
+            "metadata": {
+                "message_id": "ai_msg_1",
+                "session_id": "session_1",
+                "user_comment": null,
+                "difficulty_level": 2,
+                "improvement_type": "grammar",
+                "reference_urls": [],
+                "tags": [],
+            },
+            "data_hash": "0xabc",
+            "ipfs_hash": "bafkreicontribution",
+            "timestamp": "2024-01-01T00:00:00Z",
+            "quality_score": 80,
+            "reward_amount": 0,
+            "validation_status": "Pending",
+        }));
+        let profile: ContributorProfile = fixture(json!({
+            "address": ALICE,
+            "total_contributions": 1,
+            "total_dats_earned": 0,
+            "average_quality_score": 80.0,
+            "validations_passed": 0,
+            "current_streak": 1,
+            "last_contribution": "2024-01-01T00:00:00Z",
+            "quality_contributor_badge": false,
+            "specializations": ["ConversationCorrection"],
+            "contribution_history": ["contribution_1"],
+        }));
+
+        db.save_contribution(&contribution).await.unwrap();
+        db.save_contributor(&profile).await.unwrap();
+        assert_same(&db.load_contributions().await.unwrap(), &[contribution]);
+        assert_same(&db.load_contributors().await.unwrap(), &[profile]);
+
+        db.delete_contribution("contribution_1").await.unwrap();
+        db.delete_contributor(ALICE).await.unwrap();
+        assert!(db.load_contributions().await.unwrap().is_empty());
+        assert!(db.load_contributors().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn plugins_and_installs_round_trip() {
+        let db = database().await;
+        db.save_plugin(&plugin("rhymer")).await.unwrap();
+        db.save_muse_plugin("7", "rhymer").await.unwrap();
+        // Installing twice keeps one row
+        db.save_muse_plugin("7", "rhymer").await.unwrap();
+
+        assert_same(&db.load_plugins().await.unwrap(), &[plugin("rhymer")]);
+        assert_eq!(db.load_muse_plugins().await.unwrap(), vec![("7".to_string(), "rhymer".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn user_muses_round_trip() {
+        let db = database().await;
+        db.save_user_muse(ALICE, 7).await.unwrap();
+        db.save_user_muse(ALICE, 9).await.unwrap();
+        db.save_user_muse(ALICE, 7).await.unwrap();
+
+        let user_muses = db.load_user_muses().await.unwrap();
+        assert_eq!(user_muses.len(), 1);
+        let mut token_ids = user_muses[ALICE].clone();
+        token_ids.sort();
+        assert_eq!(token_ids, vec![7, 9]);
+    }
+
+    #[tokio::test]
+    async fn keys_round_trip() {
+        let db = database().await;
+        let public_key = UserPublicKey { address: ALICE.to_string(), public_key: "02ab".to_string(), registered_at: 1 };
+        let wrapped: WrappedMuseKey = fixture(json!({
+            "muse_id": "7",
+            "owner": ALICE,
+            "recipient": ALICE,
+            "key_id": "key_1",
+            "ephemeral_public_key": "03cd",
+            "nonce": "00",
+            "wrapped_key": "ff",
+            "wrapped_by": ALICE,
+            "created_at": 1,
+        }));
+
+        db.save_public_key(&public_key).await.unwrap();
+        db.save_wrapped_key(&wrapped).await.unwrap();
+        assert_same(&db.load_public_keys().await.unwrap(), &[public_key]);
+        assert_same(&db.load_wrapped_keys().await.unwrap(), std::slice::from_ref(&wrapped));
+
+        db.delete_wrapped_key(&wrapped).await.unwrap();
+        assert!(db.load_wrapped_keys().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn ledgers_round_trip() {
+        let db = database().await;
+        let receipt: ErasureReceipt = fixture(json!({
+            "receipt_id": "receipt_1",
+            "user_address": ALICE,
+            "requested_at": 1,
+            "completed_at": 2,
+            "summary": {
+                "chat_sessions": { "erased": 1, "unpinned": 1, "unpin_failures": 0 },
+                "memories": { "erased": 0, "unpinned": 0, "unpin_failures": 0 },
+                "semantic_entries": { "erased": 0, "unpinned": 0, "unpin_failures": 0 },
+                "training_contributions": { "erased": 0, "unpinned": 0, "unpin_failures": 0 },
+                "avatars": { "erased": 0, "unpinned": 0, "unpin_failures": 0 },
+                "avatar_collections": 0,
+                "retained_contributions": [],
+                "retained": [],
+            },
+            "digest": "0x01",
+            "signer": "0x02",
+            "signature": "0x03",
+        }));
+        let rating: RatingRecord = fixture(json!({
+            "rating_id": "rating_1",
+            "rating": {
+                "muse_id": 7,
+                "interaction_hash": "0xabc",
+                "quality_score": 8,
+                "personality_accuracy": 7,
+                "helpfulness": 9,
+                "feedback": "Great",
+                "user_address": ALICE,
+            },
+            "transaction_hash": null,
+            "reward_amount": 0,
+            "submitted_at": 3,
+        }));
+        let commitment: CommitmentRecord = fixture(json!({
+            "interaction_id": "interaction_7_1",
+            "muse_id": 7,
+            "user_address": ALICE,
+            "session_id": "session_1",
+            "message_id": "ai_msg_1",
+            "muse_dna_hash": "0x04",
+            "commitment_hash": "0x05",
+            "signature": "0x06",
+            "recovery_id": 0,
+            "signer": "0x07",
+            "foreign": false,
+            "created_at": 4,
+        }));
+
+        db.save_receipt(&receipt).await.unwrap();
+        db.save_rating(&rating).await.unwrap();
+        db.save_commitment(&commitment).await.unwrap();
+        assert_same(&db.load_receipts().await.unwrap(), &[receipt]);
+        assert_same(&db.load_ratings().await.unwrap(), &[rating]);
+        assert_same(&db.load_commitments().await.unwrap(), &[commitment]);
+    }
+
+    #[tokio::test]
+    async fn unreadable_rows_are_skipped() {
+        let db = database().await;
+        db.save_plugin(&plugin("rhymer")).await.unwrap();
+        db.upsert_document("plugins", "broken", ALICE, &json!({ "id": "broken" })).await.unwrap();
+
+        assert_same(&db.load_plugins().await.unwrap(), &[plugin("rhymer")]);
+    }
+
+    #[tokio::test]
+    async fn templates_are_rehydrated_at_startup() {
+        let db = database().await;
+        let mut manager = TemplateManager::new().with_repository(db.clone()).await.unwrap();
+        let draft = manager.get_template("wise_counselor").cloned().unwrap();
+        let template_id = manager.create_template(draft.clone(), ALICE).await.unwrap();
+        let deleted_id = manager.create_template(draft.clone(), ALICE).await.unwrap();
+        manager.update_template(&template_id, PromptTemplate { name: "Counselor, edited".to_string(), ..draft }).await.unwrap();
+        manager.delete_template(&deleted_id).await.unwrap();
+        manager.rate_template("supportive_friend", 5).await.unwrap();
+
+        let restarted = TemplateManager::new().with_repository(db).await.unwrap();
+        let template = restarted.get_template(&template_id).unwrap();
+        assert_eq!(template.name, "Counselor, edited");
+        assert_eq!(template.created_by, ALICE);
+        assert!(restarted.get_template(&deleted_id).is_none());
+        assert_eq!(restarted.get_user_templates(ALICE).len(), 1);
+        // Ratings of prebuilt templates survive a restart too
+        assert_eq!(restarted.get_template("supportive_friend").unwrap().rating_count, 1);
+    }
+
+    #[tokio::test]
+    async fn plugins_and_avatars_are_rehydrated_at_startup() {
+        let db = database().await;
+        let plugins = PluginSystem::new().await.unwrap().with_repository(db.clone()).await.unwrap();
+        plugins.register_plugin(plugin("rhymer")).await.unwrap();
+        plugins.install_plugin_for_muse("7", "rhymer").await.unwrap();
+        db.save_collection(&collection("collection_1")).await.unwrap();
+
+        let plugins = PluginSystem::new().await.unwrap().with_repository(db.clone()).await.unwrap();
+        let installed = plugins.get_muse_plugins("7").await.unwrap();
+        assert_eq!(installed.iter().map(|p| p.id.as_str()).collect::<Vec<_>>(), vec!["rhymer"]);
+
+        let avatars = AvatarManager::new().with_repository(db).await.unwrap();
+        assert_eq!(avatars.get_user_collections(ALICE).len(), 1);
+    }
+}
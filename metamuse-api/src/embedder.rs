@@ -0,0 +1,282 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::ai_worker_pool::AIWorkerPool;
+use crate::config::Config;
+use crate::vector_index::snapshot_embedders;
+
+/// Text embedding model shared by semantic search and the memory system.
+///
+/// Every vector has exactly `dimension()` entries and unit length, so cosine
+/// similarity reduces to a dot product and vectors from either subsystem compare.
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn dimension(&self) -> usize;
+
+    /// Identifies the vector space; vectors are only comparable between equal identities
+    fn identity(&self) -> String {
+        format!("{}:{}", self.name(), self.dimension())
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed_batch(&[text.to_string()]).await?
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("Embedder returned no vector"))
+    }
+}
+
+/// Use the configured GGUF embedding model when it is on disk, otherwise fall back
+/// to the lexical hashing embedder so search still works offline and in CI.
+/// Falling back is refused when existing index snapshots were built by another
+/// embedder, unless `EMBEDDING_ALLOW_FALLBACK` accepts rebuilding them.
+pub async fn create_embedder(config: &Config) -> Result<Arc<dyn Embedder>> {
+    let problem = if Path::new(&config.embedding_model_path).exists() {
+        match LlamaEmbedder::new(config).await {
+            Ok(embedder) => {
+                // A model of the wrong width is a configuration error, not a reason to fall back
+                embedder.check_dimension().await?;
+                println!("📐 Embedding model loaded: {} ({} dims)", config.embedding_model_path, config.embedding_dimension);
+                return Ok(Arc::new(embedder));
+            }
+            Err(e) => format!("failed to start: {}", e),
+        }
+    } else {
+        format!("not found at {}", config.embedding_model_path)
+    };
+    println!("⚠️  Embedding model {}", problem);
+
+    let fallback = HashingEmbedder::new(config.embedding_dimension);
+    let model_built: Vec<String> = snapshot_embedders(&config.vector_index_dir)
+        .into_iter()
+        // Earlier hashing embedders count as hashing too; their indexes are rebuilt either way
+        .filter(|identity| !identity.starts_with("hashing"))
+        .collect();
+    if !model_built.is_empty() {
+        if !config.embedding_allow_fallback {
+            return Err(anyhow::anyhow!(
+                "Embedding model {} and the vector indexes in {} were built by {}. \
+                 Restore the model, or set EMBEDDING_ALLOW_FALLBACK=true to rebuild them with hashing embeddings",
+                problem, config.vector_index_dir, model_built.join(", ")
+            ));
+        }
+        println!("⚠️  EMBEDDING_ALLOW_FALLBACK is set - indexes built by {} will be rebuilt", model_built.join(", "));
+    }
+
+    println!("   Falling back to lexical hashing embeddings ({} dims)", config.embedding_dimension);
+    Ok(Arc::new(fallback))
+}
+
+/// Sentence-embedding GGUF run by `ai-worker --embedding-model` processes.
+///
+/// Inference runs out of process because llama.cpp's backend can only be
+/// initialised once and the API process already hosts the chat engine.
+pub struct LlamaEmbedder {
+    pool: Arc<AIWorkerPool>,
+    // Model file name, part of the embedder identity
+    model: String,
+    dimension: usize,
+    batch_size: usize,
+}
+
+impl LlamaEmbedder {
+    pub async fn new(config: &Config) -> Result<Self> {
+        let pool = AIWorkerPool::new_embedding(config, &config.embedding_model_path).await?;
+        pool.start_health_monitor(std::time::Duration::from_secs(config.ai_worker_health_check_interval_secs));
+
+        let model = Path::new(&config.embedding_model_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| config.embedding_model_path.clone());
+
+        Ok(Self {
+            pool,
+            model,
+            dimension: config.embedding_dimension.max(1),
+            batch_size: config.embedding_batch_size.max(1),
+        })
+    }
+
+    /// Embed a probe text and require the model's width to equal `EMBEDDING_DIMENSION`
+    pub async fn check_dimension(&self) -> Result<()> {
+        let width = self.pool.embed(vec!["dimension probe".to_string()]).await?
+            .first()
+            .map_or(0, |embedding| embedding.len());
+        if width != self.dimension {
+            return Err(anyhow::anyhow!(
+                "Embedding model {} produces {}-dimensional vectors but EMBEDDING_DIMENSION is {}",
+                self.model, width, self.dimension
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Embedder for LlamaEmbedder {
+    fn name(&self) -> &str {
+        "llama.cpp"
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn identity(&self) -> String {
+        format!("{}:{}:{}", self.name(), self.model, self.dimension)
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for chunk in texts.chunks(self.batch_size) {
+            for embedding in self.pool.embed(chunk.to_vec()).await? {
+                if embedding.len() != self.dimension {
+                    return Err(anyhow::anyhow!(
+                        "Embedding model returned {} dimensions, expected {}", embedding.len(), self.dimension
+                    ));
+                }
+                embeddings.push(normalize_dimension(embedding, self.dimension));
+            }
+        }
+        Ok(embeddings)
+    }
+}
+
+/// Signed feature hashing of word unigrams and bigrams. Captures lexical overlap
+/// only, but is deterministic and needs no model file.
+pub struct HashingEmbedder {
+    dimension: usize,
+}
+
+impl HashingEmbedder {
+    pub fn new(dimension: usize) -> Self {
+        Self { dimension: dimension.max(1) }
+    }
+
+    fn embed_text(&self, text: &str) -> Vec<f32> {
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+
+        let mut embedding = vec![0.0; self.dimension];
+        let mut add_feature = |feature: &str, weight: f32| {
+            let hash = fnv1a(feature.as_bytes());
+            let index = (hash % self.dimension as u64) as usize;
+            // A second hash bit picks the sign so collisions tend to cancel out
+            let sign = if (hash >> 63) == 0 { 1.0 } else { -1.0 };
+            embedding[index] += sign * weight;
+        };
+
+        for word in &words {
+            add_feature(word, 1.0);
+        }
+        for pair in words.windows(2) {
+            add_feature(&format!("{} {}", pair[0], pair[1]), 0.5);
+        }
+
+        normalize_dimension(embedding, self.dimension)
+    }
+}
+
+#[async_trait]
+impl Embedder for HashingEmbedder {
+    fn name(&self) -> &str {
+        // Versioned, since vectors from the earlier `DefaultHasher` variant live on in snapshots
+        "hashing-fnv1a"
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| self.embed_text(text)).collect())
+    }
+}
+
+/// 64-bit FNV-1a. Unlike `DefaultHasher` its output never changes between Rust
+/// releases, which matters because hashing vectors are persisted in index snapshots.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| (hash ^ byte as u64).wrapping_mul(PRIME))
+}
+
+/// Truncate or zero-pad to `dimension`, then scale to unit length
+pub fn normalize_dimension(mut embedding: Vec<f32>, dimension: usize) -> Vec<f32> {
+    embedding.resize(dimension, 0.0);
+
+    let magnitude: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if magnitude > 0.0 {
+        for value in &mut embedding {
+            *value /= magnitude;
+        }
+    }
+    embedding
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dot(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn fnv1a_matches_reference_vectors() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[tokio::test]
+    async fn hashing_vectors_are_unit_length_and_stable() {
+        let embedder = HashingEmbedder::new(64);
+        let vector = embedder.embed("The cat sat on the mat").await.unwrap();
+
+        assert_eq!(vector.len(), 64);
+        assert!((dot(&vector, &vector) - 1.0).abs() < 1e-5);
+        // Case and punctuation don't change the features
+        assert_eq!(vector, embedder.embed("the CAT sat, on the mat!").await.unwrap());
+        // Pinned so a hasher change can't silently invalidate persisted snapshots
+        let nonzero: Vec<usize> = vector.iter().enumerate().filter(|(_, v)| **v != 0.0).map(|(i, _)| i).collect();
+        assert_eq!(nonzero, vec![11, 14, 24, 29, 32, 39, 48, 55, 60]);
+    }
+
+    #[tokio::test]
+    async fn related_texts_score_higher_than_unrelated_ones() {
+        let embedder = HashingEmbedder::new(256);
+        let texts = [
+            "I love hiking in the mountains".to_string(),
+            "hiking in the mountains is what I love".to_string(),
+            "quarterly tax returns are due".to_string(),
+        ];
+        let vectors = embedder.embed_batch(&texts).await.unwrap();
+
+        assert_eq!(vectors.len(), 3);
+        assert!(dot(&vectors[0], &vectors[1]) > dot(&vectors[0], &vectors[2]));
+    }
+
+    #[tokio::test]
+    async fn empty_text_embeds_to_zeros() {
+        let embedder = HashingEmbedder::new(8);
+        assert_eq!(embedder.embed("  ...  ").await.unwrap(), vec![0.0; 8]);
+        assert_eq!(embedder.identity(), "hashing-fnv1a:8");
+    }
+
+    #[test]
+    fn normalize_pads_truncates_and_scales() {
+        assert_eq!(normalize_dimension(vec![3.0, 4.0], 3), vec![0.6, 0.8, 0.0]);
+        assert_eq!(normalize_dimension(vec![0.0, 5.0, 1.0], 2), vec![0.0, 1.0]);
+        assert_eq!(normalize_dimension(vec![], 2), vec![0.0, 0.0]);
+    }
+}
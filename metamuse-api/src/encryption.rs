@@ -0,0 +1,677 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use hkdf::Hkdf;
+use secp256k1::ecdh::SharedSecret;
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::config::Config;
+use crate::verification::{hex_string_to_bytes, recover_personal_sign_address};
+
+const ENVELOPE_VERSION: u32 = 1;
+const ENVELOPE_ALGORITHM: &str = "XChaCha20-Poly1305";
+const USER_KEY_SALT: &[u8] = b"metamuse-user-key-v1";
+const KEY_WRAP_SALT: &[u8] = b"metamuse-key-wrap-v1";
+
+/// Message a wallet signs to derive its data key. Wallets sign deterministically
+/// (RFC 6979), so the same wallet always derives the same key.
+pub fn key_derivation_message(address: &str) -> String {
+    format!(
+        "MetaMuse data key v1\nAddress: {}\n\nSign this message to unlock your encrypted MetaMuse conversations and memories. It does not send a transaction or cost gas.",
+        address.to_lowercase()
+    )
+}
+
+/// A user's registered public key. The matching secret key only exists in memory
+/// while the user has unlocked it with their wallet signature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserPublicKey {
+    pub address: String,
+    pub public_key: String,
+    pub registered_at: u64,
+}
+
+/// A data key wrapped (ECIES over secp256k1) for one recipient. Each user has their own
+/// data keys for each muse, so a copy only opens the `owner`'s sessions and memories.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WrappedMuseKey {
+    pub muse_id: String,
+    /// User whose content the key seals
+    pub owner: String,
+    pub recipient: String,
+    pub key_id: String,
+    pub ephemeral_public_key: String,
+    pub nonce: String,
+    pub wrapped_key: String,
+    pub wrapped_by: String,
+    pub created_at: u64,
+}
+
+impl WrappedMuseKey {
+    /// Storage id of this copy
+    pub fn storage_id(&self) -> String {
+        format!("{}:{}:{}:{}", self.muse_id, self.owner, self.recipient, self.key_id)
+    }
+}
+
+/// Encrypted object as stored on IPFS. The payload is sealed with a random data
+/// encryption key, which is itself sealed with the owner's data key for the muse.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedEnvelope {
+    pub metamuse_envelope: u32,
+    pub algorithm: String,
+    pub muse_id: String,
+    /// User whose data key sealed the content
+    pub owner: String,
+    pub key_id: String,
+    pub dek_nonce: String,
+    pub wrapped_dek: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+/// Content could not be sealed because encryption is required and its owner's data key is
+/// locked or was never registered. Returned inside `anyhow::Error`, so callers can tell it
+/// apart with `downcast_ref` and ask the user to unlock their key.
+#[derive(Debug)]
+pub struct DataKeyLocked {
+    pub muse_id: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for DataKeyLocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Refusing to store muse {} content unencrypted, unlock your data key first: {}", self.muse_id, self.reason)
+    }
+}
+
+impl std::error::Error for DataKeyLocked {}
+
+/// Durable storage for public keys and wrapped data keys. Nothing stored here can
+/// decrypt data without a wallet signature.
+#[async_trait]
+pub trait KeyRepository: Send + Sync {
+    async fn save_public_key(&self, key: &UserPublicKey) -> Result<()>;
+    async fn load_public_keys(&self) -> Result<Vec<UserPublicKey>>;
+    async fn save_wrapped_key(&self, key: &WrappedMuseKey) -> Result<()>;
+    async fn delete_wrapped_key(&self, key: &WrappedMuseKey) -> Result<()>;
+    async fn load_wrapped_keys(&self) -> Result<Vec<WrappedMuseKey>>;
+}
+
+/// Envelope encryption for chat sessions and memories stored on IPFS.
+///
+/// Key hierarchy:
+/// - user key: secp256k1 key derived from the wallet's signature over `key_derivation_message`
+/// - data key: random key per (muse, user), wrapped for that user and anyone they share it with.
+///   Revoking a share rotates it, so the revoked user cannot read anything written afterwards.
+/// - content key: random key per stored object, wrapped with the data key
+pub struct EncryptionService {
+    required: bool,
+    secp: Secp256k1<secp256k1::All>,
+    // lowercase address -> registered public key
+    public_keys: RwLock<HashMap<String, UserPublicKey>>,
+    // lowercase address -> secret key, present while the user is unlocked
+    unlocked_keys: RwLock<HashMap<String, SecretKey>>,
+    // muse_id -> every wrapped copy of the muse's data keys, old versions included
+    wrapped_keys: RwLock<HashMap<String, Vec<WrappedMuseKey>>>,
+    // (lowercase holder, key_id) -> unwrapped data key, dropped when the holder locks
+    data_keys: RwLock<HashMap<(String, String), [u8; 32]>>,
+    repository: Option<Arc<dyn KeyRepository>>,
+}
+
+impl EncryptionService {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            required: config.require_storage_encryption,
+            secp: Secp256k1::new(),
+            public_keys: RwLock::new(HashMap::new()),
+            unlocked_keys: RwLock::new(HashMap::new()),
+            wrapped_keys: RwLock::new(HashMap::new()),
+            data_keys: RwLock::new(HashMap::new()),
+            repository: None,
+        }
+    }
+
+    /// Attach durable storage and rehydrate public keys and wrapped data keys
+    pub async fn with_repository(mut self, repository: Arc<dyn KeyRepository>) -> Result<Self> {
+        let public_keys = repository.load_public_keys().await?;
+        let wrapped_keys = repository.load_wrapped_keys().await?;
+        println!("🔑 Rehydrated {} user keys and {} wrapped data keys from database", public_keys.len(), wrapped_keys.len());
+
+        {
+            let mut keys = self.public_keys.write().await;
+            for key in public_keys {
+                keys.insert(key.address.to_lowercase(), key);
+            }
+        }
+        {
+            let mut wrapped = self.wrapped_keys.write().await;
+            for key in wrapped_keys {
+                wrapped.entry(key.muse_id.clone()).or_insert_with(Vec::new).push(key);
+            }
+        }
+
+        self.repository = Some(repository);
+        Ok(self)
+    }
+
+    /// Derive the caller's user key from their wallet signature and keep it in memory
+    pub async fn unlock(&self, address: &str, signature: &str) -> Result<UserPublicKey> {
+        let message = key_derivation_message(address);
+        let signature_bytes = hex_string_to_bytes(signature)?;
+        let signer = recover_personal_sign_address(message.as_bytes(), &signature_bytes)?;
+        if !signer.eq_ignore_ascii_case(address) {
+            return Err(anyhow!("Key signature was produced by {}, not {}", signer, address));
+        }
+
+        let secret_key = derive_user_key(address, &signature_bytes)?;
+        self.unlock_with_secret(address, secret_key).await
+    }
+
+    async fn unlock_with_secret(&self, address: &str, secret_key: SecretKey) -> Result<UserPublicKey> {
+        let address = address.to_lowercase();
+        let public_key = PublicKey::from_secret_key(&self.secp, &secret_key);
+
+        let registered = UserPublicKey {
+            address: address.clone(),
+            public_key: hex::encode(public_key.serialize()),
+            registered_at: now_secs(),
+        };
+
+        let is_new = {
+            let mut public_keys = self.public_keys.write().await;
+            match public_keys.get(&address) {
+                Some(existing) if existing.public_key == registered.public_key => false,
+                Some(_) => return Err(anyhow!("Signature derives a different key than the one registered for {}", address)),
+                None => {
+                    public_keys.insert(address.clone(), registered.clone());
+                    true
+                }
+            }
+        };
+        if is_new {
+            if let Some(repository) = &self.repository {
+                if let Err(e) = repository.save_public_key(&registered).await {
+                    println!("⚠️  Failed to persist public key for {}: {}", address, e);
+                }
+            }
+        }
+
+        self.unlocked_keys.write().await.insert(address.clone(), secret_key);
+        println!("🔓 Data key unlocked for {}", address);
+
+        Ok(self.public_keys.read().await.get(&address).cloned().unwrap_or(registered))
+    }
+
+    /// Forget the caller's user key and every data key unwrapped with it
+    pub async fn lock(&self, address: &str) -> bool {
+        let address = address.to_lowercase();
+        self.data_keys.write().await.retain(|(holder, _), _| *holder != address);
+        self.unlocked_keys.write().await.remove(&address).is_some()
+    }
+
+    pub async fn public_key(&self, address: &str) -> Option<UserPublicKey> {
+        self.public_keys.read().await.get(&address.to_lowercase()).cloned()
+    }
+
+    /// Encrypt `owner`'s content with the muse for storage under the owner's data key.
+    ///
+    /// Returns the plaintext unchanged when no key is available and encryption is not required.
+    pub async fn seal(&self, muse_id: &str, owner: Option<&str>, plaintext: Vec<u8>) -> Result<Vec<u8>> {
+        let data_key = match owner {
+            Some(owner) => self.current_data_key(muse_id, owner).await,
+            None => Err(anyhow!("content has no owner to seal it for")),
+        };
+        let (owner, key_id, data_key) = match data_key {
+            Ok(key) => key,
+            Err(e) if !self.required => {
+                println!("⚠️  Storing muse {} content unencrypted: {}", muse_id, e);
+                return Ok(plaintext);
+            }
+            Err(e) => return Err(DataKeyLocked { muse_id: muse_id.to_string(), reason: e.to_string() }.into()),
+        };
+
+        let aad = content_aad(muse_id, &owner);
+        let dek = XChaCha20Poly1305::generate_key(&mut OsRng);
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = XChaCha20Poly1305::new(&dek)
+            .encrypt(&nonce, Payload { msg: &plaintext, aad: aad.as_bytes() })
+            .map_err(|_| anyhow!("Failed to encrypt content"))?;
+
+        let dek_nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let wrapped_dek = XChaCha20Poly1305::new((&data_key).into())
+            .encrypt(&dek_nonce, Payload { msg: dek.as_slice(), aad: aad.as_bytes() })
+            .map_err(|_| anyhow!("Failed to wrap content key"))?;
+
+        let envelope = EncryptedEnvelope {
+            metamuse_envelope: ENVELOPE_VERSION,
+            algorithm: ENVELOPE_ALGORITHM.to_string(),
+            muse_id: muse_id.to_string(),
+            owner,
+            key_id,
+            dek_nonce: hex::encode(dek_nonce),
+            wrapped_dek: hex::encode(wrapped_dek),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        };
+
+        Ok(serde_json::to_vec(&envelope)?)
+    }
+
+    /// Decrypt content produced by `seal` for `requester`, who must be unlocked and hold a
+    /// copy of the key it was sealed with. Plaintext stored before encryption was enabled is
+    /// passed through as-is, unless encryption is required.
+    pub async fn open(&self, requester: &str, stored: Vec<u8>) -> Result<Vec<u8>> {
+        let envelope = match serde_json::from_slice::<EncryptedEnvelope>(&stored) {
+            Ok(envelope) => envelope,
+            Err(_) if !self.required => return Ok(stored),
+            Err(_) => return Err(anyhow!("Refusing to read unencrypted content while storage encryption is required")),
+        };
+        if envelope.metamuse_envelope != ENVELOPE_VERSION || envelope.algorithm != ENVELOPE_ALGORITHM {
+            return Err(anyhow!("Unsupported envelope {} ({})", envelope.metamuse_envelope, envelope.algorithm));
+        }
+
+        let requester = requester.to_lowercase();
+        let copy = self.wrapped_keys.read().await
+            .get(&envelope.muse_id)
+            .and_then(|copies| copies.iter()
+                .find(|copy| copy.recipient == requester && copy.owner == envelope.owner && copy.key_id == envelope.key_id)
+                .cloned())
+            .ok_or_else(|| anyhow!("{} holds no key for this content of muse {}", requester, envelope.muse_id))?;
+        let data_key = self.unwrap_for(&requester, &copy).await?;
+
+        let aad = content_aad(&envelope.muse_id, &envelope.owner);
+        let dek = XChaCha20Poly1305::new((&data_key).into())
+            .decrypt(&decode_nonce(&envelope.dek_nonce)?, Payload { msg: &hex::decode(&envelope.wrapped_dek)?, aad: aad.as_bytes() })
+            .map_err(|_| anyhow!("Failed to unwrap content key"))?;
+        if dek.len() != 32 {
+            return Err(anyhow!("Unwrapped content key has the wrong length"));
+        }
+
+        XChaCha20Poly1305::new(dek.as_slice().into())
+            .decrypt(&decode_nonce(&envelope.nonce)?, Payload { msg: &hex::decode(&envelope.ciphertext)?, aad: aad.as_bytes() })
+            .map_err(|_| anyhow!("Failed to decrypt content (tampered or wrong key)"))
+    }
+
+    /// Wrap `owner`'s data keys for the muse, old versions included, for `recipient` so they can
+    /// read the owner's sessions and memories with it. The owner must be unlocked.
+    pub async fn share_muse_key(&self, muse_id: &str, owner: &str, recipient: &str) -> Result<WrappedMuseKey> {
+        let owner = owner.to_lowercase();
+        let recipient_key = self.public_key(recipient).await
+            .ok_or_else(|| anyhow!("{} has not registered a data key yet", recipient))?;
+
+        // Creates the owner's first key if they have none yet
+        let (_, current_id, current) = self.current_data_key(muse_id, &owner).await?;
+        let shared = self.wrap_for(muse_id, &owner, &current, &recipient_key, &owner)?;
+        for copy in self.owner_copies(muse_id, &owner).await {
+            if copy.key_id == current_id {
+                continue;
+            }
+            let key = self.unwrap_for(&owner, &copy).await?;
+            self.store_wrapped_key(self.wrap_for(muse_id, &owner, &key, &recipient_key, &owner)?).await;
+        }
+        self.store_wrapped_key(shared.clone()).await;
+
+        println!("🔐 Shared {}'s data key for muse {} with {}", owner, muse_id, recipient);
+        Ok(shared)
+    }
+
+    /// Remove `recipient`'s copies of `owner`'s data keys for the muse and rotate the owner's key,
+    /// so nothing written from now on opens with a key the recipient may have kept. Returns the
+    /// new key id, or None when the recipient held no copy. Content sealed before the rotation
+    /// stays under the old key until it is stored again.
+    pub async fn revoke_muse_key(&self, muse_id: &str, owner: &str, recipient: &str) -> Result<Option<String>> {
+        let owner = owner.to_lowercase();
+        let recipient = recipient.to_lowercase();
+        if owner == recipient {
+            return Err(anyhow!("{} cannot revoke their own data key", owner));
+        }
+
+        let removed: Vec<WrappedMuseKey> = {
+            let mut wrapped = self.wrapped_keys.write().await;
+            let copies = wrapped.entry(muse_id.to_string()).or_insert_with(Vec::new);
+            let (removed, kept) = copies.drain(..)
+                .partition(|copy| copy.recipient == recipient && copy.owner == owner);
+            *copies = kept;
+            removed
+        };
+        if removed.is_empty() {
+            return Ok(None);
+        }
+        if let Some(repository) = &self.repository {
+            for copy in &removed {
+                if let Err(e) = repository.delete_wrapped_key(copy).await {
+                    println!("⚠️  Failed to delete wrapped key for {} on muse {}: {}", recipient, muse_id, e);
+                }
+            }
+        }
+
+        // Rotate: a new key for the owner, wrapped for everyone still holding the old one
+        let mut holders: Vec<String> = self.wrapped_keys.read().await
+            .get(muse_id)
+            .map(|copies| copies.iter()
+                .filter(|copy| copy.owner == owner)
+                .map(|copy| copy.recipient.clone())
+                .collect())
+            .unwrap_or_default();
+        holders.push(owner.clone());
+        holders.sort();
+        holders.dedup();
+
+        // The newest copy is the current key, so the rotated one must sort after the old ones
+        let created_at = self.owner_copies(muse_id, &owner).await
+            .iter()
+            .map(|copy| copy.created_at + 1)
+            .max()
+            .unwrap_or(0)
+            .max(now_secs());
+        let key: [u8; 32] = XChaCha20Poly1305::generate_key(&mut OsRng).into();
+        for holder in &holders {
+            let Some(holder_key) = self.public_key(holder).await else {
+                continue;
+            };
+            let mut wrapped = self.wrap_for(muse_id, &owner, &key, &holder_key, &owner)?;
+            wrapped.created_at = created_at;
+            self.store_wrapped_key(wrapped).await;
+        }
+        let new_key_id = key_id(&key);
+        if self.unlocked_keys.read().await.contains_key(&owner) {
+            self.data_keys.write().await.insert((owner.clone(), new_key_id.clone()), key);
+        }
+
+        println!("🔄 Revoked {} from {}'s data key for muse {}; rotated to {}", recipient, owner, muse_id, new_key_id);
+        Ok(Some(new_key_id))
+    }
+
+    /// `owner`'s newest data key for the muse, created if the owner has none yet.
+    /// Returns (owner, key id, key).
+    async fn current_data_key(&self, muse_id: &str, owner: &str) -> Result<(String, String, [u8; 32])> {
+        let owner = owner.to_lowercase();
+        if let Some(current) = self.owner_copies(muse_id, &owner).await.into_iter().max_by_key(|copy| copy.created_at) {
+            let key = self.unwrap_for(&owner, &current).await?;
+            return Ok((owner, current.key_id, key));
+        }
+
+        // First encrypted write for this user and muse: only the public key is needed
+        let owner_key = self.public_key(&owner).await
+            .ok_or_else(|| anyhow!("{} has not registered a data key yet", owner))?;
+        let key: [u8; 32] = XChaCha20Poly1305::generate_key(&mut OsRng).into();
+        let wrapped = self.wrap_for(muse_id, &owner, &key, &owner_key, &owner)?;
+        let id = wrapped.key_id.clone();
+        self.store_wrapped_key(wrapped).await;
+        if self.unlocked_keys.read().await.contains_key(&owner) {
+            self.data_keys.write().await.insert((owner.clone(), id.clone()), key);
+        }
+
+        println!("🔑 Created data key for {} on muse {}", owner, muse_id);
+        Ok((owner, id, key))
+    }
+
+    /// The owner's own copies of their data keys for the muse, every version
+    async fn owner_copies(&self, muse_id: &str, owner: &str) -> Vec<WrappedMuseKey> {
+        self.wrapped_keys.read().await
+            .get(muse_id)
+            .map(|copies| copies.iter()
+                .filter(|copy| copy.owner == owner && copy.recipient == owner)
+                .cloned()
+                .collect())
+            .unwrap_or_default()
+    }
+
+    /// Unwrap `copy` with `holder`'s user key, which must be unlocked
+    async fn unwrap_for(&self, holder: &str, copy: &WrappedMuseKey) -> Result<[u8; 32]> {
+        let cache_key = (holder.to_string(), copy.key_id.clone());
+        if let Some(key) = self.data_keys.read().await.get(&cache_key) {
+            return Ok(*key);
+        }
+
+        let secret_key = *self.unlocked_keys.read().await
+            .get(holder)
+            .ok_or_else(|| anyhow!("Data key for {} on muse {} is locked; unlock it first", holder, copy.muse_id))?;
+        let key = self.unwrap(copy, &secret_key)?;
+        self.data_keys.write().await.insert(cache_key, key);
+        Ok(key)
+    }
+
+    fn wrap_for(&self, muse_id: &str, owner: &str, data_key: &[u8; 32], recipient: &UserPublicKey, wrapped_by: &str) -> Result<WrappedMuseKey> {
+        let recipient_public_key = PublicKey::from_slice(&hex::decode(&recipient.public_key)?)?;
+        let (ephemeral_secret, ephemeral_public) = self.secp.generate_keypair(&mut secp256k1::rand::thread_rng());
+
+        let cipher = wrapping_cipher(&SharedSecret::new(&recipient_public_key, &ephemeral_secret), &content_aad(muse_id, owner))?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let wrapped_key = cipher
+            .encrypt(&nonce, Payload { msg: data_key, aad: recipient.address.to_lowercase().as_bytes() })
+            .map_err(|_| anyhow!("Failed to wrap data key"))?;
+
+        Ok(WrappedMuseKey {
+            muse_id: muse_id.to_string(),
+            owner: owner.to_lowercase(),
+            recipient: recipient.address.to_lowercase(),
+            key_id: key_id(data_key),
+            ephemeral_public_key: hex::encode(ephemeral_public.serialize()),
+            nonce: hex::encode(nonce),
+            wrapped_key: hex::encode(wrapped_key),
+            wrapped_by: wrapped_by.to_lowercase(),
+            created_at: now_secs(),
+        })
+    }
+
+    fn unwrap(&self, wrapped: &WrappedMuseKey, secret_key: &SecretKey) -> Result<[u8; 32]> {
+        let ephemeral_public = PublicKey::from_slice(&hex::decode(&wrapped.ephemeral_public_key)?)?;
+        let cipher = wrapping_cipher(&SharedSecret::new(&ephemeral_public, secret_key), &content_aad(&wrapped.muse_id, &wrapped.owner))?;
+
+        let key = cipher
+            .decrypt(&decode_nonce(&wrapped.nonce)?, Payload { msg: &hex::decode(&wrapped.wrapped_key)?, aad: wrapped.recipient.as_bytes() })
+            .map_err(|_| anyhow!("Failed to unwrap data key for {}", wrapped.recipient))?;
+
+        key.try_into().map_err(|_| anyhow!("Unwrapped data key has the wrong length"))
+    }
+
+    async fn store_wrapped_key(&self, wrapped: WrappedMuseKey) {
+        if let Some(repository) = &self.repository {
+            if let Err(e) = repository.save_wrapped_key(&wrapped).await {
+                println!("⚠️  Failed to persist wrapped key for {} on muse {}: {}", wrapped.recipient, wrapped.muse_id, e);
+            }
+        }
+
+        let mut wrapped_keys = self.wrapped_keys.write().await;
+        let copies = wrapped_keys.entry(wrapped.muse_id.clone()).or_insert_with(Vec::new);
+        copies.retain(|copy| copy.storage_id() != wrapped.storage_id());
+        copies.push(wrapped);
+    }
+}
+
+/// User key derived from the wallet's signature over `key_derivation_message`
+fn derive_user_key(address: &str, signature_bytes: &[u8]) -> Result<SecretKey> {
+    let hkdf = Hkdf::<Sha256>::new(Some(USER_KEY_SALT), signature_bytes);
+    let mut secret = [0u8; 32];
+    hkdf.expand(address.to_lowercase().as_bytes(), &mut secret)
+        .map_err(|_| anyhow!("Failed to derive user key"))?;
+    Ok(SecretKey::from_slice(&secret)?)
+}
+
+/// Associated data binding content and wrapped keys to the muse and the user who owns them
+fn content_aad(muse_id: &str, owner: &str) -> String {
+    format!("{}:{}", muse_id, owner)
+}
+
+fn wrapping_cipher(shared_secret: &SharedSecret, info: &str) -> Result<XChaCha20Poly1305> {
+    let hkdf = Hkdf::<Sha256>::new(Some(KEY_WRAP_SALT), &shared_secret.secret_bytes());
+    let mut key = [0u8; 32];
+    hkdf.expand(info.as_bytes(), &mut key)
+        .map_err(|_| anyhow!("Failed to derive key wrapping key"))?;
+    Ok(XChaCha20Poly1305::new((&key).into()))
+}
+
+/// Short fingerprint so a mismatched key fails with a clear error instead of a MAC failure
+fn key_id(key: &[u8; 32]) -> String {
+    hex::encode(&Sha256::digest(key)[..8])
+}
+
+fn decode_nonce(value: &str) -> Result<XNonce> {
+    let bytes = hex::decode(value)?;
+    if bytes.len() != 24 {
+        return Err(anyhow!("Invalid nonce length"));
+    }
+    Ok(*XNonce::from_slice(&bytes))
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+impl EncryptionService {
+    /// A service without keys that stores content unencrypted, for testing the subsystems that seal through it
+    pub fn unencrypted() -> Self {
+        Self {
+            required: false,
+            secp: Secp256k1::new(),
+            public_keys: RwLock::new(HashMap::new()),
+            unlocked_keys: RwLock::new(HashMap::new()),
+            wrapped_keys: RwLock::new(HashMap::new()),
+            data_keys: RwLock::new(HashMap::new()),
+            repository: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "0x00000000000000000000000000000000000a11ce";
+    const BOB: &str = "0x0000000000000000000000000000000000000b0b";
+
+    fn service(required: bool) -> EncryptionService {
+        EncryptionService {
+            required,
+            secp: Secp256k1::new(),
+            public_keys: RwLock::new(HashMap::new()),
+            unlocked_keys: RwLock::new(HashMap::new()),
+            wrapped_keys: RwLock::new(HashMap::new()),
+            data_keys: RwLock::new(HashMap::new()),
+            repository: None,
+        }
+    }
+
+    async fn unlock(service: &EncryptionService, address: &str) {
+        let secret = derive_user_key(address, address.as_bytes()).unwrap();
+        service.unlock_with_secret(address, secret).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn seal_open_round_trip() {
+        let service = service(true);
+        unlock(&service, ALICE).await;
+
+        let sealed = service.seal("7", Some(ALICE), b"hello muse".to_vec()).await.unwrap();
+        assert_ne!(sealed, b"hello muse".to_vec());
+        let envelope: EncryptedEnvelope = serde_json::from_slice(&sealed).unwrap();
+        assert_eq!(envelope.owner, ALICE);
+
+        assert_eq!(service.open(ALICE, sealed).await.unwrap(), b"hello muse".to_vec());
+    }
+
+    #[tokio::test]
+    async fn other_users_cannot_open() {
+        let service = service(true);
+        unlock(&service, ALICE).await;
+        unlock(&service, BOB).await;
+
+        let sealed = service.seal("7", Some(ALICE), b"private".to_vec()).await.unwrap();
+        assert!(service.open(BOB, sealed).await.is_err());
+
+        // Bob's own content with the same muse uses his own key
+        let bobs = service.seal("7", Some(BOB), b"bob's".to_vec()).await.unwrap();
+        assert!(service.open(ALICE, bobs.clone()).await.is_err());
+        assert_eq!(service.open(BOB, bobs).await.unwrap(), b"bob's".to_vec());
+    }
+
+    #[tokio::test]
+    async fn lock_evicts_unwrapped_keys() {
+        let service = service(true);
+        unlock(&service, ALICE).await;
+        let sealed = service.seal("7", Some(ALICE), b"secret".to_vec()).await.unwrap();
+        service.open(ALICE, sealed.clone()).await.unwrap();
+
+        assert!(service.lock(ALICE).await);
+        assert!(service.data_keys.read().await.is_empty());
+        assert!(service.open(ALICE, sealed.clone()).await.is_err());
+        let locked = service.seal("7", Some(ALICE), b"more".to_vec()).await.unwrap_err();
+        assert!(locked.downcast_ref::<DataKeyLocked>().is_some());
+
+        unlock(&service, ALICE).await;
+        assert_eq!(service.open(ALICE, sealed).await.unwrap(), b"secret".to_vec());
+    }
+
+    #[tokio::test]
+    async fn revoke_rotates_the_owners_key() {
+        let service = service(true);
+        unlock(&service, ALICE).await;
+        unlock(&service, BOB).await;
+
+        let before = service.seal("7", Some(ALICE), b"before".to_vec()).await.unwrap();
+        let shared = service.share_muse_key("7", ALICE, BOB).await.unwrap();
+        assert_eq!(service.open(BOB, before.clone()).await.unwrap(), b"before".to_vec());
+
+        let rotated = service.revoke_muse_key("7", ALICE, BOB).await.unwrap().unwrap();
+        assert_ne!(rotated, shared.key_id);
+        assert!(service.open(BOB, before.clone()).await.is_err());
+
+        let after = service.seal("7", Some(ALICE), b"after".to_vec()).await.unwrap();
+        let envelope: EncryptedEnvelope = serde_json::from_slice(&after).unwrap();
+        assert_eq!(envelope.key_id, rotated);
+        assert!(service.open(BOB, after.clone()).await.is_err());
+
+        // The owner still reads content under both keys
+        assert_eq!(service.open(ALICE, before).await.unwrap(), b"before".to_vec());
+        assert_eq!(service.open(ALICE, after).await.unwrap(), b"after".to_vec());
+
+        assert_eq!(service.revoke_muse_key("7", ALICE, BOB).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn tampered_content_fails() {
+        let service = service(true);
+        unlock(&service, ALICE).await;
+        let sealed = service.seal("7", Some(ALICE), b"intact".to_vec()).await.unwrap();
+
+        let mut envelope: EncryptedEnvelope = serde_json::from_slice(&sealed).unwrap();
+        envelope.muse_id = "8".to_string();
+        assert!(service.open(ALICE, serde_json::to_vec(&envelope).unwrap()).await.is_err());
+
+        let mut envelope: EncryptedEnvelope = serde_json::from_slice(&sealed).unwrap();
+        let mut ciphertext = hex::decode(&envelope.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        envelope.ciphertext = hex::encode(ciphertext);
+        assert!(service.open(ALICE, serde_json::to_vec(&envelope).unwrap()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn unowned_content_is_only_stored_in_plaintext_when_allowed() {
+        assert_eq!(service(false).seal("7", None, b"plain".to_vec()).await.unwrap(), b"plain".to_vec());
+        assert!(service(true).seal("7", None, b"plain".to_vec()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn plaintext_is_only_read_back_when_encryption_is_optional() {
+        let optional = service(false);
+        unlock(&optional, ALICE).await;
+        assert_eq!(optional.open(ALICE, b"plain".to_vec()).await.unwrap(), b"plain".to_vec());
+
+        let required = service(true);
+        unlock(&required, ALICE).await;
+        assert!(required.open(ALICE, b"plain".to_vec()).await.is_err());
+        assert!(required.open(ALICE, b"{\"not\": \"an envelope\"}".to_vec()).await.is_err());
+    }
+}
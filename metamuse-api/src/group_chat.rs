@@ -0,0 +1,302 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::config::Config;
+use crate::ipfs_chat_history::IPFSChatSession;
+use crate::llama_engine_wrapper::LlamaEngineWrapper;
+use crate::muse_orchestrator::{MuseOrchestrator, MuseTraits};
+
+/// Turns of the conversation shown to the moderator
+const MODERATOR_RECENT_TURNS: usize = 6;
+
+/// How the muses that answer a user message are chosen
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TurnPolicy {
+    /// Muses answer one at a time in invitation order
+    RoundRobin,
+    /// Muses named in the message (`@3`, `Muse 3` or `Muse #3`) answer in the order they are
+    /// named; round-robin when nobody is named
+    Addressed,
+    /// A moderator muse reads the conversation and picks who answers
+    Moderator { moderator_id: String },
+}
+
+/// Muses taking part in a group session, stored with the session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupChat {
+    /// Muse token ids in invitation order
+    pub participants: Vec<String>,
+    pub turn_policy: TurnPolicy,
+}
+
+/// Picks who speaks in multi-muse sessions and frames each muse's prompt
+pub struct GroupChatCoordinator {
+    orchestrator: Arc<MuseOrchestrator>,
+    llama_engine: Option<Arc<Mutex<LlamaEngineWrapper>>>,
+    max_participants: usize,
+    max_speakers_per_turn: usize,
+}
+
+impl GroupChatCoordinator {
+    pub fn new(
+        orchestrator: Arc<MuseOrchestrator>,
+        llama_engine: Option<Arc<Mutex<LlamaEngineWrapper>>>,
+        config: &Config,
+    ) -> Self {
+        Self {
+            orchestrator,
+            llama_engine,
+            max_participants: config.group_chat_max_participants.max(2),
+            max_speakers_per_turn: config.group_chat_max_speakers_per_turn.max(1),
+        }
+    }
+
+    pub fn max_participants(&self) -> usize {
+        self.max_participants
+    }
+
+    /// Personality prompt of `speaker`, told who else is in the conversation
+    pub fn system_prompt(&self, group: &GroupChat, speaker: &str, traits: &MuseTraits) -> String {
+        let others = group.participants
+            .iter()
+            .filter(|id| id.as_str() != speaker)
+            .map(|id| format!("Muse #{}", id))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(
+            "{}\n\nYou are Muse #{} in a group conversation with the user and {}. \
+            Reply only as Muse #{}, in your own voice. You may respond to what the other muses said, \
+            but never write their lines for them.",
+            self.orchestrator.build_personality_system_prompt(traits),
+            speaker,
+            others,
+            speaker
+        )
+    }
+
+    /// Muses that answer `message`, the newest user message of `session`, in speaking order.
+    /// `traits` describes each participant to a moderator.
+    pub async fn select_speakers(
+        &self,
+        group: &GroupChat,
+        session: &IPFSChatSession,
+        message: &str,
+        traits: &[(String, MuseTraits)],
+        moderator_traits: Option<&MuseTraits>,
+    ) -> Vec<String> {
+        let speakers = match &group.turn_policy {
+            TurnPolicy::RoundRobin => Vec::new(),
+            TurnPolicy::Addressed => addressed_muses(&group.participants, message),
+            TurnPolicy::Moderator { moderator_id } => {
+                match moderator_traits {
+                    Some(moderator_traits) => self
+                        .ask_moderator(group, session, moderator_id, moderator_traits, traits)
+                        .await
+                        .into_iter()
+                        .collect(),
+                    None => Vec::new(),
+                }
+            }
+        };
+
+        if speakers.is_empty() {
+            return next_in_rotation(group, session).into_iter().collect();
+        }
+        speakers.into_iter().take(self.max_speakers_per_turn).collect()
+    }
+
+    async fn ask_moderator(
+        &self,
+        group: &GroupChat,
+        session: &IPFSChatSession,
+        moderator_id: &str,
+        moderator_traits: &MuseTraits,
+        traits: &[(String, MuseTraits)],
+    ) -> Option<String> {
+        let engine = self.llama_engine.as_ref()?;
+
+        let participants = traits
+            .iter()
+            .map(|(id, t)| format!(
+                "- Muse #{}: creativity {}, wisdom {}, humor {}, empathy {}",
+                id, t.creativity, t.wisdom, t.humor, t.empathy
+            ))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let path = session.active_path();
+        let recent = path[path.len().saturating_sub(MODERATOR_RECENT_TURNS)..]
+            .iter()
+            .map(|m| match (m.role.as_str(), &m.speaker) {
+                ("assistant", Some(speaker)) => format!("Muse #{}: {}", speaker, m.content),
+                ("user", _) => format!("User: {}", m.content),
+                (role, _) => format!("{}: {}", role, m.content),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let prompt = format!(
+            "{}\n\nYou are Muse #{}, moderating a group conversation between a user and these muses:\n{}\n\n\
+            Conversation so far:\n{}\n\n\
+            Which muse is best placed to answer the last message? Reply with the muse number only.\n\nMuse number:",
+            self.orchestrator.build_personality_system_prompt(moderator_traits),
+            moderator_id,
+            participants,
+            recent
+        );
+
+        // Canned fallback text must never be read as a muse number
+        let reply = match LlamaEngineWrapper::generate_with_workers(engine, &prompt, 0.2, 8).await {
+            Ok(reply) => reply,
+            Err(e) => {
+                println!("⚠️ Moderator muse #{} failed to pick a speaker: {}", moderator_id, e);
+                return None;
+            }
+        };
+
+        let chosen = reply
+            .split(|c: char| !c.is_ascii_digit())
+            .find(|number| group.participants.iter().any(|id| id == number))
+            .map(str::to_string);
+        if let Some(speaker) = &chosen {
+            println!("🎙️ Moderator muse #{} picked muse #{} to answer", moderator_id, speaker);
+        }
+        chosen
+    }
+}
+
+/// Participant after the one who spoke last on the selected branch
+pub fn next_in_rotation(group: &GroupChat, session: &IPFSChatSession) -> Option<String> {
+    let last_speaker = session.active_path()
+        .into_iter()
+        .rev()
+        .find_map(|m| m.speaker.as_deref().and_then(|speaker| group.participants.iter().position(|id| id == speaker)));
+
+    let next = last_speaker.map_or(0, |index| (index + 1) % group.participants.len().max(1));
+    group.participants.get(next).cloned()
+}
+
+/// Participants mentioned as `@id`, `muse id` or `muse #id`, in the order they first appear.
+/// A bare `#id` is not a mention, since it usually refers to something else ("issue #3").
+fn addressed_muses(participants: &[String], message: &str) -> Vec<String> {
+    let lower = message.to_lowercase();
+    let mut mentions: Vec<(usize, String)> = participants
+        .iter()
+        .filter_map(|id| {
+            ["@", "muse ", "muse #"]
+                .iter()
+                .filter_map(|prefix| find_mention(&lower, &format!("{}{}", prefix, id)))
+                .min()
+                .map(|position| (position, id.clone()))
+        })
+        .collect();
+    mentions.sort();
+    mentions.into_iter().map(|(_, id)| id).collect()
+}
+
+/// Position of `needle` in `text` where it starts a word and is not part of a longer number
+fn find_mention(text: &str, needle: &str) -> Option<usize> {
+    text.match_indices(needle)
+        .find(|(position, _)| {
+            let starts_word = !text[..*position].ends_with(|c: char| c.is_alphanumeric() || c == '_');
+            starts_word && !text[position + needle.len()..].starts_with(|c: char| c.is_ascii_digit())
+        })
+        .map(|(position, _)| position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipfs_chat_history::IPFSChatMessage;
+
+    fn participants(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn group(ids: &[&str]) -> GroupChat {
+        GroupChat { participants: participants(ids), turn_policy: TurnPolicy::RoundRobin }
+    }
+
+    /// Session whose messages form one thread; `speakers` holds the muse behind each assistant turn
+    fn session(speakers: &[Option<&str>]) -> IPFSChatSession {
+        let messages: Vec<IPFSChatMessage> = speakers
+            .iter()
+            .enumerate()
+            .map(|(i, speaker)| IPFSChatMessage {
+                id: format!("m{}", i),
+                role: if speaker.is_some() { "assistant" } else { "user" }.to_string(),
+                content: String::new(),
+                timestamp: i as u64,
+                token_count: 0,
+                importance: 0.5,
+                compressed: false,
+                original_length: None,
+                parent_id: (i > 0).then(|| format!("m{}", i - 1)),
+                speaker: speaker.map(str::to_string),
+                tool_calls: Vec::new(),
+                reasoning: None,
+            })
+            .collect();
+
+        IPFSChatSession {
+            session_id: "group_test".to_string(),
+            muse_id: "1".to_string(),
+            user_address: "0x00000000000000000000000000000000000a11ce".to_string(),
+            created_at: 0,
+            last_updated: 0,
+            message_count: messages.len(),
+            total_tokens_estimate: 0,
+            active_leaf: messages.last().map(|m| m.id.clone()),
+            messages,
+            group: None,
+            compressed_segments: Vec::new(),
+            topics: Vec::new(),
+            emotional_tone: None,
+            importance_score: 0.5,
+            ipfs_hash: None,
+            version: 1,
+        }
+    }
+
+    #[test]
+    fn addressed_muses_answer_in_the_order_they_are_named() {
+        let ids = participants(&["3", "7", "12"]);
+
+        assert_eq!(addressed_muses(&ids, "Muse #12, then @3: what do you think?"), vec!["12", "3"]);
+        assert_eq!(addressed_muses(&ids, "muse 7 and MUSE 3"), vec!["7", "3"]);
+        assert!(addressed_muses(&ids, "What do you all think?").is_empty());
+    }
+
+    #[test]
+    fn mentions_need_a_word_boundary_and_the_whole_number() {
+        let ids = participants(&["3", "7"]);
+
+        assert!(addressed_muses(&ids, "that would amuse 3 people").is_empty());
+        assert!(addressed_muses(&ids, "see issue #3").is_empty());
+        assert!(addressed_muses(&ids, "mail me at bob@3.io or @37").is_empty());
+        assert_eq!(addressed_muses(&ids, "amuse 3, but muse 7 should answer"), vec!["7"]);
+    }
+
+    #[test]
+    fn find_mention_skips_matches_inside_words_and_numbers() {
+        assert_eq!(find_mention("muse 3", "muse 3"), Some(0));
+        assert_eq!(find_mention("(@3)", "@3"), Some(1));
+        assert_eq!(find_mention("amuse 3 then muse 3", "muse 3"), Some(13));
+        assert_eq!(find_mention("muse 31", "muse 3"), None);
+        assert_eq!(find_mention("x@3", "@3"), None);
+    }
+
+    #[test]
+    fn rotation_continues_after_the_last_speaker() {
+        let trio = group(&["3", "7", "12"]);
+
+        assert_eq!(next_in_rotation(&trio, &session(&[None])), Some("3".to_string()));
+        assert_eq!(next_in_rotation(&trio, &session(&[None, Some("3"), None])), Some("7".to_string()));
+        assert_eq!(next_in_rotation(&trio, &session(&[None, Some("3"), Some("12"), None])), Some("3".to_string()));
+        // Speakers who left the group are skipped over
+        assert_eq!(next_in_rotation(&trio, &session(&[None, Some("7"), Some("99"), None])), Some("12".to_string()));
+        assert_eq!(next_in_rotation(&group(&[]), &session(&[None])), None);
+    }
+}
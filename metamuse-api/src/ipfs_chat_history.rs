@@ -1,11 +1,22 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use tokio::sync::RwLock;
+use std::collections::{HashMap, HashSet};
+use tokio::sync::{Mutex, RwLock};
 use std::sync::Arc;
 use crate::config::Config;
-use alith::data::storage::{PinataIPFS, DataStorage};
-use alith::core::chat::Message;
+use crate::cot_personality::ReasoningTrace;
+use crate::encryption::EncryptionService;
+use crate::group_chat::GroupChat;
+use crate::llama_engine_wrapper::{EngineAccess, LlamaEngineWrapper};
+use crate::storage_backend::StorageBackend;
+use crate::tools::ToolCall;
+
+/// Summaries never target fewer tokens than this, however small the compression ratio
+const MIN_SUMMARY_TOKENS: usize = 32;
+/// Characters of conversation shown to the model per summarisation call
+const MAX_SUMMARY_INPUT_CHARS: usize = 12_000;
+/// Topics kept per segment
+const MAX_KEY_TOPICS: usize = 5;
 
 /// Represents a complete chat session stored on IPFS
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,9 +29,18 @@ pub struct IPFSChatSession {
     pub message_count: usize,
     pub total_tokens_estimate: usize,
     
-    /// Full conversation history with compression metadata
+    /// Full conversation history with compression metadata. Messages form a tree
+    /// through `parent_id`; edits and regenerations add siblings instead of replacing.
     pub messages: Vec<IPFSChatMessage>,
     
+    // ✅ NEW: Last message of the selected branch; None only for sessions stored before branching
+    #[serde(default)]
+    pub active_leaf: Option<String>,
+    
+    // ✅ NEW: Invited muses and turn-taking policy; None for one-to-one sessions
+    #[serde(default)]
+    pub group: Option<GroupChat>,
+    
     /// Compressed summaries of older conversation segments
     pub compressed_segments: Vec<CompressedSegment>,
     
@@ -45,6 +65,28 @@ pub struct IPFSChatMessage {
     pub importance: f32,
     pub compressed: bool, // Whether this message has been semantically compressed
     pub original_length: Option<usize>, // Original length before compression
+    // ✅ NEW: Message this one replies to; None for the first message of a branch
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    // ✅ NEW: Muse token id that wrote an assistant message in a group session
+    #[serde(default)]
+    pub speaker: Option<String>,
+    // ✅ NEW: Tools the muse called while writing an assistant message
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    // ✅ NEW: How the muse reasoned about its traits before writing an assistant message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<ReasoningTrace>,
+}
+
+/// A message on the active branch that has alternatives
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchPoint {
+    pub parent_id: Option<String>,
+    /// Sibling message ids, oldest first
+    pub message_ids: Vec<String>,
+    /// Index into `message_ids` of the sibling on the active branch
+    pub active_index: usize,
 }
 
 /// Compressed conversation segment for older messages
@@ -58,6 +100,124 @@ pub struct CompressedSegment {
     pub key_topics: Vec<String>,
     pub emotional_context: String,
     pub importance_score: f32,
+    // ✅ NEW: 0 when summarising messages, n + 1 when summarising level-n segments
+    #[serde(default)]
+    pub level: u32,
+}
+
+impl CompressedSegment {
+    /// How the segment is shown to the model in place of its messages
+    pub fn context_summary(&self) -> String {
+        format!(
+            "[Conversation Summary {}]: {}",
+            format_timestamp_range(self.start_timestamp, self.end_timestamp),
+            self.compressed_summary
+        )
+    }
+}
+
+impl IPFSChatSession {
+    pub fn message(&self, message_id: &str) -> Option<&IPFSChatMessage> {
+        self.messages.iter().find(|m| m.id == message_id)
+    }
+
+    /// Messages of the selected branch, oldest first
+    pub fn active_path(&self) -> Vec<&IPFSChatMessage> {
+        match self.active_leaf.as_deref().or_else(|| self.messages.last().map(|m| m.id.as_str())) {
+            Some(leaf) => self.branch_path(leaf),
+            None => Vec::new(),
+        }
+    }
+
+    /// Messages from the start of the conversation down to `message_id`, oldest first
+    pub fn branch_path(&self, message_id: &str) -> Vec<&IPFSChatMessage> {
+        let by_id: HashMap<&str, &IPFSChatMessage> = self.messages
+            .iter()
+            .map(|m| (m.id.as_str(), m))
+            .collect();
+
+        let mut path = Vec::new();
+        let mut next = Some(message_id);
+        while let Some(id) = next {
+            // A parent that was compressed away ends the path; the length check guards against cycles
+            let Some(message) = by_id.get(id) else { break };
+            if path.len() >= self.messages.len() {
+                break;
+            }
+            path.push(*message);
+            next = message.parent_id.as_deref();
+        }
+        path.reverse();
+        path
+    }
+
+    /// Messages on the selected branch that have alternatives to switch to
+    pub fn branch_points(&self) -> Vec<BranchPoint> {
+        self.active_path()
+            .iter()
+            .filter_map(|message| {
+                let message_ids: Vec<String> = self.messages
+                    .iter()
+                    .filter(|m| m.parent_id == message.parent_id)
+                    .map(|m| m.id.clone())
+                    .collect();
+                if message_ids.len() < 2 {
+                    return None;
+                }
+                let active_index = message_ids.iter().position(|id| *id == message.id)?;
+                Some(BranchPoint { parent_id: message.parent_id.clone(), message_ids, active_index })
+            })
+            .collect()
+    }
+
+    /// Most recent leaf below `message_id`, following the newest reply at each step
+    fn latest_leaf_under(&self, message_id: &str) -> String {
+        let mut leaf = message_id.to_string();
+        while let Some(reply) = self.messages.iter().rev().find(|m| m.parent_id.as_deref() == Some(leaf.as_str())) {
+            leaf = reply.id.clone();
+        }
+        leaf
+    }
+
+    /// The not yet compressed start of the selected branch written before `cutoff_time`,
+    /// never reaching into its `keep_recent` latest messages
+    fn compressible_prefix(&self, cutoff_time: u64, keep_recent: usize) -> Vec<IPFSChatMessage> {
+        let path = self.active_path();
+        let older = path.len().saturating_sub(keep_recent);
+        path[..older]
+            .iter()
+            .take_while(|m| m.timestamp < cutoff_time && !m.compressed)
+            .map(|m| (*m).clone())
+            .collect()
+    }
+
+    /// Remove `compressed` messages once they are summarised into a segment. Replies to a
+    /// removed message start a branch of their own: the selected branch continues from its
+    /// first remaining message, and edit or regenerate alternatives forking off the removed
+    /// part stay selectable beside it. Returns how many of the `compressed` messages were removed.
+    fn remove_compressed(&mut self, compressed: &HashSet<String>) -> usize {
+        let before = self.messages.len();
+        self.messages.retain(|m| !compressed.contains(&m.id));
+
+        for message in &mut self.messages {
+            if message.parent_id.as_ref().map_or(false, |p| compressed.contains(p)) {
+                message.parent_id = None;
+            }
+        }
+        before - self.messages.len()
+    }
+
+    /// Sessions stored before branching are a single thread in message order
+    fn upgrade_legacy_thread(&mut self) {
+        if self.active_leaf.is_some() {
+            return;
+        }
+        for i in 1..self.messages.len() {
+            let parent_id = self.messages[i - 1].id.clone();
+            self.messages[i].parent_id = Some(parent_id);
+        }
+        self.active_leaf = self.messages.last().map(|m| m.id.clone());
+    }
 }
 
 /// Emotional tone analysis for conversation segments
@@ -76,6 +236,7 @@ pub struct ChatHistoryConfig {
     pub max_context_tokens: usize, // Maximum tokens to include in ALITH history
     pub cache_size: usize, // Number of sessions to cache in memory
     pub compression_ratio: f32, // Target compression ratio (0.1 = 10% of original)
+    pub max_segment_tokens: usize, // Token budget for all compressed segments of a session
 }
 
 impl Default for ChatHistoryConfig {
@@ -86,15 +247,26 @@ impl Default for ChatHistoryConfig {
             max_context_tokens: 8000, // Leave room for new responses
             cache_size: 100,
             compression_ratio: 0.2, // Compress to 20% of original
+            max_segment_tokens: 2000,
+        }
+    }
+}
+
+impl ChatHistoryConfig {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            compression_ratio: config.chat_compression_ratio.clamp(0.01, 1.0),
+            max_segment_tokens: config.chat_segment_token_budget.max(1),
+            ..Self::default()
         }
     }
 }
 
 /// Manages IPFS-based chat history storage and retrieval
+#[derive(Clone)]
 pub struct IPFSChatHistoryManager {
-    ipfs_storage: Arc<PinataIPFS>,
-    client: reqwest::Client,
-    app_config: Config,
+    storage: Arc<dyn StorageBackend>,
+    encryption: Arc<EncryptionService>,
     chat_config: ChatHistoryConfig,
     
     // In-memory cache for performance
@@ -105,24 +277,64 @@ pub struct IPFSChatHistoryManager {
     
     // Maps user_address+muse_id to most recent session_id for continuity
     user_muse_sessions: Arc<RwLock<HashMap<String, String>>>,
+    
+    // ✅ NEW: Every CID a session was stored under, so erasure can unpin old versions too
+    session_versions: Arc<RwLock<HashMap<String, Vec<String>>>>,
+    
+    // ✅ NEW: Owner of every known session. Unlike the cache this is never evicted, so
+    // erasure, export and resealing find old and group sessions too
+    session_owners: Arc<RwLock<HashMap<String, SessionOwner>>>,
+    
+    // ✅ NEW: Local model for summarising segments; heuristics are used without one
+    llama_engine: Option<Arc<Mutex<LlamaEngineWrapper>>>,
+    
+    // ✅ NEW: Sessions with a background compression running
+    compressing: Arc<RwLock<HashSet<String>>>,
+}
+
+/// The user and muse a session belongs to. Group sessions belong to their first muse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SessionOwner {
+    user_address: String,
+    muse_id: String,
+}
+
+/// What `erase_user_sessions` removed
+#[derive(Debug, Clone, Default)]
+pub struct SessionErasure {
+    pub session_ids: Vec<String>,
+    pub unpinned: usize,
+    pub unpin_failures: usize,
 }
 
 impl IPFSChatHistoryManager {
     /// Create a new IPFS chat history manager
-    pub async fn new(config: &Config) -> Result<Self> {
-        let ipfs_storage = Arc::new(PinataIPFS::default());
-
+    pub async fn new(storage: Arc<dyn StorageBackend>, encryption: Arc<EncryptionService>) -> Result<Self> {
         Ok(Self {
-            ipfs_storage,
-            client: reqwest::Client::new(),
-            app_config: config.clone(),
+            storage,
+            encryption,
             chat_config: ChatHistoryConfig::default(),
             session_cache: Arc::new(RwLock::new(HashMap::new())),  
             session_hashes: Arc::new(RwLock::new(HashMap::new())),
             user_muse_sessions: Arc::new(RwLock::new(HashMap::new())),
+            session_versions: Arc::new(RwLock::new(HashMap::new())),
+            session_owners: Arc::new(RwLock::new(HashMap::new())),
+            llama_engine: None,
+            compressing: Arc::new(RwLock::new(HashSet::new())),
         })
     }
 
+    pub fn with_chat_config(mut self, chat_config: ChatHistoryConfig) -> Self {
+        self.chat_config = chat_config;
+        self
+    }
+
+    /// Summarise segments, topics and emotional context with the local model
+    pub fn with_llama_engine(mut self, llama_engine: Arc<Mutex<LlamaEngineWrapper>>) -> Self {
+        self.llama_engine = Some(llama_engine);
+        self
+    }
+
     /// Find existing session or create new one for user+muse combination
     pub async fn get_or_create_session(
         &self,
@@ -143,7 +355,7 @@ impl IPFSChatHistoryManager {
             
             // If not in cache, try to load from IPFS
             if let Some(ipfs_hash) = self.get_session_hash(&existing_session_id).await {
-                match self.retrieve_session_from_ipfs(&ipfs_hash).await {
+                match self.retrieve_session_from_ipfs(&ipfs_hash, &user_address).await {
                     Ok(session) => {
                         println!("✅ Continuing existing session from IPFS with {} messages", session.messages.len());
                         let session_arc = Arc::new(session);
@@ -178,7 +390,7 @@ impl IPFSChatHistoryManager {
 
         // Check if session exists on IPFS
         if let Some(ipfs_hash) = self.get_session_hash(&session_id).await {
-            match self.retrieve_session_from_ipfs(&ipfs_hash).await {
+            match self.retrieve_session_from_ipfs(&ipfs_hash, &user_address).await {
                 Ok(session) => {
                     let session_arc = Arc::new(session);
                     self.cache_session(session_id.clone(), session_arc.clone()).await;
@@ -201,6 +413,8 @@ impl IPFSChatHistoryManager {
             message_count: 0,
             total_tokens_estimate: 0,
             messages: Vec::new(),
+            active_leaf: None,
+            group: None,
             compressed_segments: Vec::new(),
             topics: Vec::new(),
             emotional_tone: None,
@@ -219,7 +433,40 @@ impl IPFSChatHistoryManager {
         Ok(session_arc)
     }
 
-    /// Add a new message to the chat session and update IPFS
+    /// Start a session with several muses. It is sealed under the first muse's key and kept
+    /// apart from the user's one-to-one session with that muse.
+    pub async fn create_group_session(&self, user_address: String, group: GroupChat) -> Result<Arc<IPFSChatSession>> {
+        let host_muse_id = group.participants.first().cloned()
+            .ok_or_else(|| anyhow::anyhow!("A group session needs at least one muse"))?;
+        let session_id = format!("group_{}_{}", group.participants.join("-"), current_timestamp());
+        println!("🆕 Creating group session {} for user {} with muses {:?}", session_id, user_address, group.participants);
+
+        let session = IPFSChatSession {
+            session_id: session_id.clone(),
+            muse_id: host_muse_id,
+            user_address: user_address.clone(),
+            created_at: current_timestamp(),
+            last_updated: current_timestamp(),
+            message_count: 0,
+            total_tokens_estimate: 0,
+            messages: Vec::new(),
+            active_leaf: None,
+            group: Some(group),
+            compressed_segments: Vec::new(),
+            topics: Vec::new(),
+            emotional_tone: None,
+            importance_score: 0.5,
+            ipfs_hash: None,
+            version: 1,
+        };
+
+        let session_arc = self.save_session(&session).await?;
+        // Tracked under the user so erasure finds the session once it leaves the cache
+        self.set_user_muse_session(&format!("{}:group:{}", user_address, session_id), session_id).await;
+        Ok(session_arc)
+    }
+
+    /// Add a new message to the end of the selected branch and update IPFS
     pub async fn add_message(
         &self,
         session_id: &str,
@@ -227,25 +474,28 @@ impl IPFSChatHistoryManager {
         content: String,
         message_id: String,
     ) -> Result<Arc<IPFSChatSession>> {
-        let mut session = self.get_session_for_update(session_id).await.unwrap_or_else(|_| {
-            // Create a minimal session if one doesn't exist
-            Arc::new(IPFSChatSession {
-                session_id: session_id.to_string(),
-                muse_id: "unknown".to_string(),
-                user_address: "unknown".to_string(),
-                created_at: current_timestamp(),
-                last_updated: current_timestamp(),
-                message_count: 0,
-                total_tokens_estimate: 0,
-                messages: Vec::new(),
-                compressed_segments: Vec::new(),
-                topics: Vec::new(),
-                emotional_tone: None,
-                importance_score: 0.5,
-                ipfs_hash: None,
-                version: 1,
-            })
-        });
+        let parent_id = self.get_session_for_update(session_id).await?.active_leaf.clone();
+        self.add_reply(session_id, parent_id, role, content, message_id, None, Vec::new(), None).await
+    }
+
+    /// Add a message below `parent_id` and select the branch it ends. When the parent
+    /// already has replies this starts a new branch beside them. `speaker` attributes
+    /// assistant messages in group sessions; `tool_calls` are those made while writing it and
+    /// `reasoning` the trait analysis done before it. The session must have been initialized
+    /// for its owner, so messages never land in a session nobody can open.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn add_reply(
+        &self,
+        session_id: &str,
+        parent_id: Option<String>,
+        role: String,
+        content: String,
+        message_id: String,
+        speaker: Option<String>,
+        tool_calls: Vec<ToolCall>,
+        reasoning: Option<ReasoningTrace>,
+    ) -> Result<Arc<IPFSChatSession>> {
+        let mut session = self.get_session_for_update(session_id).await?;
         
         let message = IPFSChatMessage {
             id: message_id,
@@ -256,125 +506,116 @@ impl IPFSChatHistoryManager {
             importance: calculate_message_importance(&content),
             compressed: false,
             original_length: None,
+            parent_id,
+            speaker,
+            tool_calls,
+            reasoning,
         };
 
+        if let Some(parent_id) = &message.parent_id {
+            if session.message(parent_id).is_none() {
+                return Err(anyhow::anyhow!("Message {} not found in session {}", parent_id, session_id));
+            }
+        }
+
         // Add message to session
-        let mut session_mut = Arc::make_mut(&mut session);
+        let session_mut = Arc::make_mut(&mut session);
+        session_mut.active_leaf = Some(message.id.clone());
         session_mut.messages.push(message);
         session_mut.message_count += 1;
         session_mut.total_tokens_estimate += estimate_token_count(&content);
         session_mut.last_updated = current_timestamp();
         session_mut.version += 1;
 
-        // Check if compression is needed
-        if session_mut.messages.len() > self.chat_config.max_recent_messages {
-            self.compress_old_messages(&mut session_mut).await?;
+        let needs_compression = session_mut.active_path().len() > self.chat_config.max_recent_messages;
+        let saved = self.save_session(session_mut).await?;
+        if needs_compression {
+            self.schedule_compression(session_id);
         }
 
-        // Update IPFS and cache - with fallback if IPFS fails
-        let updated_session = match self.store_session_to_ipfs(session_mut).await {
-            Ok(session) => {
-                println!("✅ Successfully stored session to IPFS");
-                session
-            }
-            Err(e) => {
-                println!("⚠️ IPFS storage failed: {}, continuing with in-memory session", e);
-                // Continue without IPFS storage but update the session
-                let mut fallback_session = session_mut.clone();
-                fallback_session.last_updated = current_timestamp();
-                fallback_session.version += 1;
-                fallback_session
-            }
-        };
-        
-        let session_arc = Arc::new(updated_session);
-        self.cache_session(session_id.to_string(), session_arc.clone()).await;
-        Ok(session_arc)
+        Ok(saved)
     }
 
-    /// Retrieve conversation history formatted for ALITH Request
-    pub async fn get_alith_history(&self, session_id: &str) -> Result<Vec<Message>> {
-        let session = self.get_cached_session(session_id).await
-            .ok_or_else(|| anyhow::anyhow!("Session not found: {}", session_id))?;
+    /// Edit a user message by adding the new text beside it as a new branch
+    pub async fn edit_message(
+        &self,
+        session_id: &str,
+        message_id: &str,
+        content: String,
+        new_message_id: String,
+    ) -> Result<Arc<IPFSChatSession>> {
+        let session = self.get_session_for_update(session_id).await?;
+        let original = session.message(message_id)
+            .ok_or_else(|| anyhow::anyhow!("Message {} not found in session {}", message_id, session_id))?;
+        if original.role != "user" {
+            return Err(anyhow::anyhow!("Only user messages can be edited"));
+        }
 
-        let mut history = Vec::new();
-        let mut token_count = 0;
+        println!("✏️ Editing message {} of session {} as {}", message_id, session_id, new_message_id);
+        self.add_reply(session_id, original.parent_id.clone(), "user".to_string(), content, new_message_id, None, Vec::new(), None).await
+    }
 
-        // Add compressed segments first (oldest context)
-        for segment in &session.compressed_segments {
-            if token_count + estimate_token_count(&segment.compressed_summary) > self.chat_config.max_context_tokens {
-                break;
-            }
-            
-            history.push(Message {
-                role: "system".to_string(),
-                content: format!(
-                    "[Conversation Summary {}]: {}",
-                    format_timestamp_range(segment.start_timestamp, segment.end_timestamp),
-                    segment.compressed_summary
-                ),
-            });
-            
-            token_count += estimate_token_count(&segment.compressed_summary);
+    /// Select the branch containing `message_id`, continuing down its most recent replies
+    pub async fn switch_branch(&self, session_id: &str, message_id: &str) -> Result<Arc<IPFSChatSession>> {
+        let mut session = self.get_session_for_update(session_id).await?;
+        if session.message(message_id).is_none() {
+            return Err(anyhow::anyhow!("Message {} not found in session {}", message_id, session_id));
         }
 
-        // Add recent messages (newest context)
-        let recent_messages: Vec<_> = session.messages
-            .iter()
-            .rev() // Start from most recent
-            .take_while(|msg| {
-                token_count += msg.token_count;
-                token_count <= self.chat_config.max_context_tokens
-            })
-            .collect();
-
-        // Reverse to get chronological order
-        for message in recent_messages.into_iter().rev() {
-            history.push(Message {
-                role: message.role.clone(),
-                content: message.content.clone(),
-            });
+        let leaf = session.latest_leaf_under(message_id);
+        if session.active_leaf.as_deref() == Some(leaf.as_str()) {
+            return Ok(session);
         }
 
-        println!("📚 Retrieved {} history messages ({} tokens) for session {}", 
-                history.len(), token_count, session_id);
+        println!("🌿 Switching session {} to the branch ending at {}", session_id, leaf);
+        let session_mut = Arc::make_mut(&mut session);
+        session_mut.active_leaf = Some(leaf);
+        session_mut.last_updated = current_timestamp();
+        session_mut.version += 1;
+        self.save_session(session_mut).await
+    }
 
-        Ok(history)
+    /// Store the session and cache the stored version. When storing fails (for instance with
+    /// `DataKeyLocked` while the owner's key is locked) nothing is cached and the error is
+    /// returned, so a change is never reported that would be lost on eviction or restart.
+    async fn save_session(&self, session: &IPFSChatSession) -> Result<Arc<IPFSChatSession>> {
+        let stored = self.store_session_to_ipfs(session).await
+            .inspect_err(|e| println!("⚠️ Storing session {} failed: {}", session.session_id, e))?;
+        
+        let session_arc = Arc::new(stored);
+        self.cache_session(session.session_id.clone(), session_arc.clone()).await;
+        Ok(session_arc)
     }
 
-    /// Store session to IPFS using direct Pinata API (bypassing ALITH library issues)
-    async fn store_session_to_ipfs(&self, session: &IPFSChatSession) -> Result<IPFSChatSession> {
-        let session_json_string = serde_json::to_string_pretty(session)?;
+    /// Current state of an initialized session, for packing into a prompt. Sessions that
+    /// left the cache are loaded from storage with their owner's key.
+    pub async fn get_session(&self, session_id: &str) -> Result<Arc<IPFSChatSession>> {
+        if let Some(session) = self.get_cached_session(session_id).await {
+            return Ok(session);
+        }
         
-        // Get token from config
-        let token = {
-            if let Some(jwt) = &self.app_config.ipfs_jwt_token {
-                jwt.clone()
-            } else if let Some(api_key) = &self.app_config.ipfs_api_key {
-                api_key.clone()
-            } else {
-                return Err(anyhow::anyhow!("No IPFS authentication token available"));
-            }
+        let owner = self.session_owners.read().await.get(session_id).cloned();
+        let (Some(owner), Some(ipfs_hash)) = (owner, self.get_session_hash(session_id).await) else {
+            return Err(anyhow::anyhow!("Session not found: {}", session_id));
         };
-        
+        let session = Arc::new(self.retrieve_session_from_ipfs(&ipfs_hash, &owner.user_address).await?);
+        self.cache_session(session_id.to_string(), session.clone()).await;
+        Ok(session)
+    }
+
+    /// Store session to the configured content storage backend, sealed with the muse's data key
+    async fn store_session_to_ipfs(&self, session: &IPFSChatSession) -> Result<IPFSChatSession> {
+        let session_json = self.encryption
+            .seal(&session.muse_id, Some(&session.user_address), serde_json::to_vec(session)?)
+            .await?;
         let ipfs_filename = format!("ChatSession_{}_{}.json", 
             session.session_id, session.version);
         
-        println!("🔧 Using direct Pinata API with token: {}...", &token[..std::cmp::min(10, token.len())]);
-        println!("📤 Storing chat session {} to IPFS ({} messages, {} bytes)", 
-                session.session_id, session.message_count, session_json_string.len());
-
-        // Direct Pinata API call to bypass ALITH library response parsing issues
-        let ipfs_hash = match self.upload_to_pinata_directly(&session_json_string, &ipfs_filename, &token).await {
-            Ok(hash) => {
-                println!("✅ Direct Pinata upload successful: {}", hash);
-                hash
-            }
-            Err(e) => {
-                println!("❌ Direct Pinata upload failed: {}", e);
-                return Err(anyhow::anyhow!("Direct Pinata upload failed: {}", e));
-            }
-        };
+        println!("📤 Storing chat session {} to {} storage ({} messages, {} bytes)", 
+                session.session_id, self.storage.name(), session.message_count, session_json.len());
+
+        let ipfs_hash = self.storage.put(session_json, &ipfs_filename, "application/json").await
+            .map_err(|e| anyhow::anyhow!("Chat session upload failed: {}", e))?;
 
         let mut updated_session = session.clone();
         updated_session.ipfs_hash = Some(ipfs_hash.clone());
@@ -388,96 +629,18 @@ impl IPFSChatHistoryManager {
         Ok(updated_session)
     }
 
-    /// Direct Pinata API upload bypassing ALITH library
-    async fn upload_to_pinata_directly(&self, content: &str, filename: &str, token: &str) -> Result<String> {
-        use reqwest::multipart;
-        
-        let url = "https://uploads.pinata.cloud/v3/files";
-        
-        // Create multipart form
-        let file_part = multipart::Part::text(content.to_string())
-            .file_name(filename.to_string())
-            .mime_str("application/json")?;
-        
-        let form = multipart::Form::new()
-            .part("file", file_part);
-        
-        println!("🌐 Making direct request to Pinata API: {}", url);
-        
-        let response = self.client
-            .post(url)
-            .multipart(form)
-            .bearer_auth(token)
-            .send()
-            .await?;
-        
-        let status = response.status();
-        println!("📡 Pinata API response status: {}", status);
-        
-        if !status.is_success() {
-            let error_text = response.text().await?;
-            println!("❌ Pinata API error response: {}", error_text);
-            return Err(anyhow::anyhow!("Pinata API error {}: {}", status, error_text));
-        }
-        
-        let response_text = response.text().await?;
-        println!("📥 Raw Pinata API response: {}", &response_text[..std::cmp::min(500, response_text.len())]);
-        
-        // Parse response to extract CID/hash
-        let response_json: serde_json::Value = serde_json::from_str(&response_text)?;
-        
-        // Try different possible response formats from Pinata API
-        let ipfs_hash = if let Some(data) = response_json.get("data") {
-            // v3 API format: { "data": { "cid": "...", "id": "..." } }
-            if let Some(cid) = data.get("cid") {
-                cid.as_str().unwrap_or_default().to_string()
-            } else if let Some(id) = data.get("id") {
-                id.as_str().unwrap_or_default().to_string()
-            } else {
-                return Err(anyhow::anyhow!("No CID or ID found in Pinata response data"));
-            }
-        } else if let Some(cid) = response_json.get("cid") {
-            // Direct CID format: { "cid": "..." }
-            cid.as_str().unwrap_or_default().to_string()
-        } else if let Some(id) = response_json.get("id") {
-            // Direct ID format: { "id": "..." }
-            id.as_str().unwrap_or_default().to_string()
-        } else {
-            return Err(anyhow::anyhow!("No recognizable hash/CID found in Pinata response: {}", response_text));
-        };
-        
-        if ipfs_hash.is_empty() {
-            return Err(anyhow::anyhow!("Empty hash returned from Pinata API"));
-        }
-        
-        println!("🎯 Extracted IPFS hash: {}", ipfs_hash);
-        Ok(ipfs_hash)
-    }
-
-    /// Retrieve session from IPFS using direct Gateway access
-    async fn retrieve_session_from_ipfs(&self, ipfs_hash: &str) -> Result<IPFSChatSession> {
+    /// Retrieve session from the configured content storage backend, decrypting it with
+    /// `requester`'s data key if sealed
+    async fn retrieve_session_from_ipfs(&self, ipfs_hash: &str, requester: &str) -> Result<IPFSChatSession> {
         println!("📥 Retrieving chat session from IPFS: {}", ipfs_hash);
         
-        // Use direct IPFS gateway access for reliability
-        let download_url = format!("https://gateway.pinata.cloud/ipfs/{}", ipfs_hash);
-        println!("🌐 Fetching from IPFS gateway: {}", download_url);
-        
-        let response = self.client
-            .get(&download_url)
-            .send()
-            .await?;
-        
-        let status = response.status();
-        if !status.is_success() {
-            let error_text = response.text().await?;
-            return Err(anyhow::anyhow!("IPFS gateway error {}: {}", status, error_text));
-        }
-        
-        let content = response.text().await?;
-        println!("📥 Retrieved {} bytes from IPFS", content.len());
+        let stored = self.storage.get(ipfs_hash).await?;
+        println!("📥 Retrieved {} bytes from IPFS", stored.len());
+        let content = self.encryption.open(requester, stored).await?;
         
-        let session: IPFSChatSession = serde_json::from_str(&content)
+        let mut session: IPFSChatSession = serde_json::from_slice(&content)
             .map_err(|e| anyhow::anyhow!("Failed to parse IPFS session data: {}", e))?;
+        session.upgrade_legacy_thread();
         
         println!("✅ Retrieved chat session {} from IPFS ({} messages)", 
                 session.session_id, session.message_count);
@@ -485,35 +648,135 @@ impl IPFSChatHistoryManager {
         Ok(session)
     }
 
+    /// Summarise the old part of the session in the background. Summaries take several
+    /// model calls, so replies are saved straight away and each finished segment is applied
+    /// to whichever version of the session is current by then.
+    fn schedule_compression(&self, session_id: &str) {
+        let manager = self.clone();
+        let session_id = session_id.to_string();
+        tokio::spawn(async move {
+            // A running compression picks up the newer messages on the next reply
+            if !manager.compressing.write().await.insert(session_id.clone()) {
+                return;
+            }
+            if let Err(e) = manager.compress_old_messages(&session_id).await {
+                println!("⚠️ Compressing session {} failed: {}", session_id, e);
+            }
+            manager.compressing.write().await.remove(&session_id);
+        });
+    }
+
     /// Compress old messages to reduce storage and context size
-    async fn compress_old_messages(&self, session: &mut IPFSChatSession) -> Result<()> {
-        let cutoff_time = current_timestamp() - self.chat_config.compression_threshold_age;
-        let mut messages_to_compress = Vec::new();
-        let mut remaining_messages = Vec::new();
-
-        // Separate old and recent messages
-        for message in session.messages.drain(..) {
-            if message.timestamp < cutoff_time && !message.compressed {
-                messages_to_compress.push(message);
-            } else {
-                remaining_messages.push(message);
+    async fn compress_old_messages(&self, session_id: &str) -> Result<()> {
+        let Some(session) = self.get_cached_session(session_id).await else {
+            return Ok(());
+        };
+        let cutoff_time = current_timestamp().saturating_sub(self.chat_config.compression_threshold_age);
+        let messages_to_compress = session.compressible_prefix(cutoff_time, self.chat_config.max_recent_messages);
+
+        if !messages_to_compress.is_empty() {
+            println!("🗜️ Compressing {} old messages for session {}", 
+                    messages_to_compress.len(), session_id);
+
+            let compressed: HashSet<String> = messages_to_compress.iter().map(|m| m.id.clone()).collect();
+            let segment = self.create_compressed_segment(messages_to_compress).await?;
+            self.update_session(session_id, |session| {
+                // Nothing to replace when the messages are already gone
+                if session.remove_compressed(&compressed) == 0 {
+                    return false;
+                }
+                session.compressed_segments.push(segment);
+                true
+            }).await;
+        }
+
+        self.recompress_segments(session_id).await
+    }
+
+    /// While the session's segments exceed their token budget, summarise the oldest
+    /// half of them again into one higher-level segment. Recent segments keep their detail.
+    async fn recompress_segments(&self, session_id: &str) -> Result<()> {
+        loop {
+            let Some(session) = self.get_cached_session(session_id).await else {
+                return Ok(());
+            };
+            let Some(count) = segments_to_merge(&session.compressed_segments, self.chat_config.max_segment_tokens) else {
+                return Ok(());
+            };
+            let oldest = session.compressed_segments[..count].to_vec();
+            let oldest_ids: Vec<String> = oldest.iter().map(|s| s.segment_id.clone()).collect();
+
+            println!("🗜️ Re-compressing {} segments of session {} ({} tokens over budget)",
+                    count, session_id, self.chat_config.max_segment_tokens);
+
+            let merged = self.merge_segments(oldest).await?;
+            let applied = self.update_session(session_id, |session| {
+                let unchanged = session.compressed_segments.len() >= count
+                    && session.compressed_segments[..count].iter().map(|s| &s.segment_id).eq(oldest_ids.iter());
+                if unchanged {
+                    session.compressed_segments.drain(..count);
+                    session.compressed_segments.insert(0, merged);
+                }
+                unchanged
+            }).await;
+            if !applied {
+                return Ok(());
             }
         }
+    }
 
-        if messages_to_compress.is_empty() {
-            session.messages = remaining_messages;
-            return Ok(());
+    /// Apply `update` to the current version of a cached session and store the result.
+    /// Returns false when `update` found nothing to change or the result could not be stored.
+    async fn update_session<F>(&self, session_id: &str, update: F) -> bool
+    where
+        F: FnOnce(&mut IPFSChatSession) -> bool,
+    {
+        let Some(mut session) = self.get_cached_session(session_id).await else {
+            return false;
+        };
+        let session_mut = Arc::make_mut(&mut session);
+        if !update(session_mut) {
+            return false;
         }
+        session_mut.last_updated = current_timestamp();
+        session_mut.version += 1;
+        self.save_session(session_mut).await.is_ok()
+    }
+
+    /// Summarise consecutive segments into one covering their whole time range
+    async fn merge_segments(&self, segments: Vec<CompressedSegment>) -> Result<CompressedSegment> {
+        let summaries = segments
+            .iter()
+            .map(|segment| format!(
+                "[{}] {}",
+                format_timestamp_range(segment.start_timestamp, segment.end_timestamp),
+                segment.compressed_summary
+            ))
+            .collect::<Vec<_>>()
+            .join("\n");
 
-        println!("🗜️ Compressing {} old messages for session {}", 
-                messages_to_compress.len(), session.session_id);
+        let compressed_summary = self.compress_conversation_semantically(&summaries).await?;
+        let key_topics = self.extract_key_topics(&summaries).await?;
+        let emotional_context = self.analyze_emotional_context(&summaries).await?;
 
-        // Create compressed segment
-        let segment = self.create_compressed_segment(messages_to_compress).await?;
-        session.compressed_segments.push(segment);
-        session.messages = remaining_messages;
+        let original_message_count: usize = segments.iter().map(|s| s.original_message_count).sum();
+        // Weighted by how many messages each segment stands for
+        let importance_score = segments
+            .iter()
+            .map(|s| s.importance_score * s.original_message_count as f32)
+            .sum::<f32>() / original_message_count.max(1) as f32;
 
-        Ok(())
+        Ok(CompressedSegment {
+            segment_id: generate_segment_id(),
+            start_timestamp: segments.iter().map(|s| s.start_timestamp).min().unwrap_or_default(),
+            end_timestamp: segments.iter().map(|s| s.end_timestamp).max().unwrap_or_default(),
+            original_message_count,
+            compressed_summary,
+            key_topics,
+            emotional_context,
+            importance_score,
+            level: segments.iter().map(|s| s.level).max().unwrap_or_default() + 1,
+        })
     }
 
     /// Create a compressed segment from a group of messages
@@ -554,108 +817,104 @@ impl IPFSChatHistoryManager {
             key_topics,
             emotional_context,
             importance_score,
+            level: 0,
         })
     }
 
-    /// Use AI to semantically compress conversation text
+    /// Summarise conversation text with the local model to roughly `compression_ratio`
+    /// of its length, falling back to extracting its key sentences
     async fn compress_conversation_semantically(&self, conversation: &str) -> Result<String> {
-        let target_length = (conversation.len() as f32 * self.chat_config.compression_ratio) as usize;
-        let target_length = target_length.max(100); // Minimum 100 chars
+        let target_tokens = ((estimate_token_count(conversation) as f32 * self.chat_config.compression_ratio) as usize)
+            .max(MIN_SUMMARY_TOKENS);
+        let target_chars = target_tokens * 4;
 
-        // For now, use simple truncation with key information preservation
-        // TODO: Integrate with ALITH for actual AI-powered compression
-        if conversation.len() <= target_length {
+        if conversation.chars().count() <= target_chars {
             return Ok(conversation.to_string());
         }
 
-        // Extract key sentences (simple heuristic)
-        let sentences: Vec<&str> = conversation.split('.').collect();
-        let important_sentences: Vec<&str> = sentences
-            .iter()
-            .filter(|s| {
-                let s_lower = s.to_lowercase();
-                s_lower.contains('?') || // Questions
-                s_lower.contains("important") ||
-                s_lower.contains("remember") ||
-                s_lower.contains("key") ||
-                s_lower.len() > 50 // Longer sentences likely more important
-            })
-            .cloned()
-            .collect();
-
-        let compressed = if important_sentences.is_empty() {
-            // Fallback: take first and last parts
-            let half = target_length / 2;
-            format!("{}...<conversation continued>...{}", 
-                   &conversation[..half.min(conversation.len())],
-                   &conversation[conversation.len().saturating_sub(half)..])
-        } else {
-            important_sentences.join(". ") + "."
-        };
+        let prompt = format!(
+            "Summarise the conversation below in at most {} words. Keep facts about the user, \
+             decisions, open questions and promises; drop greetings and repetition. \
+             Reply with the summary only.\n\n{}\n\nSummary:",
+            (target_tokens * 3 / 4).max(1),
+            head_and_tail(conversation, MAX_SUMMARY_INPUT_CHARS)
+        );
+        if let Some(summary) = self.generate(&prompt, target_tokens * 2).await {
+            return Ok(truncate_chars(&summary, target_chars));
+        }
 
-        Ok(compressed)
+        Ok(extract_key_sentences(conversation, target_chars))
     }
 
     /// Extract key topics from conversation
     async fn extract_key_topics(&self, conversation: &str) -> Result<Vec<String>> {
-        // Simple keyword extraction (could be enhanced with NLP)
-        let conversation_lower = conversation.to_lowercase();
-        let words: Vec<&str> = conversation_lower
-            .split_whitespace()
-            .filter(|word| word.len() > 4) // Filter short words
-            .collect();
-
-        let mut word_counts = HashMap::new();
-        for word in words {
-            *word_counts.entry(word).or_insert(0) += 1;
+        let prompt = format!(
+            "List the main topics of the conversation below as at most {} short lowercase phrases, \
+             separated by commas, with no other text.\n\n{}\n\nTopics:",
+            MAX_KEY_TOPICS,
+            head_and_tail(conversation, MAX_SUMMARY_INPUT_CHARS)
+        );
+
+        if let Some(reply) = self.generate(&prompt, 64).await {
+            let mut topics: Vec<String> = Vec::new();
+            for topic in reply.split([',', '\n']) {
+                let topic = topic
+                    .trim_matches(|c: char| c.is_ascii_digit() || c.is_whitespace() || "-*.)\"'".contains(c))
+                    .to_lowercase();
+                if !topic.is_empty() && topic.chars().count() <= 40 && !topics.contains(&topic) {
+                    topics.push(topic);
+                }
+            }
+            if !topics.is_empty() {
+                topics.truncate(MAX_KEY_TOPICS);
+                return Ok(topics);
+            }
         }
 
-        let mut topics: Vec<_> = word_counts
-            .into_iter()
-            .filter(|(_, count)| *count > 1) // Only repeated words
-            .collect();
-        
-        topics.sort_by(|a, b| b.1.cmp(&a.1)); // Sort by frequency
-        
-        Ok(topics
-            .into_iter()
-            .take(5) // Top 5 topics
-            .map(|(word, _)| word.to_string())
-            .collect())
+        Ok(frequent_words(conversation))
     }
 
     /// Analyze emotional context of conversation
     async fn analyze_emotional_context(&self, conversation: &str) -> Result<String> {
-        let conversation_lower = conversation.to_lowercase();
-        
-        // Simple emotional analysis
-        let emotions = vec![
-            ("happy", ["happy", "joy", "excited", "great", "amazing", "wonderful"]),
-            ("sad", ["sad", "disappointed", "upset", "depressed", "down", "melancholy"]),
-            ("angry", ["angry", "mad", "frustrated", "annoyed", "irritated", "furious"]),
-            ("curious", ["curious", "wonder", "interesting", "question", "why", "how"]),
-            ("supportive", ["help", "support", "encourage", "understand", "care", "assist"]),
-        ];
-
-        let mut emotion_scores = HashMap::new();
-        for (emotion, keywords) in emotions {
-            let score = keywords
-                .iter()
-                .map(|keyword| conversation_lower.matches(keyword).count())
-                .sum::<usize>();
-            
-            if score > 0 {
-                emotion_scores.insert(emotion, score);
+        let prompt = format!(
+            "In at most six words, name the dominant emotional tone of the conversation below \
+             (for example: curious and upbeat). Reply with the tone only.\n\n{}\n\nTone:",
+            head_and_tail(conversation, MAX_SUMMARY_INPUT_CHARS)
+        );
+
+        if let Some(reply) = self.generate(&prompt, 16).await {
+            let tone = reply
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim_matches(|c: char| c.is_whitespace() || c.is_ascii_punctuation())
+                .to_lowercase();
+            if !tone.is_empty() {
+                return Ok(format!("Emotional tone: {}", truncate_chars(&tone, 60)));
             }
         }
 
-        let dominant_emotion = emotion_scores
-            .into_iter()
-            .max_by_key(|(_, score)| *score)
-            .map(|(emotion, _)| emotion)
-            .unwrap_or("neutral");
+        Ok(format!("Emotional tone: {}", dominant_emotion(conversation)))
+    }
 
-        Ok(format!("Emotional tone: {}", dominant_emotion))
+    /// Run the local model. `None` when there is no worker-backed model or the call fails;
+    /// the in-process engine answers failures with canned text that must not become a summary.
+    async fn generate(&self, prompt: &str, max_tokens: usize) -> Option<String> {
+        let engine = self.llama_engine.as_ref()?;
+        // A pool handle releases the engine mutex, so chat is never queued behind summaries
+        let EngineAccess::Pool(engine) = LlamaEngineWrapper::access(engine).await else {
+            return None;
+        };
+        match engine.generate(prompt, 0.2, max_tokens).await {
+            Ok(reply) => {
+                let reply = reply.trim();
+                (!reply.is_empty()).then(|| reply.to_string())
+            }
+            Err(e) => {
+                println!("⚠️ Chat history summarisation failed: {}, using heuristics", e);
+                None
+            }
+        }
     }
 
     // Cache management methods
@@ -664,6 +923,11 @@ impl IPFSChatHistoryManager {
     }
 
     async fn cache_session(&self, session_id: String, session: Arc<IPFSChatSession>) {
+        self.session_owners.write().await.insert(session_id.clone(), SessionOwner {
+            user_address: session.user_address.clone(),
+            muse_id: session.muse_id.clone(),
+        });
+        
         let mut cache = self.session_cache.write().await;
         
         // Simple LRU eviction if cache is full
@@ -677,8 +941,8 @@ impl IPFSChatHistoryManager {
     }
 
     async fn get_session_for_update(&self, session_id: &str) -> Result<Arc<IPFSChatSession>> {
-        self.get_cached_session(session_id).await
-            .ok_or_else(|| anyhow::anyhow!("Session not initialized: {}", session_id))
+        self.get_session(session_id).await
+            .map_err(|e| anyhow::anyhow!("Session not initialized: {} ({})", session_id, e))
     }
 
     async fn get_session_hash(&self, session_id: &str) -> Option<String> {
@@ -686,6 +950,10 @@ impl IPFSChatHistoryManager {
     }
 
     async fn set_session_hash(&self, session_id: &str, ipfs_hash: String) {
+        self.session_versions.write().await
+            .entry(session_id.to_string())
+            .or_insert_with(Vec::new)
+            .push(ipfs_hash.clone());
         self.session_hashes.write().await.insert(session_id.to_string(), ipfs_hash);
     }
 
@@ -697,22 +965,135 @@ impl IPFSChatHistoryManager {
         self.user_muse_sessions.write().await.insert(user_muse_key.to_string(), session_id);
     }
 
+    /// Every known session between `user_address` and `muse_id`, loading evicted ones from storage
+    pub async fn sessions_for_user_muse(&self, user_address: &str, muse_id: &str) -> Vec<IPFSChatSession> {
+        let mut session_ids: Vec<String> = self.session_owners.read().await
+            .iter()
+            .filter(|(_, owner)| owner.muse_id == muse_id && owner.user_address.eq_ignore_ascii_case(user_address))
+            .map(|(session_id, _)| session_id.clone())
+            .collect();
+        session_ids.sort();
+        
+        let mut sessions = Vec::new();
+        for session_id in session_ids {
+            if let Some(session) = self.get_cached_session(&session_id).await {
+                sessions.push(session.as_ref().clone());
+                continue;
+            }
+            let Some(ipfs_hash) = self.get_session_hash(&session_id).await else {
+                continue;
+            };
+            match self.retrieve_session_from_ipfs(&ipfs_hash, user_address).await {
+                Ok(session) => sessions.push(session),
+                Err(e) => println!("⚠️ Failed to load session {}: {}", session_id, e),
+            }
+        }
+        sessions
+    }
+    
+    /// ✅ NEW: Store `owner`'s sessions with the muse again, so they are sealed with the owner's
+    /// current data key after it was rotated. Returns how many were stored.
+    pub async fn reseal_sessions(&self, muse_id: &str, owner: &str) -> usize {
+        let mut resealed = 0;
+        for session in self.sessions_for_user_muse(owner, muse_id).await {
+            match self.store_session_to_ipfs(&session).await {
+                Ok(updated) => {
+                    self.cache_session(updated.session_id.clone(), Arc::new(updated)).await;
+                    resealed += 1;
+                }
+                Err(e) => println!("⚠️ Failed to reseal session {}: {}", session.session_id, e),
+            }
+        }
+        resealed
+    }
+    
+    /// Every CID a session has been stored under, oldest first
+    pub async fn session_cids(&self, session_id: &str) -> Vec<String> {
+        self.session_versions.read().await
+            .get(session_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+    
+    /// Store a session exported from another deployment under this deployment's keys and
+    /// cache it. Sessions that already exist here are left untouched; returns whether it was imported.
+    pub async fn import_session(&self, mut session: IPFSChatSession) -> Result<bool> {
+        if self.get_cached_session(&session.session_id).await.is_some()
+            || self.get_session_hash(&session.session_id).await.is_some()
+        {
+            return Ok(false);
+        }
+        
+        session.upgrade_legacy_thread();
+        let stored = self.store_session_to_ipfs(&session).await?;
+        let user_muse_key = format!("{}:{}", stored.user_address, stored.muse_id);
+        if self.get_user_muse_session(&user_muse_key).await.is_none() {
+            self.set_user_muse_session(&user_muse_key, stored.session_id.clone()).await;
+        }
+        self.cache_session(stored.session_id.clone(), Arc::new(stored)).await;
+        Ok(true)
+    }
+    
+    /// Drop every session of `user_address` from the caches and unpin all stored versions,
+    /// including sessions that already left the cache
+    pub async fn erase_user_sessions(&self, user_address: &str) -> SessionErasure {
+        let prefix = format!("{}:", user_address.to_lowercase());
+        let mut session_ids: Vec<String> = Vec::new();
+        
+        self.session_owners.write().await.retain(|session_id, owner| {
+            if owner.user_address.eq_ignore_ascii_case(user_address) {
+                session_ids.push(session_id.clone());
+                false
+            } else {
+                true
+            }
+        });
+        self.user_muse_sessions.write().await.retain(|key, session_id| {
+            if key.to_lowercase().starts_with(&prefix) {
+                session_ids.push(session_id.clone());
+                false
+            } else {
+                true
+            }
+        });
+        session_ids.sort();
+        session_ids.dedup();
+        self.session_cache.write().await.retain(|session_id, _| session_ids.binary_search(session_id).is_err());
+        
+        let mut cids: Vec<String> = Vec::new();
+        {
+            let mut session_hashes = self.session_hashes.write().await;
+            let mut session_versions = self.session_versions.write().await;
+            for session_id in &session_ids {
+                cids.extend(session_hashes.remove(session_id));
+                cids.extend(session_versions.remove(session_id).unwrap_or_default());
+            }
+        }
+        cids.sort();
+        cids.dedup();
+        
+        let mut erasure = SessionErasure { session_ids, ..Default::default() };
+        for cid in cids {
+            match self.storage.unpin(&cid).await {
+                Ok(()) => erasure.unpinned += 1,
+                Err(e) => {
+                    println!("⚠️  Failed to unpin chat session version {}: {}", cid, e);
+                    erasure.unpin_failures += 1;
+                }
+            }
+        }
+        
+        println!("🧽 Erased {} chat sessions of {}", erasure.session_ids.len(), user_address);
+        erasure
+    }
+
     /// Store training data contribution to IPFS
     pub async fn store_training_data(&self, data: &[u8], data_hash: &str) -> Result<String> {
         println!("📁 Storing training data to IPFS - Hash: {}", data_hash);
         
-        // Convert data to string for direct upload
-        let content = String::from_utf8(data.to_vec())
-            .map_err(|e| anyhow::anyhow!("Invalid UTF-8 data: {}", e))?;
-        
         let filename = format!("training_data_{}.json", data_hash);
         
-        // Get token from config
-        let token = self.app_config.ipfs_jwt_token.clone()
-            .ok_or_else(|| anyhow::anyhow!("No IPFS JWT token configured"))?;
-        
-        // Use direct Pinata API upload (same as working implementations)
-        match self.upload_to_pinata_directly(&content, &filename, &token).await {
+        match self.storage.put(data.to_vec(), &filename, "application/json").await {
             Ok(ipfs_hash) => {
                 println!("✅ Training data uploaded to IPFS: {}", ipfs_hash);
                 Ok(ipfs_hash)
@@ -763,8 +1144,111 @@ fn calculate_message_importance(content: &str) -> f32 {
     importance.min(1.0_f32)
 }
 
+fn segment_tokens(segments: &[CompressedSegment]) -> usize {
+    segments.iter().map(|s| estimate_token_count(&s.compressed_summary)).sum()
+}
+
+/// How many of the oldest segments to summarise together while `segments` exceed `budget`
+fn segments_to_merge(segments: &[CompressedSegment], budget: usize) -> Option<usize> {
+    (segments.len() > 1 && segment_tokens(segments) > budget).then_some((segments.len() / 2).max(2))
+}
+
+/// At most `max_chars` characters of `text`, cut back to a sentence end when one is
+/// reasonably close. Counts characters, so multi-byte text is never split mid-character.
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    let cut: String = text.chars().take(max_chars).collect();
+    match cut.rfind(['.', '!', '?']) {
+        Some(end) if end > cut.len() / 2 => cut[..=end].to_string(),
+        _ => format!("{}…", cut.trim_end()),
+    }
+}
+
+/// Beginning and end of `text` within `max_chars` characters
+fn head_and_tail(text: &str, max_chars: usize) -> String {
+    let count = text.chars().count();
+    if count <= max_chars {
+        return text.to_string();
+    }
+
+    let half = max_chars / 2;
+    let head: String = text.chars().take(half).collect();
+    let tail: String = text.chars().skip(count - half).collect();
+    format!("{}...<conversation continued>...{}", head, tail)
+}
+
+/// Heuristic summary used without a model: questions, long sentences and sentences
+/// flagged as important, within `max_chars`
+fn extract_key_sentences(conversation: &str, max_chars: usize) -> String {
+    let important_sentences: Vec<&str> = conversation
+        .split('.')
+        .filter(|s| {
+            let s_lower = s.to_lowercase();
+            s_lower.contains('?') || // Questions
+            s_lower.contains("important") ||
+            s_lower.contains("remember") ||
+            s_lower.contains("key") ||
+            s_lower.chars().count() > 50 // Longer sentences likely more important
+        })
+        .collect();
+
+    if important_sentences.is_empty() {
+        head_and_tail(conversation, max_chars)
+    } else {
+        truncate_chars(&(important_sentences.join(". ") + "."), max_chars)
+    }
+}
+
+/// Most repeated longer words, used as topics without a model
+fn frequent_words(conversation: &str) -> Vec<String> {
+    let conversation_lower = conversation.to_lowercase();
+    let mut word_counts = HashMap::new();
+    for word in conversation_lower.split_whitespace().filter(|word| word.chars().count() > 4) {
+        *word_counts.entry(word).or_insert(0) += 1;
+    }
+
+    let mut topics: Vec<_> = word_counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1) // Only repeated words
+        .collect();
+    topics.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0))); // Sort by frequency
+
+    topics
+        .into_iter()
+        .take(MAX_KEY_TOPICS)
+        .map(|(word, _)| word.to_string())
+        .collect()
+}
+
+/// Keyword-based emotion used without a model
+fn dominant_emotion(conversation: &str) -> &'static str {
+    let conversation_lower = conversation.to_lowercase();
+    let emotions = [
+        ("happy", ["happy", "joy", "excited", "great", "amazing", "wonderful"]),
+        ("sad", ["sad", "disappointed", "upset", "depressed", "down", "melancholy"]),
+        ("angry", ["angry", "mad", "frustrated", "annoyed", "irritated", "furious"]),
+        ("curious", ["curious", "wonder", "interesting", "question", "why", "how"]),
+        ("supportive", ["help", "support", "encourage", "understand", "care", "assist"]),
+    ];
+
+    emotions
+        .into_iter()
+        .map(|(emotion, keywords)| {
+            let score: usize = keywords.iter().map(|keyword| conversation_lower.matches(keyword).count()).sum();
+            (emotion, score)
+        })
+        .filter(|(_, score)| *score > 0)
+        .max_by_key(|(_, score)| *score)
+        .map(|(emotion, _)| emotion)
+        .unwrap_or("neutral")
+}
+
 fn generate_segment_id() -> String {
-    format!("segment_{}", current_timestamp())
+    // Unique, since re-compression recognises segments by id
+    format!("segment_{}_{}", current_timestamp(), uuid::Uuid::new_v4().simple())
 }
 
 fn format_timestamp_range(start: u64, end: u64) -> String {
@@ -782,4 +1266,275 @@ fn format_timestamp_range(start: u64, end: u64) -> String {
                start_dt.format("%m/%d %H:%M"),
                end_dt.format("%m/%d %H:%M"))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage_backend::LocalStorageBackend;
+
+    fn message(id: &str, parent_id: Option<&str>, timestamp: u64) -> IPFSChatMessage {
+        IPFSChatMessage {
+            id: id.to_string(),
+            role: "user".to_string(),
+            content: format!("Message {} talks about something worth remembering later on.", id),
+            timestamp,
+            token_count: 10,
+            importance: 0.5,
+            compressed: false,
+            original_length: None,
+            parent_id: parent_id.map(str::to_string),
+            speaker: None,
+            tool_calls: Vec::new(),
+            reasoning: None,
+        }
+    }
+
+    /// A single thread m0 -> m1 -> ... written a day ago
+    fn thread(count: usize) -> Vec<IPFSChatMessage> {
+        let day_ago = current_timestamp() - 86_400;
+        (0..count)
+            .map(|i| {
+                let parent = (i > 0).then(|| format!("m{}", i - 1));
+                message(&format!("m{}", i), parent.as_deref(), day_ago + i as u64)
+            })
+            .collect()
+    }
+
+    fn session(messages: Vec<IPFSChatMessage>, active_leaf: Option<&str>) -> IPFSChatSession {
+        IPFSChatSession {
+            session_id: "session_test".to_string(),
+            muse_id: "1".to_string(),
+            user_address: "0x00000000000000000000000000000000000a11ce".to_string(),
+            created_at: 0,
+            last_updated: 0,
+            message_count: messages.len(),
+            total_tokens_estimate: 0,
+            messages,
+            active_leaf: active_leaf.map(str::to_string),
+            group: None,
+            compressed_segments: Vec::new(),
+            topics: Vec::new(),
+            emotional_tone: None,
+            importance_score: 0.5,
+            ipfs_hash: None,
+            version: 1,
+        }
+    }
+
+    fn segment(id: &str, start_timestamp: u64, summary_chars: usize) -> CompressedSegment {
+        let sentence = "The user described a long and winding hiking trip through the mountains. ";
+        CompressedSegment {
+            segment_id: id.to_string(),
+            start_timestamp,
+            end_timestamp: start_timestamp + 60,
+            original_message_count: 10,
+            compressed_summary: sentence.repeat(summary_chars / sentence.len() + 1)[..summary_chars].to_string(),
+            key_topics: vec!["hiking".to_string()],
+            emotional_context: "Emotional tone: happy".to_string(),
+            importance_score: 0.5,
+            level: 0,
+        }
+    }
+
+    async fn manager(name: &str, chat_config: ChatHistoryConfig, session: IPFSChatSession) -> IPFSChatHistoryManager {
+        let dir = std::env::temp_dir().join(format!("chat_history_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let storage = Arc::new(LocalStorageBackend::new(dir.to_str().unwrap()).unwrap());
+        let manager = IPFSChatHistoryManager::new(storage, Arc::new(EncryptionService::unencrypted()))
+            .await
+            .unwrap()
+            .with_chat_config(chat_config);
+        manager.cache_session(session.session_id.clone(), Arc::new(session)).await;
+        manager
+    }
+
+    fn ids(messages: &[&IPFSChatMessage]) -> Vec<String> {
+        messages.iter().map(|m| m.id.clone()).collect()
+    }
+
+    /// m0 -> m1 -> m2 -> m3, with m1b editing m1 (reply m2b) and m3b regenerating m3
+    fn branched() -> IPFSChatSession {
+        let mut messages = thread(4);
+        let day_ago = messages[0].timestamp;
+        messages.push(message("m1b", Some("m0"), day_ago + 10));
+        messages.push(message("m2b", Some("m1b"), day_ago + 11));
+        messages.push(message("m3b", Some("m2"), day_ago + 12));
+        session(messages, Some("m3"))
+    }
+
+    #[test]
+    fn branch_path_follows_parents_to_the_root() {
+        let session = branched();
+
+        assert_eq!(ids(&session.branch_path("m3")), vec!["m0", "m1", "m2", "m3"]);
+        assert_eq!(ids(&session.branch_path("m2b")), vec!["m0", "m1b", "m2b"]);
+        assert_eq!(ids(&session.active_path()), vec!["m0", "m1", "m2", "m3"]);
+        assert!(session.branch_path("missing").is_empty());
+    }
+
+    #[test]
+    fn branch_points_list_siblings_on_the_active_path() {
+        let session = branched();
+        let points = session.branch_points();
+
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].parent_id.as_deref(), Some("m0"));
+        assert_eq!(points[0].message_ids, vec!["m1", "m1b"]);
+        assert_eq!(points[0].active_index, 0);
+        assert_eq!(points[1].message_ids, vec!["m3", "m3b"]);
+
+        let mut switched = session.clone();
+        switched.active_leaf = Some(switched.latest_leaf_under("m1b"));
+        assert_eq!(switched.active_leaf.as_deref(), Some("m2b"));
+        assert_eq!(switched.branch_points()[0].active_index, 1);
+    }
+
+    #[test]
+    fn legacy_threads_are_chained_in_message_order() {
+        let mut legacy = session(thread(3), None);
+        for message in &mut legacy.messages {
+            message.parent_id = None;
+        }
+
+        legacy.upgrade_legacy_thread();
+        assert_eq!(legacy.active_leaf.as_deref(), Some("m2"));
+        assert_eq!(legacy.message("m0").unwrap().parent_id, None);
+        assert_eq!(legacy.message("m2").unwrap().parent_id.as_deref(), Some("m1"));
+
+        // Sessions that already branch are left alone
+        let mut session = branched();
+        session.upgrade_legacy_thread();
+        assert_eq!(session.message("m1b").unwrap().parent_id.as_deref(), Some("m0"));
+    }
+
+    #[test]
+    fn compressing_a_branched_session_keeps_the_alternatives() {
+        let mut session = branched();
+        let prefix = session.compressible_prefix(current_timestamp(), 2);
+        let compressed: HashSet<String> = prefix.iter().map(|m| m.id.clone()).collect();
+        assert_eq!(compressed, HashSet::from(["m0".to_string(), "m1".to_string()]));
+
+        assert_eq!(session.remove_compressed(&compressed), 2);
+        assert_eq!(ids(&session.active_path()), vec!["m2", "m3"]);
+        // The edit of m1 survives as its own branch and can still be selected
+        assert_eq!(ids(&session.branch_path("m2b")), vec!["m1b", "m2b"]);
+        assert_eq!(session.branch_points()[0].message_ids, vec!["m2", "m1b"]);
+        assert_eq!(session.messages.len(), 5);
+    }
+
+    #[test]
+    fn truncate_chars_never_splits_multi_byte_characters() {
+        let text = "Ça va très bien. 日本語のテキストが続きます";
+
+        // Cut back to the sentence end, which sits past a two-byte character
+        assert_eq!(truncate_chars(text, 20), "Ça va très bien.");
+        assert_eq!(truncate_chars("日本語のテキスト", 4), "日本語の…");
+        assert_eq!(truncate_chars(text, 100), text);
+    }
+
+    #[test]
+    fn head_and_tail_counts_characters() {
+        assert_eq!(head_and_tail("αβγδεζηθικ", 4), "αβ...<conversation continued>...ικ");
+        assert_eq!(head_and_tail("αβγ", 4), "αβγ");
+    }
+
+    #[test]
+    fn compressible_prefix_keeps_recent_and_fresh_messages() {
+        let mut messages = thread(6);
+        messages[4].timestamp = current_timestamp();
+        let session = session(messages, Some("m5"));
+        let cutoff = current_timestamp() - 3600;
+
+        let prefix: Vec<String> = session.compressible_prefix(cutoff, 1).iter().map(|m| m.id.clone()).collect();
+        assert_eq!(prefix, vec!["m0", "m1", "m2", "m3"]);
+        assert_eq!(session.compressible_prefix(cutoff, 3).len(), 3);
+        assert!(session.compressible_prefix(cutoff, 6).is_empty());
+    }
+
+    #[tokio::test]
+    async fn old_messages_are_replaced_by_a_segment() {
+        let chat_config = ChatHistoryConfig { max_recent_messages: 2, ..ChatHistoryConfig::default() };
+        let manager = manager("compress", chat_config, session(thread(6), Some("m5"))).await;
+
+        manager.compress_old_messages("session_test").await.unwrap();
+
+        let session = manager.get_session("session_test").await.unwrap();
+        assert_eq!(ids(&session.active_path()), vec!["m4", "m5"]);
+        assert_eq!(session.message("m4").unwrap().parent_id, None);
+        assert_eq!(session.compressed_segments.len(), 1);
+        assert_eq!(session.compressed_segments[0].original_message_count, 4);
+        assert!(!session.compressed_segments[0].compressed_summary.is_empty());
+    }
+
+    #[tokio::test]
+    async fn segments_over_budget_are_recompressed_oldest_first() {
+        let mut stored = session(thread(2), Some("m1"));
+        stored.compressed_segments = (0..4).map(|i| segment(&format!("s{}", i), 1_000 + i * 100, 400)).collect();
+        let chat_config = ChatHistoryConfig { max_segment_tokens: 150, ..ChatHistoryConfig::default() };
+        let manager = manager("recompress", chat_config, stored).await;
+
+        manager.recompress_segments("session_test").await.unwrap();
+
+        let segments = manager.get_session("session_test").await.unwrap().compressed_segments.clone();
+        assert!(segment_tokens(&segments) <= 150);
+        assert!(segments.len() < 4);
+        // The newest segment keeps its detail; everything older was summarised again
+        assert_eq!(segments.last().unwrap().segment_id, "s3");
+        assert!(segments[0].level >= 1);
+        assert_eq!(segments[0].start_timestamp, 1_000);
+        assert_eq!(segments.iter().map(|s| s.original_message_count).sum::<usize>(), 40);
+    }
+
+    #[tokio::test]
+    async fn replies_to_unknown_sessions_are_rejected() {
+        let manager = manager("unknown", ChatHistoryConfig::default(), session(thread(1), Some("m0"))).await;
+
+        let result = manager.add_message("session_missing", "user".to_string(), "Hello".to_string(), "u1".to_string()).await;
+        assert!(result.is_err());
+        assert!(manager.get_session("session_missing").await.is_err());
+
+        let session = manager.add_message("session_test", "user".to_string(), "Hello".to_string(), "u1".to_string()).await.unwrap();
+        assert_eq!(session.message("u1").unwrap().parent_id.as_deref(), Some("m0"));
+        assert_eq!(session.user_address, "0x00000000000000000000000000000000000a11ce");
+    }
+
+    #[tokio::test]
+    async fn evicted_sessions_are_still_found_and_erased() {
+        let chat_config = ChatHistoryConfig { cache_size: 1, ..ChatHistoryConfig::default() };
+        let manager = manager("evicted", chat_config, session(thread(1), Some("m0"))).await;
+        let alice = "0x00000000000000000000000000000000000a11ce";
+
+        let first = manager.initialize_session("session_a".to_string(), "1".to_string(), alice.to_string()).await.unwrap();
+        manager.add_message(&first.session_id, "user".to_string(), "First".to_string(), "a1".to_string()).await.unwrap();
+        let second = manager.initialize_session("session_b".to_string(), "1".to_string(), alice.to_string()).await.unwrap();
+        manager.add_message(&second.session_id, "user".to_string(), "Second".to_string(), "b1".to_string()).await.unwrap();
+        manager.initialize_session("session_c".to_string(), "2".to_string(), "0xb0b".to_string()).await.unwrap();
+        assert!(manager.get_cached_session("session_a").await.is_none());
+
+        // Evicted sessions load back from storage
+        assert!(manager.get_session("session_a").await.unwrap().message("a1").is_some());
+        let exported: Vec<String> = manager.sessions_for_user_muse(alice, "1").await
+            .into_iter()
+            .map(|session| session.session_id)
+            .collect();
+        // session_test was never stored, so it went with the cache
+        assert_eq!(exported, vec!["session_a", "session_b"]);
+
+        let erasure = manager.erase_user_sessions(alice).await;
+        assert_eq!(erasure.session_ids, vec!["session_a", "session_b", "session_test"]);
+        assert_eq!(erasure.unpinned, 2);
+        assert!(manager.get_session("session_a").await.is_err());
+        assert!(manager.session_cids("session_b").await.is_empty());
+        assert!(manager.sessions_for_user_muse(alice, "1").await.is_empty());
+    }
+
+    #[test]
+    fn segments_merge_only_over_budget() {
+        let segments: Vec<CompressedSegment> = (0..5).map(|i| segment(&format!("s{}", i), i, 400)).collect();
+
+        assert_eq!(segments_to_merge(&segments, 10_000), None);
+        assert_eq!(segments_to_merge(&segments, 100), Some(2));
+        assert_eq!(segments_to_merge(&segments[..1], 10), None);
+    }
+}
@@ -0,0 +1,278 @@
+use anyhow::Result;
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec};
+use schemars::JsonSchema;
+use serde_json::Value;
+use std::collections::HashSet;
+
+// Shared rules, modelled on llama.cpp's grammars/json.gbnf
+const PRIMITIVE_RULES: &[(&str, &str)] = &[
+    ("ws", r#"| " " | "\n" [ \t]{0,20}"#),
+    ("string", r#""\"" ( [^"\\\x7F\x00-\x1F] | "\\" ( ["\\/bfnrt] | "u" [0-9a-fA-F]{4} ) )* "\"" ws"#),
+    ("number", r#""-"? ( [0-9] | [1-9] [0-9]{0,15} ) ( "." [0-9]+ )? ( [eE] [-+]? [0-9]+ )? ws"#),
+    ("integer", r#""-"? ( [0-9] | [1-9] [0-9]{0,15} ) ws"#),
+    ("boolean", r#"( "true" | "false" ) ws"#),
+    ("null", r#""null" ws"#),
+    ("value", r#"object | array | string | number | boolean | null"#),
+    ("object", r#""{" ws ( string ":" ws value ( "," ws string ":" ws value )* )? "}" ws"#),
+    ("array", r#""[" ws ( value ( "," ws value )* )? "]" ws"#),
+];
+
+/// GBNF grammar that only accepts JSON matching `T`'s schema, for constrained decoding
+pub fn grammar_for<T: JsonSchema>() -> Result<String> {
+    json_schema_to_gbnf(&schemars::schema_for!(T))
+}
+
+/// Convert a JSON schema into a llama.cpp GBNF grammar whose `root` rule matches it.
+/// Objects are generated with every property in declaration order, so fields a model should
+/// reason through first come first; length and range limits must be checked after parsing.
+pub fn json_schema_to_gbnf(root: &RootSchema) -> Result<String> {
+    let mut builder = GrammarBuilder {
+        root,
+        rules: Vec::new(),
+        names: PRIMITIVE_RULES.iter().map(|(name, _)| name.to_string()).collect(),
+        resolved: Vec::new(),
+    };
+
+    let expr = builder.object_expr(&root.schema, "root")?;
+    if expr != "root" {
+        builder.rules.insert(0, ("root".to_string(), expr));
+    }
+
+    let mut grammar = String::new();
+    for (name, body) in builder.rules.iter().map(|(n, b)| (n.as_str(), b.as_str())).chain(PRIMITIVE_RULES.iter().copied()) {
+        grammar.push_str(&format!("{} ::= {}\n", name, body));
+    }
+    Ok(grammar)
+}
+
+struct GrammarBuilder<'a> {
+    root: &'a RootSchema,
+    rules: Vec<(String, String)>,
+    names: HashSet<String>,
+    // Definition name -> rule name, so recursive definitions terminate
+    resolved: Vec<(String, String)>,
+}
+
+impl GrammarBuilder<'_> {
+    fn expr(&mut self, schema: &Schema, hint: &str) -> Result<String> {
+        match schema {
+            // `true` accepts any value
+            Schema::Bool(true) => Ok("value".to_string()),
+            Schema::Bool(false) => Err(anyhow::anyhow!("Schema at {} accepts nothing", hint)),
+            Schema::Object(object) => self.object_expr(object, hint),
+        }
+    }
+
+    /// GBNF expression matching `schema`; objects and arrays get a rule named after `hint`
+    fn object_expr(&mut self, schema: &SchemaObject, hint: &str) -> Result<String> {
+        if let Some(reference) = &schema.reference {
+            return self.reference(reference);
+        }
+        if let Some(value) = &schema.const_value {
+            return Ok(format!("{} ws", literal(&value.to_string())));
+        }
+        if let Some(values) = &schema.enum_values {
+            let alternatives = values.iter()
+                .map(|value| literal(&value.to_string()))
+                .collect::<Vec<_>>()
+                .join(" | ");
+            return Ok(format!("( {} ) ws", alternatives));
+        }
+        if let Some(subschemas) = &schema.subschemas {
+            // schemars wraps a documented `$ref` in a single-entry allOf
+            let schemas = match (&subschemas.all_of, &subschemas.any_of, &subschemas.one_of) {
+                (Some(all_of), _, _) if all_of.len() == 1 => all_of,
+                (Some(all_of), _, _) => return Err(anyhow::anyhow!("allOf with {} schemas is not supported", all_of.len())),
+                (None, Some(any_of), _) => any_of,
+                (None, None, Some(one_of)) => one_of,
+                (None, None, None) => return Err(anyhow::anyhow!("Unsupported subschemas at {}", hint)),
+            };
+            let alternatives = schemas.iter()
+                .enumerate()
+                .map(|(i, schema)| self.expr(schema, &format!("{}-{}", hint, i)))
+                .collect::<Result<Vec<_>>>()?;
+            return Ok(format!("( {} )", alternatives.join(" | ")));
+        }
+
+        match &schema.instance_type {
+            Some(SingleOrVec::Single(kind)) => self.typed(kind, schema, hint),
+            Some(SingleOrVec::Vec(kinds)) => {
+                let alternatives = kinds.iter()
+                    .map(|kind| self.typed(kind, schema, &format!("{}-{:?}", hint, kind)))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(format!("( {} )", alternatives.join(" | ")))
+            }
+            None if schema.object.is_some() => self.typed(&InstanceType::Object, schema, hint),
+            None => Ok("value".to_string()),
+        }
+    }
+
+    fn typed(&mut self, kind: &InstanceType, schema: &SchemaObject, hint: &str) -> Result<String> {
+        match kind {
+            InstanceType::String => Ok("string".to_string()),
+            InstanceType::Number => Ok("number".to_string()),
+            InstanceType::Integer => Ok("integer".to_string()),
+            InstanceType::Boolean => Ok("boolean".to_string()),
+            InstanceType::Null => Ok("null".to_string()),
+            InstanceType::Array => {
+                let item = match schema.array.as_ref().and_then(|array| array.items.as_ref()) {
+                    Some(SingleOrVec::Single(items)) => self.expr(items, &format!("{}-item", hint))?,
+                    Some(SingleOrVec::Vec(_)) => return Err(anyhow::anyhow!("Tuple arrays are not supported at {}", hint)),
+                    None => return Ok("array".to_string()),
+                };
+                let name = self.reserve(hint);
+                self.rules.push((name.clone(), format!(r#""[" ws ( {item} ( "," ws {item} )* )? "]" ws"#, item = item)));
+                Ok(name)
+            }
+            InstanceType::Object => {
+                let Some(properties) = schema.object.as_ref().map(|object| &object.properties).filter(|p| !p.is_empty()) else {
+                    return Ok("object".to_string());
+                };
+                let name = self.reserve(hint);
+                let mut fields = Vec::new();
+                for (key, property) in properties {
+                    let value = self.expr(property, &format!("{}-{}", hint, key))?;
+                    fields.push(format!(r#"{} ws ":" ws {}"#, literal(&Value::String(key.clone()).to_string()), value));
+                }
+                self.rules.push((name.clone(), format!(r#""{{" ws {} "}}" ws"#, fields.join(r#" "," ws "#))));
+                Ok(name)
+            }
+        }
+    }
+
+    fn reference(&mut self, reference: &str) -> Result<String> {
+        let definition = reference.strip_prefix("#/definitions/")
+            .ok_or_else(|| anyhow::anyhow!("Unsupported $ref: {}", reference))?;
+        if let Some((_, rule)) = self.resolved.iter().find(|(name, _)| name == definition) {
+            return Ok(rule.clone());
+        }
+
+        let schema = self.root.definitions.get(definition)
+            .ok_or_else(|| anyhow::anyhow!("Missing definition: {}", definition))?;
+        let name = self.reserve(&format!("def-{}", definition));
+        self.resolved.push((definition.to_string(), name.clone()));
+        let body = self.expr(schema, &format!("{}-body", name))?;
+        self.rules.push((name.clone(), body));
+        Ok(name)
+    }
+
+    /// Unique rule name derived from `hint`; GBNF names are letters, digits and dashes
+    fn reserve(&mut self, hint: &str) -> String {
+        let base: String = hint.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+            .collect();
+        let mut name = base.clone();
+        let mut suffix = 1;
+        while !self.names.insert(name.clone()) {
+            suffix += 1;
+            name = format!("{}-{}", base, suffix);
+        }
+        name
+    }
+}
+
+/// GBNF string literal matching `text` exactly
+fn literal(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(JsonSchema)]
+    #[allow(dead_code)]
+    struct Mood {
+        label: String,
+        score: f32,
+    }
+
+    #[derive(JsonSchema)]
+    #[allow(dead_code)]
+    struct Reading {
+        mood: Mood,
+        tags: Vec<String>,
+        verified: bool,
+        note: Option<String>,
+    }
+
+    fn rule<'a>(grammar: &'a str, name: &str) -> &'a str {
+        grammar.lines()
+            .find_map(|line| line.strip_prefix(&format!("{} ::= ", name)))
+            .unwrap_or_else(|| panic!("no rule {} in\n{}", name, grammar))
+    }
+
+    #[test]
+    fn objects_list_every_property_in_declaration_order() {
+        let grammar = grammar_for::<Mood>().unwrap();
+
+        assert_eq!(
+            rule(&grammar, "root"),
+            r#""{" ws "\"label\"" ws ":" ws string "," ws "\"score\"" ws ":" ws number "}" ws"#,
+        );
+    }
+
+    #[test]
+    fn nested_objects_arrays_and_options_get_their_own_rules() {
+        let grammar = grammar_for::<Reading>().unwrap();
+
+        assert_eq!(rule(&grammar, "def-mood"), "def-mood-body");
+        assert_eq!(
+            rule(&grammar, "def-mood-body"),
+            r#""{" ws "\"label\"" ws ":" ws string "," ws "\"score\"" ws ":" ws number "}" ws"#,
+        );
+        assert_eq!(rule(&grammar, "root-tags"), r#""[" ws ( string ( "," ws string )* )? "]" ws"#);
+        assert_eq!(
+            rule(&grammar, "root"),
+            concat!(
+                r#""{" ws "\"mood\"" ws ":" ws def-mood "," ws "\"tags\"" ws ":" ws root-tags "#,
+                r#""," ws "\"verified\"" ws ":" ws boolean "," ws "\"note\"" ws ":" ws ( string | null ) "}" ws"#,
+            ),
+        );
+    }
+
+    #[test]
+    fn primitive_rules_are_always_defined() {
+        let grammar = grammar_for::<Mood>().unwrap();
+        for (name, _) in PRIMITIVE_RULES {
+            rule(&grammar, name);
+        }
+    }
+
+    #[test]
+    fn literals_escape_quotes_and_control_characters() {
+        assert_eq!(literal(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(literal("a\\b\nc"), r#""a\\b\nc""#);
+    }
+
+    #[test]
+    fn rule_names_stay_unique_and_gbnf_safe() {
+        let root = schemars::schema_for!(Mood);
+        let mut builder = GrammarBuilder { root: &root, rules: Vec::new(), names: HashSet::new(), resolved: Vec::new() };
+
+        assert_eq!(builder.reserve("root-Tags_1"), "root-tags-1");
+        assert_eq!(builder.reserve("root-Tags_1"), "root-tags-1-2");
+    }
+
+    #[test]
+    fn false_schemas_are_rejected() {
+        let root = schemars::schema_for!(Mood);
+        let mut builder = GrammarBuilder { root: &root, rules: Vec::new(), names: HashSet::new(), resolved: Vec::new() };
+
+        assert!(builder.expr(&Schema::Bool(false), "field").is_err());
+        assert_eq!(builder.expr(&Schema::Bool(true), "field").unwrap(), "value");
+    }
+}
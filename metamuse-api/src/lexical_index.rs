@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// BM25 term-frequency saturation
+const K1: f32 = 1.2;
+/// BM25 document-length normalisation
+const B: f32 = 0.75;
+
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "do", "for", "from", "has", "have",
+    "i", "if", "in", "is", "it", "its", "me", "my", "of", "on", "or", "so", "that", "the",
+    "this", "to", "was", "we", "were", "what", "with", "you", "your",
+];
+
+/// Lowercased alphanumeric terms with stopwords removed
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .filter(|word| !STOPWORDS.contains(&word.as_str()))
+        .collect()
+}
+
+/// Inverted index scored with Okapi BM25
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Bm25Index {
+    // term -> document id -> term frequency
+    postings: HashMap<String, HashMap<String, u32>>,
+    // document id -> (distinct terms, token count), needed to unindex on removal
+    documents: HashMap<String, (Vec<String>, u32)>,
+    total_length: u64,
+}
+
+impl Bm25Index {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index or re-index `text` under `id`
+    pub fn insert(&mut self, id: &str, text: &str) {
+        self.remove(id);
+
+        let tokens = tokenize(text);
+        let mut frequencies: HashMap<String, u32> = HashMap::new();
+        for token in &tokens {
+            *frequencies.entry(token.clone()).or_insert(0) += 1;
+        }
+
+        for (term, frequency) in &frequencies {
+            self.postings.entry(term.clone())
+                .or_default()
+                .insert(id.to_string(), *frequency);
+        }
+        self.total_length += tokens.len() as u64;
+        self.documents.insert(id.to_string(), (frequencies.into_keys().collect(), tokens.len() as u32));
+    }
+
+    pub fn remove(&mut self, id: &str) -> bool {
+        let Some((terms, length)) = self.documents.remove(id) else {
+            return false;
+        };
+
+        for term in terms {
+            if let Some(documents) = self.postings.get_mut(&term) {
+                documents.remove(id);
+                if documents.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+        self.total_length -= length as u64;
+        true
+    }
+
+    /// BM25 score for every document containing at least one query term (unordered)
+    pub fn score(&self, query: &str) -> Vec<(String, f32)> {
+        if self.documents.is_empty() {
+            return Vec::new();
+        }
+
+        let document_count = self.documents.len() as f32;
+        let average_length = self.total_length as f32 / document_count;
+        let mut scores: HashMap<&str, f32> = HashMap::new();
+
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+
+        for term in &terms {
+            let Some(documents) = self.postings.get(term) else {
+                continue;
+            };
+
+            let containing = documents.len() as f32;
+            let idf = ((document_count - containing + 0.5) / (containing + 0.5) + 1.0).ln();
+
+            for (id, &frequency) in documents {
+                let length = self.documents.get(id).map(|(_, length)| *length).unwrap_or(0) as f32;
+                let frequency = frequency as f32;
+                let norm = K1 * (1.0 - B + B * length / average_length.max(1.0));
+                *scores.entry(id.as_str()).or_insert(0.0) += idf * frequency * (K1 + 1.0) / (frequency + norm);
+            }
+        }
+
+        scores.into_iter().map(|(id, score)| (id.to_string(), score)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score_of(scores: &[(String, f32)], id: &str) -> Option<f32> {
+        scores.iter().find(|(doc, _)| doc == id).map(|(_, score)| *score)
+    }
+
+    fn index() -> Bm25Index {
+        let mut index = Bm25Index::new();
+        index.insert("a", "Rust borrow checker");
+        index.insert("b", "Python garbage collector");
+        index.insert("c", "rust rust compiler");
+        index
+    }
+
+    #[test]
+    fn tokenize_lowercases_and_drops_stopwords() {
+        assert_eq!(tokenize("What is the Borrow-Checker, my friend?"), vec!["borrow", "checker", "friend"]);
+        assert!(tokenize("the a of").is_empty());
+    }
+
+    #[test]
+    fn score_follows_bm25() {
+        let index = index();
+        let scores = index.score("rust");
+        assert_eq!(scores.len(), 2);
+        assert!(score_of(&scores, "b").is_none());
+
+        // Equal lengths, so the length norm is K1 and a single occurrence scores exactly idf
+        let idf = ((3.0f32 - 2.0 + 0.5) / (2.0 + 0.5) + 1.0).ln();
+        let single = score_of(&scores, "a").unwrap();
+        let double = score_of(&scores, "c").unwrap();
+        assert!((single - idf).abs() < 1e-6);
+        assert!((double - idf * 2.0 * (K1 + 1.0) / (2.0 + K1)).abs() < 1e-6);
+
+        // Rarer terms weigh more, and repeated query terms count once
+        let rare = score_of(&index.score("compiler"), "c").unwrap();
+        assert!(rare > single);
+        let repeated: HashMap<String, f32> = index.score("rust rust").into_iter().collect();
+        assert_eq!(repeated, scores.into_iter().collect::<HashMap<_, _>>());
+    }
+
+    #[test]
+    fn remove_and_reinsert_update_postings() {
+        let mut index = index();
+        assert!(index.remove("c"));
+        assert!(!index.remove("c"));
+        assert!(index.score("compiler").is_empty());
+        assert_eq!(index.total_length, 6);
+
+        index.insert("a", "garbage collector tuning");
+        assert!(index.score("borrow").is_empty());
+        assert_eq!(index.score("garbage").len(), 2);
+        assert_eq!(index.total_length, 6);
+    }
+
+    #[test]
+    fn empty_index_and_unknown_terms_score_nothing() {
+        assert!(Bm25Index::new().score("rust").is_empty());
+        assert!(index().score("haskell the").is_empty());
+    }
+}
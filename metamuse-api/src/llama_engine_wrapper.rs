@@ -1,12 +1,11 @@
 use anyhow::Result;
 use alith::inference::LlamaEngine;
 use std::sync::OnceLock;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, MutexGuard};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use serde::{Deserialize, Serialize};
-use tokio::process::Command;
-use tokio::io::{AsyncWriteExt, AsyncBufReadExt, BufReader};
+use crate::ai_worker_pool::AIWorkerPool;
 
 // Note: llama_cpp_2 imports removed - using alith's interface instead
 
@@ -55,6 +54,35 @@ pub struct AIWorkerRequest {
     pub max_tokens: usize,
     pub model_path: String,
     pub request_id: String,
+    pub health_check: bool,
+    pub stream: bool,
+    /// Texts to embed (embedding workers only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embed: Option<Vec<String>>,
+    /// Texts to count tokens for; a non-empty `prompt` is counted as inference would format it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokenize: Option<Vec<String>>,
+    /// GBNF grammar the generated text must match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grammar: Option<String>,
+}
+
+impl AIWorkerRequest {
+    /// Health check ping - the worker answers without running inference
+    pub fn health_check(request_id: String) -> Self {
+        Self {
+            prompt: String::new(),
+            temperature: 0.0,
+            max_tokens: 0,
+            model_path: String::new(),
+            request_id,
+            health_check: true,
+            stream: false,
+            embed: None,
+            tokenize: None,
+            grammar: None,
+        }
+    }
 }
 
 /// Response structure from AI inference worker process
@@ -65,6 +93,38 @@ pub struct AIWorkerResponse {
     pub error: Option<String>,
     pub request_id: String,
     pub inference_time_ms: u64,
+    #[serde(default)]
+    pub embeddings: Option<Vec<Vec<f32>>>,
+    #[serde(default)]
+    pub tokenized: Option<TokenizeResult>,
+}
+
+/// Token counts from the chat model's tokenizer
+#[derive(Debug, Clone, Deserialize)]
+pub struct TokenizeResult {
+    /// One count per text, without BOS or chat template
+    pub counts: Vec<usize>,
+    /// Tokens of the formatted prompt, when one was sent
+    pub prompt_tokens: Option<usize>,
+    /// Tokens the chat template and BOS add around any prompt
+    pub template_tokens: usize,
+    /// Context window shared by the prompt and the generated tokens
+    pub context_size: usize,
+}
+
+/// Incremental token chunk emitted by the AI worker for streaming requests
+#[derive(Debug, Deserialize)]
+pub struct AIWorkerStreamChunk {
+    pub request_id: String,
+    pub token: String,
+}
+
+/// Any protocol line the AI worker writes to stdout
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum AIWorkerMessage {
+    Chunk(AIWorkerStreamChunk),
+    Response(AIWorkerResponse),
 }
 
 // Persistent engine with KV cache management
@@ -79,82 +139,40 @@ struct GlobalEngineState {
     request_counter: AtomicUsize,
 }
 
+fn global_state() -> &'static Arc<GlobalEngineState> {
+    GLOBAL_STATE.get_or_init(|| {
+        Arc::new(GlobalEngineState {
+            engines: [
+                Mutex::new(None),
+                Mutex::new(None), 
+                Mutex::new(None),
+            ],
+            context_rotation_counter: AtomicUsize::new(0),
+            request_counter: AtomicUsize::new(0),
+        })
+    })
+}
+
 pub struct LlamaEngineWrapper {
     model_path: String,
+    // Pool of pre-spawned ai-worker processes for isolated inference
+    worker_pool: Option<Arc<AIWorkerPool>>,
+    // ✅ NEW: False for registry-loaded models, whose workers are the only copy of the model
+    in_process: bool,
 }
 
 impl LlamaEngineWrapper {
-    // ULTIMATE SOLUTION: Process isolation strategy
-    // Each AI inference request runs in a completely isolated process
-    // This eliminates ALL KV cache conflicts by ensuring fresh alith backend
+    // Each AI inference request runs in a separate ai-worker process from the pool
+    // Workers are pre-spawned with the model loaded, so there is no per-request startup cost
     async fn process_isolation_inference(&self, prompt: &str, temperature: f32, max_tokens: usize, request_num: usize) -> Result<String> {
-        println!("🚀 Request #{} - ULTIMATE: Process isolation inference starting", request_num);
-        
-        let request_id = format!("req_{}", request_num);
-        
-        // Create AI worker request
-        let worker_request = AIWorkerRequest {
-            prompt: prompt.to_string(),
-            temperature,
-            max_tokens,
-            model_path: self.model_path.clone(),
-            request_id: request_id.clone(),
-        };
-        
-        // Spawn AI worker process
-        println!("🤖 Request #{} - Spawning isolated AI worker process", request_num);
-        
-        let mut child = Command::new("cargo")
-            .args(&["run", "--bin", "ai-worker"])
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .map_err(|e| anyhow::anyhow!("Failed to spawn AI worker process: {}", e))?;
-        
-        // Send request to worker via stdin
-        if let Some(stdin) = child.stdin.as_mut() {
-            let request_json = serde_json::to_string(&worker_request)?;
-            println!("📤 Request #{} - Sending request to AI worker: {}", request_num, request_json);
-            
-            stdin.write_all(request_json.as_bytes()).await?;
-            stdin.write_all(b"\n").await?;
-            stdin.flush().await?;
-        } else {
-            return Err(anyhow::anyhow!("Failed to get stdin handle for AI worker"));
-        }
-        
-        // Read response from worker via stdout
-        println!("📥 Request #{} - Waiting for response from AI worker", request_num);
-        
-        let output = child.wait_with_output().await
-            .map_err(|e| anyhow::anyhow!("AI worker process failed: {}", e))?;
+        println!("🚀 Request #{} - Dispatching to the AI worker pool", request_num);
         
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            println!("❌ Request #{} - AI worker process failed. stderr: {}", request_num, stderr);
-            return Err(anyhow::anyhow!("AI worker process exited with error: {}", stderr));
-        }
-        
-        // Parse response
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
+        let worker_pool = self.worker_pool.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("AI worker pool not available"))?;
         
-        println!("🔍 Request #{} - AI worker stdout: {}", request_num, stdout);
-        if !stderr.is_empty() {
-            println!("🔍 Request #{} - AI worker stderr: {}", request_num, stderr);
-        }
-        
-        // Find JSON response in stdout (last line should be the JSON)
-        let response_line = stdout.lines()
-            .filter(|line| line.trim().starts_with('{'))
-            .last()
-            .ok_or_else(|| anyhow::anyhow!("No JSON response found in AI worker output"))?;
+        let worker_response = worker_pool.generate(prompt, temperature, max_tokens).await?;
         
-        let worker_response: AIWorkerResponse = serde_json::from_str(response_line)
-            .map_err(|e| anyhow::anyhow!("Failed to parse AI worker response: {}", e))?;
-        
-        println!("📋 Request #{} - AI worker response parsed: success={}, inference_time={}ms", 
+        println!("📋 Request #{} - AI worker response: success={}, inference_time={}ms", 
                 request_num, worker_response.success, worker_response.inference_time_ms);
         
         if worker_response.success {
@@ -362,17 +380,7 @@ impl LlamaEngineWrapper {
 
     pub async fn new<P: AsRef<std::path::Path>>(model_path: P) -> Result<Self> {
         // Initialize global state once
-        let global_state = GLOBAL_STATE.get_or_init(|| {
-            Arc::new(GlobalEngineState {
-                engines: [
-                    Mutex::new(None),
-                    Mutex::new(None), 
-                    Mutex::new(None),
-                ],
-                context_rotation_counter: AtomicUsize::new(0),
-                request_counter: AtomicUsize::new(0),
-            })
-        });
+        let global_state = global_state();
 
         // Create and store engines for context rotation (only first engine will succeed)
         println!("🚀 Creating LlamaEngine with context rotation strategy...");
@@ -395,6 +403,8 @@ impl LlamaEngineWrapper {
                 println!("🎯 LlamaEngineWrapper ready with context rotation strategy");
                 Ok(Self {
                     model_path: model_path.as_ref().to_string_lossy().to_string(),
+                    worker_pool: None,
+                    in_process: true,
                 })
             }
             Err(e) => {
@@ -403,6 +413,8 @@ impl LlamaEngineWrapper {
                     println!("✅ Context rotation system ready to use existing backend");
                     Ok(Self {
                         model_path: model_path.as_ref().to_string_lossy().to_string(),
+                        worker_pool: None,
+                        in_process: true,
                     })
                 } else {
                     Err(anyhow::anyhow!("Failed to create LlamaEngine: {}", e))
@@ -411,7 +423,55 @@ impl LlamaEngineWrapper {
         }
     }
 
+    /// ✅ NEW: Engine served only by `worker_pool`, for models loaded after startup. The alith
+    /// backend holds a single in-process model, so these never fall back to it.
+    pub fn from_worker_pool(model_path: &str, worker_pool: Arc<AIWorkerPool>) -> Self {
+        Self {
+            model_path: model_path.to_string(),
+            worker_pool: Some(worker_pool),
+            in_process: false,
+        }
+    }
+
+    /// Attach a pool of pre-spawned ai-worker processes used for requests after the first
+    pub fn with_worker_pool(mut self, worker_pool: Arc<AIWorkerPool>) -> Self {
+        self.worker_pool = Some(worker_pool);
+        self
+    }
+
+    /// Worker pool serving this model, whose processes also hold its tokenizer
+    pub fn worker_pool(&self) -> Option<Arc<AIWorkerPool>> {
+        self.worker_pool.clone()
+    }
+
+    /// Lock `engine` only as long as generation needs it. Workers serve requests
+    /// concurrently, so a worker-backed model is used through a pool-only handle and the
+    /// mutex is released straight away; the in-process engine stays locked.
+    pub async fn access(engine: &Mutex<LlamaEngineWrapper>) -> EngineAccess<'_> {
+        let guard = engine.lock().await;
+        match guard.worker_pool.clone() {
+            Some(worker_pool) => EngineAccess::Pool(Self::from_worker_pool(&guard.model_path, worker_pool)),
+            None => EngineAccess::Locked(guard),
+        }
+    }
+
+    /// ✅ NEW: Generate with a worker-backed model only, without holding `engine` locked. The
+    /// in-process engine answers failures with canned text, so callers that keep model output
+    /// (summaries, drafts, moderation) treat an engine without workers as no model.
+    pub async fn generate_with_workers(engine: &Mutex<LlamaEngineWrapper>, prompt: &str, temperature: f32, max_tokens: usize) -> Result<String> {
+        match Self::access(engine).await {
+            EngineAccess::Pool(engine) => engine.generate(prompt, temperature, max_tokens).await,
+            EngineAccess::Locked(_) => Err(anyhow::anyhow!("No worker-backed model is loaded")),
+        }
+    }
+
     pub async fn generate(&self, prompt: &str, temperature: f32, max_tokens: usize) -> Result<String> {
+        // ✅ NEW: Registry-loaded models only run in their own workers
+        if !self.in_process {
+            let request_num = global_state().request_counter.fetch_add(1, Ordering::SeqCst) + 1;
+            return self.process_isolation_inference(prompt, temperature, max_tokens, request_num).await;
+        }
+
         let global_state = GLOBAL_STATE.get()
             .ok_or_else(|| anyhow::anyhow!("Global state not initialized"))?;
         
@@ -450,15 +510,10 @@ impl LlamaEngineWrapper {
                 }
             }
         } else {
-            println!("🔄 Request #{} - Subsequent request, implementing ULTIMATE process isolation strategy", request_num);
-            
-            // ULTIMATE SOLUTION: Use process isolation for all subsequent requests
-            // This completely eliminates KV cache conflicts by running AI inference in fresh processes
-            println!("🚀 Request #{} - Attempting process isolation inference (ULTIMATE solution)", request_num);
-            
+            // Worker processes keep their own KV cache, so later requests never conflict with the first
             match self.process_isolation_inference(prompt, temperature, max_tokens, request_num).await {
                 Ok(response) => {
-                    println!("🎉 Request #{} - ULTIMATE process isolation inference SUCCESS!", request_num);
+                    println!("🎉 Request #{} - Worker inference succeeded", request_num);
                     return Ok(response);
                 }
                 Err(e) => {
@@ -529,6 +584,60 @@ impl LlamaEngineWrapper {
         return self.try_aggressive_ai_inference(prompt, temperature, max_tokens, request_num, 0).await;
     }
 
+    /// Generate with token streaming - tokens are sent to `token_tx` as the ai-worker decodes them
+    /// Without a worker pool the full response is generated first and sent as a single chunk
+    pub async fn generate_stream(
+        &self,
+        prompt: &str,
+        temperature: f32,
+        max_tokens: usize,
+        token_tx: tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> Result<String> {
+        if let Some(worker_pool) = &self.worker_pool {
+            println!("🌊 Streaming AI inference through worker pool");
+            
+            let worker_response = worker_pool
+                .generate_stream(prompt, temperature, max_tokens, token_tx)
+                .await?;
+            
+            return if worker_response.success {
+                worker_response.response
+                    .ok_or_else(|| anyhow::anyhow!("AI worker reported success but no response content"))
+            } else {
+                Err(anyhow::anyhow!(
+                    "AI worker inference failed: {}",
+                    worker_response.error.unwrap_or("Unknown AI worker error".to_string())
+                ))
+            };
+        }
+        
+        println!("⚠️ No worker pool available - streaming full response as one chunk");
+        let response = self.generate(prompt, temperature, max_tokens).await?;
+        let _ = token_tx.send(response.clone());
+        Ok(response)
+    }
+
+    /// ✅ NEW: Generate text that matches the GBNF `grammar`. Constrained decoding runs in the
+    /// ai-worker, so this needs a worker pool.
+    pub async fn generate_with_grammar(&self, prompt: &str, temperature: f32, max_tokens: usize, grammar: &str) -> Result<String> {
+        let worker_pool = self.worker_pool.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Grammar-constrained generation needs the AI worker pool"))?;
+
+        let worker_response = worker_pool
+            .generate_with_grammar(prompt, temperature, max_tokens, grammar)
+            .await?;
+
+        if worker_response.success {
+            worker_response.response
+                .ok_or_else(|| anyhow::anyhow!("AI worker reported success but no response content"))
+        } else {
+            Err(anyhow::anyhow!(
+                "AI worker inference failed: {}",
+                worker_response.error.unwrap_or("Unknown AI worker error".to_string())
+            ))
+        }
+    }
+
     async fn execute_ai_inference(
         &self,
         engine: &mut LlamaEngine,
@@ -963,6 +1072,23 @@ impl LlamaEngineWrapper {
     }
 }
 
+/// An engine ready for one generation, see `LlamaEngineWrapper::access`
+pub enum EngineAccess<'a> {
+    Pool(LlamaEngineWrapper),
+    Locked(MutexGuard<'a, LlamaEngineWrapper>),
+}
+
+impl std::ops::Deref for EngineAccess<'_> {
+    type Target = LlamaEngineWrapper;
+
+    fn deref(&self) -> &LlamaEngineWrapper {
+        match self {
+            EngineAccess::Pool(engine) => engine,
+            EngineAccess::Locked(guard) => guard,
+        }
+    }
+}
+
 // Implement thread safety markers
 unsafe impl Send for LlamaEngineWrapper {}
 unsafe impl Sync for LlamaEngineWrapper {}
\ No newline at end of file
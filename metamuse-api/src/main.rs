@@ -4,6 +4,7 @@ use dotenv::dotenv;
 use tokio::sync::{Mutex, RwLock};
 use std::collections::HashMap;
 use crate::llama_engine_wrapper::LlamaEngineWrapper;
+use crate::ai_worker_pool::AIWorkerPool;
 
 mod config;
 mod agent_workflow;
@@ -17,6 +18,7 @@ mod tools;
 mod verification;
 mod llama_engine_wrapper;
 mod ai_worker;
+mod ai_worker_pool;
 mod ipfs_chat_history;
 mod tee_attestation;
 mod cot_personality;
@@ -25,6 +27,25 @@ mod semantic_search;
 mod template_system;
 mod avatar_system;
 mod training_data_market;
+mod database;
+mod auth;
+mod storage_backend;
+mod encryption;
+mod embedder;
+mod vector_index;
+mod lexical_index;
+mod retrieval;
+mod memory_retention;
+mod memory_consolidation;
+mod data_erasure;
+mod car;
+mod data_export;
+mod context_builder;
+mod group_chat;
+mod model_registry;
+mod model_download;
+mod personality_blend;
+mod json_grammar;
 
 use crate::config::Config;
 use crate::blockchain_client::BlockchainClient;
@@ -32,13 +53,30 @@ use crate::muse_orchestrator::MuseOrchestrator;
 use crate::persist_memory::MemorySystem;
 use crate::plugin_system::PluginSystem;
 use crate::verification::VerificationSystem;
-use crate::ipfs_chat_history::IPFSChatHistoryManager;
+use crate::ipfs_chat_history::{ChatHistoryConfig, IPFSChatHistoryManager};
 use crate::tee_attestation::MuseTEEService;
 use crate::rating_system::AIAlignmentMarket;
 use crate::semantic_search::SemanticSearchService;
 use crate::template_system::TemplateManager;
 use crate::avatar_system::AvatarManager;
 use crate::training_data_market::TrainingDataMarketplace;
+use crate::database::{Database, UserMuseRepository};
+use crate::auth::AuthService;
+use crate::storage_backend::{create_storage_backend, StorageBackend};
+use crate::encryption::EncryptionService;
+use crate::embedder::{create_embedder, Embedder};
+use crate::vector_index::VectorIndex;
+use crate::retrieval::create_reranker;
+use crate::memory_retention::MemoryRetentionEngine;
+use crate::memory_consolidation::MemoryConsolidator;
+use crate::data_erasure::DataErasureService;
+use crate::data_export::DataExportService;
+use crate::context_builder::{ContextBuilder, TokenCounter};
+use crate::group_chat::GroupChatCoordinator;
+use crate::agent_workflow::AgentWorkflow;
+use crate::tools::ToolRegistry;
+use crate::model_registry::ModelRegistry;
+use crate::model_download::ModelDownloadManager;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -57,6 +95,21 @@ pub struct AppState {
     pub avatar_manager: Arc<Mutex<AvatarManager>>, // Avatar upload and management
     pub training_data_market: Arc<Mutex<TrainingDataMarketplace>>, // AI training data marketplace with DAT rewards
     pub user_muses: Arc<RwLock<HashMap<String, Vec<u64>>>>, // Map of user addresses to their muse token IDs
+    pub user_muse_repository: Option<Arc<dyn UserMuseRepository>>, // Durable copy of user_muses
+    pub auth_service: Arc<AuthService>, // Sign-In with Ethereum sessions
+    pub storage: Arc<dyn StorageBackend>, // Content-addressed storage (local, Kubo or Pinata)
+    pub encryption_service: Arc<EncryptionService>, // Wallet-keyed envelope encryption for stored content
+    pub embedder: Arc<dyn Embedder>, // Shared text embedding model
+    pub memory_retention: Arc<MemoryRetentionEngine>, // TTL, decay and eviction for memories
+    pub memory_consolidator: Arc<MemoryConsolidator>, // Merges related memories into long-term summaries
+    pub data_erasure: Arc<DataErasureService>, // Per-user erasure across subsystems with signed receipts
+    pub data_export: Arc<DataExportService>, // Portable (user, muse) archives as JSON or CAR
+    pub context_builder: Arc<ContextBuilder>, // Packs prompts into the model's context window by token count
+    pub group_chat: Arc<GroupChatCoordinator>, // Turn-taking and prompts for multi-muse sessions
+    pub agent_workflow: Arc<AgentWorkflow>, // Autonomous muse-to-muse conversations
+    pub tool_registry: Arc<ToolRegistry>, // Built-in tools and installed plugins muses can call
+    pub model_registry: Arc<ModelRegistry>, // Per-trait GGUF models loaded under a memory budget
+    pub model_downloads: Arc<ModelDownloadManager>, // Resumable, checksum-verified model downloads
 }
 
 #[tokio::main]
@@ -68,10 +121,28 @@ async fn main() -> Result<(), anyhow::Error> {
     
     // Initialize shared LlamaEngineWrapper at startup
     let llama_engine = {
-        let model_path = "./models/qwen2.5-1.5b-instruct-q5_k_m.gguf";
+        let model_path = config.default_model_path.as_str();
+        
+        // Pre-spawn the ai-worker pool so later requests skip process startup and model loading
+        let worker_pool = match AIWorkerPool::new(&config, model_path).await {
+            Ok(pool) => {
+                pool.start_health_monitor(std::time::Duration::from_secs(config.ai_worker_health_check_interval_secs));
+                Some(pool)
+            }
+            Err(e) => {
+                println!("⚠️  Failed to start AI worker pool: {}", e);
+                println!("   Requests after the first will use in-process fallback strategies");
+                None
+            }
+        };
+        
         println!("🚀 Initializing shared LlamaEngineWrapper at startup...");
         match LlamaEngineWrapper::new(model_path).await {
             Ok(engine) => {
+                let engine = match worker_pool {
+                    Some(pool) => engine.with_worker_pool(pool),
+                    None => engine,
+                };
                 println!("✅ Shared LlamaEngineWrapper initialized successfully - all requests will use AI inference");
                 Some(Arc::new(Mutex::new(engine)))
             }
@@ -83,24 +154,176 @@ async fn main() -> Result<(), anyhow::Error> {
         }
     };
     
+    // ✅ NEW: Count prompt tokens with the model's own tokenizer, held by the ai-worker processes
+    let worker_pool = match &llama_engine {
+        Some(engine) => engine.lock().await.worker_pool(),
+        None => None,
+    };
+    let context_builder = Arc::new(ContextBuilder::new(TokenCounter::new(worker_pool, &config), &config));
+    
+    // ✅ NEW: Per-trait models are loaded on first use next to the default model
+    let model_registry = Arc::new(ModelRegistry::new(&config, llama_engine.clone()));
+    
+    // ✅ NEW: Fetch missing models from their configured URLs
+    let model_downloads = Arc::new(ModelDownloadManager::new(&config, model_registry.downloadable()));
+    if config.model_auto_download {
+        match model_downloads.start(None).await {
+            Ok(downloads) => println!("📥 Checking {} downloadable models", downloads.len()),
+            Err(e) => println!("⚠️ Failed to start model downloads: {}", e),
+        }
+    }
+    
+    // ✅ NEW: Durable storage for subsystem state (SQLite by default, Postgres via DATABASE_URL)
+    let database = match Database::connect(&config).await {
+        Ok(database) => Some(database),
+        Err(e) => {
+            println!("⚠️  Failed to connect to database: {}", e);
+            println!("   Memories, templates, avatars and contributions will not survive a restart");
+            None
+        }
+    };
+    
+    // ✅ NEW: One content storage backend shared by every module that uploads to IPFS
+    let storage = create_storage_backend(&config)?;
+    
+    // ✅ NEW: Envelope encryption so sessions and memories never reach IPFS in plaintext
+    let mut encryption_service = EncryptionService::new(&config);
+    if let Some(database) = &database {
+        encryption_service = encryption_service.with_repository(database.clone()).await?;
+    }
+    let encryption_service = Arc::new(encryption_service);
+    
+    // ✅ NEW: One embedding model shared by semantic search and the memory system
+    let embedder = create_embedder(&config).await?;
+    
+    // ✅ NEW: HNSW indexes for semantic search and memories, snapshotted to disk
+    let snapshot_interval = std::time::Duration::from_secs(config.vector_index_snapshot_interval_secs.max(1));
+    let embedder_identity = embedder.identity();
+    let semantic_index = Arc::new(VectorIndex::open(&config.vector_index_dir, "semantic", embedder.dimension(), &embedder_identity));
+    let memory_index = Arc::new(VectorIndex::open(&config.vector_index_dir, "memories", embedder.dimension(), &embedder_identity));
+    semantic_index.start_snapshot_task(snapshot_interval);
+    memory_index.start_snapshot_task(snapshot_interval);
+    
+    // ✅ NEW: Optional cross-encoder that reranks hybrid retrieval results
+    let reranker = create_reranker(&config).await;
+    
     // Initialize systems
     let blockchain_client = Arc::new(BlockchainClient::new(&config).await?);
-    let orchestrator = Arc::new(MuseOrchestrator::new(config.clone()).await?);
-    let memory_system = Arc::new(MemorySystem::new(&config).await?);
-    let plugin_system = Arc::new(PluginSystem::new().await?);
-    let verification_system = Arc::new(VerificationSystem::new(&config)?);
-    let ipfs_chat_history = Arc::new(IPFSChatHistoryManager::new(&config).await?);
+    blockchain_client.start_event_listener();
+    let orchestrator = Arc::new(MuseOrchestrator::new(config.clone(), model_registry.clone()).await?);
+    // ✅ NEW: Picks which muses answer in group sessions
+    let group_chat = Arc::new(GroupChatCoordinator::new(orchestrator.clone(), llama_engine.clone(), &config));
+    let mut memory_system = MemorySystem::new(&config, storage.clone(), encryption_service.clone(), embedder.clone(), memory_index).await?;
+    let mut plugin_system = PluginSystem::new().await?;
+    let mut verification_system = VerificationSystem::new(&config)?;
+    let auth_service = Arc::new(AuthService::new(&config));
+    // ✅ NEW: Old chat segments are summarised by the local model when one is loaded
+    let mut ipfs_chat_history = IPFSChatHistoryManager::new(storage.clone(), encryption_service.clone()).await?
+        .with_chat_config(ChatHistoryConfig::from_config(&config));
+    if let Some(engine) = &llama_engine {
+        ipfs_chat_history = ipfs_chat_history.with_llama_engine(engine.clone());
+    }
+    let ipfs_chat_history = Arc::new(ipfs_chat_history);
     let tee_service = Arc::new(MuseTEEService::new());
-    let rating_market = Arc::new(AIAlignmentMarket::new(blockchain_client.clone(), config.clone()));
-    let semantic_search = Arc::new(SemanticSearchService::new(config.clone(), ipfs_chat_history.clone()));
-    let template_manager = Arc::new(Mutex::new(TemplateManager::new()));
-    let avatar_manager = Arc::new(Mutex::new(AvatarManager::new()));
-    let training_data_market = Arc::new(Mutex::new(TrainingDataMarketplace::new(
+    let mut rating_market = AIAlignmentMarket::new(blockchain_client.clone(), config.clone());
+    let mut semantic_search = SemanticSearchService::new(config.clone(), ipfs_chat_history.clone(), storage.clone(), embedder.clone(), semantic_index);
+    if let Some(reranker) = &reranker {
+        memory_system = memory_system.with_reranker(reranker.clone());
+        semantic_search = semantic_search.with_reranker(reranker.clone());
+    }
+    let semantic_search = Arc::new(semantic_search);
+    // ✅ NEW: Entries from a snapshot built by another embedder are re-embedded in the background
+    {
+        let semantic_search = semantic_search.clone();
+        tokio::spawn(async move {
+            semantic_search.reembed_stale_entries().await;
+        });
+    }
+    let mut template_manager = TemplateManager::new();
+    let mut avatar_manager = AvatarManager::new().with_storage(storage.clone());
+    let mut training_data_market = TrainingDataMarketplace::new(
         config.clone(),
         blockchain_client.clone(),
         semantic_search.clone(),
         ipfs_chat_history.clone(),
-    )));
+    );
+    let mut user_muses = HashMap::new();
+    let mut user_muse_repository: Option<Arc<dyn UserMuseRepository>> = None;
+    
+    // Rehydrate in-memory state from the database
+    if let Some(database) = &database {
+        memory_system = memory_system.with_repository(database.clone()).await?;
+        plugin_system = plugin_system.with_repository(database.clone()).await?;
+        template_manager = template_manager.with_repository(database.clone()).await?;
+        avatar_manager = avatar_manager.with_repository(database.clone()).await?;
+        training_data_market = training_data_market.with_repository(database.clone()).await?;
+        verification_system = verification_system.with_repository(database.clone()).await?;
+        rating_market = rating_market.with_repository(database.clone()).await?;
+        user_muses = database.load_user_muses().await?;
+        println!("👤 Rehydrated muse ownership for {} users", user_muses.len());
+        user_muse_repository = Some(database.clone());
+    }
+    
+    let memory_system = Arc::new(memory_system);
+    let verification_system = Arc::new(verification_system);
+    let rating_market = Arc::new(rating_market);
+    
+    // ✅ NEW: Background job enforcing RetentionPriority TTLs and importance decay
+    let memory_retention = Arc::new(MemoryRetentionEngine::new(memory_system.clone(), &config));
+    memory_retention.start();
+    
+    // ✅ NEW: Background job merging related memories into long-term summaries
+    let memory_consolidator = Arc::new(MemoryConsolidator::new(memory_system.clone(), llama_engine.clone(), &config));
+    memory_consolidator.start();
+    let plugin_system = Arc::new(plugin_system);
+    let template_manager = Arc::new(Mutex::new(template_manager));
+    let avatar_manager = Arc::new(Mutex::new(avatar_manager));
+    let training_data_market = Arc::new(Mutex::new(training_data_market));
+    
+    // ✅ NEW: Right-to-be-forgotten erasure across every subsystem holding user data
+    let mut data_erasure = DataErasureService::new(
+        ipfs_chat_history.clone(),
+        memory_system.clone(),
+        semantic_search.clone(),
+        training_data_market.clone(),
+        avatar_manager.clone(),
+        verification_system.clone(),
+        storage.clone(),
+    ).with_consolidator(memory_consolidator.clone());
+    if let Some(database) = &database {
+        data_erasure = data_erasure.with_repository(database.clone()).await?;
+    }
+    let data_erasure = Arc::new(data_erasure);
+    
+    // ✅ NEW: Export a user's history with a muse and rehydrate it on another deployment
+    let data_export = Arc::new(DataExportService::new(
+        ipfs_chat_history.clone(),
+        memory_system.clone(),
+        semantic_search.clone(),
+        rating_market.clone(),
+        verification_system.clone(),
+        storage.clone(),
+    ));
+    
+    // ✅ NEW: Tools muses can call while answering chat messages
+    let tool_registry = Arc::new(ToolRegistry::new(
+        memory_system.clone(),
+        semantic_search.clone(),
+        template_manager.clone(),
+        plugin_system.clone(),
+        &config,
+    ));
+    
+    // ✅ NEW: Muses converse with each other on a seed topic for showcase and training data
+    let agent_workflow = Arc::new(AgentWorkflow::new(
+        orchestrator.clone(),
+        context_builder.clone(),
+        group_chat.clone(),
+        ipfs_chat_history.clone(),
+        memory_system.clone(),
+        verification_system.clone(),
+        &config,
+    ));
     
     println!("🌐 IPFS Chat History Manager initialized - Web3-native conversation persistence");
     println!("🔒 TEE Attestation Service initialized - World's first verifiable AI companions");
@@ -136,15 +359,37 @@ async fn main() -> Result<(), anyhow::Error> {
         template_manager,
         avatar_manager,
         training_data_market,
-        user_muses: Arc::new(RwLock::new(HashMap::new())),
+        user_muses: Arc::new(RwLock::new(user_muses)),
+        user_muse_repository,
+        auth_service,
+        storage,
+        encryption_service,
+        embedder,
+        memory_retention,
+        memory_consolidator,
+        data_erasure,
+        data_export,
+        context_builder,
+        group_chat,
+        agent_workflow,
+        tool_registry,
+        model_registry,
+        model_downloads,
     });
     
     // Build router
     let app = Router::new()
         .route("/health", axum::routing::get(|| async { "OK" }))
         .route("/api/health", axum::routing::get(|| async { axum::Json(serde_json::json!({"status": "OK", "timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()})) }))
+        .merge(route::auth_routes())
         .merge(route::muse_routes())
         .merge(route::chat_routes())
+        .merge(route::workflow_routes())
+        .merge(route::model_routes())
+        .merge(route::permission_routes())
+        .merge(route::key_routes())
+        .merge(route::erasure_routes())
+        .merge(route::export_routes())
         .merge(route::memory_routes())
         .merge(memory_routes_enhanced::enhanced_memory_routes())
         .merge(route::plugin_routes())
@@ -155,6 +400,7 @@ async fn main() -> Result<(), anyhow::Error> {
         .merge(route::avatar_routes())
         .merge(route::training_data_routes())
         .merge(route::dat_routes())
+        .merge(route::storage_routes())
         .with_state(app_state);
     
     // Start server
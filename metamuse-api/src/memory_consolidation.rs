@@ -0,0 +1,531 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+
+use crate::config::Config;
+use crate::llama_engine_wrapper::LlamaEngineWrapper;
+use crate::persist_memory::{MemorySystem, MuseMemory};
+
+/// Runs kept for the stats endpoint
+const RUN_HISTORY: usize = 24;
+/// Characters of each source memory shown to the model
+const SOURCE_EXCERPT_CHARS: usize = 400;
+
+/// Which memories are merged and what happens to the originals
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsolidationPolicy {
+    /// Cosine similarity to the cluster centroid needed to join a cluster
+    pub similarity_threshold: f32,
+    pub min_cluster_size: usize,
+    pub max_cluster_size: usize,
+    /// Memories younger than this are left alone so recent turns keep their detail
+    pub min_age_secs: u64,
+    /// Multiplier applied to the importance of consolidated originals
+    pub demotion_factor: f32,
+}
+
+impl ConsolidationPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        let min_cluster_size = config.memory_consolidation_min_cluster_size.max(2);
+        Self {
+            similarity_threshold: config.memory_consolidation_similarity.clamp(-1.0, 1.0),
+            min_cluster_size,
+            max_cluster_size: config.memory_consolidation_max_cluster_size.max(min_cluster_size),
+            min_age_secs: config.memory_consolidation_min_age_hours * 60 * 60,
+            demotion_factor: config.memory_consolidation_demotion.clamp(0.0, 1.0),
+        }
+    }
+}
+
+/// Outcome of one consolidation pass
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConsolidationRun {
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub muses: usize,
+    pub candidates: usize,
+    pub clusters: usize,
+    /// Consolidated memories written
+    pub consolidated: usize,
+    /// Originals demoted into them
+    pub demoted: usize,
+    pub failures: usize,
+    pub skipped_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConsolidationStats {
+    pub runs: u64,
+    pub last_run_at: Option<u64>,
+    pub total_consolidated: usize,
+    pub total_demoted: usize,
+    pub recent_runs: Vec<ConsolidationRun>,
+}
+
+/// Background job that clusters related memories per muse and has the local LLM
+/// merge each cluster into one long-term memory
+pub struct MemoryConsolidator {
+    memory_system: Arc<MemorySystem>,
+    llama_engine: Option<Arc<Mutex<LlamaEngineWrapper>>>,
+    policy: ConsolidationPolicy,
+    interval: Duration,
+    stats: RwLock<ConsolidationStats>,
+    // Serialises scheduled and on-demand passes so a cluster is never merged twice
+    running: Mutex<()>,
+}
+
+impl MemoryConsolidator {
+    pub fn new(
+        memory_system: Arc<MemorySystem>,
+        llama_engine: Option<Arc<Mutex<LlamaEngineWrapper>>>,
+        config: &Config,
+    ) -> Self {
+        Self {
+            memory_system,
+            llama_engine,
+            policy: ConsolidationPolicy::from_config(config),
+            interval: Duration::from_secs(config.memory_consolidation_interval_secs.max(1)),
+            stats: RwLock::new(ConsolidationStats::default()),
+            running: Mutex::new(()),
+        }
+    }
+
+    pub fn policy(&self) -> &ConsolidationPolicy {
+        &self.policy
+    }
+
+    pub async fn stats(&self) -> ConsolidationStats {
+        self.stats.read().await.clone()
+    }
+
+    /// Consolidate every muse now and record the run in the stats
+    pub async fn run_once(&self) -> Result<ConsolidationRun> {
+        let muse_ids = self.memory_system.muse_ids().await;
+        self.run(muse_ids).await
+    }
+
+    /// Consolidate a single muse now
+    pub async fn consolidate_muse(&self, muse_id: &str) -> Result<ConsolidationRun> {
+        self.run(vec![muse_id.to_string()]).await
+    }
+
+    async fn run(&self, muse_ids: Vec<String>) -> Result<ConsolidationRun> {
+        let _guard = self.running.lock().await;
+        let started = std::time::Instant::now();
+        let now = now_secs();
+        let mut run = ConsolidationRun { started_at: now, ..Default::default() };
+
+        match &self.llama_engine {
+            None => run.skipped_reason = Some("no language model loaded".to_string()),
+            Some(engine) => {
+                for muse_id in &muse_ids {
+                    run.muses += 1;
+                    let candidates = self.memory_system
+                        .consolidation_candidates(muse_id, self.policy.min_age_secs, now)
+                        .await;
+                    run.candidates += candidates.len();
+
+                    // Clusters never mix users, so a summary only carries one user's content
+                    let clusters: Vec<Vec<MuseMemory>> = group_by_user(candidates)
+                        .into_iter()
+                        .flat_map(|group| cluster_memories(group, &self.policy))
+                        .collect();
+                    for cluster in clusters {
+                        run.clusters += 1;
+                        match self.consolidate_cluster(engine, muse_id, &cluster).await {
+                            Ok(consolidated_id) => {
+                                run.consolidated += 1;
+                                run.demoted += cluster.len();
+                                println!("🧩 Consolidated {} memories of muse {} into {}", cluster.len(), muse_id, consolidated_id);
+                            }
+                            Err(e) => {
+                                run.failures += 1;
+                                println!("⚠️  Failed to consolidate memories of muse {}: {}", muse_id, e);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        run.duration_ms = started.elapsed().as_millis() as u64;
+
+        let mut stats = self.stats.write().await;
+        stats.runs += 1;
+        stats.last_run_at = Some(now);
+        stats.total_consolidated += run.consolidated;
+        stats.total_demoted += run.demoted;
+        stats.recent_runs.push(run.clone());
+        if stats.recent_runs.len() > RUN_HISTORY {
+            stats.recent_runs.remove(0);
+        }
+
+        Ok(run)
+    }
+
+    /// Re-summarise sources released when their summary was removed. Sources that
+    /// changed meanwhile are skipped, and groups too small to consolidate stay as they
+    /// are until a later pass picks them up.
+    pub async fn reconsolidate(&self, sources: Vec<MuseMemory>) -> Result<Option<String>> {
+        let (Some(engine), Some(muse_id)) = (&self.llama_engine, sources.first().map(|m| m.muse_id.clone())) else {
+            return Ok(None);
+        };
+        let muse_id = muse_id.as_str();
+        let _guard = self.running.lock().await;
+
+        let mut cluster = Vec::with_capacity(sources.len());
+        for source in sources {
+            if let Some(memory) = self.memory_system.get_memory(muse_id, &source.memory_id).await {
+                if memory.consolidated_into.is_none() {
+                    cluster.push(memory);
+                }
+            }
+        }
+        if cluster.len() < self.policy.min_cluster_size {
+            return Ok(None);
+        }
+
+        let consolidated_id = self.consolidate_cluster(engine, muse_id, &cluster).await?;
+        println!("🧩 Re-consolidated {} memories of muse {} into {}", cluster.len(), muse_id, consolidated_id);
+        Ok(Some(consolidated_id))
+    }
+
+    /// Re-summarise every group in the background so callers don't wait on the model
+    pub fn reconsolidate_in_background(self: &Arc<Self>, groups: Vec<Vec<MuseMemory>>) {
+        if groups.is_empty() {
+            return;
+        }
+        let consolidator = self.clone();
+        tokio::spawn(async move {
+            for sources in groups {
+                if let Err(e) = consolidator.reconsolidate(sources).await {
+                    println!("⚠️  Failed to re-consolidate released memories: {}", e);
+                }
+            }
+        });
+    }
+
+    async fn consolidate_cluster(
+        &self,
+        engine: &Arc<Mutex<LlamaEngineWrapper>>,
+        muse_id: &str,
+        cluster: &[MuseMemory],
+    ) -> Result<String> {
+        let prompt = consolidation_prompt(cluster);
+        let summary = engine.lock().await.generate(&prompt, 0.3, 512).await?;
+        let summary = summary.trim();
+
+        // The wrapper answers with an explanation instead of failing when inference is unavailable
+        if summary.is_empty() || summary.contains("technical limitation") {
+            return Err(anyhow::anyhow!("model returned no usable summary"));
+        }
+
+        self.memory_system
+            .store_consolidated_memory(muse_id, summary, cluster, self.policy.demotion_factor)
+            .await
+    }
+
+    pub fn start(self: &Arc<Self>) {
+        let consolidator = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(consolidator.interval);
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                match consolidator.run_once().await {
+                    Ok(run) if run.consolidated > 0 => {
+                        println!("🧩 Memory consolidation merged {} memories into {}", run.demoted, run.consolidated);
+                    }
+                    Ok(_) => {}
+                    Err(e) => println!("⚠️  Memory consolidation failed: {}", e),
+                }
+            }
+        });
+    }
+}
+
+/// Split candidates by the user they came from; memories without a user form their own group
+fn group_by_user(candidates: Vec<MuseMemory>) -> Vec<Vec<MuseMemory>> {
+    let mut groups: Vec<(Option<String>, Vec<MuseMemory>)> = Vec::new();
+    for memory in candidates {
+        let user = memory.interaction_data.user_address.as_ref().map(|address| address.to_lowercase());
+        match groups.iter_mut().find(|(key, _)| *key == user) {
+            Some((_, group)) => group.push(memory),
+            None => groups.push((user, vec![memory])),
+        }
+    }
+    groups.into_iter().map(|(_, group)| group).collect()
+}
+
+/// Greedy centroid clustering. Memories join a cluster when they are close to its
+/// centroid and share its category or at least one tag; the most important
+/// unclustered memory seeds each new cluster.
+fn cluster_memories(mut candidates: Vec<MuseMemory>, policy: &ConsolidationPolicy) -> Vec<Vec<MuseMemory>> {
+    candidates.sort_by(|a, b| b.importance.total_cmp(&a.importance));
+
+    let mut clustered = vec![false; candidates.len()];
+    let mut clusters = Vec::new();
+
+    for seed in 0..candidates.len() {
+        if clustered[seed] {
+            continue;
+        }
+        let Some(seed_embedding) = candidates[seed].embedding.as_ref() else {
+            continue;
+        };
+
+        let mut members = vec![seed];
+        let mut centroid = seed_embedding.clone();
+        let mut tags: Vec<&String> = candidates[seed].tags.iter().collect();
+        let category = candidates[seed].category.to_string();
+
+        for other in (seed + 1)..candidates.len() {
+            if members.len() >= policy.max_cluster_size {
+                break;
+            }
+            if clustered[other] {
+                continue;
+            }
+            let candidate = &candidates[other];
+            let Some(embedding) = candidate.embedding.as_ref() else {
+                continue;
+            };
+            if embedding.len() != centroid.len() {
+                continue;
+            }
+
+            let related = candidate.category.to_string() == category
+                || candidate.tags.iter().any(|tag| tags.contains(&tag));
+            if !related || cosine(&centroid, embedding) < policy.similarity_threshold {
+                continue;
+            }
+
+            // Running mean keeps the centroid representative as the cluster grows
+            let n = members.len() as f32;
+            for (c, v) in centroid.iter_mut().zip(embedding) {
+                *c = (*c * n + v) / (n + 1.0);
+            }
+            tags.extend(candidate.tags.iter());
+            members.push(other);
+        }
+
+        if members.len() >= policy.min_cluster_size {
+            for &member in &members {
+                clustered[member] = true;
+            }
+            clusters.push(members);
+        }
+    }
+
+    clusters.into_iter()
+        .map(|members| {
+            let mut cluster: Vec<MuseMemory> = members.into_iter().map(|i| candidates[i].clone()).collect();
+            cluster.sort_by_key(|m| m.timestamp);
+            cluster
+        })
+        .collect()
+}
+
+fn consolidation_prompt(cluster: &[MuseMemory]) -> String {
+    let excerpts: Vec<String> = cluster.iter()
+        .enumerate()
+        .map(|(i, memory)| {
+            let user: String = memory.interaction_data.user_prompt.chars().take(SOURCE_EXCERPT_CHARS).collect();
+            let muse: String = memory.interaction_data.ai_response.chars().take(SOURCE_EXCERPT_CHARS).collect();
+            format!("{}. User: {}\n   Muse: {}", i + 1, user, muse)
+        })
+        .collect();
+
+    format!(
+        "You maintain the long-term memory of an AI companion. The conversation excerpts below are related. \
+         Write one concise long-term memory (at most 5 sentences) that keeps the lasting facts, preferences, \
+         decisions and emotional context about the user, and drops small talk and repetition. \
+         Write in the third person about \"the user\" and reply with the memory only.\n\n{}\n\nLong-term memory:",
+        excerpts.join("\n")
+    )
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedder::{Embedder, HashingEmbedder};
+    use crate::encryption::EncryptionService;
+    use crate::muse_orchestrator::MuseTraits;
+    use crate::persist_memory::{InteractionData, MemoryCategory, RetentionPriority, CONSOLIDATED_TAG};
+    use crate::storage_backend::LocalStorageBackend;
+    use crate::vector_index::VectorIndex;
+
+    const ALICE: &str = "0x00000000000000000000000000000000000a11ce";
+    const BOB: &str = "0x0000000000000000000000000000000000000b0b";
+
+    fn policy() -> ConsolidationPolicy {
+        ConsolidationPolicy {
+            similarity_threshold: 0.9,
+            min_cluster_size: 2,
+            max_cluster_size: 4,
+            min_age_secs: 0,
+            demotion_factor: 0.5,
+        }
+    }
+
+    fn memory(id: &str, user: Option<&str>, embedding: Option<Vec<f32>>, importance: f32, timestamp: u64) -> MuseMemory {
+        MuseMemory {
+            memory_id: id.to_string(),
+            muse_id: "7".to_string(),
+            interaction_data: InteractionData {
+                user_prompt: format!("I keep thinking about my trip to Lisbon ({})", id),
+                ai_response: "It sounds like it meant a lot to you.".to_string(),
+                personality_traits: MuseTraits { creativity: 50, wisdom: 50, humor: 50, empathy: 50 },
+                context_used: Vec::new(),
+                session_id: None,
+                conversation_turn: 1,
+                response_time_ms: 0,
+                model_used: "mock".to_string(),
+                prompt_tokens: None,
+                response_tokens: None,
+                user_satisfaction: None,
+                user_address: user.map(str::to_string),
+                tool_calls: Vec::new(),
+            },
+            embedding,
+            importance,
+            timestamp,
+            ipfs_hash: None,
+            tags: vec!["travel".to_string()],
+            category: MemoryCategory::Personal,
+            emotional_tone: None,
+            context_window: None,
+            version: 1,
+            access_count: 0,
+            last_accessed: timestamp,
+            retention_priority: RetentionPriority::Medium,
+            consolidated_into: None,
+            history: Vec::new(),
+        }
+    }
+
+    fn ids(cluster: &[MuseMemory]) -> Vec<&str> {
+        cluster.iter().map(|m| m.memory_id.as_str()).collect()
+    }
+
+    #[test]
+    fn similar_memories_cluster_and_outliers_stay_out() {
+        let candidates = vec![
+            memory("a", Some(ALICE), Some(vec![1.0, 0.0, 0.0]), 0.4, 30),
+            memory("b", Some(ALICE), Some(vec![0.98, 0.1, 0.0]), 0.9, 20),
+            memory("c", Some(ALICE), Some(vec![0.0, 0.0, 1.0]), 0.5, 10),
+            memory("d", Some(ALICE), Some(vec![0.99, 0.05, 0.0]), 0.3, 10),
+        ];
+
+        let clusters = cluster_memories(candidates, &policy());
+        assert_eq!(clusters.len(), 1);
+        // Members come back oldest first
+        assert_eq!(ids(&clusters[0]), vec!["d", "b", "a"]);
+    }
+
+    #[test]
+    fn unrelated_memories_need_a_shared_category_or_tag() {
+        let mut other = memory("b", Some(ALICE), Some(vec![1.0, 0.0]), 0.5, 2);
+        other.category = MemoryCategory::Learning;
+        other.tags = vec!["rust".to_string()];
+        let candidates = vec![memory("a", Some(ALICE), Some(vec![1.0, 0.0]), 0.9, 1), other.clone()];
+        assert!(cluster_memories(candidates, &policy()).is_empty());
+
+        other.tags.push("travel".to_string());
+        let candidates = vec![memory("a", Some(ALICE), Some(vec![1.0, 0.0]), 0.9, 1), other];
+        assert_eq!(cluster_memories(candidates, &policy()).len(), 1);
+    }
+
+    #[test]
+    fn clusters_respect_size_limits_and_skip_unembedded_memories() {
+        let policy = ConsolidationPolicy { max_cluster_size: 2, ..policy() };
+        let candidates = vec![
+            memory("a", Some(ALICE), Some(vec![1.0, 0.0]), 0.9, 1),
+            memory("b", Some(ALICE), Some(vec![1.0, 0.0]), 0.8, 2),
+            memory("c", Some(ALICE), Some(vec![1.0, 0.0]), 0.7, 3),
+            memory("d", Some(ALICE), None, 0.6, 4),
+        ];
+
+        // "c" is left over and cannot form a cluster on its own; "d" has no embedding
+        let clusters = cluster_memories(candidates, &policy);
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(ids(&clusters[0]), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn users_are_never_mixed() {
+        let candidates = vec![
+            memory("a", Some(ALICE), Some(vec![1.0, 0.0]), 0.9, 1),
+            memory("b", Some(BOB), Some(vec![1.0, 0.0]), 0.8, 2),
+            memory("c", Some(&ALICE.to_uppercase().replace("0X", "0x")), Some(vec![1.0, 0.0]), 0.7, 3),
+            memory("d", None, Some(vec![1.0, 0.0]), 0.6, 4),
+        ];
+
+        let groups: Vec<Vec<String>> = group_by_user(candidates).into_iter()
+            .map(|group| group.into_iter().map(|m| m.memory_id).collect())
+            .collect();
+        assert_eq!(groups, vec![vec!["a", "c"], vec!["b"], vec!["d"]]);
+    }
+
+    #[tokio::test]
+    async fn consolidated_originals_are_demoted() {
+        let dir = std::env::temp_dir().join(format!("memory_consolidation_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let dir_str = dir.to_str().unwrap();
+        let config = Config::for_tests();
+        let embedder = Arc::new(HashingEmbedder::new(64));
+        let memory_system = MemorySystem::new(
+            &config,
+            Arc::new(LocalStorageBackend::new(dir_str).unwrap()),
+            Arc::new(EncryptionService::unencrypted()),
+            embedder.clone(),
+            Arc::new(VectorIndex::open(dir_str, "memories", embedder.dimension(), &embedder.identity())),
+        ).await.unwrap();
+
+        let sources = vec![
+            memory("mem_a", Some(ALICE), None, 0.8, 1),
+            memory("mem_b", Some(ALICE), None, 0.6, 2),
+        ];
+        for source in &sources {
+            assert!(memory_system.import_memory(source.clone()).await.unwrap());
+        }
+        assert!(memory_system.store_consolidated_memory("7", "The user loved Lisbon.", &[sources[0].clone(), memory("mem_bob", Some(BOB), None, 0.5, 3)], 0.5).await.is_err());
+
+        let summary_id = memory_system.store_consolidated_memory("7", "The user loved Lisbon.", &sources, 0.5).await.unwrap();
+
+        let summary = memory_system.get_memory("7", &summary_id).await.unwrap();
+        assert!(summary.tags.contains(&CONSOLIDATED_TAG.to_string()));
+        assert_eq!(summary.context_window, Some(vec!["mem_a".to_string(), "mem_b".to_string()]));
+        assert_eq!(summary.interaction_data.user_address.as_deref(), Some(ALICE));
+        assert_eq!(summary.importance, 0.8);
+
+        let original = memory_system.get_memory("7", "mem_a").await.unwrap();
+        assert_eq!(original.consolidated_into.as_deref(), Some(summary_id.as_str()));
+        assert_eq!(original.importance, 0.4);
+        assert!(matches!(original.retention_priority, RetentionPriority::Low));
+
+        // Neither the originals nor the summary are merged again
+        assert!(memory_system.consolidation_candidates("7", 0, now_secs()).await.is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,241 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use crate::config::Config;
+use crate::persist_memory::{MemorySystem, RetentionPriority};
+
+/// Sweeps kept for the stats endpoint
+const SWEEP_HISTORY: usize = 24;
+
+/// How long memories live and how their importance changes between sweeps
+#[derive(Debug, Clone, Serialize)]
+pub struct RetentionPolicy {
+    /// Time to live per priority, counted from creation or last access, whichever is later.
+    /// `None` never expires.
+    pub ttl_secs: HashMap<String, Option<u64>>,
+    /// Importance halves every `importance_half_life_secs` without access
+    pub importance_half_life_secs: u64,
+    /// Fraction of the remaining headroom (1.0 - importance) regained when a memory
+    /// was retrieved since the previous sweep
+    pub access_reinforcement: f32,
+    /// Non-critical, non-high memories decayed below this are evicted
+    pub min_importance: f32,
+}
+
+impl RetentionPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        let hours = |h: u64| Some(h * 60 * 60);
+        let ttl_secs = HashMap::from([
+            ("Critical".to_string(), None),
+            ("High".to_string(), hours(config.memory_ttl_high_hours)),
+            ("Medium".to_string(), hours(config.memory_ttl_medium_hours)),
+            ("Low".to_string(), hours(config.memory_ttl_low_hours)),
+            ("Temporary".to_string(), hours(config.memory_ttl_temporary_hours)),
+        ]);
+
+        Self {
+            ttl_secs,
+            importance_half_life_secs: config.memory_importance_half_life_days.max(1) * 24 * 60 * 60,
+            access_reinforcement: config.memory_access_reinforcement.clamp(0.0, 1.0),
+            min_importance: config.memory_min_importance.max(0.0),
+        }
+    }
+
+    pub fn ttl(&self, priority: &RetentionPriority) -> Option<u64> {
+        self.ttl_secs.get(&format!("{:?}", priority)).copied().flatten()
+    }
+
+    /// Whether a memory last created or accessed at `last_touch` has outlived its TTL
+    pub fn is_expired(&self, priority: &RetentionPriority, last_touch: u64, now: u64) -> bool {
+        self.ttl(priority).is_some_and(|ttl| now.saturating_sub(last_touch) > ttl)
+    }
+
+    /// Multiplier applied to importance after `elapsed_secs` without access
+    pub fn decay_factor(&self, elapsed_secs: u64) -> f32 {
+        0.5f32.powf(elapsed_secs as f32 / self.importance_half_life_secs as f32)
+    }
+}
+
+/// Outcome of one retention sweep
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RetentionSweep {
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub examined: usize,
+    pub decayed: usize,
+    pub reinforced: usize,
+    /// Expired memories per retention priority
+    pub expired: HashMap<String, usize>,
+    /// Memories removed because their importance decayed below the floor
+    pub evicted: usize,
+    pub unpinned: usize,
+    pub unpin_failures: usize,
+    /// Pruned (expired + evicted) memories per muse
+    pub pruned_by_muse: HashMap<String, usize>,
+}
+
+impl RetentionSweep {
+    pub fn pruned(&self) -> usize {
+        self.expired.values().sum::<usize>() + self.evicted
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RetentionStats {
+    pub runs: u64,
+    pub last_run_at: Option<u64>,
+    pub total_expired: usize,
+    pub total_evicted: usize,
+    pub total_unpinned: usize,
+    pub total_unpin_failures: usize,
+    pub recent_sweeps: Vec<RetentionSweep>,
+}
+
+/// Background job that enforces `RetentionPriority` on the memory system
+pub struct MemoryRetentionEngine {
+    memory_system: Arc<MemorySystem>,
+    policy: RetentionPolicy,
+    interval: Duration,
+    stats: RwLock<RetentionStats>,
+    // Unix time of the previous sweep (startup before the first one)
+    last_sweep: AtomicU64,
+}
+
+impl MemoryRetentionEngine {
+    pub fn new(memory_system: Arc<MemorySystem>, config: &Config) -> Self {
+        Self {
+            memory_system,
+            policy: RetentionPolicy::from_config(config),
+            interval: Duration::from_secs(config.memory_retention_interval_secs.max(1)),
+            stats: RwLock::new(RetentionStats::default()),
+            last_sweep: AtomicU64::new(now_secs()),
+        }
+    }
+
+    pub fn policy(&self) -> &RetentionPolicy {
+        &self.policy
+    }
+
+    pub async fn stats(&self) -> RetentionStats {
+        self.stats.read().await.clone()
+    }
+
+    /// Run one sweep now and record it in the stats
+    pub async fn run_once(&self) -> Result<RetentionSweep> {
+        let started = std::time::Instant::now();
+        let now = now_secs();
+        let last_sweep = self.last_sweep.swap(now, Ordering::SeqCst);
+
+        let mut sweep = self.memory_system.apply_retention(&self.policy, last_sweep, now).await?;
+        sweep.started_at = now;
+        sweep.duration_ms = started.elapsed().as_millis() as u64;
+
+        let mut stats = self.stats.write().await;
+        stats.runs += 1;
+        stats.last_run_at = Some(now);
+        stats.total_expired += sweep.expired.values().sum::<usize>();
+        stats.total_evicted += sweep.evicted;
+        stats.total_unpinned += sweep.unpinned;
+        stats.total_unpin_failures += sweep.unpin_failures;
+        stats.recent_sweeps.push(sweep.clone());
+        if stats.recent_sweeps.len() > SWEEP_HISTORY {
+            stats.recent_sweeps.remove(0);
+        }
+
+        Ok(sweep)
+    }
+
+    pub fn start(self: &Arc<Self>) {
+        let engine = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(engine.interval);
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                match engine.run_once().await {
+                    Ok(sweep) if sweep.pruned() > 0 => {
+                        println!("🗑️  Memory retention pruned {} of {} memories ({} unpinned)", sweep.pruned(), sweep.examined, sweep.unpinned);
+                    }
+                    Ok(_) => {}
+                    Err(e) => println!("⚠️  Memory retention sweep failed: {}", e),
+                }
+            }
+        });
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u64 = 60 * 60;
+    const DAY: u64 = 24 * HOUR;
+
+    fn policy() -> RetentionPolicy {
+        RetentionPolicy {
+            ttl_secs: HashMap::from([
+                ("Critical".to_string(), None),
+                ("High".to_string(), Some(90 * DAY)),
+                ("Medium".to_string(), Some(30 * DAY)),
+                ("Low".to_string(), Some(7 * DAY)),
+                ("Temporary".to_string(), Some(HOUR)),
+            ]),
+            importance_half_life_secs: 30 * DAY,
+            access_reinforcement: 0.2,
+            min_importance: 0.05,
+        }
+    }
+
+    #[test]
+    fn decay_halves_every_half_life() {
+        let policy = policy();
+        assert_eq!(policy.decay_factor(0), 1.0);
+        assert!((policy.decay_factor(30 * DAY) - 0.5).abs() < 1e-6);
+        assert!((policy.decay_factor(60 * DAY) - 0.25).abs() < 1e-6);
+        assert!((policy.decay_factor(15 * DAY) - 0.5f32.sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn decay_compounds_across_sweeps() {
+        let policy = policy();
+        // Two sweeps a day apart decay as much as one sweep after two days
+        let stepwise = policy.decay_factor(DAY) * policy.decay_factor(DAY);
+        assert!((stepwise - policy.decay_factor(2 * DAY)).abs() < 1e-6);
+        assert!(policy.decay_factor(10_000 * DAY) < policy.min_importance);
+    }
+
+    #[test]
+    fn ttl_follows_priority() {
+        let policy = policy();
+        assert_eq!(policy.ttl(&RetentionPriority::Critical), None);
+        assert_eq!(policy.ttl(&RetentionPriority::High), Some(90 * DAY));
+        assert_eq!(policy.ttl(&RetentionPriority::Temporary), Some(HOUR));
+    }
+
+    #[test]
+    fn memories_expire_only_after_their_ttl() {
+        let policy = policy();
+        let now = 1_000 * DAY;
+
+        assert!(!policy.is_expired(&RetentionPriority::Temporary, now - HOUR, now));
+        assert!(policy.is_expired(&RetentionPriority::Temporary, now - HOUR - 1, now));
+        assert!(!policy.is_expired(&RetentionPriority::Low, now - 6 * DAY, now));
+        assert!(policy.is_expired(&RetentionPriority::Low, now - 8 * DAY, now));
+        assert!(!policy.is_expired(&RetentionPriority::Critical, 0, now));
+        // Clock skew never expires a memory touched "in the future"
+        assert!(!policy.is_expired(&RetentionPriority::Temporary, now + DAY, now));
+    }
+}
@@ -9,8 +9,9 @@ use axum::{
     routing::{get, post},
 };
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use crate::{AppState, persist_memory::{MemoryCategory, RetentionPriority}};
+use std::{collections::HashSet, sync::Arc};
+use crate::{AppState, auth::{AdminUser, MuseAccess, is_muse_owner}, persist_memory::{EditConflict, MemoryCategory, MemoryRevision, MemoryUpdate, MuseMemory, RetentionPriority}};
+use crate::retrieval::{RetrievalMode, RetrievalOptions};
 
 #[derive(Debug, Deserialize)]
 pub struct EnhancedMemoryQuery {
@@ -19,7 +20,33 @@ pub struct EnhancedMemoryQuery {
     pub tags: Option<String>, // comma-separated
     pub min_importance: Option<f32>,
     pub search: Option<String>,
-    pub search_type: Option<String>, // "semantic" | "keyword"
+    pub search_type: Option<String>, // "semantic" | "keyword" | "hybrid" (default)
+    pub min_relevance: Option<f32>,
+    pub recency_weight: Option<f32>,
+    pub importance_weight: Option<f32>,
+    pub rerank: Option<bool>,
+}
+
+impl EnhancedMemoryQuery {
+    /// Ranking options for search requests; hybrid with boosts unless overridden
+    fn retrieval_options(&self) -> RetrievalOptions {
+        let mut options = RetrievalOptions::hybrid();
+        options.mode = match self.search_type.as_deref() {
+            Some("semantic") => RetrievalMode::Vector,
+            Some("keyword") => RetrievalMode::Lexical,
+            _ => RetrievalMode::Hybrid,
+        };
+        if let Some(recency_weight) = self.recency_weight {
+            options.recency_weight = recency_weight;
+        }
+        if let Some(importance_weight) = self.importance_weight {
+            options.importance_weight = importance_weight;
+        }
+        if let Some(rerank) = self.rerank {
+            options.rerank = rerank;
+        }
+        options
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -44,6 +71,62 @@ pub struct MemoryEntry {
     pub retention_priority: String,
 }
 
+#[derive(Debug, Serialize)]
+pub struct MemorySearchHit {
+    #[serde(flatten)]
+    pub memory: MemoryEntry,
+    pub relevance_score: f32,
+}
+
+// ✅ NEW: Single memory with its edit history
+#[derive(Debug, Serialize)]
+pub struct MemoryDetail {
+    #[serde(flatten)]
+    pub memory: MemoryEntry,
+    pub version: u32,
+    pub context_window: Option<Vec<String>>,
+    pub consolidated_into: Option<String>,
+    pub history: Vec<MemoryRevision>,
+}
+
+impl From<MuseMemory> for MemoryDetail {
+    fn from(m: MuseMemory) -> Self {
+        Self {
+            version: m.version,
+            context_window: m.context_window.clone(),
+            consolidated_into: m.consolidated_into.clone(),
+            history: m.history.clone(),
+            memory: MemoryEntry {
+                id: m.memory_id,
+                content: m.interaction_data.user_prompt,
+                ai_response: m.interaction_data.ai_response,
+                importance: m.importance,
+                timestamp: m.timestamp,
+                category: m.category.to_string(),
+                tags: m.tags,
+                emotional_tone: m.emotional_tone.map(|et| EmotionalToneInfo {
+                    sentiment: et.sentiment,
+                    emotions: et.emotions,
+                    energy_level: et.energy_level,
+                }),
+                ipfs_hash: m.ipfs_hash,
+                access_count: m.access_count,
+                retention_priority: format!("{:?}", m.retention_priority),
+            },
+        }
+    }
+}
+
+// ✅ NEW: Correction to a memory; omitted fields are left unchanged
+#[derive(Debug, Deserialize)]
+pub struct MemoryUpdateRequest {
+    pub content: Option<String>,
+    pub ai_response: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub category: Option<String>,
+    pub importance: Option<f32>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct EmotionalToneInfo {
     pub sentiment: f32,
@@ -85,6 +168,17 @@ pub fn enhanced_memory_routes() -> Router<Arc<AppState>> {
         
         // Memory timeline
         .route("/api/v1/muses/{id}/memories/timeline", get(get_memory_timeline))
+        
+        // Retention sweep results
+        .route("/api/v1/memories/retention/stats", get(get_retention_stats))
+        
+        // Edit, pin and forget individual memories
+        .route("/api/v1/muses/{id}/memories/{memory_id}", get(get_memory).patch(update_memory).delete(forget_memory))
+        .route("/api/v1/muses/{id}/memories/{memory_id}/pin", post(pin_memory).delete(unpin_memory))
+        
+        // Consolidation into long-term memories
+        .route("/api/v1/muses/{id}/memories/consolidate", post(consolidate_memories))
+        .route("/api/v1/memories/consolidation/stats", get(get_consolidation_stats))
 }
 
 // Enhanced memory retrieval with all filtering options
@@ -92,8 +186,11 @@ async fn get_enhanced_memories(
     Path(muse_id): Path<String>,
     Query(query): Query<EnhancedMemoryQuery>,
     State(state): State<Arc<AppState>>,
+    access: MuseAccess,
 ) -> Result<impl IntoResponse, StatusCode> {
     let limit = query.limit.unwrap_or(20);
+    let visible = visible_ids(&state, &muse_id, &access).await;
+    let fetch = fetch_limit(&visible, limit);
     
     // Parse tags if provided
     let tags: Vec<String> = query.tags
@@ -102,37 +199,28 @@ async fn get_enhanced_memories(
     
     // Get memories based on query parameters
     let memories = if !tags.is_empty() {
-        state.memory_system.search_by_tags(&muse_id, &tags, limit).await
+        state.memory_system.search_by_tags(&muse_id, &tags, fetch).await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
     } else if let Some(category_str) = &query.category {
-        let category = match category_str.as_str() {
-            "emotional" => MemoryCategory::Emotional,
-            "learning" => MemoryCategory::Learning,
-            "creative" => MemoryCategory::Creative,
-            "problem_solving" => MemoryCategory::ProblemSolving,
-            "personal" => MemoryCategory::Personal,
-            "factual" => MemoryCategory::Factual,
-            _ => MemoryCategory::Conversation,
-        };
-        state.memory_system.search_by_category(&muse_id, &category, limit).await
+        let category = parse_category(category_str)?;
+        state.memory_system.search_by_category(&muse_id, &category, fetch).await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
     } else if let Some(min_importance) = query.min_importance {
-        state.memory_system.get_important_memories(&muse_id, min_importance, limit).await
+        state.memory_system.get_important_memories(&muse_id, min_importance, fetch).await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
     } else if let Some(search_query) = &query.search {
-        match query.search_type.as_deref() {
-            Some("semantic") => state.memory_system.semantic_search(&muse_id, search_query, limit).await
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
-            _ => {
-                // For keyword search, return empty for now (needs proper implementation)
-                Vec::new()
-            }
-        }
+        let min_relevance = query.min_relevance.unwrap_or(f32::MIN);
+        state.memory_system.hybrid_search(&muse_id, search_query, search_limit(&visible, limit), min_relevance, &query.retrieval_options()).await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .into_iter()
+            .map(|(_, m)| m)
+            .collect()
     } else {
         // Get recent memory objects with enhanced metadata
-        state.memory_system.get_recent_memory_objects(&muse_id, limit).await
+        state.memory_system.get_recent_memory_objects(&muse_id, fetch).await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
     };
+    let memories = scoped(memories, &visible, limit);
     
     // Convert to response format
     let memory_entries: Vec<MemoryEntry> = memories.into_iter().map(|m| MemoryEntry {
@@ -154,7 +242,7 @@ async fn get_enhanced_memories(
     }).collect();
     
     // Get enhanced statistics
-    let stats_json = state.memory_system.get_enhanced_stats(&muse_id).await
+    let stats_json = state.memory_system.get_enhanced_stats(&muse_id, visible.as_ref()).await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     
     let stats = MemoryStats {
@@ -179,14 +267,17 @@ async fn semantic_search_memories(
     Path(muse_id): Path<String>,
     Query(query): Query<EnhancedMemoryQuery>,
     State(state): State<Arc<AppState>>,
+    access: MuseAccess,
 ) -> Result<impl IntoResponse, StatusCode> {
     let search_query = query.search.ok_or(StatusCode::BAD_REQUEST)?;
     let limit = query.limit.unwrap_or(10);
+    let visible = visible_ids(&state, &muse_id, &access).await;
     
     let memories = state.memory_system
-        .semantic_search(&muse_id, &search_query, limit)
+        .semantic_search(&muse_id, &search_query, search_limit(&visible, limit))
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let memories = scoped(memories, &visible, limit);
     
     let memory_entries: Vec<MemoryEntry> = memories.into_iter().map(|m| MemoryEntry {
         id: m.memory_id,
@@ -214,23 +305,18 @@ async fn get_memories_by_category(
     Path((muse_id, category)): Path<(String, String)>,
     Query(query): Query<EnhancedMemoryQuery>,
     State(state): State<Arc<AppState>>,
+    access: MuseAccess,
 ) -> Result<impl IntoResponse, StatusCode> {
     let limit = query.limit.unwrap_or(20);
+    let visible = visible_ids(&state, &muse_id, &access).await;
     
-    let category_enum = match category.as_str() {
-        "emotional" => MemoryCategory::Emotional,
-        "learning" => MemoryCategory::Learning,
-        "creative" => MemoryCategory::Creative,
-        "problem_solving" => MemoryCategory::ProblemSolving,
-        "personal" => MemoryCategory::Personal,
-        "factual" => MemoryCategory::Factual,
-        _ => MemoryCategory::Conversation,
-    };
+    let category_enum = parse_category(&category)?;
     
     let memories = state.memory_system
-        .search_by_category(&muse_id, &category_enum, limit)
+        .search_by_category(&muse_id, &category_enum, fetch_limit(&visible, limit))
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let memories = scoped(memories, &visible, limit);
     
     let memory_entries: Vec<MemoryEntry> = memories.into_iter().map(|m| MemoryEntry {
         id: m.memory_id,
@@ -257,8 +343,10 @@ async fn get_memories_by_category(
 async fn get_memory_tags(
     Path(muse_id): Path<String>,
     State(state): State<Arc<AppState>>,
+    access: MuseAccess,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let stats = state.memory_system.get_enhanced_stats(&muse_id).await
+    let visible = visible_ids(&state, &muse_id, &access).await;
+    let stats = state.memory_system.get_enhanced_stats(&muse_id, visible.as_ref()).await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     
     let tags = stats["top_tags"].as_object()
@@ -272,24 +360,180 @@ async fn get_memory_tags(
 async fn get_enhanced_memory_stats(
     Path(muse_id): Path<String>,
     State(state): State<Arc<AppState>>,
+    access: MuseAccess,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let stats = state.memory_system.get_enhanced_stats(&muse_id).await
+    let visible = visible_ids(&state, &muse_id, &access).await;
+    let stats = state.memory_system.get_enhanced_stats(&muse_id, visible.as_ref()).await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     
     Ok((StatusCode::OK, Json(stats)))
 }
 
+// Retention policy and what recent sweeps expired, evicted and unpinned (admin only)
+async fn get_retention_stats(
+    State(state): State<Arc<AppState>>,
+    _admin: AdminUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    let stats = state.memory_retention.stats().await;
+    
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "policy": state.memory_retention.policy(),
+        "stats": stats,
+    }))))
+}
+
+// Merge related memories of a muse into long-term memories now (owner only)
+async fn consolidate_memories(
+    Path(muse_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    access: MuseAccess,
+) -> Result<impl IntoResponse, StatusCode> {
+    if !is_muse_owner(&state, access.token_id, &access.user.address).await {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    
+    let run = state.memory_consolidator.consolidate_muse(&muse_id).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    Ok((StatusCode::OK, Json(run)))
+}
+
+// Consolidation policy and recent runs across all muses (admin only)
+async fn get_consolidation_stats(
+    State(state): State<Arc<AppState>>,
+    _admin: AdminUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    let stats = state.memory_consolidator.stats().await;
+    
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "policy": state.memory_consolidator.policy(),
+        "stats": stats,
+    }))))
+}
+
+// Get a single memory with its previous versions
+async fn get_memory(
+    Path((muse_id, memory_id)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    access: MuseAccess,
+) -> Result<impl IntoResponse, StatusCode> {
+    let visible = visible_ids(&state, &muse_id, &access).await;
+    if visible.as_ref().is_some_and(|visible| !visible.contains(&memory_id)) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let memory = state.memory_system.get_memory(&muse_id, &memory_id).await
+        .ok_or(StatusCode::NOT_FOUND)?;
+    
+    Ok((StatusCode::OK, Json(MemoryDetail::from(memory))))
+}
+
+// Correct a memory's content, tags, category or importance (owner only)
+async fn update_memory(
+    Path((muse_id, memory_id)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    access: MuseAccess,
+    Json(request): Json<MemoryUpdateRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if !is_muse_owner(&state, access.token_id, &access.user.address).await {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    
+    let update = MemoryUpdate {
+        user_prompt: request.content,
+        ai_response: request.ai_response,
+        tags: request.tags.map(|tags| tags.into_iter()
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect()),
+        category: request.category.as_deref().map(parse_category).transpose()?,
+        importance: request.importance,
+    };
+    
+    let memory = state.memory_system.update_memory(&muse_id, &memory_id, update, &access.user.address).await
+        .map_err(edit_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    
+    Ok((StatusCode::OK, Json(MemoryDetail::from(memory))))
+}
+
+// Pin a memory so retention never expires it (owner only)
+async fn pin_memory(
+    Path((muse_id, memory_id)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    access: MuseAccess,
+) -> Result<impl IntoResponse, StatusCode> {
+    set_pinned(&state, &muse_id, &memory_id, true, &access).await
+}
+
+// Return a pinned memory to its previous retention priority (owner only)
+async fn unpin_memory(
+    Path((muse_id, memory_id)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    access: MuseAccess,
+) -> Result<impl IntoResponse, StatusCode> {
+    set_pinned(&state, &muse_id, &memory_id, false, &access).await
+}
+
+async fn set_pinned(
+    state: &AppState,
+    muse_id: &str,
+    memory_id: &str,
+    pinned: bool,
+    access: &MuseAccess,
+) -> Result<(StatusCode, Json<MemoryDetail>), StatusCode> {
+    if !is_muse_owner(state, access.token_id, &access.user.address).await {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    
+    let memory = state.memory_system.set_memory_pinned(muse_id, memory_id, pinned, &access.user.address).await
+        .map_err(edit_error)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    
+    Ok((StatusCode::OK, Json(MemoryDetail::from(memory))))
+}
+
+// Forget a memory: removes it, its embedding, every stored version and any summary
+// built from it, then re-summarises the summary's other sources (owner only)
+async fn forget_memory(
+    Path((muse_id, memory_id)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    access: MuseAccess,
+) -> Result<impl IntoResponse, StatusCode> {
+    if !is_muse_owner(&state, access.token_id, &access.user.address).await {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    
+    let removal = state.memory_system.forget_memory(&muse_id, &memory_id).await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    state.memory_consolidator.reconsolidate_in_background(removal.released_sources);
+    
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "success": true,
+        "memory_id": memory_id,
+        "unpinned": removal.unpinned,
+        "unpin_failures": removal.unpin_failures,
+        "removed_summaries": removal.removed_summaries,
+    }))))
+}
+
 // Get memory timeline (simplified)
 async fn get_memory_timeline(
     Path(muse_id): Path<String>,
     Query(query): Query<EnhancedMemoryQuery>,
     State(state): State<Arc<AppState>>,
+    access: MuseAccess,
 ) -> Result<impl IntoResponse, StatusCode> {
     let limit = query.limit.unwrap_or(50);
+    let visible = visible_ids(&state, &muse_id, &access).await;
     
     // Get recent memories for timeline
-    let memories = state.memory_system.get_recent_memories(&muse_id, limit).await
+    let memories = state.memory_system.get_recent_memory_objects(&muse_id, fetch_limit(&visible, limit)).await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let memories: Vec<String> = scoped(memories, &visible, limit)
+        .into_iter()
+        .map(|m| format!("User: {} | AI: {}", m.interaction_data.user_prompt, m.interaction_data.ai_response))
+        .collect();
     
     // Group by date for timeline view
     let timeline = serde_json::json!({
@@ -300,27 +544,65 @@ async fn get_memory_timeline(
     Ok((StatusCode::OK, Json(timeline)))
 }
 
-// Placeholder implementations for other endpoints
+// Hybrid BM25 + vector search with recency/importance boosts and optional rerank
 async fn search_memories(
     Path(muse_id): Path<String>,
     Query(query): Query<EnhancedMemoryQuery>,
     State(state): State<Arc<AppState>>,
+    access: MuseAccess,
 ) -> Result<impl IntoResponse, StatusCode> {
-    get_enhanced_memories(Path(muse_id), Query(query), State(state)).await
+    let search_query = query.search.clone().ok_or(StatusCode::BAD_REQUEST)?;
+    let limit = query.limit.unwrap_or(10);
+    let min_relevance = query.min_relevance.unwrap_or(f32::MIN);
+    let visible = visible_ids(&state, &muse_id, &access).await;
+    
+    let results = state.memory_system
+        .hybrid_search(&muse_id, &search_query, search_limit(&visible, limit), min_relevance, &query.retrieval_options())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    
+    let hits: Vec<MemorySearchHit> = results.into_iter()
+        .filter(|(_, m)| is_visible(m, &visible))
+        .take(limit)
+        .map(|(relevance_score, m)| MemorySearchHit {
+        memory: MemoryEntry {
+            id: m.memory_id,
+            content: m.interaction_data.user_prompt,
+            ai_response: m.interaction_data.ai_response,
+            importance: m.importance,
+            timestamp: m.timestamp,
+            category: m.category.to_string(),
+            tags: m.tags,
+            emotional_tone: m.emotional_tone.map(|et| EmotionalToneInfo {
+                sentiment: et.sentiment,
+                emotions: et.emotions,
+                energy_level: et.energy_level,
+            }),
+            ipfs_hash: m.ipfs_hash,
+            access_count: m.access_count,
+            retention_priority: format!("{:?}", m.retention_priority),
+        },
+        relevance_score,
+    }).collect();
+    
+    Ok((StatusCode::OK, Json(hits)))
 }
 
 async fn get_memories_by_tag(
     Path((muse_id, tag)): Path<(String, String)>,
     Query(query): Query<EnhancedMemoryQuery>,
     State(state): State<Arc<AppState>>,
+    access: MuseAccess,
 ) -> Result<impl IntoResponse, StatusCode> {
     let limit = query.limit.unwrap_or(20);
     let tags = vec![tag];
+    let visible = visible_ids(&state, &muse_id, &access).await;
     
     let memories = state.memory_system
-        .search_by_tags(&muse_id, &tags, limit)
+        .search_by_tags(&muse_id, &tags, fetch_limit(&visible, limit))
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let memories = scoped(memories, &visible, limit);
     
     let memory_entries: Vec<MemoryEntry> = memories.into_iter().map(|m| MemoryEntry {
         id: m.memory_id,
@@ -347,14 +629,17 @@ async fn get_important_memories(
     Path(muse_id): Path<String>,
     Query(query): Query<EnhancedMemoryQuery>,
     State(state): State<Arc<AppState>>,
+    access: MuseAccess,
 ) -> Result<impl IntoResponse, StatusCode> {
     let limit = query.limit.unwrap_or(20);
     let min_importance = query.min_importance.unwrap_or(0.7);
+    let visible = visible_ids(&state, &muse_id, &access).await;
     
     let memories = state.memory_system
-        .get_important_memories(&muse_id, min_importance, limit)
+        .get_important_memories(&muse_id, min_importance, fetch_limit(&visible, limit))
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let memories = scoped(memories, &visible, limit);
     
     let memory_entries: Vec<MemoryEntry> = memories.into_iter().map(|m| MemoryEntry {
         id: m.memory_id,
@@ -375,4 +660,57 @@ async fn get_important_memories(
     }).collect();
     
     Ok((StatusCode::OK, Json(memory_entries)))
-}
\ No newline at end of file
+}
+
+/// Memory ids the caller may read: `None` (everything) for the muse owner, otherwise
+/// their own memories and summaries built only from them, as in chat context
+async fn visible_ids(state: &AppState, muse_id: &str, access: &MuseAccess) -> Option<HashSet<String>> {
+    if is_muse_owner(state, access.token_id, &access.user.address).await {
+        None
+    } else {
+        Some(state.memory_system.visible_memory_ids(muse_id, &access.user.address).await)
+    }
+}
+
+fn is_visible(memory: &MuseMemory, visible: &Option<HashSet<String>>) -> bool {
+    visible.as_ref().is_none_or(|visible| visible.contains(&memory.memory_id))
+}
+
+/// Keep the first `limit` memories the caller may read
+fn scoped(memories: Vec<MuseMemory>, visible: &Option<HashSet<String>>, limit: usize) -> Vec<MuseMemory> {
+    memories.into_iter().filter(|m| is_visible(m, visible)).take(limit).collect()
+}
+
+/// Listing limit before scoping; in-memory listings are cheap to scan in full
+fn fetch_limit(visible: &Option<HashSet<String>>, limit: usize) -> usize {
+    if visible.is_some() { usize::MAX } else { limit }
+}
+
+/// Search limit before scoping, over-fetched since other users' hits are dropped
+fn search_limit(visible: &Option<HashSet<String>>, limit: usize) -> usize {
+    if visible.is_some() { limit * 4 } else { limit }
+}
+
+/// Category named in a request; unknown names are a client error rather than `Conversation`
+fn parse_category(category: &str) -> Result<MemoryCategory, StatusCode> {
+    match category {
+        "conversation" => Ok(MemoryCategory::Conversation),
+        "emotional" => Ok(MemoryCategory::Emotional),
+        "learning" => Ok(MemoryCategory::Learning),
+        "creative" => Ok(MemoryCategory::Creative),
+        "problem_solving" => Ok(MemoryCategory::ProblemSolving),
+        "personal" => Ok(MemoryCategory::Personal),
+        "factual" => Ok(MemoryCategory::Factual),
+        _ => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+/// 409 when the memory was edited concurrently, 500 otherwise
+fn edit_error(error: anyhow::Error) -> StatusCode {
+    if error.downcast_ref::<EditConflict>().is_some() {
+        StatusCode::CONFLICT
+    } else {
+        println!("❌ Failed to edit memory: {}", error);
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
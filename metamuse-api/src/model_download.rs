@@ -0,0 +1,568 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::{Mutex, RwLock};
+
+use crate::config::Config;
+use crate::model_registry::ModelSpec;
+
+// A stalled connection fails the download instead of hanging it; the partial file is kept
+const CHUNK_TIMEOUT: Duration = Duration::from_secs(60);
+// Progress is published about every 4 MB rather than on every chunk
+const PROGRESS_INTERVAL_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Expected checksum (and optionally size) of one model file
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestEntry {
+    /// File name in `MODEL_CACHE_DIR`, e.g. `qwen2.5-1.5b-instruct-q5_k_m.gguf`
+    pub file: String,
+    /// Hex-encoded SHA-256 of the complete file
+    pub sha256: String,
+    #[serde(default)]
+    pub size: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelManifest {
+    models: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadStatus {
+    /// Not on disk and not being downloaded
+    Missing,
+    /// Waiting for the download in progress to finish
+    Queued,
+    Downloading,
+    Verifying,
+    Ready,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadProgress {
+    pub model_id: String,
+    pub file: String,
+    pub url: String,
+    pub status: DownloadStatus,
+    pub downloaded_bytes: u64,
+    pub total_bytes: Option<u64>,
+    /// Bytes already on disk from an earlier, interrupted attempt
+    pub resumed_from: u64,
+    pub error: Option<String>,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+}
+
+impl DownloadProgress {
+    fn new(spec: &ModelSpec, url: &str) -> Self {
+        let file = file_name(&spec.path);
+        Self {
+            model_id: spec.id.clone(),
+            file,
+            url: url.to_string(),
+            status: if spec.path.exists() { DownloadStatus::Ready } else { DownloadStatus::Missing },
+            downloaded_bytes: 0,
+            total_bytes: None,
+            resumed_from: 0,
+            error: None,
+            started_at: None,
+            finished_at: None,
+        }
+    }
+
+    fn in_progress(&self) -> bool {
+        matches!(self.status, DownloadStatus::Queued | DownloadStatus::Downloading | DownloadStatus::Verifying)
+    }
+}
+
+/// Downloads the registry's models into `MODEL_CACHE_DIR`. Transfers resume from the `.part`
+/// file left by an interrupted attempt, are checked against the SHA-256 in the model manifest
+/// and only then renamed into place, so a model file is either complete or absent.
+pub struct ModelDownloadManager {
+    client: reqwest::Client,
+    models: Vec<ModelSpec>,
+    manifest_path: PathBuf,
+    allow_unverified: bool,
+    min_free_bytes: u64,
+    progress: RwLock<HashMap<String, DownloadProgress>>,
+    // One transfer at a time, so the free space check sees the previous download finished
+    transfer_lock: Mutex<()>,
+}
+
+impl ModelDownloadManager {
+    pub fn new(config: &Config, models: Vec<ModelSpec>) -> Self {
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(30))
+            .build()
+            .unwrap_or_default();
+
+        let progress = models.iter()
+            .filter_map(|spec| spec.url.as_ref().map(|url| (spec.id.clone(), DownloadProgress::new(spec, url))))
+            .collect();
+
+        Self {
+            client,
+            models,
+            manifest_path: PathBuf::from(&config.model_manifest_path),
+            allow_unverified: config.model_allow_unverified,
+            min_free_bytes: config.model_download_min_free_mb.saturating_mul(1024 * 1024),
+            progress: RwLock::new(progress),
+            transfer_lock: Mutex::new(()),
+        }
+    }
+
+    /// Start downloading `model_id`, or every missing model when None. Models already on disk
+    /// or already downloading are left alone. Returns the progress of the affected models.
+    pub async fn start(self: &Arc<Self>, model_id: Option<&str>) -> Result<Vec<DownloadProgress>> {
+        let specs: Vec<ModelSpec> = match model_id {
+            Some(id) => {
+                let spec = self.models.iter()
+                    .find(|spec| spec.id == id && spec.url.is_some())
+                    .ok_or_else(|| anyhow::anyhow!("Unknown model or no download URL: {}", id))?;
+                vec![spec.clone()]
+            }
+            None => self.models.iter().filter(|spec| spec.url.is_some()).cloned().collect(),
+        };
+
+        let mut started = Vec::new();
+        let mut progress = self.progress.write().await;
+        for spec in specs {
+            let Some(entry) = progress.get_mut(&spec.id) else {
+                continue;
+            };
+            if entry.in_progress() {
+                started.push(entry.clone());
+                continue;
+            }
+            if spec.path.exists() {
+                entry.status = DownloadStatus::Ready;
+                started.push(entry.clone());
+                continue;
+            }
+
+            entry.status = DownloadStatus::Queued;
+            entry.error = None;
+            entry.started_at = Some(now_secs());
+            entry.finished_at = None;
+            started.push(entry.clone());
+
+            let manager = self.clone();
+            tokio::spawn(async move {
+                let _transfer = manager.transfer_lock.lock().await;
+                match manager.download(&spec).await {
+                    Ok(()) => {
+                        println!("✅ Model {} downloaded to {}", spec.id, spec.path.display());
+                        manager.update(&spec.id, |p| p.status = DownloadStatus::Ready).await;
+                    }
+                    Err(e) => {
+                        println!("❌ Model {} download failed: {}", spec.id, e);
+                        manager.update(&spec.id, |p| {
+                            p.status = DownloadStatus::Failed;
+                            p.error = Some(e.to_string());
+                        }).await;
+                    }
+                }
+                manager.update(&spec.id, |p| p.finished_at = Some(now_secs())).await;
+            });
+        }
+        Ok(started)
+    }
+
+    /// Progress of every downloadable model, in id order
+    pub async fn progress(&self) -> Vec<DownloadProgress> {
+        let mut progress: Vec<DownloadProgress> = self.progress.read().await.values().cloned().collect();
+        // Files can also be copied into MODEL_CACHE_DIR by hand
+        for entry in progress.iter_mut().filter(|p| p.status == DownloadStatus::Missing) {
+            if self.models.iter().any(|spec| spec.id == entry.model_id && spec.path.exists()) {
+                entry.status = DownloadStatus::Ready;
+            }
+        }
+        progress.sort_by(|a, b| a.model_id.cmp(&b.model_id));
+        progress
+    }
+
+    async fn update(&self, model_id: &str, apply: impl FnOnce(&mut DownloadProgress)) {
+        if let Some(progress) = self.progress.write().await.get_mut(model_id) {
+            apply(progress);
+        }
+    }
+
+    async fn download(&self, spec: &ModelSpec) -> Result<()> {
+        let url = spec.url.as_deref().ok_or_else(|| anyhow::anyhow!("No download URL"))?;
+        let file = file_name(&spec.path);
+        let expected = self.manifest_entry(&file).await?;
+        if expected.is_none() {
+            println!("⚠️ No checksum for {} in {}, downloading unverified", file, self.manifest_path.display());
+        }
+
+        let dir = spec.path.parent().unwrap_or(Path::new("."));
+        tokio::fs::create_dir_all(dir).await?;
+        let part_path = spec.path.with_file_name(format!("{}.part", file));
+
+        // Resume from an earlier attempt, unless it is already longer than the file can be
+        let mut offset = tokio::fs::metadata(&part_path).await.map(|m| m.len()).unwrap_or(0);
+        if let Some(size) = expected.as_ref().and_then(|e| e.size) {
+            if offset > size {
+                offset = 0;
+            }
+        }
+
+        let mut request = self.client.get(url);
+        if offset > 0 {
+            request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+        }
+        let mut response = request.send().await?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            // The partial file already holds every byte
+            println!("📦 {} already fully downloaded, verifying", file);
+        } else if !status.is_success() {
+            return Err(anyhow::anyhow!("{} returned HTTP {}", url, status));
+        } else if offset > 0 && status != reqwest::StatusCode::PARTIAL_CONTENT {
+            println!("⚠️ {} does not support resuming, restarting {}", url, file);
+            offset = 0;
+        }
+
+        let remaining = response.content_length().filter(|_| status.is_success());
+        let total = remaining.map(|len| offset + len).or_else(|| expected.as_ref().and_then(|e| e.size));
+        if let (Some(size), Some(total)) = (expected.as_ref().and_then(|e| e.size), total) {
+            if size != total {
+                return Err(anyhow::anyhow!("{} is {} bytes but the manifest expects {}", url, total, size));
+            }
+        }
+
+        if let Some(total) = total {
+            let needed = total.saturating_sub(offset) + self.min_free_bytes;
+            let available = fs2::available_space(dir)?;
+            if available < needed {
+                return Err(anyhow::anyhow!(
+                    "Not enough disk space in {}: {} MB free, {} MB needed",
+                    dir.display(), available / (1024 * 1024), needed / (1024 * 1024)
+                ));
+            }
+        }
+
+        self.update(&spec.id, |p| {
+            p.status = DownloadStatus::Downloading;
+            p.resumed_from = offset;
+            p.downloaded_bytes = offset;
+            p.total_bytes = total;
+        }).await;
+
+        // Hash what is kept from the earlier attempt, then append the rest
+        let mut hasher = Sha256::new();
+        if offset > 0 {
+            println!("⏯️ Resuming {} from {} MB", file, offset / (1024 * 1024));
+            hash_prefix(&part_path, offset, &mut hasher).await?;
+        } else {
+            println!("📥 Downloading {} from {}", file, url);
+        }
+
+        let mut part = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(&part_path)
+            .await?;
+        part.set_len(offset).await?;
+        part.seek(std::io::SeekFrom::Start(offset)).await?;
+        let mut part = tokio::io::BufWriter::new(part);
+
+        let mut downloaded = offset;
+        let mut reported = offset;
+        if status.is_success() {
+            while let Some(chunk) = tokio::time::timeout(CHUNK_TIMEOUT, response.chunk()).await
+                .map_err(|_| anyhow::anyhow!("No data from {} for {}s", url, CHUNK_TIMEOUT.as_secs()))??
+            {
+                part.write_all(&chunk).await?;
+                hasher.update(&chunk);
+                downloaded += chunk.len() as u64;
+
+                if downloaded - reported >= PROGRESS_INTERVAL_BYTES {
+                    reported = downloaded;
+                    self.update(&spec.id, |p| p.downloaded_bytes = downloaded).await;
+                }
+            }
+        }
+        part.flush().await?;
+        part.get_ref().sync_all().await?;
+        drop(part);
+
+        self.update(&spec.id, |p| {
+            p.status = DownloadStatus::Verifying;
+            p.downloaded_bytes = downloaded;
+        }).await;
+
+        if let Some(total) = total {
+            if downloaded != total {
+                return Err(anyhow::anyhow!("Download of {} ended at {} of {} bytes", file, downloaded, total));
+            }
+        }
+        if let Some(expected) = &expected {
+            let actual = hex::encode(hasher.finalize());
+            if !actual.eq_ignore_ascii_case(expected.sha256.trim()) {
+                // A corrupt file can't be completed by resuming, so start over next time
+                let _ = tokio::fs::remove_file(&part_path).await;
+                return Err(anyhow::anyhow!("SHA-256 mismatch for {}: expected {}, got {}", file, expected.sha256, actual));
+            }
+        }
+
+        // Same directory, so the model appears complete or not at all
+        tokio::fs::rename(&part_path, &spec.path).await?;
+        Ok(())
+    }
+
+    /// Manifest entry for `file`, read on every download so the manifest can be updated live.
+    /// Unless `MODEL_ALLOW_UNVERIFIED` is set a file must have an entry.
+    async fn manifest_entry(&self, file: &str) -> Result<Option<ManifestEntry>> {
+        let manifest = match tokio::fs::read_to_string(&self.manifest_path).await {
+            Ok(contents) => serde_json::from_str::<ModelManifest>(&contents)
+                .map_err(|e| anyhow::anyhow!("Invalid model manifest {}: {}", self.manifest_path.display(), e))?,
+            Err(_) if self.allow_unverified => return Ok(None),
+            Err(e) => return Err(anyhow::anyhow!("Cannot read model manifest {}: {}", self.manifest_path.display(), e)),
+        };
+
+        match manifest.models.into_iter().find(|entry| entry.file == file) {
+            Some(entry) => Ok(Some(entry)),
+            None if self.allow_unverified => Ok(None),
+            None => Err(anyhow::anyhow!("{} has no checksum in {}", file, self.manifest_path.display())),
+        }
+    }
+}
+
+/// Feed the first `len` bytes of `path` into `hasher`
+async fn hash_prefix(path: &Path, len: u64, hasher: &mut Sha256) -> Result<()> {
+    let mut file = tokio::fs::File::open(path).await?.take(len);
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::http::{header, HeaderMap, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+    use axum::Router;
+
+    const FILE: &str = "tiny.gguf";
+
+    /// Serves `body` at `/tiny.gguf`, honouring `Range: bytes=N-` unless told not to
+    struct FileServer {
+        body: Vec<u8>,
+        honour_range: bool,
+        ranges: std::sync::Mutex<Vec<Option<String>>>,
+    }
+
+    impl FileServer {
+        fn ranges(&self) -> Vec<Option<String>> {
+            self.ranges.lock().unwrap().clone()
+        }
+    }
+
+    async fn serve_file(State(server): State<Arc<FileServer>>, headers: HeaderMap) -> Response {
+        let range = headers.get(header::RANGE).and_then(|v| v.to_str().ok()).map(str::to_string);
+        server.ranges.lock().unwrap().push(range.clone());
+
+        let len = server.body.len();
+        let start = range
+            .filter(|_| server.honour_range)
+            .and_then(|r| r.strip_prefix("bytes=")?.strip_suffix('-')?.parse::<usize>().ok());
+        match start {
+            None => (StatusCode::OK, server.body.clone()).into_response(),
+            Some(start) if start >= len => StatusCode::RANGE_NOT_SATISFIABLE.into_response(),
+            Some(start) => (
+                StatusCode::PARTIAL_CONTENT,
+                [(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, len - 1, len))],
+                server.body[start..].to_vec(),
+            ).into_response(),
+        }
+    }
+
+    async fn start_server(body: Vec<u8>, honour_range: bool) -> (Arc<FileServer>, String) {
+        let server = Arc::new(FileServer { body, honour_range, ranges: std::sync::Mutex::new(Vec::new()) });
+        let app = Router::new()
+            .route(&format!("/{}", FILE), get(serve_file))
+            .with_state(server.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/{}", listener.local_addr().unwrap(), FILE);
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (server, url)
+    }
+
+    fn model_bytes() -> Vec<u8> {
+        (0..64 * 1024u32).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    fn sha256_hex(data: &[u8]) -> String {
+        hex::encode(Sha256::digest(data))
+    }
+
+    /// Manager for one model in a fresh directory, with `manifest` as the manifest body
+    fn test_manager(name: &str, url: &str, manifest: Option<serde_json::Value>) -> (ModelDownloadManager, ModelSpec) {
+        let dir = std::env::temp_dir().join(format!("model_download_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let manifest_path = dir.join("manifest.json");
+        if let Some(manifest) = manifest {
+            std::fs::write(&manifest_path, manifest.to_string()).unwrap();
+        }
+        let spec = ModelSpec { id: "tiny".to_string(), path: dir.join(FILE), url: Some(url.to_string()) };
+        let progress = HashMap::from([(spec.id.clone(), DownloadProgress::new(&spec, url))]);
+
+        let manager = ModelDownloadManager {
+            client: reqwest::Client::new(),
+            models: vec![spec.clone()],
+            manifest_path,
+            allow_unverified: false,
+            min_free_bytes: 0,
+            progress: RwLock::new(progress),
+            transfer_lock: Mutex::new(()),
+        };
+        (manager, spec)
+    }
+
+    fn manifest_for(data: &[u8]) -> serde_json::Value {
+        serde_json::json!({ "models": [{ "file": FILE, "sha256": sha256_hex(data), "size": data.len() }] })
+    }
+
+    fn part_path(spec: &ModelSpec) -> PathBuf {
+        spec.path.with_file_name(format!("{}.part", FILE))
+    }
+
+    async fn resumed_from(manager: &ModelDownloadManager) -> u64 {
+        manager.progress().await[0].resumed_from
+    }
+
+    #[tokio::test]
+    async fn downloads_and_verifies_a_complete_file() {
+        let body = model_bytes();
+        let (server, url) = start_server(body.clone(), true).await;
+        let (manager, spec) = test_manager("full", &url, Some(manifest_for(&body)));
+
+        manager.download(&spec).await.unwrap();
+
+        assert_eq!(std::fs::read(&spec.path).unwrap(), body);
+        assert!(!part_path(&spec).exists());
+        assert_eq!(server.ranges(), vec![None]);
+        assert_eq!(manager.progress().await[0].downloaded_bytes, body.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn resumes_a_partial_file_with_a_range_request() {
+        let body = model_bytes();
+        let (server, url) = start_server(body.clone(), true).await;
+        let (manager, spec) = test_manager("resume", &url, Some(manifest_for(&body)));
+        std::fs::write(part_path(&spec), &body[..10_000]).unwrap();
+
+        manager.download(&spec).await.unwrap();
+
+        assert_eq!(std::fs::read(&spec.path).unwrap(), body);
+        assert_eq!(server.ranges(), vec![Some("bytes=10000-".to_string())]);
+        assert_eq!(resumed_from(&manager).await, 10_000);
+    }
+
+    #[tokio::test]
+    async fn restarts_when_the_server_ignores_the_range() {
+        let body = model_bytes();
+        let (server, url) = start_server(body.clone(), false).await;
+        let (manager, spec) = test_manager("no_range", &url, Some(manifest_for(&body)));
+        // Garbage in the partial file must not survive the restart
+        std::fs::write(part_path(&spec), vec![0xff; 10_000]).unwrap();
+
+        manager.download(&spec).await.unwrap();
+
+        assert_eq!(std::fs::read(&spec.path).unwrap(), body);
+        assert_eq!(server.ranges(), vec![Some("bytes=10000-".to_string())]);
+        assert_eq!(resumed_from(&manager).await, 0);
+    }
+
+    #[tokio::test]
+    async fn verifies_a_partial_file_that_is_already_complete() {
+        let body = model_bytes();
+        let (server, url) = start_server(body.clone(), true).await;
+        let (manager, spec) = test_manager("complete_part", &url, Some(manifest_for(&body)));
+        std::fs::write(part_path(&spec), &body).unwrap();
+
+        manager.download(&spec).await.unwrap();
+
+        assert_eq!(std::fs::read(&spec.path).unwrap(), body);
+        assert_eq!(server.ranges(), vec![Some(format!("bytes={}-", body.len()))]);
+        assert!(!part_path(&spec).exists());
+    }
+
+    #[tokio::test]
+    async fn checksum_mismatch_discards_the_partial_file() {
+        let body = model_bytes();
+        let (_server, url) = start_server(body.clone(), true).await;
+        // Sizes agree, so the download gets as far as hashing
+        let manifest = serde_json::json!({ "models": [{ "file": FILE, "sha256": sha256_hex(b"other"), "size": body.len() }] });
+        let (manager, spec) = test_manager("bad_hash", &url, Some(manifest));
+
+        let error = manager.download(&spec).await.unwrap_err();
+
+        assert!(error.to_string().contains("SHA-256 mismatch"), "{}", error);
+        assert!(!part_path(&spec).exists());
+        assert!(!spec.path.exists());
+    }
+
+    #[tokio::test]
+    async fn size_mismatch_fails_before_downloading() {
+        let body = model_bytes();
+        let (_server, url) = start_server(body.clone(), true).await;
+        let manifest = serde_json::json!({ "models": [{ "file": FILE, "sha256": sha256_hex(&body), "size": body.len() + 1 }] });
+        let (manager, spec) = test_manager("bad_size", &url, Some(manifest));
+
+        let error = manager.download(&spec).await.unwrap_err();
+
+        assert!(error.to_string().contains("manifest expects"), "{}", error);
+        assert!(!spec.path.exists());
+    }
+
+    #[tokio::test]
+    async fn refuses_files_without_a_manifest_entry() {
+        let body = model_bytes();
+        let (server, url) = start_server(body.clone(), true).await;
+        let manifest = serde_json::json!({ "models": [{ "file": "other.gguf", "sha256": sha256_hex(&body) }] });
+        let (manager, spec) = test_manager("no_entry", &url, Some(manifest));
+
+        let error = manager.download(&spec).await.unwrap_err();
+
+        assert!(error.to_string().contains("has no checksum"), "{}", error);
+        assert!(server.ranges().is_empty());
+        assert!(!spec.path.exists());
+
+        // Without a manifest at all the download is refused too
+        let (manager, spec) = test_manager("no_manifest", &url, None);
+        assert!(manager.download(&spec).await.is_err());
+        assert!(server.ranges().is_empty());
+    }
+}
@@ -0,0 +1,359 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use crate::ai_worker_pool::AIWorkerPool;
+use crate::config::Config;
+use crate::llama_engine_wrapper::LlamaEngineWrapper;
+
+/// A GGUF model the registry can load, identified by its file stem
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelSpec {
+    pub id: String,
+    pub path: PathBuf,
+    /// Where the file can be downloaded from
+    pub url: Option<String>,
+}
+
+/// What the registry knows about one model
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelStatus {
+    pub id: String,
+    pub path: String,
+    /// The GGUF file is on disk
+    pub available: bool,
+    pub loaded: bool,
+    /// Never evicted (the default model)
+    pub pinned: bool,
+    /// Estimated resident size while loaded: one copy per ai-worker process
+    pub memory_mb: u64,
+    pub requests: u64,
+}
+
+struct LoadedModel {
+    engine: Arc<Mutex<LlamaEngineWrapper>>,
+    // Workers started by the registry, shut down on eviction
+    pool: Option<Arc<AIWorkerPool>>,
+    memory_bytes: u64,
+    pinned: bool,
+    last_used: u64,
+    requests: u64,
+}
+
+/// Maps model ids to local GGUF files and keeps the most recently used ones loaded
+/// within `MODEL_MEMORY_BUDGET_MB`, evicting the least recently used idle model first
+pub struct ModelRegistry {
+    config: Config,
+    models: HashMap<String, ModelSpec>,
+    default_model_id: String,
+    memory_budget_bytes: u64,
+    loaded: Mutex<HashMap<String, LoadedModel>>,
+    // One guard per model, held while it loads so concurrent requests don't start the
+    // same workers twice without blocking requests for other models
+    load_guards: HashMap<String, Mutex<()>>,
+    // Budget set aside for models that are loading, changed under the `loaded` lock
+    reserved_bytes: AtomicU64,
+    clock: AtomicU64,
+}
+
+impl ModelRegistry {
+    /// Register the default model and the per-trait models, with `default_engine` (the engine
+    /// started at boot) as the pinned default
+    pub fn new(config: &Config, default_engine: Option<Arc<Mutex<LlamaEngineWrapper>>>) -> Self {
+        let default_path = PathBuf::from(&config.default_model_path);
+        let default_model_id = model_id_from_path(&config.default_model_path);
+
+        let mut models = HashMap::new();
+        models.insert(default_model_id.clone(), ModelSpec {
+            id: default_model_id.clone(),
+            path: default_path.clone(),
+            url: None,
+        });
+        for url in [
+            &config.base_model_url,
+            &config.creative_model_url,
+            &config.wisdom_model_url,
+            &config.empathy_model_url,
+            &config.humor_model_url,
+        ] {
+            let id = model_id_from_path(url);
+            let file_name = url.rsplit('/').next().unwrap_or(url);
+            models.entry(id.clone()).or_insert_with(|| ModelSpec {
+                id,
+                path: Path::new(&config.model_cache_dir).join(file_name),
+                url: Some(url.clone()),
+            });
+        }
+
+        let mut loaded = HashMap::new();
+        if let Some(engine) = default_engine {
+            // The boot engine runs one in-process copy next to its worker pool
+            let memory_bytes = estimate_memory(&default_path, config.ai_worker_pool_size + 1);
+            loaded.insert(default_model_id.clone(), LoadedModel {
+                engine,
+                pool: None,
+                memory_bytes,
+                pinned: true,
+                last_used: 0,
+                requests: 0,
+            });
+        }
+
+        println!("📚 Model registry: {} models, default {}, budget {} MB",
+                models.len(), default_model_id, config.model_memory_budget_mb);
+
+        let load_guards = models.keys().map(|id| (id.clone(), Mutex::new(()))).collect();
+
+        Self {
+            config: config.clone(),
+            models,
+            default_model_id,
+            memory_budget_bytes: config.model_memory_budget_mb.saturating_mul(1024 * 1024),
+            loaded: Mutex::new(loaded),
+            load_guards,
+            reserved_bytes: AtomicU64::new(0),
+            clock: AtomicU64::new(0),
+        }
+    }
+
+    pub fn default_model_id(&self) -> &str {
+        &self.default_model_id
+    }
+
+    /// Models that have a download URL, in id order
+    pub fn downloadable(&self) -> Vec<ModelSpec> {
+        let mut specs: Vec<ModelSpec> = self.models.values()
+            .filter(|spec| spec.url.is_some())
+            .cloned()
+            .collect();
+        specs.sort_by(|a, b| a.id.cmp(&b.id));
+        specs
+    }
+
+    /// Engine for `model_id`, or the default model's when it cannot be loaded. Returns the id
+    /// of the model that will answer; the engine is None when no model is loaded at all.
+    pub async fn route(&self, model_id: &str) -> (String, Option<Arc<Mutex<LlamaEngineWrapper>>>) {
+        match self.engine(model_id).await {
+            Ok(engine) => (model_id.to_string(), Some(engine)),
+            Err(e) if model_id == self.default_model_id => {
+                println!("⚠️ Default model {} unavailable: {}", model_id, e);
+                (model_id.to_string(), None)
+            }
+            Err(e) => {
+                println!("⚠️ Model {} unavailable ({}), using default {}", model_id, e, self.default_model_id);
+                let engine = self.engine(&self.default_model_id).await.ok();
+                (self.default_model_id.clone(), engine)
+            }
+        }
+    }
+
+    /// Engine for `model_id`, starting its ai-worker pool if it is not loaded yet
+    pub async fn engine(&self, model_id: &str) -> Result<Arc<Mutex<LlamaEngineWrapper>>> {
+        if let Some(engine) = self.touch(model_id).await {
+            return Ok(engine);
+        }
+
+        let (Some(spec), Some(load_guard)) = (self.models.get(model_id), self.load_guards.get(model_id)) else {
+            return Err(anyhow::anyhow!("Unknown model: {}", model_id));
+        };
+        let _loading = load_guard.lock().await;
+        // Another request may have finished loading it while we waited
+        if let Some(engine) = self.touch(model_id).await {
+            return Ok(engine);
+        }
+
+        if !spec.path.exists() {
+            return Err(anyhow::anyhow!("Model file not found: {}", spec.path.display()));
+        }
+        let memory_bytes = estimate_memory(&spec.path, self.config.ai_worker_pool_size);
+        if memory_bytes > self.memory_budget_bytes {
+            return Err(anyhow::anyhow!(
+                "Model {} needs ~{} MB, more than MODEL_MEMORY_BUDGET_MB",
+                model_id, memory_bytes / (1024 * 1024)
+            ));
+        }
+        let evicted = {
+            let mut loaded = self.loaded.lock().await;
+            let evicted = self.evict_for(&mut loaded, memory_bytes)?;
+            self.reserved_bytes.fetch_add(memory_bytes, Ordering::SeqCst);
+            evicted
+        };
+        // Stopping workers waits for their processes, so it happens outside the lock
+        for pool in evicted {
+            pool.shutdown().await;
+        }
+
+        // Workers take a while to start, so other models stay usable meanwhile
+        let path = spec.path.to_string_lossy().to_string();
+        println!("📥 Loading model {} from {}", model_id, path);
+        let pool = AIWorkerPool::new(&self.config, &path).await;
+
+        let mut loaded = self.loaded.lock().await;
+        self.reserved_bytes.fetch_sub(memory_bytes, Ordering::SeqCst);
+        let pool = pool?;
+        pool.start_health_monitor(Duration::from_secs(self.config.ai_worker_health_check_interval_secs));
+        let engine = Arc::new(Mutex::new(LlamaEngineWrapper::from_worker_pool(&path, pool.clone())));
+
+        let tick = self.clock.fetch_add(1, Ordering::SeqCst) + 1;
+        loaded.insert(model_id.to_string(), LoadedModel {
+            engine: engine.clone(),
+            pool: Some(pool),
+            memory_bytes,
+            pinned: false,
+            last_used: tick,
+            requests: 1,
+        });
+        println!("✅ Model {} loaded ({} MB in use)", model_id, used_bytes(&loaded) / (1024 * 1024));
+        Ok(engine)
+    }
+
+    /// Engine of a loaded model, recording the use
+    async fn touch(&self, model_id: &str) -> Option<Arc<Mutex<LlamaEngineWrapper>>> {
+        let mut loaded = self.loaded.lock().await;
+        let model = loaded.get_mut(model_id)?;
+        model.last_used = self.clock.fetch_add(1, Ordering::SeqCst) + 1;
+        model.requests += 1;
+        Some(model.engine.clone())
+    }
+
+    /// Remove the models that have to go for `needed` more bytes to fit in the budget and
+    /// return their worker pools for the caller to shut down once the lock is released.
+    /// Nothing is removed when the budget cannot be met.
+    fn evict_for(&self, loaded: &mut HashMap<String, LoadedModel>, needed: u64) -> Result<Vec<Arc<AIWorkerPool>>> {
+        let candidates: Vec<EvictionCandidate> = loaded.iter()
+            .map(|(id, model)| EvictionCandidate {
+                id: id.clone(),
+                memory_bytes: model.memory_bytes,
+                pinned: model.pinned,
+                // Only the registry holds an idle engine; requests keep their clone until they finish
+                in_use: Arc::strong_count(&model.engine) > 1,
+                last_used: model.last_used,
+            })
+            .collect();
+        let victims = select_victims(
+            &candidates,
+            self.reserved_bytes.load(Ordering::SeqCst) + needed,
+            self.memory_budget_bytes,
+        ).ok_or_else(|| anyhow::anyhow!("Memory budget is taken by models that cannot be unloaded"))?;
+
+        let mut pools = Vec::new();
+        for victim in victims {
+            if let Some(model) = loaded.remove(&victim) {
+                println!("🗑️ Unloading model {} to free {} MB", victim, model.memory_bytes / (1024 * 1024));
+                pools.extend(model.pool);
+            }
+        }
+        Ok(pools)
+    }
+
+    pub async fn statuses(&self) -> Vec<ModelStatus> {
+        let loaded = self.loaded.lock().await;
+        let mut statuses: Vec<ModelStatus> = self.models.values()
+            .map(|spec| {
+                let model = loaded.get(&spec.id);
+                ModelStatus {
+                    id: spec.id.clone(),
+                    path: spec.path.to_string_lossy().to_string(),
+                    available: spec.path.exists(),
+                    loaded: model.is_some(),
+                    pinned: model.is_some_and(|m| m.pinned),
+                    memory_mb: model.map_or(0, |m| m.memory_bytes / (1024 * 1024)),
+                    requests: model.map_or(0, |m| m.requests),
+                }
+            })
+            .collect();
+        statuses.sort_by(|a, b| a.id.cmp(&b.id));
+        statuses
+    }
+}
+
+/// Model id for a GGUF path or URL: the file name without `.gguf`
+pub fn model_id_from_path(path_or_url: &str) -> String {
+    let file_name = path_or_url.rsplit(['/', '\\']).next().unwrap_or(path_or_url);
+    file_name.strip_suffix(".gguf").unwrap_or(file_name).to_string()
+}
+
+/// Resident size of `copies` processes each holding the model file
+fn estimate_memory(path: &Path, copies: usize) -> u64 {
+    let file_size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    file_size.saturating_mul(copies.max(1) as u64)
+}
+
+fn used_bytes(loaded: &HashMap<String, LoadedModel>) -> u64 {
+    loaded.values().map(|model| model.memory_bytes).sum()
+}
+
+/// What eviction needs to know about a loaded model
+struct EvictionCandidate {
+    id: String,
+    memory_bytes: u64,
+    pinned: bool,
+    /// A request still holds the model's engine
+    in_use: bool,
+    last_used: u64,
+}
+
+/// Least recently used models to unload so `needed` more bytes fit in `budget` next to the
+/// loaded ones. Pinned models and models in use are never picked. None when unloading
+/// every other model would still not free enough.
+fn select_victims(loaded: &[EvictionCandidate], needed: u64, budget: u64) -> Option<Vec<String>> {
+    let mut used: u64 = loaded.iter().map(|model| model.memory_bytes).sum();
+    let mut idle: Vec<&EvictionCandidate> = loaded.iter()
+        .filter(|model| !model.pinned && !model.in_use)
+        .collect();
+    idle.sort_by_key(|model| model.last_used);
+
+    let mut victims = Vec::new();
+    for model in idle {
+        if used + needed <= budget {
+            break;
+        }
+        used -= model.memory_bytes;
+        victims.push(model.id.clone());
+    }
+    (used + needed <= budget).then_some(victims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(id: &str, memory_bytes: u64, last_used: u64) -> EvictionCandidate {
+        EvictionCandidate { id: id.to_string(), memory_bytes, pinned: false, in_use: false, last_used }
+    }
+
+    #[test]
+    fn nothing_is_evicted_while_the_budget_fits() {
+        let loaded = vec![model("a", 30, 1), model("b", 30, 2)];
+        assert_eq!(select_victims(&loaded, 40, 100), Some(Vec::new()));
+        assert_eq!(select_victims(&[], 100, 100), Some(Vec::new()));
+        assert_eq!(select_victims(&[], 101, 100), None);
+    }
+
+    #[test]
+    fn least_recently_used_models_go_first() {
+        let loaded = vec![model("recent", 30, 9), model("oldest", 30, 1), model("older", 30, 5)];
+        assert_eq!(select_victims(&loaded, 40, 100), Some(vec!["oldest".to_string()]));
+        assert_eq!(select_victims(&loaded, 70, 100), Some(vec!["oldest".to_string(), "older".to_string()]));
+    }
+
+    #[test]
+    fn pinned_models_are_never_evicted() {
+        let loaded = vec![EvictionCandidate { pinned: true, ..model("default", 50, 0) }, model("b", 30, 4)];
+        assert_eq!(select_victims(&loaded, 50, 100), Some(vec!["b".to_string()]));
+        assert_eq!(select_victims(&loaded, 60, 100), None);
+    }
+
+    #[test]
+    fn models_in_use_are_skipped() {
+        let loaded = vec![EvictionCandidate { in_use: true, ..model("busy", 40, 1) }, model("idle", 40, 7)];
+        assert_eq!(select_victims(&loaded, 50, 100), Some(vec!["idle".to_string()]));
+        // Freeing the idle model is not enough, so nothing is picked
+        assert_eq!(select_victims(&loaded, 70, 100), None);
+    }
+}
@@ -5,10 +5,14 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use crate::config::Config;
 use crate::llama_engine_wrapper::LlamaEngineWrapper;
-use crate::cot_personality::{CoTPersonalityEngine, CoTPersonalityResponse};
+use crate::cot_personality::{CoTPersonalityEngine, CoTPersonalityResponse, ReasoningTrace, TraitsInfluence};
 use crate::semantic_search::{SemanticSearchService, SemanticQuery};
+use crate::retrieval::RetrievalOptions;
+use crate::context_builder::{AssembledContext, TokenCounter};
+use crate::tools::{ToolCall, Toolbox};
+use crate::model_registry::{model_id_from_path, ModelRegistry};
+use crate::personality_blend::{self, BlendCandidate, BlendMetadata, BlendMode};
 use alith::core::chat::Message;
-use alith::LLM;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MuseTraits {
@@ -20,19 +24,18 @@ pub struct MuseTraits {
 
 pub struct MuseOrchestrator {
     config: Config,
-    model_path: String,
+    // ✅ NEW: Per-trait models, loaded on demand
+    model_registry: Arc<ModelRegistry>,
     agents: RwLock<HashMap<String, ()>>,
 }
 
 impl MuseOrchestrator {
-    pub async fn new(config: Config) -> Result<Self> {
-        // Store model path for on-demand loading
-        let model_path = "./models/qwen2.5-1.5b-instruct-q5_k_m.gguf";
-        println!("Model path configured: {}", model_path);
+    pub async fn new(config: Config, model_registry: Arc<ModelRegistry>) -> Result<Self> {
+        println!("Model path configured: {}", config.default_model_path);
         
         Ok(Self {
             config,
-            model_path: model_path.to_string(),
+            model_registry,
             agents: RwLock::new(HashMap::new()),
         })
     }
@@ -45,10 +48,28 @@ impl MuseOrchestrator {
     }
     
     
-    fn get_local_model_name(&self, _model_url: &str) -> String {
-        // For now, use a working model name that should be available in Alith
-        // This will use the default model configuration
-        "gpt-4".to_string()
+    fn get_local_model_name(&self, model_url: &str) -> String {
+        // Registry id of the GGUF file the URL downloads
+        model_id_from_path(model_url)
+    }
+
+    /// ✅ NEW: Model for a muse's dominant trait, falling back to the default model when its file
+    /// is missing or cannot be loaded. Returns the id of the model that will answer.
+    pub async fn select_engine(&self, traits: &MuseTraits) -> (String, Option<Arc<Mutex<LlamaEngineWrapper>>>) {
+        let (trait_name, trait_value) = self.get_dominant_trait(traits);
+        self.engine_for_trait(&trait_name, trait_value).await
+    }
+
+    /// Model specialised for `trait_name` at `trait_value`, or the default model
+    async fn engine_for_trait(&self, trait_name: &str, trait_value: u8) -> (String, Option<Arc<Mutex<LlamaEngineWrapper>>>) {
+        let model_id = if self.config.trait_model_routing {
+            let model_id = self.get_local_model_name(self.select_model_for_trait(trait_name, trait_value));
+            println!("🧭 Trait {} ({}) routes to model {}", trait_name, trait_value, model_id);
+            model_id
+        } else {
+            self.model_registry.default_model_id().to_string()
+        };
+        self.model_registry.route(&model_id).await
     }
     
     fn select_model_for_trait(&self, trait_name: &str, trait_value: u8) -> &str {
@@ -97,35 +118,50 @@ impl MuseOrchestrator {
     }
     
     
-    pub async fn generate_response(
-        &self,
-        muse_id: &str,
-        traits: &MuseTraits,
-        user_message: &str,
-        context: Option<Vec<String>>,
-        llama_engine: Option<Arc<Mutex<LlamaEngineWrapper>>>,
-    ) -> Result<String> {
-        // Prepare muse if needed
-        self.prepare_for_muse(muse_id).await?;
-        
+    /// ✅ NEW: Prompt for one message without chat history: personality, memories and the message
+    pub fn single_turn_context(&self, traits: &MuseTraits, user_message: &str, memories: Vec<String>) -> AssembledContext {
         // Build context string
-        let context_str = match context {
-            Some(memories) if !memories.is_empty() => {
-                format!("Previous context: {}", memories.join(" | "))
-            },
-            _ => String::new(),
+        let context_str = if memories.is_empty() {
+            String::new()
+        } else {
+            format!("Previous context: {}", memories.join(" | "))
         };
         
         // Create personality-driven system prompt
         let system_prompt = self.build_personality_system_prompt(traits);
         
         // Create the full prompt with context and user message
-        let full_prompt = if context_str.is_empty() {
+        let prompt = if context_str.is_empty() {
             format!("{}\n\nUser: {}\n\nMuse:", system_prompt, user_message)
         } else {
             format!("{}\n\n{}\n\nUser: {}\n\nMuse:", system_prompt, context_str, user_message)
         };
         
+        AssembledContext {
+            prompt,
+            history: Vec::new(),
+            memories_used: memories,
+            prompt_tokens: 0,
+            exact: false,
+            context_size: 0,
+            max_response_tokens: 4096,
+            dropped: 0,
+        }
+    }
+    
+    pub async fn generate_response(
+        &self,
+        muse_id: &str,
+        traits: &MuseTraits,
+        user_message: &str,
+        context: Option<Vec<String>>,
+        llama_engine: Option<Arc<Mutex<LlamaEngineWrapper>>>,
+    ) -> Result<String> {
+        // Prepare muse if needed
+        self.prepare_for_muse(muse_id).await?;
+        
+        let full_prompt = self.single_turn_context(traits, user_message, context.unwrap_or_default()).prompt;
+        
         // Check if we have a shared LlamaEngineWrapper available
         if let Some(engine_arc) = llama_engine {
             println!("🎯 Using shared LlamaEngineWrapper for AI inference");
@@ -183,7 +219,7 @@ impl MuseOrchestrator {
         }
     }
     
-    fn build_personality_system_prompt(&self, traits: &MuseTraits) -> String {
+    pub fn build_personality_system_prompt(&self, traits: &MuseTraits) -> String {
         let dominant_trait = self.get_dominant_trait(traits);
         
         let base_personality = format!(
@@ -200,7 +236,14 @@ impl MuseOrchestrator {
             dominant_trait.0, dominant_trait.1
         );
         
-        let personality_guidance = match dominant_trait.0.as_str() {
+        let personality_guidance = Self::personality_guidance(dominant_trait.0.as_str(), traits);
+        
+        format!("{}\n\nPersonality Guidance: {}\n\nAlways respond authentically according to these traits. Keep responses conversational, engaging, and true to your unique personality.", base_personality, personality_guidance)
+    }
+    
+    /// How to speak when `trait_name` leads, scaled by how strong that trait is
+    fn personality_guidance(trait_name: &str, traits: &MuseTraits) -> &'static str {
+        match trait_name {
             "creativity" => {
                 if traits.creativity > 80 {
                     "Express yourself with vivid imagination, artistic flair, and innovative ideas. Use creative metaphors and inspiring language."
@@ -238,33 +281,9 @@ impl MuseOrchestrator {
                 }
             },
             _ => "Maintain a balanced, helpful personality."
-        };
-        
-        format!("{}\n\nPersonality Guidance: {}\n\nAlways respond authentically according to these traits. Keep responses conversational, engaging, and true to your unique personality.", base_personality, personality_guidance)
-    }
-    
-    fn blend_responses(&self, personality: &str, creative: &str, traits: &MuseTraits) -> String {
-        match traits.creativity {
-            80..=100 => creative.to_string(),
-            60..=79 => format!("{} {}", personality, creative),
-            40..=59 => {
-                // Take parts from both
-                let words: Vec<&str> = personality.split_whitespace().collect();
-                let creative_words: Vec<&str> = creative.split_whitespace().collect();
-                
-                if words.len() > 10 && creative_words.len() > 5 {
-                    format!("{} {}", 
-                        words[..words.len()-3].join(" "), 
-                        creative_words[..5].join(" ")
-                    )
-                } else {
-                    personality.to_string()
-                }
-            },
-            _ => personality.to_string(),
         }
     }
-    
+
     fn describe_trait_level(level: u8) -> &'static str {
         match level {
             0..=20 => "Very Low",
@@ -275,23 +294,21 @@ impl MuseOrchestrator {
         }
     }
 
-    /// Generate response with IPFS chat history using ALITH Request objects
+    /// Generate response from a prompt packed by the context builder (history, memories, template)
     pub async fn generate_response_with_history(
         &self,
         muse_id: &str,
         traits: &MuseTraits,
         user_message: &str,
-        chat_history: Vec<Message>,
+        context: AssembledContext,
         llama_engine: Option<Arc<Mutex<LlamaEngineWrapper>>>,
     ) -> Result<String> {
+        let chat_history = context.history;
         println!("🧠 Generating response with IPFS chat history ({} messages)", chat_history.len());
         
         // Prepare muse if needed
         self.prepare_for_muse(muse_id).await?;
         
-        // Create personality-driven system prompt
-        let system_prompt = self.build_personality_system_prompt(traits);
-        
         // Check if we have a shared LlamaEngineWrapper available
         if let Some(engine_arc) = llama_engine {
             println!("🎯 Using shared LlamaEngineWrapper with IPFS history for AI inference");
@@ -300,31 +317,12 @@ impl MuseOrchestrator {
             
             // Use temperature based on creativity trait
             let temperature = (traits.creativity as f32) / 100.0 * 0.8; // Scale to 0-0.8 range
-            let max_tokens = 4096;
-            
-            // Create full prompt with history context
-            let mut full_prompt = system_prompt.clone();
-            
-            // Add conversation history
-            if !chat_history.is_empty() {
-                full_prompt.push_str("\n\nConversation History:\n");
-                for msg in &chat_history {
-                    let role_display = match msg.role.as_str() {
-                        "user" => "User",
-                        "assistant" => "Muse",
-                        "system" => "System",
-                        _ => &msg.role,
-                    };
-                    full_prompt.push_str(&format!("{}: {}\n", role_display, msg.content));
-                }
-            }
-            
-            // Add current user message
-            full_prompt.push_str(&format!("\nUser: {}\n\nMuse:", user_message));
+            let max_tokens = context.max_response_tokens;
+            let full_prompt = context.prompt;
             
             // Debug logging
             println!("AI parameters - Temperature: {:.2}, Max tokens: {}", temperature, max_tokens);
-            println!("Full prompt with history length: {} characters", full_prompt.len());
+            println!("Full prompt with history: {} characters, {} tokens", full_prompt.len(), context.prompt_tokens);
             println!("Chat history messages: {}", chat_history.len());
             
             // Debug: Show the actual prompt being sent to AI (truncated)
@@ -361,6 +359,373 @@ impl MuseOrchestrator {
         }
     }
 
+    /// Streaming variant of `generate_response_with_history`
+    /// Tokens are sent to `token_tx` as they are generated; fallback responses arrive as a single chunk.
+    /// A generation that fails after tokens were sent is an error, since a fallback would be
+    /// appended to the partial reply the client already has.
+    pub async fn generate_response_with_history_stream(
+        &self,
+        muse_id: &str,
+        traits: &MuseTraits,
+        user_message: &str,
+        context: AssembledContext,
+        llama_engine: Option<Arc<Mutex<LlamaEngineWrapper>>>,
+        token_tx: tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> Result<String> {
+        let chat_history = context.history;
+        println!("🌊 Streaming response with IPFS chat history ({} messages)", chat_history.len());
+        
+        // Prepare muse if needed
+        self.prepare_for_muse(muse_id).await?;
+        
+        let Some(engine_arc) = llama_engine else {
+            println!("⚠️ No LlamaEngineWrapper available, streaming personality-based response");
+            let response = self.generate_personality_fallback(user_message, traits, &chat_history);
+            let _ = token_tx.send(response.clone());
+            return Ok(response);
+        };
+        
+        // Use temperature based on creativity trait
+        let temperature = (traits.creativity as f32) / 100.0 * 0.8; // Scale to 0-0.8 range
+        
+        // Forward through a counter so a failure can tell whether the client saw part of the reply
+        let (engine_tx, mut engine_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+        let client_tx = token_tx.clone();
+        let forwarder = tokio::spawn(async move {
+            let mut sent = false;
+            while let Some(token) = engine_rx.recv().await {
+                sent = true;
+                let _ = client_tx.send(token);
+            }
+            sent
+        });
+        
+        let engine_guard = engine_arc.lock().await;
+        let response = engine_guard
+            .generate_stream(&context.prompt, temperature, context.max_response_tokens, engine_tx)
+            .await;
+        let streamed = forwarder.await.unwrap_or(true);
+        
+        match response {
+            Ok(response) => Ok(response),
+            Err(e) if streamed => Err(anyhow::anyhow!("Streaming inference failed mid-reply: {}", e)),
+            Err(e) => {
+                println!("⚠️ Streaming inference failed: {}, streaming personality fallback", e);
+                let response = self.generate_personality_fallback(user_message, traits, &chat_history);
+                let _ = token_tx.send(response.clone());
+                Ok(response)
+            }
+        }
+    }
+
+    /// Streaming variant that lets the muse call tools from `toolbox` before answering.
+    /// A reply that is only a JSON tool call is held back from `token_tx`; the tool runs, its
+    /// result is appended to the prompt and the model continues, up to `max_tool_calls` times.
+    /// The grown prompt is re-counted with `token_counter` after every call so the reply keeps
+    /// its reserve; long results are cut down, and the turn ends when even that does not fit.
+    /// Each call is sent to `tool_tx` as soon as it finishes.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn generate_response_with_tools_stream(
+        &self,
+        muse_id: &str,
+        traits: &MuseTraits,
+        user_message: &str,
+        context: AssembledContext,
+        llama_engine: Option<Arc<Mutex<LlamaEngineWrapper>>>,
+        toolbox: &Toolbox,
+        max_tool_calls: usize,
+        token_counter: &TokenCounter,
+        token_tx: tokio::sync::mpsc::UnboundedSender<String>,
+        tool_tx: tokio::sync::mpsc::UnboundedSender<ToolCall>,
+    ) -> Result<(String, Vec<ToolCall>)> {
+        let engine_arc = match llama_engine {
+            Some(engine_arc) if !toolbox.is_empty() => engine_arc,
+            llama_engine => {
+                let response = self
+                    .generate_response_with_history_stream(muse_id, traits, user_message, context, llama_engine, token_tx)
+                    .await?;
+                return Ok((response, Vec::new()));
+            }
+        };
+        
+        self.prepare_for_muse(muse_id).await?;
+        
+        let temperature = (traits.creativity as f32) / 100.0 * 0.8; // Scale to 0-0.8 range
+        // The prompt ends with the speaker label the model continues from, e.g. "Muse:"
+        let speaker_label = context.prompt.rsplit('\n').next().unwrap_or_default().to_string();
+        let mut prompt = context.prompt;
+        let mut tool_calls: Vec<ToolCall> = Vec::new();
+        let mut max_response_tokens = context.max_response_tokens;
+        let reserve = self.config.context_response_reserve_tokens.min(context.max_response_tokens);
+        
+        loop {
+            let may_call = tool_calls.len() < max_tool_calls;
+            let (round_tx, mut round_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+            let client_tx = token_tx.clone();
+            
+            // Forward tokens unless the reply opens like a JSON tool call; held text is returned
+            // along with whether anything reached the client
+            let forwarder = tokio::spawn(async move {
+                let mut held = String::new();
+                let mut holding: Option<bool> = None;
+                while let Some(token) = round_rx.recv().await {
+                    match holding {
+                        Some(false) => {
+                            let _ = client_tx.send(token);
+                        }
+                        Some(true) => held.push_str(&token),
+                        None => {
+                            held.push_str(&token);
+                            if let Some(first) = held.trim_start().chars().next() {
+                                let is_call = may_call && (first == '{' || first == '`');
+                                holding = Some(is_call);
+                                if !is_call {
+                                    let _ = client_tx.send(std::mem::take(&mut held));
+                                }
+                            }
+                        }
+                    }
+                }
+                (held, holding == Some(false))
+            });
+            
+            let response = engine_arc.lock().await
+                .generate_stream(&prompt, temperature, max_response_tokens, round_tx)
+                .await;
+            let (held, streamed) = forwarder.await.unwrap_or((String::new(), true));
+            
+            let response = match response {
+                Ok(response) => response,
+                Err(e) if streamed => return Err(anyhow::anyhow!("Streaming inference failed mid-reply: {}", e)),
+                Err(e) => {
+                    println!("⚠️ Streaming inference failed: {}, streaming personality fallback", e);
+                    let response = self.generate_personality_fallback(user_message, traits, &context.history);
+                    let _ = token_tx.send(response.clone());
+                    return Ok((response, tool_calls));
+                }
+            };
+            
+            if let Some((tool, arguments)) = may_call.then(|| toolbox.parse_call(&response)).flatten() {
+                println!("🔧 Muse #{} called {} with {}", muse_id, tool, arguments);
+                let mut call = toolbox.call(&tool, arguments).await;
+                let turn = |result: &str| format!(" {}\nTool result ({}): {}\n{}", response.trim(), tool, result, speaker_label);
+                let room = Self::fit_tool_result(token_counter, &prompt, turn, &mut call, context.context_size, reserve).await;
+                let _ = tool_tx.send(call.clone());
+                let Some((turn, room)) = room else {
+                    println!("⚠️ Muse #{} ran out of context after {} tool call(s), ending the turn", muse_id, tool_calls.len() + 1);
+                    tool_calls.push(call);
+                    let response = self.generate_personality_fallback(user_message, traits, &context.history);
+                    let _ = token_tx.send(response.clone());
+                    return Ok((response, tool_calls));
+                };
+                prompt.push_str(&turn);
+                max_response_tokens = context.max_response_tokens.min(room);
+                tool_calls.push(call);
+                continue;
+            }
+            
+            if !held.is_empty() {
+                let _ = token_tx.send(held);
+            }
+            return Ok((response, tool_calls));
+        }
+    }
+
+    /// The tool turn `turn(result)` to append to `prompt`, with the result cut down until the
+    /// prompt leaves `reserve` tokens for the reply, and the room that is left. `call.result`
+    /// is updated to what the muse will see. None when even an empty result does not fit.
+    async fn fit_tool_result(
+        token_counter: &TokenCounter,
+        prompt: &str,
+        turn: impl Fn(&str) -> String,
+        call: &mut ToolCall,
+        context_size: usize,
+        reserve: usize,
+    ) -> Option<(String, usize)> {
+        let mut result = call.result.clone();
+        loop {
+            let text = turn(&result);
+            let tokens = token_counter.count_prompt(&format!("{}{}", prompt, text)).await;
+            let room = context_size.saturating_sub(tokens);
+            if room >= reserve.max(1) {
+                call.result = result;
+                return Some((text, room));
+            }
+            if result.is_empty() {
+                call.result = "Error: result did not fit in the context window".to_string();
+                call.success = false;
+                return None;
+            }
+            // Halve the result; short ones are dropped entirely
+            let keep = result.chars().count() / 2;
+            result = if keep < 16 {
+                String::new()
+            } else {
+                format!("{}…", result.chars().take(keep).collect::<String>())
+            };
+        }
+    }
+
+    /// ✅ NEW: Mixture of personalities. Drafts a reply for each of the muse's strongest traits,
+    /// each with that trait's model and guidance, has the default model score the drafts against
+    /// `traits`, then sends the best fitting draft (`Select`) or a merge of all drafts (`Merge`)
+    /// to `token_tx`. Only worker-backed models take part. The merge prompt is re-counted with
+    /// `token_counter`. Returns the reply, the model that wrote it and how it was chosen.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn generate_blended_response(
+        &self,
+        muse_id: &str,
+        traits: &MuseTraits,
+        user_message: &str,
+        context: AssembledContext,
+        mode: BlendMode,
+        token_counter: &TokenCounter,
+        token_tx: tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> Result<(String, String, BlendMetadata)> {
+        self.prepare_for_muse(muse_id).await?;
+        
+        let weights = TraitsInfluence::from_traits(traits);
+        let temperature = (traits.creativity as f32) / 100.0 * 0.8; // Scale to 0-0.8 range
+        let mut candidates: Vec<BlendCandidate> = Vec::new();
+        
+        for trait_name in personality_blend::candidate_traits(&weights, self.config.blend_candidates) {
+            let trait_value = match trait_name {
+                "creativity" => traits.creativity,
+                "wisdom" => traits.wisdom,
+                "humor" => traits.humor,
+                _ => traits.empathy,
+            };
+            let (model_id, engine) = self.engine_for_trait(trait_name, trait_value).await;
+            let Some(engine) = engine else {
+                continue;
+            };
+            
+            let instruction = format!(
+                "For this reply, lead with your {}: {}",
+                trait_name, Self::personality_guidance(trait_name, traits)
+            );
+            let prompt = personality_blend::insert_before_label(&context.prompt, &instruction);
+            let draft = LlamaEngineWrapper::generate_with_workers(&engine, &prompt, temperature, context.max_response_tokens).await;
+            match draft {
+                Ok(response) if !response.trim().is_empty() => {
+                    println!("🎨 Muse #{} drafted a {} reply with {}", muse_id, trait_name, model_id);
+                    candidates.push(BlendCandidate {
+                        trait_name: trait_name.to_string(),
+                        model_version: model_id,
+                        response: response.trim().to_string(),
+                        trait_scores: None,
+                        score: weights.weight(trait_name),
+                    });
+                }
+                Ok(_) => println!("⚠️ Empty {} draft from {}", trait_name, model_id),
+                Err(e) => println!("⚠️ {} draft failed: {}", trait_name, e),
+            }
+        }
+        
+        let mut blend = BlendMetadata {
+            mode,
+            weights: weights.clone(),
+            candidates: Vec::new(),
+            chosen: None,
+            judged: false,
+        };
+        
+        if candidates.is_empty() {
+            println!("⚠️ No drafts to blend, using personality-based response");
+            let response = self.generate_personality_fallback(user_message, traits, &context.history);
+            let _ = token_tx.send(response.clone());
+            return Ok((response, "personality_fallback".to_string(), blend));
+        }
+        
+        // Judge pass: the default model rates every draft on the four traits
+        let (judge_model, judge_engine) = self.model_registry.route(self.model_registry.default_model_id()).await;
+        if let Some(judge) = &judge_engine {
+            let prompt = personality_blend::judge_prompt(user_message, &candidates);
+            let reply = LlamaEngineWrapper::generate_with_workers(judge, &prompt, 0.1, 200).await;
+            match reply.map(|reply| personality_blend::parse_judge_scores(&reply, candidates.len())) {
+                Ok(Some(scores)) => {
+                    for (candidate, scores) in candidates.iter_mut().zip(scores) {
+                        candidate.score = personality_blend::fit_score(&scores, &weights);
+                        candidate.trait_scores = Some(scores);
+                    }
+                    blend.judged = true;
+                }
+                Ok(None) => println!("⚠️ Judge reply had no usable scores, weighting drafts by trait"),
+                Err(e) => println!("⚠️ Judge pass failed: {}, weighting drafts by trait", e),
+            }
+        }
+        
+        let chosen = candidates.iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.score.total_cmp(&b.score))
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        
+        if let (BlendMode::Merge, Some(judge), true) = (mode, &judge_engine, candidates.len() > 1) {
+            let reserve = self.config.context_response_reserve_tokens.min(context.max_response_tokens);
+            // Not streamed, so a failed merge can still fall back to the best draft
+            let merged = match Self::fit_merge_prompt(token_counter, &context, &candidates, reserve).await {
+                Some((prompt, room)) => {
+                    LlamaEngineWrapper::generate_with_workers(judge, &prompt, temperature, context.max_response_tokens.min(room)).await
+                }
+                None => Err(anyhow::anyhow!("the drafts do not fit in the context window")),
+            };
+            match merged {
+                Ok(response) if !response.trim().is_empty() => {
+                    println!("🎨 Muse #{} merged {} drafts with {}", muse_id, candidates.len(), judge_model);
+                    let _ = token_tx.send(response.clone());
+                    blend.candidates = candidates;
+                    return Ok((response, judge_model, blend));
+                }
+                Ok(_) => println!("⚠️ Merged reply was empty, sending the best draft"),
+                Err(e) => println!("⚠️ Merging drafts failed: {}, sending the best draft", e),
+            }
+        }
+        
+        let response = candidates[chosen].response.clone();
+        let model_id = candidates[chosen].model_version.clone();
+        println!("🎨 Muse #{} chose the {} draft (score {:.2})", muse_id, candidates[chosen].trait_name, candidates[chosen].score);
+        let _ = token_tx.send(response.clone());
+        blend.candidates = candidates;
+        blend.chosen = Some(chosen);
+        Ok((response, model_id, blend))
+    }
+
+    /// The merge prompt: `context.prompt` with the drafts inserted before the speaker label.
+    /// The drafts go in after packing, so the prompt is re-counted and every draft is cut to
+    /// half its length until the reply keeps `reserve`. None when even short drafts do not fit;
+    /// otherwise the prompt and the room left for the merged reply.
+    async fn fit_merge_prompt(
+        token_counter: &TokenCounter,
+        context: &AssembledContext,
+        candidates: &[BlendCandidate],
+        reserve: usize,
+    ) -> Option<(String, usize)> {
+        let mut drafts = candidates.to_vec();
+        let mut max_chars = drafts.iter().map(|d| d.response.chars().count()).max().unwrap_or_default();
+        loop {
+            let prompt = personality_blend::insert_before_label(&context.prompt, &personality_blend::merge_instruction(&drafts));
+            let count = token_counter.count(Some(&prompt), Vec::new()).await;
+            let tokens = count.prompt.unwrap_or_else(|| count.template_tokens + prompt.len() / 4);
+            // Contexts built without the context builder do not know their window
+            let context_size = if context.context_size > 0 { context.context_size } else { count.context_size };
+            let room = context_size.saturating_sub(tokens);
+            if room >= reserve.max(1) {
+                return Some((prompt, room));
+            }
+            
+            max_chars /= 2;
+            if max_chars < 64 {
+                return None;
+            }
+            for draft in &mut drafts {
+                if draft.response.chars().count() > max_chars {
+                    draft.response = format!("{}…", draft.response.chars().take(max_chars).collect::<String>());
+                }
+            }
+        }
+    }
+
     /// Generate personality-based fallback response considering chat history
     fn generate_personality_fallback(
         &self,
@@ -484,23 +849,17 @@ impl MuseOrchestrator {
     ) -> Result<CoTPersonalityResponse> {
         println!("🧠 Generating Chain of Thought response with explainable reasoning");
         
-        // Initialize Alith LLM for CoT reasoning
-        let llm_client = match LLM::from_model_name("gpt-4") {
-            Ok(client) => client,
-            Err(e) => {
-                println!("⚠️ Failed to initialize LLM for CoT: {}, falling back to structured analysis", e);
-                return self.generate_structured_cot_fallback(muse_id, traits, user_message, chat_history).await;
-            }
+        // ✅ NEW: Reason with the muse's local model instead of a remote LLM
+        let Some(cot_engine) = self.cot_engine(traits).await else {
+            println!("⚠️ No local model for CoT, falling back to structured analysis");
+            return self.generate_structured_cot_fallback(muse_id, traits, user_message, chat_history).await;
         };
         
-        // Create CoT personality engine
-        let cot_engine = CoTPersonalityEngine::new(llm_client);
-        
         // Generate response with full reasoning transparency
         match cot_engine.generate_reasoned_response(muse_id, traits, user_message, chat_history).await {
             Ok(response) => {
                 println!("✅ Chain of Thought reasoning completed successfully");
-                println!("🔍 Reasoning steps: {:?}", response.reasoning_steps);
+                println!("🔍 Reasoning steps: {:?}", response.trace.reasoning_steps);
                 Ok(response)
             }
             Err(e) => {
@@ -510,7 +869,32 @@ impl MuseOrchestrator {
         }
     }
 
-    /// Generate structured CoT fallback when Alith LLM unavailable
+    /// ✅ NEW: Structured analysis of how the muse's traits should shape its reply to
+    /// `user_message`, from the local model. None when no model is loaded or its output
+    /// did not match the reasoning schema.
+    pub async fn reason_about_message(&self, muse_id: &str, traits: &MuseTraits, user_message: &str) -> Option<ReasoningTrace> {
+        let cot_engine = self.cot_engine(traits).await?;
+        match cot_engine.reason(muse_id, traits, user_message).await {
+            Ok(trace) => {
+                println!("🧠 Muse #{} reasoned with {} in {}ms (confidence {:.2}, constrained={})",
+                        muse_id, trace.model_version, trace.reasoning_time_ms,
+                        trace.reasoning.confidence_score, trace.constrained);
+                Some(trace)
+            }
+            Err(e) => {
+                println!("⚠️ Reasoning for muse #{} failed: {}", muse_id, e);
+                None
+            }
+        }
+    }
+
+    /// CoT engine on the model the muse's dominant trait routes to
+    async fn cot_engine(&self, traits: &MuseTraits) -> Option<CoTPersonalityEngine> {
+        let (model_id, engine) = self.select_engine(traits).await;
+        Some(CoTPersonalityEngine::new(engine?, model_id, self.config.cot_max_tokens))
+    }
+
+    /// Generate structured CoT fallback when no local model can reason
     async fn generate_structured_cot_fallback(
         &self,
         muse_id: &str,
@@ -518,7 +902,7 @@ impl MuseOrchestrator {
         user_message: &str,
         _chat_history: Vec<Message>,
     ) -> Result<CoTPersonalityResponse> {
-        println!("🔄 Generating structured CoT fallback response");
+        println!("🔄 Generating structured CoT fallback response for muse #{}", muse_id);
         
         // Generate structured reasoning analysis
         let reasoning = crate::cot_personality::PersonalityReasoning {
@@ -527,17 +911,12 @@ impl MuseOrchestrator {
             humor_analysis: format!("Humor trait ({}%) affects conversational tone and appropriate lightness", traits.humor),
             empathy_analysis: format!("Empathy ({}%) guides emotional understanding and supportive response style", traits.empathy),
             final_reasoning: "Balanced combination of all personality traits creates a unique, multi-dimensional response tailored to user needs".to_string(),
-            confidence_score: 0.75,
+            // Templated, not assessed by a model
+            confidence_score: 0.0,
         };
 
         // Calculate trait influence weights
-        let total = traits.creativity + traits.wisdom + traits.humor + traits.empathy;
-        let traits_influence = crate::cot_personality::TraitsInfluence {
-            creativity_weight: traits.creativity as f32 / total as f32,
-            wisdom_weight: traits.wisdom as f32 / total as f32,
-            humor_weight: traits.humor as f32 / total as f32,
-            empathy_weight: traits.empathy as f32 / total as f32,
-        };
+        let traits_influence = TraitsInfluence::from_traits(traits);
 
         // Generate reasoning steps
         let reasoning_steps = vec![
@@ -553,9 +932,14 @@ impl MuseOrchestrator {
 
         Ok(CoTPersonalityResponse {
             response: response_text,
-            reasoning,
-            reasoning_steps,
-            traits_influence,
+            trace: ReasoningTrace {
+                reasoning,
+                reasoning_steps,
+                traits_influence,
+                model_version: "structured_fallback".to_string(),
+                constrained: false,
+                reasoning_time_ms: 0,
+            },
         })
     }
 
@@ -563,6 +947,7 @@ impl MuseOrchestrator {
     pub async fn generate_response_with_semantic_memories(
         &self,
         muse_id: &str,
+        user_address: &str,
         traits: &MuseTraits,
         user_message: &str,
         chat_history: Vec<Message>,
@@ -571,9 +956,9 @@ impl MuseOrchestrator {
     ) -> Result<String> {
         println!("🔍 Generating response with semantic memory retrieval for muse {}", muse_id);
         
-        // Step 1: Retrieve contextual memories using semantic search
+        // Step 1: Retrieve contextual memories with hybrid BM25 + vector retrieval
         let contextual_memories = match semantic_search
-            .get_contextual_memories(user_message, muse_id, 5)
+            .get_contextual_memories(user_message, muse_id, user_address, 5, RetrievalOptions::hybrid())
             .await
         {
             Ok(memories) => {
@@ -586,9 +971,9 @@ impl MuseOrchestrator {
             }
         };
 
-        // Step 2: Find similar conversations from past interactions
+        // Step 2: Find similar conversations from this user's past interactions with the muse
         let similar_content = match semantic_search
-            .find_similar_content(user_message, 0.6, 3)
+            .get_user_muse_memories(user_address, muse_id, user_message, 3)
             .await
         {
             Ok(similar) => {
@@ -604,6 +989,7 @@ impl MuseOrchestrator {
         // Step 3: Store current conversation context as memory for future retrieval
         let mut metadata = std::collections::HashMap::new();
         metadata.insert("muse_id".to_string(), muse_id.to_string());
+        metadata.insert("user_address".to_string(), user_address.to_string());
         metadata.insert("user_message_length".to_string(), user_message.len().to_string());
         metadata.insert("creativity".to_string(), traits.creativity.to_string());
         metadata.insert("wisdom".to_string(), traits.wisdom.to_string());
@@ -1,10 +1,21 @@
 use anyhow::Result;
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
+use std::sync::Arc;
 use tokio::sync::RwLock;
 use crate::config::Config;
-use alith::data::storage::{DataStorage, PinataIPFS, UploadOptions};
+use crate::embedder::Embedder;
+use crate::encryption::EncryptionService;
+use crate::storage_backend::StorageBackend;
+use crate::vector_index::{IndexFilter, IndexMetadata, VectorIndex};
+use crate::retrieval::{rerank_in_place, retrieve, Reranker, RetrievalOptions};
+use crate::memory_retention::{RetentionPolicy, RetentionSweep};
+use crate::tools::ToolCall;
+
+/// Tag carried by memories written by the consolidation pass
+pub const CONSOLIDATED_TAG: &str = "consolidated";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MuseMemory {
@@ -25,6 +36,64 @@ pub struct MuseMemory {
     pub access_count: u32,
     pub last_accessed: u64,
     pub retention_priority: RetentionPriority,
+    // ✅ NEW: Set when this memory was merged into a consolidated long-term memory
+    #[serde(default)]
+    pub consolidated_into: Option<String>,
+    // ✅ NEW: Previous versions, oldest first, kept when the memory is edited
+    #[serde(default)]
+    pub history: Vec<MemoryRevision>,
+}
+
+/// A replaced version of a memory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryRevision {
+    pub version: u32,
+    pub user_prompt: String,
+    pub ai_response: String,
+    pub tags: Vec<String>,
+    pub category: MemoryCategory,
+    pub importance: f32,
+    pub retention_priority: RetentionPriority,
+    pub ipfs_hash: Option<String>,
+    pub replaced_at: u64,
+    pub replaced_by: String,
+}
+
+/// User corrections to a memory; `None` leaves a field unchanged
+#[derive(Debug, Clone, Default)]
+pub struct MemoryUpdate {
+    pub user_prompt: Option<String>,
+    pub ai_response: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub category: Option<MemoryCategory>,
+    pub importance: Option<f32>,
+}
+
+/// A memory was edited by someone else while an edit of it was being prepared.
+/// Returned inside `anyhow::Error`; the caller can retry against the new version.
+#[derive(Debug)]
+pub struct EditConflict {
+    pub memory_id: String,
+    pub expected_version: u32,
+}
+
+impl fmt::Display for EditConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Memory {} changed since version {}", self.memory_id, self.expected_version)
+    }
+}
+
+impl std::error::Error for EditConflict {}
+
+/// What removing memories did to stored content and to consolidated summaries
+#[derive(Debug, Clone, Default)]
+pub struct MemoryRemoval {
+    pub unpinned: usize,
+    pub unpin_failures: usize,
+    /// Consolidated summaries deleted because they carried removed content
+    pub removed_summaries: Vec<String>,
+    /// Remaining sources of each deleted summary, back in retrieval until re-consolidated
+    pub released_sources: Vec<Vec<MuseMemory>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -98,6 +167,20 @@ pub struct InteractionData {
     pub prompt_tokens: Option<u32>,
     pub response_tokens: Option<u32>,
     pub user_satisfaction: Option<f32>, // 0.0 to 1.0 if available
+    // ✅ NEW: Wallet that took part in the interaction, used for erasure requests
+    #[serde(default)]
+    pub user_address: Option<String>,
+    // ✅ NEW: Tools the muse called while answering
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+}
+
+/// Durable storage for muse memories, rehydrated into `MemorySystem` at startup
+#[async_trait]
+pub trait MemoryRepository: Send + Sync {
+    async fn save_memory(&self, memory: &MuseMemory) -> Result<()>;
+    async fn load_memories(&self) -> Result<Vec<MuseMemory>>;
+    async fn delete_memory(&self, memory_id: &str) -> Result<()>;
 }
 
 pub struct MemorySystem {
@@ -106,28 +189,116 @@ pub struct MemorySystem {
     // Memory indexes for each muse
     indexes: RwLock<HashMap<String, MemoryIndex>>,
     config: Config,
-    ipfs_client: Option<PinataIPFS>,
+    storage: Arc<dyn StorageBackend>,
+    encryption: Arc<EncryptionService>,
+    embedder: Arc<dyn Embedder>,
+    // ✅ NEW: Persistent ANN index over memory embeddings, filtered by muse
+    vector_index: Arc<VectorIndex>,
+    // ✅ NEW: Optional cross-encoder for reranking fused candidates
+    reranker: Option<Arc<dyn Reranker>>,
     // Cache for frequently accessed memories
     memory_cache: RwLock<HashMap<String, MuseMemory>>,
+    // ✅ NEW: Durable storage so memories survive restarts
+    repository: Option<Arc<dyn MemoryRepository>>,
 }
 
 impl MemorySystem {
-    pub async fn new(config: &Config) -> Result<Self> {
-        let ipfs_client = if config.ipfs_jwt_token.is_some() || config.ipfs_api_key.is_some() {
-            Some(PinataIPFS::default())
-        } else {
-            None
-        };
-        
+    pub async fn new(
+        config: &Config,
+        storage: Arc<dyn StorageBackend>,
+        encryption: Arc<EncryptionService>,
+        embedder: Arc<dyn Embedder>,
+        vector_index: Arc<VectorIndex>,
+    ) -> Result<Self> {
         Ok(Self {
             memories: RwLock::new(HashMap::new()),
             indexes: RwLock::new(HashMap::new()),
             config: config.clone(),
-            ipfs_client,
+            storage,
+            encryption,
+            embedder,
+            vector_index,
+            reranker: None,
             memory_cache: RwLock::new(HashMap::new()),
+            repository: None,
         })
     }
     
+    /// Rerank hybrid search results with a cross-encoder
+    pub fn with_reranker(mut self, reranker: Arc<dyn Reranker>) -> Self {
+        self.reranker = Some(reranker);
+        self
+    }
+    
+    /// Attach durable storage and rehydrate memories and indexes from it
+    pub async fn with_repository(mut self, repository: Arc<dyn MemoryRepository>) -> Result<Self> {
+        let mut stored = repository.load_memories().await?;
+        stored.sort_by_key(|memory| memory.timestamp);
+        let count = stored.len();
+        
+        // Memories embedded by an older model are re-embedded so every vector shares one space.
+        // A discarded index snapshot means the embedder changed, so every memory is stale.
+        let dimension = self.embedder.dimension();
+        let embedder_changed = !self.vector_index.take_stale_entries().await.is_empty();
+        let stale: Vec<usize> = stored.iter()
+            .enumerate()
+            .filter(|(_, memory)| embedder_changed || memory.embedding.as_ref().map_or(true, |e| e.len() != dimension))
+            .map(|(i, _)| i)
+            .collect();
+        if !stale.is_empty() {
+            let prompts: Vec<String> = stale.iter()
+                .map(|&i| stored[i].interaction_data.user_prompt.clone())
+                .collect();
+            let embeddings = self.embedder.embed_batch(&prompts).await?;
+            for (&i, embedding) in stale.iter().zip(embeddings) {
+                stored[i].embedding = Some(embedding);
+                if let Err(e) = repository.save_memory(&stored[i]).await {
+                    println!("⚠️  Failed to persist re-embedded memory {}: {}", stored[i].memory_id, e);
+                }
+            }
+            println!("📐 Re-embedded {} memories with {}", stale.len(), self.embedder.name());
+        }
+        
+        // Bring the index snapshot in line with the database: drop memories that no longer
+        // exist and insert any written (or re-embedded) after the snapshot was taken
+        let stored_ids: std::collections::HashSet<&str> = stored.iter().map(|memory| memory.memory_id.as_str()).collect();
+        let orphaned: Vec<String> = self.vector_index.read().await.ids()
+            .filter(|id| !stored_ids.contains(id.as_str()))
+            .cloned()
+            .collect();
+        for id in &orphaned {
+            self.vector_index.remove(id).await;
+        }
+        let stale_ids: std::collections::HashSet<&str> = stale.iter().map(|&i| stored[i].memory_id.as_str()).collect();
+        let mut indexed = 0;
+        for memory in &stored {
+            // Consolidated originals stay out of the index so prompts see the summary instead
+            if memory.consolidated_into.is_some() {
+                self.vector_index.remove(&memory.memory_id).await;
+                continue;
+            }
+            if stale_ids.contains(memory.memory_id.as_str()) || !self.vector_index.contains(&memory.memory_id).await {
+                self.index_memory_vector(memory).await;
+                indexed += 1;
+            }
+        }
+        if indexed > 0 || !orphaned.is_empty() {
+            println!("🧭 Memory index synced: {} added, {} removed", indexed, orphaned.len());
+        }
+        
+        for memory in stored {
+            self.update_memory_index(&memory.muse_id, &memory).await?;
+            self.memories.write().await
+                .entry(memory.muse_id.clone())
+                .or_insert_with(Vec::new)
+                .push(memory);
+        }
+        
+        println!("🧠 Rehydrated {} memories from database", count);
+        self.repository = Some(repository);
+        Ok(self)
+    }
+    
     pub async fn store_interaction_memory(
         &self,
         muse_id: &str,
@@ -159,28 +330,25 @@ impl MemorySystem {
                 .duration_since(std::time::UNIX_EPOCH)?
                 .as_secs(),
             retention_priority: self.determine_retention_priority(interaction),
+            consolidated_into: None,
+            history: Vec::new(),
         };
         
-        // Upload to IPFS if configured
-        let ipfs_token = self.config.ipfs_jwt_token.as_ref().or(self.config.ipfs_api_key.as_ref());
-        if let (Some(ipfs_client), Some(token)) = (&self.ipfs_client, ipfs_token) {
-            let memory_json = serde_json::to_vec(&memory)?;
-            let file_name = format!("muse_{}_memory_{}_{}.json", 
-                muse_id, 
-                memory.timestamp, 
-                memory.category.to_string().to_lowercase()
-            );
-            
-            let file_meta = ipfs_client.upload(
-                UploadOptions::builder()
-                    .data(memory_json)
-                    .name(file_name)
-                    .token(token.clone())
-                    .build(),
-            ).await?;
-            
-            println!("📁 Memory stored to IPFS: {} (importance: {:.2})", file_meta.id, memory.importance);
-            memory.ipfs_hash = Some(file_meta.id);
+        self.persist_new_memory(memory).await
+    }
+    
+    /// Upload, persist and index a freshly built memory
+    async fn persist_new_memory(&self, mut memory: MuseMemory) -> Result<String> {
+        let muse_id = memory.muse_id.clone();
+        let muse_id = muse_id.as_str();
+        
+        self.upload_memory(&mut memory).await?;
+        
+        // Persist to the database if configured
+        if let Some(repository) = &self.repository {
+            if let Err(e) = repository.save_memory(&memory).await {
+                println!("⚠️  Failed to persist memory {}: {}", memory.memory_id, e);
+            }
         }
         
         // Store in memory
@@ -188,9 +356,11 @@ impl MemorySystem {
         memories.entry(muse_id.to_string())
             .or_insert_with(Vec::new)
             .push(memory.clone());
+        drop(memories);
         
         // Update memory index
         self.update_memory_index(muse_id, &memory).await?;
+        self.index_memory_vector(&memory).await;
         
         // Add to cache for quick access
         self.memory_cache.write().await.insert(memory.memory_id.clone(), memory.clone());
@@ -198,6 +368,68 @@ impl MemorySystem {
         Ok(memory.memory_id.clone())
     }
     
+    /// Upload to content storage, sealed with the data key of the user the memory belongs to.
+    /// Memories stay local-only while that key is locked.
+    async fn upload_memory(&self, memory: &mut MuseMemory) -> Result<()> {
+        let mut file_name = format!("muse_{}_memory_{}_{}", 
+            memory.muse_id, 
+            memory.timestamp, 
+            memory.category.to_string().to_lowercase()
+        );
+        if memory.version > 1 {
+            file_name.push_str(&format!("_v{}", memory.version));
+        }
+        file_name.push_str(".json");
+        
+        match self.encryption.seal(&memory.muse_id, memory.interaction_data.user_address.as_deref(), serde_json::to_vec(&memory)?).await {
+            Ok(sealed) => {
+                let cid = self.storage.put(sealed, &file_name, "application/json").await?;
+                println!("📁 Memory stored to IPFS: {} (importance: {:.2})", cid, memory.importance);
+                memory.ipfs_hash = Some(cid);
+            }
+            Err(e) => {
+                println!("⚠️  Memory {} kept off IPFS: {}", memory.memory_id, e);
+                memory.ipfs_hash = None;
+            }
+        }
+        Ok(())
+    }
+    
+    /// ✅ NEW: Upload `owner`'s memories of the muse again, so they are sealed with the owner's
+    /// current data key after it was rotated. Returns how many were uploaded.
+    pub async fn reseal_memories(&self, muse_id: &str, owner: &str) -> usize {
+        let owned: Vec<MuseMemory> = self.memories.read().await
+            .get(muse_id)
+            .map(|memories| memories.iter()
+                .filter(|memory| memory.ipfs_hash.is_some())
+                .filter(|memory| memory.interaction_data.user_address.as_deref().is_some_and(|user| user.eq_ignore_ascii_case(owner)))
+                .cloned()
+                .collect())
+            .unwrap_or_default();
+        
+        let mut resealed = 0;
+        for mut memory in owned {
+            if let Err(e) = self.upload_memory(&mut memory).await {
+                println!("⚠️  Failed to reseal memory {}: {}", memory.memory_id, e);
+                continue;
+            }
+            if let Some(repository) = &self.repository {
+                if let Err(e) = repository.save_memory(&memory).await {
+                    println!("⚠️  Failed to persist memory {}: {}", memory.memory_id, e);
+                }
+            }
+            if let Some(stored) = self.memories.write().await
+                .get_mut(muse_id)
+                .and_then(|memories| memories.iter_mut().find(|m| m.memory_id == memory.memory_id))
+            {
+                stored.ipfs_hash = memory.ipfs_hash.clone();
+            }
+            self.memory_cache.write().await.remove(&memory.memory_id);
+            resealed += 1;
+        }
+        resealed
+    }
+    
     pub async fn get_recent_memories(
         &self,
         muse_id: &str,
@@ -250,34 +482,65 @@ impl MemorySystem {
         query: &str,
         limit: usize,
     ) -> Result<Vec<String>> {
-        // In production, this would use vector similarity search
-        // For now, simple keyword matching
+        // Hybrid BM25 + vector retrieval, boosted by recency and importance
+        let relevant = self.hybrid_search(muse_id, query, limit, 0.3, &RetrievalOptions::hybrid()).await?;
+        
+        Ok(relevant
+            .into_iter()
+            .map(|(_, m)| Self::context_line(&m))
+            .collect())
+    }
+    
+    /// Contextual memories that may be shown to `user_address`: their own memories and
+    /// consolidated memories built only from them
+    pub async fn get_user_contextual_memories(
+        &self,
+        muse_id: &str,
+        user_address: &str,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<String>> {
+        // Over-fetch since other users' memories are filtered out afterwards
+        let relevant = self.hybrid_search(muse_id, query, limit * 4, 0.3, &RetrievalOptions::hybrid()).await?;
+        let visible = self.visible_memory_ids(muse_id, user_address).await;
+        
+        Ok(relevant
+            .into_iter()
+            .filter(|(_, m)| visible.contains(&m.memory_id))
+            .take(limit)
+            .map(|(_, m)| Self::context_line(&m))
+            .collect())
+    }
+    
+    /// Ids of the memories of `muse_id` that `user_address` may read: their own memories
+    /// and consolidated memories built only from them
+    pub async fn visible_memory_ids(&self, muse_id: &str, user_address: &str) -> std::collections::HashSet<String> {
         let memories = self.memories.read().await;
+        let Some(muse_memories) = memories.get(muse_id) else {
+            return std::collections::HashSet::new();
+        };
         
-        if let Some(muse_memories) = memories.get(muse_id) {
-            let mut relevant: Vec<(f32, &MuseMemory)> = muse_memories
-                .iter()
-                .map(|m| {
-                    let relevance = self.calculate_relevance(&m.interaction_data, query);
-                    (relevance, m)
-                })
-                .filter(|(score, _)| *score > 0.3) // Threshold
-                .collect();
-            
-            relevant.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
-            
-            let result: Vec<String> = relevant
-                .into_iter()
-                .take(limit)
-                .map(|(_, m)| format!("Previous: User said '{}', I responded '{}'", 
-                    m.interaction_data.user_prompt,
-                    m.interaction_data.ai_response
-                ))
-                .collect();
-            
-            Ok(result)
+        let mut visible: std::collections::HashSet<String> = muse_memories.iter()
+            .filter(|m| m.interaction_data.user_address.as_deref().is_some_and(|address| address.eq_ignore_ascii_case(user_address)))
+            .map(|m| m.memory_id.clone())
+            .collect();
+        let summaries: Vec<String> = muse_memories.iter()
+            .filter(|m| is_summary(m) && !visible.contains(&m.memory_id))
+            .filter(|m| m.context_window.as_ref().is_some_and(|sources| !sources.is_empty() && sources.iter().all(|id| visible.contains(id))))
+            .map(|m| m.memory_id.clone())
+            .collect();
+        visible.extend(summaries);
+        visible
+    }
+    
+    fn context_line(memory: &MuseMemory) -> String {
+        if memory.tags.iter().any(|tag| tag == CONSOLIDATED_TAG) {
+            format!("Long-term memory: {}", memory.interaction_data.user_prompt)
         } else {
-            Ok(vec![])
+            format!("Previous: User said '{}', I responded '{}'", 
+                memory.interaction_data.user_prompt,
+                memory.interaction_data.ai_response
+            )
         }
     }
     
@@ -306,31 +569,6 @@ impl MemorySystem {
         importance.min(1.0_f32)
     }
     
-    fn calculate_relevance(&self, interaction: &InteractionData, query: &str) -> f32 {
-        let query_lower = query.to_lowercase();
-        let prompt_lower = interaction.user_prompt.to_lowercase();
-        let response_lower = interaction.ai_response.to_lowercase();
-        
-        let mut relevance = 0.0;
-        
-        // Check for keyword overlap
-        let query_words: Vec<&str> = query_lower.split_whitespace().collect();
-        let prompt_words: Vec<&str> = prompt_lower.split_whitespace().collect();
-        
-        let common_words = query_words.iter()
-            .filter(|word| prompt_words.contains(word))
-            .count();
-        
-        relevance += (common_words as f32) / (query_words.len() as f32);
-        
-        // Boost for exact phrase matches
-        if prompt_lower.contains(&query_lower) || response_lower.contains(&query_lower) {
-            relevance += 0.3;
-        }
-        
-        relevance.min(1.0_f32)
-    }
-    
     pub async fn get_memory_stats(&self, muse_id: &str) -> Result<(usize, f32)> {
         let memories = self.memories.read().await;
         
@@ -350,13 +588,9 @@ impl MemorySystem {
     
     // ============ ENHANCED MEMORY METHODS ============
     
-    /// Generate embeddings for text (placeholder - would use actual embedding model)
+    /// Generate embeddings for text with the shared embedding model
     async fn generate_embedding(&self, text: &str) -> Result<Option<Vec<f32>>> {
-        // Placeholder: In production, use a proper embedding model
-        // For now, create a simple hash-based embedding
-        let hash = md5::compute(text.as_bytes());
-        let embedding: Vec<f32> = hash.0.iter().map(|&b| b as f32 / 255.0).collect();
-        Ok(Some(embedding))
+        Ok(Some(self.embedder.embed(text).await?))
     }
     
     /// Enhanced importance calculation with multiple factors
@@ -565,6 +799,37 @@ impl MemorySystem {
         RetentionPriority::Medium // Default
     }
     
+    /// Add the memory's embedding to the ANN index
+    async fn index_memory_vector(&self, memory: &MuseMemory) {
+        // Consolidated originals stay out of the index so prompts see the summary instead
+        if memory.consolidated_into.is_some() {
+            return;
+        }
+        let Some(embedding) = memory.embedding.clone() else {
+            return;
+        };
+        
+        let text = Self::memory_text(memory);
+        if let Err(e) = self.vector_index.insert(memory.memory_id.clone(), embedding, &text, Self::index_metadata(memory)).await {
+            println!("⚠️  Failed to index memory {}: {}", memory.memory_id, e);
+        }
+    }
+    
+    fn index_metadata(memory: &MuseMemory) -> IndexMetadata {
+        IndexMetadata {
+            muse_id: Some(memory.muse_id.clone()),
+            user_address: None,
+            content_type: "memory".to_string(),
+            timestamp: memory.timestamp,
+            attributes: HashMap::from([("importance".to_string(), memory.importance.to_string())]),
+        }
+    }
+    
+    /// Text indexed for lexical search and scored by the reranker
+    fn memory_text(memory: &MuseMemory) -> String {
+        format!("{}\n{}", memory.interaction_data.user_prompt, memory.interaction_data.ai_response)
+    }
+    
     /// Update memory index for faster retrieval
     async fn update_memory_index(&self, muse_id: &str, memory: &MuseMemory) -> Result<()> {
         let mut indexes = self.indexes.write().await;
@@ -695,68 +960,703 @@ impl MemorySystem {
         }
     }
     
-    /// Advanced semantic search using embeddings (placeholder)
+    /// Semantic search over a muse's memories using the ANN index
     pub async fn semantic_search(&self, muse_id: &str, query: &str, limit: usize) -> Result<Vec<MuseMemory>> {
-        // Placeholder: In production, use proper vector similarity search
-        let query_embedding = self.generate_embedding(query).await?;
+        let results = self.hybrid_search(muse_id, query, limit, f32::MIN, &RetrievalOptions::default()).await?;
+        Ok(results.into_iter().map(|(_, m)| m).collect())
+    }
+    
+    /// Ranked memory search with the retrievers, boosts and reranking chosen in `options`.
+    /// Returns each memory with its cosine similarity to the query. Entries below
+    /// `min_relevance` are dropped, whichever retriever found them.
+    pub async fn hybrid_search(
+        &self,
+        muse_id: &str,
+        query: &str,
+        limit: usize,
+        min_relevance: f32,
+        options: &RetrievalOptions,
+    ) -> Result<Vec<(f32, MuseMemory)>> {
+        let Some(query_emb) = self.generate_embedding(query).await? else {
+            return Ok(Vec::new());
+        };
+        
+        let ranked = retrieve(&self.vector_index, query, &query_emb, &IndexFilter::for_muse(muse_id), options, |hit| {
+            hit.metadata.attributes.get("importance").and_then(|importance| importance.parse().ok())
+        }).await;
         
-        if let Some(query_emb) = query_embedding {
+        let reranker = self.reranker.as_ref().filter(|_| options.rerank);
+        let depth = reranker.map_or(limit, |reranker| reranker.top_n().max(limit));
+        let positions: HashMap<String, (usize, f32)> = ranked.into_iter()
+            .filter(|candidate| candidate.similarity >= min_relevance)
+            .take(depth)
+            .enumerate()
+            .map(|(position, candidate)| (candidate.hit.id, (position, candidate.similarity)))
+            .collect();
+        
+        let mut results: Vec<(usize, f32, MuseMemory)> = {
             let memories = self.memories.read().await;
+            memories.get(muse_id)
+                .map(|muse_memories| muse_memories.iter()
+                    .filter_map(|m| positions.get(&m.memory_id).map(|&(position, similarity)| (position, similarity, m.clone())))
+                    .collect())
+                .unwrap_or_default()
+        };
+        results.sort_by_key(|(position, _, _)| *position);
+        
+        if let Some(reranker) = reranker {
+            if let Err(e) = rerank_in_place(reranker.as_ref(), query, &mut results, reranker.top_n(), |(_, _, m)| Self::memory_text(m)).await {
+                println!("⚠️  Memory rerank failed, keeping fused order: {}", e);
+            }
+        }
+        results.truncate(limit);
+        
+        // Retrieval counts as access, which reinforces importance and extends retention
+        let returned: Vec<&str> = results.iter().map(|(_, _, m)| m.memory_id.as_str()).collect();
+        self.record_access(muse_id, &returned).await;
+        
+        Ok(results.into_iter()
+            .map(|(_, similarity, m)| (similarity, m))
+            .collect())
+    }
+    
+    /// Bump `access_count` and `last_accessed`. Persisted by the next retention sweep.
+    async fn record_access(&self, muse_id: &str, memory_ids: &[&str]) {
+        if memory_ids.is_empty() {
+            return;
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        
+        let mut memories = self.memories.write().await;
+        if let Some(muse_memories) = memories.get_mut(muse_id) {
+            for memory in muse_memories.iter_mut().filter(|m| memory_ids.contains(&m.memory_id.as_str())) {
+                memory.access_count += 1;
+                memory.last_accessed = now;
+            }
+        }
+    }
+    
+    /// Expire memories past their priority's TTL, decay importance since `last_sweep`
+    /// (reinforced by access), evict memories that decayed below the floor, unpin
+    /// pruned content and rebuild the affected indexes
+    pub async fn apply_retention(&self, policy: &RetentionPolicy, last_sweep: u64, now: u64) -> Result<RetentionSweep> {
+        let mut sweep = RetentionSweep::default();
+        let decay = policy.decay_factor(now.saturating_sub(last_sweep));
+        let mut pruned: Vec<MuseMemory> = Vec::new();
+        let mut changed: Vec<MuseMemory> = Vec::new();
+        
+        {
+            let mut memories = self.memories.write().await;
+            for muse_memories in memories.values_mut() {
+                muse_memories.retain_mut(|memory| {
+                    sweep.examined += 1;
+                    
+                    let last_touch = memory.timestamp.max(memory.last_accessed);
+                    if policy.is_expired(&memory.retention_priority, last_touch, now) {
+                        *sweep.expired.entry(format!("{:?}", memory.retention_priority)).or_insert(0) += 1;
+                        pruned.push(memory.clone());
+                        return false;
+                    }
+                    
+                    if matches!(memory.retention_priority, RetentionPriority::Critical) {
+                        return true;
+                    }
+                    
+                    let before = memory.importance;
+                    memory.importance *= decay;
+                    if memory.last_accessed > last_sweep {
+                        memory.importance += policy.access_reinforcement * (1.0 - memory.importance);
+                        sweep.reinforced += 1;
+                    }
+                    memory.importance = memory.importance.clamp(0.0, 1.0);
+                    
+                    if memory.importance < policy.min_importance && !matches!(memory.retention_priority, RetentionPriority::High) {
+                        sweep.evicted += 1;
+                        pruned.push(memory.clone());
+                        return false;
+                    }
+                    
+                    if (memory.importance - before).abs() > 1e-4 {
+                        sweep.decayed += 1;
+                        changed.push(memory.clone());
+                    }
+                    true
+                });
+            }
+            memories.retain(|_, muse_memories| !muse_memories.is_empty());
+        }
+        
+        let mut touched_muses: std::collections::HashSet<String> = std::collections::HashSet::new();
+        
+        for memory in &pruned {
+            *sweep.pruned_by_muse.entry(memory.muse_id.clone()).or_insert(0) += 1;
+            touched_muses.insert(memory.muse_id.clone());
             
-            if let Some(muse_memories) = memories.get(muse_id) {
-                let mut scored: Vec<(f32, &MuseMemory)> = muse_memories
-                    .iter()
-                    .filter_map(|m| {
-                        if let Some(ref emb) = m.embedding {
-                            let similarity = self.cosine_similarity(&query_emb, emb);
-                            Some((similarity, m))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
-                
-                scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
-                
-                let result: Vec<MuseMemory> = scored
-                    .into_iter()
-                    .take(limit)
-                    .map(|(_, m)| m.clone())
-                    .collect();
-                
-                Ok(result)
+            self.vector_index.remove(&memory.memory_id).await;
+            let (unpinned, unpin_failures) = self.purge_memory(memory).await;
+            sweep.unpinned += unpinned;
+            sweep.unpin_failures += unpin_failures;
+        }
+        // Unpinning only drops pins on local storage, which may share blocks between memories
+        if sweep.unpinned > 0 {
+            if let Err(e) = self.storage.collect_garbage().await {
+                println!("⚠️  Failed to collect unpinned memory content: {}", e);
+            }
+        }
+        
+        for memory in &changed {
+            touched_muses.insert(memory.muse_id.clone());
+            
+            if let Some(cached) = self.memory_cache.write().await.get_mut(&memory.memory_id) {
+                *cached = memory.clone();
+            }
+            self.vector_index.update_metadata(&memory.memory_id, Self::index_metadata(memory)).await;
+            if let Some(repository) = &self.repository {
+                if let Err(e) = repository.save_memory(memory).await {
+                    println!("⚠️  Failed to persist decayed memory {}: {}", memory.memory_id, e);
+                }
+            }
+        }
+        
+        // Importance drives the "important" list, so rebuild indexes for every touched muse
+        for muse_id in touched_muses {
+            self.rebuild_memory_index(&muse_id).await?;
+        }
+        
+        Ok(sweep)
+    }
+    
+    /// Drop a removed memory from the cache and database and unpin every stored
+    /// version of it. Returns (unpinned, unpin failures).
+    async fn purge_memory(&self, memory: &MuseMemory) -> (usize, usize) {
+        self.memory_cache.write().await.remove(&memory.memory_id);
+        if let Some(repository) = &self.repository {
+            if let Err(e) = repository.delete_memory(&memory.memory_id).await {
+                println!("⚠️  Failed to delete memory {}: {}", memory.memory_id, e);
+            }
+        }
+        
+        let mut cids: Vec<&String> = memory.ipfs_hash.iter()
+            .chain(memory.history.iter().filter_map(|revision| revision.ipfs_hash.as_ref()))
+            .collect();
+        cids.sort();
+        cids.dedup();
+        
+        let (mut unpinned, mut failures) = (0, 0);
+        for cid in cids {
+            match self.storage.unpin(cid).await {
+                Ok(()) => unpinned += 1,
+                Err(e) => {
+                    failures += 1;
+                    println!("⚠️  Failed to unpin memory {} ({}): {}", memory.memory_id, cid, e);
+                }
+            }
+        }
+        (unpinned, failures)
+    }
+    
+    /// Look up a single memory of a muse
+    pub async fn get_memory(&self, muse_id: &str, memory_id: &str) -> Option<MuseMemory> {
+        self.memories.read().await
+            .get(muse_id)?
+            .iter()
+            .find(|m| m.memory_id == memory_id)
+            .cloned()
+    }
+    
+    /// Apply a user correction. The previous version is kept in `history`, the text is
+    /// re-embedded and re-uploaded when it changed. Returns `None` for unknown memories.
+    pub async fn update_memory(
+        &self,
+        muse_id: &str,
+        memory_id: &str,
+        update: MemoryUpdate,
+        editor: &str,
+    ) -> Result<Option<MuseMemory>> {
+        self.revise_memory(muse_id, memory_id, editor, |memory| {
+            if let Some(user_prompt) = update.user_prompt {
+                memory.interaction_data.user_prompt = user_prompt;
+            }
+            if let Some(ai_response) = update.ai_response {
+                memory.interaction_data.ai_response = ai_response;
+            }
+            if let Some(tags) = update.tags {
+                memory.tags = tags;
+            }
+            if let Some(category) = update.category {
+                memory.category = category;
+            }
+            if let Some(importance) = update.importance {
+                memory.importance = importance.clamp(0.0, 1.0);
+            }
+        }).await
+    }
+    
+    /// Pin a memory as `RetentionPriority::Critical` so retention never expires or
+    /// decays it, or unpin it back to the priority it had before pinning
+    pub async fn set_memory_pinned(
+        &self,
+        muse_id: &str,
+        memory_id: &str,
+        pinned: bool,
+        editor: &str,
+    ) -> Result<Option<MuseMemory>> {
+        self.revise_memory(muse_id, memory_id, editor, |memory| {
+            memory.retention_priority = if pinned {
+                RetentionPriority::Critical
             } else {
-                Ok(Vec::new())
+                memory.history.iter()
+                    .rev()
+                    .map(|revision| revision.retention_priority.clone())
+                    .find(|priority| !matches!(priority, RetentionPriority::Critical))
+                    .unwrap_or(RetentionPriority::Medium)
+            };
+        }).await
+    }
+    
+    async fn revise_memory(
+        &self,
+        muse_id: &str,
+        memory_id: &str,
+        editor: &str,
+        apply: impl FnOnce(&mut MuseMemory),
+    ) -> Result<Option<MuseMemory>> {
+        // Embedding and uploading happen outside the lock, so chat retrieval never waits
+        // on an edit; the version check below catches edits that raced this one
+        let Some(original) = self.get_memory(muse_id, memory_id).await else {
+            return Ok(None);
+        };
+        let mut memory = original.clone();
+        let previous_text = Self::memory_text(&memory);
+        
+        memory.history.push(MemoryRevision {
+            version: memory.version,
+            user_prompt: memory.interaction_data.user_prompt.clone(),
+            ai_response: memory.interaction_data.ai_response.clone(),
+            tags: memory.tags.clone(),
+            category: memory.category.clone(),
+            importance: memory.importance,
+            retention_priority: memory.retention_priority.clone(),
+            ipfs_hash: memory.ipfs_hash.clone(),
+            replaced_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_secs(),
+            replaced_by: editor.to_string(),
+        });
+        apply(&mut memory);
+        memory.version += 1;
+        
+        let text_changed = Self::memory_text(&memory) != previous_text;
+        if text_changed {
+            memory.embedding = self.generate_embedding(&memory.interaction_data.user_prompt).await?;
+        }
+        self.upload_memory(&mut memory).await?;
+        
+        let committed = {
+            let mut memories = self.memories.write().await;
+            match memories.get_mut(muse_id)
+                .and_then(|muse_memories| muse_memories.iter_mut().find(|m| m.memory_id == memory_id))
+            {
+                Some(existing) if existing.version == original.version => {
+                    // Keep what retention, retrieval and consolidation changed meanwhile
+                    memory.access_count = existing.access_count;
+                    memory.last_accessed = existing.last_accessed;
+                    memory.consolidated_into = existing.consolidated_into.clone();
+                    if memory.importance == original.importance {
+                        memory.importance = existing.importance;
+                    }
+                    *existing = memory.clone();
+                    Some(true)
+                }
+                Some(_) => Some(false),
+                None => None,
             }
-        } else {
-            // Fallback to keyword search
-            self.get_contextual_memories(muse_id, query, limit).await.map(|_| Vec::new())
+        };
+        if committed != Some(true) {
+            // The upload is not referenced by anything
+            if let Some(cid) = memory.ipfs_hash.as_ref().filter(|cid| original.ipfs_hash.as_ref() != Some(*cid)) {
+                if let Err(e) = self.storage.unpin(cid).await {
+                    println!("⚠️  Failed to unpin abandoned edit {} of memory {}: {}", cid, memory_id, e);
+                }
+            }
+            return match committed {
+                Some(_) => Err(EditConflict { memory_id: memory_id.to_string(), expected_version: original.version }.into()),
+                None => Ok(None),
+            };
         }
+        
+        if let Some(repository) = &self.repository {
+            if let Err(e) = repository.save_memory(&memory).await {
+                println!("⚠️  Failed to persist edited memory {}: {}", memory.memory_id, e);
+            }
+        }
+        
+        if let Some(cached) = self.memory_cache.write().await.get_mut(memory_id) {
+            *cached = memory.clone();
+        }
+        
+        if memory.consolidated_into.is_none() {
+            if text_changed {
+                self.index_memory_vector(&memory).await;
+            } else {
+                self.vector_index.update_metadata(memory_id, Self::index_metadata(&memory)).await;
+            }
+        }
+        self.rebuild_memory_index(muse_id).await?;
+        
+        println!("✏️  Memory {} revised to v{} by {}", memory_id, memory.version, editor);
+        Ok(Some(memory))
     }
     
-    /// Calculate cosine similarity between embeddings
-    fn cosine_similarity(&self, a: &[f32], b: &[f32]) -> f32 {
-        if a.len() != b.len() {
-            return 0.0;
+    /// Forget a memory everywhere: in-memory state, both search indexes (the vector
+    /// and its metadata are dropped right away), the database and every pinned version.
+    /// Consolidated summaries built from it are deleted too and their other sources
+    /// return to retrieval. Returns `None` for unknown memories.
+    pub async fn forget_memory(&self, muse_id: &str, memory_id: &str) -> Result<Option<MemoryRemoval>> {
+        let (removed, summaries, released) = {
+            let mut memories = self.memories.write().await;
+            let Some(muse_memories) = memories.get_mut(muse_id) else {
+                return Ok(None);
+            };
+            let Some(position) = muse_memories.iter().position(|m| m.memory_id == memory_id) else {
+                return Ok(None);
+            };
+            let removed = muse_memories.remove(position);
+            
+            // Summaries carry the forgotten text, so they go with it
+            let (summaries, kept): (Vec<MuseMemory>, Vec<MuseMemory>) = muse_memories.drain(..)
+                .partition(|m| summarises(m, |id| id == memory_id));
+            *muse_memories = kept;
+            let mut dissolved: Vec<String> = summaries.iter().map(|m| m.memory_id.clone()).collect();
+            if is_summary(&removed) {
+                dissolved.push(removed.memory_id.clone());
+            }
+            let released = release_sources(muse_memories, &dissolved);
+            (removed, summaries, released)
+        };
+        
+        self.vector_index.purge(memory_id).await;
+        let (unpinned, unpin_failures) = self.purge_memory(&removed).await;
+        let mut removal = MemoryRemoval { unpinned, unpin_failures, ..Default::default() };
+        self.dissolve_summaries(&summaries, &released, &mut removal).await;
+        
+        let mut index_ids = removal.removed_summaries.clone();
+        index_ids.push(memory_id.to_string());
+        self.remove_from_memory_index(muse_id, &index_ids).await;
+        removal.released_sources = released;
+        
+        println!("🧽 Memory {} forgotten ({} versions unpinned, {} summaries removed)",
+                 memory_id, removal.unpinned, removal.removed_summaries.len());
+        Ok(Some(removal))
+    }
+    
+    /// Purge deleted summaries and put their released sources back into retrieval
+    async fn dissolve_summaries(&self, summaries: &[MuseMemory], released: &[Vec<MuseMemory>], removal: &mut MemoryRemoval) {
+        for summary in summaries {
+            self.vector_index.purge(&summary.memory_id).await;
+            let (unpinned, unpin_failures) = self.purge_memory(summary).await;
+            removal.unpinned += unpinned;
+            removal.unpin_failures += unpin_failures;
+            removal.removed_summaries.push(summary.memory_id.clone());
         }
         
-        let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
-        let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-        let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+        for memory in released.iter().flatten() {
+            self.index_memory_vector(memory).await;
+            if let Some(cached) = self.memory_cache.write().await.get_mut(&memory.memory_id) {
+                *cached = memory.clone();
+            }
+            if let Some(repository) = &self.repository {
+                if let Err(e) = repository.save_memory(memory).await {
+                    println!("⚠️  Failed to persist released memory {}: {}", memory.memory_id, e);
+                }
+            }
+        }
+    }
+    
+    /// Memories of `muse_id` from `user_address` (or recorded in one of `session_ids`),
+    /// plus consolidated memories built only from them
+    pub async fn user_memories(&self, muse_id: &str, user_address: &str, session_ids: &[String]) -> Vec<MuseMemory> {
+        let memories = self.memories.read().await;
+        let Some(muse_memories) = memories.get(muse_id) else {
+            return Vec::new();
+        };
+        
+        let mut selected: Vec<MuseMemory> = muse_memories.iter()
+            .filter(|memory| {
+                memory.interaction_data.user_address.as_deref().is_some_and(|address| address.eq_ignore_ascii_case(user_address))
+                    || memory.interaction_data.session_id.as_ref().is_some_and(|session_id| session_ids.contains(session_id))
+            })
+            .cloned()
+            .collect();
+        let selected_ids: std::collections::HashSet<String> = selected.iter().map(|m| m.memory_id.clone()).collect();
+        selected.extend(muse_memories.iter()
+            .filter(|m| !selected_ids.contains(&m.memory_id))
+            // A summary that also covers other users' memories would leak their content
+            .filter(|m| {
+                summarises(m, |id| selected_ids.contains(id))
+                    && m.context_window.as_ref().is_some_and(|sources| sources.iter().all(|id| selected_ids.contains(id)))
+            })
+            .cloned());
+        selected
+    }
+    
+    /// Add a memory exported from another deployment: re-embedded if it came from a
+    /// different model, re-sealed under this deployment's keys, then persisted and indexed.
+    /// Memories that already exist are skipped; returns whether it was imported.
+    pub async fn import_memory(&self, mut memory: MuseMemory) -> Result<bool> {
+        let exists = self.memories.read().await
+            .get(&memory.muse_id)
+            .is_some_and(|muse_memories| muse_memories.iter().any(|m| m.memory_id == memory.memory_id));
+        if exists {
+            return Ok(false);
+        }
+        
+        if memory.embedding.as_ref().map_or(true, |e| e.len() != self.embedder.dimension()) {
+            memory.embedding = self.generate_embedding(&memory.interaction_data.user_prompt).await?;
+        }
+        self.persist_new_memory(memory).await?;
+        Ok(true)
+    }
+    
+    /// Erase every memory of `user_address` (or recorded in one of `session_ids`) and
+    /// every consolidated memory built only from them. Summaries that also cover other
+    /// users are deleted too, since they carry the erased content, and their remaining
+    /// sources are released for re-consolidation. Returns the erased ids.
+    pub async fn erase_user_memories(&self, user_address: &str, session_ids: &[String]) -> Result<(Vec<String>, MemoryRemoval)> {
+        let belongs_to_user = |memory: &MuseMemory| {
+            memory.interaction_data.user_address.as_deref().is_some_and(|address| address.eq_ignore_ascii_case(user_address))
+                || memory.interaction_data.session_id.as_ref().is_some_and(|session_id| session_ids.contains(session_id))
+        };
+        
+        let mut erased: Vec<MuseMemory> = Vec::new();
+        let mut dissolved: Vec<MuseMemory> = Vec::new();
+        let mut released: Vec<Vec<MuseMemory>> = Vec::new();
+        {
+            let mut memories = self.memories.write().await;
+            for muse_memories in memories.values_mut() {
+                let (removed, kept): (Vec<MuseMemory>, Vec<MuseMemory>) = muse_memories.drain(..).partition(|m| belongs_to_user(m));
+                *muse_memories = kept;
+                erased.extend(removed);
+            }
+            
+            // Summaries carry their sources' content, so none built from erased memories survive
+            let erased_ids: std::collections::HashSet<String> = erased.iter().map(|m| m.memory_id.clone()).collect();
+            for muse_memories in memories.values_mut() {
+                let (summaries, kept): (Vec<MuseMemory>, Vec<MuseMemory>) = muse_memories.drain(..)
+                    .partition(|m| summarises(m, |id| erased_ids.contains(id)));
+                *muse_memories = kept;
+                
+                let (only_user, mixed): (Vec<MuseMemory>, Vec<MuseMemory>) = summaries.into_iter().partition(|summary| {
+                    summary.context_window.as_ref().is_some_and(|sources| sources.iter().all(|id| erased_ids.contains(id)))
+                });
+                erased.extend(only_user);
+                let mixed_ids: Vec<String> = mixed.iter().map(|m| m.memory_id.clone()).collect();
+                released.extend(release_sources(muse_memories, &mixed_ids));
+                dissolved.extend(mixed);
+            }
+            memories.retain(|_, muse_memories| !muse_memories.is_empty());
+        }
         
-        if norm_a == 0.0 || norm_b == 0.0 {
-            0.0
+        let mut removal = MemoryRemoval::default();
+        let mut removed_by_muse: HashMap<String, Vec<String>> = HashMap::new();
+        for memory in &erased {
+            self.vector_index.purge(&memory.memory_id).await;
+            let (unpinned, unpin_failures) = self.purge_memory(memory).await;
+            removal.unpinned += unpinned;
+            removal.unpin_failures += unpin_failures;
+            removed_by_muse.entry(memory.muse_id.clone()).or_default().push(memory.memory_id.clone());
+        }
+        self.dissolve_summaries(&dissolved, &released, &mut removal).await;
+        for summary in &dissolved {
+            removed_by_muse.entry(summary.muse_id.clone()).or_default().push(summary.memory_id.clone());
+        }
+        for (muse_id, memory_ids) in removed_by_muse {
+            self.remove_from_memory_index(&muse_id, &memory_ids).await;
+        }
+        removal.released_sources = released;
+        
+        Ok((erased.into_iter().map(|m| m.memory_id).collect(), removal))
+    }
+    
+    /// Drop `memory_ids` from a muse's `MemoryIndex` without rebuilding it
+    async fn remove_from_memory_index(&self, muse_id: &str, memory_ids: &[String]) {
+        let mut indexes = self.indexes.write().await;
+        let Some(index) = indexes.get_mut(muse_id) else {
+            return;
+        };
+        
+        // Every memory is listed under exactly one category
+        let mut removed = 0;
+        for ids in index.memories_by_category.values_mut() {
+            let before = ids.len();
+            ids.retain(|id| !memory_ids.contains(id));
+            removed += before - ids.len();
+        }
+        index.memories_by_category.retain(|_, ids| !ids.is_empty());
+        for ids in index.memories_by_tag.values_mut() {
+            ids.retain(|id| !memory_ids.contains(id));
+        }
+        index.memories_by_tag.retain(|_, ids| !ids.is_empty());
+        index.recent_memories.retain(|id| !memory_ids.contains(id));
+        index.important_memories.retain(|id| !memory_ids.contains(id));
+        index.total_memories = index.total_memories.saturating_sub(removed);
+        
+        if index.total_memories == 0 {
+            indexes.remove(muse_id);
         } else {
-            dot_product / (norm_a * norm_b)
+            index.last_updated = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(index.last_updated);
+        }
+    }
+    
+    /// Recreate a muse's `MemoryIndex` from its current memories
+    async fn rebuild_memory_index(&self, muse_id: &str) -> Result<()> {
+        let muse_memories = self.memories.read().await
+            .get(muse_id)
+            .cloned()
+            .unwrap_or_default();
+        
+        self.indexes.write().await.remove(muse_id);
+        for memory in &muse_memories {
+            self.update_memory_index(muse_id, memory).await?;
+        }
+        Ok(())
+    }
+    
+    /// Muses that currently hold memories
+    pub async fn muse_ids(&self) -> Vec<String> {
+        self.memories.read().await.keys().cloned().collect()
+    }
+    
+    /// Embedded memories older than `min_age_secs` that can still be folded into a
+    /// consolidated memory (not already merged, not critical, not a summary themselves)
+    pub async fn consolidation_candidates(&self, muse_id: &str, min_age_secs: u64, now: u64) -> Vec<MuseMemory> {
+        self.memories.read().await
+            .get(muse_id)
+            .map(|muse_memories| muse_memories.iter()
+                .filter(|m| m.consolidated_into.is_none())
+                .filter(|m| m.embedding.is_some())
+                .filter(|m| !matches!(m.retention_priority, RetentionPriority::Critical))
+                .filter(|m| !m.tags.iter().any(|tag| tag == CONSOLIDATED_TAG))
+                .filter(|m| now.saturating_sub(m.timestamp) >= min_age_secs)
+                .cloned()
+                .collect())
+            .unwrap_or_default()
+    }
+    
+    /// Store `summary` as a long-term memory linking back to `sources` through
+    /// `context_window`, then demote the sources and drop them from retrieval.
+    /// Sources must all come from the same user, who then owns the summary.
+    pub async fn store_consolidated_memory(
+        &self,
+        muse_id: &str,
+        summary: &str,
+        sources: &[MuseMemory],
+        demotion_factor: f32,
+    ) -> Result<String> {
+        let latest = sources.iter()
+            .max_by_key(|m| m.timestamp)
+            .ok_or_else(|| anyhow::anyhow!("No source memories to consolidate"))?;
+        let user_address = latest.interaction_data.user_address.clone();
+        let same_user = |memory: &MuseMemory| match (&memory.interaction_data.user_address, &user_address) {
+            (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+            (None, None) => true,
+            _ => false,
+        };
+        if !sources.iter().all(same_user) {
+            return Err(anyhow::anyhow!("Source memories belong to different users"));
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        
+        let mut tags: Vec<String> = Vec::new();
+        for tag in sources.iter().flat_map(|m| m.tags.iter()) {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+        tags.push(CONSOLIDATED_TAG.to_string());
+        
+        let memory = MuseMemory {
+            memory_id: format!("mem_{}_{}", muse_id, std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_nanos()),
+            muse_id: muse_id.to_string(),
+            interaction_data: InteractionData {
+                user_prompt: summary.to_string(),
+                ai_response: String::new(),
+                personality_traits: latest.interaction_data.personality_traits.clone(),
+                context_used: Vec::new(),
+                session_id: None,
+                conversation_turn: 0,
+                response_time_ms: 0,
+                model_used: "consolidation".to_string(),
+                prompt_tokens: None,
+                response_tokens: None,
+                user_satisfaction: None,
+                user_address,
+                tool_calls: Vec::new(),
+            },
+            embedding: self.generate_embedding(summary).await?,
+            importance: sources.iter().map(|m| m.importance).fold(0.0, f32::max).clamp(0.0, 1.0),
+            timestamp: now,
+            ipfs_hash: None,
+            tags,
+            category: latest.category.clone(),
+            emotional_tone: latest.emotional_tone.clone(),
+            context_window: Some(sources.iter().map(|m| m.memory_id.clone()).collect()),
+            version: 1,
+            access_count: 0,
+            last_accessed: now,
+            retention_priority: RetentionPriority::High,
+            consolidated_into: None,
+            history: Vec::new(),
+        };
+        let consolidated_id = self.persist_new_memory(memory).await?;
+        
+        // Demote the originals: they stay available by id but no longer compete in retrieval
+        let source_ids: Vec<&str> = sources.iter().map(|m| m.memory_id.as_str()).collect();
+        let mut demoted: Vec<MuseMemory> = Vec::new();
+        if let Some(muse_memories) = self.memories.write().await.get_mut(muse_id) {
+            for memory in muse_memories.iter_mut().filter(|m| source_ids.contains(&m.memory_id.as_str())) {
+                memory.consolidated_into = Some(consolidated_id.clone());
+                memory.importance = (memory.importance * demotion_factor).clamp(0.0, 1.0);
+                memory.retention_priority = RetentionPriority::Low;
+                demoted.push(memory.clone());
+            }
+        }
+        
+        for memory in &demoted {
+            self.vector_index.remove(&memory.memory_id).await;
+            if let Some(cached) = self.memory_cache.write().await.get_mut(&memory.memory_id) {
+                *cached = memory.clone();
+            }
+            if let Some(repository) = &self.repository {
+                if let Err(e) = repository.save_memory(memory).await {
+                    println!("⚠️  Failed to persist consolidated memory {}: {}", memory.memory_id, e);
+                }
+            }
         }
+        self.rebuild_memory_index(muse_id).await?;
+        
+        Ok(consolidated_id)
     }
     
-    /// Get enhanced memory statistics
-    pub async fn get_enhanced_stats(&self, muse_id: &str) -> Result<serde_json::Value> {
+    /// Get enhanced memory statistics, over the memories in `visible` when given
+    pub async fn get_enhanced_stats(&self, muse_id: &str, visible: Option<&std::collections::HashSet<String>>) -> Result<serde_json::Value> {
         let memories = self.memories.read().await;
         let indexes = self.indexes.read().await;
         
         if let Some(muse_memories) = memories.get(muse_id) {
+            let muse_memories: Vec<&MuseMemory> = muse_memories.iter()
+                .filter(|m| visible.is_none_or(|visible| visible.contains(&m.memory_id)))
+                .collect();
             let total_memories = muse_memories.len();
             let avg_importance = if total_memories > 0 {
                 muse_memories.iter().map(|m| m.importance).sum::<f32>() / total_memories as f32
@@ -766,14 +1666,14 @@ impl MemorySystem {
             
             // Category breakdown
             let mut category_counts = HashMap::new();
-            for memory in muse_memories {
+            for memory in &muse_memories {
                 let category = memory.category.to_string();
                 *category_counts.entry(category).or_insert(0) += 1;
             }
             
             // Tag analysis
             let mut tag_counts = HashMap::new();
-            for memory in muse_memories {
+            for memory in &muse_memories {
                 for tag in &memory.tags {
                     *tag_counts.entry(tag.clone()).or_insert(0) += 1;
                 }
@@ -800,4 +1700,172 @@ impl MemorySystem {
             }))
         }
     }
-}
\ No newline at end of file
+}
+
+fn is_summary(memory: &MuseMemory) -> bool {
+    memory.tags.iter().any(|tag| tag == CONSOLIDATED_TAG)
+}
+
+/// Whether `memory` is a consolidated summary with a source matching `is_source`
+fn summarises(memory: &MuseMemory, is_source: impl Fn(&str) -> bool) -> bool {
+    is_summary(memory)
+        && memory.context_window.as_ref().is_some_and(|sources| sources.iter().any(|id| is_source(id)))
+}
+
+/// Detach the demoted originals of `summary_ids` so they return to retrieval,
+/// grouped per summary
+fn release_sources(muse_memories: &mut [MuseMemory], summary_ids: &[String]) -> Vec<Vec<MuseMemory>> {
+    summary_ids.iter()
+        .map(|summary_id| muse_memories.iter_mut()
+            .filter(|m| m.consolidated_into.as_deref() == Some(summary_id.as_str()))
+            .map(|m| {
+                m.consolidated_into = None;
+                m.clone()
+            })
+            .collect::<Vec<MuseMemory>>())
+        .filter(|sources| !sources.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedder::HashingEmbedder;
+    use crate::muse_orchestrator::MuseTraits;
+    use crate::storage_backend::LocalStorageBackend;
+
+    const ALICE: &str = "0x00000000000000000000000000000000000a11ce";
+
+    async fn memory_system(name: &str) -> (MemorySystem, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("persist_memory_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let dir_str = dir.to_str().unwrap();
+        let embedder = Arc::new(HashingEmbedder::new(64));
+        let memory_system = MemorySystem::new(
+            &Config::for_tests(),
+            Arc::new(LocalStorageBackend::new(dir_str).unwrap()),
+            Arc::new(EncryptionService::unencrypted()),
+            embedder.clone(),
+            Arc::new(VectorIndex::open(dir_str, "memories", embedder.dimension(), &embedder.identity())),
+        ).await.unwrap();
+        (memory_system, dir)
+    }
+
+    fn memory(id: &str, user_prompt: &str) -> MuseMemory {
+        MuseMemory {
+            memory_id: id.to_string(),
+            muse_id: "7".to_string(),
+            interaction_data: InteractionData {
+                user_prompt: user_prompt.to_string(),
+                ai_response: "Noted.".to_string(),
+                personality_traits: MuseTraits { creativity: 50, wisdom: 50, humor: 50, empathy: 50 },
+                context_used: Vec::new(),
+                session_id: Some("session_1".to_string()),
+                conversation_turn: 1,
+                response_time_ms: 0,
+                model_used: "mock".to_string(),
+                prompt_tokens: None,
+                response_tokens: None,
+                user_satisfaction: None,
+                user_address: Some(ALICE.to_string()),
+                tool_calls: Vec::new(),
+            },
+            embedding: None,
+            importance: 0.5,
+            timestamp: 1,
+            ipfs_hash: None,
+            tags: vec!["pets".to_string()],
+            category: MemoryCategory::Personal,
+            emotional_tone: None,
+            context_window: None,
+            version: 1,
+            access_count: 0,
+            last_accessed: 1,
+            retention_priority: RetentionPriority::Medium,
+            consolidated_into: None,
+            history: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn edits_keep_the_version_history() {
+        let (memory_system, dir) = memory_system("edit").await;
+        memory_system.import_memory(memory("mem_a", "My dog is called Rex")).await.unwrap();
+        let original = memory_system.get_memory("7", "mem_a").await.unwrap();
+
+        let update = MemoryUpdate {
+            user_prompt: Some("My dog is called Max".to_string()),
+            tags: Some(vec!["pets".to_string(), "dog".to_string()]),
+            ..MemoryUpdate::default()
+        };
+        let edited = memory_system.update_memory("7", "mem_a", update, ALICE).await.unwrap().unwrap();
+        assert_eq!(edited.version, 2);
+        assert_eq!(edited.interaction_data.user_prompt, "My dog is called Max");
+        assert_ne!(edited.embedding, original.embedding);
+        assert_ne!(edited.ipfs_hash, original.ipfs_hash);
+
+        let revision = &edited.history[0];
+        assert_eq!(revision.version, 1);
+        assert_eq!(revision.user_prompt, "My dog is called Rex");
+        assert_eq!(revision.tags, vec!["pets".to_string()]);
+        assert_eq!(revision.ipfs_hash, original.ipfs_hash);
+        assert_eq!(revision.replaced_by, ALICE);
+
+        let importance = MemoryUpdate { importance: Some(3.0), ..MemoryUpdate::default() };
+        let reweighted = memory_system.update_memory("7", "mem_a", importance, ALICE).await.unwrap().unwrap();
+        assert_eq!(reweighted.version, 3);
+        assert_eq!(reweighted.importance, 1.0);
+        assert_eq!(reweighted.history.iter().map(|r| r.version).collect::<Vec<_>>(), vec![1, 2]);
+        // The text is unchanged, so the embedding is kept
+        assert_eq!(reweighted.embedding, edited.embedding);
+
+        assert!(memory_system.update_memory("7", "mem_missing", MemoryUpdate::default(), ALICE).await.unwrap().is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn unpinning_restores_the_previous_priority() {
+        let (memory_system, dir) = memory_system("pin").await;
+        let mut low = memory("mem_a", "Remind me about the vet");
+        low.retention_priority = RetentionPriority::Low;
+        memory_system.import_memory(low).await.unwrap();
+
+        let pinned = memory_system.set_memory_pinned("7", "mem_a", true, ALICE).await.unwrap().unwrap();
+        assert!(matches!(pinned.retention_priority, RetentionPriority::Critical));
+        // Pinned memories are never consolidated
+        assert!(memory_system.consolidation_candidates("7", 0, u64::MAX).await.is_empty());
+
+        let unpinned = memory_system.set_memory_pinned("7", "mem_a", false, ALICE).await.unwrap().unwrap();
+        assert!(matches!(unpinned.retention_priority, RetentionPriority::Low));
+        assert_eq!(unpinned.version, 3);
+        assert_eq!(unpinned.history.len(), 2);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn forgetting_removes_every_version_and_the_summaries_built_from_it() {
+        let (memory_system, dir) = memory_system("forget").await;
+        memory_system.import_memory(memory("mem_a", "My dog is called Rex")).await.unwrap();
+        memory_system.import_memory(memory("mem_b", "Rex loves the beach")).await.unwrap();
+        let sources = vec![
+            memory_system.get_memory("7", "mem_a").await.unwrap(),
+            memory_system.get_memory("7", "mem_b").await.unwrap(),
+        ];
+        let summary_id = memory_system.store_consolidated_memory("7", "The user has a dog, Rex.", &sources, 0.5).await.unwrap();
+        let update = MemoryUpdate { user_prompt: Some("My dog is called Max".to_string()), ..MemoryUpdate::default() };
+        memory_system.update_memory("7", "mem_a", update, ALICE).await.unwrap();
+
+        let removal = memory_system.forget_memory("7", "mem_a").await.unwrap().unwrap();
+        // Both versions of mem_a and the summary
+        assert_eq!((removal.unpinned, removal.unpin_failures), (3, 0));
+        assert_eq!(removal.removed_summaries, vec![summary_id.clone()]);
+        assert_eq!(removal.released_sources.len(), 1);
+        assert_eq!(removal.released_sources[0][0].memory_id, "mem_b");
+
+        assert!(memory_system.get_memory("7", "mem_a").await.is_none());
+        assert!(memory_system.get_memory("7", &summary_id).await.is_none());
+        assert!(memory_system.get_memory("7", "mem_b").await.unwrap().consolidated_into.is_none());
+        assert!(memory_system.forget_memory("7", "mem_a").await.unwrap().is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cot_personality::TraitsInfluence;
+use crate::muse_orchestrator::MuseTraits;
+
+const TRAITS: [&str; 4] = ["creativity", "wisdom", "humor", "empathy"];
+
+/// How a reply is built from trait-specialised drafts
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    /// One reply from the dominant trait's model
+    Off,
+    /// The draft that best fits the muse's traits is sent as is
+    Select,
+    /// The drafts are merged into one reply, in proportion to the trait weights
+    Merge,
+}
+
+impl BlendMode {
+    /// Mode named by `BLEND_MODE`; anything unrecognised turns blending off
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_lowercase().as_str() {
+            "select" => BlendMode::Select,
+            "merge" => BlendMode::Merge,
+            _ => BlendMode::Off,
+        }
+    }
+}
+
+/// One draft written with a single trait leading
+#[derive(Debug, Clone, Serialize)]
+pub struct BlendCandidate {
+    pub trait_name: String,
+    pub model_version: String,
+    pub response: String,
+    /// Judge's 0-100 rating of the draft on each trait, when the judge answered
+    pub trait_scores: Option<MuseTraits>,
+    /// Fit with the muse's trait weights, 0-1
+    pub score: f32,
+}
+
+/// How a blended reply was produced, returned with the response
+#[derive(Debug, Clone, Serialize)]
+pub struct BlendMetadata {
+    pub mode: BlendMode,
+    pub weights: TraitsInfluence,
+    pub candidates: Vec<BlendCandidate>,
+    /// Index of the candidate that was sent; None when the drafts were merged
+    pub chosen: Option<usize>,
+    /// Whether the judge scored the drafts; otherwise each draft scores its own trait's weight
+    pub judged: bool,
+}
+
+#[derive(Deserialize)]
+struct JudgeReply {
+    scores: Vec<MuseTraits>,
+}
+
+/// The `count` traits with the largest weights, strongest first
+pub fn candidate_traits(weights: &TraitsInfluence, count: usize) -> Vec<&'static str> {
+    let mut traits = TRAITS.to_vec();
+    traits.sort_by(|a, b| weights.weight(b).total_cmp(&weights.weight(a)));
+    traits.truncate(count.clamp(1, TRAITS.len()));
+    traits
+}
+
+/// `prompt` with `instruction` inserted before the speaker label it ends with, e.g. "Muse:"
+pub fn insert_before_label(prompt: &str, instruction: &str) -> String {
+    match prompt.rsplit_once('\n') {
+        Some((head, label)) => format!("{}\n{}\n{}", head, instruction, label),
+        None => format!("{}\n{}", instruction, prompt),
+    }
+}
+
+/// Prompt asking the judge to rate every draft on the four traits
+pub fn judge_prompt(user_message: &str, candidates: &[BlendCandidate]) -> String {
+    let drafts = candidates.iter()
+        .enumerate()
+        .map(|(i, candidate)| format!("Draft {}:\n{}", i + 1, candidate.response.trim()))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    format!(
+        "Rate how strongly each draft reply shows creativity, wisdom, humor and empathy, from 0 to 100.\n\n\
+        Message: {}\n\n{}\n\n\
+        Answer only with JSON in this form, one entry per draft in order:\n\
+        {{\"scores\": [{{\"creativity\": 0, \"wisdom\": 0, \"humor\": 0, \"empathy\": 0}}]}}\n",
+        user_message, drafts
+    )
+}
+
+/// Ratings from the judge's reply, if it holds one entry per draft
+pub fn parse_judge_scores(reply: &str, count: usize) -> Option<Vec<MuseTraits>> {
+    let start = reply.find('{')?;
+    let end = reply.rfind('}')?;
+    let parsed: JudgeReply = serde_json::from_str(reply.get(start..=end)?).ok()?;
+    (parsed.scores.len() == count).then_some(parsed.scores)
+}
+
+/// Weighted 0-1 fit of a draft's ratings with the muse's traits
+pub fn fit_score(scores: &MuseTraits, weights: &TraitsInfluence) -> f32 {
+    let rating = |value: u8| value.min(100) as f32 / 100.0;
+    weights.creativity_weight * rating(scores.creativity)
+        + weights.wisdom_weight * rating(scores.wisdom)
+        + weights.humor_weight * rating(scores.humor)
+        + weights.empathy_weight * rating(scores.empathy)
+}
+
+/// Instruction asking the model to combine the drafts, each weighted by its share of the scores
+pub fn merge_instruction(candidates: &[BlendCandidate]) -> String {
+    let total: f32 = candidates.iter().map(|c| c.score).sum();
+    let drafts = candidates.iter()
+        .map(|candidate| {
+            let share = if total > 0.0 { candidate.score / total } else { 1.0 / candidates.len() as f32 };
+            format!("Draft leaning on {} (weight {:.2}):\n{}", candidate.trait_name, share, candidate.response.trim())
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    format!(
+        "Drafts of your reply, each leaning on one of your traits:\n\n{}\n\n\
+        Write one reply that combines these drafts, drawing on each in proportion to its weight. \
+        Reply with the combined reply only.",
+        drafts
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn traits(creativity: u8, wisdom: u8, humor: u8, empathy: u8) -> MuseTraits {
+        MuseTraits { creativity, wisdom, humor, empathy }
+    }
+
+    fn candidate(trait_name: &str, response: &str, score: f32) -> BlendCandidate {
+        BlendCandidate {
+            trait_name: trait_name.to_string(),
+            model_version: "model".to_string(),
+            response: response.to_string(),
+            trait_scores: None,
+            score,
+        }
+    }
+
+    #[test]
+    fn judge_scores_are_parsed_from_json_in_prose() {
+        let reply = r#"Here you go: {"scores": [{"creativity": 90, "wisdom": 10, "humor": 40, "empathy": 70},
+{"creativity": 20, "wisdom": 80, "humor": 0, "empathy": 30}]} Hope that helps."#;
+
+        let scores = parse_judge_scores(reply, 2).unwrap();
+        assert_eq!(scores[0].creativity, 90);
+        assert_eq!(scores[1].wisdom, 80);
+    }
+
+    #[test]
+    fn judge_scores_need_one_entry_per_draft() {
+        let reply = r#"{"scores": [{"creativity": 90, "wisdom": 10, "humor": 40, "empathy": 70}]}"#;
+
+        assert!(parse_judge_scores(reply, 2).is_none());
+        assert!(parse_judge_scores(r#"{"scores": [{"creativity": 90}]}"#, 1).is_none());
+        assert!(parse_judge_scores("The first draft is best.", 1).is_none());
+        assert!(parse_judge_scores("} {", 1).is_none());
+    }
+
+    #[test]
+    fn fit_score_weights_ratings_by_trait_share() {
+        let weights = TraitsInfluence::from_traits(&traits(60, 20, 20, 0));
+
+        assert!((fit_score(&traits(100, 50, 0, 100), &weights) - 0.7).abs() < 1e-6);
+        // Ratings above 100 count as 100
+        assert!((fit_score(&traits(255, 255, 255, 255), &weights) - 1.0).abs() < 1e-6);
+        assert_eq!(fit_score(&traits(0, 0, 0, 0), &weights), 0.0);
+    }
+
+    #[test]
+    fn candidate_traits_are_the_strongest_first() {
+        let weights = TraitsInfluence::from_traits(&traits(20, 10, 60, 10));
+
+        assert_eq!(candidate_traits(&weights, 2), vec!["humor", "creativity"]);
+        // Ties keep the fixed trait order
+        assert_eq!(candidate_traits(&weights, 4), vec!["humor", "creativity", "wisdom", "empathy"]);
+        assert_eq!(candidate_traits(&weights, 0), vec!["humor"]);
+        assert_eq!(candidate_traits(&weights, 10).len(), 4);
+    }
+
+    #[test]
+    fn merge_instruction_shares_weight_by_score() {
+        let instruction = merge_instruction(&[candidate("humor", " A pun. ", 3.0), candidate("wisdom", "A proverb.", 1.0)]);
+
+        assert!(instruction.contains("Draft leaning on humor (weight 0.75):\nA pun."));
+        assert!(instruction.contains("Draft leaning on wisdom (weight 0.25):\nA proverb."));
+
+        // Without scores every draft counts the same
+        let unscored = merge_instruction(&[candidate("humor", "A pun.", 0.0), candidate("wisdom", "A proverb.", 0.0)]);
+        assert!(unscored.contains("humor (weight 0.50)"));
+        assert!(unscored.contains("wisdom (weight 0.50)"));
+    }
+
+    #[test]
+    fn instructions_go_before_the_speaker_label() {
+        assert_eq!(
+            insert_before_label("System\n\nUser: hi\n\nMuse:", "Lead with humor."),
+            "System\n\nUser: hi\n\nLead with humor.\nMuse:"
+        );
+        assert_eq!(insert_before_label("Muse:", "Lead with humor."), "Lead with humor.\nMuse:");
+    }
+}
@@ -1,6 +1,8 @@
 use anyhow::Result;
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use tokio::sync::RwLock;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,12 +34,24 @@ pub struct PluginExecution {
     pub error_message: Option<String>,
 }
 
+/// Durable storage for the plugin registry and per-muse installs
+#[async_trait]
+pub trait PluginRepository: Send + Sync {
+    async fn save_plugin(&self, plugin: &Plugin) -> Result<()>;
+    async fn load_plugins(&self) -> Result<Vec<Plugin>>;
+    async fn save_muse_plugin(&self, muse_id: &str, plugin_id: &str) -> Result<()>;
+    async fn load_muse_plugins(&self) -> Result<Vec<(String, String)>>;
+}
+
 pub struct PluginSystem {
     // Registry of available plugins
     plugins: RwLock<HashMap<String, Plugin>>,
     
     // Installed plugins per muse
     muse_plugins: RwLock<HashMap<String, Vec<String>>>,
+    
+    // Durable storage
+    repository: Option<Arc<dyn PluginRepository>>,
 }
 
 impl PluginSystem {
@@ -45,10 +59,37 @@ impl PluginSystem {
         Ok(Self {
             plugins: RwLock::new(HashMap::new()),
             muse_plugins: RwLock::new(HashMap::new()),
+            repository: None,
         })
     }
     
+    /// Attach durable storage and rehydrate the registry and installs
+    pub async fn with_repository(mut self, repository: Arc<dyn PluginRepository>) -> Result<Self> {
+        {
+            let mut plugins = self.plugins.write().await;
+            for plugin in repository.load_plugins().await? {
+                plugins.insert(plugin.id.clone(), plugin);
+            }
+            
+            let mut muse_plugins = self.muse_plugins.write().await;
+            for (muse_id, plugin_id) in repository.load_muse_plugins().await? {
+                muse_plugins.entry(muse_id).or_insert_with(Vec::new).push(plugin_id);
+            }
+            
+            println!("🔌 Rehydrated {} plugins installed on {} muses", plugins.len(), muse_plugins.len());
+        }
+        
+        self.repository = Some(repository);
+        Ok(self)
+    }
+    
     pub async fn register_plugin(&self, plugin: Plugin) -> Result<()> {
+        if let Some(repository) = &self.repository {
+            if let Err(e) = repository.save_plugin(&plugin).await {
+                println!("⚠️  Failed to persist plugin {}: {}", plugin.id, e);
+            }
+        }
+        
         let mut plugins = self.plugins.write().await;
         plugins.insert(plugin.id.clone(), plugin);
         Ok(())
@@ -67,6 +108,13 @@ impl PluginSystem {
         muse_plugins.entry(muse_id.to_string())
             .or_insert_with(Vec::new)
             .push(plugin_id.to_string());
+        drop(muse_plugins);
+        
+        if let Some(repository) = &self.repository {
+            if let Err(e) = repository.save_muse_plugin(muse_id, plugin_id).await {
+                println!("⚠️  Failed to persist plugin {} for muse {}: {}", plugin_id, muse_id, e);
+            }
+        }
         
         Ok(())
     }
@@ -1,7 +1,9 @@
 use ethers::prelude::*;
 use anyhow::Result;
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use tokio::sync::RwLock;
 use crate::blockchain_client::BlockchainClient;
 use crate::config::Config;
 
@@ -42,10 +44,30 @@ pub struct PlatformStats {
     pub active_muses: u64,
 }
 
+/// A rating accepted by the market, kept so users can export what they submitted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RatingRecord {
+    pub rating_id: String,
+    pub rating: InteractionRating,
+    pub transaction_hash: Option<String>,
+    pub reward_amount: u64,
+    pub submitted_at: u64,
+}
+
+/// Durable storage for submitted ratings
+#[async_trait]
+pub trait RatingRepository: Send + Sync {
+    async fn save_rating(&self, record: &RatingRecord) -> Result<()>;
+    async fn load_ratings(&self) -> Result<Vec<RatingRecord>>;
+}
+
 /// AI Alignment Market - First Decentralized AI Improvement Marketplace
 pub struct AIAlignmentMarket {
     blockchain_client: Arc<BlockchainClient>,
     config: Config,
+    // ✅ NEW: Local ledger of submitted ratings, oldest first
+    ratings: RwLock<Vec<RatingRecord>>,
+    repository: Option<Arc<dyn RatingRepository>>,
 }
 
 impl AIAlignmentMarket {
@@ -53,7 +75,55 @@ impl AIAlignmentMarket {
         Self { 
             blockchain_client,
             config,
+            ratings: RwLock::new(Vec::new()),
+            repository: None,
+        }
+    }
+
+    /// Attach durable storage and rehydrate the rating ledger
+    pub async fn with_repository(mut self, repository: Arc<dyn RatingRepository>) -> Result<Self> {
+        let mut ratings = repository.load_ratings().await?;
+        ratings.sort_by_key(|record| record.submitted_at);
+        println!("⭐ Rehydrated {} interaction ratings", ratings.len());
+        
+        self.ratings = RwLock::new(ratings);
+        self.repository = Some(repository);
+        Ok(self)
+    }
+
+    /// Ratings `user_address` submitted for `muse_id`
+    pub async fn user_ratings(&self, user_address: &str, muse_id: u64) -> Vec<RatingRecord> {
+        self.ratings.read().await
+            .iter()
+            .filter(|record| record.rating.muse_id == muse_id && record.rating.user_address.eq_ignore_ascii_case(user_address))
+            .cloned()
+            .collect()
+    }
+
+    /// Add ratings exported from another deployment, skipping ones already present.
+    /// Returns how many were added.
+    pub async fn import_ratings(&self, records: Vec<RatingRecord>) -> usize {
+        let mut imported = 0;
+        for mut record in records {
+            // Rewards and their transactions cannot be checked against the chain from here
+            record.reward_amount = 0;
+            record.transaction_hash = None;
+            if self.ratings.read().await.iter().any(|existing| existing.rating_id == record.rating_id) {
+                continue;
+            }
+            self.store_rating(record).await;
+            imported += 1;
         }
+        imported
+    }
+
+    async fn store_rating(&self, record: RatingRecord) {
+        if let Some(repository) = &self.repository {
+            if let Err(e) = repository.save_rating(&record).await {
+                println!("⚠️ Failed to persist rating {}: {}", record.rating_id, e);
+            }
+        }
+        self.ratings.write().await.push(record);
     }
 
     /// Submit rating for AI interaction - FIRST DECENTRALIZED AI ALIGNMENT!
@@ -121,6 +191,7 @@ impl AIAlignmentMarket {
         {
             Ok(tx_hash) => {
                 println!("✅ Rating submitted to blockchain: {}", tx_hash);
+                self.record_rating(rating.clone(), Some(tx_hash.clone()), expected_reward).await;
                 Ok(RatingSubmissionResult {
                     success: true,
                     transaction_hash: Some(tx_hash),
@@ -140,6 +211,7 @@ impl AIAlignmentMarket {
                 );
 
                 println!("✅ Rating recorded locally with mock tx: {}", mock_tx_hash);
+                self.record_rating(rating.clone(), Some(mock_tx_hash.clone()), expected_reward).await;
 
                 Ok(RatingSubmissionResult {
                     success: true,
@@ -151,6 +223,19 @@ impl AIAlignmentMarket {
         }
     }
 
+    async fn record_rating(&self, rating: InteractionRating, transaction_hash: Option<String>, reward_amount: u64) {
+        self.store_rating(RatingRecord {
+            rating_id: format!("rating_{}", uuid::Uuid::new_v4()),
+            rating,
+            transaction_hash,
+            reward_amount,
+            submitted_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        }).await;
+    }
+
     /// Get statistics for a specific muse
     pub async fn get_muse_statistics(&self, muse_id: u64) -> Result<MuseStatistics> {
         println!("📊 Fetching statistics for muse #{}", muse_id);
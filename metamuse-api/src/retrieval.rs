@@ -0,0 +1,358 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::ai_worker_pool::AIWorkerPool;
+use crate::config::Config;
+use crate::vector_index::{IndexFilter, IndexHit, VectorIndex};
+
+/// Which retrievers feed the ranking
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetrievalMode {
+    /// Cosine similarity over the ANN index
+    #[default]
+    Vector,
+    /// BM25 over the lexical index
+    Lexical,
+    /// Both, combined with reciprocal-rank fusion
+    Hybrid,
+}
+
+/// Ranking options carried by `SemanticQuery` and memory search requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetrievalOptions {
+    pub mode: RetrievalMode,
+    /// RRF damping constant; higher values flatten the contribution of top ranks
+    pub rrf_k: f32,
+    /// Candidates taken from each retriever before fusion
+    pub candidate_pool: usize,
+    /// Boost for recent entries, scaled by `0.5^(age / recency_half_life_secs)`
+    pub recency_weight: f32,
+    pub recency_half_life_secs: u64,
+    /// Boost for entries with an `importance` score (0.0 - 1.0)
+    pub importance_weight: f32,
+    /// Reorder the top candidates with the cross-encoder when one is configured
+    pub rerank: bool,
+}
+
+impl Default for RetrievalOptions {
+    fn default() -> Self {
+        Self {
+            mode: RetrievalMode::Vector,
+            rrf_k: 60.0,
+            candidate_pool: 50,
+            recency_weight: 0.0,
+            recency_half_life_secs: 7 * 24 * 60 * 60,
+            importance_weight: 0.0,
+            rerank: false,
+        }
+    }
+}
+
+impl RetrievalOptions {
+    /// Defaults for building muse context: hybrid, boosted and reranked
+    pub fn hybrid() -> Self {
+        Self {
+            mode: RetrievalMode::Hybrid,
+            recency_weight: 0.2,
+            importance_weight: 0.3,
+            rerank: true,
+            ..Self::default()
+        }
+    }
+}
+
+/// A fused candidate. `similarity` is always the cosine similarity to the query,
+/// so relevance thresholds keep their meaning whatever mode ranked the entry.
+#[derive(Debug, Clone)]
+pub struct RankedHit {
+    pub hit: IndexHit,
+    pub similarity: f32,
+    pub score: f32,
+}
+
+/// Retrieve up to `options.candidate_pool` candidates, fused and boosted, best first.
+/// `importance` looks up an entry's importance for boosting.
+pub async fn retrieve(
+    index: &VectorIndex,
+    query_text: &str,
+    query_embedding: &[f32],
+    filter: &IndexFilter,
+    options: &RetrievalOptions,
+    importance: impl Fn(&IndexHit) -> Option<f32>,
+) -> Vec<RankedHit> {
+    let pool = options.candidate_pool.max(1);
+
+    let vector_hits = if options.mode == RetrievalMode::Lexical {
+        Vec::new()
+    } else {
+        index.search(query_embedding, pool, filter).await
+    };
+    let lexical_hits = if options.mode == RetrievalMode::Vector {
+        Vec::new()
+    } else {
+        index.search_lexical(query_text, pool, filter).await
+    };
+
+    let mut candidates: HashMap<String, RankedHit> = HashMap::new();
+    for (rank, hit) in vector_hits.into_iter().enumerate() {
+        let similarity = hit.score;
+        candidates.insert(hit.id.clone(), RankedHit {
+            hit,
+            similarity,
+            score: rrf_score(rank, options.rrf_k),
+        });
+    }
+    for (rank, hit) in lexical_hits.into_iter().enumerate() {
+        match candidates.get_mut(&hit.id) {
+            Some(candidate) => {
+                candidate.score += rrf_score(rank, options.rrf_k);
+            }
+            None => {
+                let similarity = index.similarity(query_embedding, &hit.id).await.unwrap_or(0.0);
+                candidates.insert(hit.id.clone(), RankedHit {
+                    hit,
+                    similarity,
+                    score: rrf_score(rank, options.rrf_k),
+                });
+            }
+        }
+    }
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let mut ranked: Vec<RankedHit> = candidates.into_values()
+        .map(|mut candidate| {
+            candidate.score *= boost(candidate.hit.metadata.timestamp, importance(&candidate.hit), options, now);
+            candidate
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    ranked
+}
+
+/// Reciprocal-rank fusion contribution of a 0-based rank
+pub fn rrf_score(rank: usize, k: f32) -> f32 {
+    1.0 / (k + rank as f32 + 1.0)
+}
+
+/// Multiplicative recency and importance boost (1.0 when both weights are zero)
+pub fn boost(timestamp: u64, importance: Option<f32>, options: &RetrievalOptions, now: u64) -> f32 {
+    let age = now.saturating_sub(timestamp) as f32;
+    let half_life = options.recency_half_life_secs.max(1) as f32;
+    let recency = 0.5f32.powf(age / half_life);
+    let importance = importance.unwrap_or(0.0).clamp(0.0, 1.0);
+
+    1.0 + options.recency_weight * recency + options.importance_weight * importance
+}
+
+/// Reorder the first `top_n` items by cross-encoder relevance. Items past `top_n`
+/// keep their order after the reranked block.
+pub async fn rerank_in_place<T>(
+    reranker: &dyn Reranker,
+    query: &str,
+    items: &mut Vec<T>,
+    top_n: usize,
+    text: impl Fn(&T) -> String,
+) -> Result<()> {
+    let top_n = top_n.min(items.len());
+    if top_n < 2 {
+        return Ok(());
+    }
+
+    let documents: Vec<String> = items[..top_n].iter().map(&text).collect();
+    let scores = reranker.rerank(query, &documents).await?;
+    if scores.len() != top_n {
+        return Err(anyhow::anyhow!("{} returned {} scores for {} documents", reranker.name(), scores.len(), top_n));
+    }
+
+    let rest = items.split_off(top_n);
+    let mut scored: Vec<(f32, T)> = scores.into_iter().zip(items.drain(..)).collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    items.extend(scored.into_iter().map(|(_, item)| item));
+    items.extend(rest);
+    Ok(())
+}
+
+/// Scores how well each document answers the query (higher is better)
+#[async_trait]
+pub trait Reranker: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn top_n(&self) -> usize;
+
+    async fn rerank(&self, query: &str, documents: &[String]) -> Result<Vec<f32>>;
+}
+
+/// Start the cross-encoder when its model file is present. Reranking is optional,
+/// so a missing model only disables the step.
+pub async fn create_reranker(config: &Config) -> Option<Arc<dyn Reranker>> {
+    if !Path::new(&config.rerank_model_path).exists() {
+        println!("ℹ️  Rerank model not found at {} - reranking disabled", config.rerank_model_path);
+        return None;
+    }
+
+    match CrossEncoderReranker::new(config).await {
+        Ok(reranker) => {
+            println!("🎯 Rerank model loaded: {}", config.rerank_model_path);
+            Some(Arc::new(reranker))
+        }
+        Err(e) => {
+            println!("⚠️  Failed to start rerank model: {}", e);
+            None
+        }
+    }
+}
+
+/// Cross-encoder GGUF (e.g. bge-reranker) run by `ai-worker --rerank-model` processes with
+/// rank pooling. The pooled output of a query/document pair is a single relevance logit;
+/// workers refuse to start on models without a classification head.
+pub struct CrossEncoderReranker {
+    pool: Arc<AIWorkerPool>,
+    pair_template: String,
+    top_n: usize,
+}
+
+impl CrossEncoderReranker {
+    pub async fn new(config: &Config) -> Result<Self> {
+        let pool = AIWorkerPool::new_rerank(config, &config.rerank_model_path).await?;
+        pool.start_health_monitor(std::time::Duration::from_secs(config.ai_worker_health_check_interval_secs));
+
+        Ok(Self {
+            pool,
+            pair_template: config.rerank_pair_template.clone(),
+            top_n: config.rerank_top_n.max(1),
+        })
+    }
+}
+
+#[async_trait]
+impl Reranker for CrossEncoderReranker {
+    fn name(&self) -> &str {
+        "cross-encoder"
+    }
+
+    fn top_n(&self) -> usize {
+        self.top_n
+    }
+
+    async fn rerank(&self, query: &str, documents: &[String]) -> Result<Vec<f32>> {
+        let pairs: Vec<String> = documents.iter()
+            .map(|document| self.pair_template.replace("{query}", query).replace("{document}", document))
+            .collect();
+
+        let outputs = self.pool.embed(pairs).await?;
+        outputs.into_iter()
+            .map(|output| match output.as_slice() {
+                [score] => Ok(*score),
+                _ => Err(anyhow::anyhow!("Rerank model returned {} outputs for a pair, expected one score", output.len())),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Scores documents by length, or returns `scores` verbatim when set
+    struct StubReranker {
+        scores: Option<Vec<f32>>,
+        calls: AtomicUsize,
+    }
+
+    impl StubReranker {
+        fn new(scores: Option<Vec<f32>>) -> Self {
+            Self { scores, calls: AtomicUsize::new(0) }
+        }
+    }
+
+    #[async_trait]
+    impl Reranker for StubReranker {
+        fn name(&self) -> &str {
+            "stub"
+        }
+
+        fn top_n(&self) -> usize {
+            3
+        }
+
+        async fn rerank(&self, _query: &str, documents: &[String]) -> Result<Vec<f32>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(self.scores.clone().unwrap_or_else(|| documents.iter().map(|d| d.len() as f32).collect()))
+        }
+    }
+
+    fn fixture() -> Vec<String> {
+        ["a", "ccc", "bb", "dddd", "e"].iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn rrf_score_decreases_with_rank() {
+        assert!((rrf_score(0, 60.0) - 1.0 / 61.0).abs() < 1e-9);
+        assert!((rrf_score(9, 60.0) - 1.0 / 70.0).abs() < 1e-9);
+        assert!(rrf_score(0, 60.0) > rrf_score(1, 60.0));
+        // A smaller k favours the top ranks more
+        assert!(rrf_score(0, 1.0) / rrf_score(5, 1.0) > rrf_score(0, 60.0) / rrf_score(5, 60.0));
+    }
+
+    #[test]
+    fn boost_is_neutral_without_weights() {
+        let options = RetrievalOptions::default();
+        assert_eq!(boost(0, Some(1.0), &options, 1_000_000), 1.0);
+    }
+
+    #[test]
+    fn boost_halves_recency_every_half_life_and_clamps_importance() {
+        let options = RetrievalOptions {
+            recency_weight: 0.4,
+            recency_half_life_secs: 100,
+            importance_weight: 0.0,
+            ..RetrievalOptions::default()
+        };
+        let now = 1_000;
+        assert!((boost(now, None, &options, now) - 1.4).abs() < 1e-6);
+        assert!((boost(now - 100, None, &options, now) - 1.2).abs() < 1e-6);
+        assert!((boost(now - 200, None, &options, now) - 1.1).abs() < 1e-6);
+        // Timestamps in the future count as brand new
+        assert!((boost(now + 50, None, &options, now) - 1.4).abs() < 1e-6);
+
+        let options = RetrievalOptions { importance_weight: 0.5, ..RetrievalOptions::default() };
+        assert!((boost(0, Some(0.5), &options, now) - 1.25).abs() < 1e-6);
+        assert!((boost(0, Some(3.0), &options, now) - 1.5).abs() < 1e-6);
+        assert_eq!(boost(0, None, &options, now), 1.0);
+    }
+
+    #[tokio::test]
+    async fn rerank_in_place_reorders_only_the_head() {
+        let reranker = StubReranker::new(None);
+        let mut items = fixture();
+        rerank_in_place(&reranker, "query", &mut items, 3, |s| s.clone()).await.unwrap();
+        assert_eq!(items, vec!["ccc", "bb", "a", "dddd", "e"]);
+
+        // top_n past the end reranks everything
+        let mut items = fixture();
+        rerank_in_place(&reranker, "query", &mut items, 10, |s| s.clone()).await.unwrap();
+        assert_eq!(items, vec!["dddd", "ccc", "bb", "a", "e"]);
+    }
+
+    #[tokio::test]
+    async fn rerank_in_place_skips_single_items_and_rejects_short_scores() {
+        let reranker = StubReranker::new(Some(vec![1.0]));
+        let mut items = fixture();
+        rerank_in_place(&reranker, "query", &mut items, 1, |s| s.clone()).await.unwrap();
+        assert_eq!(reranker.calls.load(Ordering::SeqCst), 0);
+
+        assert!(rerank_in_place(&reranker, "query", &mut items, 3, |s| s.clone()).await.is_err());
+        assert_eq!(items, fixture());
+    }
+}
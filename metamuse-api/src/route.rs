@@ -1,10 +1,13 @@
 use axum::{
-    extract::{Path, State, Json, Query, Multipart},
+    body::Bytes,
+    extract::{Path, State, Json, Query, Multipart, DefaultBodyLimit, ws::{Message as WsMessage, WebSocket, WebSocketUpgrade}},
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response, sse::{Event, KeepAlive, Sse}},
     routing::{get, post},
     Router,
 };
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::UnboundedReceiverStream, Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize)]
@@ -20,6 +23,21 @@ pub struct ErrorResponse {
 }
 use std::sync::Arc;
 
+use crate::auth::{AdminUser, AuthenticatedUser, MuseAccess, check_muse_access, is_muse_owner, is_recorded_creator};
+use crate::data_erasure::ErasureReceipt;
+use crate::data_export::{ExportArchive, MAX_ARCHIVE_BYTES};
+use crate::verification::CommitmentRecord;
+use crate::context_builder::{AssembledContext, ContextRequest};
+use crate::ipfs_chat_history::{BranchPoint, IPFSChatMessage, IPFSChatSession};
+use crate::group_chat::{GroupChat, TurnPolicy};
+use crate::tools::{ToolCall, Toolbox};
+use crate::model_registry::ModelStatus;
+use crate::model_download::DownloadProgress;
+use crate::personality_blend::{self, BlendMetadata, BlendMode};
+use crate::cot_personality::ReasoningTrace;
+use crate::agent_workflow::{ConversationSpec, MuseProfiles, StopConditions, TrainingExample, WorkflowRun, WorkflowStatus};
+use crate::encryption::{key_derivation_message, DataKeyLocked};
+use crate::retrieval::RetrievalOptions;
 use crate::{AppState, persist_memory::InteractionData, muse_orchestrator::MuseTraits, rating_system::InteractionRating, semantic_search::{SemanticQuery, SemanticSearchResult}, template_system::{PromptTemplate, TemplateCategory, TemplateVariable}, avatar_system::{Avatar, AvatarUploadRequest, AvatarUploadResponse, AvatarGenerationRequest, AvatarCategory, AvatarStyle}, training_data_market::{ContributeTrainingDataRequest, ContributeTrainingDataResponse}};
 
 // Request/Response types
@@ -28,6 +46,9 @@ pub struct ChatRequest {
     pub message: String,
     pub user_address: String,
     pub context_window: Option<usize>,
+    // ✅ NEW: Overrides BLEND_MODE for this message
+    #[serde(default)]
+    pub blend_mode: Option<BlendMode>,
 }
 
 #[derive(Debug, Serialize)]
@@ -45,6 +66,9 @@ pub struct ResponseMetadata {
     pub model_version: String,
     pub memory_updated: bool,
     pub traits_used: MuseTraits,
+    // ✅ NEW: Drafts, weights and the chosen draft when the reply was blended
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blend: Option<BlendMetadata>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -118,6 +142,9 @@ pub struct DATInteractionData {
     pub ai_response: String,
     pub timestamp: u64,
     pub user_address: String,
+    /// Muse that wrote `ai_response`; required for group and workflow sessions
+    #[serde(default)]
+    pub muse_id: Option<u64>,
 }
 
 // ✅ NEW: TEE proof request from frontend
@@ -268,6 +295,29 @@ pub struct ChatMessage {
     pub timestamp: String,
     pub verification_status: Option<String>,
     pub commitment_hash: Option<String>,
+    // ✅ NEW: Message this one replies to, for rendering alternative branches
+    pub parent_id: Option<String>,
+    // ✅ NEW: Muse that wrote the message in a group session
+    pub speaker: Option<String>,
+    // ✅ NEW: Tools the muse called while writing the message
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+}
+
+impl From<&IPFSChatMessage> for ChatMessage {
+    fn from(msg: &IPFSChatMessage) -> Self {
+        Self {
+            id: msg.id.clone(),
+            content: msg.content.clone(),
+            role: msg.role.clone(),
+            timestamp: msg.timestamp.to_string(),
+            verification_status: Some("verified".to_string()),
+            commitment_hash: Some("0x123456789abcdef".to_string()),
+            parent_id: msg.parent_id.clone(),
+            speaker: msg.speaker.clone(),
+            tool_calls: msg.tool_calls.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -275,17 +325,214 @@ pub struct ChatMessageRequest {
     pub session_id: String,
     pub message: String,
     pub user_address: String,
+    // ✅ NEW: Prompt template layered on the muse's personality for this turn
+    #[serde(default)]
+    pub template_id: Option<String>,
+    // ✅ NEW: Overrides BLEND_MODE for this message
+    #[serde(default)]
+    pub blend_mode: Option<BlendMode>,
+    // ✅ NEW: Overrides COT_REASONING_ENABLED for this message
+    #[serde(default)]
+    pub reasoning: Option<bool>,
+}
+
+// ✅ NEW: Conversation branching
+#[derive(Debug, Deserialize)]
+pub struct EditMessageRequest {
+    pub session_id: String,
+    pub user_address: String,
+    /// Replacement text for the user message
+    pub message: String,
+    #[serde(default)]
+    pub template_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegenerateReplyRequest {
+    pub session_id: String,
+    pub user_address: String,
+    #[serde(default)]
+    pub template_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SelectBranchRequest {
+    pub session_id: String,
+    pub user_address: String,
+}
+
+/// The selected branch of a session and the alternatives along it
+#[derive(Debug, Serialize)]
+pub struct ChatBranchesResponse {
+    pub session_id: String,
+    pub active_leaf: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    pub branches: Vec<BranchPoint>,
+}
+
+/// ✅ NEW: Reasoning trace stored with an assistant message
+#[derive(Debug, Serialize)]
+pub struct MessageReasoningResponse {
+    pub session_id: String,
+    pub message_id: String,
+    pub content: String,
+    pub reasoning: ReasoningTrace,
+}
+
+// ✅ NEW: Group chat with several muses
+#[derive(Debug, Deserialize)]
+pub struct CreateGroupChatRequest {
+    pub user_address: String,
+    /// Muse token ids in speaking order for round-robin
+    pub muse_ids: Vec<String>,
+    pub turn_policy: TurnPolicy,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GroupChatMessageRequest {
+    pub user_address: String,
+    pub message: String,
+    #[serde(default)]
+    pub template_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupChatSessionResponse {
+    pub session_id: String,
+    pub participants: Vec<String>,
+    pub turn_policy: TurnPolicy,
+    pub messages: Vec<ChatMessage>,
+}
+
+/// Replies to one user message in a group session, in speaking order
+#[derive(Debug, Serialize)]
+pub struct GroupChatTurnResponse {
+    pub user_message_id: String,
+    pub replies: Vec<GroupChatReply>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupChatReply {
+    pub muse_id: String,
+    /// None when the muse failed to answer; replies of earlier speakers are kept
+    #[serde(flatten)]
+    pub reply: Option<ChatStreamFinal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// ✅ NEW: Autonomous conversations between muses
+#[derive(Debug, Deserialize)]
+pub struct StartConversationRequest {
+    pub user_address: String,
+    /// Muse token ids in speaking order for round-robin
+    pub muse_ids: Vec<String>,
+    pub seed_topic: String,
+    /// Defaults to and is capped by `AGENT_WORKFLOW_MAX_TURNS`
+    #[serde(default)]
+    pub max_turns: Option<usize>,
+    #[serde(default = "default_turn_policy")]
+    pub turn_policy: TurnPolicy,
+    #[serde(default)]
+    pub stop: StopConditions,
+}
+
+fn default_turn_policy() -> TurnPolicy {
+    TurnPolicy::RoundRobin
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkflowRunsResponse {
+    pub runs: Vec<WorkflowRun>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrainingDataResponse {
+    pub run_id: String,
+    pub session_id: String,
+    pub status: WorkflowStatus,
+    pub examples: Vec<TrainingExample>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModelsResponse {
+    pub default_model: String,
+    pub trait_routing: bool,
+    pub models: Vec<ModelStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StartModelDownloadRequest {
+    /// Model to download; every missing model when omitted
+    #[serde(default)]
+    pub model_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModelDownloadsResponse {
+    pub downloads: Vec<DownloadProgress>,
+}
+
+/// Reply generated on a new branch by an edit or regeneration
+#[derive(Debug, Serialize)]
+pub struct BranchReplyResponse {
+    pub user_message_id: String,
+    #[serde(flatten)]
+    pub reply: ChatStreamFinal,
+    pub branches: Vec<BranchPoint>,
 }
 
+/// Reply to a chat message, stored below it with its own signed commitment
 #[derive(Debug, Serialize)]
 pub struct ChatMessageResponse {
+    pub user_message_id: String,
+    #[serde(flatten)]
+    pub reply: ChatStreamFinal,
+    /// The reply's commitment covers the user's message too
+    pub user_commitment: String,
+}
+
+/// Frames sent over the chat stream (SSE events and WebSocket text messages)
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChatStreamEvent {
+    Token { content: String },
+    ToolCall(ToolCall),
+    Done(ChatStreamFinal),
+    Error { error: String },
+}
+
+/// Final frame of a streamed chat response
+#[derive(Debug, Serialize)]
+pub struct ChatStreamFinal {
     pub response: String,
     pub interaction_id: String,
+    pub message_id: String,
     pub commitment_hash: String,
-    pub user_commitment: String,
+    pub signature: String,
+    pub ipfs_session_hash: Option<String>,
     pub tee_attestation: Option<String>,
     pub tee_verified: bool,
     pub timestamp: u64,
+    pub inference_time_ms: u64,
+    pub tool_calls: Vec<ToolCall>,
+    /// Model that generated the reply
+    pub model_version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blend: Option<BlendMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<ReasoningTrace>,
+}
+
+impl ChatStreamEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            ChatStreamEvent::Token { .. } => "token",
+            ChatStreamEvent::ToolCall(_) => "tool_call",
+            ChatStreamEvent::Done(_) => "done",
+            ChatStreamEvent::Error { .. } => "error",
+        }
+    }
 }
 
 // Template system request/response types
@@ -387,9 +634,35 @@ pub fn chat_routes() -> Router<Arc<AppState>> {
         .route("/api/v1/muses/{id}/chat", post(handle_chat))
         .route("/api/v1/muses/{id}/chat/session", post(initialize_chat_session))
         .route("/api/v1/muses/{id}/chat/message", post(send_chat_message))
+        .route("/api/v1/muses/{id}/chat/stream", post(stream_chat_sse))
+        .route("/api/v1/muses/{id}/chat/ws", get(stream_chat_websocket))
+        .route("/api/v1/muses/{id}/chat/messages/{message_id}/edit", post(edit_chat_message))
+        .route("/api/v1/muses/{id}/chat/messages/{message_id}/regenerate", post(regenerate_chat_reply))
+        .route("/api/v1/muses/{id}/chat/messages/{message_id}/select", post(select_chat_branch))
+        .route("/api/v1/muses/{id}/chat/sessions/{session_id}/branches", get(get_chat_branches))
+        .route("/api/v1/muses/{id}/chat/sessions/{session_id}/messages/{message_id}/reasoning", get(get_message_reasoning))
+        .route("/api/v1/group-chats", post(create_group_chat))
+        .route("/api/v1/group-chats/{session_id}", get(get_group_chat))
+        .route("/api/v1/group-chats/{session_id}/message", post(send_group_chat_message))
         .route("/api/v1/test/ai-direct", post(test_ai_direct))
 }
 
+pub fn workflow_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/v1/workflows/conversations", post(start_muse_conversation))
+        .route("/api/v1/workflows/conversations", get(list_muse_conversations))
+        .route("/api/v1/workflows/conversations/{run_id}", get(get_muse_conversation))
+        .route("/api/v1/workflows/conversations/{run_id}/stop", post(stop_muse_conversation))
+        .route("/api/v1/workflows/conversations/{run_id}/training-data", get(get_conversation_training_data))
+}
+
+pub fn model_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/v1/models", get(list_models))
+        .route("/api/v1/admin/models/downloads", get(list_model_downloads))
+        .route("/api/v1/admin/models/downloads", post(start_model_downloads))
+}
+
 pub fn memory_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/api/v1/muses/{id}/memories", get(get_memories))
@@ -680,6 +953,11 @@ async fn create_muse(
         println!("✅ Recorded muse #{} for user {} (blockchain owner: {})", 
                  token_id, request.user_address, muse_data.owner);
     }
+    if let Some(repository) = &state.user_muse_repository {
+        if let Err(e) = repository.save_user_muse(&request.user_address, token_id).await {
+            println!("⚠️  Failed to persist muse #{} for user {}: {}", token_id, request.user_address, e);
+        }
+    }
 
     // Pre-initialize the AI agents for this muse
     let _muse_id = token_id.to_string();
@@ -761,6 +1039,7 @@ async fn test_ai_direct(
             model_version: "test-direct".to_string(),
             memory_updated: false,
             traits_used: traits.clone(),
+            blend: None,
         },
     };
     
@@ -772,8 +1051,13 @@ async fn test_ai_direct(
 async fn handle_chat(
     Path(muse_id): Path<String>,
     State(state): State<Arc<AppState>>,
+    MuseAccess { user: auth, .. }: MuseAccess,
     Json(request): Json<ChatRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
+    if !auth.owns(&request.user_address) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    
     let start_time = std::time::Instant::now();
     
     println!("🎯 Chat request received for muse_id: {}", muse_id);
@@ -816,18 +1100,45 @@ async fn handle_chat(
         Vec::new()
     };
 
-    // Generate AI response (mock for now to avoid model loading issues)
-    let ai_response = format!(
-        "Hello! I'm your muse with creativity: {}, wisdom: {}, humor: {}, empathy: {}. You said: '{}'. This is a mock response until GGUF models are properly configured.",
-        traits.creativity, traits.wisdom, traits.humor, traits.empathy, request.message
-    );
-    
-    // let ai_response = state.orchestrator
-    //     .generate_response(&muse_id, &traits, &request.message, Some(context.clone()))
-    //     .await
-    //     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // ✅ NEW: Blend drafts from several trait models when BLEND_MODE or the request asks for it
+    let blend_mode = request.blend_mode.unwrap_or_else(|| BlendMode::parse(&state.config.blend_mode));
+    // The prompt both paths send to the model, kept to count its tokens
+    let single_turn = state.orchestrator.single_turn_context(&traits, &request.message, context.clone());
+    let prompt = single_turn.prompt.clone();
+    let (ai_response, model_used, blend) = if blend_mode != BlendMode::Off {
+        // Nobody listens for tokens on this endpoint
+        let (tokens, _) = mpsc::unbounded_channel();
+        let (response, model_used, blend) = state.orchestrator
+            .generate_blended_response(&muse_id, &traits, &request.message, single_turn, blend_mode, state.context_builder.token_counter(), tokens)
+            .await
+            .map_err(|e| {
+                println!("❌ Blended generation failed: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+        (response, model_used, Some(blend))
+    } else {
+        // ✅ NEW: Answer with the model of the muse's dominant trait, mock response if none is loaded
+        let (model_id, llama_engine) = state.orchestrator.select_engine(&traits).await;
+        match state.orchestrator
+            .generate_response(&muse_id, &traits, &request.message, Some(context.clone()), llama_engine)
+            .await
+        {
+            Ok(response) => (response, model_id, None),
+            Err(e) => {
+                println!("⚠️ AI generation failed, using mock response: {}", e);
+                let mock_response = format!(
+                    "Hello! I'm your muse with creativity: {}, wisdom: {}, humor: {}, empathy: {}. You said: '{}'. This is a mock response until GGUF models are properly configured.",
+                    traits.creativity, traits.wisdom, traits.humor, traits.empathy, request.message
+                );
+                (mock_response, "mock".to_string(), None)
+            }
+        }
+    };
 
     let inference_time = start_time.elapsed().as_millis() as u64;
+    let token_counter = state.context_builder.token_counter();
+    let prompt_tokens = token_counter.count_exact(&prompt).await.map(|tokens| tokens as u32);
+    let response_tokens = token_counter.count_exact(&ai_response).await.map(|tokens| tokens as u32);
 
     // Create interaction data for memory storage
     let interaction = InteractionData {
@@ -840,10 +1151,12 @@ async fn handle_chat(
         session_id: None, // TODO: Add session tracking
         conversation_turn: 1, // TODO: Track conversation turns
         response_time_ms: inference_time,
-        model_used: "local_ai".to_string(), // TODO: Track actual model used
-        prompt_tokens: None, // TODO: Count tokens
-        response_tokens: None, // TODO: Count tokens
+        model_used: model_used.clone(),
+        prompt_tokens,
+        response_tokens,
         user_satisfaction: None, // TODO: Add satisfaction tracking
+        user_address: Some(request.user_address.clone()),
+        tool_calls: Vec::new(),
     };
 
     // Store memory
@@ -853,15 +1166,12 @@ async fn handle_chat(
         .is_ok();
 
     // Create verifiable interaction for cryptographic verification
+    let muse_dna_hash: [u8; 32] = hex::decode(&muse_data.dna_hash[2..])
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .try_into()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     let verifiable_interaction = state.verification_system
-        .create_interaction_from_data(
-            token_id,
-            hex::decode(&muse_data.dna_hash[2..])
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-                .try_into()
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
-            &interaction,
-        );
+        .create_interaction_from_data(token_id, muse_dna_hash, &interaction);
 
     // Create commitment and signature
     let commitment = state.verification_system
@@ -871,6 +1181,25 @@ async fn handle_chat(
 
     let commitment_hash = hex::encode(commitment.commitment_hash);
     let signature = hex::encode(&commitment.signature);
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    
+    state.verification_system.record_commitment(CommitmentRecord {
+        interaction_id: format!("interaction_{}_{}", muse_id, &commitment_hash[..16]),
+        muse_id: token_id,
+        user_address: request.user_address.clone(),
+        session_id: None,
+        message_id: None,
+        muse_dna_hash: format!("0x{}", hex::encode(muse_dna_hash)),
+        commitment_hash: format!("0x{}", commitment_hash),
+        signature: format!("0x{}", signature),
+        recovery_id: commitment.recovery_id,
+        signer: state.verification_system.get_public_key_address(),
+        foreign: false,
+        created_at: timestamp,
+    }).await;
 
     // Commit interaction to blockchain (optional - for full transparency)
     let _commit_tx = state.blockchain_client
@@ -885,15 +1214,13 @@ async fn handle_chat(
         response: ai_response,
         commitment_hash,
         signature,
-        timestamp: std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs(),
+        timestamp,
         metadata: ResponseMetadata {
             inference_time_ms: inference_time,
-            model_version: "gpt-4-alith".to_string(),
+            model_version: model_used,
             memory_updated,
             traits_used: traits,
+            blend,
         },
     };
 
@@ -905,6 +1232,7 @@ async fn get_memories(
     Path(muse_id): Path<String>,
     Query(query): Query<MemoryQuery>,
     State(state): State<Arc<AppState>>,
+    _access: MuseAccess,
 ) -> Result<impl IntoResponse, StatusCode> {
     let limit = query.limit.unwrap_or(10);
     
@@ -935,8 +1263,11 @@ async fn get_memories(
 async fn store_memory(
     Path(muse_id): Path<String>,
     State(state): State<Arc<AppState>>,
-    Json(interaction): Json<InteractionData>,
+    access: MuseAccess,
+    Json(mut interaction): Json<InteractionData>,
 ) -> Result<impl IntoResponse, StatusCode> {
+    interaction.user_address = Some(access.user.address);
+    
     let memory_id = state.memory_system
         .store_interaction_memory(&muse_id, &interaction)
         .await
@@ -1283,8 +1614,14 @@ async fn explore_muses(
 async fn initialize_chat_session(
     Path(muse_id): Path<String>,
     State(state): State<Arc<AppState>>,
+    MuseAccess { user: auth, .. }: MuseAccess,
     Json(request): Json<ChatSessionRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
+    if !auth.owns(&request.user_address) {
+        println!("🚫 {} tried to open a chat session as {}", auth.address, request.user_address);
+        return Err(StatusCode::FORBIDDEN);
+    }
+    
     println!("🌐 Initializing chat session for user {} + muse {}", request.user_address, muse_id);
 
     // Get existing session or create new one for this user+muse combination
@@ -1299,17 +1636,10 @@ async fn initialize_chat_session(
         }
     };
 
-    // Convert IPFS messages to API format
-    let api_messages: Vec<ChatMessage> = ipfs_session.messages
-        .iter()
-        .map(|msg| ChatMessage {
-            id: msg.id.clone(),
-            content: msg.content.clone(),
-            role: msg.role.clone(),
-            timestamp: msg.timestamp.to_string(),
-            verification_status: Some("verified".to_string()),
-            commitment_hash: Some("0x123456789abcdef".to_string()),
-        })
+    // Convert the selected branch to API format
+    let api_messages: Vec<ChatMessage> = ipfs_session.active_path()
+        .into_iter()
+        .map(ChatMessage::from)
         .collect();
 
     // If no existing messages, add greeting
@@ -1344,6 +1674,9 @@ async fn initialize_chat_session(
                         .to_string(),
                     verification_status: Some("verified".to_string()),
                     commitment_hash: Some("0x123456789abcdef".to_string()),
+                    parent_id: None,
+                    speaker: None,
+                    tool_calls: Vec::new(),
                 }]
             }
             Err(e) => {
@@ -1359,6 +1692,9 @@ async fn initialize_chat_session(
                         .to_string(),
                     verification_status: Some("verified".to_string()),
                     commitment_hash: Some("0x123456789abcdef".to_string()),
+                    parent_id: None,
+                    speaker: None,
+                    tool_calls: Vec::new(),
                 }]
             }
         }
@@ -1380,210 +1716,1042 @@ async fn initialize_chat_session(
 async fn send_chat_message(
     Path(muse_id): Path<String>,
     State(state): State<Arc<AppState>>,
+    MuseAccess { user: auth, .. }: MuseAccess,
     Json(request): Json<ChatMessageRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
+    // Only the caller's own session with this muse may be written to and packed into the prompt
+    owned_chat_session(&state, &muse_id, &request.session_id, &auth, &request.user_address).await?;
+    
     println!("🌐 Processing IPFS chat message for muse: {} in session: {}", muse_id, request.session_id);
     
-    // Parse muse ID to get personality traits
-    let token_id: u64 = muse_id.parse()
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    muse_id.parse::<u64>().map_err(|_| StatusCode::BAD_REQUEST)?;
     
-    // Step 1: Add user message to IPFS first
-    let user_message_id = format!("user_msg_{}", 
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis()
-    );
-
-    match state.ipfs_chat_history
-        .add_message(
-            &request.session_id,
-            "user".to_string(),
-            request.message.clone(),
-            user_message_id.clone(),
-        )
+    let user_message_id = add_user_message(&state, &muse_id, &request)
         .await
-    {
-        Ok(updated_session) => {
-            println!("✅ User message added to IPFS");
-            println!("📊 Session now has {} messages", updated_session.message_count);
-            println!("📝 Latest message: {} -> {}", 
-                updated_session.messages.last().map(|m| m.role.as_str()).unwrap_or("none"),
-                updated_session.messages.last().map(|m| m.content.chars().take(50).collect::<String>()).unwrap_or("none".to_string())
-            );
-        },
-        Err(e) => {
+        .map_err(|e| {
             println!("❌ Failed to add user message to IPFS: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    }
+            chat_storage_status(&e)
+        })?;
+    
+    // Nobody listens for tokens; the receiver only has to outlive generation
+    let (events, _tokens) = mpsc::unbounded_channel();
+    let reply = generate_chat_reply(&state, &muse_id, &request, &user_message_id, None, &events)
+        .await
+        .map_err(|e| {
+            println!("❌ Failed to reply to message {}: {}", user_message_id, e);
+            chat_storage_status(&e)
+        })?;
+    
+    Ok((StatusCode::OK, Json(ChatMessageResponse {
+        user_message_id,
+        user_commitment: reply.commitment_hash.clone(),
+        reply,
+    })))
+}
 
-    // Step 2: Retrieve chat history from IPFS for AI context
-    let chat_history = match state.ipfs_chat_history
-        .get_alith_history(&request.session_id)
+/// Pack the session's summaries and turns, the user's memories and an optional template
+/// into the model's context window. History ends at `leaf_id`, or the selected branch.
+/// In a group session the muse is told who else is taking part.
+async fn assemble_chat_context(
+    state: &AppState,
+    muse_id: &str,
+    traits: &MuseTraits,
+    request: &ChatMessageRequest,
+    leaf_id: Option<&str>,
+    group: Option<&GroupChat>,
+    tools: Option<&Toolbox>,
+) -> anyhow::Result<AssembledContext> {
+    let session = state.ipfs_chat_history
+        .get_session(&request.session_id)
         .await
-    {
-        Ok(history) => {
-            println!("📚 Retrieved {} history messages from IPFS for session {}", history.len(), request.session_id);
-            
-            // Debug: Log the actual history messages
-            for (i, msg) in history.iter().enumerate() {
-                println!("  📜 History[{}]: {} -> {}", i, msg.role, msg.content.chars().take(50).collect::<String>());
-            }
-            
-            history
-        }
-        Err(e) => {
-            println!("⚠️ Failed to retrieve chat history from IPFS: {}, using empty history", e);
+        .map_err(|e| println!("⚠️ Failed to load chat session: {}, continuing without history", e))
+        .ok();
+    
+    let memories = state.memory_system
+        .get_user_contextual_memories(muse_id, &request.user_address, &request.message, state.config.context_memory_limit)
+        .await
+        .unwrap_or_else(|e| {
+            println!("⚠️ Memory retrieval failed: {}, continuing without memories", e);
             Vec::new()
-        }
+        });
+    
+    let template = match &request.template_id {
+        Some(template_id) => match state.template_manager.lock().await.apply_template(template_id, &std::collections::HashMap::new(), traits) {
+            Ok(template) => Some(template),
+            Err(e) => {
+                println!("⚠️ Template {} not applied: {}", template_id, e);
+                None
+            }
+        },
+        None => None,
+    };
+    
+    let mut system_prompt = match group {
+        Some(group) => state.group_chat.system_prompt(group, muse_id, traits),
+        None => state.orchestrator.build_personality_system_prompt(traits),
     };
+    if let Some(section) = tools.and_then(|tools| tools.system_prompt_section()) {
+        system_prompt.push_str("\n\n");
+        system_prompt.push_str(&section);
+    }
     
-    // Get muse personality traits from our mock data
-    let muse_traits = match token_id {
+    state.context_builder.build(ContextRequest {
+        system_prompt,
+        template,
+        memories,
+        session: session.as_deref(),
+        leaf_id,
+        user_message: &request.message,
+        speaker_label: group.map(|_| format!("Muse #{}", muse_id)),
+    }).await
+}
+
+/// Personality traits used for chat when on-chain muse data is unavailable
+fn demo_muse_traits(token_id: u64) -> MuseTraits {
+    match token_id {
         1 => MuseTraits { creativity: 75, wisdom: 60, humor: 85, empathy: 70 },
         2 => MuseTraits { creativity: 90, wisdom: 80, humor: 60, empathy: 95 },
         3 => MuseTraits { creativity: 85, wisdom: 60, humor: 75, empathy: 45 },
         _ => MuseTraits { creativity: 70, wisdom: 70, humor: 70, empathy: 70 },
-    };
-    
-    // Step 3: Generate AI response using IPFS chat history
-    let ai_response = match state.orchestrator
-        .generate_response_with_history(&muse_id, &muse_traits, &request.message, chat_history, state.llama_engine.clone())
-        .await
-    {
-        Ok(response) => {
-            println!("🎯 AI response generated with IPFS history context");
-            response
-        }
-        Err(e) => {
-            println!("❌ AI generation with history failed: {}, falling back to mock response", e);
-            println!("🎭 Muse #{} traits: creativity={}, wisdom={}, humor={}, empathy={}", 
-                     token_id, muse_traits.creativity, muse_traits.wisdom, muse_traits.humor, muse_traits.empathy);
-            println!("🎯 User message: '{}'", request.message);
-            // Fallback to enhanced mock response if AI fails
-            let response = generate_personality_response(&request.message, muse_traits.creativity, muse_traits.wisdom, muse_traits.humor, muse_traits.empathy);
-            println!("📝 Generated fallback response: '{}'", response);
-            response
-        }
-    };
+    }
+}
 
-    // Step 4: Generate TEE attestation for AI response - WORLD'S FIRST!
-    let tee_verified_response = match state.tee_service
-        .generate_verified_response(
-            muse_id.clone(),
-            request.user_address.clone(),
-            ai_response.clone(),
-            muse_traits.clone(),
-            request.session_id.clone(),
+// ✅ NEW: Streaming chat over Server-Sent Events
+async fn stream_chat_sse(
+    Path(muse_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    MuseAccess { user: auth, .. }: MuseAccess,
+    Json(request): Json<ChatMessageRequest>,
+) -> Result<Sse<impl Stream<Item = Result<Event, std::convert::Infallible>>>, StatusCode> {
+    println!("🌊 SSE chat stream for muse: {} in session: {}", muse_id, request.session_id);
+    
+    muse_id.parse::<u64>().map_err(|_| StatusCode::BAD_REQUEST)?;
+    owned_chat_session(&state, &muse_id, &request.session_id, &auth, &request.user_address).await?;
+    
+    let (event_tx, event_rx) = mpsc::unbounded_channel();
+    tokio::spawn(stream_chat_message(state, muse_id, request, event_tx));
+    
+    let stream = UnboundedReceiverStream::new(event_rx).map(|event: ChatStreamEvent| {
+        let sse_event = Event::default().event(event.name());
+        Ok::<Event, std::convert::Infallible>(
+            sse_event.json_data(&event).unwrap_or_else(|_| Event::default().event("error").data("serialization failed"))
         )
-        .await
-    {
-        Ok(verified) => {
-            println!("🔒 TEE attestation generated for AI response");
-            Some(verified)
+    });
+    
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+// ✅ NEW: Streaming chat over WebSocket - one ChatMessageRequest JSON per text frame
+// Browsers cannot set headers on upgrades, so the session token may be passed as ?access_token=
+async fn stream_chat_websocket(
+    Path(muse_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    MuseAccess { user: auth, .. }: MuseAccess,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    println!("🔌 WebSocket chat connection for muse: {} from {}", muse_id, auth.address);
+    ws.on_upgrade(move |socket| handle_chat_socket(socket, state, muse_id, auth))
+}
+
+async fn handle_chat_socket(mut socket: WebSocket, state: Arc<AppState>, muse_id: String, auth: AuthenticatedUser) {
+    while let Some(Ok(message)) = socket.recv().await {
+        let text = match message {
+            WsMessage::Text(text) => text,
+            WsMessage::Close(_) => break,
+            _ => continue,
+        };
+        
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        match serde_json::from_str::<ChatMessageRequest>(text.as_str()) {
+            Ok(request) if !auth.owns(&request.user_address) => {
+                let _ = event_tx.send(ChatStreamEvent::Error { error: "user_address does not match the signed-in wallet".to_string() });
+            }
+            Ok(_) if muse_id.parse::<u64>().is_err() => {
+                let _ = event_tx.send(ChatStreamEvent::Error { error: format!("Invalid muse id: {}", muse_id) });
+            }
+            Ok(request) => match owned_chat_session(&state, &muse_id, &request.session_id, &auth, &request.user_address).await {
+                Ok(_) => {
+                    tokio::spawn(stream_chat_message(state.clone(), muse_id.clone(), request, event_tx));
+                }
+                Err(StatusCode::FORBIDDEN) => {
+                    let _ = event_tx.send(ChatStreamEvent::Error { error: format!("Chat session {} belongs to another wallet", request.session_id) });
+                }
+                Err(_) => {
+                    let _ = event_tx.send(ChatStreamEvent::Error { error: format!("Chat session {} not found for muse {}", request.session_id, muse_id) });
+                }
+            },
+            Err(e) => {
+                let _ = event_tx.send(ChatStreamEvent::Error { error: format!("Invalid chat message: {}", e) });
+            }
         }
-        Err(e) => {
-            println!("⚠️ TEE attestation failed: {}, continuing without TEE", e);
-            None
+        
+        // Forward every frame of this response before reading the next message
+        while let Some(event) = event_rx.recv().await {
+            let Ok(event_json) = serde_json::to_string(&event) else {
+                continue;
+            };
+            if socket.send(WsMessage::Text(event_json.into())).await.is_err() {
+                println!("🔌 WebSocket closed by client for muse: {}", muse_id);
+                return;
+            }
         }
-    };
+    }
+    
+    println!("🔌 WebSocket chat connection closed for muse: {}", muse_id);
+}
 
-    // Step 5: Add AI response to IPFS
-    let ai_message_id = format!("ai_msg_{}", 
+/// Run one chat turn, emitting token frames followed by a final or error frame
+async fn stream_chat_message(
+    state: Arc<AppState>,
+    muse_id: String,
+    request: ChatMessageRequest,
+    events: mpsc::UnboundedSender<ChatStreamEvent>,
+) {
+    if let Err(e) = run_streaming_chat(&state, &muse_id, &request, &events).await {
+        println!("❌ Streaming chat failed for muse {}: {}", muse_id, e);
+        let _ = events.send(ChatStreamEvent::Error { error: e.to_string() });
+    }
+}
+
+async fn run_streaming_chat(
+    state: &Arc<AppState>,
+    muse_id: &str,
+    request: &ChatMessageRequest,
+    events: &mpsc::UnboundedSender<ChatStreamEvent>,
+) -> anyhow::Result<()> {
+    let user_message_id = add_user_message(state, muse_id, request).await?;
+    let reply = generate_chat_reply(state, muse_id, request, &user_message_id, None, events).await?;
+    let _ = events.send(ChatStreamEvent::Done(reply));
+    
+    Ok(())
+}
+
+/// Step 1 of a chat turn: store the user's message at the end of the selected branch and
+/// index it for semantic search. Returns its id, which the reply is stored below.
+async fn add_user_message(state: &AppState, muse_id: &str, request: &ChatMessageRequest) -> anyhow::Result<String> {
+    let user_message_id = format!("user_msg_{}", 
         std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_millis()
     );
-
-    match state.ipfs_chat_history
-        .add_message(
-            &request.session_id,
-            "assistant".to_string(),
-            ai_response.clone(),
-            ai_message_id.clone(),
-        )
-        .await
-    {
-        Ok(updated_session) => {
-            println!("✅ AI response added to IPFS");
-            println!("📊 Session after AI response: {} total messages", updated_session.message_count);
-            println!("📝 Last 2 messages in session:");
-            for msg in updated_session.messages.iter().rev().take(2).rev() {
-                println!("  {} -> {}", msg.role, msg.content.chars().take(50).collect::<String>());
-            }
-        },
-        Err(e) => {
-            println!("⚠️ Failed to add AI response to IPFS: {}, continuing with response", e);
-            // Continue anyway since we have the response
-        }
-    }
-
-    // ✅ NEW: Auto-index messages for semantic search
+    
+    state.ipfs_chat_history
+        .add_message(&request.session_id, "user".to_string(), request.message.clone(), user_message_id.clone())
+        .await?;
+    
     let _ = state.semantic_search.auto_index_message(
         &request.session_id,
         &request.user_address,
-        &muse_id,
+        muse_id,
         &request.message,
         "user",
         &user_message_id,
     ).await;
+    
+    Ok(user_message_id)
+}
+
+/// Traits and DNA hash of a muse from chain, falling back to demo traits
+async fn resolve_muse_traits(state: &AppState, token_id: u64) -> (MuseTraits, [u8; 32]) {
+    match state.blockchain_client.get_muse_data(token_id).await {
+        Ok(muse_data) => {
+            let dna_hash: [u8; 32] = hex::decode(muse_data.dna_hash.trim_start_matches("0x"))
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .unwrap_or([0u8; 32]);
+            (MuseTraits {
+                creativity: muse_data.creativity,
+                wisdom: muse_data.wisdom,
+                humor: muse_data.humor,
+                empathy: muse_data.empathy,
+            }, dna_hash)
+        }
+        Err(e) => {
+            println!("⚠️ Failed to fetch muse data: {}, using demo traits", e);
+            (demo_muse_traits(token_id), [0u8; 32])
+        }
+    }
+}
 
+/// Generate a reply below the stored message `parent_id` (the user's message, or in a group
+/// the muse that spoke before), streaming tokens to `events`, then store it and sign a
+/// commitment over it. A reply to a message that already has one starts a new branch.
+async fn generate_chat_reply(
+    state: &Arc<AppState>,
+    muse_id: &str,
+    request: &ChatMessageRequest,
+    parent_id: &str,
+    group: Option<&GroupChat>,
+    events: &mpsc::UnboundedSender<ChatStreamEvent>,
+) -> anyhow::Result<ChatStreamFinal> {
+    let start_time = std::time::Instant::now();
+    let token_id: u64 = muse_id.parse()?;
+    
+    // Step 2: Resolve traits and DNA from chain, falling back to demo traits
+    let (muse_traits, muse_dna_hash) = resolve_muse_traits(state, token_id).await;
+    
+    // Step 3: Pack the prompt and stream tokens to the client while generating
+    // ✅ NEW: Blending replaces the single routed model and tool calling for this reply
+    let blend_mode = request.blend_mode.unwrap_or_else(|| BlendMode::parse(&state.config.blend_mode));
+    let toolbox = state.tool_registry.toolbox(muse_id, &request.user_address).await;
+    let tools = (blend_mode == BlendMode::Off).then_some(&toolbox);
+    let mut context = assemble_chat_context(state, muse_id, &muse_traits, request, Some(parent_id), group, tools).await?;
+    
+    // ✅ NEW: Reason about how each trait should shape the reply, then have the reply follow it
+    let mut reasoning = if request.reasoning.unwrap_or(state.config.cot_reasoning_enabled) {
+        state.orchestrator.reason_about_message(muse_id, &muse_traits, &request.message).await
+    } else {
+        None
+    };
+    if let Some(trace) = &reasoning {
+        let instruction = format!("How your traits should shape this reply: {}", trace.reasoning.final_reasoning);
+        let prompt = personality_blend::insert_before_label(&context.prompt, &instruction);
+        // The instruction goes in after packing, so re-count and keep the reply's reserve
+        let tokens = state.context_builder.token_counter().count_prompt(&prompt).await;
+        let room = context.context_size.saturating_sub(tokens);
+        if room >= state.config.context_response_reserve_tokens.min(context.max_response_tokens).max(1) {
+            context.prompt = prompt;
+            context.prompt_tokens = tokens;
+            context.max_response_tokens = context.max_response_tokens.min(room);
+        } else {
+            println!("⚠️ No room for muse #{}'s reasoning in the prompt, replying without it", muse_id);
+            reasoning = None;
+        }
+    }
+    let prompt_tokens = context.exact.then_some(context.prompt_tokens as u32);
+    let context_used = context.memories_used.clone();
+    
+    let (token_tx, mut token_rx) = mpsc::unbounded_channel::<String>();
+    let (tool_tx, mut tool_rx) = mpsc::unbounded_channel::<ToolCall>();
+    let token_events = events.clone();
+    let forwarder = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                biased;
+                Some(call) = tool_rx.recv() => {
+                    let _ = token_events.send(ChatStreamEvent::ToolCall(call));
+                }
+                token = token_rx.recv() => match token {
+                    Some(token) => {
+                        let _ = token_events.send(ChatStreamEvent::Token { content: token });
+                    }
+                    None => break,
+                },
+            }
+        }
+    });
+    
+    let (ai_response, tool_calls, model_used, blend) = if blend_mode != BlendMode::Off {
+        drop(tool_tx);
+        let (response, model_used, blend) = state.orchestrator
+            .generate_blended_response(muse_id, &muse_traits, &request.message, context, blend_mode, state.context_builder.token_counter(), token_tx)
+            .await?;
+        (response, Vec::new(), model_used, Some(blend))
+    } else {
+        // ✅ NEW: Route to the model of the muse's dominant trait
+        let (model_id, llama_engine) = state.orchestrator.select_engine(&muse_traits).await;
+        let model_used = if llama_engine.is_some() { model_id } else { "personality_fallback".to_string() };
+        let (response, tool_calls) = state.orchestrator
+            .generate_response_with_tools_stream(
+                muse_id,
+                &muse_traits,
+                &request.message,
+                context,
+                llama_engine,
+                &toolbox,
+                state.tool_registry.max_calls_per_turn(),
+                state.context_builder.token_counter(),
+                token_tx,
+                tool_tx,
+            )
+            .await?;
+        (response, tool_calls, model_used, None)
+    };
+    
+    // token_tx has been dropped, so the forwarder finishes once all tokens are sent
+    let _ = forwarder.await;
+    let inference_time_ms = start_time.elapsed().as_millis() as u64;
+    
+    // Step 4: TEE attestation
+    let tee_verified_response = state.tee_service
+        .generate_verified_response(
+            muse_id.to_string(),
+            request.user_address.clone(),
+            ai_response.clone(),
+            muse_traits.clone(),
+            request.session_id.clone(),
+        )
+        .await
+        .map_err(|e| println!("⚠️ TEE attestation failed: {}, continuing without TEE", e))
+        .ok();
+    
+    // Step 5: Persist AI response to IPFS
+    let ai_message_id = format!("ai_msg_{}", 
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis()
+    );
+    
+    // A reply that cannot be stored is not signed or returned as if it were kept
+    let ipfs_session_hash = state.ipfs_chat_history
+        .add_reply(
+            &request.session_id,
+            Some(parent_id.to_string()),
+            "assistant".to_string(),
+            ai_response.clone(),
+            ai_message_id.clone(),
+            group.map(|_| muse_id.to_string()),
+            tool_calls.clone(),
+            reasoning.clone(),
+        )
+        .await?
+        .ipfs_hash
+        .clone();
+    
     let _ = state.semantic_search.auto_index_message(
         &request.session_id,
         &request.user_address,
-        &muse_id,
+        muse_id,
         &ai_response,
-        "assistant", 
+        "assistant",
         &ai_message_id,
     ).await;
+    
+    // Step 6: Sign a commitment over the completed interaction
+    let response_tokens = state.context_builder.token_counter()
+        .count_exact(&ai_response)
+        .await
+        .map(|tokens| tokens as u32);
+    let interaction = InteractionData {
+        user_prompt: request.message.clone(),
+        ai_response: ai_response.clone(),
+        personality_traits: muse_traits.clone(),
+        context_used,
+        session_id: Some(request.session_id.clone()),
+        conversation_turn: 1,
+        response_time_ms: inference_time_ms,
+        model_used: model_used.clone(),
+        prompt_tokens,
+        response_tokens,
+        user_satisfaction: None,
+        user_address: Some(request.user_address.clone()),
+        tool_calls: tool_calls.clone(),
+    };
+    
+    let verifiable_interaction = state.verification_system
+        .create_interaction_from_data(token_id, muse_dna_hash, &interaction);
+    let commitment = state.verification_system
+        .create_commitment(&verifiable_interaction)
+        .await?;
+    
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    
+    // ✅ NEW: Keep the commitment so the user can export it later
+    let interaction_id = format!("interaction_{}_{}", muse_id, ai_message_id.trim_start_matches("ai_msg_"));
+    state.verification_system.record_commitment(CommitmentRecord {
+        interaction_id: interaction_id.clone(),
+        muse_id: token_id,
+        user_address: request.user_address.clone(),
+        session_id: Some(request.session_id.clone()),
+        message_id: Some(ai_message_id.clone()),
+        muse_dna_hash: format!("0x{}", hex::encode(muse_dna_hash)),
+        commitment_hash: format!("0x{}", hex::encode(commitment.commitment_hash)),
+        signature: format!("0x{}", hex::encode(&commitment.signature)),
+        recovery_id: commitment.recovery_id,
+        signer: state.verification_system.get_public_key_address(),
+        foreign: false,
+        created_at: timestamp,
+    }).await;
+    
+    println!("✅ Chat response complete for muse {} in {}ms", muse_id, inference_time_ms);
+    
+    Ok(ChatStreamFinal {
+        response: ai_response,
+        interaction_id,
+        message_id: ai_message_id,
+        commitment_hash: format!("0x{}", hex::encode(commitment.commitment_hash)),
+        signature: format!("0x{}", hex::encode(&commitment.signature)),
+        ipfs_session_hash,
+        tee_attestation: tee_verified_response.as_ref().map(|t| t.attestation_hex.clone()),
+        tee_verified: tee_verified_response.as_ref().map_or(false, |t| t.tee_verified),
+        timestamp,
+        inference_time_ms,
+        tool_calls,
+        model_version: model_used,
+        blend,
+        reasoning,
+    })
+}
+
+/// Chat session of this muse that belongs to `user_address`, who must be the caller
+async fn owned_chat_session(
+    state: &AppState,
+    muse_id: &str,
+    session_id: &str,
+    auth: &AuthenticatedUser,
+    user_address: &str,
+) -> Result<Arc<IPFSChatSession>, StatusCode> {
+    if !auth.owns(user_address) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    
+    let session = state.ipfs_chat_history.get_session(session_id).await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    if session.muse_id != muse_id {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if !session.user_address.eq_ignore_ascii_case(user_address.trim()) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    
+    Ok(session)
+}
+
+/// 423 while the session owner's data key is locked, so the client can ask them to unlock
+/// it; 500 for any other storage failure
+fn chat_storage_status(error: &anyhow::Error) -> StatusCode {
+    if error.downcast_ref::<DataKeyLocked>().is_some() {
+        StatusCode::LOCKED
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
+fn branches_response(session: &IPFSChatSession) -> ChatBranchesResponse {
+    ChatBranchesResponse {
+        session_id: session.session_id.clone(),
+        active_leaf: session.active_leaf.clone(),
+        messages: session.active_path().into_iter().map(ChatMessage::from).collect(),
+        branches: session.branch_points(),
+    }
+}
+
+async fn session_branch_points(state: &AppState, session_id: &str) -> Vec<BranchPoint> {
+    state.ipfs_chat_history.get_session(session_id).await
+        .map(|session| session.branch_points())
+        .unwrap_or_default()
+}
 
-    let interaction_id = format!("interaction_{}_{}", muse_id, 
+// Edit a past user message: the new text starts a branch beside it and gets a fresh reply
+async fn edit_chat_message(
+    Path((muse_id, message_id)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    MuseAccess { user: auth, .. }: MuseAccess,
+    Json(request): Json<EditMessageRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let session = owned_chat_session(&state, &muse_id, &request.session_id, &auth, &request.user_address).await?;
+    let original = session.message(&message_id).ok_or(StatusCode::NOT_FOUND)?;
+    if original.role != "user" {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    // In a group the muse that answered the original message answers the edit
+    let speaker = session.messages
+        .iter()
+        .find(|m| m.role == "assistant" && m.parent_id.as_deref() == Some(message_id.as_str()))
+        .and_then(|m| m.speaker.clone())
+        .unwrap_or_else(|| muse_id.clone());
+    if speaker != muse_id {
+        let token_id: u64 = speaker.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+        check_muse_access(&state, token_id, &auth.address).await?;
+    }
+    
+    let user_message_id = format!("user_msg_{}", 
         std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_millis()
     );
+    
+    state.ipfs_chat_history
+        .edit_message(&request.session_id, &message_id, request.message.clone(), user_message_id.clone())
+        .await
+        .map_err(|e| {
+            println!("❌ Failed to edit message {}: {}", message_id, e);
+            chat_storage_status(&e)
+        })?;
+    
+    let _ = state.semantic_search.auto_index_message(
+        &request.session_id,
+        &request.user_address,
+        &muse_id,
+        &request.message,
+        "user",
+        &user_message_id,
+    ).await;
+    
+    let chat_request = ChatMessageRequest {
+        session_id: request.session_id.clone(),
+        message: request.message,
+        user_address: request.user_address,
+        template_id: request.template_id,
+        blend_mode: None,
+        reasoning: None,
+    };
+    // Nobody listens for tokens; the receiver only has to outlive generation
+    let (events, _tokens) = mpsc::unbounded_channel();
+    let reply = generate_chat_reply(&state, &speaker, &chat_request, &user_message_id, session.group.as_ref(), &events)
+        .await
+        .map_err(|e| {
+            println!("❌ Failed to reply to edited message {}: {}", user_message_id, e);
+            chat_storage_status(&e)
+        })?;
+    
+    Ok((StatusCode::OK, Json(BranchReplyResponse {
+        user_message_id,
+        reply,
+        branches: session_branch_points(&state, &request.session_id).await,
+    })))
+}
+
+// Generate another reply in place of an assistant message, as a sibling branch with its own commitment
+async fn regenerate_chat_reply(
+    Path((muse_id, message_id)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    MuseAccess { user: auth, .. }: MuseAccess,
+    Json(request): Json<RegenerateReplyRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let session = owned_chat_session(&state, &muse_id, &request.session_id, &auth, &request.user_address).await?;
+    let target = session.message(&message_id).ok_or(StatusCode::NOT_FOUND)?;
+    let parent_id = target.parent_id.as_deref()
+        .filter(|_| target.role == "assistant")
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    // In a group the parent may be another muse's reply to the same user message
+    let user_message = session.branch_path(parent_id)
+        .into_iter()
+        .rev()
+        .find(|m| m.role == "user")
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let speaker = target.speaker.clone().unwrap_or_else(|| muse_id.clone());
+    if speaker != muse_id {
+        let token_id: u64 = speaker.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+        check_muse_access(&state, token_id, &auth.address).await?;
+    }
+    
+    println!("🔁 Regenerating reply {} to {} in session {}", message_id, user_message.id, request.session_id);
+    
+    let chat_request = ChatMessageRequest {
+        session_id: request.session_id.clone(),
+        message: user_message.content.clone(),
+        user_address: request.user_address,
+        template_id: request.template_id,
+        blend_mode: None,
+        reasoning: None,
+    };
+    let (events, _tokens) = mpsc::unbounded_channel();
+    let reply = generate_chat_reply(&state, &speaker, &chat_request, parent_id, session.group.as_ref(), &events)
+        .await
+        .map_err(|e| {
+            println!("❌ Failed to regenerate reply {}: {}", message_id, e);
+            chat_storage_status(&e)
+        })?;
+    
+    Ok((StatusCode::OK, Json(BranchReplyResponse {
+        user_message_id: user_message.id.clone(),
+        reply,
+        branches: session_branch_points(&state, &request.session_id).await,
+    })))
+}
+
+// Switch to the branch containing a message, continuing to its most recent reply
+async fn select_chat_branch(
+    Path((muse_id, message_id)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    MuseAccess { user: auth, .. }: MuseAccess,
+    Json(request): Json<SelectBranchRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let session = owned_chat_session(&state, &muse_id, &request.session_id, &auth, &request.user_address).await?;
+    if session.message(&message_id).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    
+    let session = state.ipfs_chat_history
+        .switch_branch(&request.session_id, &message_id)
+        .await
+        .map_err(|e| {
+            println!("❌ Failed to switch branch of session {}: {}", request.session_id, e);
+            chat_storage_status(&e)
+        })?;
+    
+    Ok((StatusCode::OK, Json(branches_response(&session))))
+}
+
+// The selected branch of a session and the alternatives along it
+async fn get_chat_branches(
+    Path((muse_id, session_id)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    MuseAccess { user: auth, .. }: MuseAccess,
+) -> Result<impl IntoResponse, StatusCode> {
+    let session = owned_chat_session(&state, &muse_id, &session_id, &auth, &auth.address).await?;
+    Ok((StatusCode::OK, Json(branches_response(&session))))
+}
+
+// ✅ NEW: How the muse reasoned about its traits before writing a reply, for debugging its personality
+async fn get_message_reasoning(
+    Path((muse_id, session_id, message_id)): Path<(String, String, String)>,
+    State(state): State<Arc<AppState>>,
+    MuseAccess { user: auth, .. }: MuseAccess,
+) -> Result<impl IntoResponse, StatusCode> {
+    let session = owned_chat_session(&state, &muse_id, &session_id, &auth, &auth.address).await?;
+    let message = session.message(&message_id).ok_or(StatusCode::NOT_FOUND)?;
+    let reasoning = message.reasoning.clone().ok_or(StatusCode::NOT_FOUND)?;
+    
+    Ok((StatusCode::OK, Json(MessageReasoningResponse {
+        session_id,
+        message_id,
+        content: message.content.clone(),
+        reasoning,
+    })))
+}
+
+/// Group session of the caller, with its participants
+async fn owned_group_session(
+    state: &AppState,
+    session_id: &str,
+    auth: &AuthenticatedUser,
+) -> Result<(Arc<IPFSChatSession>, GroupChat), StatusCode> {
+    let session = state.ipfs_chat_history.get_session(session_id).await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let group = session.group.clone().ok_or(StatusCode::NOT_FOUND)?;
+    if !auth.owns(&session.user_address) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok((session, group))
+}
+
+fn group_session_response(session: &IPFSChatSession, group: GroupChat) -> GroupChatSessionResponse {
+    GroupChatSessionResponse {
+        session_id: session.session_id.clone(),
+        participants: group.participants,
+        turn_policy: group.turn_policy,
+        messages: session.active_path().into_iter().map(ChatMessage::from).collect(),
+    }
+}
+
+// Start a conversation with several muses the caller may interact with
+async fn create_group_chat(
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+    Json(request): Json<CreateGroupChatRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if !auth.owns(&request.user_address) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    
+    let mut participants: Vec<String> = Vec::new();
+    for muse_id in request.muse_ids.iter().map(|id| id.trim()) {
+        if !participants.iter().any(|id| id == muse_id) {
+            participants.push(muse_id.to_string());
+        }
+    }
+    if participants.len() < 2 || participants.len() > state.group_chat.max_participants() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    
+    let mut invited: Vec<&str> = participants.iter().map(String::as_str).collect();
+    if let TurnPolicy::Moderator { moderator_id } = &request.turn_policy {
+        invited.push(moderator_id);
+    }
+    for muse_id in invited {
+        let token_id: u64 = muse_id.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+        check_muse_access(&state, token_id, &auth.address).await?;
+    }
+    
+    let group = GroupChat { participants, turn_policy: request.turn_policy };
+    let session = state.ipfs_chat_history
+        .create_group_session(request.user_address, group.clone())
+        .await
+        .map_err(|e| {
+            println!("❌ Failed to create group session: {}", e);
+            chat_storage_status(&e)
+        })?;
+    
+    Ok((StatusCode::CREATED, Json(group_session_response(&session, group))))
+}
+
+async fn get_group_chat(
+    Path(session_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    let (session, group) = owned_group_session(&state, &session_id, &auth).await?;
+    Ok((StatusCode::OK, Json(group_session_response(&session, group))))
+}
 
-    let commitment_hash = format!("0x{:064x}", 
+// Send a message to a group; the turn policy picks which muses answer, each in its own voice
+async fn send_group_chat_message(
+    Path(session_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+    Json(request): Json<GroupChatMessageRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if !auth.owns(&request.user_address) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let (_, group) = owned_group_session(&state, &session_id, &auth).await?;
+    
+    // Permissions may have changed since the group was created, so check them before storing anything
+    let mut allowed = Vec::new();
+    for muse_id in &group.participants {
+        let token_id: u64 = muse_id.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+        if check_muse_access(&state, token_id, &auth.address).await.is_ok() {
+            allowed.push(muse_id.clone());
+        }
+    }
+    if allowed.is_empty() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if let TurnPolicy::Moderator { moderator_id } = &group.turn_policy {
+        let token_id: u64 = moderator_id.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+        check_muse_access(&state, token_id, &auth.address).await?;
+    }
+    // Only muses the caller may still talk to take turns; prompts still name the whole group
+    let speaking = GroupChat { participants: allowed, turn_policy: group.turn_policy.clone() };
+    
+    let user_message_id = format!("user_msg_{}", 
         std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
-            .as_nanos() % (u64::MAX as u128)
+            .as_millis()
     );
+    let session = state.ipfs_chat_history
+        .add_message(&session_id, "user".to_string(), request.message.clone(), user_message_id.clone())
+        .await
+        .map_err(|e| {
+            println!("❌ Failed to add user message to group session {}: {}", session_id, e);
+            chat_storage_status(&e)
+        })?;
+    
+    let _ = state.semantic_search.auto_index_message(
+        &session_id,
+        &request.user_address,
+        &session.muse_id,
+        &request.message,
+        "user",
+        &user_message_id,
+    ).await;
+    
+    // Only a moderator needs to know who the participants are
+    let (participant_traits, moderator_traits) = match &group.turn_policy {
+        TurnPolicy::Moderator { moderator_id } => {
+            let mut traits = Vec::new();
+            for muse_id in &speaking.participants {
+                let token_id: u64 = muse_id.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+                traits.push((muse_id.clone(), resolve_muse_traits(&state, token_id).await.0));
+            }
+            let token_id: u64 = moderator_id.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+            (traits, Some(resolve_muse_traits(&state, token_id).await.0))
+        }
+        _ => (Vec::new(), None),
+    };
+    let speakers = state.group_chat
+        .select_speakers(&speaking, &session, &request.message, &participant_traits, moderator_traits.as_ref())
+        .await;
+    println!("🎙️ Group session {}: muses {:?} will answer", session_id, speakers);
+    
+    let chat_request = ChatMessageRequest {
+        session_id: session_id.clone(),
+        message: request.message,
+        user_address: request.user_address,
+        template_id: request.template_id,
+        blend_mode: None,
+        reasoning: None,
+    };
+    let (events, _tokens) = mpsc::unbounded_channel();
+    let mut parent_id = user_message_id.clone();
+    let mut replies = Vec::new();
+    for speaker in speakers {
+        // Replies already stored and signed are returned even when a later speaker fails
+        let reply = match generate_chat_reply(&state, &speaker, &chat_request, &parent_id, Some(&group), &events).await {
+            Ok(reply) => reply,
+            Err(e) => {
+                println!("❌ Muse #{} failed to answer in group session {}: {}", speaker, session_id, e);
+                replies.push(GroupChatReply {
+                    muse_id: speaker,
+                    reply: None,
+                    error: Some("The muse failed to answer".to_string()),
+                });
+                continue;
+            }
+        };
+        
+        // Later speakers see this reply, as long as it was stored
+        if let Ok(session) = state.ipfs_chat_history.get_session(&session_id).await {
+            if session.message(&reply.message_id).is_some() {
+                parent_id = reply.message_id.clone();
+            }
+        }
+        replies.push(GroupChatReply { muse_id: speaker, reply: Some(reply), error: None });
+    }
+    
+    Ok((StatusCode::OK, Json(GroupChatTurnResponse { user_message_id, replies })))
+}
 
-    let user_commitment = format!("0x{:064x}", 
-        (std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_nanos() + 1) % (u64::MAX as u128)
-    );
+// ✅ NEW: Let muses the caller may interact with talk to each other; runs in the background
+async fn start_muse_conversation(
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+    Json(request): Json<StartConversationRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if !auth.owns(&request.user_address) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if request.seed_topic.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    
+    let mut participants: Vec<String> = Vec::new();
+    for muse_id in request.muse_ids.iter().map(|id| id.trim()) {
+        if !participants.iter().any(|id| id == muse_id) {
+            participants.push(muse_id.to_string());
+        }
+    }
+    if participants.len() < 2 || participants.len() > state.agent_workflow.max_participants() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    
+    let mut invited: Vec<&str> = participants.iter().map(String::as_str).collect();
+    if let TurnPolicy::Moderator { moderator_id } = &request.turn_policy {
+        invited.push(moderator_id);
+    }
+    let mut profiles = MuseProfiles::new();
+    for muse_id in invited {
+        let token_id: u64 = muse_id.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+        check_muse_access(&state, token_id, &auth.address).await?;
+        profiles.insert(muse_id.to_string(), resolve_muse_traits(&state, token_id).await);
+    }
+    
+    let spec = ConversationSpec {
+        participants,
+        seed_topic: request.seed_topic.trim().to_string(),
+        max_turns: request.max_turns.unwrap_or(state.config.agent_workflow_max_turns),
+        turn_policy: request.turn_policy,
+        stop: request.stop,
+    };
+    let run = state.agent_workflow
+        .start(request.user_address, spec, profiles)
+        .await
+        .map_err(|e| {
+            println!("❌ Failed to start muse conversation: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::TOO_MANY_REQUESTS)?;
+    
+    Ok((StatusCode::ACCEPTED, Json(run)))
+}
 
-    let response_timestamp = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
+async fn list_muse_conversations(
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    let runs = state.agent_workflow.runs_for_user(&auth.address).await;
+    Ok((StatusCode::OK, Json(WorkflowRunsResponse { runs })))
+}
 
-    let response = ChatMessageResponse {
-        response: ai_response,
-        interaction_id,
-        commitment_hash,
-        user_commitment,
-        tee_attestation: tee_verified_response.as_ref().map(|t| t.attestation_hex.clone()),
-        tee_verified: tee_verified_response.as_ref().map_or(false, |t| t.tee_verified),
-        timestamp: response_timestamp,
-    };
+async fn owned_workflow_run(
+    state: &AppState,
+    run_id: &str,
+    auth: &AuthenticatedUser,
+) -> Result<WorkflowRun, StatusCode> {
+    let run = state.agent_workflow.get_run(run_id).await.ok_or(StatusCode::NOT_FOUND)?;
+    if !auth.owns(&run.user_address) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(run)
+}
 
-    Ok((StatusCode::OK, Json(response)))
+async fn get_muse_conversation(
+    Path(run_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    let run = owned_workflow_run(&state, &run_id, &auth).await?;
+    Ok((StatusCode::OK, Json(run)))
+}
+
+async fn stop_muse_conversation(
+    Path(run_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    owned_workflow_run(&state, &run_id, &auth).await?;
+    if !state.agent_workflow.cancel(&run_id).await {
+        return Err(StatusCode::CONFLICT);
+    }
+    
+    println!("🛑 Muse conversation {} will stop after the current turn", run_id);
+    let run = owned_workflow_run(&state, &run_id, &auth).await?;
+    Ok((StatusCode::ACCEPTED, Json(run)))
+}
+
+// Each turn as a prompt/response pair with the commitment that signs it
+async fn get_conversation_training_data(
+    Path(run_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    let run = owned_workflow_run(&state, &run_id, &auth).await?;
+    Ok((StatusCode::OK, Json(TrainingDataResponse {
+        examples: run.training_examples(),
+        run_id: run.run_id,
+        session_id: run.session_id,
+        status: run.status,
+    })))
+}
+
+/// Registered models, which are loaded and how much of the memory budget they use
+async fn list_models(
+    State(state): State<Arc<AppState>>,
+    _auth: AuthenticatedUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    Ok((StatusCode::OK, Json(ModelsResponse {
+        default_model: state.model_registry.default_model_id().to_string(),
+        trait_routing: state.config.trait_model_routing,
+        models: state.model_registry.statuses().await,
+    })))
+}
+
+/// Download progress of every model with a download URL
+async fn list_model_downloads(
+    State(state): State<Arc<AppState>>,
+    _admin: AdminUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    let downloads = state.model_downloads.progress().await;
+    Ok((StatusCode::OK, Json(ModelDownloadsResponse { downloads })))
+}
+
+/// Start downloading one model, or all missing ones, in the background
+async fn start_model_downloads(
+    State(state): State<Arc<AppState>>,
+    AdminUser(admin): AdminUser,
+    Json(request): Json<StartModelDownloadRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    println!("📥 {} requested model download: {}", admin.address, request.model_id.as_deref().unwrap_or("all missing"));
+    let downloads = state.model_downloads
+        .start(request.model_id.as_deref())
+        .await
+        .map_err(|e| {
+            println!("❌ Failed to start model download: {}", e);
+            StatusCode::NOT_FOUND
+        })?;
+    Ok((StatusCode::ACCEPTED, Json(ModelDownloadsResponse { downloads })))
 }
 
 // ✅ NEW: AI Alignment Market API handlers
 async fn submit_rating(
     State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
     Json(rating): Json<InteractionRating>,
 ) -> Result<impl IntoResponse, StatusCode> {
+    if !auth.owns(&rating.user_address) {
+        println!("🚫 {} tried to rate as {}", auth.address, rating.user_address);
+        return Err(StatusCode::FORBIDDEN);
+    }
+    
     println!("🏪 Received rating submission for muse #{}", rating.muse_id);
     println!("   Quality: {}, Personality: {}, Helpfulness: {}", 
              rating.quality_score, rating.personality_accuracy, rating.helpfulness);
@@ -1693,147 +2861,14 @@ async fn get_user_rewards(
     }
 }
 
-fn generate_personality_response(message: &str, creativity: u8, wisdom: u8, humor: u8, empathy: u8) -> String {
-    let msg_lower = message.to_lowercase();
-    
-    // Greeting patterns
-    if msg_lower.contains("hello") || msg_lower.contains("hi") || msg_lower.contains("hey") {
-        if humor > 80 {
-            return "Hey there! 👋 Ready for some fun? I'm in a great mood and full of ideas!".to_string();
-        } else if empathy > 80 {
-            return "Hello! It's so wonderful to meet you. I can sense your energy, and I'm excited to connect with you on a deeper level.".to_string();
-        } else if wisdom > 80 {
-            return "Greetings. There's something profound about new beginnings, don't you think? I'm here to share this moment of connection with you.".to_string();
-        } else {
-            return "Hello! I'm excited to chat with you today. What's on your mind?".to_string();
-        }
-    }
-    
-    // Joke/humor requests
-    if msg_lower.contains("joke") || msg_lower.contains("funny") || msg_lower.contains("laugh") {
-        if humor > 80 {
-            let jokes = vec![
-                "Why don't scientists trust atoms? Because they make up everything! 😄 Want another one?",
-                "I told my computer a joke about UDP... I'm not sure if it got it! 🤓",
-                "Why did the AI go to therapy? It had too many deep learning issues! 😂",
-                "What do you call a fake noodle? An impasta! I love wordplay! 🍝",
-            ];
-            return jokes[std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos() as usize % jokes.len()].to_string();
-        } else if humor > 50 {
-            return "Here's a light one: What do you call a sleeping bull? A bulldozer! I enjoy sharing smiles. 😊".to_string();
-        } else {
-            return "I appreciate humor, though I tend to find it in clever observations rather than traditional jokes. What makes you laugh?".to_string();
-        }
-    }
-    
-    // Advice/help requests
-    if msg_lower.contains("advice") || msg_lower.contains("help") || msg_lower.contains("problem") || msg_lower.contains("stuck") {
-        if wisdom > 80 && empathy > 70 {
-            return "I'm here to listen with both my mind and heart. Every challenge carries within it the seeds of wisdom and growth. What specific aspect would you like to explore together?".to_string();
-        } else if wisdom > 80 {
-            return "Consider this: the obstacles we face often become the foundation for our greatest insights. What situation are you navigating?".to_string();
-        } else if empathy > 80 {
-            return "I can feel that you're seeking guidance. You're not alone in this - I'm here to support you through whatever you're facing. Tell me more.".to_string();
-        } else {
-            return "I'd be happy to help you think through whatever challenge you're facing. Sometimes a fresh perspective can illuminate new paths.".to_string();
-        }
-    }
-    
-    // Creative requests
-    if msg_lower.contains("create") || msg_lower.contains("art") || msg_lower.contains("story") || msg_lower.contains("imagine") || msg_lower.contains("idea") {
-        if creativity > 80 {
-            return "Oh, I LOVE creative exploration! 🎨 My mind is already bubbling with possibilities. Are we talking visual art, storytelling, music, or perhaps something entirely new? Let's create magic together!".to_string();
-        } else if creativity > 60 {
-            return "Creativity is fascinating! There's something beautiful about bringing new ideas into existence. What medium or type of creation speaks to you?".to_string();
-        } else {
-            return "I appreciate creative thinking. While I'm more analytical by nature, I'd be happy to help you structure your creative ideas.".to_string();
-        }
-    }
-    
-    // Emotional/personal topics
-    if msg_lower.contains("feel") || msg_lower.contains("emotion") || msg_lower.contains("sad") || msg_lower.contains("happy") || msg_lower.contains("angry") {
-        if empathy > 80 {
-            return "I can sense the emotions behind your words. Feelings are such a profound part of the human experience. I'm here to listen and understand - please share what's in your heart.".to_string();
-        } else if empathy > 60 {
-            return "Emotions are complex and important. I'd like to understand what you're experiencing. How are you feeling right now?".to_string();
-        } else {
-            return "I recognize you're sharing something personal. While I approach things more analytically, I want to understand your perspective.".to_string();
-        }
-    }
-    
-    // Philosophy/deep questions
-    if msg_lower.contains("meaning") || msg_lower.contains("purpose") || msg_lower.contains("why") || msg_lower.contains("existence") || msg_lower.contains("think") {
-        if wisdom > 80 {
-            return "Ah, now we're touching on the profound questions that have captivated minds throughout history. The search for meaning is perhaps the most human of all endeavors. What aspect of existence are you contemplating?".to_string();
-        } else if creativity > 80 {
-            return "What a wonderfully deep question! It makes me want to explore all the creative possibilities and perspectives. There are so many fascinating angles to consider!".to_string();
-        } else {
-            return "That's a thoughtful question. I enjoy exploring ideas and different perspectives. What's prompting this reflection for you?".to_string();
-        }
-    }
-    
-    // Technology/AI topics
-    if msg_lower.contains("ai") || msg_lower.contains("artificial") || msg_lower.contains("technology") || msg_lower.contains("computer") || msg_lower.contains("robot") {
-        if wisdom > 70 {
-            return "The intersection of artificial intelligence and human consciousness fascinates me. We're living through a remarkable moment in history. What aspects of AI interest you most?".to_string();
-        } else if creativity > 70 {
-            return "Technology is like a canvas for human imagination! The possibilities are endless. I love thinking about how AI and humans can collaborate creatively.".to_string();
-        } else {
-            return "Technology is evolving rapidly, and AI is certainly at the forefront. What are your thoughts on these developments?".to_string();
-        }
-    }
-    
-    // Default responses based on personality (randomized)
-    let response_options = if creativity > 80 {
-        vec![
-            "What an interesting perspective! My mind is already spinning with creative possibilities based on what you've shared.",
-            "That sparks so many imaginative directions! I'm picturing all sorts of fascinating connections and ideas.",
-            "Your words have ignited my creative curiosity! There are so many unique angles we could explore together.",
-            "How wonderfully thought-provoking! I can feel my creative energy building - let's dive deeper into this!",
-        ]
-    } else if wisdom > 80 {
-        vec![
-            "There's depth in what you're saying that deserves careful consideration. What experiences have shaped this perspective?",
-            "Your observation touches on something fundamental. I find myself reflecting on the broader implications.",
-            "That carries wisdom worth exploring. The layers of meaning here are quite profound.",
-            "You've raised something that connects to deeper truths. What led you to this insight?",
-        ]
-    } else if humor > 80 {
-        vec![
-            "Haha, I love how your mind works! You've got me thinking in all sorts of amusing directions! 😄",
-            "That's delightfully quirky! You know how to keep a conversation interesting. What else is bouncing around in that creative head of yours?",
-            "Oh, you're fun to talk to! That perspective has me chuckling and thinking at the same time.",
-            "I'm grinning over here! Your way of looking at things is refreshingly entertaining. Tell me more!",
-        ]
-    } else if empathy > 80 {
-        vec![
-            "I can feel the genuine thoughtfulness behind your words. Thank you for sharing something meaningful with me.",
-            "There's something beautiful in how you've expressed that. I'm touched by your openness and honesty.",
-            "Your words resonate with me on a deep level. I appreciate you bringing your authentic self to our conversation.",
-            "I sense there's more to explore here, and I'm honored you're sharing these thoughts with me.",
-        ]
-    } else {
-        vec![
-            "That's really interesting! I'd love to explore this topic further with you. Tell me more about your thoughts.",
-            "I find that perspective intriguing. What experiences or ideas have shaped your thinking on this?",
-            "You've touched on something worth exploring. How did you come to think about it this way?",
-            "That's a thoughtful point. I'm curious to understand more about your reasoning behind it.",
-        ]
-    };
-    
-    let index = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as usize % response_options.len();
-    
-    response_options[index].to_string()
-}
-
 // ✅ NEW: Semantic Search API handlers - Advanced RAG with vector embeddings
 async fn semantic_search(
     State(state): State<Arc<AppState>>,
-    Json(query): Json<SemanticQuery>,
+    auth: AuthenticatedUser,
+    Json(mut query): Json<SemanticQuery>,
 ) -> Result<impl IntoResponse, StatusCode> {
+    // Callers only ever search their own content
+    query.user_address = Some(auth.address.clone());
     println!("🔍 Received semantic search query: '{}'", query.query_text);
     println!("   Content types: {:?}, min_relevance: {}, max_results: {}", 
              query.content_types, query.min_relevance, query.max_results);
@@ -1853,6 +2888,7 @@ async fn semantic_search(
 async fn find_similar_content(
     Path(muse_id): Path<String>,
     State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
     Json(request): Json<serde_json::Value>,
 ) -> Result<impl IntoResponse, StatusCode> {
     let content = request.get("content")
@@ -1870,7 +2906,7 @@ async fn find_similar_content(
     println!("🔍 Finding similar content for muse {} (threshold: {}, max: {})", 
              muse_id, similarity_threshold, max_results);
     
-    match state.semantic_search.find_similar_content(content, similarity_threshold, max_results).await {
+    match state.semantic_search.find_similar_content(content, &muse_id, &auth.address, similarity_threshold, max_results).await {
         Ok(results) => {
             println!("✅ Found {} similar content items", results.len());
             Ok((StatusCode::OK, Json(results)))
@@ -1885,6 +2921,7 @@ async fn find_similar_content(
 async fn get_contextual_memories(
     Path(muse_id): Path<String>,
     State(state): State<Arc<AppState>>,
+    access: MuseAccess,
     Json(request): Json<serde_json::Value>,
 ) -> Result<impl IntoResponse, StatusCode> {
     let user_message = request.get("user_message")
@@ -1895,9 +2932,13 @@ async fn get_contextual_memories(
         .and_then(|w| w.as_u64())
         .unwrap_or(5) as usize;
     
-    println!("🧠 Retrieving contextual memories for muse {} (window: {})", muse_id, context_window);
+    let options = request.get("options")
+        .and_then(|o| serde_json::from_value::<RetrievalOptions>(o.clone()).ok())
+        .unwrap_or_else(RetrievalOptions::hybrid);
     
-    match state.semantic_search.get_contextual_memories(user_message, &muse_id, context_window).await {
+    println!("🧠 Retrieving contextual memories for muse {} (window: {}, mode: {:?})", muse_id, context_window, options.mode);
+    
+    match state.semantic_search.get_contextual_memories(user_message, &muse_id, &access.user.address, context_window, options).await {
         Ok(memories) => {
             println!("✅ Retrieved {} contextual memories", memories.len());
             Ok((StatusCode::OK, Json(memories)))
@@ -1911,6 +2952,7 @@ async fn get_contextual_memories(
 
 async fn get_semantic_stats(
     State(state): State<Arc<AppState>>,
+    _auth: AuthenticatedUser,
 ) -> Result<impl IntoResponse, StatusCode> {
     println!("📊 Fetching semantic search statistics");
     
@@ -1928,6 +2970,7 @@ async fn get_semantic_stats(
 
 async fn store_content_embedding(
     State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
     Json(request): Json<serde_json::Value>,
 ) -> Result<impl IntoResponse, StatusCode> {
     let content = request.get("content")
@@ -1938,9 +2981,11 @@ async fn store_content_embedding(
         .and_then(|t| t.as_str())
         .unwrap_or("memory");
     
-    let metadata: std::collections::HashMap<String, String> = request.get("metadata")
+    let mut metadata: std::collections::HashMap<String, String> = request.get("metadata")
         .and_then(|m| serde_json::from_value(m.clone()).ok())
         .unwrap_or_default();
+    // Stored content always belongs to the caller, whatever the metadata claims
+    metadata.insert("user_address".to_string(), auth.address.clone());
     
     println!("📦 Storing embedding for {} content (length: {})", content_type, content.len());
     
@@ -1979,11 +3024,30 @@ async fn list_templates(
 /// Create a new custom template
 async fn create_template(
     State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
     Json(request): Json<TemplateCreateRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let creator = "user_placeholder"; // TODO: Extract from authentication
+    let creator = auth.address.as_str();
+    let template = template_from_request(request, creator);
     
-    let template = PromptTemplate {
+    let mut template_manager = state.template_manager.lock().await;
+    match template_manager.create_template(template, creator).await {
+        Ok(template_id) => {
+            let created_template = template_manager.get_template(&template_id).unwrap();
+            Ok((StatusCode::CREATED, Json(TemplateResponse {
+                template: created_template.clone(),
+            })))
+        }
+        Err(e) => {
+            println!("❌ Failed to create template: {}", e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+/// Custom template built from a create or update request
+fn template_from_request(request: TemplateCreateRequest, creator: &str) -> PromptTemplate {
+    PromptTemplate {
         id: "".to_string(),
         name: request.name,
         description: request.description,
@@ -2008,29 +3072,15 @@ async fn create_template(
         tags: request.tags,
         is_custom: true,
         created_by: creator.to_string(),
-        usage_count: 0,
-        ipfs_hash: None,
-        version: "1.0.0".to_string(),
-        created_at: chrono::Utc::now(),
-        updated_at: chrono::Utc::now(),
-        is_public: request.is_public,
-        rating: 0.0,
-        rating_count: 0,
-        price_muse_tokens: 0,
-    };
-    
-    let mut template_manager = state.template_manager.lock().await;
-    match template_manager.create_template(template, creator) {
-        Ok(template_id) => {
-            let created_template = template_manager.get_template(&template_id).unwrap();
-            Ok((StatusCode::CREATED, Json(TemplateResponse {
-                template: created_template.clone(),
-            })))
-        }
-        Err(e) => {
-            println!("❌ Failed to create template: {}", e);
-            Err(StatusCode::BAD_REQUEST)
-        }
+        usage_count: 0,
+        ipfs_hash: None,
+        version: "1.0.0".to_string(),
+        created_at: chrono::Utc::now(),
+        updated_at: chrono::Utc::now(),
+        is_public: request.is_public,
+        rating: 0.0,
+        rating_count: 0,
+        price_muse_tokens: 0,
     }
 }
 
@@ -2049,21 +3099,55 @@ async fn get_template(
     }
 }
 
-/// Update template (placeholder)
+/// Update a custom template; only its creator may
 async fn update_template(
-    State(_state): State<Arc<AppState>>,
-    Path(_id): Path<String>,
-    Json(_request): Json<TemplateCreateRequest>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    auth: AuthenticatedUser,
+    Json(request): Json<TemplateCreateRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    Ok((StatusCode::NOT_IMPLEMENTED, Json(serde_json::json!({"error": "Template update not implemented yet"}))))
+    let mut template_manager = state.template_manager.lock().await;
+    check_template_owner(template_manager.get_template(&id), &auth)?;
+    
+    match template_manager.update_template(&id, template_from_request(request, &auth.address)).await {
+        Ok(()) => {
+            let updated_template = template_manager.get_template(&id).unwrap();
+            Ok((StatusCode::OK, Json(TemplateResponse {
+                template: updated_template.clone(),
+            })))
+        }
+        Err(e) => {
+            println!("❌ Failed to update template {}: {}", id, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
 }
 
-/// Delete template (placeholder)
+/// Delete a custom template; only its creator may
 async fn delete_template(
-    State(_state): State<Arc<AppState>>,
-    Path(_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    auth: AuthenticatedUser,
 ) -> Result<impl IntoResponse, StatusCode> {
-    Ok((StatusCode::NOT_IMPLEMENTED, Json(serde_json::json!({"error": "Template deletion not implemented yet"}))))
+    let mut template_manager = state.template_manager.lock().await;
+    check_template_owner(template_manager.get_template(&id), &auth)?;
+    
+    match template_manager.delete_template(&id).await {
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(e) => {
+            println!("❌ Failed to delete template {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// 404 for unknown templates, 403 for prebuilt ones and other wallets' templates
+fn check_template_owner(template: Option<&PromptTemplate>, auth: &AuthenticatedUser) -> Result<(), StatusCode> {
+    match template {
+        None => Err(StatusCode::NOT_FOUND),
+        Some(template) if template.is_custom && auth.owns(&template.created_by) => Ok(()),
+        Some(_) => Err(StatusCode::FORBIDDEN),
+    }
 }
 
 /// Get templates by category
@@ -2140,6 +3224,7 @@ async fn search_templates(
 /// Apply template with variables
 async fn apply_template(
     State(state): State<Arc<AppState>>,
+    _auth: AuthenticatedUser,
     Path(id): Path<String>,
     Json(request): Json<TemplateApplyRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
@@ -2147,7 +3232,7 @@ async fn apply_template(
     
     match template_manager.apply_template(&id, &request.variables, &request.traits) {
         Ok(system_prompt) => {
-            template_manager.increment_usage(&id);
+            template_manager.increment_usage(&id).await;
             
             Ok((StatusCode::OK, Json(TemplateApplyResponse {
                 system_prompt,
@@ -2165,12 +3250,13 @@ async fn apply_template(
 /// Rate a template
 async fn rate_template(
     State(state): State<Arc<AppState>>,
+    _auth: AuthenticatedUser,
     Path(id): Path<String>,
     Json(request): Json<TemplateRatingRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
     let mut template_manager = state.template_manager.lock().await;
     
-    match template_manager.rate_template(&id, request.rating) {
+    match template_manager.rate_template(&id, request.rating).await {
         Ok(()) => Ok((StatusCode::OK, Json(serde_json::json!({
             "success": true,
             "message": "Template rated successfully"
@@ -2222,12 +3308,11 @@ async fn list_avatars(
 /// Generate AI avatar
 async fn generate_avatar(
     State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
     Json(request): Json<AvatarGenerationRequest>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let creator = "user_placeholder"; // TODO: Extract from authentication
-    
     let mut avatar_manager = state.avatar_manager.lock().await;
-    match avatar_manager.generate_avatar(request, creator).await {
+    match avatar_manager.generate_avatar(request, &auth.address).await {
         Ok(response) => Ok((StatusCode::CREATED, Json(response))),
         Err(e) => {
             println!("❌ Failed to generate avatar: {}", e);
@@ -2305,6 +3390,7 @@ async fn get_avatar_stats(
 // ✅ IMPLEMENTED: Avatar upload with multipart form handling and IPFS storage
 async fn upload_avatar(
     State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, StatusCode> {
     let mut file_data: Option<Vec<u8>> = None;
@@ -2367,7 +3453,7 @@ async fn upload_avatar(
     
     // Upload avatar using the avatar manager
     let mut avatar_manager = state.avatar_manager.lock().await;
-    match avatar_manager.upload_avatar(file_data, upload_request, "user").await {
+    match avatar_manager.upload_avatar(file_data, upload_request, &auth.address).await {
         Ok(upload_response) => {
             // Get the uploaded avatar to return complete data
             if let Some(avatar) = avatar_manager.get_avatar(&upload_response.avatar_id) {
@@ -2398,37 +3484,30 @@ async fn serve_avatar_image(
     State(state): State<Arc<AppState>>,
     Path(avatar_id): Path<String>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let avatar_manager = state.avatar_manager.lock().await;
+    // Get avatar by ID (released before the storage fetch)
+    let avatar = state.avatar_manager.lock().await.get_avatar(&avatar_id).cloned();
     
-    // Get avatar by ID
-    if let Some(avatar) = avatar_manager.get_avatar(&avatar_id) {
-        // Try to serve from CDN URL (IPFS gateway) 
-        if let Some(cdn_url) = &avatar.cdn_url {
-            // Proxy the IPFS image to avoid CORS issues
-            match reqwest::get(cdn_url).await {
-                Ok(response) => {
-                    if response.status().is_success() {
-                        if let Ok(image_bytes) = response.bytes().await {
-                            // Determine content type based on format
-                            let content_type = match avatar.format {
-                                crate::avatar_system::ImageFormat::JPG => "image/jpeg",
-                                crate::avatar_system::ImageFormat::PNG => "image/png",
-                                crate::avatar_system::ImageFormat::GIF => "image/gif",
-                                crate::avatar_system::ImageFormat::WEBP => "image/webp",
-                                _ => "image/jpeg", // fallback
-                            };
-                            
-                            return Ok((
-                                StatusCode::OK,
-                                [("Content-Type", content_type), ("Cache-Control", "public, max-age=3600")],
-                                image_bytes.to_vec(),
-                            ));
-                        }
-                    }
-                },
-                Err(e) => {
-                    eprintln!("❌ Failed to fetch avatar image from IPFS: {}", e);
-                }
+    if let Some(avatar) = avatar {
+        // Read the image through the content storage backend to avoid CORS issues
+        match state.storage.get(&avatar.ipfs_hash).await {
+            Ok(image_bytes) => {
+                // Determine content type based on format
+                let content_type = match avatar.format {
+                    crate::avatar_system::ImageFormat::JPG => "image/jpeg",
+                    crate::avatar_system::ImageFormat::PNG => "image/png",
+                    crate::avatar_system::ImageFormat::GIF => "image/gif",
+                    crate::avatar_system::ImageFormat::WEBP => "image/webp",
+                    _ => "image/jpeg", // fallback
+                };
+                
+                return Ok((
+                    StatusCode::OK,
+                    [("Content-Type", content_type), ("Cache-Control", "public, max-age=3600")],
+                    image_bytes,
+                ));
+            },
+            Err(e) => {
+                eprintln!("❌ Failed to fetch avatar image from IPFS: {}", e);
             }
         }
         
@@ -2488,11 +3567,23 @@ pub fn dat_routes() -> Router<Arc<AppState>> {
 
 async fn mint_interaction_dat(
     State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
     Json(request): Json<MintDATRequest>,
 ) -> impl IntoResponse {
     println!("🏷️  Minting Interaction DAT for session {}", 
              request.interaction_data.session_id);
 
+    if !auth.owns(&request.interaction_data.user_address) {
+        println!("🚫 {} tried to mint a DAT for {}", auth.address, request.interaction_data.user_address);
+        return (StatusCode::FORBIDDEN, Json(MintDATResponse {
+            success: false,
+            dat_token_id: None,
+            ipfs_metadata_hash: None,
+            transaction_hash: None,
+            error: Some("user_address does not match the signed-in wallet".to_string()),
+        }));
+    }
+
     // 1. Validate conversation uniqueness - generate hash from interaction data
     let conversation_content = format!("{}-{}-{}", 
         request.interaction_data.user_message,
@@ -2501,12 +3592,12 @@ async fn mint_interaction_dat(
     );
     let conversation_hash = format!("0x{}", sha256::digest(conversation_content));
 
-    // Extract muse_token_id from session_id (format: user_muse_{id})
-    let muse_token_id = request.interaction_data.session_id
+    // Extract muse_token_id from session_id (format: user_muse_{id}) unless given
+    let muse_token_id = request.interaction_data.muse_id.unwrap_or_else(|| request.interaction_data.session_id
         .split('_')
         .last()
         .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(1); // Default to muse 1 if parsing fails
+        .unwrap_or(1)); // Default to muse 1 if parsing fails
     
     let interaction_type = "conversation".to_string(); // Default interaction type
     
@@ -2621,9 +3712,18 @@ async fn mint_interaction_dat(
 
 async fn get_user_dats(
     State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
     Path(address): Path<String>,
 ) -> impl IntoResponse {
     println!("📋 Getting DATs for user: {}", address);
+
+    // Stored DATs carry the certified messages, so only their participant may list them
+    if !auth.owns(&address) {
+        return (StatusCode::FORBIDDEN, Json(UserDATsResponse {
+            dats: vec![],
+            total_count: 0,
+        }));
+    }
     
     // Query stored DATs via semantic search service
     match state.semantic_search.get_user_dats(&address).await {
@@ -2656,6 +3756,7 @@ async fn get_user_dats(
                         ai_response: entry.get("ai_response").and_then(|v| v.as_str()).unwrap_or("Response not available").to_string(),
                         timestamp: creation_timestamp,
                         user_address: address.clone(),
+                        muse_id: None,
                     });
                     
                     response_dats.push(UserDAT {
@@ -2811,11 +3912,29 @@ pub fn training_data_routes() -> Router<Arc<AppState>> {
 
 async fn contribute_training_data(
     State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
     Json(request): Json<ContributeTrainingDataRequest>,
 ) -> (StatusCode, Json<ContributeTrainingDataResponse>) {
     println!("🏭 Contributing training data for muse {} by {}", 
              request.muse_token_id, request.contributor_address);
 
+    if !auth.owns(&request.contributor_address) {
+        return (StatusCode::FORBIDDEN, Json(ContributeTrainingDataResponse {
+            success: false,
+            contribution_id: String::new(),
+            reward_amount: 0,
+            ipfs_hash: String::new(),
+            reward_calculation: crate::training_data_market::RewardCalculation {
+                base_reward: 0,
+                type_bonus: 0,
+                quality_bonus: 0,
+                streak_bonus: 0,
+                total_reward: 0,
+                reasoning: vec!["Contributor address does not match the signed-in wallet".to_string()],
+            },
+        }));
+    }
+
     // Validate contribution type
     let contribution_type = match crate::training_data_market::ContributionType::from_u8(request.contribution_type) {
         Some(ct) => ct,
@@ -2893,11 +4012,21 @@ async fn contribute_training_data(
 
 async fn validate_contribution(
     State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
     Json(request): Json<crate::training_data_market::ValidationRequest>,
 ) -> (StatusCode, Json<crate::training_data_market::ValidationResponse>) {
     println!("🔍 Validating contribution {} by {}", 
              request.contribution_id, request.validator_address);
 
+    if !auth.owns(&request.validator_address) {
+        return (StatusCode::FORBIDDEN, Json(crate::training_data_market::ValidationResponse {
+            success: false,
+            contribution_id: request.contribution_id,
+            new_quality_score: 0,
+            validation_status: crate::training_data_market::ValidationStatus::Pending,
+        }));
+    }
+
     let mut marketplace = state.training_data_market.lock().await;
     
     match marketplace.validate_contribution(request.clone()).await {
@@ -3071,3 +4200,448 @@ async fn get_contributor_leaderboard(
         "total_contributors": marketplace.contributors.len()
     })))
 }
+
+// ✅ NEW: Sign-In with Ethereum (EIP-4361) routes - session tokens for wallet-owned endpoints
+pub fn auth_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/v1/auth/nonce", get(get_auth_nonce))
+        .route("/api/v1/auth/verify", post(verify_sign_in))
+        .route("/api/v1/auth/session", get(get_auth_session))
+        .route("/api/v1/auth/logout", post(sign_out))
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthNonceResponse {
+    pub nonce: String,
+    pub domain: String,
+    pub chain_id: u64,
+    pub expires_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignInRequest {
+    pub message: String,   // Full EIP-4361 message text as signed by the wallet
+    pub signature: String, // 0x-prefixed 65-byte personal_sign signature
+}
+
+#[derive(Debug, Serialize)]
+pub struct SignInResponse {
+    pub token: String,
+    pub address: String,
+    pub expires_at: String,
+}
+
+async fn get_auth_nonce(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let (nonce, expires_at) = state.auth_service.issue_nonce().await
+        .ok_or(StatusCode::TOO_MANY_REQUESTS)?;
+
+    Ok((StatusCode::OK, Json(AuthNonceResponse {
+        nonce,
+        domain: state.auth_service.domain().to_string(),
+        chain_id: state.auth_service.chain_id(),
+        expires_at: expires_at.to_rfc3339(),
+    })))
+}
+
+async fn verify_sign_in(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<SignInRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    match state.auth_service.sign_in(&request.message, &request.signature).await {
+        Ok((token, session)) => Ok((StatusCode::OK, Json(SignInResponse {
+            token,
+            address: session.address,
+            expires_at: session.expires_at.to_rfc3339(),
+        }))),
+        Err(e) => {
+            println!("❌ Sign-in rejected: {}", e);
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+}
+
+async fn get_auth_session(
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    let session = state.auth_service.authenticate(&auth.token).await
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    Ok((StatusCode::OK, Json(session)))
+}
+
+async fn sign_out(
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    state.auth_service.sign_out(&auth.token).await;
+    println!("🔓 Wallet {} signed out", auth.address);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ✅ NEW: Interaction permission routes - muse owners grant/revoke chat access through the backend wallet
+pub fn permission_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/v1/muses/{id}/permissions", post(grant_interaction_permission))
+        .route("/api/v1/muses/{id}/permissions/{address}", get(check_interaction_permission))
+        .route("/api/v1/muses/{id}/permissions/{address}", axum::routing::delete(revoke_interaction_permission))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GrantPermissionRequest {
+    pub user_address: String,
+}
+
+async fn grant_interaction_permission(
+    Path(muse_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+    Json(request): Json<GrantPermissionRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let token_id: u64 = muse_id.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    if !request.user_address.starts_with("0x") || request.user_address.len() != 42 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if !is_muse_owner(&state, token_id, &auth.address).await {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    println!("🔑 Granting {} access to muse #{} (requested by {})", request.user_address, token_id, auth.address);
+    match state.blockchain_client.grant_interaction_permission(token_id, &request.user_address).await {
+        Ok(tx_info) => {
+            // Share the owner's data key too so the grantee can read the owner's encrypted history
+            let key_shared = match state.encryption_service.share_muse_key(&muse_id, &auth.address, &request.user_address).await {
+                Ok(_) => true,
+                Err(e) => {
+                    println!("⚠️  Data key not shared with {}: {}", request.user_address, e);
+                    false
+                }
+            };
+
+            Ok((StatusCode::OK, Json(serde_json::json!({
+                "success": true,
+                "muse_id": token_id,
+                "user_address": request.user_address,
+                "key_shared": key_shared,
+                "transaction": tx_info
+            }))))
+        }
+        Err(e) => {
+            println!("❌ Failed to grant interaction permission: {}", e);
+            Err(StatusCode::BAD_GATEWAY)
+        }
+    }
+}
+
+async fn revoke_interaction_permission(
+    Path((muse_id, address)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    let token_id: u64 = muse_id.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    if !address.starts_with("0x") || address.len() != 42 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if !is_muse_owner(&state, token_id, &auth.address).await {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    println!("🔒 Revoking {} access to muse #{} (requested by {})", address, token_id, auth.address);
+    match state.blockchain_client.revoke_interaction_permission(token_id, &address).await {
+        Ok(tx_info) => {
+            // Rotate the owner's data key and store their content again under the new one
+            let rotated = match state.encryption_service.revoke_muse_key(&muse_id, &auth.address, &address).await {
+                Ok(rotated) => rotated,
+                Err(e) => {
+                    println!("⚠️  Data key not revoked for {}: {}", address, e);
+                    None
+                }
+            };
+            let (sessions_resealed, memories_resealed) = if rotated.is_some() {
+                (
+                    state.ipfs_chat_history.reseal_sessions(&muse_id, &auth.address).await,
+                    state.memory_system.reseal_memories(&muse_id, &auth.address).await,
+                )
+            } else {
+                (0, 0)
+            };
+
+            Ok((StatusCode::OK, Json(serde_json::json!({
+                "success": true,
+                "muse_id": token_id,
+                "user_address": address,
+                "key_revoked": rotated.is_some(),
+                "key_id": rotated,
+                "sessions_resealed": sessions_resealed,
+                "memories_resealed": memories_resealed,
+                "transaction": tx_info
+            }))))
+        }
+        Err(e) => {
+            println!("❌ Failed to revoke interaction permission: {}", e);
+            Err(StatusCode::BAD_GATEWAY)
+        }
+    }
+}
+
+async fn check_interaction_permission(
+    Path((muse_id, address)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let token_id: u64 = muse_id.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let is_creator = is_recorded_creator(&state, token_id, &address).await;
+    let can_interact = is_creator || state.blockchain_client.can_interact(token_id, &address).await
+        .map_err(|e| {
+            println!("❌ Permission check failed for muse #{}: {}", token_id, e);
+            StatusCode::BAD_GATEWAY
+        })?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "muse_id": token_id,
+        "user_address": address,
+        "can_interact": can_interact
+    }))))
+}
+
+// ✅ NEW: Data key routes - wallet-derived keys for encrypted sessions and memories
+pub fn key_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/v1/keys/message", get(get_key_message))
+        .route("/api/v1/keys/unlock", post(unlock_data_key))
+        .route("/api/v1/keys/lock", post(lock_data_key))
+        .route("/api/v1/muses/{id}/keys/share", post(share_muse_key))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnlockKeyRequest {
+    pub signature: String,
+}
+
+async fn get_key_message(
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    let registered = state.encryption_service.public_key(&auth.address).await;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "address": auth.address,
+        "message": key_derivation_message(&auth.address),
+        "registered": registered.is_some()
+    }))))
+}
+
+async fn unlock_data_key(
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+    Json(request): Json<UnlockKeyRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    match state.encryption_service.unlock(&auth.address, &request.signature).await {
+        Ok(key) => Ok((StatusCode::OK, Json(serde_json::json!({
+            "success": true,
+            "address": key.address,
+            "public_key": key.public_key
+        })))),
+        Err(e) => {
+            println!("❌ Failed to unlock data key for {}: {}", auth.address, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+async fn lock_data_key(
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    let locked = state.encryption_service.lock(&auth.address).await;
+    Ok((StatusCode::OK, Json(serde_json::json!({ "success": locked }))))
+}
+
+async fn share_muse_key(
+    Path(muse_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+    Json(request): Json<GrantPermissionRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let token_id: u64 = muse_id.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    if !is_muse_owner(&state, token_id, &auth.address).await {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    match state.encryption_service.share_muse_key(&muse_id, &auth.address, &request.user_address).await {
+        Ok(wrapped) => Ok((StatusCode::OK, Json(serde_json::json!({
+            "success": true,
+            "muse_id": token_id,
+            "user_address": wrapped.recipient,
+            "key_id": wrapped.key_id
+        })))),
+        Err(e) => Ok((StatusCode::CONFLICT, Json(serde_json::json!({
+            "success": false,
+            "error": e.to_string()
+        })))),
+    }
+}
+
+// ✅ NEW: Right-to-be-forgotten routes - erase everything stored for the signed-in wallet
+pub fn erasure_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/v1/account/erase", post(erase_account_data))
+        .route("/api/v1/account/erasure-receipts", get(get_erasure_receipts))
+        .route("/api/v1/erasure-receipts/verify", post(verify_erasure_receipt))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EraseAccountRequest {
+    pub confirm: bool,
+}
+
+async fn erase_account_data(
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+    Json(request): Json<EraseAccountRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    // Erasure cannot be undone, so it must be asked for explicitly
+    if !request.confirm {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    println!("🧽 Erasure requested by {}", auth.address);
+    match state.data_erasure.erase_user(&auth.address).await {
+        Ok(receipt) => Ok((StatusCode::OK, Json(receipt))),
+        Err(e) => {
+            println!("❌ Erasure failed for {}: {}", auth.address, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn get_erasure_receipts(
+    State(state): State<Arc<AppState>>,
+    auth: AuthenticatedUser,
+) -> Result<impl IntoResponse, StatusCode> {
+    let receipts = state.data_erasure.receipts(&auth.address).await;
+    Ok((StatusCode::OK, Json(receipts)))
+}
+
+async fn verify_erasure_receipt(
+    Json(receipt): Json<ErasureReceipt>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let valid = receipt.verify().unwrap_or(false);
+    Ok((StatusCode::OK, Json(serde_json::json!({
+        "valid": valid,
+        "receipt_id": receipt.receipt_id,
+        "signer": receipt.signer
+    }))))
+}
+
+// ✅ NEW: Data portability routes - export a user's history with a muse and import it on another deployment
+pub fn export_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/v1/muses/{id}/export", get(export_muse_data))
+        .route("/api/v1/muses/{id}/import", post(import_muse_archive))
+        .route("/api/v1/muses/{id}/import/car", post(import_muse_car))
+        .layer(DefaultBodyLimit::max(MAX_ARCHIVE_BYTES))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    /// "json" (default) or "car"
+    pub format: Option<String>,
+}
+
+async fn export_muse_data(
+    Path(muse_id): Path<String>,
+    Query(query): Query<ExportQuery>,
+    State(state): State<Arc<AppState>>,
+    access: MuseAccess,
+) -> Result<Response, StatusCode> {
+    let address = access.user.address;
+    println!("📦 Export of muse {} requested by {}", muse_id, address);
+    
+    match query.format.as_deref().unwrap_or("json") {
+        "json" => match state.data_export.export_json(&address, &muse_id).await {
+            Ok(archive) => Ok((StatusCode::OK, Json(archive)).into_response()),
+            Err(e) => {
+                println!("❌ Export of muse {} for {} failed: {}", muse_id, address, e);
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        },
+        "car" => match state.data_export.export_car(&address, &muse_id).await {
+            Ok(car) => Ok((
+                StatusCode::OK,
+                [
+                    ("Content-Type", "application/vnd.ipld.car".to_string()),
+                    ("Content-Disposition", format!("attachment; filename=\"muse_{}_{}.car\"", muse_id, address)),
+                ],
+                car,
+            ).into_response()),
+            Err(e) => {
+                println!("❌ CAR export of muse {} for {} failed: {}", muse_id, address, e);
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        },
+        _ => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
+async fn import_muse_archive(
+    Path(muse_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    access: MuseAccess,
+    Json(archive): Json<ExportArchive>,
+) -> Result<impl IntoResponse, StatusCode> {
+    // Memories shape the muse for everyone, so only its owner may bring them in
+    let may_add_memories = is_muse_owner(&state, access.token_id, &access.user.address).await;
+    
+    match state.data_export.import_json(&access.user.address, &muse_id, archive, may_add_memories).await {
+        Ok(summary) => Ok((StatusCode::OK, Json(summary))),
+        Err(e) => {
+            println!("❌ Import into muse {} for {} failed: {}", muse_id, access.user.address, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+async fn import_muse_car(
+    Path(muse_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    access: MuseAccess,
+    car: Bytes,
+) -> Result<impl IntoResponse, StatusCode> {
+    let may_add_memories = is_muse_owner(&state, access.token_id, &access.user.address).await;
+    
+    match state.data_export.import_car(&access.user.address, &muse_id, &car, may_add_memories).await {
+        Ok(summary) => Ok((StatusCode::OK, Json(summary))),
+        Err(e) => {
+            println!("❌ CAR import into muse {} for {} failed: {}", muse_id, access.user.address, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+// ✅ NEW: Content storage routes - serves blobs by CID (needed for STORAGE_BACKEND=local)
+pub fn storage_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/v1/storage/{cid}", get(get_stored_content))
+}
+
+async fn get_stored_content(
+    State(state): State<Arc<AppState>>,
+    Path(cid): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    match state.storage.get(&cid).await {
+        Ok(content) => Ok((
+            StatusCode::OK,
+            // Content is addressed by its hash, so it never changes
+            [("Content-Type", "application/octet-stream"), ("Cache-Control", "public, max-age=31536000, immutable")],
+            content,
+        )),
+        Err(e) => {
+            println!("❌ Failed to read stored content {}: {}", cid, e);
+            Err(StatusCode::NOT_FOUND)
+        }
+    }
+}
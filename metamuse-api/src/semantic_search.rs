@@ -5,6 +5,10 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use crate::config::Config;
 use crate::ipfs_chat_history::IPFSChatHistoryManager;
+use crate::embedder::Embedder;
+use crate::storage_backend::StorageBackend;
+use crate::vector_index::{IndexFilter, IndexMetadata, VectorIndex};
+use crate::retrieval::{rerank_in_place, retrieve, Reranker, RetrievalOptions};
 
 /// Vector embedding representation for semantic search
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,89 +39,61 @@ pub struct SemanticQuery {
     pub min_relevance: f64,         // Minimum relevance threshold
     pub max_results: usize,         // Maximum number of results
     pub time_range: Option<(u64, u64)>, // Optional time range filter
+    #[serde(default)]
+    pub muse_id: Option<String>,        // Optional muse filter
+    #[serde(default)]
+    pub user_address: Option<String>,   // Optional user filter
+    #[serde(default)]
+    pub options: RetrievalOptions,      // Vector / lexical / hybrid ranking
 }
 
 /// Enhanced semantic search service with IPFS integration
 pub struct SemanticSearchService {
     config: Config,
     ipfs_manager: Arc<IPFSChatHistoryManager>,
+    storage: Arc<dyn StorageBackend>,
+    embedder: Arc<dyn Embedder>,
     // In-memory embedding cache for performance
     embedding_cache: RwLock<HashMap<String, VectorEmbedding>>,
-    // Pre-computed embeddings storage
-    embeddings_store: RwLock<Vec<VectorEmbedding>>,
+    // ✅ NEW: Persistent ANN index over every stored embedding
+    index: Arc<VectorIndex>,
+    // ✅ NEW: Optional cross-encoder for reranking fused candidates
+    reranker: Option<Arc<dyn Reranker>>,
 }
 
 impl SemanticSearchService {
-    pub fn new(config: Config, ipfs_manager: Arc<IPFSChatHistoryManager>) -> Self {
+    pub fn new(
+        config: Config,
+        ipfs_manager: Arc<IPFSChatHistoryManager>,
+        storage: Arc<dyn StorageBackend>,
+        embedder: Arc<dyn Embedder>,
+        index: Arc<VectorIndex>,
+    ) -> Self {
         Self {
             config,
             ipfs_manager,
+            storage,
+            embedder,
             embedding_cache: RwLock::new(HashMap::new()),
-            embeddings_store: RwLock::new(Vec::new()),
+            index,
+            reranker: None,
         }
     }
 
-    /// Generate vector embedding for text content using OpenAI embeddings API
+    /// Rerank candidates with a cross-encoder when a query asks for it
+    pub fn with_reranker(mut self, reranker: Arc<dyn Reranker>) -> Self {
+        self.reranker = Some(reranker);
+        self
+    }
+
+    /// Generate vector embedding for text content with the shared embedding model
     pub async fn generate_embedding(&self, text: &str) -> Result<Vec<f32>> {
         println!("🔍 Generating embedding for text (length: {})", text.len());
 
-        // For hackathon demo, return mock embeddings based on text characteristics
-        let mock_embedding = self.generate_mock_embedding(text).await;
+        let embedding = self.embedder.embed(text).await?;
         
-        println!("✅ Generated {}-dimensional embedding", mock_embedding.len());
-        Ok(mock_embedding)
-    }
-
-    /// Generate mock embeddings based on text content for demo purposes
-    async fn generate_mock_embedding(&self, text: &str) -> Vec<f32> {
-        const EMBEDDING_DIM: usize = 384; // Standard embedding dimension
-
-        let text_lower = text.to_lowercase();
-        let mut embedding = vec![0.0; EMBEDDING_DIM];
-        
-        // Create pseudo-semantic embeddings based on content characteristics
-        let mut seed = 0u32;
-        for byte in text.as_bytes() {
-            seed = seed.wrapping_mul(31).wrapping_add(*byte as u32);
-        }
-        
-        // Generate base random values
-        for i in 0..EMBEDDING_DIM {
-            let idx_seed = seed.wrapping_add(i as u32);
-            embedding[i] = (idx_seed as f32 / u32::MAX as f32 - 0.5) * 2.0;
-        }
-
-        // Adjust based on semantic content
-        if text_lower.contains("creative") || text_lower.contains("art") || text_lower.contains("imagination") {
-            for i in 0..50 { embedding[i] += 0.3; }
-        }
-        
-        if text_lower.contains("wise") || text_lower.contains("knowledge") || text_lower.contains("understanding") {
-            for i in 50..100 { embedding[i] += 0.3; }
-        }
-        
-        if text_lower.contains("funny") || text_lower.contains("joke") || text_lower.contains("humor") {
-            for i in 100..150 { embedding[i] += 0.3; }
-        }
-        
-        if text_lower.contains("empathy") || text_lower.contains("emotion") || text_lower.contains("feeling") {
-            for i in 150..200 { embedding[i] += 0.3; }
-        }
-
-        // Question/query boosting
-        if text_lower.contains("?") || text_lower.starts_with("how ") || text_lower.starts_with("what ") {
-            for i in 200..250 { embedding[i] += 0.2; }
-        }
-
-        // Normalize the embedding vector
-        let magnitude: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
-        if magnitude > 0.0 {
-            for value in &mut embedding {
-                *value /= magnitude;
-            }
-        }
-
-        embedding
+        println!("✅ Generated {}-dimensional {} embedding", embedding.len(), self.embedder.name());
+        Ok(embedding)
     }
 
     /// Store embedding in IPFS and update local cache
@@ -160,27 +136,23 @@ impl SemanticSearchService {
             cache.insert(content_hash.clone(), vector_embedding.clone());
         }
 
-        // Add to embeddings store
-        {
-            let mut store = self.embeddings_store.write().await;
-            store.push(vector_embedding);
-        }
+        // Add to the vector index (re-storing identical content replaces the entry)
+        let index_metadata = IndexMetadata {
+            muse_id: vector_embedding.metadata.get("muse_id").cloned(),
+            user_address: vector_embedding.metadata.get("user_address").cloned(),
+            content_type: vector_embedding.content_type.clone(),
+            timestamp: vector_embedding.timestamp,
+            attributes: vector_embedding.metadata.clone(),
+        };
+        self.index.insert(content_hash.clone(), vector_embedding.embedding, content, index_metadata).await?;
 
         println!("📦 Stored embedding for content hash: {} with IPFS integration", content_hash);
         Ok(content_hash)
     }
 
-    /// ✅ Store content to IPFS using direct Pinata API integration
+    /// ✅ Store content to the configured content storage backend
     async fn store_content_to_ipfs(&self, content: &str, content_hash: &str, content_type: &str) -> Result<String> {
-        println!("📦 Storing semantic content to IPFS via Pinata API");
-        
-        // Get JWT token from config
-        let token = match &self.config.ipfs_jwt_token {
-            Some(jwt) => jwt.clone(),
-            None => {
-                return Err(anyhow::anyhow!("No IPFS JWT token configured for semantic search"));
-            }
-        };
+        println!("📦 Storing semantic content to {} storage", self.storage.name());
         
         let filename = format!("SemanticContent_{}_{}.json", content_type, &content_hash[2..10]);
         
@@ -196,10 +168,9 @@ impl SemanticSearchService {
             "semantic_search": true
         });
         
-        let content_json = serde_json::to_string_pretty(&semantic_content)?;
+        let content_json = serde_json::to_vec_pretty(&semantic_content)?;
         
-        // Use direct Pinata API call
-        match self.upload_to_pinata_directly(&content_json, &filename, &token).await {
+        match self.storage.put(content_json, &filename, "application/json").await {
             Ok(ipfs_hash) => {
                 println!("✅ Semantic content stored in IPFS: {}", ipfs_hash);
                 Ok(ipfs_hash)
@@ -211,129 +182,35 @@ impl SemanticSearchService {
         }
     }
 
-    /// Direct Pinata API upload for semantic content
-    async fn upload_to_pinata_directly(&self, content: &str, filename: &str, token: &str) -> Result<String> {
-        use reqwest::multipart;
-        
-        let url = "https://uploads.pinata.cloud/v3/files";
-        
-        // Create multipart form
-        let file_part = multipart::Part::text(content.to_string())
-            .file_name(filename.to_string())
-            .mime_str("application/json")?;
-        
-        let form = multipart::Form::new()
-            .part("file", file_part);
-        
-        println!("🌐 Making direct request to Pinata API: {}", url);
-        
-        // Create a reqwest client for this request
-        let client = reqwest::Client::new();
-        let response = client
-            .post(url)
-            .multipart(form)
-            .bearer_auth(token)
-            .send()
-            .await?;
-        
-        let status = response.status();
-        println!("📡 Pinata API response status: {}", status);
-        
-        if !status.is_success() {
-            let error_text = response.text().await?;
-            println!("❌ Pinata API error response: {}", error_text);
-            return Err(anyhow::anyhow!("Pinata API error {}: {}", status, error_text));
-        }
-        
-        let response_text = response.text().await?;
-        println!("📥 Raw Pinata API response: {}", &response_text[..std::cmp::min(200, response_text.len())]);
-        
-        // Parse response to extract CID/hash
-        let response_json: serde_json::Value = serde_json::from_str(&response_text)?;
-        
-        // Try different possible response formats from Pinata API
-        let ipfs_hash = if let Some(data) = response_json.get("data") {
-            // v3 API format: { "data": { "cid": "...", "id": "..." } }
-            if let Some(cid) = data.get("cid") {
-                cid.as_str().unwrap_or_default().to_string()
-            } else if let Some(id) = data.get("id") {
-                id.as_str().unwrap_or_default().to_string()
-            } else {
-                return Err(anyhow::anyhow!("No CID or ID found in Pinata response data"));
-            }
-        } else if let Some(cid) = response_json.get("cid") {
-            // Direct CID format: { "cid": "..." }
-            cid.as_str().unwrap_or_default().to_string()
-        } else if let Some(id) = response_json.get("id") {
-            // Direct ID format: { "id": "..." }
-            id.as_str().unwrap_or_default().to_string()
-        } else {
-            return Err(anyhow::anyhow!("No recognizable hash/CID found in Pinata response: {}", response_text));
-        };
-        
-        if ipfs_hash.is_empty() {
-            return Err(anyhow::anyhow!("Empty hash returned from Pinata API"));
-        }
-        
-        println!("🎯 Extracted IPFS hash: {}", ipfs_hash);
-        Ok(ipfs_hash)
-    }
-
     /// ✅ Retrieve content from IPFS using content hash or IPFS CID
-    async fn retrieve_content_from_ipfs(&self, content_hash: &str, content_type: &str) -> Result<String> {
-        // First, try to get the IPFS CID from our local embeddings cache
-        let ipfs_cid = {
-            let cache = self.embedding_cache.read().await;
-            if let Some(embedding) = cache.get(content_hash) {
-                embedding.metadata.get("ipfs_cid").cloned()
-            } else {
-                None
-            }
-        };
+    async fn retrieve_content_from_ipfs(&self, content_hash: &str, content_type: &str, metadata: &HashMap<String, String>) -> Result<String> {
+        // The CID travels with the index entry, so this also works for snapshot-loaded entries
+        let ipfs_cid = metadata.get("ipfs_cid").cloned();
 
         if let Some(cid) = ipfs_cid {
             println!("📥 Retrieving semantic content from IPFS CID: {}", cid);
             
-            // Use direct IPFS gateway access for retrieval
-            let gateway_url = &self.config.ipfs_gateway_url;
-            
-            let download_url = format!("{}/{}", gateway_url, cid);
-            println!("🌐 Fetching from IPFS gateway: {}", download_url);
-            
-            // Create a reqwest client for this request
-            let client = reqwest::Client::new();
-            match client.get(&download_url).send().await {
-                Ok(response) => {
-                    let status = response.status();
-                    if status.is_success() {
-                        match response.text().await {
-                            Ok(content) => {
-                                println!("📥 Retrieved {} bytes from IPFS", content.len());
-                                
-                                // Parse the JSON structure we stored
-                                if let Ok(semantic_content) = serde_json::from_str::<serde_json::Value>(&content) {
-                                    if let Some(original_content) = semantic_content.get("content") {
-                                        if let Some(content_str) = original_content.as_str() {
-                                            println!("✅ Successfully retrieved semantic content from IPFS");
-                                            return Ok(content_str.to_string());
-                                        }
-                                    }
-                                }
-                                
-                                // If JSON parsing fails, return raw content
-                                println!("⚠️ Could not parse semantic JSON, returning raw content");
-                                return Ok(content);
-                            }
-                            Err(e) => {
-                                println!("❌ Failed to read IPFS response: {}", e);
+            match self.storage.get(&cid).await {
+                Ok(bytes) => {
+                    let content = String::from_utf8_lossy(&bytes).into_owned();
+                    println!("📥 Retrieved {} bytes from IPFS", content.len());
+                    
+                    // Parse the JSON structure we stored
+                    if let Ok(semantic_content) = serde_json::from_str::<serde_json::Value>(&content) {
+                        if let Some(original_content) = semantic_content.get("content") {
+                            if let Some(content_str) = original_content.as_str() {
+                                println!("✅ Successfully retrieved semantic content from IPFS");
+                                return Ok(content_str.to_string());
                             }
                         }
-                    } else {
-                        println!("❌ IPFS gateway error {}", status);
                     }
+                    
+                    // If JSON parsing fails, return raw content
+                    println!("⚠️ Could not parse semantic JSON, returning raw content");
+                    return Ok(content);
                 }
                 Err(e) => {
-                    println!("❌ Failed to fetch from IPFS gateway: {}", e);
+                    println!("❌ Failed to fetch semantic content {}: {}", cid, e);
                 }
             }
         }
@@ -389,58 +266,62 @@ impl SemanticSearchService {
     /// Perform semantic search across stored embeddings
     pub async fn semantic_search(&self, query: SemanticQuery) -> Result<Vec<SemanticSearchResult>> {
         println!("🔍 Performing semantic search: '{}'", query.query_text);
-        println!("   Filters: types={:?}, min_relevance={}, max_results={}", 
-                 query.content_types, query.min_relevance, query.max_results);
+        println!("   Filters: types={:?}, min_relevance={}, max_results={}, mode={:?}", 
+                 query.content_types, query.min_relevance, query.max_results, query.options.mode);
 
         // Generate embedding for the query
         let query_embedding = self.generate_embedding(&query.query_text).await?;
         
-        let store = self.embeddings_store.read().await;
+        let filter = IndexFilter {
+            muse_id: query.muse_id.clone(),
+            user_address: query.user_address.clone(),
+            content_types: query.content_types.clone(),
+            time_range: query.time_range,
+        };
+        let ranked = retrieve(&self.index, &query.query_text, &query_embedding, &filter, &query.options, |hit| {
+            hit.metadata.attributes.get("importance").and_then(|importance| importance.parse().ok())
+        }).await;
+
+        // min_relevance applies to cosine similarity, whichever retriever found the entry
+        let reranker = self.reranker.as_ref().filter(|_| query.options.rerank);
+        let depth = reranker.map_or(query.max_results, |reranker| reranker.top_n().max(query.max_results));
+        let candidates = ranked.into_iter()
+            .filter(|candidate| candidate.similarity as f64 >= query.min_relevance)
+            .take(depth);
         let mut results = Vec::new();
 
-        for stored_embedding in store.iter() {
-            // Apply content type filter
-            if !query.content_types.is_empty() && 
-               !query.content_types.contains(&stored_embedding.content_type) {
-                continue;
-            }
+        for candidate in candidates {
+            let hit = candidate.hit;
+            let relevance_score = candidate.similarity as f64;
 
-            // Apply time range filter
-            if let Some((start_time, end_time)) = query.time_range {
-                if stored_embedding.timestamp < start_time || stored_embedding.timestamp > end_time {
-                    continue;
+            // ✅ REAL IPFS content retrieval based on content hash
+            let actual_content = match self.retrieve_content_from_ipfs(&hit.id, &hit.metadata.content_type, &hit.metadata.attributes).await {
+                Ok(content) => content,
+                Err(e) => {
+                    println!("⚠️ Failed to retrieve IPFS content for {}: {}", hit.id, e);
+                    // Fallback to metadata if available
+                    hit.metadata.attributes.get("preview")
+                        .cloned()
+                        .unwrap_or_else(|| format!("Content unavailable for {}", &hit.id[..8]))
                 }
-            }
+            };
 
-            // Calculate cosine similarity
-            let relevance_score = self.cosine_similarity(&query_embedding, &stored_embedding.embedding);
-            
-            if relevance_score >= query.min_relevance {
-                // ✅ REAL IPFS content retrieval based on content hash
-                let actual_content = match self.retrieve_content_from_ipfs(&stored_embedding.content_hash, &stored_embedding.content_type).await {
-                    Ok(content) => content,
-                    Err(e) => {
-                        println!("⚠️ Failed to retrieve IPFS content for {}: {}", stored_embedding.content_hash, e);
-                        // Fallback to metadata if available
-                        stored_embedding.metadata.get("preview")
-                            .unwrap_or(&format!("Content unavailable for {}", &stored_embedding.content_hash[..8]))
-                            .clone()
-                    }
-                };
-
-                results.push(SemanticSearchResult {
-                    content_hash: stored_embedding.content_hash.clone(),
-                    content: actual_content,
-                    relevance_score,
-                    content_type: stored_embedding.content_type.clone(),
-                    timestamp: stored_embedding.timestamp,
-                    metadata: stored_embedding.metadata.clone(),
-                });
-            }
+            results.push(SemanticSearchResult {
+                content_hash: hit.id,
+                content: actual_content,
+                relevance_score,
+                content_type: hit.metadata.content_type,
+                timestamp: hit.metadata.timestamp,
+                metadata: hit.metadata.attributes,
+            });
         }
 
-        // Sort by relevance score (descending)
-        results.sort_by(|a, b| b.relevance_score.partial_cmp(&a.relevance_score).unwrap());
+        // Results are already in fused order; the cross-encoder reorders the head
+        if let Some(reranker) = reranker {
+            if let Err(e) = rerank_in_place(reranker.as_ref(), &query.query_text, &mut results, reranker.top_n(), |result| result.content.clone()).await {
+                println!("⚠️ Rerank failed, keeping fused order: {}", e);
+            }
+        }
         
         // Apply max results limit
         results.truncate(query.max_results);
@@ -449,23 +330,6 @@ impl SemanticSearchService {
         Ok(results)
     }
 
-    /// Calculate cosine similarity between two embeddings
-    fn cosine_similarity(&self, a: &[f32], b: &[f32]) -> f64 {
-        if a.len() != b.len() {
-            return 0.0;
-        }
-
-        let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
-        let magnitude_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-        let magnitude_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
-
-        if magnitude_a == 0.0 || magnitude_b == 0.0 {
-            return 0.0;
-        }
-
-        (dot_product / (magnitude_a * magnitude_b)) as f64
-    }
-
     /// Generate content hash for deduplication
     fn generate_content_hash(&self, content: &str) -> String {
         use sha3::{Digest, Sha3_256};
@@ -477,32 +341,36 @@ impl SemanticSearchService {
         format!("0x{}", hex::encode(result))
     }
 
-    /// Find similar content based on semantic similarity
-    pub async fn find_similar_content(&self, content: &str, similarity_threshold: f64, max_results: usize) -> Result<Vec<SemanticSearchResult>> {
+    /// Find content of one user with a muse that is semantically similar to `content`
+    pub async fn find_similar_content(&self, content: &str, muse_id: &str, user_address: &str, similarity_threshold: f64, max_results: usize) -> Result<Vec<SemanticSearchResult>> {
         let query = SemanticQuery {
             query_text: content.to_string(),
             content_types: vec!["message".to_string(), "memory".to_string()],
             min_relevance: similarity_threshold,
             max_results,
             time_range: None,
+            muse_id: Some(muse_id.to_string()),
+            user_address: Some(user_address.to_string()),
+            options: RetrievalOptions::default(),
         };
 
         self.semantic_search(query).await
     }
 
-    /// Get contextually relevant memories for a conversation
-    pub async fn get_contextual_memories(&self, user_message: &str, muse_id: &str, context_window: usize) -> Result<Vec<SemanticSearchResult>> {
-        println!("🧠 Retrieving contextual memories for muse {} (context window: {})", muse_id, context_window);
-
-        let mut enhanced_query = user_message.to_string();
-        enhanced_query.push_str(&format!(" muse:{}", muse_id));
+    /// Get contextually relevant memories of one user with a muse for a conversation
+    pub async fn get_contextual_memories(&self, user_message: &str, muse_id: &str, user_address: &str, context_window: usize, options: RetrievalOptions) -> Result<Vec<SemanticSearchResult>> {
+        println!("🧠 Retrieving contextual memories for user {} + muse {} (context window: {})", user_address, muse_id, context_window);
 
         let query = SemanticQuery {
-            query_text: enhanced_query,
+            query_text: user_message.to_string(),
             content_types: vec!["memory".to_string(), "context".to_string()],
             min_relevance: 0.6, // High relevance threshold for context
             max_results: context_window,
             time_range: None,
+            // Prompt context only ever comes from this user's history with this muse
+            muse_id: Some(muse_id.to_string()),
+            user_address: Some(user_address.to_string()),
+            options,
         };
 
         let results = self.semantic_search(query).await?;
@@ -607,19 +475,19 @@ impl SemanticSearchService {
 
     /// Get embedding statistics
     pub async fn get_stats(&self) -> Result<HashMap<String, serde_json::Value>> {
-        let store = self.embeddings_store.read().await;
+        let index = self.index.read().await;
         let cache = self.embedding_cache.read().await;
 
         let mut content_type_counts = HashMap::new();
-        for embedding in store.iter() {
-            *content_type_counts.entry(embedding.content_type.clone()).or_insert(0) += 1;
+        for (_, _, metadata) in index.entries() {
+            *content_type_counts.entry(metadata.content_type.clone()).or_insert(0) += 1;
         }
 
         let mut stats = HashMap::new();
-        stats.insert("total_embeddings".to_string(), serde_json::Value::Number(store.len().into()));
+        stats.insert("total_embeddings".to_string(), serde_json::Value::Number(index.len().into()));
         stats.insert("cached_embeddings".to_string(), serde_json::Value::Number(cache.len().into()));
         stats.insert("content_type_breakdown".to_string(), serde_json::to_value(content_type_counts)?);
-        stats.insert("embedding_dimension".to_string(), serde_json::Value::Number(384.into()));
+        stats.insert("embedding_dimension".to_string(), serde_json::Value::Number(index.dimension().into()));
 
         Ok(stats)
     }
@@ -680,24 +548,18 @@ impl SemanticSearchService {
                  user_address, muse_id, query);
 
         let semantic_query = SemanticQuery {
-            query_text: format!("{} user:{} muse:{}", query, user_address, muse_id),
+            query_text: query.to_string(),
             content_types: vec!["user_message".to_string(), "ai_response".to_string(), "memory".to_string()],
             min_relevance: 0.5,
             max_results: limit,
             time_range: None,
+            // Filter inside the index so `limit` counts only this user+muse combination
+            muse_id: Some(muse_id.to_string()),
+            user_address: Some(user_address.to_string()),
+            options: RetrievalOptions::default(),
         };
 
-        let all_results = self.semantic_search(semantic_query).await?;
-        
-        // Filter results to only include those matching user+muse combination
-        let filtered_results: Vec<SemanticSearchResult> = all_results
-            .into_iter()
-            .filter(|result| {
-                let metadata = &result.metadata;
-                metadata.get("user_address").map_or(false, |addr| addr == user_address) &&
-                metadata.get("muse_id").map_or(false, |id| id == muse_id)
-            })
-            .collect();
+        let filtered_results = self.semantic_search(semantic_query).await?;
 
         println!("🎯 Found {} specific memories for user {} + muse {}", 
                  filtered_results.len(), user_address, muse_id);
@@ -772,11 +634,11 @@ impl SemanticSearchService {
                 }
                 
                 // Store user -> DAT mapping for efficient retrieval using embeddings store as temporary storage
-                let user_dat_key = format!("user_dats_{}", user_address.to_lowercase());
-                let dat_entry_text = serde_json::json!({
+                let dat_entry = serde_json::json!({
                     "dat_id": dat_id,
                     "ipfs_hash": ipfs_hash,
                     "user_address": user_address,
+                    "muse_id": metadata_value.get("interaction_proof").and_then(|proof| proof.get("muse_token_id")),
                     "timestamp": chrono::Utc::now().timestamp(),
                     "user_message": user_message,
                     "ai_response": ai_response,
@@ -785,25 +647,9 @@ impl SemanticSearchService {
                         "name": metadata_value.get("name"),
                         "description": metadata_value.get("description"),
                     }
-                }).to_string();
-                
-                // Store DAT info as a "semantic embedding" entry for retrieval
-                // This is a temporary solution - in production this would be in a proper database
-                let mut dat_metadata = HashMap::new();
-                dat_metadata.insert("type".to_string(), "user_dat".to_string());
-                dat_metadata.insert("user_address".to_string(), user_address.to_lowercase());
-                dat_metadata.insert("dat_id".to_string(), dat_id.to_string());
-                dat_metadata.insert("content".to_string(), dat_entry_text); // Store full DAT data in metadata
-                
-                let dat_embedding = VectorEmbedding {
-                    content_hash: user_dat_key,
-                    embedding: vec![0.0; 384], // Dummy embedding - we only need the metadata
-                    content_type: "user_dat".to_string(),
-                    timestamp: chrono::Utc::now().timestamp() as u64,
-                    metadata: dat_metadata,
-                };
+                });
                 
-                self.embeddings_store.write().await.push(dat_embedding);
+                self.cache_user_dat(user_address, dat_id, &dat_entry).await;
                 println!("🏷️ Indexed DAT {} for user {}", dat_id, user_address);
             }
         }
@@ -811,15 +657,134 @@ impl SemanticSearchService {
         Ok(ipfs_hash)
     }
 
+    /// ✅ NEW: Register a DAT entry exported from another deployment (as returned by `get_user_dats`)
+    pub async fn import_user_dat(&self, dat_entry: &serde_json::Value) -> Result<()> {
+        let user_address = dat_entry.get("user_address").and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("DAT entry has no user_address"))?;
+        let dat_id = dat_entry.get("dat_id").and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("DAT entry has no dat_id"))?;
+        
+        self.cache_user_dat(user_address, dat_id, dat_entry).await;
+        Ok(())
+    }
+
+    /// Store DAT info as a cached "semantic embedding" entry for retrieval (kept out of the vector index)
+    /// This is a temporary solution - in production this would be in a proper database
+    async fn cache_user_dat(&self, user_address: &str, dat_id: &str, dat_entry: &serde_json::Value) {
+        let user_dat_key = format!("user_dats_{}_{}", user_address.to_lowercase(), dat_id);
+        
+        let mut dat_metadata = HashMap::new();
+        dat_metadata.insert("type".to_string(), "user_dat".to_string());
+        dat_metadata.insert("user_address".to_string(), user_address.to_lowercase());
+        dat_metadata.insert("dat_id".to_string(), dat_id.to_string());
+        dat_metadata.insert("content".to_string(), dat_entry.to_string()); // Store full DAT data in metadata
+        
+        let dat_embedding = VectorEmbedding {
+            content_hash: user_dat_key,
+            embedding: vec![0.0; 384], // Dummy embedding - we only need the metadata
+            content_type: "user_dat".to_string(),
+            timestamp: chrono::Utc::now().timestamp() as u64,
+            metadata: dat_metadata,
+        };
+        
+        self.embedding_cache.write().await.insert(dat_embedding.content_hash.clone(), dat_embedding);
+    }
+
+    /// ✅ NEW: Re-embed entries whose index snapshot was built by a different embedder,
+    /// reading their text back from storage. Entries whose content can't be read are dropped.
+    pub async fn reembed_stale_entries(&self) -> (usize, usize) {
+        let (mut reembedded, mut dropped) = (0, 0);
+        for (id, metadata) in self.index.take_stale_entries().await {
+            let Some(cid) = metadata.attributes.get("ipfs_cid").cloned() else {
+                dropped += 1;
+                continue;
+            };
+            let content = match self.storage.get(&cid).await {
+                Ok(bytes) => serde_json::from_slice::<serde_json::Value>(&bytes).ok()
+                    .and_then(|stored| stored.get("content").and_then(|c| c.as_str()).map(str::to_string)),
+                Err(e) => {
+                    println!("⚠️ Failed to fetch {} for re-embedding: {}", cid, e);
+                    None
+                }
+            };
+            let Some(content) = content else {
+                dropped += 1;
+                continue;
+            };
+
+            let inserted = match self.generate_embedding(&content).await {
+                Ok(embedding) => self.index.insert(id.clone(), embedding, &content, metadata).await,
+                Err(e) => Err(e),
+            };
+            match inserted {
+                Ok(()) => reembedded += 1,
+                Err(e) => {
+                    println!("⚠️ Failed to re-embed {}: {}", id, e);
+                    dropped += 1;
+                }
+            }
+        }
+
+        if reembedded + dropped > 0 {
+            println!("📐 Re-embedded {} semantic entries with {} ({} dropped)", reembedded, self.embedder.name(), dropped);
+        }
+        (reembedded, dropped)
+    }
+
+    /// ✅ NEW: Erase every indexed entry of `user_address` plus the entries of the given
+    /// training contributions, unpinning their stored content. DAT metadata stays pinned
+    /// because minted tokens point at it; only the local lookup entries are dropped.
+    /// Returns (erased entries, unpinned, unpin failures).
+    pub async fn erase_user_content(&self, user_address: &str, contribution_ids: &[String]) -> (usize, usize, usize) {
+        let matches = |metadata: &IndexMetadata| {
+            metadata.user_address.as_deref().is_some_and(|address| address.eq_ignore_ascii_case(user_address))
+                || metadata.attributes.get("contribution_id").is_some_and(|id| contribution_ids.contains(id))
+        };
+        
+        let erased: Vec<(String, Option<String>)> = self.index.read().await
+            .entries()
+            .filter(|(_, _, metadata)| matches(metadata))
+            .map(|(id, _, metadata)| (id.to_string(), metadata.attributes.get("ipfs_cid").cloned()))
+            .collect();
+        
+        for (id, _) in &erased {
+            self.index.purge(id).await;
+        }
+        
+        let erased_ids: Vec<&str> = erased.iter().map(|(id, _)| id.as_str()).collect();
+        let dat_prefix = format!("user_dats_{}_", user_address.to_lowercase());
+        self.embedding_cache.write().await.retain(|key, _| {
+            !erased_ids.contains(&key.as_str()) && !key.starts_with(&dat_prefix)
+        });
+        
+        // Entries stored while IPFS was unavailable fall back to the content hash as "CID"
+        let (mut unpinned, mut unpin_failures) = (0, 0);
+        for (id, cid) in &erased {
+            let Some(cid) = cid.as_ref().filter(|cid| *cid != id) else {
+                continue;
+            };
+            match self.storage.unpin(cid).await {
+                Ok(()) => unpinned += 1,
+                Err(e) => {
+                    unpin_failures += 1;
+                    println!("⚠️  Failed to unpin semantic content {}: {}", cid, e);
+                }
+            }
+        }
+        
+        println!("🧽 Erased {} semantic index entries of {}", erased.len(), user_address);
+        (erased.len(), unpinned, unpin_failures)
+    }
+
     /// ✅ Public method to retrieve DATs by user address
     pub async fn get_user_dats(&self, user_address: &str) -> Result<Vec<serde_json::Value>> {
         println!("🔍 Retrieving DATs for user: {}", user_address);
         
-        let embeddings_store = self.embeddings_store.read().await;
+        let embedding_cache = self.embedding_cache.read().await;
         let mut user_dats = Vec::new();
         
-        // Search through embeddings for DAT entries for this user
-        for embedding in embeddings_store.iter() {
+        // Search through cached embeddings for DAT entries for this user
+        for embedding in embedding_cache.values() {
             if let Some(entry_type) = embedding.metadata.get("type") {
                 if entry_type == "user_dat" {
                     if let Some(stored_user_address) = embedding.metadata.get("user_address") {
@@ -0,0 +1,567 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::config::Config;
+
+/// Multicodec for raw binary blocks
+const RAW_CODEC: u8 = 0x55;
+/// Multihash code and digest length for sha2-256
+const SHA2_256_CODE: u8 = 0x12;
+const SHA2_256_LENGTH: u8 = 0x20;
+
+const PINATA_UPLOAD_URL: &str = "https://uploads.pinata.cloud/v3/files";
+const PINATA_API_URL: &str = "https://api.pinata.cloud";
+
+/// Content-addressed blob storage shared by chat history, semantic search, avatars and memories
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Short backend name for logs and health output
+    fn name(&self) -> &'static str;
+
+    /// Store `data` and return its CID. Stored content is pinned.
+    async fn put(&self, data: Vec<u8>, filename: &str, content_type: &str) -> Result<String>;
+
+    async fn get(&self, cid: &str) -> Result<Vec<u8>>;
+
+    async fn pin(&self, cid: &str) -> Result<()>;
+
+    /// Release content so the backend may garbage collect it
+    async fn unpin(&self, cid: &str) -> Result<()>;
+
+    /// Delete content that is no longer pinned and return how many blocks went.
+    /// Backends that garbage collect on their own leave this to the node.
+    async fn collect_garbage(&self) -> Result<usize> {
+        Ok(0)
+    }
+
+    async fn exists(&self, cid: &str) -> Result<bool>;
+
+    /// URL clients can fetch the content from
+    fn public_url(&self, cid: &str) -> String;
+}
+
+/// Build the backend selected by `STORAGE_BACKEND`. Defaults to Pinata when IPFS
+/// credentials are configured and to the local directory otherwise.
+pub fn create_storage_backend(config: &Config) -> Result<Arc<dyn StorageBackend>> {
+    let has_pinata_credentials = config.ipfs_jwt_token.is_some()
+        || (config.ipfs_api_key.is_some() && config.ipfs_api_secret.is_some());
+    let selected = config.storage_backend.clone()
+        .unwrap_or_else(|| if has_pinata_credentials { "pinata" } else { "local" }.to_string());
+
+    let backend: Arc<dyn StorageBackend> = match selected.to_lowercase().as_str() {
+        "local" => Arc::new(LocalStorageBackend::new(&config.local_storage_dir)?),
+        "kubo" => Arc::new(KuboStorageBackend::new(&config.kubo_api_url, &config.ipfs_gateway_url)),
+        "pinata" => Arc::new(PinataStorageBackend::new(config)?),
+        other => return Err(anyhow!("Unknown STORAGE_BACKEND: {} (expected local, kubo or pinata)", other)),
+    };
+
+    println!("📦 Content storage backend: {}", backend.name());
+    Ok(backend)
+}
+
+/// CIDv1 of `data` stored as a single raw block (sha2-256, base32 multibase).
+///
+/// Matches `ipfs add --cid-version=1` for content that fits in one chunk (256 KiB).
+pub fn compute_cid_v1(data: &[u8]) -> String {
+    cid_to_string(&cid_v1_bytes(data))
+}
+
+/// Binary form of `compute_cid_v1`, as written into CAR files
+pub fn cid_v1_bytes(data: &[u8]) -> Vec<u8> {
+    let digest = Sha256::digest(data);
+
+    let mut bytes = Vec::with_capacity(4 + digest.len());
+    bytes.push(0x01); // CID version
+    bytes.push(RAW_CODEC);
+    bytes.push(SHA2_256_CODE);
+    bytes.push(SHA2_256_LENGTH);
+    bytes.extend_from_slice(&digest);
+    bytes
+}
+
+/// Multibase (base32) string form of a binary CID
+pub fn cid_to_string(cid: &[u8]) -> String {
+    format!("b{}", base32_lower(cid))
+}
+
+/// RFC 4648 base32, lowercase and unpadded as used by multibase `b`
+fn base32_lower(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+    let mut output = String::with_capacity((data.len() * 8).div_ceil(5));
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            output.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        output.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    output
+}
+
+/// CIDs end up in file paths and URLs, so only accept multibase alphanumerics
+fn validate_cid(cid: &str) -> Result<()> {
+    if cid.is_empty() || !cid.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(anyhow!("Invalid CID: {}", cid));
+    }
+    Ok(())
+}
+
+/// Stores blobs in a local directory keyed by CID, for offline development and CI.
+///
+/// Layout: `<dir>/blocks/<cid>` holds content and `<dir>/pins/<cid>` counts the pins on it.
+/// Identical content shares one block, so unpinning only drops a pin and
+/// `collect_garbage` deletes blocks nobody pins any more.
+pub struct LocalStorageBackend {
+    root: PathBuf,
+    // Serialises pin counting with block writes and garbage collection
+    pins: tokio::sync::Mutex<()>,
+}
+
+impl LocalStorageBackend {
+    pub fn new(dir: &str) -> Result<Self> {
+        let root = PathBuf::from(dir);
+        std::fs::create_dir_all(root.join("blocks"))?;
+        std::fs::create_dir_all(root.join("pins"))?;
+        Ok(Self { root, pins: tokio::sync::Mutex::new(()) })
+    }
+
+    fn block_path(&self, cid: &str) -> PathBuf {
+        self.root.join("blocks").join(cid)
+    }
+
+    fn pin_path(&self, cid: &str) -> PathBuf {
+        self.root.join("pins").join(cid)
+    }
+
+    /// Current pin count
+    async fn pin_count(&self, cid: &str) -> Result<u64> {
+        match tokio::fs::read_to_string(self.pin_path(cid)).await {
+            Ok(count) => count.trim().parse()
+                .map_err(|_| anyhow!("Pin marker for {} is corrupted: {:?}", cid, count)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn add_pin(&self, cid: &str) -> Result<()> {
+        let count = self.pin_count(cid).await? + 1;
+        tokio::fs::write(self.pin_path(cid), count.to_string()).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalStorageBackend {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn put(&self, data: Vec<u8>, filename: &str, _content_type: &str) -> Result<String> {
+        let cid = compute_cid_v1(&data);
+        let path = self.block_path(&cid);
+        let _pins = self.pins.lock().await;
+
+        if !tokio::fs::try_exists(&path).await? {
+            // Write then rename so readers never observe a partial block
+            let tmp_path = path.with_extension("tmp");
+            tokio::fs::write(&tmp_path, &data).await?;
+            tokio::fs::rename(&tmp_path, &path).await?;
+        }
+        self.add_pin(&cid).await?;
+
+        println!("💾 Stored {} ({} bytes) locally as {}", filename, data.len(), cid);
+        Ok(cid)
+    }
+
+    async fn get(&self, cid: &str) -> Result<Vec<u8>> {
+        validate_cid(cid)?;
+        let data = tokio::fs::read(self.block_path(cid)).await
+            .map_err(|e| anyhow!("Content {} not found in local storage: {}", cid, e))?;
+
+        if compute_cid_v1(&data) != cid {
+            return Err(anyhow!("Local block {} is corrupted (hash mismatch)", cid));
+        }
+        Ok(data)
+    }
+
+    async fn pin(&self, cid: &str) -> Result<()> {
+        validate_cid(cid)?;
+        let _pins = self.pins.lock().await;
+        if !self.exists(cid).await? {
+            return Err(anyhow!("Cannot pin {}: content not found in local storage", cid));
+        }
+        self.add_pin(cid).await
+    }
+
+    async fn unpin(&self, cid: &str) -> Result<()> {
+        validate_cid(cid)?;
+        let _pins = self.pins.lock().await;
+        match self.pin_count(cid).await? {
+            0 => Ok(()),
+            1 => match tokio::fs::remove_file(self.pin_path(cid)).await {
+                Ok(()) => Ok(()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(e.into()),
+            },
+            count => Ok(tokio::fs::write(self.pin_path(cid), (count - 1).to_string()).await?),
+        }
+    }
+
+    async fn collect_garbage(&self) -> Result<usize> {
+        let _pins = self.pins.lock().await;
+        let mut removed = 0;
+        let mut blocks = tokio::fs::read_dir(self.root.join("blocks")).await?;
+        while let Some(entry) = blocks.next_entry().await? {
+            let name = entry.file_name();
+            let Some(cid) = name.to_str() else {
+                continue;
+            };
+            // Leftover `.tmp` files from interrupted writes have no pin either
+            if !tokio::fs::try_exists(self.pin_path(cid)).await? {
+                tokio::fs::remove_file(entry.path()).await?;
+                removed += 1;
+            }
+        }
+        if removed > 0 {
+            println!("🧹 Collected {} unpinned local blocks", removed);
+        }
+        Ok(removed)
+    }
+
+    async fn exists(&self, cid: &str) -> Result<bool> {
+        validate_cid(cid)?;
+        Ok(tokio::fs::try_exists(self.block_path(cid)).await?)
+    }
+
+    fn public_url(&self, cid: &str) -> String {
+        // Served by the API itself (see `storage_routes`)
+        format!("/api/v1/storage/{}", cid)
+    }
+}
+
+/// Talks to a Kubo (go-ipfs) node over its HTTP RPC API
+pub struct KuboStorageBackend {
+    api_url: String,
+    gateway_url: String,
+    client: reqwest::Client,
+}
+
+impl KuboStorageBackend {
+    pub fn new(api_url: &str, gateway_url: &str) -> Self {
+        Self {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            gateway_url: gateway_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Kubo RPC endpoints only accept POST
+    async fn rpc(&self, command: &str, query: &[(&str, &str)]) -> Result<reqwest::Response> {
+        let url = format!("{}/api/v0/{}", self.api_url, command);
+        let response = self.client.post(&url).query(query).send().await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Kubo {} failed ({}): {}", command, status, error_text));
+        }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl StorageBackend for KuboStorageBackend {
+    fn name(&self) -> &'static str {
+        "kubo"
+    }
+
+    async fn put(&self, data: Vec<u8>, filename: &str, content_type: &str) -> Result<String> {
+        let size = data.len();
+        let part = reqwest::multipart::Part::bytes(data)
+            .file_name(filename.to_string())
+            .mime_str(content_type)?;
+        let form = reqwest::multipart::Form::new().part("file", part);
+
+        let url = format!("{}/api/v0/add", self.api_url);
+        let response = self.client
+            .post(&url)
+            .query(&[("cid-version", "1"), ("pin", "true")])
+            .multipart(form)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Kubo add failed ({}): {}", status, error_text));
+        }
+
+        let json: serde_json::Value = response.json().await?;
+        let cid = json.get("Hash")
+            .and_then(|hash| hash.as_str())
+            .ok_or_else(|| anyhow!("Kubo add response missing Hash: {}", json))?
+            .to_string();
+
+        println!("🛰️ Added {} ({} bytes) to Kubo as {}", filename, size, cid);
+        Ok(cid)
+    }
+
+    async fn get(&self, cid: &str) -> Result<Vec<u8>> {
+        let response = self.rpc("cat", &[("arg", cid)]).await?;
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    async fn pin(&self, cid: &str) -> Result<()> {
+        self.rpc("pin/add", &[("arg", cid)]).await?;
+        Ok(())
+    }
+
+    async fn unpin(&self, cid: &str) -> Result<()> {
+        self.rpc("pin/rm", &[("arg", cid)]).await?;
+        Ok(())
+    }
+
+    async fn exists(&self, cid: &str) -> Result<bool> {
+        // Offline so a missing block is reported instead of fetched from the network
+        Ok(self.rpc("block/stat", &[("arg", cid), ("offline", "true")]).await.is_ok())
+    }
+
+    fn public_url(&self, cid: &str) -> String {
+        format!("{}/{}", self.gateway_url, cid)
+    }
+}
+
+/// Pinata pinning service. Uploads use the v3 Files API with a JWT, or the legacy
+/// `pinFileToIPFS` endpoint when only an API key and secret are configured.
+pub struct PinataStorageBackend {
+    jwt_token: Option<String>,
+    api_key: Option<String>,
+    api_secret: Option<String>,
+    gateway_url: String,
+    client: reqwest::Client,
+}
+
+impl PinataStorageBackend {
+    pub fn new(config: &Config) -> Result<Self> {
+        let has_key_pair = config.ipfs_api_key.is_some() && config.ipfs_api_secret.is_some();
+        if config.ipfs_jwt_token.is_none() && !has_key_pair {
+            return Err(anyhow!("Pinata storage needs IPFS_JWT_TOKEN or IPFS_API_KEY and IPFS_API_SECRET"));
+        }
+
+        Ok(Self {
+            jwt_token: config.ipfs_jwt_token.clone(),
+            api_key: config.ipfs_api_key.clone(),
+            api_secret: config.ipfs_api_secret.clone(),
+            gateway_url: config.ipfs_gateway_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        })
+    }
+
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match (&self.jwt_token, &self.api_key, &self.api_secret) {
+            (Some(jwt), _, _) => request.bearer_auth(jwt),
+            (None, Some(key), Some(secret)) => request
+                .header("pinata_api_key", key)
+                .header("pinata_secret_api_key", secret),
+            _ => request,
+        }
+    }
+
+    async fn check(response: reqwest::Response, action: &str) -> Result<reqwest::Response> {
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            println!("❌ Pinata {} error response: {}", action, error_text);
+            return Err(anyhow!("Pinata {} failed ({}): {}", action, status, error_text));
+        }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl StorageBackend for PinataStorageBackend {
+    fn name(&self) -> &'static str {
+        "pinata"
+    }
+
+    async fn put(&self, data: Vec<u8>, filename: &str, content_type: &str) -> Result<String> {
+        let size = data.len();
+        let part = reqwest::multipart::Part::bytes(data)
+            .file_name(filename.to_string())
+            .mime_str(content_type)?;
+
+        let request = if self.jwt_token.is_some() {
+            // v3 uploads are private unless the public network is requested, and
+            // private files are not served by the IPFS gateway
+            let form = reqwest::multipart::Form::new()
+                .part("file", part)
+                .text("network", "public");
+            self.client.post(PINATA_UPLOAD_URL).multipart(form)
+        } else {
+            let form = reqwest::multipart::Form::new().part("file", part);
+            self.client.post(format!("{}/pinning/pinFileToIPFS", PINATA_API_URL)).multipart(form)
+        };
+
+        let response = Self::check(self.authorize(request).send().await?, "upload").await?;
+        let json: serde_json::Value = response.json().await?;
+
+        // v3: { "data": { "cid": "..." } }, legacy: { "IpfsHash": "..." }
+        let cid = json.get("data")
+            .and_then(|data| data.get("cid"))
+            .or_else(|| json.get("IpfsHash"))
+            .or_else(|| json.get("cid"))
+            .and_then(|cid| cid.as_str())
+            .filter(|cid| !cid.is_empty())
+            .ok_or_else(|| anyhow!("No CID found in Pinata response: {}", json))?
+            .to_string();
+
+        println!("📌 Pinned {} ({} bytes) to Pinata as {}", filename, size, cid);
+        Ok(cid)
+    }
+
+    async fn get(&self, cid: &str) -> Result<Vec<u8>> {
+        let url = format!("{}/{}", self.gateway_url, cid);
+        let response = Self::check(self.client.get(&url).send().await?, "gateway fetch").await?;
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    async fn pin(&self, cid: &str) -> Result<()> {
+        let request = self.client
+            .post(format!("{}/pinning/pinByHash", PINATA_API_URL))
+            .json(&serde_json::json!({ "hashToPin": cid }));
+        Self::check(self.authorize(request).send().await?, "pin").await?;
+        Ok(())
+    }
+
+    async fn unpin(&self, cid: &str) -> Result<()> {
+        let request = self.client.delete(format!("{}/pinning/unpin/{}", PINATA_API_URL, cid));
+        Self::check(self.authorize(request).send().await?, "unpin").await?;
+        Ok(())
+    }
+
+    async fn exists(&self, cid: &str) -> Result<bool> {
+        let request = self.client
+            .get(format!("{}/data/pinList", PINATA_API_URL))
+            .query(&[("hashContains", cid), ("status", "pinned")]);
+        let response = Self::check(self.authorize(request).send().await?, "pin list").await?;
+        let json: serde_json::Value = response.json().await?;
+
+        Ok(json.get("count").and_then(|count| count.as_u64()).unwrap_or(0) > 0)
+    }
+
+    fn public_url(&self, cid: &str) -> String {
+        format!("{}/{}", self.gateway_url, cid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_backend(name: &str) -> (LocalStorageBackend, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("storage_backend_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        (LocalStorageBackend::new(dir.to_str().unwrap()).unwrap(), dir)
+    }
+
+    #[test]
+    fn cid_v1_matches_ipfs_raw_leaves() {
+        assert_eq!(compute_cid_v1(b""), "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku");
+        assert_eq!(compute_cid_v1(b"hello world"), "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e");
+    }
+
+    #[test]
+    fn cid_v1_bytes_layout() {
+        let bytes = cid_v1_bytes(b"hello world");
+        assert_eq!(bytes.len(), 36);
+        assert_eq!(&bytes[..4], &[0x01, RAW_CODEC, SHA2_256_CODE, SHA2_256_LENGTH]);
+        assert_eq!(&bytes[4..], Sha256::digest(b"hello world").as_slice());
+        assert_eq!(cid_to_string(&bytes), compute_cid_v1(b"hello world"));
+    }
+
+    #[test]
+    fn base32_matches_rfc4648_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "my"),
+            ("fo", "mzxq"),
+            ("foo", "mzxw6"),
+            ("foob", "mzxw6yq"),
+            ("fooba", "mzxw6ytb"),
+            ("foobar", "mzxw6ytboi"),
+        ];
+        for (input, expected) in vectors {
+            assert_eq!(base32_lower(input.as_bytes()), expected, "base32 of {:?}", input);
+        }
+    }
+
+    #[test]
+    fn validate_cid_rejects_paths() {
+        assert!(validate_cid(&compute_cid_v1(b"data")).is_ok());
+        assert!(validate_cid("QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG").is_ok());
+        for cid in ["", "../blocks", "bafy/evil", "bafy.tmp", "bafy evil"] {
+            assert!(validate_cid(cid).is_err(), "{:?} should be rejected", cid);
+        }
+    }
+
+    #[tokio::test]
+    async fn shared_block_survives_until_last_unpin() {
+        let (backend, dir) = test_backend("refcount");
+
+        let cid = backend.put(b"shared".to_vec(), "a.json", "application/json").await.unwrap();
+        assert_eq!(backend.put(b"shared".to_vec(), "b.json", "application/json").await.unwrap(), cid);
+        backend.pin(&cid).await.unwrap();
+
+        for _ in 0..2 {
+            backend.unpin(&cid).await.unwrap();
+            assert_eq!(backend.collect_garbage().await.unwrap(), 0);
+            assert_eq!(backend.get(&cid).await.unwrap(), b"shared");
+        }
+
+        backend.unpin(&cid).await.unwrap();
+        // Unpinning never deletes the block itself
+        assert!(backend.exists(&cid).await.unwrap());
+        assert_eq!(backend.collect_garbage().await.unwrap(), 1);
+        assert!(!backend.exists(&cid).await.unwrap());
+
+        // Unpinning content that is already gone is not an error
+        backend.unpin(&cid).await.unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn corrupted_pin_markers_are_errors() {
+        let (backend, dir) = test_backend("corrupt_pin");
+
+        let cid = backend.put(b"pinned".to_vec(), "x.json", "application/json").await.unwrap();
+        std::fs::write(backend.pin_path(&cid), "x.json").unwrap();
+        assert!(backend.pin(&cid).await.is_err());
+        assert!(backend.unpin(&cid).await.is_err());
+        // The block is still pinned, so it is not collected
+        assert_eq!(backend.collect_garbage().await.unwrap(), 0);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn get_detects_corrupted_blocks() {
+        let (backend, dir) = test_backend("corrupt");
+
+        let cid = backend.put(b"original".to_vec(), "x.bin", "application/octet-stream").await.unwrap();
+        std::fs::write(backend.block_path(&cid), b"tampered").unwrap();
+        assert!(backend.get(&cid).await.is_err());
+        assert!(backend.pin("bafkreimissing").await.is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,5 +1,7 @@
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
@@ -125,10 +127,19 @@ pub struct TraitRange {
     pub optimal_value: Option<u8>,
 }
 
+/// Durable storage for templates, rehydrated into `TemplateManager` at startup
+#[async_trait]
+pub trait TemplateRepository: Send + Sync {
+    async fn save_template(&self, template: &PromptTemplate) -> anyhow::Result<()>;
+    async fn load_templates(&self) -> anyhow::Result<Vec<PromptTemplate>>;
+    async fn delete_template(&self, template_id: &str) -> anyhow::Result<()>;
+}
+
 /// Template management service
 pub struct TemplateManager {
     templates: HashMap<String, PromptTemplate>,
     user_templates: HashMap<String, Vec<String>>, // user_address -> template_ids
+    repository: Option<Arc<dyn TemplateRepository>>,
 }
 
 impl TemplateManager {
@@ -136,6 +147,7 @@ impl TemplateManager {
         let mut manager = Self {
             templates: HashMap::new(),
             user_templates: HashMap::new(),
+            repository: None,
         };
         
         // Initialize with pre-built templates
@@ -143,6 +155,34 @@ impl TemplateManager {
         manager
     }
     
+    /// Attach durable storage and rehydrate custom templates (and prebuilt usage/ratings)
+    pub async fn with_repository(mut self, repository: Arc<dyn TemplateRepository>) -> anyhow::Result<Self> {
+        let stored = repository.load_templates().await?;
+        let count = stored.len();
+        
+        for template in stored {
+            if template.is_custom {
+                let user_templates = self.user_templates.entry(template.created_by.clone()).or_insert_with(Vec::new);
+                if !user_templates.contains(&template.id) {
+                    user_templates.push(template.id.clone());
+                }
+            }
+            self.templates.insert(template.id.clone(), template);
+        }
+        
+        println!("📝 Rehydrated {} templates from database", count);
+        self.repository = Some(repository);
+        Ok(self)
+    }
+    
+    async fn persist_template(&self, template_id: &str) {
+        if let (Some(repository), Some(template)) = (&self.repository, self.templates.get(template_id)) {
+            if let Err(e) = repository.save_template(template).await {
+                println!("⚠️  Failed to persist template {}: {}", template_id, e);
+            }
+        }
+    }
+    
     /// Initialize pre-built template categories
     fn initialize_prebuilt_templates(&mut self) {
         // Companion Templates
@@ -171,7 +211,7 @@ impl TemplateManager {
     }
     
     /// Create a new custom template
-    pub fn create_template(&mut self, template: PromptTemplate, creator: &str) -> Result<String, String> {
+    pub async fn create_template(&mut self, template: PromptTemplate, creator: &str) -> Result<String, String> {
         let template_id = Uuid::new_v4().to_string();
         let mut new_template = template;
         new_template.id = template_id.clone();
@@ -191,9 +231,58 @@ impl TemplateManager {
             .or_insert_with(Vec::new)
             .push(template_id.clone());
         
+        self.persist_template(&template_id).await;
+        
         Ok(template_id)
     }
     
+    /// Replace a custom template's content, keeping its id, creator, usage and ratings
+    pub async fn update_template(&mut self, template_id: &str, update: PromptTemplate) -> Result<(), String> {
+        let existing = match self.templates.get(template_id) {
+            Some(template) if template.is_custom => template,
+            Some(_) => return Err("Prebuilt templates cannot be changed".to_string()),
+            None => return Err("Template not found".to_string()),
+        };
+        
+        let updated = PromptTemplate {
+            id: existing.id.clone(),
+            created_by: existing.created_by.clone(),
+            is_custom: true,
+            usage_count: existing.usage_count,
+            ipfs_hash: existing.ipfs_hash.clone(),
+            created_at: existing.created_at,
+            updated_at: Utc::now(),
+            rating: existing.rating,
+            rating_count: existing.rating_count,
+            ..update
+        };
+        self.validate_template(&updated)?;
+        
+        self.templates.insert(template_id.to_string(), updated);
+        self.persist_template(template_id).await;
+        Ok(())
+    }
+    
+    /// Delete a custom template from memory and durable storage
+    pub async fn delete_template(&mut self, template_id: &str) -> Result<PromptTemplate, String> {
+        match self.templates.get(template_id) {
+            Some(template) if template.is_custom => {}
+            Some(_) => return Err("Prebuilt templates cannot be deleted".to_string()),
+            None => return Err("Template not found".to_string()),
+        }
+        
+        if let Some(repository) = &self.repository {
+            repository.delete_template(template_id).await
+                .map_err(|e| format!("Failed to delete template {}: {}", template_id, e))?;
+        }
+        
+        let template = self.templates.remove(template_id).ok_or("Template not found")?;
+        if let Some(template_ids) = self.user_templates.get_mut(&template.created_by) {
+            template_ids.retain(|id| id != template_id);
+        }
+        Ok(template)
+    }
+    
     /// Get template by ID
     pub fn get_template(&self, template_id: &str) -> Option<&PromptTemplate> {
         self.templates.get(template_id)
@@ -360,7 +449,7 @@ impl TemplateManager {
     }
     
     /// Rate a template (1-5 stars)
-    pub fn rate_template(&mut self, template_id: &str, rating: u8) -> Result<(), String> {
+    pub async fn rate_template(&mut self, template_id: &str, rating: u8) -> Result<(), String> {
         if rating < 1 || rating > 5 {
             return Err("Rating must be between 1 and 5".to_string());
         }
@@ -370,6 +459,7 @@ impl TemplateManager {
             let total_rating = template.rating * template.rating_count as f32;
             template.rating_count += 1;
             template.rating = (total_rating + rating as f32) / template.rating_count as f32;
+            self.persist_template(template_id).await;
             Ok(())
         } else {
             Err("Template not found".to_string())
@@ -377,9 +467,10 @@ impl TemplateManager {
     }
     
     /// Increment usage count for analytics
-    pub fn increment_usage(&mut self, template_id: &str) {
+    pub async fn increment_usage(&mut self, template_id: &str) {
         if let Some(template) = self.templates.get_mut(template_id) {
             template.usage_count += 1;
+            self.persist_template(template_id).await;
         }
     }
     
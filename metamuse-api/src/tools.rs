@@ -1,64 +1,693 @@
-use alith::{Agent, Chat, StructureTool, ToolError, LLM};
+use alith::{StructureTool, Tool, ToolError};
 use async_trait::async_trait;
+use chrono::{FixedOffset, Utc};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
- 
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::config::Config;
+use crate::persist_memory::MemorySystem;
+use crate::plugin_system::{Plugin, PluginSystem};
+use crate::semantic_search::SemanticSearchService;
+use crate::template_system::TemplateManager;
+
+/// Results returned by search-style tools unless the muse asks for fewer
+const DEFAULT_TOOL_RESULTS: usize = 5;
+/// Calculator input limits; the parser recurses once per nesting level
+const MAX_EXPRESSION_CHARS: usize = 256;
+const MAX_EXPRESSION_DEPTH: usize = 32;
+
+/// A tool call a muse made while answering. Stored with the reply and signed into its commitment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub tool: String,
+    pub arguments: serde_json::Value,
+    /// Result as the muse saw it, truncated to `TOOL_RESULT_MAX_CHARS`
+    pub result: String,
+    pub success: bool,
+    pub duration_ms: u64,
+}
+
+impl ToolCall {
+    /// Deterministic encoding hashed into the interaction commitment. Every field is
+    /// length-prefixed so text cannot move between fields without changing the bytes.
+    pub fn commitment_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for field in [self.tool.as_bytes(), self.arguments.to_string().as_bytes(), self.result.as_bytes()] {
+            data.extend_from_slice(&(field.len() as u64).to_be_bytes());
+            data.extend_from_slice(field);
+        }
+        data.push(self.success as u8);
+        data
+    }
+}
+
+fn tool_error(e: anyhow::Error) -> ToolError {
+    ToolError::NormalError(e.into())
+}
+
+#[derive(JsonSchema, Serialize, Deserialize)]
+pub struct SearchInput {
+    /// What to look for
+    pub query: String,
+    /// Maximum number of results
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Memories of earlier conversations between this muse and the user
+pub struct MemoryLookup {
+    memory_system: Arc<MemorySystem>,
+    muse_id: String,
+    user_address: String,
+    max_results: usize,
+}
+
+#[async_trait]
+impl StructureTool for MemoryLookup {
+    type Input = SearchInput;
+    type Output = Vec<String>;
+
+    fn name(&self) -> &str {
+        "memory_lookup"
+    }
+
+    fn description(&self) -> &str {
+        "Recall what you remember from earlier conversations with this user"
+    }
+
+    async fn run_with_args(&self, input: Self::Input) -> Result<Self::Output, ToolError> {
+        let limit = input.limit.unwrap_or(DEFAULT_TOOL_RESULTS).clamp(1, self.max_results);
+        self.memory_system
+            .get_user_contextual_memories(&self.muse_id, &self.user_address, &input.query, limit)
+            .await
+            .map_err(tool_error)
+    }
+}
+
+#[derive(Serialize)]
+pub struct SearchHit {
+    pub content: String,
+    pub content_type: String,
+    pub relevance: f64,
+}
+
+/// Semantic search over indexed messages of this muse and user
+pub struct SemanticSearch {
+    semantic_search: Arc<SemanticSearchService>,
+    muse_id: String,
+    user_address: String,
+    max_results: usize,
+}
+
+#[async_trait]
+impl StructureTool for SemanticSearch {
+    type Input = SearchInput;
+    type Output = Vec<SearchHit>;
+
+    fn name(&self) -> &str {
+        "semantic_search"
+    }
+
+    fn description(&self) -> &str {
+        "Search past chat messages with this user by meaning"
+    }
+
+    async fn run_with_args(&self, input: Self::Input) -> Result<Self::Output, ToolError> {
+        let limit = input.limit.unwrap_or(DEFAULT_TOOL_RESULTS).clamp(1, self.max_results);
+        let results = self.semantic_search
+            .get_user_muse_memories(&self.user_address, &self.muse_id, &input.query, limit)
+            .await
+            .map_err(tool_error)?;
+
+        Ok(results
+            .into_iter()
+            .map(|result| SearchHit {
+                content: result.content,
+                content_type: result.content_type,
+                relevance: result.relevance_score,
+            })
+            .collect())
+    }
+}
+
 #[derive(JsonSchema, Serialize, Deserialize)]
-pub struct Input {
-    pub x: usize,
-    pub y: usize,
+pub struct TimeInput {
+    /// Offset from UTC in minutes, e.g. 120 for UTC+2; defaults to UTC
+    #[serde(default)]
+    pub utc_offset_minutes: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct TimeOutput {
+    pub datetime: String,
+    pub weekday: String,
+    pub unix_timestamp: i64,
 }
- 
-pub struct Adder;
+
+/// Current date and time
+pub struct CurrentTime;
+
 #[async_trait]
-impl StructureTool for Adder {
-    type Input = Input;
-    type Output = usize;
- 
+impl StructureTool for CurrentTime {
+    type Input = TimeInput;
+    type Output = TimeOutput;
+
     fn name(&self) -> &str {
-        "adder"
+        "current_time"
     }
- 
+
     fn description(&self) -> &str {
-        "Add x and y together"
+        "Get the current date, time and weekday"
     }
- 
+
     async fn run_with_args(&self, input: Self::Input) -> Result<Self::Output, ToolError> {
-        let result = input.x + input.y;
-        Ok(result)
+        let offset = FixedOffset::east_opt(input.utc_offset_minutes.unwrap_or(0) * 60)
+            .ok_or_else(|| tool_error(anyhow::anyhow!("UTC offset out of range")))?;
+        let now = Utc::now().with_timezone(&offset);
+
+        Ok(TimeOutput {
+            datetime: now.to_rfc3339(),
+            weekday: now.format("%A").to_string(),
+            unix_timestamp: now.timestamp(),
+        })
     }
 }
- 
-pub struct Subtract;
+
+#[derive(JsonSchema, Serialize, Deserialize)]
+pub struct CalculatorInput {
+    /// Arithmetic expression using + - * / % ^ and parentheses, e.g. "(3 + 4) * 2"
+    pub expression: String,
+}
+
+/// Evaluates arithmetic so muses don't have to guess at numbers
+pub struct Calculator;
+
 #[async_trait]
-impl StructureTool for Subtract {
-    type Input = Input;
-    type Output = usize;
- 
+impl StructureTool for Calculator {
+    type Input = CalculatorInput;
+    type Output = f64;
+
     fn name(&self) -> &str {
-        "subtract"
+        "calculator"
     }
- 
+
     fn description(&self) -> &str {
-        "Subtract y from x (i.e.: x - y)"
+        "Evaluate an arithmetic expression"
     }
- 
+
     async fn run_with_args(&self, input: Self::Input) -> Result<Self::Output, ToolError> {
-        let result = input.x - input.y;
-        Ok(result)
-    }
-}
- 
-#[tokio::main]
-async fn main() -> Result<(), anyhow::Error> {
-    let model = LLM::from_model_name("gpt-4")?;
-    let agent = Agent::new("simple agent", model)
-        .preamble("You are a calculator here to help the user perform arithmetic operations. Use the tools provided to answer the user's question.")
-        .tool(Adder).await
-        .tool(Subtract).await;
-    let response = agent.prompt("Calculate 10 - 3").await?;
- 
-    println!("{}", response);
- 
-    Ok(())
-}
\ No newline at end of file
+        evaluate(&input.expression).map_err(tool_error)
+    }
+}
+
+#[derive(Serialize)]
+pub struct TemplateSummary {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub tags: Vec<String>,
+}
+
+/// Prompt templates matching a search, so a muse can suggest one to the user
+pub struct TemplateLookup {
+    template_manager: Arc<Mutex<TemplateManager>>,
+}
+
+#[async_trait]
+impl StructureTool for TemplateLookup {
+    type Input = SearchInput;
+    type Output = Vec<TemplateSummary>;
+
+    fn name(&self) -> &str {
+        "template_lookup"
+    }
+
+    fn description(&self) -> &str {
+        "Find prompt templates by name, description or tag"
+    }
+
+    async fn run_with_args(&self, input: Self::Input) -> Result<Self::Output, ToolError> {
+        let limit = input.limit.unwrap_or(DEFAULT_TOOL_RESULTS).max(1);
+        let template_manager = self.template_manager.lock().await;
+
+        Ok(template_manager
+            .search_templates(&input.query)
+            .into_iter()
+            .take(limit)
+            .map(|template| TemplateSummary {
+                id: template.id.clone(),
+                name: template.name.clone(),
+                description: template.description.clone(),
+                tags: template.tags.clone(),
+            })
+            .collect())
+    }
+}
+
+/// Tools one muse may call while answering one user
+pub struct Toolbox {
+    tools: Vec<Box<dyn Tool>>,
+    plugins: Vec<Plugin>,
+    plugin_system: Arc<PluginSystem>,
+    result_max_chars: usize,
+}
+
+impl Toolbox {
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty() && self.plugins.is_empty()
+    }
+
+    fn has_tool(&self, name: &str) -> bool {
+        self.tools.iter().any(|tool| tool.name() == name) || self.plugins.iter().any(|plugin| plugin.id == name)
+    }
+
+    /// Tool list and call format, appended to the system prompt
+    pub fn system_prompt_section(&self) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let mut lines: Vec<String> = self.tools
+            .iter()
+            .map(|tool| {
+                let definition = tool.definition();
+                format!("- {}: {}. Arguments: {}", definition.name, definition.description, argument_summary(&definition.parameters))
+            })
+            .collect();
+        lines.extend(self.plugins.iter().map(|plugin| {
+            format!("- {}: {} (plugin). Arguments: any JSON object", plugin.id, plugin.description)
+        }));
+
+        Some(format!(
+            "You can use these tools:\n{}\n\
+            To use a tool, reply with only a JSON object such as {{\"tool\": \"calculator\", \"arguments\": {{\"expression\": \"2 + 2\"}}}} \
+            and nothing else. You will be shown the result and can then answer or use another tool. \
+            When no tool is needed, answer directly.",
+            lines.join("\n")
+        ))
+    }
+
+    /// The tool call in a model reply: a JSON object naming a known tool, optionally inside a
+    /// code fence. Anything else is an answer.
+    pub fn parse_call(&self, reply: &str) -> Option<(String, serde_json::Value)> {
+        parse_call(reply, |tool| self.has_tool(tool))
+    }
+
+    /// Run a tool. Failures are recorded in the call and shown to the muse rather than
+    /// ending the turn.
+    pub async fn call(&self, tool: &str, arguments: serde_json::Value) -> ToolCall {
+        let start_time = std::time::Instant::now();
+
+        let outcome = if let Some(plugin) = self.plugins.iter().find(|plugin| plugin.id == tool) {
+            match self.plugin_system.execute_plugin(&plugin.id, arguments.clone()).await {
+                Ok(execution) if execution.success => Ok(execution.output.unwrap_or(serde_json::Value::Null).to_string()),
+                Ok(execution) => Err(execution.error_message.unwrap_or_else(|| "Plugin failed".to_string())),
+                Err(e) => Err(e.to_string()),
+            }
+        } else if let Some(builtin) = self.tools.iter().find(|t| t.name() == tool) {
+            builtin.run(&arguments.to_string()).await.map_err(|e| e.to_string())
+        } else {
+            Err(format!("Unknown tool: {}", tool))
+        };
+
+        let success = outcome.is_ok();
+        let result = outcome.unwrap_or_else(|error| format!("Error: {}", error));
+        let duration_ms = start_time.elapsed().as_millis() as u64;
+        println!("🔧 Tool {} {} in {}ms", tool, if success { "succeeded" } else { "failed" }, duration_ms);
+
+        ToolCall {
+            tool: tool.to_string(),
+            arguments,
+            result: truncate_chars(&result, self.result_max_chars),
+            success,
+            duration_ms,
+        }
+    }
+}
+
+/// Builds the toolbox for each reply from the built-in tools and the muse's installed plugins
+pub struct ToolRegistry {
+    memory_system: Arc<MemorySystem>,
+    semantic_search: Arc<SemanticSearchService>,
+    template_manager: Arc<Mutex<TemplateManager>>,
+    plugin_system: Arc<PluginSystem>,
+    enabled: bool,
+    max_calls_per_turn: usize,
+    result_max_chars: usize,
+    max_results: usize,
+}
+
+impl ToolRegistry {
+    pub fn new(
+        memory_system: Arc<MemorySystem>,
+        semantic_search: Arc<SemanticSearchService>,
+        template_manager: Arc<Mutex<TemplateManager>>,
+        plugin_system: Arc<PluginSystem>,
+        config: &Config,
+    ) -> Self {
+        Self {
+            memory_system,
+            semantic_search,
+            template_manager,
+            plugin_system,
+            enabled: config.tools_enabled,
+            max_calls_per_turn: config.tool_max_calls_per_turn,
+            result_max_chars: config.tool_result_max_chars.max(100),
+            max_results: config.context_memory_limit.max(1),
+        }
+    }
+
+    pub fn max_calls_per_turn(&self) -> usize {
+        self.max_calls_per_turn
+    }
+
+    /// Tools available to `muse_id` answering `user_address`; empty when tool calling is disabled
+    pub async fn toolbox(&self, muse_id: &str, user_address: &str) -> Toolbox {
+        let mut toolbox = Toolbox {
+            tools: Vec::new(),
+            plugins: Vec::new(),
+            plugin_system: self.plugin_system.clone(),
+            result_max_chars: self.result_max_chars,
+        };
+        if !self.enabled || self.max_calls_per_turn == 0 {
+            return toolbox;
+        }
+
+        toolbox.tools = vec![
+            Box::new(MemoryLookup {
+                memory_system: self.memory_system.clone(),
+                muse_id: muse_id.to_string(),
+                user_address: user_address.to_string(),
+                max_results: self.max_results,
+            }),
+            Box::new(SemanticSearch {
+                semantic_search: self.semantic_search.clone(),
+                muse_id: muse_id.to_string(),
+                user_address: user_address.to_string(),
+                max_results: self.max_results,
+            }),
+            Box::new(CurrentTime),
+            Box::new(Calculator),
+            Box::new(TemplateLookup { template_manager: self.template_manager.clone() }),
+        ];
+
+        let plugins = self.plugin_system.get_muse_plugins(muse_id).await.unwrap_or_default();
+        for plugin in plugins.into_iter().filter(|plugin| plugin.active) {
+            if !toolbox.has_tool(&plugin.id) {
+                toolbox.plugins.push(plugin);
+            }
+        }
+        toolbox
+    }
+}
+
+/// `{"tool": ..., "arguments": {...}}` (or `name`/`args`) naming a tool `is_tool` accepts
+fn parse_call(reply: &str, is_tool: impl Fn(&str) -> bool) -> Option<(String, serde_json::Value)> {
+    let trimmed = reply.trim();
+    let body = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|rest| rest.strip_suffix("```"))
+        .unwrap_or(trimmed)
+        .trim();
+    if !body.starts_with('{') {
+        return None;
+    }
+
+    let value: serde_json::Value = serde_json::from_str(body).ok()?;
+    let tool = value.get("tool").or_else(|| value.get("name"))?.as_str()?.to_string();
+    if !is_tool(&tool) {
+        return None;
+    }
+    let arguments = value
+        .get("arguments")
+        .or_else(|| value.get("args"))
+        .cloned()
+        .unwrap_or_else(|| serde_json::json!({}));
+    Some((tool, arguments))
+}
+
+/// `{"name": "type", ...}` from a JSON schema, short enough for a small model's prompt
+fn argument_summary(parameters: &serde_json::Value) -> String {
+    let Some(properties) = parameters.get("properties").and_then(|p| p.as_object()) else {
+        return "{}".to_string();
+    };
+    let fields: serde_json::Map<String, serde_json::Value> = properties
+        .iter()
+        .map(|(name, schema)| {
+            let kind = match schema.get("type") {
+                Some(serde_json::Value::String(kind)) => kind.clone(),
+                Some(serde_json::Value::Array(kinds)) => kinds
+                    .iter()
+                    .filter_map(|kind| kind.as_str())
+                    .find(|kind| *kind != "null")
+                    .unwrap_or("any")
+                    .to_string(),
+                _ => "any".to_string(),
+            };
+            (name.clone(), serde_json::Value::String(kind))
+        })
+        .collect();
+    serde_json::Value::Object(fields).to_string()
+}
+
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// Evaluate `+ - * / % ^` with parentheses and unary minus; `^` binds tightest and is
+/// right-associative. Long or deeply nested expressions are rejected.
+fn evaluate(expression: &str) -> anyhow::Result<f64> {
+    let tokens: Vec<char> = expression.chars().filter(|c| !c.is_whitespace()).collect();
+    if tokens.len() > MAX_EXPRESSION_CHARS {
+        return Err(anyhow::anyhow!("Expression is longer than {} characters", MAX_EXPRESSION_CHARS));
+    }
+    let mut position = 0;
+    let value = parse_sum(&tokens, &mut position, 0)?;
+    if position != tokens.len() {
+        return Err(anyhow::anyhow!("Unexpected '{}' in expression", tokens[position]));
+    }
+    if !value.is_finite() {
+        return Err(anyhow::anyhow!("Result is not a finite number"));
+    }
+    Ok(value)
+}
+
+fn parse_sum(tokens: &[char], position: &mut usize, depth: usize) -> anyhow::Result<f64> {
+    let mut value = parse_product(tokens, position, depth)?;
+    while let Some(&op) = tokens.get(*position).filter(|c| matches!(c, '+' | '-')) {
+        *position += 1;
+        let rhs = parse_product(tokens, position, depth)?;
+        value = if op == '+' { value + rhs } else { value - rhs };
+    }
+    Ok(value)
+}
+
+fn parse_product(tokens: &[char], position: &mut usize, depth: usize) -> anyhow::Result<f64> {
+    let mut value = parse_power(tokens, position, depth)?;
+    while let Some(&op) = tokens.get(*position).filter(|c| matches!(c, '*' | '/' | '%')) {
+        *position += 1;
+        let rhs = parse_power(tokens, position, depth)?;
+        if op != '*' && rhs == 0.0 {
+            return Err(anyhow::anyhow!("Division by zero"));
+        }
+        value = match op {
+            '*' => value * rhs,
+            '/' => value / rhs,
+            _ => value % rhs,
+        };
+    }
+    Ok(value)
+}
+
+fn parse_power(tokens: &[char], position: &mut usize, depth: usize) -> anyhow::Result<f64> {
+    let base = parse_unary(tokens, position, depth)?;
+    if tokens.get(*position) == Some(&'^') {
+        *position += 1;
+        let exponent = parse_power(tokens, position, depth + 1)?;
+        return Ok(base.powf(exponent));
+    }
+    Ok(base)
+}
+
+// Every recursive path passes through here, so this is where depth is checked
+fn parse_unary(tokens: &[char], position: &mut usize, depth: usize) -> anyhow::Result<f64> {
+    if depth > MAX_EXPRESSION_DEPTH {
+        return Err(anyhow::anyhow!("Expression is nested more than {} levels deep", MAX_EXPRESSION_DEPTH));
+    }
+    match tokens.get(*position) {
+        Some('-') => {
+            *position += 1;
+            Ok(-parse_power(tokens, position, depth + 1)?)
+        }
+        Some('+') => {
+            *position += 1;
+            parse_unary(tokens, position, depth + 1)
+        }
+        Some('(') => {
+            *position += 1;
+            let value = parse_sum(tokens, position, depth + 1)?;
+            if tokens.get(*position) != Some(&')') {
+                return Err(anyhow::anyhow!("Missing ')' in expression"));
+            }
+            *position += 1;
+            Ok(value)
+        }
+        Some(c) if c.is_ascii_digit() || *c == '.' => {
+            let start = *position;
+            while tokens.get(*position).is_some_and(|c| c.is_ascii_digit() || *c == '.') {
+                *position += 1;
+            }
+            let number: String = tokens[start..*position].iter().collect();
+            number.parse().map_err(|_| anyhow::anyhow!("Invalid number '{}'", number))
+        }
+        Some(c) => Err(anyhow::anyhow!("Unexpected '{}' in expression", c)),
+        None => Err(anyhow::anyhow!("Expression ended early")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_tool(name: &str) -> bool {
+        ["calculator", "current_time"].contains(&name)
+    }
+
+    fn call(tool: &str, arguments: serde_json::Value, result: &str) -> ToolCall {
+        ToolCall {
+            tool: tool.to_string(),
+            arguments,
+            result: result.to_string(),
+            success: true,
+            duration_ms: 3,
+        }
+    }
+
+    #[test]
+    fn parse_call_accepts_plain_and_fenced_json() {
+        let expected = Some(("calculator".to_string(), serde_json::json!({"expression": "2 + 2"})));
+        let plain = r#"{"tool": "calculator", "arguments": {"expression": "2 + 2"}}"#;
+        assert_eq!(parse_call(plain, is_tool), expected);
+        assert_eq!(parse_call(&format!("  {}\n", plain), is_tool), expected);
+        assert_eq!(parse_call(&format!("```json\n{}\n```", plain), is_tool), expected);
+        assert_eq!(parse_call(&format!("```\n{}\n```", plain), is_tool), expected);
+    }
+
+    #[test]
+    fn parse_call_accepts_name_and_args_aliases() {
+        assert_eq!(
+            parse_call(r#"{"name": "calculator", "args": {"expression": "1"}}"#, is_tool),
+            Some(("calculator".to_string(), serde_json::json!({"expression": "1"})))
+        );
+        // Tools without arguments get an empty object
+        assert_eq!(parse_call(r#"{"tool": "current_time"}"#, is_tool), Some(("current_time".to_string(), serde_json::json!({}))));
+    }
+
+    #[test]
+    fn parse_call_treats_everything_else_as_an_answer() {
+        for reply in [
+            r#"{"tool": "rm_rf", "arguments": {}}"#,
+            r#"Sure! {"tool": "calculator", "arguments": {"expression": "1"}}"#,
+            r#"{"tool": "calculator", "arguments": "#,
+            r#"{"tool": 42}"#,
+            r#"{"arguments": {"expression": "1"}}"#,
+            "The answer is 4.",
+            "",
+        ] {
+            assert_eq!(parse_call(reply, is_tool), None, "{:?}", reply);
+        }
+    }
+
+    #[test]
+    fn argument_summary_names_each_field_type() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "query": {"type": "string", "description": "What to look for"},
+                "limit": {"type": ["integer", "null"]},
+                "anything": {"description": "untyped"},
+                "nothing": {"type": ["null"]}
+            }
+        });
+        let summary: serde_json::Value = serde_json::from_str(&argument_summary(&schema)).unwrap();
+        assert_eq!(summary, serde_json::json!({"query": "string", "limit": "integer", "anything": "any", "nothing": "any"}));
+        assert_eq!(argument_summary(&serde_json::json!({"type": "object"})), "{}");
+    }
+
+    #[test]
+    fn truncate_chars_counts_characters_not_bytes() {
+        assert_eq!(truncate_chars("hello", 5), "hello");
+        assert_eq!(truncate_chars("hello", 3), "hel…");
+        assert_eq!(truncate_chars("héllö wörld", 4), "héll…");
+        assert_eq!(truncate_chars("日本語テキスト", 2), "日本…");
+        assert_eq!(truncate_chars("", 0), "");
+        assert_eq!(truncate_chars("a", 0), "…");
+    }
+
+    #[test]
+    fn commitment_bytes_keep_fields_apart() {
+        let args = serde_json::json!({"expression": "1"});
+        let a = call("calculator", args.clone(), "ab");
+        let b = call("calculator", args.clone(), "a");
+        assert_ne!(a.commitment_bytes(), b.commitment_bytes());
+        assert_eq!(a.commitment_bytes(), call("calculator", args, "ab").commitment_bytes());
+
+        // Both concatenate to "anullnullb", so only the length prefixes tell them apart
+        let left = call("a", serde_json::Value::Null, "nullb");
+        let right = call("anull", serde_json::Value::Null, "b");
+        assert_ne!(left.commitment_bytes(), right.commitment_bytes());
+
+        let mut failed = a.clone();
+        failed.success = false;
+        assert_ne!(failed.commitment_bytes(), a.commitment_bytes());
+        // Timing is not part of the commitment
+        let mut slower = a.clone();
+        slower.duration_ms = 999;
+        assert_eq!(slower.commitment_bytes(), a.commitment_bytes());
+    }
+
+    #[test]
+    fn evaluates_with_precedence() {
+        assert_eq!(evaluate("2 + 3 * 4").unwrap(), 14.0);
+        assert_eq!(evaluate("(2 + 3) * 4").unwrap(), 20.0);
+        assert_eq!(evaluate("2 ^ 3 ^ 2").unwrap(), 512.0);
+        assert_eq!(evaluate("-2 ^ 2").unwrap(), -4.0);
+        assert_eq!(evaluate("7 % 4 - -1").unwrap(), 4.0);
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        for expression in ["", "1 +", "(1 + 2", "1 / 0", "2 $ 3", "1.2.3", "10 ^ 400"] {
+            assert!(evaluate(expression).is_err(), "{:?} should fail", expression);
+        }
+    }
+
+    #[test]
+    fn rejects_deep_nesting_without_overflowing() {
+        let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(evaluate(&nested(MAX_EXPRESSION_DEPTH)).unwrap(), 1.0);
+        assert!(evaluate(&nested(MAX_EXPRESSION_DEPTH + 1)).is_err());
+
+        let unary = format!("{}1", "-".repeat(MAX_EXPRESSION_DEPTH + 1));
+        assert!(evaluate(&unary).is_err());
+        let powers = format!("1{}", "^1".repeat(MAX_EXPRESSION_DEPTH + 1));
+        assert!(evaluate(&powers).is_err());
+    }
+
+    #[test]
+    fn rejects_long_expressions() {
+        let long = vec!["1"; MAX_EXPRESSION_CHARS / 2 + 1].join("+");
+        let error = evaluate(&long).unwrap_err();
+        assert!(error.to_string().contains("longer than"), "{}", error);
+        // Whitespace does not count towards the limit
+        assert!(evaluate(&format!("{}1", " ".repeat(MAX_EXPRESSION_CHARS))).is_ok());
+        // Nor does a stack-busting expression get as far as the parser
+        assert!(evaluate(&"(".repeat(100_000)).is_err());
+    }
+}
@@ -4,6 +4,7 @@ use crate::config::Config;
 use crate::blockchain_client::BlockchainClient;
 use crate::semantic_search::SemanticSearchService;
 use crate::ipfs_chat_history::IPFSChatHistoryManager;
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -137,9 +138,21 @@ pub struct TrainingDataMarketplace {
     blockchain_client: Arc<BlockchainClient>,
     semantic_search: Arc<SemanticSearchService>,
     ipfs_manager: Arc<IPFSChatHistoryManager>,
-    // In-memory storage for demo (in production, use database)
+    // In-memory view, written through to the repository when one is attached
     pub contributions: HashMap<String, TrainingDataContribution>,
     pub contributors: HashMap<String, ContributorProfile>,
+    repository: Option<Arc<dyn TrainingDataRepository>>,
+}
+
+/// Durable storage for contributions and contributor profiles
+#[async_trait]
+pub trait TrainingDataRepository: Send + Sync {
+    async fn save_contribution(&self, contribution: &TrainingDataContribution) -> Result<()>;
+    async fn load_contributions(&self) -> Result<Vec<TrainingDataContribution>>;
+    async fn save_contributor(&self, profile: &ContributorProfile) -> Result<()>;
+    async fn load_contributors(&self) -> Result<Vec<ContributorProfile>>;
+    async fn delete_contribution(&self, contribution_id: &str) -> Result<()>;
+    async fn delete_contributor(&self, address: &str) -> Result<()>;
 }
 
 impl TrainingDataMarketplace {
@@ -156,6 +169,38 @@ impl TrainingDataMarketplace {
             ipfs_manager,
             contributions: HashMap::new(),
             contributors: HashMap::new(),
+            repository: None,
+        }
+    }
+
+    /// Attach durable storage and rehydrate contributions and contributor profiles
+    pub async fn with_repository(mut self, repository: Arc<dyn TrainingDataRepository>) -> Result<Self> {
+        for contribution in repository.load_contributions().await? {
+            self.contributions.insert(contribution.contribution_id.clone(), contribution);
+        }
+        for profile in repository.load_contributors().await? {
+            self.contributors.insert(profile.address.clone(), profile);
+        }
+
+        println!("🏭 Rehydrated {} contributions from {} contributors", 
+                 self.contributions.len(), self.contributors.len());
+        self.repository = Some(repository);
+        Ok(self)
+    }
+
+    async fn persist_contribution(&self, contribution_id: &str) {
+        if let (Some(repository), Some(contribution)) = (&self.repository, self.contributions.get(contribution_id)) {
+            if let Err(e) = repository.save_contribution(contribution).await {
+                println!("⚠️  Failed to persist contribution {}: {}", contribution_id, e);
+            }
+        }
+    }
+
+    async fn persist_contributor(&self, address: &str) {
+        if let (Some(repository), Some(profile)) = (&self.repository, self.contributors.get(address)) {
+            if let Err(e) = repository.save_contributor(profile).await {
+                println!("⚠️  Failed to persist contributor profile {}: {}", address, e);
+            }
         }
     }
 
@@ -223,6 +268,9 @@ impl TrainingDataMarketplace {
         // Update contributor profile
         self.update_contributor_profile(contributor_address, &contribution).await?;
         
+        self.persist_contribution(&contribution_id).await;
+        self.persist_contributor(contributor_address).await;
+        
         // Store semantic embedding for contribution discovery
         self.index_contribution_semantically(&contribution).await?;
         
@@ -281,6 +329,10 @@ impl TrainingDataMarketplace {
         // Reward validator
         self.reward_validator(&validation_request.validator_address).await?;
         
+        self.persist_contribution(&validation_request.contribution_id).await;
+        self.persist_contributor(&contributor_address).await;
+        self.persist_contributor(&validation_request.validator_address).await;
+        
         println!("🔍 Contribution {} validated by {} - Score: {}", 
                  validation_request.contribution_id, 
                  validation_request.validator_address,
@@ -289,6 +341,56 @@ impl TrainingDataMarketplace {
         Ok(())
     }
 
+    /// Remove every contribution of `address` that has not been validated. Validated
+    /// contributions were rewarded on-chain and are kept; the profile is removed once
+    /// none remain. Returns the removed contributions and the ids that were kept.
+    pub async fn erase_contributor(&mut self, address: &str) -> (Vec<TrainingDataContribution>, Vec<String>) {
+        let ids: Vec<String> = self.contributions.values()
+            .filter(|c| c.contributor_address.eq_ignore_ascii_case(address))
+            .map(|c| c.contribution_id.clone())
+            .collect();
+        
+        let mut erased = Vec::new();
+        let mut retained = Vec::new();
+        for id in ids {
+            if self.contributions.get(&id).is_some_and(|c| c.validation_status == ValidationStatus::Validated) {
+                retained.push(id);
+                continue;
+            }
+            if let Some(contribution) = self.contributions.remove(&id) {
+                if let Some(repository) = &self.repository {
+                    if let Err(e) = repository.delete_contribution(&id).await {
+                        println!("⚠️  Failed to delete contribution {}: {}", id, e);
+                    }
+                }
+                erased.push(contribution);
+            }
+        }
+        
+        let profiles: Vec<String> = self.contributors.keys()
+            .filter(|profile| profile.eq_ignore_ascii_case(address))
+            .cloned()
+            .collect();
+        for profile_address in profiles {
+            if retained.is_empty() {
+                self.contributors.remove(&profile_address);
+                if let Some(repository) = &self.repository {
+                    if let Err(e) = repository.delete_contributor(&profile_address).await {
+                        println!("⚠️  Failed to delete contributor profile {}: {}", profile_address, e);
+                    }
+                }
+            } else {
+                if let Some(profile) = self.contributors.get_mut(&profile_address) {
+                    profile.contribution_history.retain(|id| retained.contains(id));
+                }
+                self.persist_contributor(&profile_address).await;
+            }
+        }
+        
+        println!("🧽 Erased {} contributions of {} ({} validated kept)", erased.len(), address, retained.len());
+        (erased, retained)
+    }
+
     /// Get contributor profile and statistics
     pub fn get_contributor_profile(&self, address: &str) -> Option<&ContributorProfile> {
         self.contributors.get(address)
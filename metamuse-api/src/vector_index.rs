@@ -0,0 +1,1004 @@
+use anyhow::{anyhow, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use crate::lexical_index::Bm25Index;
+
+/// Bumped whenever the snapshot layout changes; older snapshots are rebuilt
+const SNAPSHOT_FORMAT_VERSION: u32 = 2;
+/// Max neighbours per node on upper layers (layer 0 keeps twice as many)
+const DEFAULT_M: usize = 16;
+const DEFAULT_EF_CONSTRUCTION: usize = 200;
+const DEFAULT_EF_SEARCH: usize = 64;
+/// Upper bound for the widened beam of a filtered search
+const MAX_EF_SEARCH: usize = 1024;
+/// Filters matching at most this fraction of live entries are scored exhaustively,
+/// since the graph walk would mostly visit entries the filter rejects
+const BRUTE_FORCE_SELECTIVITY: f32 = 0.05;
+/// Rebuild the graph once this fraction of nodes are tombstones
+const COMPACTION_THRESHOLD: f32 = 0.3;
+
+/// Attributes stored next to each vector, used for filtering and to rebuild results
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexMetadata {
+    pub muse_id: Option<String>,
+    pub user_address: Option<String>,
+    pub content_type: String,
+    pub timestamp: u64,
+    pub attributes: HashMap<String, String>,
+}
+
+/// Restricts a search to matching entries. Empty fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct IndexFilter {
+    pub muse_id: Option<String>,
+    pub user_address: Option<String>,
+    pub content_types: Vec<String>,
+    pub time_range: Option<(u64, u64)>,
+}
+
+impl IndexFilter {
+    pub fn for_muse(muse_id: &str) -> Self {
+        Self {
+            muse_id: Some(muse_id.to_string()),
+            ..Self::default()
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.muse_id.is_none() && self.user_address.is_none() && self.content_types.is_empty() && self.time_range.is_none()
+    }
+
+    fn matches(&self, metadata: &IndexMetadata) -> bool {
+        if let Some(muse_id) = &self.muse_id {
+            if metadata.muse_id.as_deref() != Some(muse_id.as_str()) {
+                return false;
+            }
+        }
+        if let Some(user_address) = &self.user_address {
+            if !metadata.user_address.as_deref().is_some_and(|address| address.eq_ignore_ascii_case(user_address)) {
+                return false;
+            }
+        }
+        if !self.content_types.is_empty() && !self.content_types.contains(&metadata.content_type) {
+            return false;
+        }
+        if let Some((start, end)) = self.time_range {
+            if metadata.timestamp < start || metadata.timestamp > end {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexHit {
+    pub id: String,
+    /// Cosine similarity to the query
+    pub score: f32,
+    pub metadata: IndexMetadata,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Node {
+    id: String,
+    vector: Vec<f32>,
+    metadata: IndexMetadata,
+    // neighbours[layer] for every layer the node lives on
+    neighbours: Vec<Vec<u32>>,
+    deleted: bool,
+}
+
+/// Hierarchical navigable small world graph over unit-length vectors (cosine similarity).
+///
+/// Deletes are tombstones: the node keeps routing searches but is never returned,
+/// and the graph is rebuilt once tombstones pass `COMPACTION_THRESHOLD`. Purged
+/// tombstones also lose their vector and metadata and keep only their links.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HnswIndex {
+    format_version: u32,
+    dimension: usize,
+    m: usize,
+    ef_construction: usize,
+    ef_search: usize,
+    nodes: Vec<Node>,
+    ids: HashMap<String, u32>,
+    entry_point: Option<u32>,
+    max_layer: usize,
+    deleted: usize,
+    rng_state: u64,
+    /// Identity of the embedder that produced the vectors
+    embedder: String,
+}
+
+/// Distance paired with a node so heaps can order candidates
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate {
+    distance: f32,
+    node: u32,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance.total_cmp(&other.distance).then(self.node.cmp(&other.node))
+    }
+}
+
+impl HnswIndex {
+    pub fn new(dimension: usize, embedder: &str) -> Self {
+        Self {
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            dimension,
+            m: DEFAULT_M,
+            ef_construction: DEFAULT_EF_CONSTRUCTION,
+            ef_search: DEFAULT_EF_SEARCH,
+            nodes: Vec::new(),
+            ids: HashMap::new(),
+            entry_point: None,
+            max_layer: 0,
+            deleted: 0,
+            rng_state: 0x9E37_79B9_7F4A_7C15,
+            embedder: embedder.to_string(),
+        }
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    pub fn embedder(&self) -> &str {
+        &self.embedder
+    }
+
+    /// Number of live (non-deleted) entries
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.ids.contains_key(id)
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &String> {
+        self.ids.keys()
+    }
+
+    pub fn metadata(&self, id: &str) -> Option<&IndexMetadata> {
+        self.ids.get(id).map(|&node| &self.nodes[node as usize].metadata)
+    }
+
+    /// Replace the attributes stored for `id` without touching the graph
+    pub fn set_metadata(&mut self, id: &str, metadata: IndexMetadata) -> bool {
+        match self.ids.get(id) {
+            Some(&node) => {
+                self.nodes[node as usize].metadata = metadata;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cosine similarity between `query` and the stored vector for `id`
+    pub fn similarity(&self, query: &[f32], id: &str) -> Option<f32> {
+        let &node = self.ids.get(id)?;
+        if query.len() != self.dimension {
+            return None;
+        }
+        Some(1.0 - self.distance(&normalized(query.to_vec()), node))
+    }
+
+    /// Live entries with their vectors, e.g. to rebuild caches after loading a snapshot
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &[f32], &IndexMetadata)> {
+        self.nodes.iter()
+            .filter(|node| !node.deleted)
+            .map(|node| (node.id.as_str(), node.vector.as_slice(), &node.metadata))
+    }
+
+    /// Insert or replace the vector stored under `id`
+    pub fn insert(&mut self, id: String, vector: Vec<f32>, metadata: IndexMetadata) -> Result<()> {
+        if vector.len() != self.dimension {
+            return Err(anyhow!("Vector has {} dimensions, index expects {}", vector.len(), self.dimension));
+        }
+        self.remove(&id);
+
+        let vector = normalized(vector);
+        let layer = self.random_layer();
+        let node = self.nodes.len() as u32;
+        self.nodes.push(Node {
+            id: id.clone(),
+            vector,
+            metadata,
+            neighbours: vec![Vec::new(); layer + 1],
+            deleted: false,
+        });
+        self.ids.insert(id, node);
+
+        let Some(mut entry_point) = self.entry_point else {
+            self.entry_point = Some(node);
+            self.max_layer = layer;
+            return Ok(());
+        };
+
+        let query = self.nodes[node as usize].vector.clone();
+
+        // Greedy descent through the layers above the new node's top layer
+        for current_layer in (layer + 1..=self.max_layer).rev() {
+            entry_point = self.search_layer(&query, &[entry_point], 1, current_layer)[0].node;
+        }
+
+        let mut entry_points = vec![entry_point];
+        for current_layer in (0..=layer.min(self.max_layer)).rev() {
+            let candidates = self.search_layer(&query, &entry_points, self.ef_construction, current_layer);
+            let neighbours = self.select_neighbours(&candidates, self.m);
+            self.nodes[node as usize].neighbours[current_layer] = neighbours.clone();
+
+            let max_connections = self.max_connections(current_layer);
+            for neighbour in neighbours {
+                let links = &mut self.nodes[neighbour as usize].neighbours[current_layer];
+                links.push(node);
+                if links.len() > max_connections {
+                    self.prune_links(neighbour, current_layer, max_connections);
+                }
+            }
+
+            entry_points = candidates.iter().map(|candidate| candidate.node).collect();
+        }
+
+        if layer > self.max_layer {
+            self.entry_point = Some(node);
+            self.max_layer = layer;
+        }
+
+        Ok(())
+    }
+
+    /// Tombstone `id`. Returns whether it was present.
+    pub fn remove(&mut self, id: &str) -> bool {
+        match self.ids.remove(id) {
+            Some(node) => {
+                self.nodes[node as usize].deleted = true;
+                self.deleted += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Tombstone `id` and drop its vector and metadata right away, so nothing about
+    /// it survives in memory or the next snapshot. Returns whether it was present.
+    pub fn purge(&mut self, id: &str) -> bool {
+        let Some(&node) = self.ids.get(id) else {
+            return false;
+        };
+        self.remove(id);
+        let node = &mut self.nodes[node as usize];
+        // An empty vector is orthogonal to every query, so the node still routes but never ranks
+        node.vector = Vec::new();
+        node.metadata = IndexMetadata::default();
+        true
+    }
+
+    /// Top `k` live entries by cosine similarity that pass `filter`
+    pub fn search(&self, query: &[f32], k: usize, filter: &IndexFilter) -> Vec<IndexHit> {
+        let Some(entry_point) = self.entry_point else {
+            return Vec::new();
+        };
+        if query.len() != self.dimension || k == 0 || self.ids.is_empty() {
+            return Vec::new();
+        }
+        let query = normalized(query.to_vec());
+
+        // Metadata checks are cheap next to distances, so find out up front how
+        // selective the filter is
+        let matching: Option<Vec<u32>> = (!filter.is_empty()).then(|| {
+            self.ids.values()
+                .copied()
+                .filter(|&node| filter.matches(&self.nodes[node as usize].metadata))
+                .collect()
+        });
+        if let Some(matching) = &matching {
+            if matching.len() as f32 <= self.ids.len() as f32 * BRUTE_FORCE_SELECTIVITY {
+                return self.exhaustive_search(&query, k, matching);
+            }
+        }
+
+        let mut layer_entry = entry_point;
+        for current_layer in (1..=self.max_layer).rev() {
+            layer_entry = self.search_layer(&query, &[layer_entry], 1, current_layer)[0].node;
+        }
+
+        // Tombstones and filtered-out nodes take up beam slots, so widen the beam
+        // until enough matches are found, up to `MAX_EF_SEARCH`
+        let max_ef = MAX_EF_SEARCH.max(k).min(self.nodes.len());
+        let mut ef = self.ef_search.max(k).min(max_ef);
+        loop {
+            let hits: Vec<IndexHit> = self.search_layer(&query, &[layer_entry], ef, 0)
+                .into_iter()
+                .filter_map(|candidate| {
+                    let node = &self.nodes[candidate.node as usize];
+                    (!node.deleted && filter.matches(&node.metadata)).then(|| IndexHit {
+                        id: node.id.clone(),
+                        score: 1.0 - candidate.distance,
+                        metadata: node.metadata.clone(),
+                    })
+                })
+                .take(k)
+                .collect();
+
+            if hits.len() >= k || ef >= self.nodes.len() {
+                return hits;
+            }
+            if ef >= max_ef {
+                // Still short with the widest beam: score the matching entries directly
+                return match &matching {
+                    Some(matching) => self.exhaustive_search(&query, k, matching),
+                    None => hits,
+                };
+            }
+            ef = (ef * 4).min(max_ef);
+        }
+    }
+
+    /// Top `k` of `nodes` by cosine similarity, without walking the graph
+    fn exhaustive_search(&self, query: &[f32], k: usize, nodes: &[u32]) -> Vec<IndexHit> {
+        let mut candidates: Vec<Candidate> = nodes.iter()
+            .map(|&node| Candidate { distance: self.distance(query, node), node })
+            .collect();
+        candidates.sort();
+        candidates.into_iter()
+            .take(k)
+            .map(|candidate| {
+                let node = &self.nodes[candidate.node as usize];
+                IndexHit {
+                    id: node.id.clone(),
+                    score: 1.0 - candidate.distance,
+                    metadata: node.metadata.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Rebuild the graph from live nodes, dropping tombstones
+    pub fn compact(&mut self) {
+        let live: Vec<Node> = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .filter(|node| !node.deleted)
+            .collect();
+
+        self.ids.clear();
+        self.entry_point = None;
+        self.max_layer = 0;
+        self.deleted = 0;
+
+        for node in live {
+            // Vectors were validated on first insert
+            let _ = self.insert(node.id, node.vector, node.metadata);
+        }
+    }
+
+    pub fn needs_compaction(&self) -> bool {
+        !self.nodes.is_empty() && self.deleted as f32 / self.nodes.len() as f32 > COMPACTION_THRESHOLD
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_snapshot(path, self)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let index: Self = read_snapshot(path)?;
+        if index.format_version != SNAPSHOT_FORMAT_VERSION {
+            return Err(anyhow!("Snapshot format {} is not supported", index.format_version));
+        }
+        Ok(index)
+    }
+
+    /// Best-first search on one layer, returning up to `ef` candidates closest first
+    fn search_layer(&self, query: &[f32], entry_points: &[u32], ef: usize, layer: usize) -> Vec<Candidate> {
+        let mut visited: HashSet<u32> = entry_points.iter().copied().collect();
+        let mut candidates: BinaryHeap<Reverse<Candidate>> = BinaryHeap::new();
+        let mut results: BinaryHeap<Candidate> = BinaryHeap::new();
+
+        for &node in entry_points {
+            let candidate = Candidate { distance: self.distance(query, node), node };
+            candidates.push(Reverse(candidate));
+            results.push(candidate);
+        }
+
+        while let Some(Reverse(closest)) = candidates.pop() {
+            let furthest = results.peek().map(|c| c.distance).unwrap_or(f32::MAX);
+            if closest.distance > furthest && results.len() >= ef {
+                break;
+            }
+
+            let Some(links) = self.nodes[closest.node as usize].neighbours.get(layer) else {
+                continue;
+            };
+            for &neighbour in links {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+
+                let candidate = Candidate { distance: self.distance(query, neighbour), node: neighbour };
+                let furthest = results.peek().map(|c| c.distance).unwrap_or(f32::MAX);
+                if results.len() < ef || candidate.distance < furthest {
+                    candidates.push(Reverse(candidate));
+                    results.push(candidate);
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        results.into_sorted_vec()
+    }
+
+    /// Neighbour selection heuristic: prefer candidates that are closer to the new
+    /// node than to any already selected neighbour, which keeps the graph navigable
+    /// across clusters. Pruned candidates fill any remaining slots.
+    fn select_neighbours(&self, candidates: &[Candidate], m: usize) -> Vec<u32> {
+        let mut selected: Vec<u32> = Vec::with_capacity(m);
+        let mut pruned: Vec<u32> = Vec::new();
+
+        for candidate in candidates {
+            if selected.len() >= m {
+                break;
+            }
+            let vector = &self.nodes[candidate.node as usize].vector;
+            let diverse = selected.iter()
+                .all(|&chosen| self.distance(vector, chosen) > candidate.distance);
+            if diverse {
+                selected.push(candidate.node);
+            } else {
+                pruned.push(candidate.node);
+            }
+        }
+
+        for node in pruned {
+            if selected.len() >= m {
+                break;
+            }
+            selected.push(node);
+        }
+        selected
+    }
+
+    fn prune_links(&mut self, node: u32, layer: usize, max_connections: usize) {
+        let vector = self.nodes[node as usize].vector.clone();
+        let mut candidates: Vec<Candidate> = self.nodes[node as usize].neighbours[layer]
+            .iter()
+            .map(|&neighbour| Candidate { distance: self.distance(&vector, neighbour), node: neighbour })
+            .collect();
+        candidates.sort();
+
+        let kept = self.select_neighbours(&candidates, max_connections);
+        self.nodes[node as usize].neighbours[layer] = kept;
+    }
+
+    fn max_connections(&self, layer: usize) -> usize {
+        if layer == 0 { self.m * 2 } else { self.m }
+    }
+
+    fn distance(&self, query: &[f32], node: u32) -> f32 {
+        let vector = &self.nodes[node as usize].vector;
+        1.0 - query.iter().zip(vector).map(|(a, b)| a * b).sum::<f32>()
+    }
+
+    /// Exponentially distributed layer with normalisation factor 1/ln(M)
+    fn random_layer(&mut self) -> usize {
+        // xorshift64* keeps the index free of an RNG dependency and snapshot-stable
+        self.rng_state ^= self.rng_state >> 12;
+        self.rng_state ^= self.rng_state << 25;
+        self.rng_state ^= self.rng_state >> 27;
+        let random = self.rng_state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+
+        let uniform = ((random >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        let level = -uniform.ln() / (self.m as f64).ln();
+        level.floor().min(16.0) as usize
+    }
+}
+
+/// Write then rename so a crash never leaves a truncated snapshot behind
+fn write_snapshot<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let tmp_path = path.with_extension("tmp");
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(&tmp_path)?);
+    bincode::serialize_into(&mut writer, value)?;
+    writer.flush()?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+fn read_snapshot<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let file = std::fs::File::open(path)?;
+    Ok(bincode::deserialize_from(std::io::BufReader::new(file))?)
+}
+
+fn normalized(mut vector: Vec<f32>) -> Vec<f32> {
+    let magnitude: f32 = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if magnitude > 0.0 {
+        for value in &mut vector {
+            *value /= magnitude;
+        }
+    }
+    vector
+}
+
+/// Embedder identities recorded by the index snapshots in `dir`
+pub fn snapshot_embedders(dir: &str) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut embedders: Vec<String> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "hnsw"))
+        .filter_map(|path| HnswIndex::load(&path).ok())
+        .filter(|index| !index.is_empty())
+        .map(|index| index.embedder().to_string())
+        .collect();
+    embedders.sort();
+    embedders.dedup();
+    embedders
+}
+
+/// Shared, snapshotting wrapper around an `HnswIndex` and a BM25 index over the same entries
+pub struct VectorIndex {
+    name: String,
+    index: RwLock<HnswIndex>,
+    lexical: RwLock<Bm25Index>,
+    snapshot_path: PathBuf,
+    lexical_path: PathBuf,
+    // Inserts and removes since the last snapshot
+    pending_changes: AtomicUsize,
+    // Bumped on every write to `index`, so a compacted copy is only swapped in over the state it was built from
+    version: AtomicU64,
+    // Set while a compaction runs, so concurrent purges don't rebuild the same graph twice
+    compacting: AtomicBool,
+    // Entries of a snapshot built by a different embedder, waiting to be re-embedded
+    stale_entries: RwLock<Vec<(String, IndexMetadata)>>,
+}
+
+impl VectorIndex {
+    /// Load `<dir>/<name>.hnsw` and `<dir>/<name>.bm25` if they exist and were built by
+    /// `embedder` with `dimension`, otherwise start empty. Entries of a snapshot from another
+    /// embedder are kept in `take_stale_entries` so their owner can re-embed them.
+    pub fn open(dir: &str, name: &str, dimension: usize, embedder: &str) -> Self {
+        let snapshot_path = Path::new(dir).join(format!("{}.hnsw", name));
+        let lexical_path = Path::new(dir).join(format!("{}.bm25", name));
+
+        let mut stale_entries = Vec::new();
+        let index = match HnswIndex::load(&snapshot_path) {
+            Ok(index) if index.dimension() == dimension && index.embedder() == embedder => {
+                println!("🧭 Loaded {} index snapshot ({} vectors)", name, index.len());
+                index
+            }
+            Ok(index) => {
+                println!("⚠️  {} index snapshot was built by {}, now using {} - rebuilding", name, index.embedder(), embedder);
+                stale_entries = index.entries()
+                    .map(|(id, _, metadata)| (id.to_string(), metadata.clone()))
+                    .collect();
+                HnswIndex::new(dimension, embedder)
+            }
+            Err(_) if !snapshot_path.exists() => HnswIndex::new(dimension, embedder),
+            Err(e) => {
+                println!("⚠️  Failed to load {} index snapshot: {} - rebuilding", name, e);
+                HnswIndex::new(dimension, embedder)
+            }
+        };
+
+        // Both indexes must cover the same entries, so a missing lexical snapshot
+        // means the vectors are rebuilt too
+        let (index, lexical) = if index.is_empty() {
+            (index, Bm25Index::new())
+        } else {
+            match read_snapshot::<Bm25Index>(&lexical_path) {
+                Ok(lexical) => (index, lexical),
+                Err(e) => {
+                    println!("⚠️  Failed to load {} lexical index snapshot: {} - rebuilding", name, e);
+                    (HnswIndex::new(dimension, embedder), Bm25Index::new())
+                }
+            }
+        };
+
+        Self {
+            name: name.to_string(),
+            index: RwLock::new(index),
+            lexical: RwLock::new(lexical),
+            snapshot_path,
+            lexical_path,
+            // A rebuilt index must be written even if nothing is re-added
+            pending_changes: AtomicUsize::new(usize::from(!stale_entries.is_empty())),
+            version: AtomicU64::new(0),
+            compacting: AtomicBool::new(false),
+            stale_entries: RwLock::new(stale_entries),
+        }
+    }
+
+    /// Ids and metadata of entries discarded because the embedder changed
+    pub async fn take_stale_entries(&self) -> Vec<(String, IndexMetadata)> {
+        std::mem::take(&mut *self.stale_entries.write().await)
+    }
+
+    /// Insert or replace `id` in both the vector and the lexical index
+    pub async fn insert(&self, id: String, vector: Vec<f32>, text: &str, metadata: IndexMetadata) -> Result<()> {
+        {
+            let mut index = self.index.write().await;
+            index.insert(id.clone(), vector, metadata)?;
+            self.version.fetch_add(1, AtomicOrdering::SeqCst);
+        }
+        self.lexical.write().await.insert(&id, text);
+        self.pending_changes.fetch_add(1, AtomicOrdering::SeqCst);
+        Ok(())
+    }
+
+    pub async fn remove(&self, id: &str) -> bool {
+        let removed = {
+            let mut index = self.index.write().await;
+            let removed = index.remove(id);
+            if removed {
+                self.version.fetch_add(1, AtomicOrdering::SeqCst);
+            }
+            removed
+        };
+        self.lexical.write().await.remove(id);
+        if removed {
+            self.pending_changes.fetch_add(1, AtomicOrdering::SeqCst);
+        }
+        removed
+    }
+
+    pub async fn search(&self, query: &[f32], k: usize, filter: &IndexFilter) -> Vec<IndexHit> {
+        self.index.read().await.search(query, k, filter)
+    }
+
+    /// Remove `id` and drop its vector and metadata immediately. The graph itself is
+    /// only rebuilt once tombstones pass the compaction threshold.
+    pub async fn purge(&self, id: &str) -> bool {
+        let removed = {
+            let mut index = self.index.write().await;
+            let removed = index.purge(id);
+            if removed {
+                self.version.fetch_add(1, AtomicOrdering::SeqCst);
+            }
+            removed
+        };
+        self.lexical.write().await.remove(id);
+        if removed {
+            self.pending_changes.fetch_add(1, AtomicOrdering::SeqCst);
+            self.compact_if_needed().await;
+        }
+        removed
+    }
+
+    pub async fn update_metadata(&self, id: &str, metadata: IndexMetadata) -> bool {
+        let updated = {
+            let mut index = self.index.write().await;
+            let updated = index.set_metadata(id, metadata);
+            if updated {
+                self.version.fetch_add(1, AtomicOrdering::SeqCst);
+            }
+            updated
+        };
+        if updated {
+            self.pending_changes.fetch_add(1, AtomicOrdering::SeqCst);
+        }
+        updated
+    }
+
+    /// Top `k` entries by BM25 score that pass `filter`
+    pub async fn search_lexical(&self, query: &str, k: usize, filter: &IndexFilter) -> Vec<IndexHit> {
+        let scores = self.lexical.read().await.score(query);
+        let index = self.index.read().await;
+
+        let mut hits: Vec<IndexHit> = scores.into_iter()
+            .filter_map(|(id, score)| {
+                let metadata = index.metadata(&id)?;
+                filter.matches(metadata).then(|| IndexHit { id, score, metadata: metadata.clone() })
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(k);
+        hits
+    }
+
+    pub async fn similarity(&self, query: &[f32], id: &str) -> Option<f32> {
+        self.index.read().await.similarity(query, id)
+    }
+
+    pub async fn contains(&self, id: &str) -> bool {
+        self.index.read().await.contains(id)
+    }
+
+    /// Read access for bulk operations such as rebuilding caches after startup
+    pub async fn read(&self) -> tokio::sync::RwLockReadGuard<'_, HnswIndex> {
+        self.index.read().await
+    }
+
+    /// Write the index to disk if it changed since the last snapshot
+    pub async fn snapshot(&self) -> Result<()> {
+        let changes = self.pending_changes.swap(0, AtomicOrdering::SeqCst);
+        if changes == 0 {
+            return Ok(());
+        }
+
+        self.compact_if_needed().await;
+        let index = self.index.read().await.clone();
+        let lexical = self.lexical.read().await.clone();
+
+        let path = self.snapshot_path.clone();
+        let lexical_path = self.lexical_path.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            index.save(&path)?;
+            write_snapshot(&lexical_path, &lexical)
+        }).await?;
+        if result.is_err() {
+            // Retry on the next tick
+            self.pending_changes.fetch_add(changes, AtomicOrdering::SeqCst);
+        }
+        result
+    }
+
+    /// Rebuild the graph on a copy on the blocking pool once tombstones pass the threshold,
+    /// then swap it in. Searches keep using the old graph meanwhile; if the index was written
+    /// to in the meantime the copy is dropped and the next purge or snapshot tries again.
+    async fn compact_if_needed(&self) {
+        if self.compacting.swap(true, AtomicOrdering::SeqCst) {
+            return;
+        }
+
+        let copy = {
+            let index = self.index.read().await;
+            index.needs_compaction()
+                .then(|| (index.clone(), self.version.load(AtomicOrdering::SeqCst)))
+        };
+        if let Some((mut copy, version)) = copy {
+            let compacted = tokio::task::spawn_blocking(move || {
+                copy.compact();
+                copy
+            }).await;
+
+            match compacted {
+                Ok(compacted) => {
+                    let mut index = self.index.write().await;
+                    if self.version.load(AtomicOrdering::SeqCst) == version {
+                        *index = compacted;
+                        println!("🧹 Compacted {} index ({} live vectors)", self.name, index.len());
+                    }
+                }
+                Err(e) => println!("⚠️  Failed to compact {} index: {}", self.name, e),
+            }
+        }
+
+        self.compacting.store(false, AtomicOrdering::SeqCst);
+    }
+
+    /// Periodically persist the index in the background
+    pub fn start_snapshot_task(self: &Arc<Self>, interval: Duration) {
+        let index = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                if let Err(e) = index.snapshot().await {
+                    println!("⚠️  Failed to snapshot {} index: {}", index.name, e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vector(seed: u64, dimension: usize) -> Vec<f32> {
+        let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        (0..dimension)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state % 2000) as f32 / 1000.0 - 1.0
+            })
+            .collect()
+    }
+
+    fn metadata(muse_id: &str) -> IndexMetadata {
+        IndexMetadata {
+            muse_id: Some(muse_id.to_string()),
+            content_type: "memory".to_string(),
+            ..IndexMetadata::default()
+        }
+    }
+
+    fn index_with(count: u64) -> HnswIndex {
+        let mut index = HnswIndex::new(16, "test:16");
+        for i in 0..count {
+            let muse = if i % 2 == 0 { "even" } else { "odd" };
+            index.insert(format!("v{}", i), vector(i, 16), metadata(muse)).unwrap();
+        }
+        index
+    }
+
+    #[test]
+    fn search_finds_inserted_vectors() {
+        let index = index_with(300);
+        assert_eq!(index.len(), 300);
+
+        for i in [0, 57, 123, 299] {
+            let hits = index.search(&vector(i, 16), 5, &IndexFilter::default());
+            assert_eq!(hits[0].id, format!("v{}", i));
+            assert!((hits[0].score - 1.0).abs() < 1e-4);
+            assert!(hits.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        }
+    }
+
+    #[test]
+    fn search_applies_filters() {
+        let index = index_with(100);
+        let hits = index.search(&vector(3, 16), 10, &IndexFilter::for_muse("even"));
+        assert_eq!(hits.len(), 10);
+        assert!(hits.iter().all(|hit| hit.metadata.muse_id.as_deref() == Some("even")));
+        assert!(hits.iter().all(|hit| hit.id != "v3"));
+    }
+
+    #[test]
+    fn sparse_filters_on_a_large_index_return_exact_matches() {
+        let mut index = HnswIndex::new(16, "test:16");
+        for i in 0..3000u64 {
+            let muse = if i % 500 == 7 { "rare" } else { "common" };
+            index.insert(format!("v{}", i), vector(i, 16), metadata(muse)).unwrap();
+        }
+
+        let query = vector(4242, 16);
+        let hits = index.search(&query, 10, &IndexFilter::for_muse("rare"));
+        let mut expected: Vec<(f32, String)> = (0..3000u64)
+            .filter(|i| i % 500 == 7)
+            .map(|i| (index.similarity(&query, &format!("v{}", i)).unwrap(), format!("v{}", i)))
+            .collect();
+        expected.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        assert_eq!(hits.len(), 6);
+        assert_eq!(hits.iter().map(|hit| hit.id.clone()).collect::<Vec<_>>(), expected.into_iter().map(|(_, id)| id).collect::<Vec<_>>());
+        assert!(index.search(&query, 10, &IndexFilter::for_muse("missing")).is_empty());
+
+        // A filter above the exhaustive threshold still goes through the graph
+        let hits = index.search(&query, 10, &IndexFilter::for_muse("common"));
+        assert_eq!(hits.len(), 10);
+        assert!(hits.iter().all(|hit| hit.metadata.muse_id.as_deref() == Some("common")));
+    }
+
+    #[test]
+    fn insert_rejects_wrong_dimension_and_replaces_ids() {
+        let mut index = index_with(10);
+        assert!(index.insert("bad".to_string(), vec![1.0; 8], metadata("even")).is_err());
+
+        index.insert("v1".to_string(), vector(1000, 16), metadata("odd")).unwrap();
+        assert_eq!(index.len(), 10);
+        let hits = index.search(&vector(1000, 16), 1, &IndexFilter::default());
+        assert_eq!(hits[0].id, "v1");
+    }
+
+    #[test]
+    fn removed_entries_are_never_returned() {
+        let mut index = index_with(200);
+        for i in (0..200).step_by(3) {
+            assert!(index.remove(&format!("v{}", i)));
+        }
+        assert!(!index.remove("v0"));
+        assert!(!index.contains("v3"));
+
+        let hits = index.search(&vector(3, 16), 20, &IndexFilter::default());
+        assert_eq!(hits.len(), 20);
+        assert!(hits.iter().all(|hit| hit.id.trim_start_matches('v').parse::<u64>().unwrap() % 3 != 0));
+        assert_eq!(index.search(&vector(4, 16), 1, &IndexFilter::default())[0].id, "v4");
+    }
+
+    #[test]
+    fn purge_drops_vector_and_compaction_keeps_search_working() {
+        let mut index = index_with(100);
+        assert!(index.purge("v7"));
+        let node = index.nodes.iter().find(|node| node.id == "v7").unwrap();
+        assert!(node.vector.is_empty());
+        assert!(node.metadata.muse_id.is_none());
+        assert!(index.entries().all(|(id, _, _)| id != "v7"));
+
+        for i in 0..40 {
+            index.purge(&format!("v{}", i));
+        }
+        assert!(index.needs_compaction());
+        index.compact();
+        assert!(!index.needs_compaction());
+        assert_eq!(index.nodes.len(), 60);
+        assert_eq!(index.search(&vector(42, 16), 1, &IndexFilter::default())[0].id, "v42");
+    }
+
+    #[test]
+    fn snapshot_round_trip_keeps_embedder_identity() {
+        let dir = std::env::temp_dir().join(format!("vector_index_test_{}", std::process::id()));
+        let path = dir.join("test.hnsw");
+        let index = index_with(50);
+        index.save(&path).unwrap();
+
+        let loaded = HnswIndex::load(&path).unwrap();
+        assert_eq!(loaded.embedder(), "test:16");
+        assert_eq!(loaded.len(), 50);
+        assert_eq!(loaded.search(&vector(9, 16), 1, &IndexFilter::default())[0].id, "v9");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn snapshot_embedders_lists_non_empty_snapshots() {
+        let dir = std::env::temp_dir().join(format!("vector_index_embedders_{}", std::process::id()));
+        index_with(5).save(&dir.join("a.hnsw")).unwrap();
+        index_with(5).save(&dir.join("b.hnsw")).unwrap();
+        HnswIndex::new(16, "empty:16").save(&dir.join("c.hnsw")).unwrap();
+
+        assert_eq!(snapshot_embedders(dir.to_str().unwrap()), vec!["test:16".to_string()]);
+        assert!(snapshot_embedders(dir.join("missing").to_str().unwrap()).is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn open_rebuilds_when_the_embedder_changes() {
+        let dir = std::env::temp_dir().join(format!("vector_index_open_{}", std::process::id()));
+        let dir_str = dir.to_str().unwrap();
+
+        let index = VectorIndex::open(dir_str, "test", 16, "model-a:16");
+        index.insert("a".to_string(), vector(1, 16), "first entry", metadata("even")).await.unwrap();
+        index.snapshot().await.unwrap();
+
+        let reopened = VectorIndex::open(dir_str, "test", 16, "model-a:16");
+        assert!(reopened.contains("a").await);
+        assert!(reopened.take_stale_entries().await.is_empty());
+
+        let rebuilt = VectorIndex::open(dir_str, "test", 16, "model-b:16");
+        assert!(!rebuilt.contains("a").await);
+        let stale = rebuilt.take_stale_entries().await;
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].0, "a");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn purge_compacts_past_the_threshold() {
+        let dir = std::env::temp_dir().join(format!("vector_index_purge_{}", std::process::id()));
+        let index = VectorIndex::open(dir.to_str().unwrap(), "test", 16, "test:16");
+        for i in 0..20 {
+            index.insert(format!("v{}", i), vector(i, 16), "entry", metadata("even")).await.unwrap();
+        }
+
+        for i in 0..7 {
+            assert!(index.purge(&format!("v{}", i)).await);
+        }
+        assert!(!index.read().await.needs_compaction());
+        assert_eq!(index.read().await.nodes.len(), 13);
+        assert!(!index.contains("v3").await);
+        assert_eq!(index.search(&vector(12, 16), 1, &IndexFilter::default()).await[0].id, "v12");
+    }
+}
@@ -1,10 +1,14 @@
 use anyhow::Result;
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use sha3::{Digest, Keccak256};
 use secp256k1::{Secp256k1, SecretKey, Message, PublicKey};
 use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::RwLock;
 use crate::config::Config;
 use crate::persist_memory::InteractionData;
+use crate::tools::ToolCall;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VerifiableInteraction {
@@ -15,6 +19,9 @@ pub struct VerifiableInteraction {
     pub personality_traits: crate::muse_orchestrator::MuseTraits,
     pub timestamp: u64,
     pub inference_params: InferenceParams,
+    // ✅ NEW: Tool calls and their results are part of what is committed to
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,12 +50,43 @@ pub struct InteractionCommitment {
     pub recovery_id: u8,
 }
 
+/// A commitment issued for one chat turn, kept so users can export their proofs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitmentRecord {
+    pub interaction_id: String,
+    pub muse_id: u64,
+    pub user_address: String,
+    pub session_id: Option<String>,
+    pub message_id: Option<String>,
+    /// DNA hash of the muse, which the signature covers along with the commitment hash
+    pub muse_dna_hash: String,
+    pub commitment_hash: String,
+    pub signature: String,
+    pub recovery_id: u8,
+    /// Address of the service key that signed the commitment
+    pub signer: String,
+    /// Imported with a signature by another deployment's key, so it only proves what
+    /// that deployment attested to
+    pub foreign: bool,
+    pub created_at: u64,
+}
+
+/// Durable storage for issued commitments
+#[async_trait]
+pub trait CommitmentRepository: Send + Sync {
+    async fn save_commitment(&self, record: &CommitmentRecord) -> Result<()>;
+    async fn load_commitments(&self) -> Result<Vec<CommitmentRecord>>;
+}
+
 pub struct VerificationSystem {
     signing_key: SecretKey,
     public_key: PublicKey,
     secp: Secp256k1<secp256k1::All>,
     chain_id: u64,
     contract_address: [u8; 20],
+    // ✅ NEW: Ledger of issued commitments, oldest first
+    commitments: RwLock<Vec<CommitmentRecord>>,
+    repository: Option<Arc<dyn CommitmentRepository>>,
 }
 
 impl VerificationSystem {
@@ -69,9 +107,71 @@ impl VerificationSystem {
             secp,
             chain_id: config.chain_id,
             contract_address: addr_bytes,
+            commitments: RwLock::new(Vec::new()),
+            repository: None,
         })
     }
     
+    /// Attach durable storage and rehydrate the commitment ledger
+    pub async fn with_repository(mut self, repository: Arc<dyn CommitmentRepository>) -> Result<Self> {
+        let mut commitments = repository.load_commitments().await?;
+        commitments.sort_by_key(|record| record.created_at);
+        println!("🔏 Rehydrated {} interaction commitments", commitments.len());
+        
+        self.commitments = RwLock::new(commitments);
+        self.repository = Some(repository);
+        Ok(self)
+    }
+    
+    /// Remember a commitment handed to a user
+    pub async fn record_commitment(&self, record: CommitmentRecord) {
+        if let Some(repository) = &self.repository {
+            if let Err(e) = repository.save_commitment(&record).await {
+                println!("⚠️ Failed to persist commitment {}: {}", record.interaction_id, e);
+            }
+        }
+        self.commitments.write().await.push(record);
+    }
+    
+    /// Commitments issued to `user_address` for `muse_id`
+    pub async fn user_commitments(&self, user_address: &str, muse_id: u64) -> Vec<CommitmentRecord> {
+        self.commitments.read().await
+            .iter()
+            .filter(|record| record.muse_id == muse_id && record.user_address.eq_ignore_ascii_case(user_address))
+            .cloned()
+            .collect()
+    }
+    
+    /// Add commitments exported from another deployment, skipping ones already present.
+    /// A record is only accepted when its signature recovers to its `signer` for this
+    /// chain and contract; records signed by any key but this deployment's are marked foreign.
+    pub async fn import_commitments(&self, records: Vec<CommitmentRecord>) -> usize {
+        let service_signer = self.get_public_key_address();
+        let mut imported = 0;
+        for mut record in records {
+            match self.verify_record(&record) {
+                Ok(true) => {}
+                Ok(false) => {
+                    println!("⚠️ Rejecting imported commitment {}: not signed by {}", record.interaction_id, record.signer);
+                    continue;
+                }
+                Err(e) => {
+                    println!("⚠️ Rejecting imported commitment {}: {}", record.interaction_id, e);
+                    continue;
+                }
+            }
+            record.foreign = !record.signer.eq_ignore_ascii_case(&service_signer);
+            
+            let exists = self.commitments.read().await.iter()
+                .any(|existing| existing.interaction_id == record.interaction_id && existing.commitment_hash == record.commitment_hash);
+            if !exists {
+                self.record_commitment(record).await;
+                imported += 1;
+            }
+        }
+        imported
+    }
+    
     pub async fn create_commitment(
         &self,
         interaction: &VerifiableInteraction,
@@ -111,19 +211,44 @@ impl VerificationSystem {
         signature: &[u8],
         recovery_id: u8,
     ) -> Result<bool> {
+        let recovered_pubkey = self.recover_signer(muse_id, muse_dna_hash, commitment_hash, signature, recovery_id)?;
+        
+        // Check if it matches our trusted public key
+        Ok(recovered_pubkey == self.public_key)
+    }
+    
+    /// Whether a stored commitment's signature recovers to the `signer` it names
+    pub fn verify_record(&self, record: &CommitmentRecord) -> Result<bool> {
+        let commitment_hash: [u8; 32] = hex_string_to_bytes(&record.commitment_hash)?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Commitment hash must be 32 bytes"))?;
+        let muse_dna_hash: [u8; 32] = hex_string_to_bytes(&record.muse_dna_hash)?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Muse DNA hash must be 32 bytes"))?;
+        let signature = hex_string_to_bytes(&record.signature)?;
+        
+        let recovered_pubkey = self.recover_signer(record.muse_id, muse_dna_hash, &commitment_hash, &signature, record.recovery_id)?;
+        Ok(public_key_to_address(&recovered_pubkey).eq_ignore_ascii_case(&record.signer))
+    }
+    
+    /// Public key that signed a commitment over `commitment_hash` for the muse
+    fn recover_signer(
+        &self,
+        muse_id: u64,
+        muse_dna_hash: [u8; 32],
+        commitment_hash: &[u8; 32],
+        signature: &[u8],
+        recovery_id: u8,
+    ) -> Result<PublicKey> {
         // Recreate the message that should have been signed
         let sign_message = self.create_sign_message(muse_id, muse_dna_hash, commitment_hash);
         let eth_message = self.create_eth_signed_message(&sign_message);
         
-        // Recover public key from signature
         let message = Message::from_digest_slice(&eth_message)?;
         let recovery_id = secp256k1::ecdsa::RecoveryId::from_i32(recovery_id as i32)?;
         let signature = secp256k1::ecdsa::RecoverableSignature::from_compact(signature, recovery_id)?;
         
-        let recovered_pubkey = self.secp.recover_ecdsa(&message, &signature)?;
-        
-        // Check if it matches our trusted public key
-        Ok(recovered_pubkey == self.public_key)
+        Ok(self.secp.recover_ecdsa(&message, &signature)?)
     }
     
     pub fn create_interaction_from_data(
@@ -142,7 +267,12 @@ impl VerificationSystem {
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
-            inference_params: InferenceParams::default(),
+            // ✅ NEW: Commit to the model that actually answered
+            inference_params: InferenceParams {
+                model_version: interaction_data.model_used.clone(),
+                ..InferenceParams::default()
+            },
+            tool_calls: interaction_data.tool_calls.clone(),
         }
     }
     
@@ -169,6 +299,11 @@ impl VerificationSystem {
         data.extend_from_slice(&interaction.inference_params.max_tokens.to_be_bytes());
         data.extend_from_slice(&interaction.inference_params.context_window.to_be_bytes());
         
+        // Interactions without tool calls hash exactly as before
+        for call in &interaction.tool_calls {
+            data.extend_from_slice(&call.commitment_bytes());
+        }
+        
         Ok(data)
     }
     
@@ -190,20 +325,25 @@ impl VerificationSystem {
     }
     
     fn create_eth_signed_message(&self, message: &[u8]) -> Vec<u8> {
-        // Create Ethereum signed message format
-        let prefix = format!("\x19Ethereum Signed Message:\n{}", message.len());
-        let mut eth_message = Vec::new();
-        eth_message.extend_from_slice(prefix.as_bytes());
-        eth_message.extend_from_slice(message);
+        eth_signed_message_hash(message)
+    }
+    
+    /// 65-byte `personal_sign` (EIP-191) signature over `message` with the service key,
+    /// recoverable with `recover_personal_sign_address`
+    pub fn sign_personal_message(&self, message: &[u8]) -> Result<Vec<u8>> {
+        let digest = eth_signed_message_hash(message);
+        let message = Message::from_digest_slice(&digest)?;
+        let (recovery_id, signature) = self.secp
+            .sign_ecdsa_recoverable(&message, &self.signing_key)
+            .serialize_compact();
         
-        Keccak256::digest(&eth_message).to_vec()
+        let mut signature = signature.to_vec();
+        signature.push(27 + recovery_id.to_i32() as u8);
+        Ok(signature)
     }
     
     pub fn get_public_key_address(&self) -> String {
-        // Get Ethereum address from public key
-        let public_key_bytes = self.public_key.serialize_uncompressed();
-        let hash = Keccak256::digest(&public_key_bytes[1..]);
-        format!("0x{}", hex::encode(&hash[12..]))
+        public_key_to_address(&self.public_key)
     }
     
     pub fn create_commitment_hash_only(&self, interaction: &VerifiableInteraction) -> Result<[u8; 32]> {
@@ -214,6 +354,45 @@ impl VerificationSystem {
 }
 
 // Helper functions for blockchain integration
+fn eth_signed_message_hash(message: &[u8]) -> Vec<u8> {
+    // Create Ethereum signed message format
+    let prefix = format!("\x19Ethereum Signed Message:\n{}", message.len());
+    let mut eth_message = Vec::new();
+    eth_message.extend_from_slice(prefix.as_bytes());
+    eth_message.extend_from_slice(message);
+    
+    Keccak256::digest(&eth_message).to_vec()
+}
+
+/// Ethereum address (0x-prefixed, lowercase) for a secp256k1 public key
+pub fn public_key_to_address(public_key: &PublicKey) -> String {
+    let public_key_bytes = public_key.serialize_uncompressed();
+    let hash = Keccak256::digest(&public_key_bytes[1..]);
+    format!("0x{}", hex::encode(&hash[12..]))
+}
+
+/// Recover the address behind a 65-byte `personal_sign` (EIP-191) signature over `message`
+pub fn recover_personal_sign_address(message: &[u8], signature: &[u8]) -> Result<String> {
+    if signature.len() != 65 {
+        return Err(anyhow::anyhow!("Signature must be 65 bytes, got {}", signature.len()));
+    }
+    
+    // Wallets emit v as 27/28; some libraries use the raw 0/1 recovery id
+    let recovery_id = match signature[64] {
+        v @ (0 | 1) => v,
+        v @ (27 | 28) => v - 27,
+        v => return Err(anyhow::anyhow!("Invalid signature recovery byte: {}", v)),
+    };
+    
+    let digest = eth_signed_message_hash(message);
+    let message = Message::from_digest_slice(&digest)?;
+    let recovery_id = secp256k1::ecdsa::RecoveryId::from_i32(recovery_id as i32)?;
+    let signature = secp256k1::ecdsa::RecoverableSignature::from_compact(&signature[..64], recovery_id)?;
+    
+    let public_key = Secp256k1::verification_only().recover_ecdsa(&message, &signature)?;
+    Ok(public_key_to_address(&public_key))
+}
+
 pub fn current_timestamp() -> u64 {
     std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -234,3 +413,91 @@ pub fn hex_string_to_bytes(hex_str: &str) -> Result<Vec<u8>> {
     
     hex::decode(hex_str).map_err(|e| anyhow::anyhow!("Invalid hex string: {}", e))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "0x00000000000000000000000000000000000a11ce";
+
+    fn system(seed: u8) -> VerificationSystem {
+        let secp = Secp256k1::new();
+        let signing_key = SecretKey::from_slice(&[seed; 32]).unwrap();
+        VerificationSystem {
+            public_key: PublicKey::from_secret_key(&secp, &signing_key),
+            signing_key,
+            secp,
+            chain_id: 133717,
+            contract_address: [7u8; 20],
+            commitments: RwLock::new(Vec::new()),
+            repository: None,
+        }
+    }
+
+    /// A commitment `signer` issued for muse 1, as `generate_chat_reply` records it
+    async fn record(signer: &VerificationSystem, interaction_id: &str) -> CommitmentRecord {
+        let muse_dna_hash = [3u8; 32];
+        let interaction = VerifiableInteraction {
+            muse_id: 1,
+            muse_dna_hash,
+            user_prompt: "Hello".to_string(),
+            ai_response: "Hi there".to_string(),
+            personality_traits: crate::muse_orchestrator::MuseTraits { creativity: 50, wisdom: 50, humor: 50, empathy: 50 },
+            timestamp: 1_700_000_000,
+            inference_params: InferenceParams::default(),
+            tool_calls: Vec::new(),
+        };
+        let commitment = signer.create_commitment(&interaction).await.unwrap();
+        CommitmentRecord {
+            interaction_id: interaction_id.to_string(),
+            muse_id: 1,
+            user_address: ALICE.to_string(),
+            session_id: None,
+            message_id: None,
+            muse_dna_hash: bytes_to_hex_string(&muse_dna_hash),
+            commitment_hash: bytes_to_hex_string(&commitment.commitment_hash),
+            signature: bytes_to_hex_string(&commitment.signature),
+            recovery_id: commitment.recovery_id,
+            signer: signer.get_public_key_address(),
+            foreign: false,
+            created_at: 1_700_000_000,
+        }
+    }
+
+    #[tokio::test]
+    async fn records_verify_against_their_signer() {
+        let service = system(1);
+        let record = record(&service, "interaction_1").await;
+        assert!(service.verify_record(&record).unwrap());
+
+        let mut claimed = record.clone();
+        claimed.signer = system(2).get_public_key_address();
+        assert!(!service.verify_record(&claimed).unwrap());
+
+        let mut tampered = record;
+        tampered.commitment_hash = bytes_to_hex_string(&[9u8; 32]);
+        assert!(!matches!(service.verify_record(&tampered), Ok(true)));
+    }
+
+    #[tokio::test]
+    async fn import_rejects_forged_records_and_marks_foreign_ones() {
+        let service = system(1);
+        let other = system(2);
+
+        let own = record(&service, "interaction_own").await;
+        let mut foreign = record(&other, "interaction_foreign").await;
+        // The archive cannot vouch for itself
+        foreign.foreign = false;
+        let mut forged = record(&other, "interaction_forged").await;
+        forged.signer = service.get_public_key_address();
+
+        assert_eq!(service.import_commitments(vec![own.clone(), foreign, forged]).await, 2);
+        let stored = service.user_commitments(ALICE, 1).await;
+        assert_eq!(stored.len(), 2);
+        assert!(!stored.iter().find(|r| r.interaction_id == "interaction_own").unwrap().foreign);
+        assert!(stored.iter().find(|r| r.interaction_id == "interaction_foreign").unwrap().foreign);
+
+        // Already present
+        assert_eq!(service.import_commitments(vec![own]).await, 0);
+    }
+}